@@ -0,0 +1,116 @@
+//! Parsing of large depth messages
+//!
+//! `cargo bench --bench depth_parsing` compares the parsing of `DepthUpdate` and `DepthSnapshot` with the former
+//! deserializer, which allocated a `Vec<String>` per level. `--features simd-json` benchmarks the simd-json path.
+//! The `pooled` cases give the level buffers back to the level pool after each parse, as the book processor does
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mdc::mdc_core::fixed_point::FixedPoint;
+use mdc::mdc_core::level_pool::LEVEL_POOL;
+use mdc::mdc_core::models::{DepthSnapshot, DepthUpdate, FromJson};
+use serde::{de, Deserialize, Deserializer};
+
+const PARSER: &str = if cfg!(feature = "simd-json") { "simd_json" } else { "serde_json" };
+
+/// The former level deserializer
+#[allow(dead_code)]
+struct LegacyEntry {
+    price: FixedPoint,
+    quantity: FixedPoint,
+}
+
+impl<'de> Deserialize<'de> for LegacyEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let arr: Vec<String> = Vec::deserialize(deserializer)?;
+        if arr.len() != 2 {
+            return Err(de::Error::invalid_length(arr.len(), &"2"));
+        }
+
+        Ok(LegacyEntry {
+            price: arr[0].parse().map_err(de::Error::custom)?,
+            quantity: arr[1].parse().map_err(de::Error::custom)?,
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct LegacyUpdate {
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    last_update_id: u64,
+    #[serde(rename = "b")]
+    bids: Vec<LegacyEntry>,
+    #[serde(rename = "a")]
+    asks: Vec<LegacyEntry>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct LegacySnapshot {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: Vec<LegacyEntry>,
+    asks: Vec<LegacyEntry>,
+}
+
+fn levels(count: usize, start: f64, step: f64) -> String {
+    let levels: Vec<_> = (0..count)
+        .map(|i| format!(r#"["{:.2}","{:.8}"]"#, start + step * i as f64, 0.001 * (i % 997 + 1) as f64))
+        .collect();
+    format!("[{}]", levels.join(","))
+}
+
+fn depth_update(count: usize) -> String {
+    format!(
+        r#"{{"e":"depthUpdate","E":1700000000000,"s":"BTCUSDT","U":1000,"u":{},"b":{},"a":{}}}"#,
+        1000 + 2 * count,
+        levels(count, 30000.0, -0.01),
+        levels(count, 30000.01, 0.01),
+    )
+}
+
+fn depth_snapshot(count: usize) -> String {
+    format!(r#"{{"lastUpdateId":1000,"bids":{},"asks":{}}}"#, levels(count, 30000.0, -0.01), levels(count, 30000.01, 0.01))
+}
+
+fn bench_depth_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("depth_update");
+    for count in [20, 250, 1000] {
+        let message = depth_update(count);
+        group.throughput(Throughput::Bytes(message.len() as u64));
+        group.bench_with_input(BenchmarkId::new("legacy", count), &message, |b, message| {
+            b.iter(|| serde_json::from_str::<LegacyUpdate>(black_box(message)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new(PARSER, count), &message, |b, message| {
+            b.iter(|| DepthUpdate::from_json(black_box(message)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new(format!("{}_pooled", PARSER), count), &message, |b, message| {
+            b.iter(|| {
+                let update = DepthUpdate::from_json(black_box(message)).unwrap();
+                LEVEL_POOL.give(update.bids);
+                LEVEL_POOL.give(update.asks);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_depth_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("depth_snapshot");
+    let message = depth_snapshot(5000);
+    group.throughput(Throughput::Bytes(message.len() as u64));
+    group.bench_function("legacy", |b| b.iter(|| serde_json::from_str::<LegacySnapshot>(black_box(&message)).unwrap()));
+    group.bench_function(PARSER, |b| b.iter(|| DepthSnapshot::from_json(black_box(&message)).unwrap()));
+    group.bench_function(format!("{}_pooled", PARSER), |b| {
+        b.iter(|| {
+            let snapshot = DepthSnapshot::from_json(black_box(&message)).unwrap();
+            LEVEL_POOL.give(snapshot.bids);
+            LEVEL_POOL.give(snapshot.asks);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_depth_update, bench_depth_snapshot);
+criterion_main!(benches);
@@ -0,0 +1,169 @@
+//! Level storage of a 5000-level book
+//!
+//! `cargo bench --bench order_book` compares `OrderBook` with the former `BTreeMap<PriceKey, FixedPoint>` storage
+//! on updates near the top of the book, updates deep in the book, reading the top levels and copying the book.
+//! It also measures the publishing of the BookProcessor, which sends a snapshot of the top levels after each update,
+//! against a copy of the whole book per update
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use mdc::mdc_core::fixed_point::FixedPoint;
+use mdc::mdc_core::models::{DepthEntry, DepthSnapshot};
+use mdc::mdc_core::order_book::{OrderBook, PriceKey};
+
+const LEVELS: usize = 5000;
+const TICK: f64 = 0.01;
+const BEST_BID: f64 = 30000.0;
+
+/// The former storage
+#[derive(Clone)]
+struct LegacyBook {
+    bids: BTreeMap<PriceKey, FixedPoint>,
+    asks: BTreeMap<PriceKey, FixedPoint>,
+}
+
+impl LegacyBook {
+    fn new(book: &OrderBook) -> Self {
+        LegacyBook {
+            bids: book.bids.iter().map(|level| (PriceKey::Bid(level.price), level.quantity)).collect(),
+            asks: book.asks.iter().map(|level| (PriceKey::Ask(level.price), level.quantity)).collect(),
+        }
+    }
+
+    fn apply_update(&mut self, price_key: PriceKey, quantity: FixedPoint) -> Option<FixedPoint> {
+        let book = match price_key {
+            PriceKey::Bid(_) => &mut self.bids,
+            PriceKey::Ask(_) => &mut self.asks,
+        };
+
+        if quantity.is_zero() {
+            return book.remove(&price_key);
+        }
+
+        book.insert(price_key, quantity)
+    }
+
+    fn top_n(&self, n: usize) -> (Vec<DepthEntry>, Vec<DepthEntry>) {
+        let levels = |side: &BTreeMap<PriceKey, FixedPoint>| {
+            side.iter().take(n).map(|(key, qty)| DepthEntry { price: key.price(), quantity: *qty }).collect()
+        };
+
+        (levels(&self.bids), levels(&self.asks))
+    }
+}
+
+fn snapshot() -> DepthSnapshot {
+    let level = |price: f64, i: usize| DepthEntry { price: FixedPoint::from_f64(price), quantity: FixedPoint::from_f64(0.001 * (i % 997 + 1) as f64) };
+    DepthSnapshot {
+        last_update_id: 1,
+        bids: (0..LEVELS).map(|i| level(BEST_BID - TICK * i as f64, i)).collect(),
+        asks: (0..LEVELS).map(|i| level(BEST_BID + TICK * (i + 1) as f64, i)).collect(),
+    }
+}
+
+/// Prices of updates within `depth` levels of the top of either side
+fn updates(depth: usize) -> Vec<PriceKey> {
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    (0..1000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let offset = (state % depth as u64) as f64 * TICK;
+            match state % 2 {
+                0 => PriceKey::Bid(FixedPoint::from_f64(BEST_BID - offset)),
+                _ => PriceKey::Ask(FixedPoint::from_f64(BEST_BID + TICK + offset)),
+            }
+        })
+        .collect()
+}
+
+/// Quantity of the i-th update. Every fourth update removes its level
+fn quantity(i: usize) -> FixedPoint {
+    match i % 4 {
+        0 => FixedPoint::ZERO,
+        _ => FixedPoint::from_f64(0.001 * (i % 97 + 1) as f64),
+    }
+}
+
+fn bench_apply_update(c: &mut Criterion) {
+    let book = OrderBook::new(&snapshot());
+    let legacy = LegacyBook::new(&book);
+
+    for (name, depth) in [("top_20", 20), ("all_5000", LEVELS)] {
+        let updates = updates(depth);
+        let mut group = c.benchmark_group(format!("apply_1000_updates/{}", name));
+        group.bench_function("order_book", |b| {
+            b.iter_batched_ref(|| book.clone(), |book| {
+                for (i, key) in updates.iter().enumerate() {
+                    black_box(book.apply_update(*key, quantity(i)));
+                }
+            }, BatchSize::LargeInput)
+        });
+        group.bench_function("legacy_btree_map", |b| {
+            b.iter_batched_ref(|| legacy.clone(), |book| {
+                for (i, key) in updates.iter().enumerate() {
+                    black_box(book.apply_update(*key, quantity(i)));
+                }
+            }, BatchSize::LargeInput)
+        });
+        group.finish();
+    }
+}
+
+fn bench_top_n(c: &mut Criterion) {
+    let book = OrderBook::new(&snapshot());
+    let legacy = LegacyBook::new(&book);
+
+    let mut group = c.benchmark_group("top_20");
+    group.bench_function("order_book", |b| b.iter(|| black_box(&book).top_n(20)));
+    group.bench_function("legacy_btree_map", |b| b.iter(|| black_box(&legacy).top_n(20)));
+    group.finish();
+
+    // The BookProcessor copies the whole book only for checkpoints and validation resyncs
+    let mut group = c.benchmark_group("clone");
+    group.bench_function("order_book", |b| b.iter(|| black_box(&book).clone()));
+    group.bench_function("legacy_btree_map", |b| b.iter(|| black_box(&legacy).clone()));
+    group.finish();
+
+    let mut group = c.benchmark_group("from_snapshot");
+    let snapshot = snapshot();
+    group.bench_function("order_book", |b| b.iter(|| OrderBook::new(black_box(&snapshot))));
+    group.finish();
+}
+
+/// Apply the updates and publish an immutable snapshot of the top `depth` levels after each one, like the BookProcessor.
+/// The consumers may hold the snapshots as long as they like, the book itself is never copied
+fn publish_top(book: &mut OrderBook, updates: &[PriceKey], depth: usize) {
+    for (i, key) in updates.iter().enumerate() {
+        black_box(book.apply_update(*key, quantity(i)));
+        black_box(Arc::new(book.top(depth)));
+    }
+}
+
+/// Apply the updates and publish a copy of the book after each one, as the BookProcessor did before
+fn publish_copy_per_update(book: &mut OrderBook, updates: &[PriceKey]) {
+    for (i, key) in updates.iter().enumerate() {
+        black_box(book.apply_update(*key, quantity(i)));
+        black_box(Arc::new(book.clone()));
+    }
+}
+
+fn bench_publish(c: &mut Criterion) {
+    let book = OrderBook::new(&snapshot());
+    let updates = updates(20);
+
+    let mut group = c.benchmark_group("publish_1000_updates");
+    for depth in [20, 1000] {
+        group.bench_function(format!("top_{}", depth), |b| {
+            b.iter_batched_ref(|| book.clone(), |book| publish_top(book, &updates, depth), BatchSize::LargeInput)
+        });
+    }
+    group.bench_function("copy_per_update", |b| {
+        b.iter_batched_ref(|| book.clone(), |book| publish_copy_per_update(book, &updates), BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_apply_update, bench_top_n, bench_publish);
+criterion_main!(benches);
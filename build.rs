@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/mdc.proto")?;
+    tonic_build::compile_protos("proto/mdc_events.proto")?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! Global allocator of the mdc binary, selected by the `mimalloc` and `jemalloc` features
+//!
+//! With the `alloc-profiling` feature the selected allocator is wrapped into the `CountingAllocator`
+#[cfg(all(feature = "mimalloc", feature = "jemalloc"))]
+compile_error!("Features 'mimalloc' and 'jemalloc' select different global allocators. Enable only one of them");
+
+#[cfg(feature = "mimalloc")]
+type Allocator = mimalloc::MiMalloc;
+#[cfg(feature = "mimalloc")]
+const ALLOCATOR: Allocator = mimalloc::MiMalloc;
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+type Allocator = tikv_jemallocator::Jemalloc;
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+const ALLOCATOR: Allocator = tikv_jemallocator::Jemalloc;
+
+#[cfg(not(any(feature = "mimalloc", feature = "jemalloc")))]
+type Allocator = std::alloc::System;
+#[cfg(not(any(feature = "mimalloc", feature = "jemalloc")))]
+const ALLOCATOR: Allocator = std::alloc::System;
+
+#[cfg(feature = "alloc-profiling")]
+#[global_allocator]
+static GLOBAL: mdc::mdc_server::allocation_profiling::CountingAllocator<Allocator> =
+    mdc::mdc_server::allocation_profiling::CountingAllocator(ALLOCATOR);
+
+#[cfg(not(feature = "alloc-profiling"))]
+#[global_allocator]
+static GLOBAL: Allocator = ALLOCATOR;
+
+/// Name of the global allocator, logged at startup
+pub fn name() -> &'static str {
+    match (cfg!(feature = "mimalloc"), cfg!(feature = "jemalloc")) {
+        (true, _) => "mimalloc",
+        (false, true) => "jemalloc",
+        (false, false) => "system",
+    }
+}
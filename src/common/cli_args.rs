@@ -1,29 +1,300 @@
 use std::path::PathBuf;
-use clap::Parser;
-use tracing::Level;
-
-fn parse_tracing_level(s: &str) -> anyhow::Result<Level, String> {
-    match s.to_lowercase().as_str() {
-        "trace" => Ok(Level::TRACE),
-        "debug" => Ok(Level::DEBUG),
-        "info"  => Ok(Level::INFO),
-        "warn"  => Ok(Level::WARN),
-        "error" => Ok(Level::ERROR),
-        other => Err(format!("Unexpected log level: '{}'", other)),
+use chrono::{DateTime, TimeZone, Utc};
+use clap::{Args, Parser, Subcommand};
+use tracing_subscriber::EnvFilter;
+
+/// Log level used if neither `--log-level` nor `RUST_LOG` is set
+const DEFAULT_LOG_FILTER: &str = "info";
+
+/// Modules of the crate, which may be referred to in the log directives without the crate name
+const CRATE_MODULES: [&str; 2] = ["mdc_core", "mdc_server"];
+
+/// Prefix the directives for crate modules with the crate name (`mdc_server::fanout=debug` becomes
+/// `mdc::mdc_server::fanout=debug`), since the targets of the events contain it
+fn expand_log_directives(directives: &str) -> String {
+    directives
+        .split(',')
+        .map(|directive| {
+            let directive = directive.trim();
+            let module = directive.split(['=', '[', ':']).next().unwrap_or_default();
+            if CRATE_MODULES.contains(&module) {
+                format!("{}::{}", env!("CARGO_CRATE_NAME"), directive)
+            } else {
+                directive.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Create the log filter from a level (e.g. `debug`) or `RUST_LOG`-style directives
+/// (e.g. `mdc_server::depth_event_dispatcher=trace,info`)
+pub fn parse_log_filter(directives: &str) -> anyhow::Result<EnvFilter, String> {
+    EnvFilter::try_new(expand_log_directives(directives))
+        .map_err(|e| format!("Unexpected log filter: '{}'. Details: '{}'", directives, e))
+}
+
+fn parse_log_directives(directives: &str) -> anyhow::Result<String, String> {
+    parse_log_filter(directives).map(|_| directives.to_string())
+}
+
+/// Parse a point in time given as milliseconds since epoch or in RFC 3339 (e.g. `2024-01-01T12:00:00.250Z`)
+fn parse_time(time: &str) -> anyhow::Result<DateTime<Utc>, String> {
+    match time.parse::<i64>() {
+        Ok(millis) => Utc.timestamp_millis_opt(millis).single(),
+        Err(_) => DateTime::parse_from_rfc3339(time).ok().map(|time| time.with_timezone(&Utc)),
     }
+    .ok_or_else(|| format!("Unexpected time: '{}'. Expected milliseconds since epoch or RFC 3339", time))
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct CliArgs {
-    #[arg(short = 'c', long = "config", default_value = "mdc.yaml")]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(short = 'c', long = "config", default_value = "mdc.yaml", global = true)]
     pub config: PathBuf,
 
-    #[arg(
-        short = 'l',
-        long = "log-level",
-        value_parser = parse_tracing_level,
-        default_value = "info"
-    )]
-    pub log_level: Level,
-}
\ No newline at end of file
+    /// Log level or RUST_LOG-style directives, which override RUST_LOG
+    #[arg(short = 'l', long = "log-level", value_name = "FILTER", value_parser = parse_log_directives, global = true)]
+    pub log_level: Option<String>,
+
+    #[arg(long = "log-file", value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+}
+
+/// Options of the live capture commands
+#[derive(Args, Debug, Default)]
+pub struct CaptureArgs {
+    /// Start even if another instance captures the same instrument
+    #[arg(long = "force")]
+    pub force: bool,
+
+    /// Run in the background, detached from the terminal
+    #[arg(long = "detach")]
+    pub detach: bool,
+
+    /// Write the pid into the file while running
+    #[arg(long = "pid-file", value_name = "PATH")]
+    pub pid_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start live capture. The default command
+    Run {
+        #[command(flatten)]
+        capture: CaptureArgs,
+    },
+    /// Start live capture and record every raw frame into a tape file
+    Record {
+        #[command(flatten)]
+        capture: CaptureArgs,
+    },
+    /// Replay a recorded tape through the processing pipeline instead of live capture
+    Replay {
+        /// Tape file to replay
+        tape: PathBuf,
+        /// Replay speed multiplier (0 - as fast as possible)
+        #[arg(long = "speed", default_value_t = 1.0)]
+        speed: f64,
+    },
+    /// Check the configuration file and print the resolved configuration
+    ValidateConfig,
+    /// Print top of book, stream status and lag of a running instance
+    Top {
+        #[arg(long = "symbol")]
+        symbol: Option<String>,
+    },
+    /// Send a command to a running instance: status, pause, resume, resync, flush, book [depth], add <symbol>
+    /// or remove <symbol>
+    Admin {
+        #[arg(long = "symbol")]
+        symbol: Option<String>,
+        /// The command and its arguments
+        #[arg(required = true)]
+        command: Vec<String>,
+    },
+    /// Merge overlapping tapes (e.g. from redundant hosts) into a single tape without duplicates
+    Compact {
+        /// Path of the merged tape. Must not exist
+        #[arg(long = "output", short = 'o')]
+        output: PathBuf,
+        /// Tapes to merge
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Scan a tape and report its time range, records per stream, gaps, rates and integrity
+    Inspect {
+        /// Tape file to inspect
+        tape: PathBuf,
+        /// Time in milliseconds without records, which is reported as a silence
+        #[arg(long = "silence", default_value_t = 5000)]
+        silence: u64,
+        /// Print the summary as JSON
+        #[arg(long = "json")]
+        json: bool,
+    },
+    /// Replay a tape up to a point in time and print the reconstructed book or export it as JSON
+    BookAt {
+        /// Tape file to replay
+        #[arg(long = "file")]
+        file: PathBuf,
+        /// Receive time, up to which the tape is replayed: milliseconds since epoch or RFC 3339
+        #[arg(long = "time", value_parser = parse_time)]
+        time: DateTime<Utc>,
+        /// Levels per side (the configured output depth if not set)
+        #[arg(long = "depth")]
+        depth: Option<usize>,
+        /// Write the book as JSON into the file instead of printing it
+        #[arg(long = "output", short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Start live capture and show the book, the latest trades and the spread in the terminal instead of the event log
+    Tui {
+        #[arg(long = "symbol")]
+        symbol: Option<String>,
+        /// Levels per side of the depth ladder
+        #[arg(long = "depth", default_value_t = 20)]
+        depth: usize,
+        /// Record every raw frame into a tape file
+        #[arg(long = "record")]
+        record: bool,
+        /// Start even if another instance captures the same instrument
+        #[arg(long = "force")]
+        force: bool,
+    },
+    /// Serve synthetic Binance market data of the configured instrument for development and load tests
+    Sim {
+        #[arg(long = "symbol")]
+        symbol: Option<String>,
+    },
+    /// Install, remove or run the Windows service, which runs live capture in the background
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+/// Actions of the `service` command
+#[cfg(windows)]
+#[derive(Subcommand, Debug)]
+pub enum ServiceAction {
+    /// Register live capture with the configuration file as an automatically started Windows service
+    Install {
+        /// Name of the service
+        #[arg(long = "name", default_value = "mdc")]
+        name: String,
+        /// Record every raw frame into a tape file
+        #[arg(long = "record")]
+        record: bool,
+        /// Start even if another instance captures the same instrument
+        #[arg(long = "force")]
+        force: bool,
+    },
+    /// Stop and remove the Windows service
+    Uninstall {
+        /// Name of the service
+        #[arg(long = "name", default_value = "mdc")]
+        name: String,
+    },
+    /// Run as the Windows service. Started by the service control manager
+    #[command(hide = true)]
+    Run {
+        #[arg(long = "name", default_value = "mdc")]
+        name: String,
+        #[arg(long = "record")]
+        record: bool,
+        #[arg(long = "force")]
+        force: bool,
+    },
+}
+
+impl CliArgs {
+    /// The log filter from `--log-level`, `RUST_LOG` or the default level, in this order
+    pub fn log_filter(&self) -> anyhow::Result<EnvFilter> {
+        let directives = match &self.log_level {
+            Some(directives) => directives.clone(),
+            None => std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_else(|_| DEFAULT_LOG_FILTER.to_string()),
+        };
+
+        parse_log_filter(&directives).map_err(anyhow::Error::msg)
+    }
+}
+
+impl Command {
+    /// Options of live capture, if the command starts it
+    pub fn capture_args(&self) -> Option<&CaptureArgs> {
+        match self {
+            Command::Run { capture } | Command::Record { capture } => Some(capture),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subcommands() {
+        let args = CliArgs::parse_from(["mdc", "-c", "btc.yaml"]);
+        assert!(args.command.is_none());
+        assert_eq!(args.config, PathBuf::from("btc.yaml"));
+
+        let args = CliArgs::parse_from(["mdc", "record", "--detach", "--pid-file", "mdc.pid", "-l", "debug"]);
+        let capture = args.command.as_ref().and_then(Command::capture_args).unwrap();
+        assert!(capture.detach && !capture.force);
+        assert_eq!(capture.pid_file, Some(PathBuf::from("mdc.pid")));
+        assert_eq!(args.log_level.as_deref(), Some("debug"));
+
+        let args = CliArgs::parse_from(["mdc", "replay", "BTCUSDT.tape", "--speed", "0"]);
+        assert!(matches!(args.command, Some(Command::Replay { speed, .. }) if speed == 0.0));
+        assert!(args.command.unwrap().capture_args().is_none());
+
+        assert!(matches!(CliArgs::parse_from(["mdc", "validate-config"]).command, Some(Command::ValidateConfig)));
+        assert!(matches!(
+            CliArgs::parse_from(["mdc", "admin", "--symbol", "ETHUSDT", "book", "10"]).command,
+            Some(Command::Admin { symbol: Some(symbol), command }) if symbol == "ETHUSDT" && command == ["book", "10"]
+        ));
+        assert!(matches!(
+            CliArgs::parse_from(["mdc", "book-at", "--file", "BTCUSDT.tape", "--time", "2024-01-01T00:00:01.5Z"]).command,
+            Some(Command::BookAt { time, depth: None, .. }) if time.timestamp_millis() == 1704067201500
+        ));
+        assert!(matches!(
+            CliArgs::parse_from(["mdc", "book-at", "--file", "BTCUSDT.tape", "--time", "1704067201500", "-o", "book.json"]).command,
+            Some(Command::BookAt { time, output: Some(_), .. }) if time.timestamp_millis() == 1704067201500
+        ));
+        assert!(CliArgs::try_parse_from(["mdc", "book-at", "--file", "BTCUSDT.tape", "--time", "yesterday"]).is_err());
+        assert!(matches!(
+            CliArgs::parse_from(["mdc", "inspect", "BTCUSDT.tape", "--json"]).command,
+            Some(Command::Inspect { silence: 5000, json: true, .. })
+        ));
+        assert!(matches!(
+            CliArgs::parse_from(["mdc", "tui", "--symbol", "ETHUSDT", "--record"]).command,
+            Some(Command::Tui { symbol: Some(_), depth: 20, record: true, force: false })
+        ));
+        assert!(CliArgs::try_parse_from(["mdc", "--record"]).is_err());
+        assert!(CliArgs::try_parse_from(["mdc", "-l", "mdc_server=verbose"]).is_err());
+    }
+
+    #[test]
+    fn test_log_filter() {
+        assert_eq!(expand_log_directives("debug"), "debug");
+        assert_eq!(
+            expand_log_directives("mdc_server::depth_event_dispatcher=trace, info"),
+            "mdc::mdc_server::depth_event_dispatcher=trace,info"
+        );
+        assert_eq!(expand_log_directives("mdc_core=debug,hyper=warn"), "mdc::mdc_core=debug,hyper=warn");
+        assert_eq!(expand_log_directives("mdc::mdc_server=debug"), "mdc::mdc_server=debug");
+
+        assert!(parse_log_filter("mdc_server::fanout=trace,info").is_ok());
+        assert!(parse_log_filter("[pipeline{symbol=ETHUSDT}]=debug,info").is_ok());
+        assert!(parse_log_filter("mdc_server=verbose").is_err());
+
+        let args = CliArgs::parse_from(["mdc", "--log-level", "mdc_server::depth_event_dispatcher=trace,info"]);
+        assert_eq!(args.log_filter().unwrap().to_string(), "mdc::mdc_server::depth_event_dispatcher=trace,info");
+    }
+}
@@ -0,0 +1,307 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+#[cfg(unix)]
+use anyhow::anyhow;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, IntoRawHandle, RawHandle};
+#[cfg(windows)]
+use tokio::sync::Notify;
+#[cfg(windows)]
+use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+#[cfg(windows)]
+use windows_sys::Win32::System::Console::{GetStdHandle, SetStdHandle, STD_ERROR_HANDLE, STD_HANDLE, STD_OUTPUT_HANDLE};
+#[cfg(windows)]
+use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+/// Device, which discards everything written into it
+#[cfg(unix)]
+pub const NULL_DEVICE: &str = "/dev/null";
+#[cfg(windows)]
+pub const NULL_DEVICE: &str = "NUL";
+
+/// Stop requests of the service control manager, which `shutdown_signal` waits for along with Ctrl-C
+#[cfg(windows)]
+static STOP_REQUESTS: Notify = Notify::const_new();
+
+/// Detach the process from the controlling terminal (Unix double fork)
+///
+/// The calling process exits, the work continues in a grandchild, which is a session leader's child, so it can
+/// never reacquire a terminal. Standard input and output are redirected to `/dev/null`. The working directory
+/// is kept, so relative paths from the config keep working
+///
+/// # Panics
+/// * Must be called before any threads (e.g. the async runtime) are started
+#[cfg(unix)]
+pub fn detach() -> Result<()> {
+    fork_and_exit_parent()?;
+
+    // SAFETY: the process is single threaded, setsid has no memory safety requirements
+    if unsafe { libc::setsid() } < 0 {
+        bail!("Failed to create a new session: '{}'", std::io::Error::last_os_error());
+    }
+
+    fork_and_exit_parent()?;
+
+    let dev_null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(NULL_DEVICE)
+        .context("Failed to open /dev/null")?;
+
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        redirect(&dev_null, fd)?;
+    }
+
+    Ok(())
+}
+
+/// Windows has no fork, a background instance runs as a Windows service instead
+#[cfg(windows)]
+pub fn detach() -> Result<()> {
+    bail!("--detach is not supported on Windows. Install MDC as a Windows service with 'mdc service install' instead")
+}
+
+#[cfg(unix)]
+fn fork_and_exit_parent() -> Result<()> {
+    // SAFETY: the process is single threaded, so the child gets a consistent copy of it
+    match unsafe { libc::fork() } {
+        pid if pid < 0 => Err(anyhow!("Failed to fork: '{}'", std::io::Error::last_os_error())),
+        0 => Ok(()),
+        _ => std::process::exit(0),
+    }
+}
+
+#[cfg(unix)]
+fn redirect(file: &File, fd: libc::c_int) -> Result<()> {
+    // SAFETY: both descriptors are valid for the duration of the call
+    if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+        bail!("Failed to redirect file descriptor '{}': '{}'", fd, std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Replace the standard handle of the process. Standard output and error of Rust look the handle up on every write
+#[cfg(windows)]
+fn redirect(handle: RawHandle, std_handle: STD_HANDLE) -> Result<()> {
+    // SAFETY: the handle stays open as long as it is the standard handle, see `redirect_output` and `DivertedOutput`
+    if unsafe { SetStdHandle(std_handle, handle) } == 0 {
+        bail!("Failed to redirect standard handle '{}': '{}'", std_handle, std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn open_log_file(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file: {:?}", path))
+}
+
+/// Redirect standard output and standard error (logs and printed events) into the file, appending to it
+#[cfg(unix)]
+pub fn redirect_output(path: &Path) -> Result<()> {
+    let file = open_log_file(path)?;
+
+    redirect(&file, libc::STDOUT_FILENO)?;
+    redirect(&file, libc::STDERR_FILENO)
+}
+
+/// Redirect standard output and standard error (logs and printed events) into the file, appending to it
+///
+/// The file is left open for the lifetime of the process, like a redirected descriptor on Unix
+#[cfg(windows)]
+pub fn redirect_output(path: &Path) -> Result<()> {
+    let handle = open_log_file(path)?.into_raw_handle();
+
+    redirect(handle, STD_OUTPUT_HANDLE)?;
+    redirect(handle, STD_ERROR_HANDLE)
+}
+
+/// Standard output and standard error, which are diverted into a file until it is dropped
+#[cfg(unix)]
+pub struct DivertedOutput {
+    stdout: File,
+    stderr: File,
+}
+
+/// Standard output and standard error, which are diverted into a file until it is dropped
+#[cfg(windows)]
+pub struct DivertedOutput {
+    stdout: RawHandle,
+    stderr: RawHandle,
+    file: File,
+}
+
+/// Divert standard output and standard error into the file, e.g. while a TUI owns the terminal
+///
+/// Unlike `redirect_output`, the original descriptors are restored once the returned value is dropped
+#[cfg(unix)]
+pub fn divert_output(path: &Path) -> Result<DivertedOutput> {
+    let duplicate = |fd: libc::c_int| -> Result<File> {
+        // SAFETY: the descriptor is valid, the duplicate is owned by the returned file
+        match unsafe { libc::dup(fd) } {
+            duplicate if duplicate < 0 => bail!("Failed to duplicate file descriptor '{}': '{}'", fd, std::io::Error::last_os_error()),
+            duplicate => Ok(unsafe { <File as std::os::fd::FromRawFd>::from_raw_fd(duplicate) }),
+        }
+    };
+
+    let diverted = DivertedOutput { stdout: duplicate(libc::STDOUT_FILENO)?, stderr: duplicate(libc::STDERR_FILENO)? };
+    redirect_output(path)?;
+    Ok(diverted)
+}
+
+/// Divert standard output and standard error into the file, e.g. while a TUI owns the terminal
+///
+/// Unlike `redirect_output`, the original handles are restored once the returned value is dropped
+#[cfg(windows)]
+pub fn divert_output(path: &Path) -> Result<DivertedOutput> {
+    let file = open_log_file(path)?;
+    // SAFETY: GetStdHandle has no memory safety requirements
+    let diverted = unsafe {
+        DivertedOutput { stdout: GetStdHandle(STD_OUTPUT_HANDLE), stderr: GetStdHandle(STD_ERROR_HANDLE), file }
+    };
+
+    redirect(diverted.file.as_raw_handle(), STD_OUTPUT_HANDLE)?;
+    redirect(diverted.file.as_raw_handle(), STD_ERROR_HANDLE)?;
+    Ok(diverted)
+}
+
+impl Drop for DivertedOutput {
+    #[cfg(unix)]
+    fn drop(&mut self) {
+        let _ = redirect(&self.stdout, libc::STDOUT_FILENO);
+        let _ = redirect(&self.stderr, libc::STDERR_FILENO);
+    }
+
+    #[cfg(windows)]
+    fn drop(&mut self) {
+        let _ = redirect(self.stdout, STD_OUTPUT_HANDLE);
+        let _ = redirect(self.stderr, STD_ERROR_HANDLE);
+    }
+}
+
+/// A file holding the pid of the running process, which is removed once the process stops
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the pid of the current process into the file
+    ///
+    /// # Behavior
+    /// * Fails if the file holds the pid of another running process
+    /// * A file left by a process which is no longer running is overwritten
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Ok(content) = fs::read_to_string(path) {
+            if let Ok(pid) = content.trim().parse::<i32>() {
+                if pid != std::process::id() as i32 && is_running(pid) {
+                    bail!("Pid file {:?} belongs to running process '{}'", path, pid);
+                }
+            }
+        }
+
+        fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write pid file: {:?}", path))?;
+
+        Ok(Self { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Check whether the process with the given id is running
+#[cfg(unix)]
+pub fn is_running(pid: i32) -> bool {
+    // 0 and negative ids address process groups rather than a process
+    if pid <= 0 {
+        return false;
+    }
+
+    // SAFETY: signal 0 only checks whether the process exists
+    unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+/// Check whether the process with the given id is running
+#[cfg(windows)]
+pub fn is_running(pid: i32) -> bool {
+    // SAFETY: the handle is checked before use and closed afterwards
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid as u32);
+        if process.is_null() {
+            // A process of another user may be running even though it can't be queried
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+
+        let mut exit_code = 0;
+        let running = GetExitCodeProcess(process, &mut exit_code) != 0 && exit_code == STILL_ACTIVE as u32;
+        CloseHandle(process);
+        running
+    }
+}
+
+/// Stop the capture like a shutdown signal, e.g. on request of the Windows service control manager
+#[cfg(windows)]
+pub fn request_stop() {
+    STOP_REQUESTS.notify_one();
+}
+
+/// Wait for SIGTERM or SIGINT
+#[cfg(unix)]
+pub async fn shutdown_signal() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate()).context("Failed to install SIGTERM handler")?;
+    let mut interrupt = signal(SignalKind::interrupt()).context("Failed to install SIGINT handler")?;
+
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+        _ = interrupt.recv() => tracing::info!("Received SIGINT"),
+    }
+
+    Ok(())
+}
+
+/// Wait for Ctrl-C, Ctrl-Break or a stop request of the service control manager (see `request_stop`)
+#[cfg(windows)]
+pub async fn shutdown_signal() -> Result<()> {
+    let mut interrupt = tokio::signal::windows::ctrl_c().context("Failed to install Ctrl-C handler")?;
+    let mut terminate = tokio::signal::windows::ctrl_break().context("Failed to install Ctrl-Break handler")?;
+
+    tokio::select! {
+        _ = interrupt.recv() => tracing::info!("Received Ctrl-C"),
+        _ = terminate.recv() => tracing::info!("Received Ctrl-Break"),
+        _ = STOP_REQUESTS.notified() => tracing::info!("Received service stop request"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_{}", std::process::id(), "pid_file"));
+
+        // A stale file is taken over
+        fs::write(&path, format!("{}\n", i32::MAX)).unwrap();
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), std::process::id().to_string());
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // A file of a running process is not. Pid 1 is always running
+        fs::write(&path, "1\n").unwrap();
+        assert!(PidFile::create(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1 +1,4 @@
-pub mod cli_args;
\ No newline at end of file
+pub mod allocator;
+pub mod cli_args;
+#[cfg(windows)]
+pub mod windows_service;
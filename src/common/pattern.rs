@@ -0,0 +1,54 @@
+/// Match the text (e.g. a symbol or a host) against a case-insensitive pattern, where `*` matches any number of
+/// characters and `?` a single one
+pub fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_uppercase().chars().collect();
+    let text: Vec<char> = text.to_uppercase().chars().collect();
+
+    // Position in the pattern after the last `*` and the text position it has been matched at
+    let mut backtrack = None;
+    let (mut p, mut s) = (0, 0);
+    while s < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, s));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[s] => {
+                p += 1;
+                s += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` match one more character
+                Some((after_star, matched)) => {
+                    backtrack = Some((after_star, matched + 1));
+                    p = after_star;
+                    s = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*USDT", "BTCUSDT"));
+        assert!(matches_pattern("*usdt", "BTCUSDT"));
+        assert!(!matches_pattern("*USDT", "BTCUSDC"));
+        assert!(matches_pattern("BTC*", "BTCUSDT"));
+        assert!(matches_pattern("*UP*", "BTCUPUSDT"));
+        assert!(matches_pattern("???USDT", "ETHUSDT"));
+        assert!(!matches_pattern("???USDT", "DOGEUSDT"));
+        assert!(matches_pattern("*", "ETHBTC"));
+        assert!(matches_pattern("*A*A", "BANANA"));
+        assert!(!matches_pattern("*A*B", "BANANA"));
+        assert!(matches_pattern("*.binance.com", "stream.Binance.com"));
+        assert!(!matches_pattern("*.binance.com", "binance.com"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::ffi::OsString;
+use std::time::Duration;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use windows_service::define_windows_service;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo, ServiceStartType,
+    ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::service_dispatcher;
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use crate::common::cli_args::{CaptureArgs, CliArgs, Command, ServiceAction};
+use mdc::common::daemon;
+
+/// Execute an action of the `service` command
+///
+/// # Arguments
+/// * `action` - The action
+/// * `cli_args` - The command line. Its global options are passed on to the installed service
+pub fn execute(action: &ServiceAction, cli_args: &CliArgs) -> Result<()> {
+    match action {
+        ServiceAction::Install { name, record, force } => install(name, *record, *force, cli_args),
+        ServiceAction::Uninstall { name } => uninstall(name),
+        ServiceAction::Run { name, .. } => service_dispatcher::start(name, ffi_service_main)
+            .with_context(|| format!("Failed to start service '{}'. Is it started by the service control manager?", name)),
+    }
+}
+
+/// Register the service, which runs `mdc service run` with the configuration file and the log options
+///
+/// Paths are made absolute, since the service starts in the system directory. Without `--log-file` the output
+/// goes into `<name>.log` next to the configuration file, since a service has no console
+fn install(name: &str, record: bool, force: bool, cli_args: &CliArgs) -> Result<()> {
+    let config = std::path::absolute(&cli_args.config)
+        .with_context(|| format!("Failed to resolve config path: {:?}", cli_args.config))?;
+    let log_file = match &cli_args.log_file {
+        Some(log_file) => std::path::absolute(log_file).with_context(|| format!("Failed to resolve log file path: {:?}", log_file))?,
+        None => config.with_file_name(format!("{}.log", name)),
+    };
+
+    let mut launch_arguments: Vec<OsString> = vec!["--config".into(), config.clone().into(), "--log-file".into(), log_file.into()];
+    if let Some(log_level) = &cli_args.log_level {
+        launch_arguments.extend(["--log-level".into(), log_level.into()]);
+    }
+    launch_arguments.extend(["service".into(), "run".into(), "--name".into(), name.into()]);
+    if record {
+        launch_arguments.push("--record".into());
+    }
+    if force {
+        launch_arguments.push("--force".into());
+    }
+
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)
+        .context("Failed to connect to the service control manager. Is the command run as administrator?")?;
+    let info = ServiceInfo {
+        name: name.into(),
+        display_name: format!("Market Depth Capture ({})", name).into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe().context("Failed to get the path of the executable")?,
+        launch_arguments,
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .with_context(|| format!("Failed to create service '{}'", name))?;
+    service.set_description(format!("Market depth capture with {:?}", config))
+        .with_context(|| format!("Failed to set the description of service '{}'", name))?;
+
+    println!("Service '{}' is installed. Start it with 'sc start {}'", name, name);
+    Ok(())
+}
+
+/// Stop the service, if it is running, and remove it
+fn uninstall(name: &str) -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .context("Failed to connect to the service control manager. Is the command run as administrator?")?;
+    let service = manager.open_service(name, ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE)
+        .with_context(|| format!("Failed to open service '{}'", name))?;
+
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop().with_context(|| format!("Failed to stop service '{}'", name))?;
+    }
+
+    // The service is removed once it stops and its handles are closed
+    service.delete().with_context(|| format!("Failed to delete service '{}'", name))?;
+    println!("Service '{}' is uninstalled", name);
+    Ok(())
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Entry point of the service, called by the service dispatcher on its own thread
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("Service failed. Details: '{:#}'", e);
+        eprintln!("Service failed. Details: '{:#}'", e);
+    }
+}
+
+fn service_status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: match state {
+            ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            _ => ServiceControlAccept::empty(),
+        },
+        exit_code: ServiceExitCode::Win32(exit_code),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
+
+/// Run live capture as the service until it stops or the service control manager stops it
+///
+/// The service is started with the launch arguments written by `install`, so the command line holds
+/// the options of the capture
+fn run_service() -> Result<()> {
+    let cli_args = CliArgs::parse();
+    let Some(Command::Service { action: ServiceAction::Run { name, record, force } }) = &cli_args.command else {
+        bail!("Unexpected service command line");
+    };
+    let (name, record, force) = (name.clone(), *record, *force);
+
+    let status: ServiceStatusHandle = service_control_handler::register(&name, |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            daemon::request_stop();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }).with_context(|| format!("Failed to register the control handler of service '{}'", name))?;
+
+    // The config path is absolute. Its relative paths (e.g. `capture_dir`) are resolved against its directory
+    if let Some(directory) = cli_args.config.parent() {
+        std::env::set_current_dir(directory)
+            .with_context(|| format!("Failed to change the working directory to {:?}", directory))?;
+    }
+
+    status.set_service_status(service_status(ServiceState::Running, 0))?;
+
+    let capture = CaptureArgs { force, ..CaptureArgs::default() };
+    let command = match record {
+        true => Command::Record { capture },
+        false => Command::Run { capture },
+    };
+    let result = crate::start(CliArgs { command: Some(command), ..cli_args });
+
+    status.set_service_status(service_status(ServiceState::Stopped, result.is_err() as u32))?;
+    result
+}
@@ -0,0 +1,20 @@
+//! Market Depth Capture (MDC) as a library
+//!
+//! `mdc_core` contains the exchange-agnostic building blocks (models, `OrderBook`, depth sequencing),
+//! `mdc_server` the components of the capture pipeline. A whole pipeline can be embedded into another program
+//! with `PipelineBuilder`, which delivers the maintained book and the market events to `PipelineSink`s
+pub mod mdc_core;
+pub mod mdc_server;
+
+/// Helpers, which the library shares with the binary's `common` module
+pub mod common {
+    pub mod daemon;
+    pub mod pattern;
+}
+
+pub use mdc_core::order_book::OrderBook;
+pub use mdc_server::book_processor::BookProcessor;
+pub use mdc_server::depth_event_dispatcher::DepthEventDispatcher;
+pub use mdc_server::market_event_stream::MarketEventStream;
+pub use mdc_server::pipeline_builder::{Pipeline, PipelineBuilder};
+pub use mdc_server::pipeline_sink::PipelineSink;
@@ -1,31 +1,145 @@
-mod mdc_server;
 mod common;
 
-use mdc_server::config::Config;
-use mdc_server::config::load_config;
-use common::cli_args::CliArgs;
+use std::future::Future;
+use std::path::Path;
+use mdc::mdc_server::config::Config;
+use mdc::mdc_server::config::{load_pipelines, pipelines_to_yaml, select_pipeline};
+use common::cli_args::{CaptureArgs, CliArgs, Command};
+use common::allocator;
+use mdc::common::daemon::{self, PidFile};
 use anyhow::Result;
 use clap::Parser;
 use tracing_subscriber::FmtSubscriber;
-use crate::mdc_server::server::MDCServer;
+use mdc::mdc_server::server::MDCServer;
+use mdc::mdc_server::supervisor::PipelineSupervisor;
+use mdc::mdc_server::symbol_discovery::discover_pipelines;
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let cli_args: CliArgs = CliArgs::parse();
-    
+
+    // Installing, removing and running the Windows service happen before anything else is set up
+    #[cfg(windows)]
+    if let Some(Command::Service { action }) = &cli_args.command {
+        return common::windows_service::execute(action, &cli_args);
+    }
+
+    start(cli_args)
+}
+
+/// Set up the process as the command line asks (detaching, log file, pid file) and run the command
+fn start(cli_args: CliArgs) -> Result<()> {
+    let log_filter = cli_args.log_filter()?;
+    let command = cli_args.command.unwrap_or(Command::Run { capture: CaptureArgs::default() });
+    let pipelines: Vec<Config> = load_pipelines(&cli_args.config)?;
+
+    if let Command::ValidateConfig = command {
+        eprintln!("Configuration {:?} is valid: '{}' pipelines", cli_args.config, pipelines.len());
+        print!("{}", pipelines_to_yaml(&pipelines)?);
+        return Ok(());
+    }
+
+    // Other commands always run in the foreground
+    let detach = command.capture_args().is_some_and(|capture| capture.detach);
+
+    // Detaching forks the process, so it has to happen before the async runtime starts its threads
+    if detach {
+        daemon::detach()?;
+    }
+
+    // The TUI owns the terminal, so the event log and the logs go to the log file or nowhere while it runs.
+    // The output is restored once it stops, so errors still reach the terminal
+    let _diverted_output = match (&cli_args.log_file, &command) {
+        (Some(log_file), _) => {
+            daemon::redirect_output(log_file)?;
+            None
+        }
+        (None, Command::Tui { .. }) => Some(daemon::divert_output(Path::new(daemon::NULL_DEVICE))?),
+        (None, _) => None,
+    };
+
     let subscriber = FmtSubscriber::builder()
-        .with_max_level(cli_args.log_level)
+        .with_env_filter(log_filter)
+        .with_ansi(!detach && cli_args.log_file.is_none())
         .finish();
 
     tracing::subscriber::set_global_default(subscriber)
         .expect("Failed to set global default subscriber");
 
-    tracing::info!("Starting Market Depth Capture tool");
-    
-    let mdc_server_config: Config = load_config(&cli_args.config)?;
-    let mdc_server: MDCServer = MDCServer::new(mdc_server_config);
-    
-    mdc_server.start().await?;
+    let _pid_file = match command.capture_args().and_then(|capture| capture.pid_file.as_ref()) {
+        Some(path) => Some(PidFile::create(path)?),
+        None => None,
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(command, pipelines))
+}
+
+/// The instrument of a tape, taken from its session name (`<INSTRUMENT>_<YYYYMMDD_HHMMSS>.tape`)
+fn tape_instrument(tape: &Path) -> Option<&str> {
+    tape.file_stem()?.to_str()?.split('_').next()
+}
+
+async fn run(command: Command, pipelines: Vec<Config>) -> Result<()> {
+    // Instrument patterns are resolved into pipelines before any command selects one
+    let pipelines = discover_pipelines(pipelines).await?;
+
+    let (record, force) = match command {
+        Command::Top { symbol } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, symbol.as_deref()));
+            return mdc_server.top(symbol).await;
+        }
+        Command::Admin { symbol, command } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, symbol.as_deref()));
+            return mdc_server.admin(symbol, &command.join(" ")).await;
+        }
+        Command::Compact { inputs, output } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, None));
+            return mdc_server.compact(inputs, output).await;
+        }
+        Command::Inspect { tape, silence, json } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, tape_instrument(&tape)));
+            return mdc_server.inspect(tape, silence, json).await;
+        }
+        Command::BookAt { file, time, depth, output } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, tape_instrument(&file)));
+            return mdc_server.book_at(file, time, depth, output).await;
+        }
+        Command::Sim { symbol } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, symbol.as_deref()));
+            return stop_on_signal(mdc_server.simulate()).await;
+        }
+        Command::Tui { symbol, depth, record, force } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, symbol.as_deref()));
+            return stop_on_signal(mdc_server.tui(depth, record, force)).await;
+        }
+        Command::Replay { tape, speed } => {
+            let mdc_server = MDCServer::new(select_pipeline(pipelines, tape_instrument(&tape)));
+            tracing::info!("Replaying tape {:?}", tape);
+            return stop_on_signal(mdc_server.replay(tape, speed)).await;
+        }
+        Command::ValidateConfig => return Ok(()),
+        #[cfg(windows)]
+        Command::Service { .. } => return Ok(()),
+        Command::Run { capture } => (false, capture.force),
+        Command::Record { capture } => (true, capture.force),
+    };
+
+    tracing::info!("Starting Market Depth Capture tool with '{}' pipelines, '{}' allocator", pipelines.len(), allocator::name());
+
+    stop_on_signal(PipelineSupervisor::new(record, force).run(pipelines)).await
+}
+
+/// Run the session until it ends or a shutdown signal is received
+async fn stop_on_signal(session: impl Future<Output = Result<()>>) -> Result<()> {
+    tokio::select! {
+        result = session => result?,
+        result = daemon::shutdown_signal() => {
+            result?;
+            tracing::info!("Stopping Market Depth Capture tool");
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::mdc_core::order_book::{OrderBook, Side};
+
+/// Minimum interval between two spread samples in milliseconds
+const SPREAD_SAMPLE_INTERVAL: i64 = 1000;
+
+/// Minimum number of spread samples, before the spread is compared with their percentile
+const MIN_SPREAD_SAMPLES: usize = 60;
+
+/// Minimum number of seconds of trades, before the trade rate is compared with their average
+const MIN_TRADE_HISTORY: usize = 60;
+
+fn default_anomaly_history() -> u64 {
+    3600000
+}
+
+fn default_spread_percentile() -> f64 {
+    99.0
+}
+
+fn default_trade_rate_factor() -> f64 {
+    5.0
+}
+
+fn default_min_spike_trades() -> u64 {
+    20
+}
+
+fn default_stale_book() -> u64 {
+    5000
+}
+
+/// Thresholds of the anomaly detection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnomalySettings {
+    /// Length of the history in milliseconds, which the spread percentile and the average trade rate are taken over
+    #[serde(default = "default_anomaly_history")]
+    pub history: u64,
+    /// Percentile of the spreads in the history, above which the spread is abnormal
+    #[serde(default = "default_spread_percentile")]
+    pub spread_percentile: f64,
+    /// Multiple of the average trade rate in the history, above which the trades of a second are a spike
+    #[serde(default = "default_trade_rate_factor")]
+    pub trade_rate_factor: f64,
+    /// Minimum number of trades in a second, which makes a spike
+    #[serde(default = "default_min_spike_trades")]
+    pub min_spike_trades: u64,
+    /// Time in milliseconds without book updates, after which the book is stale
+    #[serde(default = "default_stale_book")]
+    pub stale_book: u64,
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        Self {
+            history: default_anomaly_history(),
+            spread_percentile: default_spread_percentile(),
+            trade_rate_factor: default_trade_rate_factor(),
+            min_spike_trades: default_min_spike_trades(),
+            stale_book: default_stale_book(),
+        }
+    }
+}
+
+/// An abnormal state of the book or the trades
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Anomaly {
+    /// All levels of a side, which had levels before, are gone
+    SideWiped { side: Side },
+    /// The spread exceeds the percentile of the spreads in the history
+    SpreadWidening { spread_bps: f64, percentile_bps: f64 },
+    /// The trades of the last second exceed the multiple of the average trades per second in the history
+    TradeRateSpike { trades: u64, average: f64 },
+    /// The book hasn't been updated for `age` milliseconds
+    StaleBook { age: u64 },
+}
+
+impl Anomaly {
+    /// Name of the kind of the anomaly, e.g. `side_wiped`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Anomaly::SideWiped { .. } => "side_wiped",
+            Anomaly::SpreadWidening { .. } => "spread_widening",
+            Anomaly::TradeRateSpike { .. } => "trade_rate_spike",
+            Anomaly::StaleBook { .. } => "stale_book",
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::SideWiped { side: Side::Bid } => write!(f, "The bid side of the book is wiped"),
+            Anomaly::SideWiped { side: Side::Ask } => write!(f, "The ask side of the book is wiped"),
+            Anomaly::SpreadWidening { spread_bps, percentile_bps } => {
+                write!(f, "Spread of '{:.2}' bps is above the percentile of '{:.2}' bps", spread_bps, percentile_bps)
+            }
+            Anomaly::TradeRateSpike { trades, average } => {
+                write!(f, "'{}' trades in a second against '{:.2}' on average", trades, average)
+            }
+            Anomaly::StaleBook { age } => write!(f, "Book hasn't been updated for '{}' ms", age),
+        }
+    }
+}
+
+/// An anomaly detected at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnomalyEvent {
+    /// Local time of the detection in milliseconds since epoch
+    pub time: i64,
+    #[serde(flatten)]
+    pub anomaly: Anomaly,
+}
+
+impl fmt::Display for AnomalyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Kind: '{}', Time: '{}', {}", self.anomaly.kind(), self.time, self.anomaly)
+    }
+}
+
+/// Detects anomalies of the book and the trades. Time is given explicitly (milliseconds since epoch)
+///
+/// Every anomaly is reported once when it starts. It is reported again only after the state went back to normal,
+/// e.g. the side has levels again or the book has been updated
+pub struct AnomalyDetector {
+    settings: AnomalySettings,
+    /// Whether each side (bids, asks) had levels at the previous book update
+    sides: Option<[bool; 2]>,
+    /// Spread samples in basis points of the mid price
+    spreads: VecDeque<(i64, f64)>,
+    /// The percentile of the spread samples
+    spread_percentile: Option<f64>,
+    spread_widened: bool,
+    last_book: Option<i64>,
+    stale: bool,
+    /// Number of trades in each second with trades
+    trades: VecDeque<(i64, u64)>,
+    /// The last second, whose trades have been checked
+    checked_second: Option<i64>,
+}
+
+impl AnomalyDetector {
+    pub fn new(settings: AnomalySettings) -> Self {
+        Self {
+            settings,
+            sides: None,
+            spreads: VecDeque::new(),
+            spread_percentile: None,
+            spread_widened: false,
+            last_book: None,
+            stale: false,
+            trades: VecDeque::new(),
+            checked_second: None,
+        }
+    }
+
+    fn sample_spread(&mut self, now: i64, spread_bps: f64) {
+        if self.spreads.back().is_some_and(|(time, _)| now - time < SPREAD_SAMPLE_INTERVAL) {
+            return;
+        }
+
+        self.spreads.push_back((now, spread_bps));
+        let start = now - self.settings.history as i64;
+        while self.spreads.front().is_some_and(|(time, _)| *time <= start) {
+            self.spreads.pop_front();
+        }
+
+        self.spread_percentile = (self.spreads.len() >= MIN_SPREAD_SAMPLES).then(|| {
+            let mut spreads: Vec<f64> = self.spreads.iter().map(|(_, spread)| *spread).collect();
+            spreads.sort_by(f64::total_cmp);
+            let rank = (spreads.len() as f64 * self.settings.spread_percentile / 100.0).ceil() as usize;
+            spreads[rank.clamp(1, spreads.len()) - 1]
+        });
+    }
+
+    /// Check an update of the book for wiped sides and a widened spread
+    pub fn on_book(&mut self, now: i64, book: &OrderBook) -> Vec<AnomalyEvent> {
+        let mut anomalies = Vec::new();
+        self.last_book = Some(now);
+        self.stale = false;
+
+        let sides = [!book.bids.is_empty(), !book.asks.is_empty()];
+        if let Some(previous) = self.sides {
+            for (side, (had_levels, has_levels)) in [Side::Bid, Side::Ask].into_iter().zip(previous.into_iter().zip(sides)) {
+                if had_levels && !has_levels {
+                    anomalies.push(AnomalyEvent { time: now, anomaly: Anomaly::SideWiped { side } });
+                }
+            }
+        }
+        self.sides = Some(sides);
+
+        let Some(spread_bps) = book.mid_price().zip(book.spread()).map(|(mid_price, spread)| spread / mid_price * 10000.0) else {
+            return anomalies;
+        };
+
+        // The spread is compared with the samples before it
+        let widened = self.spread_percentile.is_some_and(|percentile| spread_bps > percentile);
+        if widened && !self.spread_widened {
+            let percentile_bps = self.spread_percentile.unwrap_or_default();
+            anomalies.push(AnomalyEvent { time: now, anomaly: Anomaly::SpreadWidening { spread_bps, percentile_bps } });
+        }
+        self.spread_widened = widened;
+        self.sample_spread(now, spread_bps);
+
+        anomalies
+    }
+
+    /// Count a trade into the second it has been received in
+    pub fn on_trade(&mut self, now: i64) {
+        let second = now.div_euclid(1000);
+        match self.trades.back_mut() {
+            Some((last, count)) if *last == second => *count += 1,
+            _ => self.trades.push_back((second, 1)),
+        }
+    }
+
+    /// Check for a stale book and a trade rate spike in the last complete second
+    pub fn on_tick(&mut self, now: i64) -> Vec<AnomalyEvent> {
+        let mut anomalies = Vec::new();
+
+        if let Some(last_book) = self.last_book {
+            let age = (now - last_book).max(0) as u64;
+            if age > self.settings.stale_book && !self.stale {
+                anomalies.push(AnomalyEvent { time: now, anomaly: Anomaly::StaleBook { age } });
+                self.stale = true;
+            }
+        }
+
+        let second = now.div_euclid(1000) - 1;
+        if self.checked_second.is_some_and(|checked| checked >= second) {
+            return anomalies;
+        }
+        self.checked_second = Some(second);
+
+        let first_second = second - (self.settings.history / 1000) as i64;
+        while self.trades.front().is_some_and(|(time, _)| *time <= first_second) {
+            self.trades.pop_front();
+        }
+
+        let trades = self.trades.iter().find(|(time, _)| *time == second).map_or(0, |(_, count)| *count);
+        let Some((start, _)) = self.trades.front() else {
+            return anomalies;
+        };
+
+        // The average covers the seconds before the checked one since the first trade of the history
+        let seconds = (second - start) as usize;
+        if seconds >= MIN_TRADE_HISTORY {
+            let history: u64 = self.trades.iter().filter(|(time, _)| *time < second).map(|(_, count)| count).sum();
+            let average = history as f64 / seconds as f64;
+            if trades >= self.settings.min_spike_trades && trades as f64 > average * self.settings.trade_rate_factor {
+                anomalies.push(AnomalyEvent { time: now, anomaly: Anomaly::TradeRateSpike { trades, average } });
+            }
+        }
+
+        anomalies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    fn book(bid: Option<f64>, ask: Option<f64>) -> OrderBook {
+        let entry = |price: f64| DepthEntry { price: FixedPoint::from(price), quantity: FixedPoint::from(1.0) };
+        OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: bid.map(entry).into_iter().collect(),
+            asks: ask.map(entry).into_iter().collect(),
+        })
+    }
+
+    fn kinds(anomalies: Vec<AnomalyEvent>) -> Vec<&'static str> {
+        anomalies.iter().map(|event| event.anomaly.kind()).collect()
+    }
+
+    #[test]
+    fn test_wiped_side_and_stale_book() {
+        let mut detector = AnomalyDetector::new(AnomalySettings::default());
+        assert!(detector.on_book(0, &book(Some(100.0), Some(100.1))).is_empty());
+        assert_eq!(detector.on_book(100, &book(Some(100.0), None))[0].anomaly, Anomaly::SideWiped { side: Side::Ask });
+        assert!(detector.on_book(200, &book(Some(100.0), None)).is_empty());
+
+        assert!(kinds(detector.on_tick(5200)).is_empty());
+        assert_eq!(detector.on_tick(5300)[0].anomaly, Anomaly::StaleBook { age: 5100 });
+        assert!(detector.on_tick(9000).is_empty());
+
+        // Both anomalies are reported again after the state went back to normal
+        assert!(detector.on_book(9100, &book(Some(100.0), Some(100.1))).is_empty());
+        assert_eq!(kinds(detector.on_book(9200, &book(None, Some(100.1)))), vec!["side_wiped"]);
+        assert_eq!(kinds(detector.on_tick(15000)), vec!["stale_book"]);
+    }
+
+    #[test]
+    fn test_spread_widening() {
+        let mut detector = AnomalyDetector::new(AnomalySettings { spread_percentile: 90.0, ..Default::default() });
+        // Spreads of about 1 to 10 bps, the last one is the percentile
+        for second in 0..99 {
+            detector.on_book(second * 1000, &book(Some(100.0), Some(100.0 + (second % 10 + 1) as f64 * 0.01)));
+        }
+        assert!(detector.on_book(99000, &book(Some(100.0), Some(100.09))).is_empty());
+
+        let anomalies = detector.on_book(99500, &book(Some(100.0), Some(100.5)));
+        let Anomaly::SpreadWidening { spread_bps, percentile_bps } = anomalies[0].anomaly else {
+            panic!("Spread widening is expected");
+        };
+        assert!((spread_bps - 49.875).abs() < 0.01 && (percentile_bps - 8.9955).abs() < 0.01, "{} {}", spread_bps, percentile_bps);
+        assert!(detector.on_book(100100, &book(Some(100.0), Some(100.5))).is_empty());
+    }
+
+    #[test]
+    fn test_trade_rate_spike() {
+        let mut detector = AnomalyDetector::new(AnomalySettings::default());
+        for second in 0..120 {
+            detector.on_trade(second * 1000);
+            detector.on_trade(second * 1000 + 500);
+            assert!(detector.on_tick(second * 1000 + 999).is_empty());
+        }
+
+        for trade in 0..30 {
+            detector.on_trade(120000 + trade);
+        }
+        let anomalies = detector.on_tick(121000);
+        assert_eq!(anomalies[0].anomaly, Anomaly::TradeRateSpike { trades: 30, average: 2.0 });
+        assert!(detector.on_tick(121500).is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+use serde::Serialize;
+use crate::mdc_core::models::DepthEntry;
+use crate::mdc_core::order_book::OrderBook;
+
+/// Compact analytics of the top of the maintained book, computed from a book update
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BookMetrics {
+    /// Local time of the book update in milliseconds since epoch
+    pub time: i64,
+    /// Number of top levels per side, which the quantities, the imbalance and the weighted mid are computed over
+    pub depth: usize,
+    /// Total quantity of the top bid levels
+    pub bid_quantity: f64,
+    /// Total quantity of the top ask levels
+    pub ask_quantity: f64,
+    /// Volume imbalance `(bid qty - ask qty) / (bid qty + ask qty)` of the top levels, from -1 (asks only) to 1
+    pub imbalance: f64,
+    /// Price halfway between the best bid and the best ask
+    pub mid_price: f64,
+    /// Mid price weighted by the opposite quantities of the best levels:
+    /// `(bid * ask qty + ask * bid qty) / (bid qty + ask qty)`
+    pub microprice: f64,
+    /// Like the microprice, but over the top levels: the volume-weighted average prices of each side
+    /// weighted by the opposite total quantities
+    pub weighted_mid: f64,
+}
+
+impl BookMetrics {
+    /// Compute the metrics of the book
+    ///
+    /// # Arguments
+    /// * `book` - The maintained book
+    /// * `depth` - Number of top levels per side to take into account (at least one)
+    /// * `time` - Local time of the book update in milliseconds since epoch
+    ///
+    /// # Returns
+    /// The metrics, or `None` if a side of the book is empty
+    pub fn compute(book: &OrderBook, depth: usize, time: i64) -> Option<Self> {
+        let depth = depth.max(1);
+        let (bids, asks) = book.top_n(depth);
+        let (best_bid, best_ask) = (bids.first()?, asks.first()?);
+
+        let quantity = |levels: &[DepthEntry]| levels.iter().map(|level| level.quantity.to_f64()).sum::<f64>();
+        let notional = |levels: &[DepthEntry]| {
+            levels.iter().map(|level| level.price.to_f64() * level.quantity.to_f64()).sum::<f64>()
+        };
+
+        let (bid_quantity, ask_quantity) = (quantity(&bids), quantity(&asks));
+        let total_quantity = bid_quantity + ask_quantity;
+        if total_quantity <= 0.0 {
+            return None;
+        }
+
+        let (bid_price, ask_price) = (best_bid.price.to_f64(), best_ask.price.to_f64());
+        let (best_bid_quantity, best_ask_quantity) = (best_bid.quantity.to_f64(), best_ask.quantity.to_f64());
+        let (bid_vwap, ask_vwap) = (notional(&bids) / bid_quantity, notional(&asks) / ask_quantity);
+
+        Some(Self {
+            time,
+            depth,
+            bid_quantity,
+            ask_quantity,
+            imbalance: (bid_quantity - ask_quantity) / total_quantity,
+            mid_price: (bid_price + ask_price) / 2.0,
+            microprice: (bid_price * best_ask_quantity + ask_price * best_bid_quantity) / (best_bid_quantity + best_ask_quantity),
+            weighted_mid: (bid_vwap * ask_quantity + ask_vwap * bid_quantity) / total_quantity,
+        })
+    }
+}
+
+impl fmt::Display for BookMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Depth: '{}', Imbalance: '{:.4}', Mid: '{}', Microprice: '{:.8}', Weighted mid: '{:.8}'",
+            self.depth,
+            self.imbalance,
+            self.mid_price,
+            self.microprice,
+            self.weighted_mid,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::DepthSnapshot;
+
+    fn entry(price: &str, quantity: &str) -> DepthEntry {
+        DepthEntry { price: price.parse().unwrap(), quantity: quantity.parse().unwrap() }
+    }
+
+    #[test]
+    fn test_book_metrics() {
+        let book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry("100", "3"), entry("99", "1"), entry("98", "10")],
+            asks: vec![entry("101", "1"), entry("102", "1")],
+        });
+
+        let metrics = BookMetrics::compute(&book, 2, 1000).unwrap();
+        assert_eq!((metrics.time, metrics.depth), (1000, 2));
+        assert_eq!((metrics.bid_quantity, metrics.ask_quantity), (4.0, 2.0));
+        assert!((metrics.imbalance - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(metrics.mid_price, 100.5);
+        // Heavier bids move the microprice towards the ask
+        assert_eq!(metrics.microprice, (100.0 * 1.0 + 101.0 * 3.0) / 4.0);
+        assert_eq!(metrics.weighted_mid, (99.75 * 2.0 + 101.5 * 4.0) / 6.0);
+
+        let one_sided = OrderBook::new(&DepthSnapshot { last_update_id: 1, bids: vec![entry("100", "1")], asks: vec![] });
+        assert_eq!(BookMetrics::compute(&one_sided, 5, 1000), None);
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::DepthEntry;
+use crate::mdc_core::order_book::OrderBook;
+
+/// Number of best levels per side, which are covered by an OKX book checksum
+pub const OKX_CHECKSUM_DEPTH: usize = 25;
+
+/// Number of best levels (or orders of a raw book) per side, which are covered by a Bitfinex book checksum
+pub const BITFINEX_CHECKSUM_DEPTH: usize = 25;
+
+/// Checksum of the top of the book, which a venue sends along with a depth update
+///
+/// The checksum is taken over the book after the update has been applied, so a mismatch means
+/// the maintained book has diverged from the venue book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookChecksum {
+    /// OKX: signed CRC32 of the best 25 bids and asks, interleaved as `bid price:bid size:ask price:ask size:...`.
+    /// The OKX decoder translates the checksum of the strings OKX sent into the formatting of `okx_checksum`
+    Okx(i32),
+    /// Bitfinex: signed CRC32 of the best 25 bids and asks, interleaved as `bid price:bid amount:ask price:-ask amount:...`
+    Bitfinex(i32),
+}
+
+impl BookChecksum {
+    /// The checksum sent by the venue
+    pub fn expected(&self) -> i64 {
+        match self {
+            BookChecksum::Okx(checksum) | BookChecksum::Bitfinex(checksum) => *checksum as i64,
+        }
+    }
+
+    /// The checksum of the given book, computed the same way as the venue does
+    pub fn compute(&self, book: &OrderBook) -> i64 {
+        match self {
+            BookChecksum::Okx(_) => okx_checksum(book) as i64,
+            BookChecksum::Bitfinex(_) => bitfinex_checksum(book) as i64,
+        }
+    }
+
+    /// Check whether the book matches the checksum
+    pub fn verify(&self, book: &OrderBook) -> bool {
+        self.compute(book) == self.expected()
+    }
+}
+
+impl fmt::Display for BookChecksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookChecksum::Okx(checksum) => write!(f, "okx:{}", checksum),
+            BookChecksum::Bitfinex(checksum) => write!(f, "bitfinex:{}", checksum),
+        }
+    }
+}
+
+/// OKX book checksum
+///
+/// Levels of both sides are interleaved, the remaining levels of the deeper side follow once the other side ends.
+/// Prices and sizes are formatted without trailing zeros. OKX takes the checksum over the strings it sent (e.g. `0.10`),
+/// so the OKX decoder translates it into this formatting before it reaches the book
+pub fn okx_checksum(book: &OrderBook) -> i32 {
+    let (bids, asks) = book.top_n(OKX_CHECKSUM_DEPTH);
+    let text = |levels: &[DepthEntry]| levels
+        .iter()
+        .map(|level| (level.price.to_string(), level.quantity.to_string()))
+        .collect::<Vec<_>>();
+
+    okx_text_checksum(&text(&bids), &text(&asks))
+}
+
+/// OKX book checksum of the given `(price, size)` strings of the best levels, best first
+pub fn okx_text_checksum<T: AsRef<str>>(bids: &[(T, T)], asks: &[(T, T)]) -> i32 {
+    let mut fields = Vec::with_capacity((bids.len() + asks.len()) * 2);
+
+    for i in 0..OKX_CHECKSUM_DEPTH {
+        for (price, size) in [bids.get(i), asks.get(i)].into_iter().flatten() {
+            fields.push(price.as_ref());
+            fields.push(size.as_ref());
+        }
+    }
+
+    crc32(fields.join(":").as_bytes()) as i32
+}
+
+/// Bitfinex book checksum of a book of price levels (`P0`-`P4` precisions)
+///
+/// Levels of both sides are interleaved like in the OKX checksum, ask amounts are negative.
+/// Numbers are formatted the way JavaScript prints them
+pub fn bitfinex_checksum(book: &OrderBook) -> i32 {
+    let (bids, asks) = book.top_n(BITFINEX_CHECKSUM_DEPTH);
+    let mut fields = Vec::with_capacity((bids.len() + asks.len()) * 2);
+
+    for i in 0..BITFINEX_CHECKSUM_DEPTH {
+        if let Some(bid) = bids.get(i) {
+            fields.push(js_number(bid.price, false));
+            fields.push(js_number(bid.quantity, false));
+        }
+        if let Some(ask) = asks.get(i) {
+            fields.push(js_number(ask.price, false));
+            fields.push(js_number(ask.quantity, true));
+        }
+    }
+
+    crc32(fields.join(":").as_bytes()) as i32
+}
+
+/// Bitfinex book checksum of a raw book (`R0` precision)
+///
+/// # Arguments
+/// * `bids` - The best bid orders as `(order id, amount)`, best first
+/// * `asks` - The best ask orders as `(order id, amount)`, best first. Amounts are negative, as sent by Bitfinex
+pub fn bitfinex_raw_checksum(bids: &[(u64, FixedPoint)], asks: &[(u64, FixedPoint)]) -> i32 {
+    let mut fields = Vec::with_capacity((bids.len() + asks.len()) * 2);
+
+    for i in 0..BITFINEX_CHECKSUM_DEPTH {
+        for (id, amount) in [bids.get(i), asks.get(i)].into_iter().flatten() {
+            fields.push(id.to_string());
+            fields.push(js_number(*amount, false));
+        }
+    }
+
+    crc32(fields.join(":").as_bytes()) as i32
+}
+
+/// Format the value the way JavaScript prints numbers: values below 10^-6 use the exponent notation (e.g. `5e-7`)
+fn js_number(value: FixedPoint, negate: bool) -> String {
+    let decimal = value.to_string();
+    let (negative, digits) = match decimal.strip_prefix('-') {
+        Some(digits) => (!negate, digits),
+        None => (negate && !value.is_zero(), decimal.as_str()),
+    };
+    let sign = if negative { "-" } else { "" };
+
+    let fraction = digits.strip_prefix("0.").unwrap_or_default();
+    let significant = fraction.trim_start_matches('0');
+    let leading_zeros = fraction.len() - significant.len();
+    if leading_zeros < 6 || significant.is_empty() {
+        return format!("{}{}", sign, digits);
+    }
+
+    let (first, rest) = significant.split_at(1);
+    let mantissa = if rest.is_empty() { first.to_string() } else { format!("{}.{}", first, rest) };
+    format!("{}{}e-{}", sign, mantissa, leading_zeros + 1)
+}
+
+/// CRC-32 (IEEE 802.3), as used by zlib
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::DepthSnapshot;
+
+    fn entry(price: &str, quantity: &str) -> DepthEntry {
+        DepthEntry { price: price.parse().unwrap(), quantity: quantity.parse().unwrap() }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_okx_checksum_interleaves_levels() {
+        let book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry("3366.1", "7"), entry("3366", "6"), entry("3365", "1.50")],
+            asks: vec![entry("3366.8", "9"), entry("3368", "8")],
+        });
+
+        let expected = crc32(b"3366.1:7:3366.8:9:3366:6:3368:8:3365:1.5") as i32;
+        assert_eq!(okx_checksum(&book), expected);
+        assert!(BookChecksum::Okx(expected).verify(&book));
+        assert!(!BookChecksum::Okx(expected.wrapping_add(1)).verify(&book));
+
+        let mut changed = book.clone();
+        changed.apply_update(OrderBook::ask(FixedPoint::from(3368.0)), FixedPoint::ZERO);
+        assert!(!BookChecksum::Okx(expected).verify(&changed));
+    }
+
+    #[test]
+    fn test_bitfinex_checksums() {
+        let book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry("6000", "1.5"), entry("5999", "0.0000005")],
+            asks: vec![entry("6001", "2")],
+        });
+
+        let expected = crc32(b"6000:1.5:6001:-2:5999:5e-7") as i32;
+        assert_eq!(bitfinex_checksum(&book), expected);
+        assert!(BookChecksum::Bitfinex(expected).verify(&book));
+        assert!(!BookChecksum::Okx(expected).verify(&book));
+
+        let bids = [(11, "0.5".parse().unwrap()), (12, "1.25".parse().unwrap())];
+        let asks = [(21, "-0.00000012".parse().unwrap())];
+        assert_eq!(bitfinex_raw_checksum(&bids, &asks), crc32(b"11:0.5:21:-1.2e-7:12:1.25") as i32);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+use serde::Serialize;
+
+/// A break in the depth update sequence: the updates after `last_update_id` have been missed,
+/// the book isn't updated until the next snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SequenceGap {
+    /// Local time of the detection in milliseconds since epoch
+    pub time: i64,
+    /// The last update, which continues the sequence
+    pub last_update_id: u64,
+    /// The first update id of the earliest update received after the gap
+    pub next_update_id: u64,
+    /// Number of updates, which wait for the next snapshot
+    pub buffered: usize,
+}
+
+impl fmt::Display for SequenceGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Last update id: '{}', Next update id: '{}', Buffered: '{}'",
+            self.last_update_id,
+            self.next_update_id,
+            self.buffered,
+        )
+    }
+}
+
+/// What replaced the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResyncSource {
+    /// A snapshot, which restarted the depth update sequence
+    Snapshot,
+    /// A validation snapshot, which the drifted book has been replaced with
+    Validation,
+}
+
+impl fmt::Display for ResyncSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResyncSource::Snapshot => write!(f, "snapshot"),
+            ResyncSource::Validation => write!(f, "validation"),
+        }
+    }
+}
+
+/// The book has been replaced, so the books before and after it aren't connected by the updates in between
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Resync {
+    /// Local time of the replacement in milliseconds since epoch
+    pub time: i64,
+    pub source: ResyncSource,
+    /// The last update applied to the replaced book
+    pub previous_update_id: u64,
+    /// The update id of the new book
+    pub update_id: u64,
+}
+
+impl fmt::Display for Resync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Source: '{}', Previous update id: '{}', Update id: '{}'",
+            self.source,
+            self.previous_update_id,
+            self.update_id,
+        )
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+/// Verdict on a trade received by the TradeDeduplicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeVerdict {
+    /// The trade has not been seen before and has to be forwarded
+    New,
+    /// The trade has already been forwarded
+    Duplicate,
+    /// The trade is too far behind the newest one to tell whether it has been forwarded
+    Stale,
+}
+
+/// TradeDeduplicator filters out copies of trades received over redundant connections
+///
+/// Trade ids grow with time, so only the ids within a window behind the newest trade are remembered.
+/// A trade missed by one connection is still forwarded when another connection delivers it, even if it is out of order
+pub struct TradeDeduplicator {
+    window: u64,
+    seen: BTreeSet<u64>,
+}
+
+impl TradeDeduplicator {
+    /// Create a new TradeDeduplicator
+    ///
+    /// # Arguments
+    /// * `window` - Number of trade ids behind the newest trade, which are remembered
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            seen: BTreeSet::new(),
+        }
+    }
+
+    /// Decide whether the trade has to be forwarded and remember it
+    pub fn accept(&mut self, trade_id: u64) -> TradeVerdict {
+        if let Some(&newest) = self.seen.last() {
+            if trade_id + self.window <= newest {
+                return TradeVerdict::Stale;
+            }
+        }
+
+        if !self.seen.insert(trade_id) {
+            return TradeVerdict::Duplicate;
+        }
+
+        if let Some(&newest) = self.seen.last() {
+            while self.seen.first().is_some_and(|&oldest| oldest + self.window <= newest) {
+                self.seen.pop_first();
+            }
+        }
+
+        TradeVerdict::New
+    }
+}
+
+/// Verdict on an update received by the MonotonicFilter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateVerdict {
+    /// The update is newer than every update forwarded so far and has to be forwarded
+    New,
+    /// The update has already been forwarded
+    Duplicate,
+    /// A newer update has already been forwarded, so the update is outdated
+    OutOfOrder,
+}
+
+/// MonotonicFilter passes updates with strictly increasing ids only
+///
+/// Unlike trades, each update replaces the previous state (e.g. a bookTicker update carries the whole top of the book),
+/// so an update arriving after a newer one is outdated and is dropped along with the copies
+#[derive(Default)]
+pub struct MonotonicFilter {
+    last_update_id: Option<u64>,
+}
+
+impl MonotonicFilter {
+    /// Decide whether the update has to be forwarded and remember it
+    pub fn accept(&mut self, update_id: u64) -> UpdateVerdict {
+        match self.last_update_id {
+            Some(last_update_id) if update_id == last_update_id => UpdateVerdict::Duplicate,
+            Some(last_update_id) if update_id < last_update_id => UpdateVerdict::OutOfOrder,
+            _ => {
+                self.last_update_id = Some(update_id);
+                UpdateVerdict::New
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copies_are_dropped() {
+        let mut deduplicator = TradeDeduplicator::new(100);
+
+        assert_eq!(deduplicator.accept(10), TradeVerdict::New);
+        assert_eq!(deduplicator.accept(10), TradeVerdict::Duplicate);
+        assert_eq!(deduplicator.accept(12), TradeVerdict::New);
+
+        // The trade missed by the faster connection is delivered by the slower one
+        assert_eq!(deduplicator.accept(11), TradeVerdict::New);
+        assert_eq!(deduplicator.accept(11), TradeVerdict::Duplicate);
+        assert_eq!(deduplicator.accept(12), TradeVerdict::Duplicate);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut deduplicator = TradeDeduplicator::new(3);
+
+        assert_eq!(deduplicator.accept(10), TradeVerdict::New);
+        assert_eq!(deduplicator.accept(12), TradeVerdict::New);
+        assert_eq!(deduplicator.accept(13), TradeVerdict::New);
+        assert_eq!(deduplicator.accept(10), TradeVerdict::Stale);
+        assert_eq!(deduplicator.accept(11), TradeVerdict::New);
+        assert_eq!(deduplicator.seen.len(), 3);
+    }
+
+    #[test]
+    fn test_monotonic_filter() {
+        let mut filter = MonotonicFilter::default();
+
+        assert_eq!(filter.accept(10), UpdateVerdict::New);
+        assert_eq!(filter.accept(10), UpdateVerdict::Duplicate);
+        assert_eq!(filter.accept(12), UpdateVerdict::New);
+        assert_eq!(filter.accept(11), UpdateVerdict::OutOfOrder);
+        assert_eq!(filter.accept(12), UpdateVerdict::Duplicate);
+        assert_eq!(filter.accept(13), UpdateVerdict::New);
+    }
+}
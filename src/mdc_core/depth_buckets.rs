@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use crate::mdc_core::order_book::{OrderBook, Side};
+
+/// Tolerance of the bucket index computation, so prices on a bucket edge aren't moved by rounding errors
+const EPSILON: f64 = 1e-9;
+
+/// Unit of the bucket width
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketUnit {
+    /// Buckets are multiples of the width in the quote currency, e.g. $1
+    #[default]
+    Price,
+    /// Buckets are distances from the mid price in basis points
+    Bps,
+}
+
+fn default_bucket_count() -> usize {
+    20
+}
+
+/// Aggregation of the book levels into a fixed number of price buckets per side
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriceBuckets {
+    pub width: f64,
+    #[serde(default)]
+    pub unit: BucketUnit,
+    /// Number of buckets per side
+    #[serde(default = "default_bucket_count")]
+    pub count: usize,
+}
+
+impl PriceBuckets {
+    /// Sum the quantities of the levels of a side into `count` buckets, starting with the bucket of the best level
+    ///
+    /// Every bucket is a `[price, quantity]` pair, where the price is the edge of the bucket away from the spread
+    /// and the quantity is 0 if the bucket has no levels. Levels beyond the last bucket are left out.
+    /// Bid buckets are `[price, price + width)` and ask buckets are `(price - width, price]`
+    ///
+    /// # Returns
+    /// Exactly `count` buckets, best first, or none if the side is empty, the width isn't positive
+    /// or, with basis points, the other side is empty
+    pub fn aggregate(&self, book: &OrderBook, side: Side) -> Vec<[f64; 2]> {
+        let Some(best) = book.levels(side).next() else {
+            return Vec::new();
+        };
+        let best = best.price.to_f64();
+
+        // Buckets are counted from the origin, the edge of the first bucket towards the spread
+        let (origin, width) = match self.unit {
+            BucketUnit::Price => {
+                let origin = match side {
+                    Side::Bid => ((best / self.width + EPSILON).floor() + 1.0) * self.width,
+                    Side::Ask => ((best / self.width - EPSILON).ceil() - 1.0) * self.width,
+                };
+                (origin, self.width)
+            }
+            BucketUnit::Bps => {
+                let Some(mid_price) = book.mid_price() else {
+                    return Vec::new();
+                };
+                (mid_price, mid_price * self.width / 10_000.0)
+            }
+        };
+        if width <= 0.0 || !width.is_finite() {
+            return Vec::new();
+        }
+
+        let direction = match side {
+            Side::Bid => -1.0,
+            Side::Ask => 1.0,
+        };
+        let mut buckets: Vec<[f64; 2]> = (0..self.count)
+            .map(|index| [origin + direction * (index + 1) as f64 * width, 0.0])
+            .collect();
+
+        for level in book.levels(side) {
+            let distance = direction * (level.price.to_f64() - origin);
+            let index = (distance / width - EPSILON).ceil() - 1.0;
+            if index >= self.count as f64 {
+                break;
+            }
+
+            // A crossed book may have levels on the other side of the origin
+            if index >= 0.0 {
+                buckets[index as usize][1] += level.quantity.to_f64();
+            }
+        }
+
+        buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    fn make_book(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> OrderBook {
+        let entries = |levels: &[(f64, f64)]| levels
+            .iter()
+            .map(|(price, quantity)| DepthEntry { price: FixedPoint::from(*price), quantity: FixedPoint::from(*quantity) })
+            .collect();
+
+        OrderBook::new(&DepthSnapshot { last_update_id: 1, bids: entries(bids), asks: entries(asks) })
+    }
+
+    #[test]
+    fn test_price_buckets() {
+        let book = make_book(
+            &[(100.3, 1.0), (100.0, 2.0), (99.9, 3.0), (97.5, 4.0)],
+            &[(100.4, 1.0), (101.0, 2.0), (101.2, 3.0)],
+        );
+        let buckets = PriceBuckets { width: 1.0, unit: BucketUnit::Price, count: 3 };
+
+        assert_eq!(buckets.aggregate(&book, Side::Bid), vec![[100.0, 3.0], [99.0, 3.0], [98.0, 0.0]]);
+        assert_eq!(buckets.aggregate(&book, Side::Ask), vec![[101.0, 3.0], [102.0, 3.0], [103.0, 0.0]]);
+    }
+
+    #[test]
+    fn test_bps_buckets() {
+        let book = make_book(&[(99.95, 1.0), (99.9, 2.0), (99.0, 5.0)], &[(100.05, 4.0)]);
+        let buckets = PriceBuckets { width: 10.0, unit: BucketUnit::Bps, count: 2 };
+
+        let bids = buckets.aggregate(&book, Side::Bid);
+        assert_eq!(bids.iter().map(|bucket| bucket[1]).collect::<Vec<_>>(), vec![3.0, 0.0]);
+        assert!((bids[1][0] - 99.8).abs() < 1e-9);
+        assert_eq!(buckets.aggregate(&book, Side::Ask)[0][1], 4.0);
+
+        assert!(buckets.aggregate(&make_book(&[(99.0, 1.0)], &[]), Side::Bid).is_empty());
+
+        let parsed: PriceBuckets = serde_yaml::from_str("{width: 10, unit: bps}").unwrap();
+        assert_eq!(parsed, PriceBuckets { width: 10.0, unit: BucketUnit::Bps, count: 20 });
+    }
+}
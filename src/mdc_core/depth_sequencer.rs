@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use crate::mdc_core::level_pool::LEVEL_POOL;
+use crate::mdc_core::models::{DepthSnapshot, DepthUpdate};
+use crate::mdc_core::sequencing::{SequencingRules, SequencingStrategy};
+
+/// Limits of the buffer of depth updates, which can't be forwarded yet
+#[derive(Debug, Clone, Copy)]
+pub struct BufferLimits {
+    /// Maximum number of buffered updates. The updates with the lowest ids are evicted first
+    pub max_size: usize,
+    /// Maximum time in milliseconds an update can stay in the buffer. 0 disables age-based eviction
+    pub max_age: u64,
+}
+
+/// Number of updates evicted from the buffer by a single `evict_stale` call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Evictions {
+    pub by_age: u64,
+    pub by_size: u64,
+}
+
+/// A buffered depth update along with the time it has been received
+struct BufferedUpdate {
+    received_at: u64,
+    update: DepthUpdate,
+}
+
+/// DepthSequencer restores the order of depth updates received over multiple connections
+///
+/// It is a pure state machine without I/O: time is passed in by the caller as milliseconds of any
+/// monotonic clock, so it can be driven by the live dispatcher as well as by a backtester replaying a tape
+///
+/// Updates, which can't be forwarded because the snapshot has not arrived yet or a gap persists,
+/// are buffered within the configured BufferLimits. Whether an update continues the sequence is decided by
+/// the SequencingStrategy, the SequencingRules of a supported venue by default
+pub struct DepthSequencer<S: SequencingStrategy = SequencingRules> {
+    strategy: S,
+    limits: BufferLimits,
+    last_processed_update_id: Option<u64>,
+    is_first_after_snapshot: bool,
+    buffer: BTreeMap<u64, BufferedUpdate>,
+}
+
+impl<S: SequencingStrategy> DepthSequencer<S> {
+    /// Create a new DepthSequencer
+    ///
+    /// # Arguments
+    /// * `strategy` - Venue-specific rules of depth update continuity
+    /// * `limits` - Limits of the buffer of pending updates
+    pub fn new(strategy: S, limits: BufferLimits) -> Self {
+        Self {
+            strategy,
+            limits,
+            last_processed_update_id: None,
+            is_first_after_snapshot: false,
+            buffer: BTreeMap::new(),
+        }
+    }
+
+    /// The last update id, which has been forwarded (or taken from the snapshot)
+    pub fn last_processed_update_id(&self) -> Option<u64> {
+        self.last_processed_update_id
+    }
+
+    /// Number of buffered updates, which wait for a snapshot or for the gap before them to be filled
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The buffered update with the lowest id, the first one after a gap
+    pub fn first_buffered(&self) -> Option<&DepthUpdate> {
+        self.buffer.first_key_value().map(|(_, buffered)| &buffered.update)
+    }
+
+    /// Buffer a received update, using last_update_id as the key. Copies of the same update replace each other
+    ///
+    /// # Arguments
+    /// * `update` - The received DepthUpdate
+    /// * `now` - Receive time in milliseconds
+    pub fn push_update(&mut self, update: DepthUpdate, now: u64) {
+        if let Some(replaced) = self.buffer.insert(update.last_update_id, BufferedUpdate { received_at: now, update }) {
+            LEVEL_POOL.recycle(replaced.update);
+        }
+    }
+
+    /// Apply a snapshot
+    ///
+    /// # Returns
+    /// `true` if the snapshot has to be forwarded, i.e. it is the first one or newer than the last processed update.
+    /// The sequence is restarted from the snapshot in that case
+    pub fn apply_snapshot(&mut self, snapshot: &DepthSnapshot) -> bool {
+        if let Some(last_processed_update_id) = self.last_processed_update_id {
+            if snapshot.last_update_id <= last_processed_update_id {
+                return false;
+            }
+        }
+
+        self.last_processed_update_id = Some(snapshot.last_update_id);
+        self.is_first_after_snapshot = true;
+        true
+    }
+
+    /// Take the buffered updates, which continue the sequence
+    ///
+    /// # Behavior
+    /// * Nothing is forwarded until the first snapshot has been applied
+    /// * Updates, which end at or before the last processed update id, are discarded
+    /// * Updates are forwarded in id order as long as each continues the sequence according to the SequencingStrategy.
+    ///   The first gap stops forwarding, the updates after it stay buffered
+    pub fn drain(&mut self) -> Vec<DepthUpdate> {
+        let Some(mut last_processed_update_id) = self.last_processed_update_id else {
+            return vec![];
+        };
+
+        let mut ready = Vec::new();
+
+        while let Some(entry) = self.buffer.first_entry() {
+            if *entry.key() <= last_processed_update_id {
+                LEVEL_POOL.recycle(entry.remove().update);
+                continue;
+            }
+
+            if !self.strategy.is_continuation(&entry.get().update, last_processed_update_id, self.is_first_after_snapshot) {
+                break;
+            }
+
+            let update = entry.remove().update;
+            last_processed_update_id = update.last_update_id;
+            self.last_processed_update_id = Some(last_processed_update_id);
+            self.is_first_after_snapshot = false;
+            ready.push(update);
+        }
+
+        ready
+    }
+
+    /// Evict updates, which exceed the buffer limits
+    ///
+    /// # Arguments
+    /// * `now` - Current time in milliseconds, on the same clock as the receive times
+    ///
+    /// # Behavior
+    /// * Updates at the head of the buffer, which are older than the maximum age, are evicted.
+    ///   Since update ids grow with time, the scan stops at the first update, which is young enough
+    /// * If the buffer is still larger than the maximum size, the updates with the lowest ids are evicted,
+    ///   as the newer ones are more likely to continue the sequence after the next snapshot
+    pub fn evict_stale(&mut self, now: u64) -> Evictions {
+        let mut evictions = Evictions::default();
+
+        if self.limits.max_age > 0 {
+            while let Some(entry) = self.buffer.first_entry() {
+                if now.saturating_sub(entry.get().received_at) < self.limits.max_age {
+                    break;
+                }
+
+                LEVEL_POOL.recycle(entry.remove().update);
+                evictions.by_age += 1;
+            }
+        }
+
+        while self.buffer.len() > self.limits.max_size {
+            if let Some((_, evicted)) = self.buffer.pop_first() {
+                LEVEL_POOL.recycle(evicted.update);
+            }
+            evictions.by_size += 1;
+        }
+
+        evictions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_update(first: u64, last: u64) -> DepthUpdate {
+        DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            last_update_id: last,
+            previous_last_update_id: None,
+            bids: vec![],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        }
+    }
+
+    fn make_futures_update(first: u64, last: u64, previous: u64) -> DepthUpdate {
+        DepthUpdate {
+            previous_last_update_id: Some(previous),
+            ..make_update(first, last)
+        }
+    }
+
+    fn make_snapshot(last: u64) -> DepthSnapshot {
+        DepthSnapshot {
+            last_update_id: last,
+            bids: vec![],
+            asks: vec![],
+        }
+    }
+
+    fn make_sequencer() -> DepthSequencer {
+        DepthSequencer::new(SequencingRules::BinanceSpot, BufferLimits { max_size: 10_000, max_age: 0 })
+    }
+
+    fn ids(updates: &[DepthUpdate]) -> Vec<(u64, u64)> {
+        updates.iter().map(|update| (update.first_update_id, update.last_update_id)).collect()
+    }
+
+    fn permutations(items: &[DepthUpdate]) -> Vec<Vec<DepthUpdate>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+
+        let mut result = Vec::new();
+        for index in 0..items.len() {
+            let mut rest = items.to_vec();
+            let head = rest.remove(index);
+            for mut tail in permutations(&rest) {
+                tail.insert(0, head.clone());
+                result.push(tail);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_nothing_is_forwarded_before_snapshot() {
+        let mut sequencer = make_sequencer();
+        sequencer.push_update(make_update(101, 105), 0);
+        assert!(sequencer.drain().is_empty());
+        assert_eq!(sequencer.last_processed_update_id(), None);
+
+        assert!(sequencer.apply_snapshot(&make_snapshot(100)));
+        assert_eq!(ids(&sequencer.drain()), vec![(101, 105)]);
+        assert_eq!(sequencer.last_processed_update_id(), Some(105));
+    }
+
+    #[test]
+    fn test_gap_holds_back_later_updates() {
+        let mut sequencer = make_sequencer();
+        sequencer.apply_snapshot(&make_snapshot(100));
+
+        sequencer.push_update(make_update(106, 110), 0);
+        sequencer.push_update(make_update(111, 115), 0);
+        assert!(sequencer.drain().is_empty());
+
+        sequencer.push_update(make_update(101, 105), 0);
+        assert_eq!(ids(&sequencer.drain()), vec![(101, 105), (106, 110), (111, 115)]);
+        assert!(sequencer.drain().is_empty());
+    }
+
+    #[test]
+    fn test_duplicates_and_old_updates_are_discarded() {
+        let mut sequencer = make_sequencer();
+        sequencer.push_update(make_update(90, 95), 0);
+        sequencer.apply_snapshot(&make_snapshot(100));
+
+        sequencer.push_update(make_update(101, 105), 0);
+        sequencer.push_update(make_update(101, 105), 0);
+        assert_eq!(ids(&sequencer.drain()), vec![(101, 105)]);
+
+        sequencer.push_update(make_update(101, 105), 0);
+        assert!(sequencer.drain().is_empty());
+        assert_eq!(sequencer.evict_stale(0), Evictions::default());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut sequencer = make_sequencer();
+        assert!(sequencer.apply_snapshot(&make_snapshot(100)));
+        assert!(!sequencer.apply_snapshot(&make_snapshot(100)));
+        assert!(!sequencer.apply_snapshot(&make_snapshot(90)));
+
+        // A newer snapshot skips the gap
+        sequencer.push_update(make_update(121, 125), 0);
+        assert!(sequencer.drain().is_empty());
+        assert!(sequencer.apply_snapshot(&make_snapshot(120)));
+        assert_eq!(ids(&sequencer.drain()), vec![(121, 125)]);
+    }
+
+    #[test]
+    fn test_every_arrival_order_is_restored() {
+        let updates = vec![
+            make_update(101, 105),
+            make_update(106, 108),
+            make_update(109, 110),
+            make_update(111, 120),
+            make_update(121, 125),
+        ];
+        let expected = ids(&updates);
+
+        for order in permutations(&updates) {
+            for snapshot_position in 0..=order.len() {
+                let mut sequencer = make_sequencer();
+                let mut forwarded = Vec::new();
+
+                for (position, update) in order.iter().enumerate() {
+                    if position == snapshot_position {
+                        sequencer.apply_snapshot(&make_snapshot(100));
+                    }
+                    sequencer.push_update(update.clone(), 0);
+                    // Every update is received twice, as over redundant connections
+                    sequencer.push_update(update.clone(), 0);
+                    forwarded.extend(sequencer.drain());
+                }
+
+                if snapshot_position == order.len() {
+                    sequencer.apply_snapshot(&make_snapshot(100));
+                    forwarded.extend(sequencer.drain());
+                }
+
+                assert_eq!(ids(&forwarded), expected, "Arrival order: {:?}", ids(&order));
+            }
+        }
+    }
+
+    #[test]
+    fn test_futures_sequence() {
+        let mut sequencer = DepthSequencer::new(SequencingRules::BinanceFutures, BufferLimits { max_size: 10, max_age: 0 });
+        sequencer.apply_snapshot(&make_snapshot(100));
+
+        sequencer.push_update(make_futures_update(120, 130, 105), 0);
+        sequencer.push_update(make_futures_update(140, 150, 131), 0);
+        assert!(sequencer.drain().is_empty());
+
+        sequencer.push_update(make_futures_update(98, 105, 97), 0);
+        assert_eq!(ids(&sequencer.drain()), vec![(98, 105), (120, 130)]);
+        assert_eq!(sequencer.last_processed_update_id(), Some(130));
+    }
+
+    /// Strategy of a venue, which numbers its updates consecutively and has no previous id
+    struct ConsecutiveSequencing;
+
+    impl SequencingStrategy for ConsecutiveSequencing {
+        fn is_continuation(&self, update: &DepthUpdate, last_processed_update_id: u64, _is_first_after_snapshot: bool) -> bool {
+            self.follows(update, last_processed_update_id)
+        }
+
+        fn follows(&self, update: &DepthUpdate, previous_last_update_id: u64) -> bool {
+            update.first_update_id == previous_last_update_id + 1 && update.last_update_id == update.first_update_id
+        }
+    }
+
+    #[test]
+    fn test_custom_strategy() {
+        let mut sequencer = DepthSequencer::new(ConsecutiveSequencing, BufferLimits { max_size: 10, max_age: 0 });
+        sequencer.apply_snapshot(&make_snapshot(7));
+
+        sequencer.push_update(make_update(9, 9), 0);
+        sequencer.push_update(make_update(8, 8), 0);
+        sequencer.push_update(make_update(11, 11), 0);
+        assert_eq!(ids(&sequencer.drain()), vec![(8, 8), (9, 9)]);
+        assert_eq!(sequencer.first_buffered().map(|update| update.first_update_id), Some(11));
+    }
+
+    #[test]
+    fn test_evict_by_size() {
+        let mut sequencer = DepthSequencer::new(SequencingRules::BinanceSpot, BufferLimits { max_size: 2, max_age: 0 });
+        sequencer.push_update(make_update(101, 105), 0);
+        sequencer.push_update(make_update(106, 110), 0);
+        sequencer.push_update(make_update(111, 115), 0);
+
+        assert_eq!(sequencer.evict_stale(1_000_000), Evictions { by_age: 0, by_size: 1 });
+
+        // The update continuing the snapshot has been evicted
+        sequencer.apply_snapshot(&make_snapshot(100));
+        assert!(sequencer.drain().is_empty());
+    }
+
+    #[test]
+    fn test_evict_by_age() {
+        let mut sequencer = DepthSequencer::new(SequencingRules::BinanceSpot, BufferLimits { max_size: 10, max_age: 50 });
+        sequencer.push_update(make_update(101, 105), 1000);
+        sequencer.push_update(make_update(106, 110), 1040);
+
+        assert_eq!(sequencer.evict_stale(1049), Evictions::default());
+        assert_eq!(sequencer.evict_stale(1050), Evictions { by_age: 1, by_size: 0 });
+        assert_eq!(sequencer.evict_stale(1089), Evictions::default());
+
+        sequencer.apply_snapshot(&make_snapshot(105));
+        assert_eq!(ids(&sequencer.drain()), vec![(106, 110)]);
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Number of decimal places of a FixedPoint value. Binance reports prices and quantities with at most 8 decimals
+pub const DECIMALS: u32 = 8;
+
+const SCALE: i64 = 10_i64.pow(DECIMALS);
+
+/// A decimal price or quantity, stored as an integer number of 10^-8 units
+///
+/// Unlike `f64`, values parsed from the exchange compare equal exactly when their decimal representations do,
+/// so they can be used as order book keys. Values are serialized as JSON numbers, so the output formats
+/// are the same as with `f64`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FixedPoint(i64);
+
+/// Error of parsing a decimal string into a FixedPoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseFixedPointError(String);
+
+impl fmt::Display for ParseFixedPointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid fixed point number: '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseFixedPointError {}
+
+impl FixedPoint {
+    pub const ZERO: FixedPoint = FixedPoint(0);
+
+    /// Convert from `f64`, rounding to the nearest 10^-8
+    pub fn from_f64(value: f64) -> Self {
+        FixedPoint((value * SCALE as f64).round() as i64)
+    }
+
+    /// The nearest `f64` value. For values parsed from the exchange it's the same as parsing the string as `f64`
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+
+    /// Convert a decimal given as `mantissa * 10^exponent` (e.g. SBE prices), without going through `f64`
+    ///
+    /// # Returns
+    /// `None` if the value has non-zero digits beyond the 8th decimal place or is out of range
+    pub fn from_mantissa(mantissa: i64, exponent: i8) -> Option<Self> {
+        let shift = exponent as i32 + DECIMALS as i32;
+        if shift >= 0 {
+            return 10_i64.checked_pow(shift as u32).and_then(|factor| mantissa.checked_mul(factor)).map(FixedPoint);
+        }
+
+        let divisor = 10_i64.checked_pow(shift.unsigned_abs())?;
+        (mantissa % divisor == 0).then(|| FixedPoint(mantissa / divisor))
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check whether the value is a whole number of steps (e.g. the tick size of a symbol). A zero step matches everything
+    pub fn is_multiple_of(self, step: FixedPoint) -> bool {
+        step.0 == 0 || self.0 % step.0 == 0
+    }
+}
+
+impl FromStr for FixedPoint {
+    type Err = ParseFixedPointError;
+
+    /// Parse a plain decimal string (e.g. `"-123.45000000"`) without going through `f64`
+    ///
+    /// Digits beyond the 8th decimal place are only accepted if they are zeros
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseFixedPointError(s.to_string());
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (integer.is_empty() && fraction.is_empty()) || !is_digits(integer) || !is_digits(fraction) {
+            return Err(error());
+        }
+
+        let (fraction, excess) = fraction.split_at(fraction.len().min(DECIMALS as usize));
+        if excess.bytes().any(|b| b != b'0') {
+            return Err(error());
+        }
+
+        // The digits of the integer part, then of the fraction padded with zeros to DECIMALS places
+        let padding = std::iter::repeat_n(b'0', DECIMALS as usize - fraction.len());
+        let mut units: i64 = 0;
+        for digit in integer.bytes().chain(fraction.bytes()).chain(padding) {
+            units = units
+                .checked_mul(10)
+                .and_then(|units| units.checked_add((digit - b'0') as i64))
+                .ok_or_else(error)?;
+        }
+
+        Ok(FixedPoint(if negative { -units } else { units }))
+    }
+}
+
+impl From<f64> for FixedPoint {
+    fn from(value: f64) -> Self {
+        FixedPoint::from_f64(value)
+    }
+}
+
+/// Prints the exact decimal value without trailing zeros, e.g. `123.45`
+impl fmt::Display for FixedPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let units = self.0.unsigned_abs();
+        let integer = units / SCALE as u64;
+        let fraction = units % SCALE as u64;
+
+        if fraction == 0 {
+            return write!(f, "{}{}", sign, integer);
+        }
+
+        let fraction = format!("{:0width$}", fraction, width = DECIMALS as usize);
+        write!(f, "{}{}.{}", sign, integer, fraction.trim_end_matches('0'))
+    }
+}
+
+impl fmt::Debug for FixedPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Add for FixedPoint {
+    type Output = FixedPoint;
+
+    fn add(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0 + other.0)
+    }
+}
+
+impl Sub for FixedPoint {
+    type Output = FixedPoint;
+
+    fn sub(self, other: FixedPoint) -> FixedPoint {
+        FixedPoint(self.0 - other.0)
+    }
+}
+
+impl Serialize for FixedPoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+/// Accepts decimal strings, as sent by the exchange, as well as numbers, as written by `Serialize`
+impl<'de> Deserialize<'de> for FixedPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FixedPointVisitor;
+
+        impl Visitor<'_> for FixedPointVisitor {
+            type Value = FixedPoint;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a decimal string or a number")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<FixedPoint, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<FixedPoint, E> {
+                Ok(FixedPoint::from_f64(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<FixedPoint, E> {
+                Ok(FixedPoint::from_f64(value as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<FixedPoint, E> {
+                Ok(FixedPoint::from_f64(value as f64))
+            }
+        }
+
+        deserializer.deserialize_any(FixedPointVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> FixedPoint {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(parse("123.45000000"), FixedPoint(12_345_000_000));
+        assert_eq!(parse("0.00000001"), FixedPoint(1));
+        assert_eq!(parse("-1.5"), FixedPoint(-150_000_000));
+        assert_eq!(parse("42"), FixedPoint(4_200_000_000));
+        assert_eq!(parse("0.1000000000"), FixedPoint(10_000_000));
+        assert_eq!(parse("92233720368.00000000"), FixedPoint(9_223_372_036_800_000_000));
+
+        assert!("0.000000001".parse::<FixedPoint>().is_err());
+        assert!("1e5".parse::<FixedPoint>().is_err());
+        assert!("".parse::<FixedPoint>().is_err());
+        assert!(".".parse::<FixedPoint>().is_err());
+        assert!("92233720369".parse::<FixedPoint>().is_err());
+
+        assert_eq!(parse("123.45000000").to_string(), "123.45");
+        assert_eq!(parse("-0.00000001").to_string(), "-0.00000001");
+        assert_eq!(parse("100.00").to_string(), "100");
+    }
+
+    #[test]
+    fn test_exact_equality() {
+        // 0.1 + 0.2 != 0.3 as f64, but the fixed point sum is exact
+        assert_eq!(parse("0.1") + parse("0.2"), parse("0.3"));
+        assert_eq!(parse("0.3") - parse("0.1"), parse("0.2"));
+        assert_eq!(parse("23456.78").to_f64(), "23456.78".parse::<f64>().unwrap());
+        assert_eq!(FixedPoint::from_f64(0.1 + 0.2), parse("0.3"));
+    }
+
+    #[test]
+    fn test_tick_size() {
+        let tick = parse("0.01");
+        assert!(parse("100.25").is_multiple_of(tick));
+        assert!(!parse("100.255").is_multiple_of(tick));
+        assert!(parse("100.255").is_multiple_of(FixedPoint::ZERO));
+    }
+
+    #[test]
+    fn test_from_mantissa() {
+        assert_eq!(FixedPoint::from_mantissa(6_543_210, -2), Some(parse("65432.1")));
+        assert_eq!(FixedPoint::from_mantissa(1, -8), Some(parse("0.00000001")));
+        assert_eq!(FixedPoint::from_mantissa(-15, 1), Some(parse("-150")));
+        assert_eq!(FixedPoint::from_mantissa(1_000, -11), Some(parse("0.00000001")));
+        assert_eq!(FixedPoint::from_mantissa(1, -9), None);
+        assert_eq!(FixedPoint::from_mantissa(i64::MAX, 0), None);
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(serde_json::to_string(&parse("123.45")).unwrap(), "123.45");
+        assert_eq!(serde_json::from_str::<FixedPoint>("\"123.45\"").unwrap(), parse("123.45"));
+        assert_eq!(serde_json::from_str::<FixedPoint>("123.45").unwrap(), parse("123.45"));
+        assert_eq!(serde_json::from_str::<FixedPoint>("2").unwrap(), parse("2"));
+        assert!(serde_json::from_str::<FixedPoint>("\"abc\"").is_err());
+    }
+}
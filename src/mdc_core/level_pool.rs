@@ -0,0 +1,160 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use crate::mdc_core::models::{DepthEntry, DepthUpdate};
+
+/// Maximum number of idle buffers kept in the pool. Updates held back by a gap are released in bursts,
+/// so the pool has room for the buffers of a few hundred updates
+const MAX_IDLE_BUFFERS: usize = 256;
+
+/// Buffers with a larger capacity (e.g. of full-depth snapshots) are dropped instead of being kept,
+/// so the idle buffers hold at most 4 MB
+const MAX_BUFFER_CAPACITY: usize = 1024;
+
+/// LevelPool reuses the buffers of the price levels of depth updates and snapshots
+///
+/// The levels are parsed into a buffer taken from the pool, and the book processor gives the buffer back
+/// once the levels are applied, so at high message rates the levels are parsed without allocating.
+/// Duplicate and stale updates dropped by the depth sequencer are recycled as well. Buffers, which are never given back, are simply freed.
+/// `level_pool_hits` and `level_pool_misses` count the buffers taken from the pool and allocated anew,
+/// `level_pool_returned` and `level_pool_discarded` the buffers given back and kept or dropped
+pub struct LevelPool {
+    idle: Mutex<Vec<Vec<DepthEntry>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    returned: AtomicU64,
+    discarded: AtomicU64,
+}
+
+/// The pool shared by all parsers and book processors of the process
+pub static LEVEL_POOL: LevelPool = LevelPool::new();
+
+impl Default for LevelPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LevelPool {
+    pub const fn new() -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            returned: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+
+    /// Take an empty buffer from the pool, or a new one if the pool is empty
+    pub fn take(&self) -> Vec<DepthEntry> {
+        let buffer = self.idle.lock().expect("Level pool lock is poisoned").pop();
+        match buffer {
+            Some(buffer) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Give a buffer back to the pool. It is dropped if the pool is full or the buffer is too large
+    pub fn give(&self, mut buffer: Vec<DepthEntry>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+
+        if buffer.capacity() <= MAX_BUFFER_CAPACITY {
+            buffer.clear();
+            let mut idle = self.idle.lock().expect("Level pool lock is poisoned");
+            if idle.len() < MAX_IDLE_BUFFERS {
+                idle.push(buffer);
+                self.returned.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        self.discarded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Give the level buffers of a depth update, which is dropped, back to the pool
+    pub fn recycle(&self, update: DepthUpdate) {
+        self.give(update.bids);
+        self.give(update.asks);
+    }
+
+    /// Counters of the pool: `level_pool_hits`, `level_pool_misses`, `level_pool_returned` and `level_pool_discarded`
+    pub fn counters(&self) -> [(String, u64); 4] {
+        [
+            ("level_pool_hits".to_string(), self.hits.load(Ordering::Relaxed)),
+            ("level_pool_misses".to_string(), self.misses.load(Ordering::Relaxed)),
+            ("level_pool_returned".to_string(), self.returned.load(Ordering::Relaxed)),
+            ("level_pool_discarded".to_string(), self.discarded.load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+/// Deserialize a list of `[price, quantity]` levels into a buffer taken from `LEVEL_POOL`
+pub fn deserialize_levels<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<DepthEntry>, D::Error> {
+    struct LevelsVisitor;
+
+    impl<'de> Visitor<'de> for LevelsVisitor {
+        type Value = Vec<DepthEntry>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a list of [price, quantity] pairs")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<DepthEntry>, A::Error> {
+            let mut levels = LEVEL_POOL.take();
+            levels.reserve(seq.size_hint().unwrap_or(0));
+            while let Some(level) = seq.next_element::<DepthEntry>()? {
+                levels.push(level);
+            }
+            Ok(levels)
+        }
+    }
+
+    deserializer.deserialize_seq(LevelsVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+
+    fn level() -> DepthEntry {
+        DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) }
+    }
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = LevelPool::new();
+        let mut buffer = pool.take();
+        buffer.push(level());
+        let capacity = buffer.capacity();
+        pool.give(buffer);
+
+        let buffer = pool.take();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), capacity);
+
+        // Never allocated buffers aren't kept, too large ones are dropped
+        pool.give(Vec::new());
+        pool.give(Vec::with_capacity(MAX_BUFFER_CAPACITY + 1));
+        assert_eq!(pool.counters().map(|(_, value)| value), [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let pool = LevelPool::new();
+        for _ in 0..MAX_IDLE_BUFFERS + 5 {
+            pool.give(vec![level()]);
+        }
+        assert_eq!(pool.counters().map(|(_, value)| value), [0, 0, MAX_IDLE_BUFFERS as u64, 5]);
+    }
+}
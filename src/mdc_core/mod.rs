@@ -0,0 +1,16 @@
+pub mod fixed_point;
+pub mod models;
+pub mod level_pool;
+pub mod order_book;
+pub mod sequencing;
+pub mod depth_sequencer;
+pub mod continuity;
+pub mod deduplication;
+pub mod checksum;
+pub mod book_metrics;
+pub mod trade_flow;
+pub mod volatility;
+pub mod anomaly;
+pub mod depth_buckets;
+#[cfg(feature = "sbe")]
+pub mod sbe;
@@ -0,0 +1,1735 @@
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::time::Instant;
+use chrono::{TimeZone, Utc};
+use crate::mdc_core::book_metrics::BookMetrics;
+use crate::mdc_core::checksum::BookChecksum;
+use crate::mdc_core::trade_flow::TradeFlow;
+use crate::mdc_core::volatility::VolatilityMetrics;
+use crate::mdc_core::anomaly::AnomalyEvent;
+use crate::mdc_core::continuity::{Resync, SequenceGap};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::level_pool::deserialize_levels;
+#[cfg(feature = "sbe")]
+use crate::mdc_core::sbe::SbeError;
+
+pub trait FromJson: Sized {
+    fn from_json(s: &str) -> Result<Self, serde_json::Error>;
+}
+
+impl<T> FromJson for T where T: de::DeserializeOwned,
+{
+    #[cfg(not(feature = "simd-json"))]
+    fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    /// Parse with simd-json. It parses in place, so the message is copied once, which is still cheaper than
+    /// serde_json for large depth updates
+    #[cfg(feature = "simd-json")]
+    fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        let mut bytes = s.as_bytes().to_vec();
+        simd_json::serde::from_slice(&mut bytes).map_err(de::Error::custom)
+    }
+}
+
+pub fn de_float_from_str<'a, D>(deserializer: D) -> Result<f64, D::Error>
+where D: Deserializer<'a>,
+{
+    let str_val = String::deserialize(deserializer)?;
+    str_val.parse::<f64>().map_err(de::Error::custom)
+}
+
+/// Deserialize a float sent as a string, which is empty if the value is not set
+pub fn de_optional_float_from_str<'a, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where D: Deserializer<'a>,
+{
+    let str_val = String::deserialize(deserializer)?;
+    if str_val.is_empty() {
+        return Ok(None);
+    }
+
+    str_val.parse::<f64>().map(Some).map_err(de::Error::custom)
+}
+
+/// A `[price, quantity]` pair. The decimal strings are parsed in place, so a level is parsed without allocations
+impl<'de> Deserialize<'de> for DepthEntry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DepthEntryVisitor;
+
+        impl<'de> de::Visitor<'de> for DepthEntryVisitor {
+            type Value = DepthEntry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a [price, quantity] pair")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<DepthEntry, A::Error> {
+                let price = seq.next_element::<FixedPoint>()?.ok_or_else(|| de::Error::invalid_length(0, &"2"))?;
+                let quantity = seq.next_element::<FixedPoint>()?.ok_or_else(|| de::Error::invalid_length(1, &"2"))?;
+                if seq.next_element::<de::IgnoredAny>()?.is_some() {
+                    return Err(de::Error::invalid_length(3, &"2"));
+                }
+
+                Ok(DepthEntry { price, quantity })
+            }
+        }
+
+        deserializer.deserialize_seq(DepthEntryVisitor)
+    }
+}
+
+/// Time, at which a message has been received from the exchange
+///
+/// The wall clock time is comparable with the exchange event time (the difference is the feed latency plus the clock
+/// skew), the monotonic time measures the time spent in the pipeline since the receipt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReceiveTime {
+    pub monotonic: Instant,
+    /// Milliseconds since epoch
+    pub wall_clock: i64,
+}
+
+impl ReceiveTime {
+    /// Receive time of a message received just now
+    pub fn now() -> Self {
+        Self::at(Utc::now().timestamp_millis())
+    }
+
+    /// Receive time of a message received at the given wall clock time (e.g. a recorded one)
+    pub fn at(wall_clock: i64) -> Self {
+        Self { monotonic: Instant::now(), wall_clock }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthEntry {
+    pub price: FixedPoint,
+    pub quantity: FixedPoint,
+}
+
+impl fmt::Display for DepthEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Price: '{}', Quantity: '{}'",
+            self.price,
+            self.quantity,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    #[serde(deserialize_with = "deserialize_levels")]
+    pub bids: Vec<DepthEntry>,
+    #[serde(deserialize_with = "deserialize_levels")]
+    pub asks: Vec<DepthEntry>,
+}
+
+impl DepthSnapshot {
+    /// The snapshot in the format of a REST snapshot response, with prices and quantities as decimal strings
+    pub fn to_json(&self) -> String {
+        let levels = |entries: &[DepthEntry]| {
+            entries.iter().map(|entry| [entry.price.to_string(), entry.quantity.to_string()]).collect::<Vec<_>>()
+        };
+
+        serde_json::json!({
+            "lastUpdateId": self.last_update_id,
+            "bids": levels(&self.bids),
+            "asks": levels(&self.asks),
+        }).to_string()
+    }
+}
+
+/// The book carried by a depth update, whose `u` is its id (e.g. a Binance futures partial book)
+impl From<DepthUpdate> for DepthSnapshot {
+    fn from(update: DepthUpdate) -> Self {
+        Self {
+            last_update_id: update.last_update_id,
+            bids: update.bids,
+            asks: update.asks,
+        }
+    }
+}
+
+impl fmt::Display for DepthSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Last update id: '{}'",
+            self.last_update_id,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DepthUpdate {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    #[allow(dead_code)]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    #[allow(dead_code)]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub last_update_id: u64,
+    /// Last update id of the previous event. Only sent by Binance futures
+    #[serde(rename = "pu", default)]
+    pub previous_last_update_id: Option<u64>,
+    #[serde(rename = "b", deserialize_with = "deserialize_levels")]
+    pub bids: Vec<DepthEntry>,
+    #[serde(rename = "a", deserialize_with = "deserialize_levels")]
+    pub asks: Vec<DepthEntry>,
+    /// Checksum of the book after the update, if the venue sends one (OKX)
+    #[serde(skip)]
+    pub checksum: Option<BookChecksum>,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl DepthUpdate {
+    /// The update in the format of a Binance spot depthUpdate frame, with prices and quantities as decimal strings
+    pub fn to_json(&self) -> String {
+        let levels = |levels: &[DepthEntry]| -> Vec<[String; 2]> {
+            levels.iter().map(|level| [level.price.to_string(), level.quantity.to_string()]).collect()
+        };
+        serde_json::json!({
+            "e": self.event_type,
+            "E": self.event_time,
+            "s": self.symbol,
+            "U": self.first_update_id,
+            "u": self.last_update_id,
+            "b": levels(&self.bids),
+            "a": levels(&self.asks),
+        }).to_string()
+    }
+}
+
+impl fmt::Display for DepthUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', First: '{}', Last: '{}', Time: '{}'",
+            self.symbol,
+            self.first_update_id,
+            self.last_update_id,
+            self.event_time,
+        )
+    }
+}
+
+/// A frame of a Binance partial book depth stream (`<symbol>@depth<levels>` or `<symbol>@depth<levels>@100ms`),
+/// captured as it is: the top levels per side, not applied to the maintained book
+///
+/// Spot partial books have the format of REST snapshots, without a symbol and an event time. Futures ones have the
+/// format of depth updates
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartialBookEvent {
+    #[serde(rename = "lastUpdateId", alias = "u")]
+    pub last_update_id: u64,
+    /// Only sent by Binance futures
+    #[serde(rename = "E", default)]
+    pub event_time: Option<u64>,
+    /// Only sent by Binance futures. Set to the canonical symbol by the pipeline sinks
+    #[serde(rename = "s", default)]
+    pub symbol: String,
+    #[serde(alias = "b", deserialize_with = "deserialize_levels")]
+    pub bids: Vec<DepthEntry>,
+    #[serde(alias = "a", deserialize_with = "deserialize_levels")]
+    pub asks: Vec<DepthEntry>,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for PartialBookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let best = |levels: &[DepthEntry]| levels.first().map(|level| level.price.to_string()).unwrap_or_default();
+        write!(
+            f,
+            "Symbol: '{}', Last update id: '{}', Levels: '{}/{}', Best bid: '{}', Best ask: '{}'",
+            self.symbol,
+            self.last_update_id,
+            self.bids.len(),
+            self.asks.len(),
+            best(&self.bids),
+            best(&self.asks),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TradeEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    #[allow(dead_code)]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "t")]
+    pub trade_id: u64, 
+    #[serde(rename = "p", deserialize_with = "de_float_from_str")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "de_float_from_str")]
+    pub quantity: f64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_market_maker: bool,
+    #[serde(rename = "M")]
+    #[allow(dead_code)]
+    pub ignore: bool,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl TradeEvent {
+    /// The trade in the format of a Binance trade frame, with the price and quantity as decimal strings
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "e": self.event_type,
+            "E": self.event_time,
+            "s": self.symbol,
+            "t": self.trade_id,
+            "p": self.price.to_string(),
+            "q": self.quantity.to_string(),
+            "T": self.trade_time,
+            "m": self.is_market_maker,
+            "M": self.ignore,
+        }).to_string()
+    }
+}
+
+impl fmt::Display for TradeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Id: '{}', 'Symbol: '{}', Price: '{}', Quantity: '{}', Time: '{}'",
+            self.trade_id,
+            self.symbol,
+            self.price,
+            self.quantity,
+            Utc.timestamp_millis_opt(self.trade_time as i64)
+                .unwrap()
+                .format("%Y-%m-%d %H:%M:%S%.3f")
+        )
+    }
+}
+
+/// Trades of a single taker order at the same price, aggregated by the exchange
+#[derive(Debug, Deserialize, Clone)]
+pub struct AggTradeEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "de_float_from_str")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "de_float_from_str")]
+    pub quantity: f64,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_market_maker: bool,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for AggTradeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Id: '{}', Trades: '{}'..'{}', Symbol: '{}', Price: '{}', Quantity: '{}', Time: '{}'",
+            self.agg_trade_id,
+            self.first_trade_id,
+            self.last_trade_id,
+            self.symbol,
+            self.price,
+            self.quantity,
+            Utc.timestamp_millis_opt(self.trade_time as i64)
+                .unwrap()
+                .format("%Y-%m-%d %H:%M:%S%.3f")
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PriceUpdate {
+    #[serde(rename = "u")]
+    pub update_id: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "b", deserialize_with = "de_float_from_str")]
+    pub best_bid_price: f64,
+    #[serde(rename = "B", deserialize_with = "de_float_from_str")]
+    pub best_bid_quantity: f64,
+    #[serde(rename = "a", deserialize_with = "de_float_from_str")]
+    pub best_ask_price: f64,
+    #[serde(rename = "A", deserialize_with = "de_float_from_str")]
+    pub best_ask_quantity: f64,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl PriceUpdate {
+    /// The update in the format of a Binance bookTicker frame, with prices and quantities as decimal strings
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "u": self.update_id,
+            "s": self.symbol,
+            "b": self.best_bid_price.to_string(),
+            "B": self.best_bid_quantity.to_string(),
+            "a": self.best_ask_price.to_string(),
+            "A": self.best_ask_quantity.to_string(),
+        }).to_string()
+    }
+}
+
+impl fmt::Display for PriceUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Id: '{}', Symbol: '{}', Best bid - (price: '{}', quantity: '{}'), Best ask - (price: '{}' quantity: '{}')",
+            self.update_id,
+            self.symbol,
+            self.best_bid_price,
+            self.best_bid_quantity,
+            self.best_ask_price,
+            self.best_ask_quantity
+        )
+    }
+}
+
+/// Rolling 24 hour statistics of a symbol, pushed every second
+///
+/// Only the fields, which are sent by both spot and futures, are kept
+#[derive(Debug, Deserialize, Clone)]
+pub struct TickerEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "p", deserialize_with = "de_float_from_str")]
+    pub price_change: f64,
+    #[serde(rename = "P", deserialize_with = "de_float_from_str")]
+    pub price_change_percent: f64,
+    #[serde(rename = "w", deserialize_with = "de_float_from_str")]
+    pub weighted_average_price: f64,
+    #[serde(rename = "c", deserialize_with = "de_float_from_str")]
+    pub last_price: f64,
+    #[serde(rename = "Q", deserialize_with = "de_float_from_str")]
+    pub last_quantity: f64,
+    #[serde(rename = "o", deserialize_with = "de_float_from_str")]
+    pub open: f64,
+    #[serde(rename = "h", deserialize_with = "de_float_from_str")]
+    pub high: f64,
+    #[serde(rename = "l", deserialize_with = "de_float_from_str")]
+    pub low: f64,
+    /// Base asset volume
+    #[serde(rename = "v", deserialize_with = "de_float_from_str")]
+    pub volume: f64,
+    /// Quote asset volume
+    #[serde(rename = "q", deserialize_with = "de_float_from_str")]
+    pub quote_volume: f64,
+    #[serde(rename = "O")]
+    pub open_time: u64,
+    #[serde(rename = "C")]
+    pub close_time: u64,
+    #[serde(rename = "F")]
+    pub first_trade_id: i64,
+    #[serde(rename = "L")]
+    pub last_trade_id: i64,
+    #[serde(rename = "n")]
+    pub trade_count: u64,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for TickerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', Last: '{}', Change: '{}' ('{}'%), High: '{}', Low: '{}', Volume: '{}', Trades: '{}'",
+            self.symbol,
+            self.last_price,
+            self.price_change,
+            self.price_change_percent,
+            self.high,
+            self.low,
+            self.volume,
+            self.trade_count,
+        )
+    }
+}
+
+/// Reduced rolling 24 hour statistics of a symbol, pushed every second
+#[derive(Debug, Deserialize, Clone)]
+pub struct MiniTickerEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "c", deserialize_with = "de_float_from_str")]
+    pub close: f64,
+    #[serde(rename = "o", deserialize_with = "de_float_from_str")]
+    pub open: f64,
+    #[serde(rename = "h", deserialize_with = "de_float_from_str")]
+    pub high: f64,
+    #[serde(rename = "l", deserialize_with = "de_float_from_str")]
+    pub low: f64,
+    /// Base asset volume
+    #[serde(rename = "v", deserialize_with = "de_float_from_str")]
+    pub volume: f64,
+    /// Quote asset volume
+    #[serde(rename = "q", deserialize_with = "de_float_from_str")]
+    pub quote_volume: f64,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for MiniTickerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', Open: '{}', High: '{}', Low: '{}', Close: '{}', Volume: '{}'",
+            self.symbol,
+            self.open,
+            self.high,
+            self.low,
+            self.close,
+            self.volume,
+        )
+    }
+}
+
+/// Mark price and funding rate of a perpetual futures symbol, pushed every second
+#[derive(Debug, Deserialize, Clone)]
+pub struct MarkPriceEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "p", deserialize_with = "de_float_from_str")]
+    pub mark_price: f64,
+    #[serde(rename = "i", deserialize_with = "de_float_from_str")]
+    pub index_price: f64,
+    /// Only meaningful in the last hour before the funding
+    #[serde(rename = "P", deserialize_with = "de_float_from_str")]
+    pub estimated_settle_price: f64,
+    /// Not set for delivery contracts, which have no funding
+    #[serde(rename = "r", deserialize_with = "de_optional_float_from_str")]
+    pub funding_rate: Option<f64>,
+    /// Zero for delivery contracts
+    #[serde(rename = "T")]
+    pub next_funding_time: u64,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for MarkPriceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', Mark: '{}', Index: '{}', Funding Rate: '{:?}', Next Funding: '{}'",
+            self.symbol,
+            self.mark_price,
+            self.index_price,
+            self.funding_rate,
+            self.next_funding_time,
+        )
+    }
+}
+
+/// Liquidation order of a futures symbol. Binance pushes at most one (the largest) liquidation per second
+#[derive(Debug, Deserialize, Clone)]
+pub struct LiquidationOrder {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// "BUY" or "SELL"
+    #[serde(rename = "S")]
+    pub side: String,
+    #[serde(rename = "o")]
+    pub order_type: String,
+    #[serde(rename = "f")]
+    pub time_in_force: String,
+    #[serde(rename = "q", deserialize_with = "de_float_from_str")]
+    pub quantity: f64,
+    #[serde(rename = "p", deserialize_with = "de_float_from_str")]
+    pub price: f64,
+    #[serde(rename = "ap", deserialize_with = "de_float_from_str")]
+    pub average_price: f64,
+    #[serde(rename = "X")]
+    pub status: String,
+    #[serde(rename = "l", deserialize_with = "de_float_from_str")]
+    pub last_filled_quantity: f64,
+    #[serde(rename = "z", deserialize_with = "de_float_from_str")]
+    pub filled_quantity: f64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LiquidationEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "o")]
+    pub order: LiquidationOrder,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for LiquidationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', Side: '{}', Price: '{}', Average Price: '{}', Quantity: '{}', Filled: '{}', Status: '{}', Time: '{}'",
+            self.order.symbol,
+            self.order.side,
+            self.order.price,
+            self.order.average_price,
+            self.order.quantity,
+            self.order.filled_quantity,
+            self.order.status,
+            Utc.timestamp_millis_opt(self.order.trade_time as i64)
+                .unwrap()
+                .format("%Y-%m-%d %H:%M:%S%.3f"),
+        )
+    }
+}
+
+/// Interval of a kline (candlestick), as named in Binance stream names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KlineInterval {
+    #[serde(rename = "1s")]
+    Second1,
+    #[serde(rename = "1m")]
+    Minute1,
+    #[serde(rename = "3m")]
+    Minute3,
+    #[serde(rename = "5m")]
+    Minute5,
+    #[serde(rename = "15m")]
+    Minute15,
+    #[serde(rename = "30m")]
+    Minute30,
+    #[serde(rename = "1h")]
+    Hour1,
+    #[serde(rename = "2h")]
+    Hour2,
+    #[serde(rename = "4h")]
+    Hour4,
+    #[serde(rename = "6h")]
+    Hour6,
+    #[serde(rename = "8h")]
+    Hour8,
+    #[serde(rename = "12h")]
+    Hour12,
+    #[serde(rename = "1d")]
+    Day1,
+    #[serde(rename = "3d")]
+    Day3,
+    #[serde(rename = "1w")]
+    Week1,
+    #[serde(rename = "1M")]
+    Month1,
+}
+
+impl KlineInterval {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KlineInterval::Second1 => "1s",
+            KlineInterval::Minute1 => "1m",
+            KlineInterval::Minute3 => "3m",
+            KlineInterval::Minute5 => "5m",
+            KlineInterval::Minute15 => "15m",
+            KlineInterval::Minute30 => "30m",
+            KlineInterval::Hour1 => "1h",
+            KlineInterval::Hour2 => "2h",
+            KlineInterval::Hour4 => "4h",
+            KlineInterval::Hour6 => "6h",
+            KlineInterval::Hour8 => "8h",
+            KlineInterval::Hour12 => "12h",
+            KlineInterval::Day1 => "1d",
+            KlineInterval::Day3 => "3d",
+            KlineInterval::Week1 => "1w",
+            KlineInterval::Month1 => "1M",
+        }
+    }
+}
+
+impl fmt::Display for KlineInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// OHLCV state of a kline. Binance pushes the current kline of the interval every update, `is_closed` is set
+/// in the last update of the interval
+#[derive(Debug, Deserialize, Clone)]
+pub struct Kline {
+    #[serde(rename = "t")]
+    pub start_time: u64,
+    #[serde(rename = "T")]
+    pub close_time: u64,
+    #[serde(rename = "i")]
+    pub interval: KlineInterval,
+    /// Id of the first trade in the kline, -1 if there were no trades
+    #[serde(rename = "f")]
+    pub first_trade_id: i64,
+    /// Id of the last trade in the kline, -1 if there were no trades
+    #[serde(rename = "L")]
+    pub last_trade_id: i64,
+    #[serde(rename = "o", deserialize_with = "de_float_from_str")]
+    pub open: f64,
+    #[serde(rename = "h", deserialize_with = "de_float_from_str")]
+    pub high: f64,
+    #[serde(rename = "l", deserialize_with = "de_float_from_str")]
+    pub low: f64,
+    #[serde(rename = "c", deserialize_with = "de_float_from_str")]
+    pub close: f64,
+    /// Base asset volume
+    #[serde(rename = "v", deserialize_with = "de_float_from_str")]
+    pub volume: f64,
+    /// Quote asset volume
+    #[serde(rename = "q", deserialize_with = "de_float_from_str")]
+    pub quote_volume: f64,
+    #[serde(rename = "n")]
+    pub trade_count: u64,
+    /// Base asset volume of the trades, where the buyer was the taker
+    #[serde(rename = "V", deserialize_with = "de_float_from_str")]
+    pub taker_buy_volume: f64,
+    /// Quote asset volume of the trades, where the buyer was the taker
+    #[serde(rename = "Q", deserialize_with = "de_float_from_str")]
+    pub taker_buy_quote_volume: f64,
+    #[serde(rename = "x")]
+    pub is_closed: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct KlineEvent {
+    #[serde(rename = "e")]
+    #[allow(dead_code)]
+    pub event_type: String,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "k")]
+    pub kline: Kline,
+    /// Local receive time, stamped by the stream, which has received the event
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+impl fmt::Display for KlineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', Interval: '{}', Start: '{}', Open: '{}', High: '{}', Low: '{}', Close: '{}', Volume: '{}', Trades: '{}', Closed: '{}'",
+            self.symbol,
+            self.kline.interval,
+            Utc.timestamp_millis_opt(self.kline.start_time as i64)
+                .unwrap()
+                .format("%Y-%m-%d %H:%M:%S"),
+            self.kline.open,
+            self.kline.high,
+            self.kline.low,
+            self.kline.close,
+            self.kline.volume,
+            self.kline.trade_count,
+            self.kline.is_closed,
+        )
+    }
+}
+
+/// Top-of-book state derived from the locally maintained order book.
+///
+/// Emitted by the BookProcessor only when the best bid or best ask (price or quantity) changes,
+/// so it is always consistent with the book it was derived from
+#[derive(Debug, Clone, PartialEq)]
+pub struct BboChange {
+    pub update_id: u64,
+    pub best_bid: Option<DepthEntry>,
+    pub best_ask: Option<DepthEntry>,
+}
+
+impl fmt::Display for BboChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_side = |entry: &Option<DepthEntry>| match entry {
+            Some(entry) => format!("(price: '{}', quantity: '{}')", entry.price, entry.quantity),
+            None => "(empty)".to_string(),
+        };
+
+        write!(
+            f,
+            "Id: '{}', Best bid - {}, Best ask - {}",
+            self.update_id,
+            format_side(&self.best_bid),
+            format_side(&self.best_ask),
+        )
+    }
+}
+
+/// Trading rule filter of a symbol, as reported by Binance exchangeInfo
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "filterType")]
+pub enum SymbolFilter {
+    #[serde(rename = "PRICE_FILTER")]
+    PriceFilter {
+        #[serde(rename = "tickSize")]
+        tick_size: FixedPoint,
+    },
+    #[serde(rename = "LOT_SIZE")]
+    LotSize {
+        #[serde(rename = "stepSize")]
+        step_size: FixedPoint,
+        #[serde(rename = "minQty")]
+        min_qty: FixedPoint,
+        #[serde(rename = "maxQty")]
+        max_qty: FixedPoint,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Symbol description, as reported by Binance exchangeInfo
+#[derive(Debug, Deserialize, Clone)]
+pub struct SymbolInfo {
+    pub symbol: String,
+    pub status: String,
+    #[serde(rename = "baseAsset")]
+    pub base_asset: String,
+    #[serde(rename = "quoteAsset")]
+    pub quote_asset: String,
+    pub filters: Vec<SymbolFilter>,
+}
+
+/// Response of the Binance exchangeInfo endpoint. Only the symbols section is of interest
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExchangeInfo {
+    pub symbols: Vec<SymbolInfo>,
+}
+
+/// Normalized symbol metadata, which is needed to interpret captured prices and quantities offline
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SymbolMetadata {
+    pub symbol: String,
+    pub status: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub tick_size: Option<FixedPoint>,
+    pub step_size: Option<FixedPoint>,
+    pub min_qty: Option<FixedPoint>,
+    pub max_qty: Option<FixedPoint>,
+}
+
+impl From<SymbolInfo> for SymbolMetadata {
+    fn from(info: SymbolInfo) -> Self {
+        let mut metadata = SymbolMetadata {
+            symbol: info.symbol,
+            status: info.status,
+            base_asset: info.base_asset,
+            quote_asset: info.quote_asset,
+            tick_size: None,
+            step_size: None,
+            min_qty: None,
+            max_qty: None,
+        };
+
+        for filter in info.filters {
+            match filter {
+                SymbolFilter::PriceFilter { tick_size } => {
+                    metadata.tick_size = Some(tick_size);
+                }
+                SymbolFilter::LotSize { step_size, min_qty, max_qty } => {
+                    metadata.step_size = Some(step_size);
+                    metadata.min_qty = Some(min_qty);
+                    metadata.max_qty = Some(max_qty);
+                }
+                SymbolFilter::Other => {}
+            }
+        }
+
+        metadata
+    }
+}
+
+impl fmt::Display for SymbolMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Symbol: '{}', Status: '{}', Tick size: '{:?}', Step size: '{:?}'",
+            self.symbol,
+            self.status,
+            self.tick_size,
+            self.step_size,
+        )
+    }
+}
+
+/// Fields computed by an event script for an event it has processed (see `scripts`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScriptFields {
+    /// Name of the script
+    pub script: String,
+    /// Kind of the processed event, e.g. `trade`
+    pub kind: String,
+    /// Exchange event time of the processed event in milliseconds since epoch
+    pub event_time: u64,
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl fmt::Display for ScriptFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Script: '{}', Kind: '{}', Event time: '{}', Fields: '{}'",
+            self.script,
+            self.kind,
+            self.event_time,
+            serde_json::Value::Object(self.fields.clone()),
+        )
+    }
+}
+
+/// An enum that can hold any of the market data types
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    DepthSnapshot(DepthSnapshot),
+    DepthUpdate(DepthUpdate),
+    TradeEvent(TradeEvent),
+    PriceUpdate(PriceUpdate),
+    BboChange(BboChange),
+    KlineEvent(KlineEvent),
+    AggTradeEvent(AggTradeEvent),
+    TickerEvent(TickerEvent),
+    MiniTickerEvent(MiniTickerEvent),
+    MarkPriceEvent(MarkPriceEvent),
+    LiquidationEvent(LiquidationEvent),
+    BookMetrics(BookMetrics),
+    TradeFlow(TradeFlow),
+    Volatility(VolatilityMetrics),
+    Anomaly(AnomalyEvent),
+    ScriptFields(ScriptFields),
+    SequenceGap(SequenceGap),
+    Resync(Resync),
+    PartialBook(PartialBookEvent),
+}
+
+impl fmt::Display for MarketEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketEvent::DepthSnapshot(ds) => write!(f, "DepthSnapshot: '{}'", ds),
+            MarketEvent::DepthUpdate(du) => write!(f, "DepthUpdate: '{}'", du),
+            MarketEvent::TradeEvent(te) => write!(f, "TradeEvent: '{}'", te),
+            MarketEvent::PriceUpdate(pu) => write!(f, "PriceUpdate: '{}'", pu),
+            MarketEvent::BboChange(bbo) => write!(f, "BboChange: '{}'", bbo),
+            MarketEvent::KlineEvent(kline) => write!(f, "KlineEvent: '{}'", kline),
+            MarketEvent::AggTradeEvent(trade) => write!(f, "AggTradeEvent: '{}'", trade),
+            MarketEvent::TickerEvent(ticker) => write!(f, "TickerEvent: '{}'", ticker),
+            MarketEvent::MiniTickerEvent(ticker) => write!(f, "MiniTickerEvent: '{}'", ticker),
+            MarketEvent::MarkPriceEvent(price) => write!(f, "MarkPriceEvent: '{}'", price),
+            MarketEvent::LiquidationEvent(liquidation) => write!(f, "LiquidationEvent: '{}'", liquidation),
+            MarketEvent::BookMetrics(metrics) => write!(f, "BookMetrics: '{}'", metrics),
+            MarketEvent::TradeFlow(flow) => write!(f, "TradeFlow: '{}'", flow),
+            MarketEvent::Volatility(metrics) => write!(f, "Volatility: '{}'", metrics),
+            MarketEvent::Anomaly(anomaly) => write!(f, "Anomaly: '{}'", anomaly),
+            MarketEvent::ScriptFields(fields) => write!(f, "ScriptFields: '{}'", fields),
+            MarketEvent::SequenceGap(gap) => write!(f, "SequenceGap: '{}'", gap),
+            MarketEvent::Resync(resync) => write!(f, "Resync: '{}'", resync),
+            MarketEvent::PartialBook(book) => write!(f, "PartialBook: '{}'", book),
+        }
+    }
+}
+
+impl MarketEvent {
+    /// Exchange-side event time in milliseconds since epoch, if the event carries it
+    pub fn event_time(&self) -> Option<u64> {
+        match self {
+            MarketEvent::DepthUpdate(update) => Some(update.event_time),
+            MarketEvent::TradeEvent(trade) => Some(trade.event_time),
+            MarketEvent::KlineEvent(kline) => Some(kline.event_time),
+            MarketEvent::AggTradeEvent(trade) => Some(trade.event_time),
+            MarketEvent::TickerEvent(ticker) => Some(ticker.event_time),
+            MarketEvent::MiniTickerEvent(ticker) => Some(ticker.event_time),
+            MarketEvent::MarkPriceEvent(price) => Some(price.event_time),
+            MarketEvent::LiquidationEvent(liquidation) => Some(liquidation.event_time),
+            MarketEvent::PartialBook(book) => book.event_time,
+            _ => None,
+        }
+    }
+
+    /// Local receive time, if the event has been received from the exchange as it is
+    pub fn received(&self) -> Option<ReceiveTime> {
+        match self {
+            MarketEvent::DepthUpdate(update) => update.received,
+            MarketEvent::TradeEvent(trade) => trade.received,
+            MarketEvent::PriceUpdate(update) => update.received,
+            MarketEvent::KlineEvent(kline) => kline.received,
+            MarketEvent::AggTradeEvent(trade) => trade.received,
+            MarketEvent::TickerEvent(ticker) => ticker.received,
+            MarketEvent::MiniTickerEvent(ticker) => ticker.received,
+            MarketEvent::MarkPriceEvent(price) => price.received,
+            MarketEvent::LiquidationEvent(liquidation) => liquidation.received,
+            MarketEvent::PartialBook(book) => book.received,
+            _ => None,
+        }
+    }
+
+    /// Stamp the event with its local receive time. Snapshots and derived events are not stamped
+    pub fn stamp(&mut self, time: ReceiveTime) {
+        let received = match self {
+            MarketEvent::DepthUpdate(update) => &mut update.received,
+            MarketEvent::TradeEvent(trade) => &mut trade.received,
+            MarketEvent::PriceUpdate(update) => &mut update.received,
+            MarketEvent::KlineEvent(kline) => &mut kline.received,
+            MarketEvent::AggTradeEvent(trade) => &mut trade.received,
+            MarketEvent::TickerEvent(ticker) => &mut ticker.received,
+            MarketEvent::MiniTickerEvent(ticker) => &mut ticker.received,
+            MarketEvent::MarkPriceEvent(price) => &mut price.received,
+            MarketEvent::LiquidationEvent(liquidation) => &mut liquidation.received,
+            MarketEvent::PartialBook(book) => &mut book.received,
+            _ => return,
+        };
+        *received = Some(time);
+    }
+
+    /// Symbol of the instrument, if the event carries it
+    pub fn symbol_mut(&mut self) -> Option<&mut String> {
+        match self {
+            MarketEvent::DepthUpdate(update) => Some(&mut update.symbol),
+            MarketEvent::TradeEvent(trade) => Some(&mut trade.symbol),
+            MarketEvent::PriceUpdate(update) => Some(&mut update.symbol),
+            MarketEvent::KlineEvent(kline) => Some(&mut kline.symbol),
+            MarketEvent::AggTradeEvent(trade) => Some(&mut trade.symbol),
+            MarketEvent::TickerEvent(ticker) => Some(&mut ticker.symbol),
+            MarketEvent::MiniTickerEvent(ticker) => Some(&mut ticker.symbol),
+            MarketEvent::MarkPriceEvent(price) => Some(&mut price.symbol),
+            MarketEvent::LiquidationEvent(liquidation) => Some(&mut liquidation.order.symbol),
+            MarketEvent::PartialBook(book) => Some(&mut book.symbol),
+            _ => None,
+        }
+    }
+
+    /// Local receive time minus exchange event time in milliseconds, i.e. the feed latency plus the clock skew
+    pub fn receive_latency(&self) -> Option<i64> {
+        Some(self.received()?.wall_clock - self.event_time()? as i64)
+    }
+}
+
+/// Trait for types that can be converted to a MarketEvent
+pub trait IntoMarketEvent {
+    fn into_market_event(self) -> MarketEvent;
+}
+
+/// Trait that combines all requirements for a type that can be used as a source for MarketEvent
+pub trait MarketEventSource: FromJson + Send + Sync + std::fmt::Debug + IntoMarketEvent + 'static {}
+
+// Implement MarketEventSource for all types that satisfy the requirements
+impl<T> MarketEventSource for T 
+where 
+    T: FromJson + Send + Sync + std::fmt::Debug + IntoMarketEvent + 'static
+{}
+
+/// Trait for the text frames of a WebSocket stream
+///
+/// A frame carries any number of market events: venues batch several events into a frame (e.g. OKX trades)
+/// or send service frames without events (e.g. subscription acknowledgements)
+pub trait StreamMessage: Send + Sync + 'static {
+    /// Decoding state of a single connection (e.g. the orders of a raw order book). It starts over with each connection
+    type State: Default + Send + 'static;
+
+    fn decode(state: &mut Self::State, message: &str) -> Result<Vec<MarketEvent>, serde_json::Error>;
+
+    /// Decode a binary frame. Only SBE streams send binary frames, other streams ignore them
+    #[cfg(feature = "sbe")]
+    fn decode_binary(_state: &mut Self::State, _message: &[u8]) -> Result<Vec<MarketEvent>, SbeError> {
+        Ok(Vec::new())
+    }
+}
+
+// A frame of a MarketEventSource carries exactly one event
+impl<T> StreamMessage for T
+where
+    T: MarketEventSource
+{
+    type State = ();
+
+    fn decode(_state: &mut (), message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        Ok(vec![T::from_json(message)?.into_market_event()])
+    }
+}
+
+/// Decoder of the frames of a single connection, which owns its decoding state
+pub type FrameDecoder = Box<dyn FnMut(&str) -> Result<Vec<MarketEvent>, serde_json::Error> + Send>;
+
+/// Create a decoder of the frames of a new connection of the stream `T`
+pub fn frame_decoder<T: StreamMessage>() -> FrameDecoder {
+    let mut state = T::State::default();
+    Box::new(move |message| T::decode(&mut state, message))
+}
+
+impl IntoMarketEvent for DepthSnapshot {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::DepthSnapshot(self)
+    }
+}
+
+impl IntoMarketEvent for DepthUpdate {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::DepthUpdate(self)
+    }
+}
+
+impl IntoMarketEvent for TradeEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::TradeEvent(self)
+    }
+}
+
+impl IntoMarketEvent for PriceUpdate {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::PriceUpdate(self)
+    }
+}
+
+impl IntoMarketEvent for AggTradeEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::AggTradeEvent(self)
+    }
+}
+
+impl IntoMarketEvent for TickerEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::TickerEvent(self)
+    }
+}
+
+impl IntoMarketEvent for MiniTickerEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::MiniTickerEvent(self)
+    }
+}
+
+impl IntoMarketEvent for MarkPriceEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::MarkPriceEvent(self)
+    }
+}
+
+impl IntoMarketEvent for LiquidationEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::LiquidationEvent(self)
+    }
+}
+
+impl IntoMarketEvent for PartialBookEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::PartialBook(self)
+    }
+}
+
+impl IntoMarketEvent for KlineEvent {
+    fn into_market_event(self) -> MarketEvent {
+        MarketEvent::KlineEvent(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_depth_entry_parsing() {
+        let json_data = r#"
+        [
+            "123.45",
+            "67.89"
+        ]
+        "#;
+        
+        let parsed : DepthEntry = DepthEntry::from_json(json_data).unwrap();
+        assert_eq!(parsed.price, FixedPoint::from(123.45));
+        assert_eq!(parsed.quantity, FixedPoint::from(67.89));
+
+        assert!(DepthEntry::from_json(r#"["123.45"]"#).is_err());
+        assert!(DepthEntry::from_json(r#"["123.45", "67.89", "1"]"#).is_err());
+        assert!(DepthEntry::from_json(r#"["123.45", "abc"]"#).is_err());
+    }
+
+    #[test]
+    fn test_depth_snapshot_parsing() {
+        let json_data = r#"
+        {
+            "lastUpdateId": 123456,
+            "bids": [
+                [ "123.45", "10.5" ],
+                [ "122.99", "8.0"  ]
+            ],
+            "asks": [
+                [ "124.45", "2.2" ]
+            ]
+        }
+        "#;
+
+        let parsed: DepthSnapshot = DepthSnapshot::from_json(json_data).unwrap();
+        assert_eq!(parsed.last_update_id, 123456);
+
+        assert_eq!(parsed.bids.len(), 2);
+        assert_eq!(parsed.bids[0].price, FixedPoint::from(123.45));
+        assert_eq!(parsed.bids[0].quantity, FixedPoint::from(10.5));
+        assert_eq!(parsed.bids[1].price, FixedPoint::from(122.99));
+        assert_eq!(parsed.bids[1].quantity, FixedPoint::from(8.0));
+
+        assert_eq!(parsed.asks.len(), 1);
+        assert_eq!(parsed.asks[0].price, FixedPoint::from(124.45));
+        assert_eq!(parsed.asks[0].quantity, FixedPoint::from(2.2));
+    }
+
+    #[test]
+    fn test_depth_update_parsing() {
+        let json_data = r#"
+        {
+            "e": "depthUpdate",
+            "E": 1672515782136,
+            "s": "BNBBTC",
+            "U": 157,
+            "u": 160,
+            "b": [
+                [
+                  "0.0024",
+                  "10"
+                ]
+              ],
+              "a": [
+                [
+                  "0.0026",
+                  "100"
+                ]
+              ]
+        }
+        "#;
+
+        let parsed: DepthUpdate = DepthUpdate::from_json(json_data).unwrap();
+        assert_eq!(parsed.event_type, "depthUpdate");
+        assert_eq!(parsed.event_time, 1672515782136);
+        assert_eq!(parsed.symbol, "BNBBTC");
+        assert_eq!(parsed.first_update_id, 157);
+        assert_eq!(parsed.last_update_id, 160);
+        assert_eq!(parsed.previous_last_update_id, None);
+        assert_eq!(parsed.bids[0].price, FixedPoint::from(0.0024));
+        assert_eq!(parsed.bids[0].quantity, FixedPoint::from(10.0));
+        assert_eq!(parsed.asks[0].price, FixedPoint::from(0.0026));
+        assert_eq!(parsed.asks[0].quantity, FixedPoint::from(100.0));
+    }
+    
+    #[test]
+    fn test_futures_depth_update_parsing() {
+        let json_data = r#"
+        {
+            "e": "depthUpdate",
+            "E": 123456789,
+            "T": 123456788,
+            "s": "BTCUSDT",
+            "U": 157,
+            "u": 160,
+            "pu": 149,
+            "b": [ [ "0.0024", "10" ] ],
+            "a": [ [ "0.0026", "100" ] ]
+        }
+        "#;
+
+        let parsed: DepthUpdate = DepthUpdate::from_json(json_data).unwrap();
+        assert_eq!(parsed.first_update_id, 157);
+        assert_eq!(parsed.last_update_id, 160);
+        assert_eq!(parsed.previous_last_update_id, Some(149));
+    }
+
+    #[test]
+    fn test_trade_event_parsing() {
+        let json_data = r#"
+        {
+            "e": "trade",
+            "E": 1675858459000,
+            "s": "BTCUSDT",
+            "t": 10003456,
+            "p": "23456.78",
+            "q": "0.00123",
+            "T": 1675858460001,
+            "m": true,
+            "M": false
+        }
+        "#;
+
+        let parsed: TradeEvent = TradeEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.event_type, "trade");
+        assert_eq!(parsed.event_time, 1675858459000);
+        assert_eq!(parsed.symbol, "BTCUSDT");
+        assert_eq!(parsed.trade_id, 10003456);
+        assert_eq!(parsed.price, 23456.78);
+        assert_eq!(parsed.quantity, 0.00123);
+        assert_eq!(parsed.trade_time, 1675858460001);
+        assert!(parsed.is_market_maker);
+        assert!(!parsed.ignore);
+    }
+
+    #[test]
+    fn test_agg_trade_event_parsing() {
+        let json_data = r#"
+        {
+            "e": "aggTrade",
+            "E": 1672515782136,
+            "s": "BNBBTC",
+            "a": 12345,
+            "p": "0.001",
+            "q": "100",
+            "f": 100,
+            "l": 105,
+            "T": 1672515782136,
+            "m": true,
+            "M": true
+        }
+        "#;
+
+        let parsed: AggTradeEvent = AggTradeEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.agg_trade_id, 12345);
+        assert_eq!(parsed.symbol, "BNBBTC");
+        assert_eq!(parsed.price, 0.001);
+        assert_eq!(parsed.quantity, 100.0);
+        assert_eq!(parsed.first_trade_id, 100);
+        assert_eq!(parsed.last_trade_id, 105);
+        assert_eq!(parsed.trade_time, 1672515782136);
+        assert!(parsed.is_market_maker);
+    }
+
+    #[test]
+    fn test_price_update_parsing() {
+        let json_data = r#"
+        {
+            "u": 555555,
+            "s": "ETHBTC",
+            "b": "0.06789",
+            "B": "120",
+            "a": "0.06795",
+            "A": "98.5"
+        }
+        "#;
+
+        let parsed: PriceUpdate = PriceUpdate::from_json(json_data).unwrap();
+        assert_eq!(parsed.update_id, 555555);
+        assert_eq!(parsed.symbol, "ETHBTC");
+        assert_eq!(parsed.best_bid_price, 0.06789);
+        assert_eq!(parsed.best_bid_quantity, 120.0);
+        assert_eq!(parsed.best_ask_price, 0.06795);
+        assert_eq!(parsed.best_ask_quantity, 98.5);
+
+        // Routed updates of the all-market stream are recorded as bookTicker frames
+        let recorded = PriceUpdate::from_json(&parsed.to_json()).unwrap();
+        assert_eq!((recorded.update_id, recorded.symbol.as_str()), (555555, "ETHBTC"));
+        assert_eq!((recorded.best_bid_price, recorded.best_ask_quantity), (0.06789, 98.5));
+    }
+
+    #[test]
+    fn test_kline_event_parsing() {
+        let json_data = r#"
+        {
+            "e": "kline",
+            "E": 1672515782136,
+            "s": "BNBBTC",
+            "k": {
+                "t": 1672515780000,
+                "T": 1672515839999,
+                "s": "BNBBTC",
+                "i": "1m",
+                "f": 100,
+                "L": 200,
+                "o": "0.0010",
+                "c": "0.0020",
+                "h": "0.0025",
+                "l": "0.0015",
+                "v": "1000",
+                "n": 100,
+                "x": false,
+                "q": "1.0000",
+                "V": "500",
+                "Q": "0.500",
+                "B": "123456"
+            }
+        }
+        "#;
+
+        let parsed: KlineEvent = KlineEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.event_time, 1672515782136);
+        assert_eq!(parsed.symbol, "BNBBTC");
+        assert_eq!(parsed.kline.start_time, 1672515780000);
+        assert_eq!(parsed.kline.close_time, 1672515839999);
+        assert_eq!(parsed.kline.interval, KlineInterval::Minute1);
+        assert_eq!(parsed.kline.first_trade_id, 100);
+        assert_eq!(parsed.kline.last_trade_id, 200);
+        assert_eq!(parsed.kline.open, 0.001);
+        assert_eq!(parsed.kline.high, 0.0025);
+        assert_eq!(parsed.kline.low, 0.0015);
+        assert_eq!(parsed.kline.close, 0.002);
+        assert_eq!(parsed.kline.volume, 1000.0);
+        assert_eq!(parsed.kline.quote_volume, 1.0);
+        assert_eq!(parsed.kline.trade_count, 100);
+        assert_eq!(parsed.kline.taker_buy_volume, 500.0);
+        assert_eq!(parsed.kline.taker_buy_quote_volume, 0.5);
+        assert!(!parsed.kline.is_closed);
+
+        let interval: KlineInterval = serde_yaml::from_str("1M").unwrap();
+        assert_eq!(interval, KlineInterval::Month1);
+        assert_eq!(interval.to_string(), "1M");
+        assert!(serde_yaml::from_str::<KlineInterval>("2m").is_err());
+    }
+
+    #[test]
+    fn test_ticker_event_parsing() {
+        // Futures tickers lack the best bid/ask fields, which spot tickers send
+        let json_data = r#"
+        {
+            "e": "24hrTicker",
+            "E": 123456789,
+            "s": "BTCUSDT",
+            "p": "0.0015",
+            "P": "250.00",
+            "w": "0.0018",
+            "c": "0.0025",
+            "Q": "10",
+            "o": "0.0010",
+            "h": "0.0025",
+            "l": "0.0010",
+            "v": "10000",
+            "q": "18",
+            "O": 0,
+            "C": 86400000,
+            "F": 0,
+            "L": 18150,
+            "n": 18151
+        }
+        "#;
+
+        let parsed: TickerEvent = TickerEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.event_time, 123456789);
+        assert_eq!(parsed.price_change_percent, 250.0);
+        assert_eq!(parsed.weighted_average_price, 0.0018);
+        assert_eq!(parsed.last_price, 0.0025);
+        assert_eq!(parsed.last_quantity, 10.0);
+        assert_eq!(parsed.quote_volume, 18.0);
+        assert_eq!(parsed.close_time, 86400000);
+        assert_eq!(parsed.last_trade_id, 18150);
+        assert_eq!(parsed.trade_count, 18151);
+
+        let json_data = r#"
+        {
+            "e": "24hrMiniTicker",
+            "E": 123456789,
+            "s": "BNBBTC",
+            "c": "0.0025",
+            "o": "0.0010",
+            "h": "0.0025",
+            "l": "0.0010",
+            "v": "10000",
+            "q": "18"
+        }
+        "#;
+
+        let parsed: MiniTickerEvent = MiniTickerEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.symbol, "BNBBTC");
+        assert_eq!(parsed.close, 0.0025);
+        assert_eq!(parsed.open, 0.001);
+        assert_eq!(parsed.volume, 10000.0);
+    }
+
+    #[test]
+    fn test_mark_price_event_parsing() {
+        let json_data = r#"
+        {
+            "e": "markPriceUpdate",
+            "E": 1562305380000,
+            "s": "BTCUSDT",
+            "p": "11794.15000000",
+            "i": "11784.62659091",
+            "P": "11784.25641265",
+            "r": "0.00038167",
+            "T": 1562306400000
+        }
+        "#;
+
+        let parsed: MarkPriceEvent = MarkPriceEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.event_time, 1562305380000);
+        assert_eq!(parsed.mark_price, 11794.15);
+        assert_eq!(parsed.index_price, 11784.62659091);
+        assert_eq!(parsed.estimated_settle_price, 11784.25641265);
+        assert_eq!(parsed.funding_rate, Some(0.00038167));
+        assert_eq!(parsed.next_funding_time, 1562306400000);
+
+        // Delivery contracts have no funding
+        let json_data = r#"{"e":"markPriceUpdate","E":1,"s":"BTCUSDT_250926","p":"1.0","i":"1.0","P":"1.0","r":"","T":0}"#;
+        let parsed: MarkPriceEvent = MarkPriceEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.funding_rate, None);
+    }
+
+    #[test]
+    fn test_liquidation_event_parsing() {
+        let json_data = r#"
+        {
+            "e": "forceOrder",
+            "E": 1568014460893,
+            "o": {
+                "s": "BTCUSDT",
+                "S": "SELL",
+                "o": "LIMIT",
+                "f": "IOC",
+                "q": "0.014",
+                "p": "9910",
+                "ap": "9910",
+                "X": "FILLED",
+                "l": "0.014",
+                "z": "0.014",
+                "T": 1568014460893
+            }
+        }
+        "#;
+
+        let parsed: LiquidationEvent = LiquidationEvent::from_json(json_data).unwrap();
+        assert_eq!(parsed.event_time, 1568014460893);
+        assert_eq!(parsed.order.symbol, "BTCUSDT");
+        assert_eq!(parsed.order.side, "SELL");
+        assert_eq!(parsed.order.order_type, "LIMIT");
+        assert_eq!(parsed.order.time_in_force, "IOC");
+        assert_eq!(parsed.order.quantity, 0.014);
+        assert_eq!(parsed.order.price, 9910.0);
+        assert_eq!(parsed.order.average_price, 9910.0);
+        assert_eq!(parsed.order.status, "FILLED");
+        assert_eq!(parsed.order.last_filled_quantity, 0.014);
+        assert_eq!(parsed.order.filled_quantity, 0.014);
+        assert_eq!(parsed.order.trade_time, 1568014460893);
+    }
+
+    #[test]
+    fn test_partial_book_event_parsing() {
+        let spot = PartialBookEvent::from_json(r#"{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[["0.0026","100"]]}"#).unwrap();
+        assert_eq!((spot.last_update_id, spot.event_time, spot.symbol.as_str()), (160, None, ""));
+        assert_eq!((spot.bids[0].price, spot.asks[0].quantity), (0.0024.into(), 100.0.into()));
+
+        let futures = PartialBookEvent::from_json(r#"{"e":"depthUpdate","E":1571889248277,"T":1571889248276,"s":"BTCUSDT","U":390497796,"u":390497878,"pu":390497794,"b":[["7403.89","0.002"]],"a":[["7405.96","3.340"],["7406.63","4.525"]]}"#).unwrap();
+        assert_eq!((futures.last_update_id, futures.event_time, futures.symbol.as_str()), (390497878, Some(1571889248277), "BTCUSDT"));
+        assert_eq!((futures.bids.len(), futures.asks.len()), (1, 2));
+    }
+
+    #[test]
+    fn test_exchange_info_parsing() {
+        let json_data = r#"
+        {
+            "timezone": "UTC",
+            "serverTime": 1565246363776,
+            "symbols": [
+                {
+                    "symbol": "BTCUSDT",
+                    "status": "TRADING",
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USDT",
+                    "filters": [
+                        { "filterType": "PRICE_FILTER", "minPrice": "0.01", "maxPrice": "1000000.00", "tickSize": "0.01" },
+                        { "filterType": "LOT_SIZE", "minQty": "0.00001", "maxQty": "9000.00", "stepSize": "0.00001" },
+                        { "filterType": "ICEBERG_PARTS", "limit": 10 }
+                    ]
+                }
+            ]
+        }
+        "#;
+
+        let parsed: ExchangeInfo = ExchangeInfo::from_json(json_data).unwrap();
+        assert_eq!(parsed.symbols.len(), 1);
+
+        let metadata = SymbolMetadata::from(parsed.symbols[0].clone());
+        assert_eq!(metadata.symbol, "BTCUSDT");
+        assert_eq!(metadata.status, "TRADING");
+        assert_eq!(metadata.base_asset, "BTC");
+        assert_eq!(metadata.quote_asset, "USDT");
+        assert_eq!(metadata.tick_size, Some(FixedPoint::from(0.01)));
+        assert_eq!(metadata.step_size, Some(FixedPoint::from(0.00001)));
+        assert_eq!(metadata.min_qty, Some(FixedPoint::from(0.00001)));
+        assert_eq!(metadata.max_qty, Some(FixedPoint::from(9000.0)));
+    }
+
+    #[test]
+    fn test_market_event_enum() {
+        // Create instances of each type
+        let depth_snapshot = DepthSnapshot {
+            last_update_id: 123456,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) }],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) }],
+        };
+
+        let depth_update = DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 157,
+            last_update_id: 160,
+            previous_last_update_id: None,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) }],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) }],
+            checksum: None,
+            received: None,
+        };
+
+        let trade_event = TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1675858459000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 10003456,
+            price: 23456.78,
+            quantity: 0.00123,
+            trade_time: 1675858460001,
+            is_market_maker: true,
+            ignore: false,
+            received: None,
+        };
+
+        let price_update = PriceUpdate {
+            update_id: 555555,
+            symbol: "ETHBTC".to_string(),
+            best_bid_price: 0.06789,
+            best_bid_quantity: 120.0,
+            best_ask_price: 0.06795,
+            best_ask_quantity: 98.5,
+            received: None,
+        };
+
+        // Convert to MarketEvent using IntoMarketEvent trait
+        let market_event1 = depth_snapshot.into_market_event();
+        let market_event2 = depth_update.into_market_event();
+        let market_event3 = trade_event.into_market_event();
+        let market_event4 = price_update.into_market_event();
+
+        // Check that they match the expected variants
+        match market_event1 {
+            MarketEvent::DepthSnapshot(_) => (),
+            _ => panic!("Expected DepthSnapshot variant"),
+        }
+
+        match market_event2 {
+            MarketEvent::DepthUpdate(_) => (),
+            _ => panic!("Expected DepthUpdate variant"),
+        }
+
+        match market_event3 {
+            MarketEvent::TradeEvent(_) => (),
+            _ => panic!("Expected TradeEvent variant"),
+        }
+
+        match market_event4 {
+            MarketEvent::PriceUpdate(_) => (),
+            _ => panic!("Expected PriceUpdate variant"),
+        }
+    }
+
+    #[test]
+    fn test_market_event_source_trait() {
+        // This test verifies that our types implement MarketEventSource
+        fn assert_market_event_source<T: MarketEventSource>() {}
+        
+        // These should compile if the types implement MarketEventSource
+        assert_market_event_source::<DepthSnapshot>();
+        assert_market_event_source::<DepthUpdate>();
+        assert_market_event_source::<TradeEvent>();
+        assert_market_event_source::<PriceUpdate>();
+    }
+
+    #[test]
+    fn test_mpsc_queue_with_market_events() {
+        // Create a channel
+        let (tx, rx) = mpsc::channel::<MarketEvent>();
+
+        // Create instances of each type
+        let depth_snapshot = DepthSnapshot {
+            last_update_id: 123456,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) }],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) }],
+        };
+
+        let trade_event = TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1675858459000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 10003456,
+            price: 23456.78,
+            quantity: 0.00123,
+            trade_time: 1675858460001,
+            is_market_maker: true,
+            ignore: false,
+            received: None,
+        };
+
+        // Send events to the channel
+        tx.send(depth_snapshot.into_market_event()).unwrap();
+        tx.send(trade_event.into_market_event()).unwrap();
+
+        // Receive and process events
+        let mut depth_snapshot_count = 0;
+        let mut trade_event_count = 0;
+
+        for _ in 0..2 {
+            match rx.recv().unwrap() {
+                MarketEvent::DepthSnapshot(snapshot) => {
+                    depth_snapshot_count += 1;
+                    assert_eq!(snapshot.last_update_id, 123456);
+                },
+                MarketEvent::TradeEvent(trade) => {
+                    trade_event_count += 1;
+                    assert_eq!(trade.trade_id, 10003456);
+                },
+                _ => panic!("Unexpected event type"),
+            }
+        }
+
+        assert_eq!(depth_snapshot_count, 1);
+        assert_eq!(trade_event_count, 1);
+    }
+}
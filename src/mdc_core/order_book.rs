@@ -0,0 +1,880 @@
+use std::cmp::Ordering;
+use std::mem;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+/// Represents a price level in the order book, distinguishing between bid and ask prices.
+/// Prices are fixed point, so the same price level is always found under the same key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceKey {
+    Bid(FixedPoint),
+    Ask(FixedPoint),
+}
+
+impl PriceKey {
+    /// Returns the underlying price value regardless of whether it's a bid or ask.
+    pub fn price(&self) -> FixedPoint {
+        match self {
+            PriceKey::Bid(price) => *price,
+            PriceKey::Ask(price) => *price,
+        }
+    }
+}
+
+/// Side of the order book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Quantity and notional of the levels of a side within a distance of the mid price.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Liquidity {
+    /// Distance from the mid price in basis points
+    pub bps: f64,
+    /// Number of levels within the distance
+    pub levels: usize,
+    pub quantity: f64,
+    pub notional: f64,
+}
+
+/// Estimated execution of a market order, which takes liquidity from one side of the book.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionEstimate {
+    pub quantity: f64,
+    /// The quantity available on the side, up to the requested one
+    pub filled_quantity: f64,
+    pub notional: f64,
+    /// Volume-weighted price of the filled quantity
+    pub average_price: f64,
+    /// Price of the last level the order reaches
+    pub worst_price: f64,
+    /// Distance of the average price from the mid price in basis points. Positive values are a cost
+    pub slippage_bps: f64,
+}
+
+/// Implements custom ordering logic for `PriceKey` values:
+/// - Bids are sorted in descending order (highest price first)
+/// - Asks are sorted in ascending order (lowest price first)
+/// - Comparing a bid with an ask (or vice versa) returns `None`
+#[allow(clippy::non_canonical_partial_ord_impl)]
+impl PartialOrd for PriceKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (PriceKey::Bid(a), PriceKey::Bid(b)) => b.partial_cmp(a),
+            (PriceKey::Ask(a), PriceKey::Ask(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl Eq for PriceKey {}
+
+/// Extends the `PartialOrd` implementation to provide a total ordering for `PriceKey`.
+impl Ord for PriceKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Number of the best levels, which are scanned linearly before binary searching the others
+const TOP_SCAN_LEVELS: usize = 16;
+
+/// The levels of one side of the book in a Vec sorted from the worst to the best price.
+///
+/// Most updates change levels near the top of the book, which are at the end of the Vec, so inserting or removing them
+/// moves few elements, and reading the top levels walks contiguous memory. Levels are found by binary search over
+/// the integer prices.
+///
+/// A side may be limited to its best levels or to a price range. Once a level is pruned, nothing is known about the prices at and beyond it,
+/// so updates of those prices are ignored until the side is rebuilt from a snapshot. The retained levels stay exact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookSide {
+    side: Side,
+    /// Prices and quantities, worst first
+    levels: Vec<(FixedPoint, FixedPoint)>,
+    /// Maximum number of retained levels, unlimited if `None`
+    max_depth: Option<usize>,
+    /// The best pruned price, if any level has been pruned
+    pruned_from: Option<FixedPoint>,
+}
+
+impl BookSide {
+    /// Creates an empty side.
+    pub fn new(side: Side) -> Self {
+        BookSide { side, levels: Vec::new(), max_depth: None, pruned_from: None }
+    }
+
+    /// Creates a side from levels in any order. A later level with the same price replaces an earlier one.
+    pub fn from_levels(side: Side, entries: &[DepthEntry]) -> Self {
+        let mut levels: Vec<_> = entries.iter().map(|entry| (entry.price, entry.quantity)).collect();
+        levels.sort_by(|(a, _), (b, _)| Self::compare(side, *a, *b));
+        levels.dedup_by(|later, earlier| {
+            let duplicate = later.0 == earlier.0;
+            if duplicate {
+                earlier.1 = later.1;
+            }
+            duplicate
+        });
+
+        BookSide { side, levels, max_depth: None, pruned_from: None }
+    }
+
+    /// Returns an unlimited copy of the `depth` best levels.
+    pub fn top(&self, depth: usize) -> Self {
+        let worst = self.levels.len().saturating_sub(depth);
+        BookSide { side: self.side, levels: self.levels[worst..].to_vec(), max_depth: None, pruned_from: None }
+    }
+
+    /// Limits the side to the `max_depth` best levels, pruning the others now and whenever better levels arrive.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self.prune();
+        self.levels.shrink_to_fit();
+        self
+    }
+
+    /// Returns whether the price is at or beyond the best pruned price, i.e. its level is not maintained.
+    pub fn is_pruned(&self, price: FixedPoint) -> bool {
+        self.pruned_from.is_some_and(|pruned_from| Self::compare(self.side, price, pruned_from) != Ordering::Greater)
+    }
+
+    /// Removes the worst levels above the maximum depth.
+    fn prune(&mut self) {
+        let excess = self.max_depth.map_or(0, |max_depth| self.levels.len().saturating_sub(max_depth));
+        self.prune_worst(excess);
+    }
+
+    /// Removes the levels worse than the price.
+    pub fn prune_beyond(&mut self, price: FixedPoint) {
+        let beyond = self.levels.partition_point(|(level, _)| Self::compare(self.side, *level, price) == Ordering::Less);
+        self.prune_worst(beyond);
+    }
+
+    /// Removes the `count` worst levels. They are better than any level pruned before.
+    fn prune_worst(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        self.pruned_from = Some(self.levels[count - 1].0);
+        self.levels.drain(..count);
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// Orders prices from the worst to the best.
+    fn compare(side: Side, a: FixedPoint, b: FixedPoint) -> Ordering {
+        match side {
+            Side::Bid => a.cmp(&b),
+            Side::Ask => b.cmp(&a),
+        }
+    }
+
+    /// Finds the position of the price: the levels near the top are scanned first, the rest is binary searched.
+    fn search(&self, price: FixedPoint) -> Result<usize, usize> {
+        let top = self.levels.len().saturating_sub(TOP_SCAN_LEVELS);
+        for (index, (level, _)) in self.levels.iter().enumerate().skip(top).rev() {
+            match Self::compare(self.side, *level, price) {
+                Ordering::Equal => return Ok(index),
+                Ordering::Less => return Err(index + 1),
+                Ordering::Greater => {}
+            }
+        }
+
+        self.levels[..top].binary_search_by(|(level, _)| Self::compare(self.side, *level, price))
+    }
+
+    /// Returns the quantity at the price, if the level exists.
+    pub fn get(&self, price: FixedPoint) -> Option<FixedPoint> {
+        self.search(price).ok().map(|index| self.levels[index].1)
+    }
+
+    pub fn contains(&self, price: FixedPoint) -> bool {
+        self.search(price).is_ok()
+    }
+
+    /// Sets the quantity at the price. Pruned prices are ignored.
+    ///
+    /// # Returns
+    /// The quantity before the update, if the level existed
+    pub fn insert(&mut self, price: FixedPoint, quantity: FixedPoint) -> Option<FixedPoint> {
+        if self.is_pruned(price) {
+            return None;
+        }
+
+        match self.search(price) {
+            Ok(index) => Some(mem::replace(&mut self.levels[index].1, quantity)),
+            Err(index) => {
+                self.levels.insert(index, (price, quantity));
+                self.prune();
+                None
+            }
+        }
+    }
+
+    /// Removes the level at the price.
+    ///
+    /// # Returns
+    /// The quantity of the removed level, if it existed
+    pub fn remove(&mut self, price: FixedPoint) -> Option<FixedPoint> {
+        self.search(price).ok().map(|index| self.levels.remove(index).1)
+    }
+
+    /// Returns the levels, best first.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = DepthEntry> + DoubleEndedIterator + '_ {
+        self.levels.iter().rev().map(|(price, quantity)| DepthEntry { price: *price, quantity: *quantity })
+    }
+
+    /// Returns the best level, if the side is not empty.
+    pub fn best(&self) -> Option<DepthEntry> {
+        self.levels.last().map(|(price, quantity)| DepthEntry { price: *price, quantity: *quantity })
+    }
+}
+
+/// A data structure that maintains the state of an order book, tracking bid and ask orders at various price levels.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub bids: BookSide,
+    pub asks: BookSide,
+}
+
+impl Default for OrderBook {
+    /// An empty book
+    fn default() -> Self {
+        OrderBook { bids: BookSide::new(Side::Bid), asks: BookSide::new(Side::Ask) }
+    }
+}
+
+/// Implements the `Display` trait for `OrderBook` to provide a human-readable representation.
+impl fmt::Display for OrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_top(usize::MAX))
+    }
+}
+
+impl OrderBook {
+    /// Returns the human-readable representation of the book limited to `depth` best levels per side.
+    ///
+    /// The mid price, the spread and the volumes are still computed over the whole book.
+    pub fn format_top(&self, depth: usize) -> String {
+        let format_price = |price: Option<f64>| price.map_or("-".to_string(), |price| price.to_string());
+        let mut formatted_string = String::from("BOOK:\n");
+
+        formatted_string.push_str(&format!(
+            "  Mid: '{}', Spread: '{}', Bid volume: '{}', Ask volume: '{}'\n",
+            format_price(self.mid_price()),
+            format_price(self.spread()),
+            self.total_volume(Side::Bid),
+            self.total_volume(Side::Ask)
+        ));
+
+        formatted_string.push_str("BIDS:\n");
+        for level in self.bids.iter().take(depth) {
+            formatted_string.push_str(&format!("  Price: '{}', Quantity: '{}'\n", level.price, level.quantity));
+        }
+
+        formatted_string.push_str("------------------------------------\n");
+
+        formatted_string.push_str("ASKS:\n");
+        for level in self.asks.iter().take(depth) {
+            formatted_string.push_str(&format!("  Price: '{}', Quantity: '{}'\n", level.price, level.quantity));
+        }
+
+        formatted_string
+    }
+
+    /// Creates a new `OrderBook` from a depth snapshot.
+    ///
+    /// # Arguments
+    /// * `snapshot` - A reference to a `DepthSnapshot` containing initial bids and asks
+    ///
+    /// # Returns
+    /// A new `OrderBook` instance populated with the bids and asks from the snapshot
+    pub fn new(snapshot: &DepthSnapshot) -> Self {
+        OrderBook {
+            bids: BookSide::from_levels(Side::Bid, &snapshot.bids),
+            asks: BookSide::from_levels(Side::Ask, &snapshot.asks),
+        }
+    }
+
+    /// Returns an owned copy of the `depth` best levels per side.
+    ///
+    /// A consumer, which keeps the latest book, keeps such a copy instead of the shared book, so the next update
+    /// doesn't have to copy the whole book (see `BookProcessor`).
+    pub fn top(&self, depth: usize) -> Self {
+        OrderBook { bids: self.bids.top(depth), asks: self.asks.top(depth) }
+    }
+
+    /// Limits each side to the `max_depth` best levels (see `BookSide::with_max_depth`).
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        OrderBook { bids: self.bids.with_max_depth(max_depth), asks: self.asks.with_max_depth(max_depth) }
+    }
+
+    /// Removes the levels further than `percent` from the mid price, if both sides are not empty (see `BookSide`).
+    pub fn prune_outside_band(&mut self, percent: f64) {
+        let Some(mid_price) = self.mid_price() else {
+            return;
+        };
+
+        self.bids.prune_beyond(FixedPoint::from_f64(mid_price * (1.0 - percent / 100.0)));
+        self.asks.prune_beyond(FixedPoint::from_f64(mid_price * (1.0 + percent / 100.0)));
+    }
+
+    /// Returns whether the level at the price is not maintained, because its side has been pruned at or before it.
+    pub fn is_pruned(&self, price_key: PriceKey) -> bool {
+        match price_key {
+            PriceKey::Bid(price) => self.bids.is_pruned(price),
+            PriceKey::Ask(price) => self.asks.is_pruned(price),
+        }
+    }
+
+    /// Apply an update to the order book
+    /// 
+    /// # Arguments
+    /// * `price_key` - The price key (Bid or Ask) with the price level to update
+    /// * `quantity` - The new quantity at this price level
+    /// 
+    /// # Behavior
+    /// * If quantity = 0, the price level will be removed
+    /// * If the price level doesn't exist, it will be created
+    /// * If the price level exists, it will be updated
+    ///
+    /// # Returns
+    /// The quantity at this price level before the update, if the level existed
+    pub fn apply_update(&mut self, price_key: PriceKey, quantity: FixedPoint) -> Option<FixedPoint> {
+        let (book, price) = match price_key {
+            PriceKey::Bid(price) => (&mut self.bids, price),
+            PriceKey::Ask(price) => (&mut self.asks, price),
+        };
+
+        if quantity.is_zero() {
+            return book.remove(price);
+        }
+
+        book.insert(price, quantity)
+    }
+
+    /// Returns the best (highest) bid level, if the bid side is not empty.
+    pub fn best_bid(&self) -> Option<DepthEntry> {
+        self.bids.best()
+    }
+
+    /// Returns the best (lowest) ask level, if the ask side is not empty.
+    pub fn best_ask(&self) -> Option<DepthEntry> {
+        self.asks.best()
+    }
+
+    /// Returns whether the best bid is at or above the best ask. A consistent book is never crossed.
+    pub fn is_crossed(&self) -> bool {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => bid.price >= ask.price,
+            _ => false,
+        }
+    }
+
+    /// Returns the difference between the best ask and the best bid, if both sides are not empty.
+    pub fn spread(&self) -> Option<f64> {
+        let (bid, ask) = self.best_bid().zip(self.best_ask())?;
+        Some((ask.price - bid.price).to_f64())
+    }
+
+    /// Returns the price halfway between the best bid and the best ask, if both sides are not empty.
+    pub fn mid_price(&self) -> Option<f64> {
+        let (bid, ask) = self.best_bid().zip(self.best_ask())?;
+        Some((bid.price + ask.price).to_f64() / 2.0)
+    }
+
+    /// Returns up to `n` best levels of each side.
+    ///
+    /// # Arguments
+    /// * `n` - The maximum number of levels per side
+    ///
+    /// # Returns
+    /// A tuple of bid and ask levels, best first
+    pub fn top_n(&self, n: usize) -> (Vec<DepthEntry>, Vec<DepthEntry>) {
+        (self.bids.iter().take(n).collect(), self.asks.iter().take(n).collect())
+    }
+
+    /// Returns the levels of the given side, best first.
+    pub fn levels(&self, side: Side) -> impl Iterator<Item = DepthEntry> + '_ {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+
+        levels.iter()
+    }
+
+    /// Returns the total quantity of all levels on the given side.
+    pub fn total_volume(&self, side: Side) -> f64 {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+
+        levels.iter().map(|level| level.quantity.to_f64()).sum()
+    }
+
+    /// Returns the quantity and notional of the levels on the given side within `bps` basis points of the mid price,
+    /// if both sides are not empty.
+    pub fn liquidity_within(&self, side: Side, bps: f64) -> Option<Liquidity> {
+        let mid_price = self.mid_price()?;
+        let (levels, limit) = match side {
+            Side::Bid => (&self.bids, mid_price * (1.0 - bps / 10_000.0)),
+            Side::Ask => (&self.asks, mid_price * (1.0 + bps / 10_000.0)),
+        };
+        let within = |price: f64| match side {
+            Side::Bid => price >= limit,
+            Side::Ask => price <= limit,
+        };
+
+        let mut liquidity = Liquidity { bps, levels: 0, quantity: 0.0, notional: 0.0 };
+        for level in levels.iter().take_while(|level| within(level.price.to_f64())) {
+            liquidity.levels += 1;
+            liquidity.quantity += level.quantity.to_f64();
+            liquidity.notional += level.price.to_f64() * level.quantity.to_f64();
+        }
+
+        Some(liquidity)
+    }
+
+    /// Estimates the execution of a market order of the given quantity, which walks the levels of one side.
+    ///
+    /// # Arguments
+    /// * `side` - The side the order takes liquidity from: `Ask` for a buy order, `Bid` for a sell order
+    /// * `quantity` - The quantity of the order
+    ///
+    /// # Returns
+    /// The estimate, or `None` if the quantity isn't positive or a side of the book is empty.
+    /// The filled quantity is less than the requested one, if the side doesn't hold enough
+    pub fn estimate_execution(&self, side: Side, quantity: f64) -> Option<ExecutionEstimate> {
+        let mid_price = self.mid_price()?;
+        if quantity <= 0.0 {
+            return None;
+        }
+
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+
+        let (mut filled_quantity, mut notional, mut worst_price) = (0.0, 0.0, 0.0);
+        for level in levels.iter() {
+            if filled_quantity >= quantity {
+                break;
+            }
+
+            let take = level.quantity.to_f64().min(quantity - filled_quantity);
+            worst_price = level.price.to_f64();
+            filled_quantity += take;
+            notional += worst_price * take;
+        }
+
+        let average_price = notional / filled_quantity;
+        let slippage = match side {
+            Side::Ask => average_price - mid_price,
+            Side::Bid => mid_price - average_price,
+        };
+
+        Some(ExecutionEstimate {
+            quantity,
+            filled_quantity,
+            notional,
+            average_price,
+            worst_price,
+            slippage_bps: slippage / mid_price * 10_000.0,
+        })
+    }
+
+    /// Helper method to create a bid price key.
+    ///
+    /// # Arguments
+    /// * `price` - The price value for the bid
+    ///
+    /// # Returns
+    /// A `PriceKey::Bid` variant with the specified price
+    pub fn bid(price: FixedPoint) -> PriceKey {
+        PriceKey::Bid(price)
+    }
+
+    /// Helper method to create an ask price key.
+    ///
+    /// # Arguments
+    /// * `price` - The price value for the ask
+    ///
+    /// # Returns
+    /// A `PriceKey::Ask` variant with the specified price
+    pub fn ask(price: FixedPoint) -> PriceKey {
+        PriceKey::Ask(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_order_book() {
+        let snapshot = DepthSnapshot {
+            last_update_id: 123456,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) },
+                DepthEntry { price: FixedPoint::from(99.5), quantity: FixedPoint::from(15.0) },
+            ],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::from(5.0) },
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(8.0) },
+            ],
+        };
+        
+        let order_book = OrderBook::new(&snapshot);
+        
+        assert_eq!(order_book.bids.len(), 2);
+        assert_eq!(order_book.bids.get(FixedPoint::from(100.0)), Some(FixedPoint::from(10.0)));
+        assert_eq!(order_book.bids.get(FixedPoint::from(99.5)), Some(FixedPoint::from(15.0)));
+        
+        assert_eq!(order_book.asks.len(), 2);
+        assert_eq!(order_book.asks.get(FixedPoint::from(100.5)), Some(FixedPoint::from(5.0)));
+        assert_eq!(order_book.asks.get(FixedPoint::from(101.0)), Some(FixedPoint::from(8.0)));
+    }
+
+    #[test]
+    fn test_apply_update_new_level() {
+        let mut order_book = OrderBook::default();
+        
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(100.0)), FixedPoint::from(10.0));
+        assert_eq!(order_book.bids.get(FixedPoint::from(100.0)), Some(FixedPoint::from(10.0)));
+        
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(101.0)), FixedPoint::from(5.0));
+        assert_eq!(order_book.asks.get(FixedPoint::from(101.0)), Some(FixedPoint::from(5.0)));
+    }
+
+    #[test]
+    fn test_apply_update_existing_level() {
+        let mut order_book = OrderBook::default();
+        order_book.bids.insert(FixedPoint::from(100.0), FixedPoint::from(10.0));
+        order_book.asks.insert(FixedPoint::from(101.0), FixedPoint::from(5.0));
+
+        
+        order_book.apply_update(PriceKey::Bid(FixedPoint::from(100.0)), FixedPoint::from(15.0));
+        assert_eq!(order_book.bids.get(FixedPoint::from(100.0)), Some(FixedPoint::from(15.0)));
+        
+        order_book.apply_update(PriceKey::Ask(FixedPoint::from(101.0)), FixedPoint::from(8.0));
+        assert_eq!(order_book.asks.get(FixedPoint::from(101.0)), Some(FixedPoint::from(8.0)));
+    }
+
+    #[test]
+    fn test_apply_update_remove_level() {
+        let mut order_book = OrderBook::default();
+        order_book.bids.insert(FixedPoint::from(100.0), FixedPoint::from(10.0));
+        order_book.bids.insert(FixedPoint::from(99.5), FixedPoint::from(15.0));
+        order_book.asks.insert(FixedPoint::from(101.0), FixedPoint::from(5.0));
+        order_book.asks.insert(FixedPoint::from(102.0), FixedPoint::from(8.0));
+
+        
+        order_book.apply_update(PriceKey::Bid(FixedPoint::from(100.0)), FixedPoint::ZERO);
+        assert_eq!(order_book.bids.get(FixedPoint::from(100.0)), None);
+        assert_eq!(order_book.bids.len(), 1);
+        
+        order_book.apply_update(PriceKey::Ask(FixedPoint::from(101.0)), FixedPoint::ZERO);
+        assert_eq!(order_book.asks.get(FixedPoint::from(101.0)), None);
+        assert_eq!(order_book.asks.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_update_nonexistent_level_zero_quantity() {
+        let mut order_book = OrderBook::default();
+        order_book.bids.insert(FixedPoint::from(100.0), FixedPoint::from(10.0));
+        order_book.asks.insert(FixedPoint::from(101.0), FixedPoint::from(5.0));
+
+        
+        order_book.apply_update(PriceKey::Bid(FixedPoint::from(99.0)), FixedPoint::ZERO);
+        assert_eq!(order_book.bids.len(), 1);
+        
+        order_book.apply_update(PriceKey::Ask(FixedPoint::from(102.0)), FixedPoint::ZERO);
+        assert_eq!(order_book.asks.len(), 1);
+    }
+
+    #[test]
+    fn test_multiple_updates() {
+        let mut order_book = OrderBook::default();
+        
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(100.0)), FixedPoint::from(10.0));
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(99.0)), FixedPoint::from(15.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(101.0)), FixedPoint::from(5.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(102.0)), FixedPoint::from(8.0));
+        
+        assert_eq!(order_book.bids.len(), 2);
+        assert_eq!(order_book.asks.len(), 2);
+        
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(100.0)), FixedPoint::from(20.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(101.0)), FixedPoint::from(10.0));
+        
+        assert_eq!(order_book.bids.get(FixedPoint::from(100.0)), Some(FixedPoint::from(20.0)));
+        assert_eq!(order_book.asks.get(FixedPoint::from(101.0)), Some(FixedPoint::from(10.0)));
+        
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(99.0)), FixedPoint::ZERO);
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(102.0)), FixedPoint::ZERO);
+        
+        assert_eq!(order_book.bids.len(), 1);
+        assert_eq!(order_book.asks.len(), 1);
+        assert_eq!(order_book.bids.get(FixedPoint::from(99.0)), None);
+        assert_eq!(order_book.asks.get(FixedPoint::from(102.0)), None);
+    }
+
+    #[test]
+    fn test_bid_ordering() {
+        let mut order_book = OrderBook::default();
+        
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(100.0)), FixedPoint::from(10.0));
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(102.0)), FixedPoint::from(5.0));
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(99.0)), FixedPoint::from(15.0));
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(101.0)), FixedPoint::from(8.0));
+        
+        let bid_prices: Vec<f64> = order_book
+            .bids
+            .iter()
+            .map(|level| level.price.to_f64())
+            .collect();
+        
+        assert_eq!(bid_prices, vec![102.0, 101.0, 100.0, 99.0]);
+    }
+
+    #[test]
+    fn test_ask_ordering() {
+        let mut order_book = OrderBook::default();
+        
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(100.0)), FixedPoint::from(10.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(102.0)), FixedPoint::from(5.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(99.0)), FixedPoint::from(15.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(101.0)), FixedPoint::from(8.0));
+        
+        let ask_prices: Vec<f64> = order_book
+            .asks
+            .iter()
+            .map(|level| level.price.to_f64())
+            .collect();
+        
+        assert_eq!(ask_prices, vec![99.0, 100.0, 101.0, 102.0]);
+    }
+
+    #[test]
+    fn test_book_side_from_levels() {
+        let entry = |price: f64, quantity: f64| DepthEntry { price: FixedPoint::from(price), quantity: FixedPoint::from(quantity) };
+        let asks = BookSide::from_levels(Side::Ask, &[entry(102.0, 1.0), entry(101.0, 2.0), entry(102.0, 3.0), entry(103.0, 4.0)]);
+
+        assert_eq!(asks.len(), 3);
+        assert_eq!(asks.best(), Some(entry(101.0, 2.0)));
+        // The later level replaces the earlier one with the same price
+        assert_eq!(asks.get(FixedPoint::from(102.0)), Some(FixedPoint::from(3.0)));
+        assert_eq!(asks.iter().rev().map(|level| level.price.to_f64()).collect::<Vec<_>>(), vec![103.0, 102.0, 101.0]);
+
+        let mut bids = BookSide::new(Side::Bid);
+        assert_eq!(bids.insert(FixedPoint::from(100.0), FixedPoint::from(1.0)), None);
+        assert_eq!(bids.insert(FixedPoint::from(100.0), FixedPoint::from(2.0)), Some(FixedPoint::from(1.0)));
+        assert_eq!(bids.remove(FixedPoint::from(99.0)), None);
+        assert_eq!(bids.remove(FixedPoint::from(100.0)), Some(FixedPoint::from(2.0)));
+        assert!(bids.is_empty());
+
+        // Beyond the scanned top levels the levels are binary searched
+        for i in 0..100 {
+            bids.insert(FixedPoint::from(((i * 37) % 100) as f64), FixedPoint::from(1.0));
+        }
+        assert_eq!(bids.iter().map(|level| level.price.to_f64()).collect::<Vec<_>>(), (0..100).rev().map(f64::from).collect::<Vec<_>>());
+        assert!((0..100).all(|price| bids.contains(FixedPoint::from(price as f64))));
+        assert!(!bids.contains(FixedPoint::from(50.5)));
+    }
+
+    #[test]
+    fn test_book_side_max_depth() {
+        let entry = |price: f64| DepthEntry { price: FixedPoint::from(price), quantity: FixedPoint::from(1.0) };
+        let mut bids = BookSide::from_levels(Side::Bid, &[entry(100.0), entry(99.0), entry(98.0), entry(97.0)]).with_max_depth(3);
+        let prices = |side: &BookSide| side.iter().map(|level| level.price.to_f64()).collect::<Vec<_>>();
+
+        assert_eq!(prices(&bids), vec![100.0, 99.0, 98.0]);
+        assert!(bids.is_pruned(FixedPoint::from(97.0)));
+        assert!(!bids.is_pruned(FixedPoint::from(97.5)));
+
+        // A better level pushes out the worst one
+        bids.insert(FixedPoint::from(101.0), FixedPoint::from(1.0));
+        assert_eq!(prices(&bids), vec![101.0, 100.0, 99.0]);
+        assert!(bids.is_pruned(FixedPoint::from(98.0)));
+
+        // Pruned prices are not maintained any more, even once there is room for them
+        bids.remove(FixedPoint::from(101.0));
+        assert_eq!(bids.insert(FixedPoint::from(98.0), FixedPoint::from(2.0)), None);
+        assert_eq!(prices(&bids), vec![100.0, 99.0]);
+
+        // A level between the worst retained level and the pruned ones is known to be complete
+        bids.insert(FixedPoint::from(98.5), FixedPoint::from(1.0));
+        assert_eq!(prices(&bids), vec![100.0, 99.0, 98.5]);
+    }
+
+    #[test]
+    fn test_prune_outside_band() {
+        let entry = |price: f64| DepthEntry { price: FixedPoint::from(price), quantity: FixedPoint::from(1.0) };
+        let mut book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry(99.5), entry(99.0), entry(98.9), entry(90.0)],
+            asks: vec![entry(100.5), entry(101.0), entry(101.1), entry(110.0)],
+        });
+
+        // 1% around the mid price of 100.0
+        book.prune_outside_band(1.0);
+        assert_eq!(book.top_n(10), (vec![entry(99.5), entry(99.0)], vec![entry(100.5), entry(101.0)]));
+        assert!(book.is_pruned(OrderBook::bid(FixedPoint::from(98.9))));
+        assert!(book.is_pruned(OrderBook::ask(FixedPoint::from(101.1))));
+        assert!(!book.is_pruned(OrderBook::ask(FixedPoint::from(101.05))));
+
+        // Once the band moves away, the pruned prices are not maintained any more
+        book.apply_update(OrderBook::bid(FixedPoint::from(98.9)), FixedPoint::from(2.0));
+        assert_eq!(book.bids.len(), 2);
+    }
+
+    #[test]
+    fn test_price_key_helpers() {
+        let bid_key = OrderBook::bid(FixedPoint::from(100.0));
+        let ask_key = OrderBook::ask(FixedPoint::from(100.0));
+        
+        assert_eq!(bid_key, PriceKey::Bid(FixedPoint::from(100.0)));
+        assert_eq!(ask_key, PriceKey::Ask(FixedPoint::from(100.0)));
+        
+        assert_eq!(bid_key.price(), FixedPoint::from(100.0));
+        assert_eq!(ask_key.price(), FixedPoint::from(100.0));
+    }
+
+    #[test]
+    fn test_best_bid_and_ask() {
+        let mut order_book = OrderBook::default();
+
+        assert_eq!(order_book.best_bid(), None);
+        assert_eq!(order_book.best_ask(), None);
+
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(100.0)), FixedPoint::from(10.0));
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(101.0)), FixedPoint::from(5.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(103.0)), FixedPoint::from(8.0));
+        order_book.apply_update(OrderBook::ask(FixedPoint::from(102.0)), FixedPoint::from(3.0));
+
+        assert_eq!(order_book.best_bid(), Some(DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) }));
+        assert_eq!(order_book.best_ask(), Some(DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(3.0) }));
+        assert!(!order_book.is_crossed());
+
+        order_book.apply_update(OrderBook::bid(FixedPoint::from(102.0)), FixedPoint::from(1.0));
+        assert!(order_book.is_crossed());
+    }
+
+    #[test]
+    fn test_query_methods() {
+        let order_book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(98.0), quantity: FixedPoint::from(3.0) },
+            ],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(4.0) },
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) },
+            ],
+        });
+
+        assert_eq!(order_book.spread(), Some(1.0));
+        assert_eq!(order_book.mid_price(), Some(100.5));
+        assert_eq!(order_book.total_volume(Side::Bid), 6.0);
+        assert_eq!(order_book.total_volume(Side::Ask), 9.0);
+
+        let (bids, asks) = order_book.top_n(2);
+        assert_eq!(bids, vec![
+            DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) },
+            DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+        ]);
+        assert_eq!(asks, vec![
+            DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) },
+            DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(4.0) },
+        ]);
+
+        let top = order_book.top(2);
+        assert_eq!(top.top_n(usize::MAX), (bids, asks));
+        assert_eq!(top.top(0).best_bid(), None);
+
+        let one_sided = OrderBook::new(&DepthSnapshot { last_update_id: 1, bids: vec![], asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(1.0) }] });
+        assert_eq!(one_sided.spread(), None);
+        assert_eq!(one_sided.mid_price(), None);
+        assert_eq!(one_sided.total_volume(Side::Bid), 0.0);
+        assert_eq!(one_sided.liquidity_within(Side::Ask, 100.0), None);
+        assert_eq!(one_sided.estimate_execution(Side::Ask, 1.0), None);
+    }
+
+    #[test]
+    fn test_liquidity_and_execution_estimates() {
+        let order_book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+                DepthEntry { price: FixedPoint::from(98.0), quantity: FixedPoint::from(3.0) },
+            ],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(4.0) },
+            ],
+        });
+
+        // The mid price is 100, so 150 bps reach 98.5 and 101.5
+        let bids = order_book.liquidity_within(Side::Bid, 150.0).unwrap();
+        assert_eq!((bids.levels, bids.quantity, bids.notional), (1, 2.0, 198.0));
+        let asks = order_book.liquidity_within(Side::Ask, 200.0).unwrap();
+        assert_eq!((asks.levels, asks.quantity, asks.notional), (2, 5.0, 509.0));
+        assert_eq!(order_book.liquidity_within(Side::Ask, 50.0).unwrap().levels, 0);
+
+        let buy = order_book.estimate_execution(Side::Ask, 3.0).unwrap();
+        assert_eq!((buy.filled_quantity, buy.notional, buy.worst_price), (3.0, 305.0, 102.0));
+        assert!((buy.average_price - 101.6667).abs() < 1e-4);
+        assert!((buy.slippage_bps - 166.6667).abs() < 1e-4);
+
+        let sell = order_book.estimate_execution(Side::Bid, 10.0).unwrap();
+        assert_eq!((sell.filled_quantity, sell.average_price, sell.worst_price), (5.0, 98.4, 98.0));
+        assert!((sell.slippage_bps - 160.0).abs() < 1e-9);
+
+        assert_eq!(order_book.estimate_execution(Side::Ask, 0.0), None);
+    }
+
+    #[test]
+    fn test_format_top() {
+        let order_book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+            ],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) },
+                DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(4.0) },
+            ],
+        });
+
+        let top = order_book.format_top(1);
+        assert!(top.contains("Bid volume: '3'"));
+        assert!(top.contains("Price: '100'"));
+        assert!(!top.contains("Price: '99'"));
+        assert!(top.contains("Price: '101'"));
+        assert!(!top.contains("Price: '102'"));
+
+        assert_eq!(order_book.format_top(usize::MAX), order_book.to_string());
+        assert!(order_book.to_string().contains("Price: '102'"));
+    }
+}
@@ -0,0 +1,368 @@
+use std::fmt;
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::{DepthEntry, DepthUpdate, MarketEvent, StreamMessage, TradeEvent};
+
+/// Id of the schema of the Binance spot market data streams (`spot_stream_1_0.xml`)
+const SCHEMA_ID: u16 = 1;
+
+/// Template id of `TradesStreamEvent`, the trades of a `<symbol>@trade` stream
+const TRADES_TEMPLATE: u16 = 10000;
+
+/// Template id of `DepthDiffStreamEvent`, the depth updates of a `<symbol>@depth` stream
+const DEPTH_DIFF_TEMPLATE: u16 = 10003;
+
+/// Error of decoding an SBE frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum SbeError {
+    /// The frame ends before the message does
+    Truncated,
+    /// The frame is encoded with another schema
+    UnknownSchema(u16),
+    /// The message isn't one of the decoded stream events
+    UnknownTemplate(u16),
+    /// A price or quantity has more decimals than a FixedPoint or is out of its range
+    InvalidDecimal { mantissa: i64, exponent: i8 },
+    /// The symbol isn't valid UTF-8
+    InvalidSymbol,
+}
+
+impl fmt::Display for SbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SbeError::Truncated => write!(f, "SBE frame is truncated"),
+            SbeError::UnknownSchema(schema_id) => write!(f, "Unknown SBE schema: '{}'", schema_id),
+            SbeError::UnknownTemplate(template_id) => write!(f, "Unknown SBE template: '{}'", template_id),
+            SbeError::InvalidDecimal { mantissa, exponent } => {
+                write!(f, "Invalid SBE decimal: '{}e{}'", mantissa, exponent)
+            }
+            SbeError::InvalidSymbol => write!(f, "SBE symbol is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for SbeError {}
+
+/// Reader of the little-endian fields of an SBE message, in order
+struct Reader<'a> {
+    frame: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(frame: &'a [u8]) -> Self {
+        Self { frame, position: 0 }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], SbeError> {
+        let bytes = self.frame.get(self.position..self.position + N).ok_or(SbeError::Truncated)?;
+        self.position += N;
+        Ok(bytes.try_into().expect("Slice has the length of the array"))
+    }
+
+    fn u8(&mut self) -> Result<u8, SbeError> {
+        self.bytes::<1>().map(|bytes| bytes[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, SbeError> {
+        self.bytes().map(i8::from_le_bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, SbeError> {
+        self.bytes().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, SbeError> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64, SbeError> {
+        self.bytes().map(i64::from_le_bytes)
+    }
+
+    /// Move to the end of the block, which starts at `start` and has the given length.
+    /// Fields appended by newer versions of the schema are skipped this way
+    fn end_block(&mut self, start: usize, length: usize) -> Result<(), SbeError> {
+        let end = start + length;
+        if end < self.position || end > self.frame.len() {
+            return Err(SbeError::Truncated);
+        }
+        self.position = end;
+        Ok(())
+    }
+
+    /// Read a timestamp in microseconds as milliseconds
+    fn timestamp(&mut self) -> Result<u64, SbeError> {
+        self.i64().map(|micros| (micros / 1000) as u64)
+    }
+
+    /// Read the dimensions of a repeating group with a `u32` (`groupSizeEncoding`) or a `u16`
+    /// (`groupSize16Encoding`) number of entries
+    ///
+    /// # Returns
+    /// The block length of an entry and the number of entries
+    fn group(&mut self, wide: bool) -> Result<(usize, usize), SbeError> {
+        let block_length = self.u16()? as usize;
+        let count = match wide {
+            true => self.u32()? as usize,
+            false => self.u16()? as usize,
+        };
+        Ok((block_length, count))
+    }
+
+    /// Read a `varString8`: a `u8` length and the UTF-8 bytes
+    fn string(&mut self) -> Result<String, SbeError> {
+        let length = self.u8()? as usize;
+        let bytes = self.frame.get(self.position..self.position + length).ok_or(SbeError::Truncated)?;
+        self.position += length;
+        String::from_utf8(bytes.to_vec()).map_err(|_| SbeError::InvalidSymbol)
+    }
+}
+
+fn decimal(mantissa: i64, exponent: i8) -> Result<FixedPoint, SbeError> {
+    FixedPoint::from_mantissa(mantissa, exponent).ok_or(SbeError::InvalidDecimal { mantissa, exponent })
+}
+
+/// Read the levels of a depth group, whose prices and quantities share the exponents of the message
+fn read_levels(reader: &mut Reader, price_exponent: i8, qty_exponent: i8) -> Result<Vec<DepthEntry>, SbeError> {
+    let (block_length, count) = reader.group(false)?;
+    let mut levels = Vec::new();
+    for _ in 0..count {
+        let start = reader.position;
+        let price = decimal(reader.i64()?, price_exponent)?;
+        let quantity = decimal(reader.i64()?, qty_exponent)?;
+        reader.end_block(start, block_length)?;
+        levels.push(DepthEntry { price, quantity });
+    }
+    Ok(levels)
+}
+
+fn decode_trades(reader: &mut Reader, block_length: usize) -> Result<Vec<MarketEvent>, SbeError> {
+    let start = reader.position;
+    let event_time = reader.timestamp()?;
+    let trade_time = reader.timestamp()?;
+    let price_exponent = reader.i8()?;
+    let qty_exponent = reader.i8()?;
+    reader.end_block(start, block_length)?;
+
+    let (block_length, count) = reader.group(true)?;
+    let mut trades = Vec::new();
+    for _ in 0..count {
+        let start = reader.position;
+        let trade_id = reader.i64()? as u64;
+        let price = decimal(reader.i64()?, price_exponent)?;
+        let quantity = decimal(reader.i64()?, qty_exponent)?;
+        let is_market_maker = reader.u8()? == 1;
+        reader.end_block(start, block_length)?;
+
+        trades.push(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time,
+            symbol: String::new(),
+            trade_id,
+            price: price.to_f64(),
+            quantity: quantity.to_f64(),
+            trade_time,
+            is_market_maker,
+            // `isBestMatch` is a constant of the schema, always true
+            ignore: true,
+            received: None,
+        });
+    }
+
+    let symbol = reader.string()?;
+    Ok(trades
+        .into_iter()
+        .map(|trade| MarketEvent::TradeEvent(TradeEvent { symbol: symbol.clone(), ..trade }))
+        .collect())
+}
+
+fn decode_depth_diff(reader: &mut Reader, block_length: usize) -> Result<Vec<MarketEvent>, SbeError> {
+    let start = reader.position;
+    let event_time = reader.timestamp()?;
+    let first_update_id = reader.i64()? as u64;
+    let last_update_id = reader.i64()? as u64;
+    let price_exponent = reader.i8()?;
+    let qty_exponent = reader.i8()?;
+    reader.end_block(start, block_length)?;
+
+    let bids = read_levels(reader, price_exponent, qty_exponent)?;
+    let asks = read_levels(reader, price_exponent, qty_exponent)?;
+    let symbol = reader.string()?;
+
+    Ok(vec![MarketEvent::DepthUpdate(DepthUpdate {
+        event_type: "depthUpdate".to_string(),
+        event_time,
+        symbol,
+        first_update_id,
+        last_update_id,
+        previous_last_update_id: None,
+        bids,
+        asks,
+        checksum: None,
+        received: None,
+    })])
+}
+
+/// Decode a frame of a Binance SBE market data stream into market events
+///
+/// Messages of newer versions of the schema are decoded by the block lengths they carry, skipping the new fields
+///
+/// # Errors
+/// If the frame is truncated, of another schema or an unsupported message, or a decimal can't be represented
+pub fn decode_frame(frame: &[u8]) -> Result<Vec<MarketEvent>, SbeError> {
+    let mut reader = Reader::new(frame);
+    let block_length = reader.u16()? as usize;
+    let template_id = reader.u16()?;
+    let schema_id = reader.u16()?;
+    let _version = reader.u16()?;
+
+    if schema_id != SCHEMA_ID {
+        return Err(SbeError::UnknownSchema(schema_id));
+    }
+
+    match template_id {
+        TRADES_TEMPLATE => decode_trades(&mut reader, block_length),
+        DEPTH_DIFF_TEMPLATE => decode_depth_diff(&mut reader, block_length),
+        template_id => Err(SbeError::UnknownTemplate(template_id)),
+    }
+}
+
+/// The frame of the JSON stream, which carries the event, so tapes of SBE streams replay like tapes of JSON streams
+pub fn json_frame(event: &MarketEvent) -> Option<String> {
+    match event {
+        MarketEvent::DepthUpdate(update) => Some(update.to_json()),
+        MarketEvent::TradeEvent(trade) => Some(trade.to_json()),
+        _ => None,
+    }
+}
+
+/// A binary frame of a Binance SBE depth (`<symbol>@depth`) or trade (`<symbol>@trade`) stream
+///
+/// Depth updates carry the same update ids as the JSON depth stream, so they are sequenced against REST snapshots
+/// the same way. The SBE streams send no text frames with events
+pub struct SbeMessage;
+
+impl StreamMessage for SbeMessage {
+    type State = ();
+
+    fn decode(_state: &mut (), _message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        Ok(Vec::new())
+    }
+
+    fn decode_binary(_state: &mut (), message: &[u8]) -> Result<Vec<MarketEvent>, SbeError> {
+        decode_frame(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::FromJson;
+
+    /// Encoder of the test frames: the message header, then the fields in order
+    #[derive(Default)]
+    struct Frame(Vec<u8>);
+
+    impl Frame {
+        fn header(block_length: u16, template_id: u16, schema_id: u16) -> Self {
+            let mut frame = Frame::default();
+            frame.put(&block_length.to_le_bytes()).put(&template_id.to_le_bytes()).put(&schema_id.to_le_bytes()).put(&[0, 0]);
+            frame
+        }
+
+        fn put(&mut self, bytes: &[u8]) -> &mut Self {
+            self.0.extend_from_slice(bytes);
+            self
+        }
+
+        fn symbol(&mut self, symbol: &str) -> &mut Self {
+            self.put(&[symbol.len() as u8]).put(symbol.as_bytes())
+        }
+    }
+
+    fn depth_diff(block_length: u16, levels: &[(i64, i64)]) -> Vec<u8> {
+        let mut frame = Frame::header(block_length, DEPTH_DIFF_TEMPLATE, SCHEMA_ID);
+        frame
+            .put(&1_700_000_000_123_456_i64.to_le_bytes())
+            .put(&157_i64.to_le_bytes())
+            .put(&160_i64.to_le_bytes())
+            .put(&(-2_i8).to_le_bytes())
+            .put(&(-8_i8).to_le_bytes())
+            .put(&[0].repeat(block_length as usize - 26));
+        for side in [&levels[..1], &levels[1..]] {
+            frame.put(&16_u16.to_le_bytes()).put(&(side.len() as u16).to_le_bytes());
+            for (price, quantity) in side {
+                frame.put(&price.to_le_bytes()).put(&quantity.to_le_bytes());
+            }
+        }
+        frame.symbol("BTCUSDT");
+        frame.0
+    }
+
+    #[test]
+    fn test_decode_depth_diff() {
+        // A newer schema version may append fields to the block, they are skipped
+        for block_length in [26, 30] {
+            let events = decode_frame(&depth_diff(block_length, &[(6_543_210, 150_000_000), (6_543_220, 0), (6_543_230, 1)])).unwrap();
+            let [MarketEvent::DepthUpdate(update)] = events.as_slice() else {
+                panic!("Expected a single depth update: '{:?}'", events);
+            };
+
+            assert_eq!((update.first_update_id, update.last_update_id), (157, 160));
+            assert_eq!(update.event_time, 1_700_000_000_123);
+            assert_eq!(update.symbol, "BTCUSDT");
+            assert_eq!(update.bids, vec![DepthEntry { price: "65432.1".parse().unwrap(), quantity: "1.5".parse().unwrap() }]);
+            assert_eq!(update.asks.len(), 2);
+            assert_eq!(update.asks[1].quantity, "0.00000001".parse().unwrap());
+
+            // Recorded as a frame of the JSON depth stream
+            let recorded = DepthUpdate::from_json(&json_frame(&events[0]).unwrap()).unwrap();
+            assert_eq!((recorded.first_update_id, recorded.last_update_id), (157, 160));
+            assert_eq!((recorded.bids, recorded.asks), (update.bids.clone(), update.asks.clone()));
+        }
+    }
+
+    #[test]
+    fn test_decode_trades() {
+        let mut frame = Frame::header(18, TRADES_TEMPLATE, SCHEMA_ID);
+        frame
+            .put(&1_700_000_000_123_456_i64.to_le_bytes())
+            .put(&1_700_000_000_120_000_i64.to_le_bytes())
+            .put(&(-2_i8).to_le_bytes())
+            .put(&(-5_i8).to_le_bytes())
+            .put(&25_u16.to_le_bytes())
+            .put(&2_u32.to_le_bytes());
+        for (trade_id, is_buyer_maker) in [(10_003_456_i64, 1_u8), (10_003_457, 0)] {
+            frame
+                .put(&trade_id.to_le_bytes())
+                .put(&2_345_678_i64.to_le_bytes())
+                .put(&123_i64.to_le_bytes())
+                .put(&[is_buyer_maker]);
+        }
+        frame.symbol("BTCUSDT");
+
+        let events = decode_frame(&frame.0).unwrap();
+        let trades: Vec<&TradeEvent> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::TradeEvent(trade) => Some(trade),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!((trades[0].trade_id, trades[1].trade_id), (10_003_456, 10_003_457));
+        assert_eq!((trades[0].price, trades[0].quantity), (23456.78, 0.00123));
+        assert_eq!((trades[0].event_time, trades[0].trade_time), (1_700_000_000_123, 1_700_000_000_120));
+        assert!(trades[0].is_market_maker && !trades[1].is_market_maker);
+        assert_eq!(trades[1].symbol, "BTCUSDT");
+
+        let recorded = TradeEvent::from_json(&json_frame(&events[0]).unwrap()).unwrap();
+        assert_eq!((recorded.trade_id, recorded.price, recorded.quantity), (10_003_456, 23456.78, 0.00123));
+
+        // Truncated frames, other schemas and unsupported messages are rejected
+        assert_eq!(decode_frame(&frame.0[..frame.0.len() - 1]).err(), Some(SbeError::Truncated));
+        assert_eq!(decode_frame(&Frame::header(18, TRADES_TEMPLATE, 2).0).err(), Some(SbeError::UnknownSchema(2)));
+        assert_eq!(decode_frame(&Frame::header(50, 10001, SCHEMA_ID).0).err(), Some(SbeError::UnknownTemplate(10001)));
+    }
+}
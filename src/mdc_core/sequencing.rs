@@ -0,0 +1,209 @@
+use crate::mdc_core::models::DepthUpdate;
+
+/// Venue-specific rules of depth update continuity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequencingRules {
+    /// Binance spot rules: the update continuing the sequence must contain `lastUpdateId + 1` within its `[U;u]` range
+    BinanceSpot,
+    /// Binance futures rules: the first update after a snapshot must contain the snapshot `lastUpdateId` within its `[U;u]` range,
+    /// every following update must have `pu` equal to `u` of the previous update
+    BinanceFutures,
+    /// OKX rules: every update must have `prevSeqId` (`pu`) equal to `seqId` (`u`) of the previous update.
+    /// The first update after a snapshot follows the `seqId` of the snapshot
+    Okx,
+    /// Bitfinex rules: messages of a connection are numbered consecutively, so every update must have `pu` equal
+    /// to `u` of the previous update. Heartbeats and checksum messages are numbered too and keep the sequence going
+    Bitfinex,
+    /// Deribit rules: every change must have `prev_change_id` (`pu`) equal to `change_id` (`u`) of the previous change.
+    /// The first change after a snapshot follows the `change_id` of the snapshot
+    Deribit,
+}
+
+/// Rules deciding whether a depth update continues the sequence of the updates before it
+///
+/// DepthSequencer and the dispatcher restore the order of the updates with a strategy, so the sequencing core is
+/// reusable for venues with their own rules. SequencingRules implements it for the supported venues
+pub trait SequencingStrategy: Send + Sync {
+    /// Check whether the update directly continues the sequence after the last processed update id
+    ///
+    /// # Arguments
+    /// * `update` - The buffered DepthUpdate
+    /// * `last_processed_update_id` - The last update id, which has been forwarded (or taken from the snapshot)
+    /// * `is_first_after_snapshot` - Whether `last_processed_update_id` has been taken from the snapshot
+    fn is_continuation(&self, update: &DepthUpdate, last_processed_update_id: u64, is_first_after_snapshot: bool) -> bool;
+
+    /// Check whether the update directly follows the previous update received over the same connection
+    ///
+    /// # Arguments
+    /// * `update` - The received DepthUpdate
+    /// * `previous_last_update_id` - Last update id of the DepthUpdate received right before it
+    fn follows(&self, update: &DepthUpdate, previous_last_update_id: u64) -> bool;
+}
+
+/// Binance spot strategy: the update continuing the sequence must contain `lastUpdateId + 1` within its `[U;u]` range
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateIdRangeSequencing;
+
+impl SequencingStrategy for UpdateIdRangeSequencing {
+    fn is_continuation(&self, update: &DepthUpdate, last_processed_update_id: u64, _is_first_after_snapshot: bool) -> bool {
+        let expected_first_update_id = last_processed_update_id + 1;
+        update.first_update_id <= expected_first_update_id && expected_first_update_id <= update.last_update_id
+    }
+
+    fn follows(&self, update: &DepthUpdate, previous_last_update_id: u64) -> bool {
+        update.first_update_id == previous_last_update_id + 1
+    }
+}
+
+/// Binance futures strategy: the first update after a snapshot must contain the snapshot `lastUpdateId` within its
+/// `[U;u]` range, every following update must have `pu` equal to `u` of the previous update
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreviousUpdateIdSequencing;
+
+impl SequencingStrategy for PreviousUpdateIdSequencing {
+    fn is_continuation(&self, update: &DepthUpdate, last_processed_update_id: u64, is_first_after_snapshot: bool) -> bool {
+        let covers_snapshot = update.first_update_id <= last_processed_update_id
+            && last_processed_update_id <= update.last_update_id;
+
+        self.follows(update, last_processed_update_id) || (is_first_after_snapshot && covers_snapshot)
+    }
+
+    fn follows(&self, update: &DepthUpdate, previous_last_update_id: u64) -> bool {
+        update.previous_last_update_id == Some(previous_last_update_id)
+    }
+}
+
+/// Sequence id strategy of OKX, Bitfinex and Deribit: every update must have `pu` equal to `u` of the previous
+/// update, the first one after a snapshot follows the sequence id of the snapshot
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SequenceIdSequencing;
+
+impl SequencingStrategy for SequenceIdSequencing {
+    fn is_continuation(&self, update: &DepthUpdate, last_processed_update_id: u64, _is_first_after_snapshot: bool) -> bool {
+        self.follows(update, last_processed_update_id)
+    }
+
+    fn follows(&self, update: &DepthUpdate, previous_last_update_id: u64) -> bool {
+        update.previous_last_update_id == Some(previous_last_update_id)
+    }
+}
+
+impl SequencingRules {
+    /// The strategy implementing the rules
+    pub fn strategy(&self) -> &'static dyn SequencingStrategy {
+        match self {
+            SequencingRules::BinanceSpot => &UpdateIdRangeSequencing,
+            SequencingRules::BinanceFutures => &PreviousUpdateIdSequencing,
+            SequencingRules::Okx | SequencingRules::Bitfinex | SequencingRules::Deribit => &SequenceIdSequencing,
+        }
+    }
+}
+
+impl SequencingStrategy for SequencingRules {
+    fn is_continuation(&self, update: &DepthUpdate, last_processed_update_id: u64, is_first_after_snapshot: bool) -> bool {
+        self.strategy().is_continuation(update, last_processed_update_id, is_first_after_snapshot)
+    }
+
+    fn follows(&self, update: &DepthUpdate, previous_last_update_id: u64) -> bool {
+        self.strategy().follows(update, previous_last_update_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_update(first: u64, last: u64) -> DepthUpdate {
+        DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            last_update_id: last,
+            previous_last_update_id: None,
+            bids: vec![],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        }
+    }
+
+    fn make_futures_update(first: u64, last: u64, previous: u64) -> DepthUpdate {
+        DepthUpdate {
+            previous_last_update_id: Some(previous),
+            ..make_update(first, last)
+        }
+    }
+
+    #[test]
+    fn test_binance_spot_sequencing_rules() {
+        let rules = SequencingRules::BinanceSpot;
+
+        assert!(rules.is_continuation(&make_update(101, 105), 100, true));
+        assert!(rules.is_continuation(&make_update(95, 105), 100, true));
+        assert!(!rules.is_continuation(&make_update(102, 105), 100, true));
+        assert!(!rules.is_continuation(&make_update(95, 100), 100, true));
+        assert!(rules.is_continuation(&make_update(101, 101), 100, false));
+    }
+
+    #[test]
+    fn test_binance_futures_sequencing_rules() {
+        let rules = SequencingRules::BinanceFutures;
+
+        assert!(rules.is_continuation(&make_futures_update(95, 105, 94), 100, true));
+        assert!(rules.is_continuation(&make_futures_update(100, 105, 99), 100, true));
+        assert!(!rules.is_continuation(&make_futures_update(101, 105, 99), 100, true));
+
+        assert!(rules.is_continuation(&make_futures_update(101, 110, 100), 100, false));
+        assert!(rules.is_continuation(&make_futures_update(120, 130, 100), 100, false));
+        assert!(!rules.is_continuation(&make_futures_update(95, 105, 94), 100, false));
+    }
+
+    #[test]
+    fn test_sequencing_rules_follows() {
+        let spot = SequencingRules::BinanceSpot;
+        assert!(spot.follows(&make_update(106, 106), 105));
+        assert!(!spot.follows(&make_update(107, 110), 105));
+
+        let futures = SequencingRules::BinanceFutures;
+        assert!(futures.follows(&make_futures_update(110, 120, 105), 105));
+        assert!(!futures.follows(&make_futures_update(110, 120, 108), 105));
+    }
+
+    #[test]
+    fn test_okx_sequencing_rules() {
+        let rules = SequencingRules::Okx;
+
+        // Updates start right after the previous seqId, an update without changes keeps the seqId
+        assert!(rules.is_continuation(&make_futures_update(101, 105, 100), 100, true));
+        assert!(rules.is_continuation(&make_futures_update(121, 130, 120), 120, false));
+        assert!(!rules.is_continuation(&make_futures_update(95, 105, 94), 100, true));
+        assert!(!rules.is_continuation(&make_futures_update(102, 105, 101), 100, false));
+
+        assert!(rules.follows(&make_futures_update(106, 105, 105), 105));
+        assert!(!rules.follows(&make_futures_update(110, 120, 108), 105));
+    }
+
+    #[test]
+    fn test_bitfinex_sequencing_rules() {
+        let rules = SequencingRules::Bitfinex;
+
+        // Every message of the connection has its own sequence number
+        assert!(rules.is_continuation(&make_futures_update(8, 8, 7), 7, true));
+        assert!(!rules.is_continuation(&make_futures_update(9, 9, 8), 7, false));
+
+        assert!(rules.follows(&make_futures_update(9, 9, 8), 8));
+        assert!(!rules.follows(&make_futures_update(10, 10, 9), 8));
+    }
+
+    #[test]
+    fn test_deribit_sequencing_rules() {
+        let rules = SequencingRules::Deribit;
+
+        // The first change after a snapshot continues its change_id
+        assert!(rules.is_continuation(&make_futures_update(297218, 297218, 297217), 297217, true));
+        assert!(!rules.is_continuation(&make_futures_update(297220, 297220, 297219), 297217, false));
+
+        assert!(rules.follows(&make_futures_update(297219, 297219, 297218), 297218));
+    }
+}
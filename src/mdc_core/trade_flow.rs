@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::fmt;
+use serde::Serialize;
+use crate::mdc_core::models::TradeEvent;
+
+/// Trade flow statistics over a rolling window of trades
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TradeFlow {
+    /// Local time, when the window ends, in milliseconds since epoch
+    pub time: i64,
+    /// Length of the window in milliseconds
+    pub window: u64,
+    /// Volume of buy-initiated trades (the buyer is the taker)
+    pub buy_volume: f64,
+    /// Volume of sell-initiated trades (the buyer is the market maker)
+    pub sell_volume: f64,
+    pub trade_count: u64,
+    /// Volume-weighted average price, if there were trades in the window
+    pub vwap: Option<f64>,
+    /// Trades per second
+    pub trade_rate: f64,
+}
+
+impl fmt::Display for TradeFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Window: '{}' ms, Buy volume: '{}', Sell volume: '{}', Trades: '{}', VWAP: '{:?}', Rate: '{:.2}'/s",
+            self.window,
+            self.buy_volume,
+            self.sell_volume,
+            self.trade_count,
+            self.vwap,
+            self.trade_rate,
+        )
+    }
+}
+
+struct WindowTrade {
+    time: i64,
+    price: f64,
+    quantity: f64,
+    is_sell: bool,
+}
+
+/// Keeps the trades of the last `window` milliseconds. Time is given explicitly (milliseconds since epoch)
+pub struct TradeFlowWindow {
+    window: u64,
+    trades: VecDeque<WindowTrade>,
+}
+
+impl TradeFlowWindow {
+    /// Create a new TradeFlowWindow
+    ///
+    /// # Arguments
+    /// * `window` - Length of the rolling window in milliseconds
+    pub fn new(window: u64) -> Self {
+        Self { window: window.max(1), trades: VecDeque::new() }
+    }
+
+    /// Account a trade received at the given time. Trades, where the buyer is the market maker, are sell-initiated
+    pub fn on_trade(&mut self, now: i64, trade: &TradeEvent) {
+        self.trades.push_back(WindowTrade {
+            time: now,
+            price: trade.price,
+            quantity: trade.quantity,
+            is_sell: trade.is_market_maker,
+        });
+    }
+
+    /// Drop the trades, which have left the window, and compute the statistics of the remaining ones
+    pub fn flow(&mut self, now: i64) -> TradeFlow {
+        let start = now - self.window as i64;
+        while self.trades.front().is_some_and(|trade| trade.time <= start) {
+            self.trades.pop_front();
+        }
+
+        let (mut buy_volume, mut sell_volume, mut notional) = (0.0, 0.0, 0.0);
+        for trade in &self.trades {
+            if trade.is_sell {
+                sell_volume += trade.quantity;
+            } else {
+                buy_volume += trade.quantity;
+            }
+            notional += trade.price * trade.quantity;
+        }
+
+        let volume = buy_volume + sell_volume;
+        TradeFlow {
+            time: now,
+            window: self.window,
+            buy_volume,
+            sell_volume,
+            trade_count: self.trades.len() as u64,
+            vwap: (volume > 0.0).then(|| notional / volume),
+            trade_rate: self.trades.len() as f64 * 1000.0 / self.window as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_trade(price: f64, quantity: f64, is_market_maker: bool) -> TradeEvent {
+        TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 0,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 1,
+            price,
+            quantity,
+            trade_time: 0,
+            is_market_maker,
+            ignore: false,
+            received: None,
+        }
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut window = TradeFlowWindow::new(2000);
+
+        window.on_trade(1000, &make_trade(100.0, 1.0, false));
+        window.on_trade(1500, &make_trade(102.0, 3.0, true));
+        window.on_trade(2500, &make_trade(101.0, 2.0, false));
+
+        let flow = window.flow(2900);
+        assert_eq!((flow.buy_volume, flow.sell_volume, flow.trade_count), (3.0, 3.0, 3));
+        assert!((flow.vwap.unwrap() - 608.0 / 6.0).abs() < 1e-9);
+        assert_eq!(flow.trade_rate, 1.5);
+
+        // The first trade has left the window
+        let flow = window.flow(3200);
+        assert_eq!((flow.buy_volume, flow.sell_volume, flow.trade_count), (2.0, 3.0, 2));
+
+        let flow = window.flow(10_000);
+        assert_eq!((flow.trade_count, flow.vwap, flow.trade_rate), (0, None, 0.0));
+    }
+}
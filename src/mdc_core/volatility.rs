@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::fmt;
+use serde::Serialize;
+
+/// Milliseconds in a year of 365 days, which volatility is annualized to
+const YEAR_MS: f64 = 365.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Realized volatility of the mid price and the average spread over a rolling window of samples
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VolatilityMetrics {
+    /// Local time of the latest sample in milliseconds since epoch
+    pub time: i64,
+    /// Length of the window in milliseconds
+    pub window: u64,
+    /// Number of samples in the window
+    pub samples: usize,
+    pub mid_price: f64,
+    pub spread: f64,
+    /// Square root of the sum of squared log returns of the mid price between the samples,
+    /// if the window has at least two samples
+    pub realized_volatility: Option<f64>,
+    /// Realized volatility scaled to a year from the time the samples cover
+    pub annualized_volatility: Option<f64>,
+    pub average_spread: f64,
+    /// Average spread in basis points of the mid price
+    pub average_spread_bps: f64,
+}
+
+impl fmt::Display for VolatilityMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Window: '{}' ms, Samples: '{}', Realized volatility: '{:?}', Annualized: '{:?}', Average spread: '{:.8}' ('{:.2}' bps)",
+            self.window,
+            self.samples,
+            self.realized_volatility,
+            self.annualized_volatility,
+            self.average_spread,
+            self.average_spread_bps,
+        )
+    }
+}
+
+struct Sample {
+    time: i64,
+    mid_price: f64,
+    spread: f64,
+}
+
+/// Keeps the mid price and spread samples of the last `window` milliseconds.
+/// Time is given explicitly (milliseconds since epoch)
+pub struct VolatilityWindow {
+    window: u64,
+    samples: VecDeque<Sample>,
+}
+
+impl VolatilityWindow {
+    /// Create a new VolatilityWindow
+    ///
+    /// # Arguments
+    /// * `window` - Length of the rolling window in milliseconds
+    pub fn new(window: u64) -> Self {
+        Self { window: window.max(1), samples: VecDeque::new() }
+    }
+
+    /// Add a sample, drop the samples, which have left the window, and compute the metrics of the remaining ones
+    ///
+    /// # Arguments
+    /// * `now` - Local time of the sample
+    /// * `mid_price` - Mid price of the book, must be positive
+    /// * `spread` - Best ask minus best bid
+    pub fn on_sample(&mut self, now: i64, mid_price: f64, spread: f64) -> VolatilityMetrics {
+        self.samples.push_back(Sample { time: now, mid_price, spread });
+
+        let start = now - self.window as i64;
+        while self.samples.front().is_some_and(|sample| sample.time <= start) {
+            self.samples.pop_front();
+        }
+
+        let count = self.samples.len() as f64;
+        let squared_returns: f64 = self.samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|(previous, next)| (next.mid_price / previous.mid_price).ln().powi(2))
+            .sum();
+        let realized_volatility = (self.samples.len() > 1).then(|| squared_returns.sqrt());
+
+        let covered = self.samples.back().map_or(0, |last| last.time) - self.samples.front().map_or(0, |first| first.time);
+        let annualized_volatility = realized_volatility
+            .filter(|_| covered > 0)
+            .map(|volatility| volatility * (YEAR_MS / covered as f64).sqrt());
+
+        VolatilityMetrics {
+            time: now,
+            window: self.window,
+            samples: self.samples.len(),
+            mid_price,
+            spread,
+            realized_volatility,
+            annualized_volatility,
+            average_spread: self.samples.iter().map(|sample| sample.spread).sum::<f64>() / count,
+            average_spread_bps: self.samples.iter().map(|sample| sample.spread / sample.mid_price * 10_000.0).sum::<f64>() / count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_volatility_and_spread() {
+        let mut window = VolatilityWindow::new(2000);
+
+        let first = window.on_sample(1000, 100.0, 1.0);
+        assert_eq!((first.samples, first.realized_volatility, first.annualized_volatility), (1, None, None));
+        assert_eq!(first.average_spread_bps, 100.0);
+
+        window.on_sample(2000, 110.0, 3.0);
+        let metrics = window.on_sample(2500, 99.0, 2.0);
+        let expected = ((1.1f64).ln().powi(2) + (0.9f64).ln().powi(2)).sqrt();
+        assert_eq!(metrics.samples, 3);
+        assert!((metrics.realized_volatility.unwrap() - expected).abs() < 1e-12);
+        assert!((metrics.annualized_volatility.unwrap() - expected * (YEAR_MS / 1500.0).sqrt()).abs() < 1e-6);
+        assert_eq!(metrics.average_spread, 2.0);
+
+        // The first sample has left the window
+        let metrics = window.on_sample(3000, 99.0, 2.0);
+        assert_eq!(metrics.samples, 3);
+        assert!((metrics.realized_volatility.unwrap() - (0.9f64).ln().abs()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,391 @@
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+#[cfg(unix)]
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, watch};
+use crate::mdc_server::config::Config;
+use crate::mdc_server::disk_space_guard::CaptureGate;
+use crate::mdc_server::live_status::{LiveStatus, StatusBoard};
+use crate::mdc_server::session_report::SessionReporter;
+use crate::mdc_server::supervisor::SupervisorHandle;
+#[cfg(unix)]
+use crate::mdc_server::log_context::spawn;
+
+/// Maximum length of a command line
+#[cfg(unix)]
+const MAX_COMMAND_LENGTH: u64 = 1024;
+
+/// Time a client has to send its command
+#[cfg(unix)]
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Path of the admin socket of the instance capturing the instrument from the exchange
+///
+/// Sockets are placed in the `.admin` subdirectory of the capture directory, next to the instance locks
+pub fn admin_socket_path<P: AsRef<Path>>(capture_dir: P, exchange: &str, instrument: &str) -> PathBuf {
+    capture_dir.as_ref().join(".admin").join(format!("{}_{}.sock", exchange, instrument))
+}
+
+/// Command accepted by the admin socket, sent as a single line
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    /// Serve the LiveStatus of the instance
+    Status,
+    /// Pause writing of capture files
+    Pause,
+    /// Resume writing of capture files paused with `Pause`
+    Resume,
+    /// Request a fresh depth snapshot, which restarts the book
+    Resync,
+    /// Write the rows buffered by batching sinks right away
+    Flush,
+    /// Write the SessionReport of the session and serve it
+    Report,
+    /// Serve the top levels of the maintained book
+    Book { depth: Option<usize> },
+    /// Start capturing another instrument with the configuration of this pipeline
+    Add { instrument: String },
+    /// Stop capturing an instrument of the process
+    Remove { instrument: String },
+}
+
+impl FromStr for AdminCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.as_slice() {
+            ["status"] => AdminCommand::Status,
+            ["pause"] => AdminCommand::Pause,
+            ["resume"] => AdminCommand::Resume,
+            ["resync"] => AdminCommand::Resync,
+            ["flush"] => AdminCommand::Flush,
+            ["report"] => AdminCommand::Report,
+            ["book"] => AdminCommand::Book { depth: None },
+            ["book", depth] => AdminCommand::Book {
+                depth: Some(depth.parse().with_context(|| format!("Invalid book depth: '{}'", depth))?),
+            },
+            ["add", instrument] => AdminCommand::Add { instrument: instrument.to_string() },
+            ["remove", instrument] => AdminCommand::Remove { instrument: instrument.to_string() },
+            _ => return Err(anyhow!(
+                "Unknown admin command: '{}'. Expected one of: status, pause, resume, resync, flush, report, book [depth], \
+                 add <symbol>, remove <symbol>",
+                line.trim()
+            )),
+        };
+
+        Ok(command)
+    }
+}
+
+impl fmt::Display for AdminCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminCommand::Status => write!(f, "status"),
+            AdminCommand::Pause => write!(f, "pause"),
+            AdminCommand::Resume => write!(f, "resume"),
+            AdminCommand::Resync => write!(f, "resync"),
+            AdminCommand::Flush => write!(f, "flush"),
+            AdminCommand::Report => write!(f, "report"),
+            AdminCommand::Book { depth: None } => write!(f, "book"),
+            AdminCommand::Book { depth: Some(depth) } => write!(f, "book {}", depth),
+            AdminCommand::Add { instrument } => write!(f, "add {}", instrument),
+            AdminCommand::Remove { instrument } => write!(f, "remove {}", instrument),
+        }
+    }
+}
+
+/// Handles of the running pipeline, which the admin socket controls
+#[derive(Clone)]
+pub struct AdminControls {
+    /// Configuration of the pipeline, the template of added pipelines
+    pub config: Config,
+    pub board: StatusBoard,
+    pub gate: CaptureGate,
+    /// Requests of a fresh snapshot from the snapshot stream
+    pub snapshot_requests: mpsc::Sender<()>,
+    /// Requests to flush the batching sinks
+    pub flush_requests: watch::Sender<()>,
+    /// Writer of the data quality report of the session
+    pub reporter: SessionReporter,
+    /// Supervisor of the pipelines of the process, if pipelines can be added and removed
+    pub supervisor: Option<SupervisorHandle>,
+}
+
+impl AdminControls {
+    /// Execute the command
+    ///
+    /// # Returns
+    /// The JSON document, which is sent back to the client
+    pub async fn execute(&self, command: AdminCommand) -> Result<serde_json::Value> {
+        let message = match command {
+            AdminCommand::Status => return Ok(serde_json::to_value(self.board.snapshot())?),
+            AdminCommand::Book { depth } => {
+                let frame = self.board
+                    .book_frame(depth.unwrap_or(self.config.output_depth))
+                    .ok_or_else(|| anyhow!("Order book is not available yet"))?;
+                return Ok(serde_json::to_value(frame)?);
+            }
+            AdminCommand::Report => {
+                let report = self.reporter.write()?;
+                tracing::info!("Session report written to {:?}. {}", self.reporter.path(), report);
+                return Ok(serde_json::to_value(report)?);
+            }
+            AdminCommand::Pause => {
+                self.gate.set_held(true);
+                "Capture paused".to_string()
+            }
+            AdminCommand::Resume => {
+                self.gate.set_held(false);
+                match self.gate.is_paused() {
+                    true => "Capture resumed, but it stays paused until there is enough free space".to_string(),
+                    false => "Capture resumed".to_string(),
+                }
+            }
+            AdminCommand::Resync => {
+                // A full channel means that a snapshot has already been requested
+                let _ = self.snapshot_requests.try_send(());
+                "Snapshot requested".to_string()
+            }
+            AdminCommand::Flush => {
+                self.flush_requests.send_replace(());
+                "Flush requested".to_string()
+            }
+            AdminCommand::Add { instrument } => self.supervisor()?.add(&self.config, &instrument).await?,
+            AdminCommand::Remove { instrument } => self.supervisor()?.remove(self.config.exchange, &instrument).await?,
+        };
+
+        tracing::info!("Admin command: '{}'", message);
+        Ok(json!({ "result": message }))
+    }
+
+    fn supervisor(&self) -> Result<&SupervisorHandle> {
+        self.supervisor.as_ref().ok_or_else(|| anyhow!("Pipelines can't be added or removed in this mode"))
+    }
+}
+
+/// A Unix socket, which serves the LiveStatus of the running instance and accepts control commands
+///
+/// Every connection sends a single command line and receives a single JSON document, after which the connection
+/// is closed. Failed commands are answered with `{"error": <message>}`. Windows builds have no admin socket
+#[cfg(unix)]
+pub struct AdminSocket {
+    path: PathBuf,
+    listener: UnixListener,
+    controls: AdminControls,
+}
+
+#[cfg(unix)]
+impl AdminSocket {
+    /// Bind the admin socket
+    ///
+    /// A socket file left at the path is replaced. The caller must hold the instance lock,
+    /// which guarantees that the file doesn't belong to another running instance
+    ///
+    /// # Arguments
+    /// * `path` - Path of the socket file
+    /// * `controls` - Handles of the pipeline, which is served
+    pub fn bind(path: PathBuf, controls: AdminControls) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create admin socket directory: {:?}", parent))?;
+        }
+
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to bind admin socket: {:?}", path))?;
+
+        Ok(Self { path, listener, controls })
+    }
+
+    /// Run the AdminSocket as an asynchronous task
+    pub async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((mut stream, _)) => {
+                    let controls = self.controls.clone();
+                    spawn(async move {
+                        if let Err(e) = Self::respond(&mut stream, &controls).await {
+                            tracing::warn!("Failed to respond on admin socket. Details: '{}'", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    tracing::error!("Failed to accept admin socket connection on {:?}. Details: '{}'", self.path, e);
+                }
+            }
+        }
+    }
+
+    async fn respond(stream: &mut UnixStream, controls: &AdminControls) -> Result<()> {
+        let mut line = String::new();
+        let mut reader = BufReader::new(&mut *stream).take(MAX_COMMAND_LENGTH);
+        tokio::time::timeout(COMMAND_TIMEOUT, reader.read_line(&mut line))
+            .await
+            .context("Admin command was not received in time")??;
+
+        let response = match line.parse::<AdminCommand>() {
+            Ok(command) => controls.execute(command).await,
+            Err(e) => Err(e),
+        };
+        let response = response.unwrap_or_else(|e| json!({ "error": format!("{:#}", e) }));
+
+        stream.write_all(&serde_json::to_vec(&response)?).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for AdminSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Send a command to a running instance over its admin socket
+///
+/// # Returns
+/// The JSON response of the instance, or an error if the instance rejected the command
+#[cfg(unix)]
+pub async fn send_command(path: &Path, command: &AdminCommand) -> Result<String> {
+    let mut stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to admin socket {:?}. Is the instance running?", path))?;
+
+    stream.write_all(format!("{}\n", command).as_bytes()).await
+        .context("Failed to send command to admin socket")?;
+
+    let mut data = String::new();
+    stream.read_to_string(&mut data).await
+        .context("Failed to read response from admin socket")?;
+
+    if let Ok(serde_json::Value::Object(response)) = serde_json::from_str(&data) {
+        if let Some(error) = response.get("error").and_then(|error| error.as_str()) {
+            return Err(anyhow!("'{}' failed: {}", command, error));
+        }
+    }
+
+    Ok(data)
+}
+
+/// Windows builds have no admin socket, so running instances can't be queried or controlled
+#[cfg(windows)]
+pub async fn send_command(path: &Path, command: &AdminCommand) -> Result<String> {
+    Err(anyhow!("'{}' failed: the admin socket {:?} is only served on Unix", command, path))
+}
+
+/// Request the LiveStatus of a running instance over its admin socket
+pub async fn query_status(path: &Path) -> Result<LiveStatus> {
+    let data = send_command(path, &AdminCommand::Status).await?;
+    serde_json::from_str(&data).context("Failed to parse status")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::mdc_server::config::load_pipelines_from_yaml_str;
+    use crate::mdc_server::metrics::Metrics;
+    use crate::mdc_server::session_report::SessionReport;
+
+    fn make_controls(board: StatusBoard) -> (AdminControls, mpsc::Receiver<()>, watch::Receiver<()>) {
+        let config = load_pipelines_from_yaml_str(r#"
+rest_endpoint: "https://api.example.com"
+wss_endpoint: "wss://stream.example.com"
+instrument: "BTCUSDT"
+connections: 1
+reconnect_timeout: 1000
+snapshot_update_interval: 1000
+max_depth: 1000
+output_depth: 5
+"#).unwrap().remove(0);
+        let (snapshot_requests, snapshot_receiver) = mpsc::channel(1);
+        let (flush_requests, flush_receiver) = watch::channel(());
+        let report_path = std::env::temp_dir().join(format!("mdc_test_{}_admin.report.json", std::process::id()));
+
+        let controls = AdminControls {
+            config,
+            reporter: SessionReporter::new(board.clone(), "BTCUSDT_20240101_000000".to_string(), report_path),
+            board,
+            gate: CaptureGate::new(),
+            snapshot_requests,
+            flush_requests,
+            supervisor: None,
+        };
+        (controls, snapshot_receiver, flush_receiver)
+    }
+
+    #[test]
+    fn test_command_parsing() {
+        assert_eq!("status\n".parse::<AdminCommand>().unwrap(), AdminCommand::Status);
+        assert_eq!("book 20".parse::<AdminCommand>().unwrap(), AdminCommand::Book { depth: Some(20) });
+        assert_eq!(" add  ETHUSDT ".parse::<AdminCommand>().unwrap(), AdminCommand::Add { instrument: "ETHUSDT".to_string() });
+        assert!("book many".parse::<AdminCommand>().is_err());
+        assert!("remove".parse::<AdminCommand>().is_err());
+        assert!("restart".parse::<AdminCommand>().is_err());
+
+        let command = AdminCommand::Book { depth: Some(5) };
+        assert_eq!(command.to_string().parse::<AdminCommand>().unwrap(), command);
+    }
+
+    #[tokio::test]
+    async fn test_query_status() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_{}", std::process::id(), "admin_socket"));
+        let path = admin_socket_path(&dir, "binance", "BTCUSDT");
+        let board = StatusBoard::new("binance", "BTCUSDT", Metrics::new());
+        board.stream("depth#0".to_string()).on_connected();
+
+        let (controls, _, _) = make_controls(board);
+        let socket = AdminSocket::bind(path.clone(), controls).unwrap();
+        tokio::spawn(socket.run());
+
+        let status = query_status(&path).await.unwrap();
+        assert_eq!(status.instrument, "BTCUSDT");
+        assert!(status.streams["depth#0"].connected);
+
+        assert!(query_status(&dir.join("missing.sock")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_control_commands() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_{}", std::process::id(), "admin_commands"));
+        let path = admin_socket_path(&dir, "binance", "BTCUSDT");
+        let (controls, mut snapshot_requests, flush_requests) = make_controls(StatusBoard::new("binance", "BTCUSDT", Metrics::new()));
+        let gate = controls.gate.clone();
+
+        let socket = AdminSocket::bind(path.clone(), controls).unwrap();
+        tokio::spawn(socket.run());
+
+        send_command(&path, &AdminCommand::Pause).await.unwrap();
+        assert!(gate.is_paused());
+        send_command(&path, &AdminCommand::Resume).await.unwrap();
+        assert!(!gate.is_paused());
+
+        send_command(&path, &AdminCommand::Resync).await.unwrap();
+        send_command(&path, &AdminCommand::Resync).await.unwrap();
+        assert!(snapshot_requests.try_recv().is_ok());
+        assert!(snapshot_requests.try_recv().is_err());
+
+        send_command(&path, &AdminCommand::Flush).await.unwrap();
+        assert!(flush_requests.has_changed().unwrap());
+
+        let report: SessionReport = serde_json::from_str(&send_command(&path, &AdminCommand::Report).await.unwrap()).unwrap();
+        assert_eq!((report.session.as_str(), report.gaps), ("BTCUSDT_20240101_000000", 0));
+        let report_path = std::env::temp_dir().join(format!("mdc_test_{}_admin.report.json", std::process::id()));
+        assert!(report_path.exists());
+        std::fs::remove_file(report_path).unwrap();
+
+        let error = send_command(&path, &AdminCommand::Book { depth: None }).await.unwrap_err();
+        assert!(error.to_string().contains("not available"));
+        assert!(send_command(&path, &AdminCommand::Add { instrument: "ETHUSDT".to_string() }).await.is_err());
+    }
+}
@@ -0,0 +1,617 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio::sync::mpsc;
+use crate::mdc_core::anomaly::AnomalyEvent;
+use crate::mdc_server::live_status::{LiveStatus, StatusBoard};
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_server::output_tiers::BookFrame;
+use crate::mdc_server::transport::Transport;
+
+/// Timeout of a single delivery of an alert
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_alert_interval() -> u64 {
+    1000
+}
+
+fn default_alert_cooldown() -> u64 {
+    300000
+}
+
+fn default_telegram_api() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+/// Alerting on the data quality of the pipeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alerting {
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+    /// Destinations, which every alert is posted to
+    #[serde(default)]
+    pub destinations: Vec<AlertDestination>,
+    /// Interval between the checks of the rules in milliseconds
+    #[serde(default = "default_alert_interval")]
+    pub interval: u64,
+    /// Minimum time in milliseconds between two alerts of the same event rule (`gap`, `price_cross`)
+    #[serde(default = "default_alert_cooldown")]
+    pub cooldown: u64,
+}
+
+/// A condition, which raises an alert
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertRule {
+    /// A stream is disconnected for longer than `seconds`. Resolved once it is connected again
+    StreamDown { seconds: u64 },
+    /// A gap in the depth updates is detected
+    Gap,
+    /// The spread of the book exceeds `bps` basis points of the mid price for `seconds`. Resolved once it is below again
+    SpreadAbove {
+        bps: f64,
+        #[serde(default)]
+        seconds: u64,
+    },
+    /// The `field` price of the book is below `price` for `seconds`. Resolved once it is not below anymore
+    PriceBelow {
+        price: f64,
+        #[serde(default)]
+        field: PriceField,
+        #[serde(default)]
+        seconds: u64,
+        /// Instrument, which the rule applies to. All instruments if not set
+        #[serde(default)]
+        instrument: Option<String>,
+    },
+    /// The `field` price of the book is above `price` for `seconds`. Resolved once it is not above anymore
+    PriceAbove {
+        price: f64,
+        #[serde(default)]
+        field: PriceField,
+        #[serde(default)]
+        seconds: u64,
+        /// Instrument, which the rule applies to. All instruments if not set
+        #[serde(default)]
+        instrument: Option<String>,
+    },
+    /// The mid price crosses `price` in either direction
+    PriceCross { price: f64 },
+    /// The anomaly detection detects an anomaly (see `anomaly_detection`)
+    Anomaly,
+}
+
+/// A price of the top of the book, which a price threshold rule is checked against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceField {
+    BestBid,
+    BestAsk,
+    #[default]
+    Mid,
+}
+
+impl PriceField {
+    fn name(&self) -> &'static str {
+        match self {
+            PriceField::BestBid => "Best bid",
+            PriceField::BestAsk => "Best ask",
+            PriceField::Mid => "Mid price",
+        }
+    }
+}
+
+/// A destination, which alerts are posted to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertDestination {
+    /// The alert as JSON
+    Webhook { url: String },
+    /// A Slack incoming webhook
+    Slack { webhook_url: String },
+    /// A Telegram chat. The bot token is taken from `TELEGRAM_BOT_TOKEN` if not set
+    Telegram {
+        #[serde(default)]
+        bot_token: Option<String>,
+        chat_id: String,
+        #[serde(default = "default_telegram_api")]
+        api_url: String,
+    },
+}
+
+impl AlertDestination {
+    /// Create the channel delivering alerts to the destination with the proxy and TLS settings of the transport
+    pub fn channel(&self, transport: Transport) -> Result<HttpAlertChannel> {
+        let endpoint = match self {
+            AlertDestination::Webhook { url } => url,
+            AlertDestination::Slack { webhook_url } => webhook_url,
+            AlertDestination::Telegram { api_url, .. } => api_url,
+        };
+        let http = transport.http_client(endpoint)?.timeout(DELIVERY_TIMEOUT).build()?;
+        let destination = match self {
+            AlertDestination::Telegram { bot_token: None, chat_id, api_url } => {
+                let bot_token = std::env::var("TELEGRAM_BOT_TOKEN")
+                    .map_err(|_| anyhow!("Telegram bot token is missing: 'TELEGRAM_BOT_TOKEN' is not set"))?;
+                AlertDestination::Telegram { bot_token: Some(bot_token), chat_id: chat_id.clone(), api_url: api_url.clone() }
+            }
+            destination => destination.clone(),
+        };
+        Ok(HttpAlertChannel { http, transport, destination })
+    }
+
+    /// URL and JSON body of the request, which posts the alert
+    fn request(&self, alert: &Alert) -> (String, serde_json::Value) {
+        match self {
+            AlertDestination::Webhook { url } => (url.clone(), json!(alert)),
+            AlertDestination::Slack { webhook_url } => (webhook_url.clone(), json!({ "text": alert.text() })),
+            AlertDestination::Telegram { bot_token, chat_id, api_url } => (
+                format!("{}/bot{}/sendMessage", api_url.trim_end_matches('/'), bot_token.as_deref().unwrap_or_default()),
+                json!({ "chat_id": chat_id, "text": alert.text() }),
+            ),
+        }
+    }
+}
+
+/// A raised or resolved alert
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    /// Rule, which raised the alert, e.g. `stream_down`
+    pub rule: String,
+    pub exchange: String,
+    pub instrument: String,
+    pub message: String,
+    /// Local time in milliseconds since epoch
+    pub time: i64,
+    /// Whether the condition of the alert is over
+    pub resolved: bool,
+}
+
+impl Alert {
+    /// Text of the alert for chat destinations
+    pub fn text(&self) -> String {
+        let prefix = if self.resolved { "Resolved: " } else { "" };
+        format!("[{} {}] {}{}", self.exchange, self.instrument, prefix, self.message)
+    }
+}
+
+/// A destination, which alerts are delivered to
+pub trait AlertChannel: Send + Sync {
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Delivers alerts to an HTTP destination
+pub struct HttpAlertChannel {
+    http: reqwest::Client,
+    transport: Transport,
+    destination: AlertDestination,
+}
+
+impl AlertChannel for HttpAlertChannel {
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let (url, body) = self.destination.request(alert);
+            let response = self.http.post(url).json(&body).send().await?;
+            self.transport.verify(&response)?;
+            response.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Evaluates the alert rules against the state of the pipeline
+///
+/// `stream_down`, `spread_above`, `price_below` and `price_above` are conditions: they raise one alert when the
+/// condition has held for the configured time and another one when it is over. `gap`, `price_cross` and `anomaly`
+/// are events, raised at most once per cooldown (anomalies once per cooldown and kind)
+struct AlertEvaluator {
+    rules: Vec<AlertRule>,
+    cooldown: i64,
+    /// Time since which each stream is disconnected
+    down_since: BTreeMap<String, i64>,
+    /// Time since which each threshold rule is breached, e.g. `spread_above:0`
+    breached_since: BTreeMap<String, i64>,
+    /// Active conditions, e.g. `stream_down:depth#0`
+    active: BTreeSet<String>,
+    /// Time of the last alert of each event rule, e.g. `gap` or `anomaly:stale_book`
+    last_event: BTreeMap<String, i64>,
+    depth_gaps: Option<u64>,
+    /// Mid price at the previous check
+    mid_price: Option<f64>,
+}
+
+impl AlertEvaluator {
+    fn new(rules: Vec<AlertRule>, cooldown: u64) -> Self {
+        Self {
+            rules,
+            cooldown: cooldown as i64,
+            down_since: BTreeMap::new(),
+            breached_since: BTreeMap::new(),
+            active: BTreeSet::new(),
+            last_event: BTreeMap::new(),
+            depth_gaps: None,
+            mid_price: None,
+        }
+    }
+
+    /// Raise or resolve the condition identified by `key`
+    ///
+    /// # Returns
+    /// `Some(resolved)` if the state of the condition changed
+    fn condition(&mut self, key: String, raised: bool) -> Option<bool> {
+        match raised {
+            true => self.active.insert(key).then_some(false),
+            false => self.active.remove(&key).then_some(true),
+        }
+    }
+
+    /// Raise or resolve the threshold condition identified by `key`, which is raised once it is breached for `seconds`
+    ///
+    /// # Returns
+    /// `Some(resolved)` if the state of the condition changed
+    fn threshold(&mut self, key: String, breached: bool, seconds: u64, now: i64) -> Option<bool> {
+        let raised = match breached {
+            true => now - *self.breached_since.entry(key.clone()).or_insert(now) >= seconds as i64 * 1000,
+            false => {
+                self.breached_since.remove(&key);
+                false
+            }
+        };
+        self.condition(key, raised)
+    }
+
+    /// Whether the event rule may raise an alert, recording it if so
+    fn event(&mut self, key: String, now: i64) -> bool {
+        if self.last_event.get(&key).is_some_and(|last| now - last < self.cooldown) {
+            return false;
+        }
+        self.last_event.insert(key, now);
+        true
+    }
+
+    /// Check an anomaly detected by the anomaly detection
+    ///
+    /// # Returns
+    /// `(rule, message)` of the alert, if it is raised
+    fn on_anomaly(&mut self, anomaly: &AnomalyEvent, now: i64) -> Option<(&'static str, String)> {
+        if !self.rules.contains(&AlertRule::Anomaly) || !self.event(format!("anomaly:{}", anomaly.anomaly.kind()), now) {
+            return None;
+        }
+        Some(("anomaly", anomaly.anomaly.to_string()))
+    }
+
+    /// Check the rules
+    ///
+    /// # Arguments
+    /// * `status` - Current status of the pipeline
+    /// * `top` - Top of the maintained book, if it is built
+    /// * `now` - Local time in milliseconds since epoch
+    ///
+    /// # Returns
+    /// `(rule, message, resolved)` of every raised or resolved alert
+    fn evaluate(&mut self, status: &LiveStatus, top: Option<&BookFrame>, now: i64) -> Vec<(&'static str, String, bool)> {
+        let mut alerts = Vec::new();
+
+        for (name, stream) in &status.streams {
+            if stream.connected {
+                self.down_since.remove(name);
+            } else {
+                self.down_since.entry(name.clone()).or_insert(now);
+            }
+        }
+
+        let gaps = status.counters.get("depth_gaps").copied().unwrap_or_default();
+        let new_gaps = gaps.saturating_sub(self.depth_gaps.unwrap_or(gaps));
+        self.depth_gaps = Some(gaps);
+
+        let best = |levels: &[[f64; 2]]| levels.first().map(|level| level[0]);
+        let (bid, ask) = top.map_or((None, None), |top| (best(&top.bids), best(&top.asks)));
+        let mid_price = bid.zip(ask).map(|(bid, ask)| (bid + ask) / 2.0);
+        let field_price = |field: PriceField| match field {
+            PriceField::BestBid => bid,
+            PriceField::BestAsk => ask,
+            PriceField::Mid => mid_price,
+        };
+        let duration = |seconds: u64| if seconds > 0 { format!(" for {} s", seconds) } else { String::new() };
+        let previous_mid_price = self.mid_price;
+        self.mid_price = mid_price.or(previous_mid_price);
+
+        for (index, rule) in self.rules.clone().into_iter().enumerate() {
+            match rule {
+                AlertRule::StreamDown { seconds } => {
+                    for name in status.streams.keys() {
+                        let down_for = self.down_since.get(name).map(|since| now - since);
+                        let raised = down_for.is_some_and(|down_for| down_for > seconds as i64 * 1000);
+                        match self.condition(format!("stream_down:{}", name), raised) {
+                            Some(false) => alerts.push(("stream_down", format!("Stream '{}' is down for more than {} s", name, seconds), false)),
+                            Some(true) => alerts.push(("stream_down", format!("Stream '{}' is connected again", name), true)),
+                            None => {}
+                        }
+                    }
+                }
+                AlertRule::Gap => {
+                    if new_gaps > 0 && self.event(format!("gap:{}", index), now) {
+                        alerts.push(("gap", format!("'{}' gaps detected in the depth updates", new_gaps), false));
+                    }
+                }
+                AlertRule::SpreadAbove { bps, seconds } => {
+                    let spread = bid.zip(ask).zip(mid_price).map(|((bid, ask), mid)| (ask - bid) / mid * 10000.0);
+                    // Without a book the condition keeps its state
+                    let Some(spread) = spread else {
+                        continue;
+                    };
+                    match self.threshold(format!("spread_above:{}", index), spread > bps, seconds, now) {
+                        Some(false) => alerts.push(("spread_above", format!("Spread is {:.1} bps, above {} bps{}", spread, bps, duration(seconds)), false)),
+                        Some(true) => alerts.push(("spread_above", format!("Spread is {:.1} bps, below {} bps again", spread, bps), true)),
+                        None => {}
+                    }
+                }
+                AlertRule::PriceBelow { price: threshold, field, seconds, ref instrument }
+                | AlertRule::PriceAbove { price: threshold, field, seconds, ref instrument } => {
+                    if instrument.as_ref().is_some_and(|instrument| !instrument.eq_ignore_ascii_case(&status.instrument)) {
+                        continue;
+                    }
+                    let Some(price) = field_price(field) else {
+                        continue;
+                    };
+                    let (name, breached, direction) = match rule {
+                        AlertRule::PriceBelow { .. } => ("price_below", price < threshold, "below"),
+                        _ => ("price_above", price > threshold, "above"),
+                    };
+                    match self.threshold(format!("{}:{}", name, index), breached, seconds, now) {
+                        Some(false) => alerts.push((name, format!("{} {} is {} {}{}", field.name(), price, direction, threshold, duration(seconds)), false)),
+                        Some(true) => alerts.push((name, format!("{} {} is no longer {} {}", field.name(), price, direction, threshold), true)),
+                        None => {}
+                    }
+                }
+                // Anomalies are checked as they are detected
+                AlertRule::Anomaly => {}
+                AlertRule::PriceCross { price } => {
+                    let (Some(previous), Some(current)) = (previous_mid_price, mid_price) else {
+                        continue;
+                    };
+                    let direction = match (previous < price, current < price) {
+                        (true, false) => "above",
+                        (false, true) => "below",
+                        _ => continue,
+                    };
+                    if self.event(format!("price_cross:{}", index), now) {
+                        alerts.push(("price_cross", format!("Mid price {} crossed {} {}", current, direction, price), false));
+                    }
+                }
+            }
+        }
+
+        alerts
+    }
+}
+
+/// AlertEngine checks the alert rules against the status of the pipeline and posts the alerts to the destinations
+///
+/// Failed deliveries are logged and counted by `alert_delivery_failures`, delivered alerts by `alerts_sent`
+pub struct AlertEngine {
+    board: StatusBoard,
+    evaluator: AlertEvaluator,
+    channels: Vec<Box<dyn AlertChannel>>,
+    interval: u64,
+    anomalies: Option<mpsc::Receiver<AnomalyEvent>>,
+    sent: Counter,
+    failures: Counter,
+}
+
+impl AlertEngine {
+    /// Create a new AlertEngine
+    ///
+    /// # Arguments
+    /// * `alerting` - The rules and the check interval
+    /// * `board` - Status board of the pipeline
+    /// * `channels` - Channels to the destinations of the alerts
+    /// * `metrics` - Registry of the delivery counters
+    pub fn new(alerting: &Alerting, board: StatusBoard, channels: Vec<Box<dyn AlertChannel>>, metrics: &Metrics) -> Self {
+        Self {
+            board,
+            evaluator: AlertEvaluator::new(alerting.rules.clone(), alerting.cooldown),
+            channels,
+            interval: alerting.interval,
+            anomalies: None,
+            sent: metrics.counter("alerts_sent"),
+            failures: metrics.counter("alert_delivery_failures"),
+        }
+    }
+
+    /// Alert on the anomalies received from the anomaly engine
+    pub fn with_anomalies(mut self, anomalies: mpsc::Receiver<AnomalyEvent>) -> Self {
+        self.anomalies = Some(anomalies);
+        self
+    }
+
+    async fn deliver(&self, status: &LiveStatus, rule: &str, message: String, time: i64, resolved: bool) {
+        let alert = Alert {
+            rule: rule.to_string(),
+            exchange: status.exchange.clone(),
+            instrument: status.instrument.clone(),
+            message,
+            time,
+            resolved,
+        };
+        tracing::warn!("Alert: '{}'", alert.text());
+
+        for channel in &self.channels {
+            match channel.send(&alert).await {
+                Ok(()) => self.sent.increment(1),
+                Err(e) => {
+                    self.failures.increment(1);
+                    tracing::error!("Failed to deliver alert. Details: '{:#}'", e);
+                }
+            }
+        }
+    }
+
+    async fn check(&mut self) {
+        let status = self.board.snapshot();
+        let top = self.board.book_frame(1);
+        let now = Utc::now().timestamp_millis();
+
+        for (rule, message, resolved) in self.evaluator.evaluate(&status, top.as_ref(), now) {
+            self.deliver(&status, rule, message, now, resolved).await;
+        }
+    }
+
+    async fn on_anomaly(&mut self, anomaly: AnomalyEvent) {
+        let now = Utc::now().timestamp_millis();
+        if let Some((rule, message)) = self.evaluator.on_anomaly(&anomaly, now) {
+            self.deliver(&self.board.snapshot(), rule, message, now, false).await;
+        }
+    }
+
+    /// Run the AlertEngine as an asynchronous task
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(self.interval.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut anomalies = self.anomalies.take();
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.check().await,
+                Some(anomaly) = async { anomalies.as_mut()?.recv().await } => self.on_anomaly(anomaly).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::anomaly::Anomaly;
+    use crate::mdc_core::order_book::Side;
+    use crate::mdc_server::live_status::StreamStatus;
+
+    fn status(connected: bool, gaps: u64) -> LiveStatus {
+        let mut status = StatusBoard::new("binance", "BTCUSDT", Metrics::new()).snapshot();
+        status.streams.insert("depth#0".to_string(), StreamStatus { connected, ..Default::default() });
+        status.counters.insert("depth_gaps".to_string(), gaps);
+        status
+    }
+
+    fn top(bid: f64, ask: f64) -> BookFrame {
+        BookFrame { sequence: 1, time: 0, bids: vec![[bid, 1.0]], asks: vec![[ask, 1.0]] }
+    }
+
+    fn rules(alerts: Vec<(&'static str, String, bool)>) -> Vec<(&'static str, bool)> {
+        alerts.into_iter().map(|(rule, _, resolved)| (rule, resolved)).collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut evaluator = AlertEvaluator::new(vec![
+            AlertRule::StreamDown { seconds: 5 },
+            AlertRule::Gap,
+            AlertRule::SpreadAbove { bps: 50.0, seconds: 0 },
+            AlertRule::PriceCross { price: 100.0 },
+        ], 60000);
+
+        // Gaps before the first check don't raise an alert
+        assert!(evaluator.evaluate(&status(false, 3), Some(&top(99.0, 99.1)), 0).is_empty());
+        assert!(evaluator.evaluate(&status(false, 3), Some(&top(99.0, 99.1)), 5000).is_empty());
+        assert_eq!(rules(evaluator.evaluate(&status(false, 3), Some(&top(99.0, 99.1)), 5001)), vec![("stream_down", false)]);
+        assert!(evaluator.evaluate(&status(false, 3), Some(&top(99.0, 99.1)), 6000).is_empty());
+
+        let alerts = evaluator.evaluate(&status(true, 4), Some(&top(100.0, 101.0)), 7000);
+        assert_eq!(rules(alerts.clone()), vec![("stream_down", true), ("gap", false), ("spread_above", false), ("price_cross", false)]);
+        assert_eq!(alerts[3].1, "Mid price 100.5 crossed above 100");
+
+        let anomaly = AnomalyEvent { time: 7000, anomaly: Anomaly::StaleBook { age: 6000 } };
+        assert!(evaluator.on_anomaly(&anomaly, 7000).is_none());
+
+        // Events are raised at most once per cooldown
+        assert_eq!(rules(evaluator.evaluate(&status(true, 5), Some(&top(99.0, 99.1)), 8000)), vec![("spread_above", true)]);
+        assert_eq!(rules(evaluator.evaluate(&status(true, 6), Some(&top(100.0, 100.1)), 68000)), vec![("gap", false), ("price_cross", false)]);
+
+        let mut evaluator = AlertEvaluator::new(vec![AlertRule::Anomaly], 60000);
+        assert_eq!(evaluator.on_anomaly(&anomaly, 7000), Some(("anomaly", "Book hasn't been updated for '6000' ms".to_string())));
+        assert!(evaluator.on_anomaly(&anomaly, 8000).is_none());
+        let wiped = AnomalyEvent { time: 8000, anomaly: Anomaly::SideWiped { side: Side::Bid } };
+        assert!(evaluator.on_anomaly(&wiped, 8000).is_some());
+    }
+
+    #[test]
+    fn test_price_thresholds() {
+        let mut evaluator = AlertEvaluator::new(vec![
+            AlertRule::PriceBelow { price: 100.0, field: PriceField::BestBid, seconds: 0, instrument: Some("btcusdt".to_string()) },
+            AlertRule::PriceAbove { price: 100.0, field: PriceField::Mid, seconds: 0, instrument: Some("ETHUSDT".to_string()) },
+            AlertRule::SpreadAbove { bps: 50.0, seconds: 10 },
+        ], 60000);
+
+        let alerts = evaluator.evaluate(&status(true, 0), Some(&top(99.0, 101.0)), 0);
+        assert_eq!(alerts, vec![("price_below", "Best bid 99 is below 100".to_string(), false)]);
+
+        // The spread has to stay above the threshold for 10 seconds
+        assert!(evaluator.evaluate(&status(true, 0), Some(&top(99.0, 101.0)), 9999).is_empty());
+        assert_eq!(rules(evaluator.evaluate(&status(true, 0), Some(&top(99.0, 101.0)), 10000)), vec![("spread_above", false)]);
+
+        let alerts = evaluator.evaluate(&status(true, 0), Some(&top(100.0, 100.1)), 11000);
+        assert_eq!(rules(alerts.clone()), vec![("price_below", true), ("spread_above", true)]);
+        assert_eq!(alerts[0].1, "Best bid 100 is no longer below 100");
+
+        // A breach shorter than the duration doesn't raise an alert
+        assert!(evaluator.evaluate(&status(true, 0), Some(&top(100.0, 101.0)), 12000).is_empty());
+        assert!(evaluator.evaluate(&status(true, 0), Some(&top(100.0, 100.1)), 13000).is_empty());
+        assert!(evaluator.evaluate(&status(true, 0), Some(&top(100.0, 101.0)), 21000).is_empty());
+    }
+
+    #[test]
+    fn test_alerting_from_yaml() {
+        let alerting: Alerting = serde_yaml::from_str(r#"
+rules:
+  - { type: stream_down, seconds: 30 }
+  - { type: gap }
+  - { type: price_below, instrument: BTCUSDT, field: best_bid, price: 95000.0 }
+  - { type: spread_above, bps: 20.0, seconds: 30 }
+destinations:
+  - { type: telegram, chat_id: "-100" }
+"#).unwrap();
+        assert_eq!(alerting.rules, vec![
+            AlertRule::StreamDown { seconds: 30 },
+            AlertRule::Gap,
+            AlertRule::PriceBelow { price: 95000.0, field: PriceField::BestBid, seconds: 0, instrument: Some("BTCUSDT".to_string()) },
+            AlertRule::SpreadAbove { bps: 20.0, seconds: 30 },
+        ]);
+        assert_eq!(alerting.destinations, vec![AlertDestination::Telegram {
+            bot_token: None,
+            chat_id: "-100".to_string(),
+            api_url: default_telegram_api(),
+        }]);
+        assert_eq!((alerting.interval, alerting.cooldown), (1000, 300000));
+    }
+
+    #[test]
+    fn test_destination_requests() {
+        let alert = Alert {
+            rule: "gap".to_string(),
+            exchange: "binance".to_string(),
+            instrument: "BTCUSDT".to_string(),
+            message: "'1' gaps detected in the depth updates".to_string(),
+            time: 1000,
+            resolved: false,
+        };
+
+        let slack = AlertDestination::Slack { webhook_url: "https://hooks.slack.com/services/T/B/X".to_string() };
+        assert_eq!(slack.request(&alert).1, json!({ "text": "[binance BTCUSDT] '1' gaps detected in the depth updates" }));
+
+        let telegram = AlertDestination::Telegram {
+            bot_token: Some("123:abc".to_string()),
+            chat_id: "-100".to_string(),
+            api_url: default_telegram_api(),
+        };
+        let (url, body) = telegram.request(&alert);
+        assert_eq!(url, "https://api.telegram.org/bot123:abc/sendMessage");
+        assert_eq!(body["chat_id"], "-100");
+
+        let webhook = AlertDestination::Webhook { url: "http://localhost/alerts".to_string() };
+        assert_eq!(serde_json::from_value::<Alert>(webhook.request(&alert).1).unwrap(), alert);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use crate::mdc_core::models::{MarketEvent, PriceUpdate};
+use crate::mdc_server::log_context::{spawn, stream_span};
+use crate::mdc_server::market_event_stream::MarketEventStream;
+use crate::mdc_server::transport::Transport;
+use crate::mdc_server::tape::TapeRecorder;
+
+/// Price updates of all symbols, which the shared connection may buffer before the demultiplexer
+const PRICE_BUFFER: usize = 10_000;
+
+/// Source of the best bid/ask updates of a pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    /// The bookTicker stream of the instrument, `price_connections` connections
+    #[default]
+    Symbol,
+    /// The all-market bookTicker stream (`!bookTicker`), shared by the pipelines of the process
+    AllMarket,
+}
+
+/// The price input of a pipeline and the recorder of its tape
+#[derive(Clone)]
+struct PriceRoute {
+    output: mpsc::Sender<MarketEvent>,
+    recorder: Option<TapeRecorder>,
+}
+
+type PriceRoutes = Arc<Mutex<HashMap<String, PriceRoute>>>;
+
+/// A cloneable handle of the all-market price stream, which pipelines subscribe their instrument to
+///
+/// One connection carries the best bid/ask updates of every symbol of the exchange, the PriceDemultiplexer
+/// routes them to the pipelines by symbol, so hundreds of symbols need no connection or subscription each
+#[derive(Clone)]
+pub struct AllMarketPrices {
+    routes: PriceRoutes,
+}
+
+impl AllMarketPrices {
+    /// Connect to the all-market price stream and start routing its updates
+    ///
+    /// # Arguments
+    /// * `tasks` - Handles of the spawned tasks: the stream and the demultiplexer
+    /// * `url` - URL of the all-market price stream
+    /// * `reconnect_timeout` - Timeout in milliseconds to wait before reconnecting
+    /// * `transport` - The proxy and TLS settings of the connection
+    pub fn start(tasks: &mut Vec<JoinHandle<()>>, url: String, reconnect_timeout: u64, transport: Transport) -> Self {
+        let (sender, receiver) = mpsc::channel(PRICE_BUFFER);
+        let mut stream = MarketEventStream::<PriceUpdate>::new(url, sender, reconnect_timeout, None, None, None)
+            .with_transport(transport);
+        let routes = PriceRoutes::default();
+        let demultiplexer = PriceDemultiplexer::new(receiver, routes.clone());
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting all-market price stream");
+            stream.run().await;
+        }.instrument(stream_span("all_market_price#0"))));
+        tasks.push(spawn(async move {
+            tracing::info!("Starting price demultiplexer");
+            demultiplexer.run().await;
+        }));
+
+        Self { routes }
+    }
+
+    /// Route the updates of the symbol to the output, until it is closed or another output subscribes the symbol
+    ///
+    /// # Arguments
+    /// * `symbol` - Venue symbol of the instrument
+    /// * `output` - Sender to the PriceEventDispatcher of the pipeline
+    /// * `recorder` - Optional recorder, which persists every routed update as a frame of the symbol's bookTicker stream
+    pub fn subscribe(&self, symbol: &str, output: mpsc::Sender<MarketEvent>, recorder: Option<TapeRecorder>) {
+        tracing::info!("Subscribing '{}' to the all-market price stream", symbol);
+        self.routes
+            .lock()
+            .expect("Price routes lock is poisoned")
+            .insert(symbol.to_string(), PriceRoute { output, recorder });
+    }
+}
+
+/// PriceDemultiplexer routes the updates of the all-market price stream to the pipelines of their symbols
+///
+/// Updates of symbols without a pipeline are dropped. A pipeline, which falls behind, loses updates instead of
+/// holding up the other pipelines; a newer update of the same symbol follows shortly anyway
+pub struct PriceDemultiplexer {
+    input: mpsc::Receiver<MarketEvent>,
+    routes: PriceRoutes,
+    dropped: u64,
+}
+
+impl PriceDemultiplexer {
+    fn new(input: mpsc::Receiver<MarketEvent>, routes: PriceRoutes) -> Self {
+        Self {
+            input,
+            routes,
+            dropped: 0,
+        }
+    }
+
+    fn route(&self, symbol: &str) -> Option<PriceRoute> {
+        self.routes.lock().expect("Price routes lock is poisoned").get(symbol).cloned()
+    }
+
+    async fn forward(&mut self, update: PriceUpdate) {
+        let Some(route) = self.route(&update.symbol) else {
+            return;
+        };
+
+        if let Some(recorder) = &route.recorder {
+            recorder.record(&update.to_json()).await;
+        }
+
+        let symbol = update.symbol.clone();
+        match route.output.try_send(MarketEvent::PriceUpdate(update)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                tracing::debug!("Pipeline of '{}' is lagging. Dropped '{}' price updates", symbol, self.dropped);
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::info!("Pipeline of '{}' is stopped. Unsubscribing it from the all-market price stream", symbol);
+                let mut routes = self.routes.lock().expect("Price routes lock is poisoned");
+                // The symbol may have been subscribed again meanwhile
+                if routes.get(&symbol).is_some_and(|current| current.output.same_channel(&route.output)) {
+                    routes.remove(&symbol);
+                }
+            }
+        }
+    }
+
+    /// Run the PriceDemultiplexer as an asynchronous task
+    ///
+    /// This method will continuously route price updates until the all-market price stream is closed
+    pub async fn run(mut self) {
+        while let Some(event) = self.input.recv().await {
+            match event {
+                MarketEvent::PriceUpdate(update) => self.forward(update).await,
+                event => tracing::warn!("Unexpected event in all-market price channel: '{}'", event),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(symbol: &str, update_id: u64) -> MarketEvent {
+        MarketEvent::PriceUpdate(PriceUpdate {
+            update_id,
+            symbol: symbol.to_string(),
+            best_bid_price: 100.0,
+            best_bid_quantity: 1.0,
+            best_ask_price: 101.0,
+            best_ask_quantity: 2.0,
+            received: None,
+        })
+    }
+
+    fn update_id(event: Option<MarketEvent>) -> Option<u64> {
+        match event {
+            Some(MarketEvent::PriceUpdate(update)) => Some(update.update_id),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_updates_by_symbol() {
+        let (input_sender, input_receiver) = mpsc::channel(10);
+        let prices = AllMarketPrices { routes: PriceRoutes::default() };
+        let (btc_sender, mut btc_receiver) = mpsc::channel(10);
+        let (eth_sender, eth_receiver) = mpsc::channel(10);
+        prices.subscribe("BTCUSDT", btc_sender, None);
+        prices.subscribe("ETHUSDT", eth_sender, None);
+        let demultiplexer = tokio::spawn(PriceDemultiplexer::new(input_receiver, prices.routes.clone()).run());
+
+        // The pipeline of ETHUSDT is stopped, so it is unsubscribed
+        drop(eth_receiver);
+        for event in [update("BTCUSDT", 1), update("SOLUSDT", 2), update("ETHUSDT", 3), update("BTCUSDT", 4)] {
+            input_sender.send(event).await.unwrap();
+        }
+        drop(input_sender);
+        demultiplexer.await.unwrap();
+
+        assert_eq!(update_id(btc_receiver.try_recv().ok()), Some(1));
+        assert_eq!(update_id(btc_receiver.try_recv().ok()), Some(4));
+        assert!(btc_receiver.try_recv().is_err());
+        assert_eq!(prices.routes.lock().unwrap().keys().collect::<Vec<_>>(), vec!["BTCUSDT"]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+thread_local! {
+    /// Allocations and allocated bytes of the current thread, counted by `CountingAllocator`
+    static THREAD_ALLOCATIONS: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+}
+
+/// CountingAllocator wraps a global allocator and counts the allocations of each thread
+///
+/// Counting only touches a thread-local cell, so it adds no contention between threads.
+/// Reallocations count as allocations of the new size, deallocations aren't counted
+pub struct CountingAllocator<A>(pub A);
+
+impl<A> CountingAllocator<A> {
+    fn count(size: usize) {
+        // The thread-local may be gone while the thread is being torn down
+        let _ = THREAD_ALLOCATIONS.try_with(|allocations| {
+            let (count, bytes) = allocations.get();
+            allocations.set((count + 1, bytes + size as u64));
+        });
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        self.0.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        self.0.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count(new_size);
+        self.0.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+}
+
+/// Allocations made within an instrumented code path, e.g. the parsing of exchange messages
+///
+/// The counts are process-wide, i.e. shared by all pipelines of the process. They are only collected
+/// with the `alloc-profiling` feature, which installs the `CountingAllocator`. Otherwise `measure` costs nothing
+pub struct AllocationSite {
+    name: &'static str,
+    allocations: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Decoding of the WebSocket frames into market events
+pub static PARSE_ALLOCATIONS: AllocationSite = AllocationSite::new("parse");
+
+/// Building the book from snapshots and applying depth updates to it
+pub static BOOK_ALLOCATIONS: AllocationSite = AllocationSite::new("book");
+
+impl AllocationSite {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, allocations: AtomicU64::new(0), bytes: AtomicU64::new(0) }
+    }
+
+    /// Run the code path, adding the allocations it makes on the current thread to the site
+    #[inline]
+    pub fn measure<T>(&self, path: impl FnOnce() -> T) -> T {
+        if !cfg!(feature = "alloc-profiling") {
+            return path();
+        }
+
+        let (count_before, bytes_before) = THREAD_ALLOCATIONS.with(Cell::get);
+        let result = path();
+        let (count_after, bytes_after) = THREAD_ALLOCATIONS.with(Cell::get);
+        self.allocations.fetch_add(count_after - count_before, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes_after - bytes_before, Ordering::Relaxed);
+        result
+    }
+
+    /// Counters of the site: `<name>_allocations` and `<name>_allocated_bytes`
+    pub fn counters(&self) -> [(String, u64); 2] {
+        [
+            (format!("{}_allocations", self.name), self.allocations.load(Ordering::Relaxed)),
+            (format!("{}_allocated_bytes", self.name), self.bytes.load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+/// Counters of all instrumented code paths
+pub fn snapshot() -> impl Iterator<Item = (String, u64)> {
+    [&PARSE_ALLOCATIONS, &BOOK_ALLOCATIONS].into_iter().flat_map(AllocationSite::counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    #[test]
+    fn test_counting_allocator() {
+        let allocator = CountingAllocator(System);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let (count_before, bytes_before) = THREAD_ALLOCATIONS.with(Cell::get);
+
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            let ptr = allocator.realloc(ptr, layout, 128);
+            allocator.dealloc(ptr, Layout::from_size_align(128, 8).unwrap());
+        }
+
+        let (count_after, bytes_after) = THREAD_ALLOCATIONS.with(Cell::get);
+        assert_eq!(count_after - count_before, 2);
+        assert_eq!(bytes_after - bytes_before, 192);
+    }
+}
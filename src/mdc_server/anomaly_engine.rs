@@ -0,0 +1,141 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use crate::mdc_core::anomaly::{AnomalyDetector, AnomalyEvent};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// Interval between the checks for a stale book and trade rate spikes in milliseconds
+const CHECK_INTERVAL: u64 = 250;
+
+/// AnomalyEngine runs the AnomalyDetector on the maintained book and the trades, and sends the detected anomalies
+/// as `Anomaly` events along with the auxiliary events, so every sink receives them
+///
+/// The anomalies are counted by `anomalies_detected` and optionally forwarded to the alert engine
+pub struct AnomalyEngine {
+    detector: AnomalyDetector,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    output: mpsc::Sender<MarketEvent>,
+    alerts: Option<mpsc::Sender<AnomalyEvent>>,
+    detected: Counter,
+}
+
+impl AnomalyEngine {
+    /// Create a new AnomalyEngine
+    ///
+    /// # Arguments
+    /// * `detector` - The anomaly detector
+    /// * `book_channel` - Receiver for OrderBook messages
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    /// * `output` - Sender for MarketEvent messages containing AnomalyEvents
+    /// * `metrics` - Registry of the anomaly counter
+    pub fn new(
+        detector: AnomalyDetector,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+        output: mpsc::Sender<MarketEvent>,
+        metrics: &Metrics,
+    ) -> Self {
+        Self {
+            detector,
+            book_channel,
+            trade_channel,
+            output,
+            alerts: None,
+            detected: metrics.counter("anomalies_detected"),
+        }
+    }
+
+    /// Forward the anomalies to the alert engine. Anomalies are dropped if the alert engine falls behind
+    pub fn with_alerts(mut self, alerts: mpsc::Sender<AnomalyEvent>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    async fn publish(&mut self, anomalies: Vec<AnomalyEvent>) -> bool {
+        for anomaly in anomalies {
+            tracing::warn!("Anomaly detected: '{}'", anomaly);
+            self.detected.increment(1);
+
+            if let Some(alerts) = &self.alerts {
+                let _ = alerts.try_send(anomaly.clone());
+            }
+
+            if let Err(e) = self.output.send(MarketEvent::Anomaly(anomaly)).await {
+                tracing::error!("Failed to send anomaly. Details: '{}'", e);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Run the AnomalyEngine as an asynchronous task
+    ///
+    /// This method will continuously check the book and the trades until the book channel is closed
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(CHECK_INTERVAL));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut trades_open = true;
+
+        loop {
+            let anomalies = tokio::select! {
+                book = self.book_channel.recv() => {
+                    match book {
+                        Some(book) => self.detector.on_book(Utc::now().timestamp_millis(), &book),
+                        None => break,
+                    }
+                }
+                event = self.trade_channel.recv(), if trades_open => {
+                    match event {
+                        Some(MarketEvent::TradeEvent(_)) => self.detector.on_trade(Utc::now().timestamp_millis()),
+                        Some(event) => tracing::warn!("Unexpected event in anomaly trade channel: '{}'", event),
+                        None => trades_open = false,
+                    }
+                    continue;
+                }
+                _ = ticker.tick() => self.detector.on_tick(Utc::now().timestamp_millis()),
+            };
+
+            if !self.publish(anomalies).await {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::anomaly::{Anomaly, AnomalySettings};
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+    use crate::mdc_core::order_book::Side;
+
+    #[tokio::test]
+    async fn test_anomalies_are_published() {
+        let (book_tx, book_rx) = mpsc::channel(10);
+        let (_trade_tx, trade_rx) = mpsc::channel(10);
+        let (output_tx, mut output_rx) = mpsc::channel(10);
+        let (alert_tx, mut alert_rx) = mpsc::channel(10);
+        let metrics = Metrics::new();
+        let engine = AnomalyEngine::new(AnomalyDetector::new(AnomalySettings::default()), book_rx, trade_rx, output_tx, &metrics)
+            .with_alerts(alert_tx);
+        let task = tokio::spawn(engine.run());
+
+        let entry = |price: &str| DepthEntry { price: price.parse().unwrap(), quantity: "1".parse().unwrap() };
+        book_tx.send(Arc::new(OrderBook::new(&DepthSnapshot { last_update_id: 1, bids: vec![entry("100")], asks: vec![entry("101")] }))).await.unwrap();
+        book_tx.send(Arc::new(OrderBook::new(&DepthSnapshot { last_update_id: 2, bids: vec![], asks: vec![entry("101")] }))).await.unwrap();
+
+        let Some(MarketEvent::Anomaly(anomaly)) = output_rx.recv().await else {
+            panic!("An anomaly is expected");
+        };
+        assert_eq!(anomaly.anomaly, Anomaly::SideWiped { side: Side::Bid });
+        assert_eq!(alert_rx.recv().await, Some(anomaly));
+        assert_eq!(metrics.snapshot()["anomalies_detected"], 1);
+
+        drop(book_tx);
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::exchange_connector::Exchange;
+use crate::mdc_server::pipeline_sink::PipelineSink;
+
+/// Best bid and ask of an instrument on a venue
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueQuote {
+    pub exchange: Exchange,
+    /// Canonical symbol of the instrument
+    pub symbol: String,
+    pub bid_price: f64,
+    pub bid_quantity: f64,
+    pub ask_price: f64,
+    pub ask_quantity: f64,
+    /// Local time of the quote in milliseconds since epoch
+    pub time: i64,
+    /// Arbitrage threshold of the pipeline in basis points
+    pub threshold_bps: f64,
+}
+
+/// QuoteSink is attached to a pipeline and sends its best quotes, taken from the maintained book
+/// and from bookTicker, to the ArbitrageMonitor
+///
+/// Quotes are superseded by the next ones, so a quote is dropped if the monitor can't keep up
+pub struct QuoteSink {
+    exchange: Exchange,
+    symbol: String,
+    threshold_bps: f64,
+    quotes: mpsc::Sender<VenueQuote>,
+}
+
+impl QuoteSink {
+    /// Create a new QuoteSink
+    ///
+    /// # Arguments
+    /// * `exchange` - The exchange of the pipeline
+    /// * `symbol` - The canonical symbol of the instrument of the pipeline
+    /// * `threshold_bps` - The arbitrage threshold of the pipeline in basis points
+    /// * `quotes` - Sender to the ArbitrageMonitor
+    pub fn new(exchange: Exchange, symbol: &str, threshold_bps: f64, quotes: mpsc::Sender<VenueQuote>) -> Self {
+        Self {
+            exchange,
+            symbol: symbol.to_string(),
+            threshold_bps,
+            quotes,
+        }
+    }
+
+    fn send(&self, bid: (f64, f64), ask: (f64, f64)) {
+        let quote = VenueQuote {
+            exchange: self.exchange,
+            symbol: self.symbol.clone(),
+            bid_price: bid.0,
+            bid_quantity: bid.1,
+            ask_price: ask.0,
+            ask_quantity: ask.1,
+            time: Utc::now().timestamp_millis(),
+            threshold_bps: self.threshold_bps,
+        };
+
+        match self.quotes.try_send(quote) {
+            Ok(()) | Err(TrySendError::Closed(_)) => {}
+            Err(TrySendError::Full(_)) => tracing::debug!("Arbitrage monitor is busy. Dropping quote of '{}'", self.symbol),
+        }
+    }
+}
+
+impl PipelineSink for QuoteSink {
+    fn on_book(&mut self, book: &Arc<OrderBook>) -> Result<()> {
+        if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+            self.send((bid.price.to_f64(), bid.quantity.to_f64()), (ask.price.to_f64(), ask.quantity.to_f64()));
+        }
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: &MarketEvent) -> Result<()> {
+        if let MarketEvent::PriceUpdate(update) = event {
+            self.send(
+                (update.best_bid_price, update.best_bid_quantity),
+                (update.best_ask_price, update.best_ask_quantity),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Whether an arbitrage opportunity has appeared or disappeared
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArbitrageEventKind {
+    Opened,
+    Closed,
+}
+
+/// Cross-venue arbitrage opportunity: the instrument can be bought on one venue below the price it can be sold
+/// for on another one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArbitrageEvent {
+    pub kind: ArbitrageEventKind,
+    /// Local time in milliseconds since epoch
+    pub time: i64,
+    pub symbol: String,
+    /// The venue of the best ask, where the instrument is bought
+    pub buy_exchange: Exchange,
+    /// The venue of the best bid, where the instrument is sold
+    pub sell_exchange: Exchange,
+    pub ask_price: f64,
+    pub bid_price: f64,
+    /// Quantity available on both venues
+    pub quantity: f64,
+    /// Bid minus ask in basis points of the ask
+    pub spread_bps: f64,
+    /// The largest spread since the opportunity has opened
+    pub max_spread_bps: f64,
+    /// Lifetime of a closed opportunity in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i64>,
+}
+
+struct OpenOpportunity {
+    opened_at: i64,
+    max_spread_bps: f64,
+}
+
+/// ArbitrageDetector compares the latest quotes of the venues of each canonical symbol and reports
+/// when a bid of one venue exceeds an ask of another one by more than the threshold, and when it no longer does
+///
+/// A pair of venues uses the larger threshold of the two
+#[derive(Default)]
+pub struct ArbitrageDetector {
+    quotes: HashMap<String, BTreeMap<Exchange, VenueQuote>>,
+    open: HashMap<(String, Exchange, Exchange), OpenOpportunity>,
+}
+
+impl ArbitrageDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the quote of the venue
+    ///
+    /// # Returns
+    /// The opportunities, which have opened or closed with the quote
+    pub fn on_quote(&mut self, quote: VenueQuote) -> Vec<ArbitrageEvent> {
+        let venues = self.quotes.entry(quote.symbol.clone()).or_default();
+        venues.insert(quote.exchange, quote.clone());
+
+        let mut events = Vec::new();
+        for other in venues.values().filter(|other| other.exchange != quote.exchange) {
+            for (buy, sell) in [(&quote, other), (other, &quote)] {
+                let event = Self::check(&mut self.open, buy, sell, quote.time);
+                events.extend(event);
+            }
+        }
+
+        events
+    }
+
+    fn check(
+        open: &mut HashMap<(String, Exchange, Exchange), OpenOpportunity>,
+        buy: &VenueQuote,
+        sell: &VenueQuote,
+        now: i64,
+    ) -> Option<ArbitrageEvent> {
+        if buy.ask_price <= 0.0 {
+            return None;
+        }
+
+        let spread_bps = (sell.bid_price - buy.ask_price) / buy.ask_price * 10_000.0;
+        let threshold_bps = buy.threshold_bps.max(sell.threshold_bps);
+        let key = (buy.symbol.clone(), buy.exchange, sell.exchange);
+
+        let (kind, max_spread_bps, duration) = if spread_bps > threshold_bps {
+            match open.get_mut(&key) {
+                Some(opportunity) => {
+                    opportunity.max_spread_bps = opportunity.max_spread_bps.max(spread_bps);
+                    return None;
+                }
+                None => {
+                    open.insert(key, OpenOpportunity { opened_at: now, max_spread_bps: spread_bps });
+                    (ArbitrageEventKind::Opened, spread_bps, None)
+                }
+            }
+        } else {
+            let opportunity = open.remove(&key)?;
+            (ArbitrageEventKind::Closed, opportunity.max_spread_bps, Some(now - opportunity.opened_at))
+        };
+
+        Some(ArbitrageEvent {
+            kind,
+            time: now,
+            symbol: buy.symbol.clone(),
+            buy_exchange: buy.exchange,
+            sell_exchange: sell.exchange,
+            ask_price: buy.ask_price,
+            bid_price: sell.bid_price,
+            quantity: buy.ask_quantity.min(sell.bid_quantity),
+            spread_bps,
+            max_spread_bps,
+            duration,
+        })
+    }
+}
+
+/// ArbitrageMonitor consumes the best quotes of the pipelines, which have the arbitrage monitor enabled,
+/// and logs arbitrage opportunities between their venues. The opportunities are also written into a JSON lines file
+pub struct ArbitrageMonitor {
+    detector: ArbitrageDetector,
+    quotes: mpsc::Receiver<VenueQuote>,
+    output: Option<BufWriter<File>>,
+}
+
+impl ArbitrageMonitor {
+    /// Create a new ArbitrageMonitor
+    ///
+    /// # Arguments
+    /// * `quotes` - Receiver for the quotes of the QuoteSinks
+    pub fn new(quotes: mpsc::Receiver<VenueQuote>) -> Self {
+        Self {
+            detector: ArbitrageDetector::new(),
+            quotes,
+            output: None,
+        }
+    }
+
+    /// Append the opportunities to the JSON lines file, creating it if needed
+    pub fn with_output(mut self, path: &PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open arbitrage output file: {:?}", path))?;
+
+        self.output = Some(BufWriter::new(file));
+        Ok(self)
+    }
+
+    fn write(&mut self, event: &ArbitrageEvent) -> Result<()> {
+        if let Some(output) = &mut self.output {
+            serde_json::to_writer(&mut *output, event)?;
+            output.write_all(b"\n")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Run the ArbitrageMonitor as an asynchronous task
+    ///
+    /// This method will continuously compare the quotes until all QuoteSinks are dropped
+    pub async fn run(mut self) {
+        while let Some(quote) = self.quotes.recv().await {
+            for event in self.detector.on_quote(quote) {
+                match event.kind {
+                    ArbitrageEventKind::Opened => tracing::info!(
+                        "Arbitrage on '{}': buy on '{:?}' at '{}', sell on '{:?}' at '{}', spread '{:.2}' bps",
+                        event.symbol, event.buy_exchange, event.ask_price, event.sell_exchange, event.bid_price, event.spread_bps
+                    ),
+                    ArbitrageEventKind::Closed => tracing::info!(
+                        "Arbitrage on '{}' between '{:?}' and '{:?}' has closed after '{}' ms, max spread '{:.2}' bps",
+                        event.symbol, event.buy_exchange, event.sell_exchange, event.duration.unwrap_or_default(), event.max_spread_bps
+                    ),
+                }
+
+                if let Err(e) = self.write(&event) {
+                    tracing::error!("Failed to write arbitrage event. Details: '{}'", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_quote(exchange: Exchange, bid: f64, ask: f64, time: i64) -> VenueQuote {
+        VenueQuote {
+            exchange,
+            symbol: "BTC-USD".to_string(),
+            bid_price: bid,
+            bid_quantity: 1.0,
+            ask_price: ask,
+            ask_quantity: 2.0,
+            time,
+            threshold_bps: 5.0,
+        }
+    }
+
+    #[test]
+    fn test_opportunities_open_and_close() {
+        let mut detector = ArbitrageDetector::new();
+
+        assert!(detector.on_quote(make_quote(Exchange::Binance, 100.0, 100.1, 1)).is_empty());
+        // Crossed by less than the threshold
+        assert!(detector.on_quote(make_quote(Exchange::Okx, 100.12, 100.2, 2)).is_empty());
+
+        let events = detector.on_quote(make_quote(Exchange::Okx, 100.2, 100.3, 3));
+        let [opened] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(opened.kind, ArbitrageEventKind::Opened);
+        assert_eq!((opened.buy_exchange, opened.sell_exchange), (Exchange::Binance, Exchange::Okx));
+        assert_eq!(opened.quantity, 1.0);
+        assert!((opened.spread_bps - 9.99).abs() < 0.01);
+
+        // Still open: nothing is reported
+        assert!(detector.on_quote(make_quote(Exchange::Okx, 100.3, 100.4, 4)).is_empty());
+        // Other symbols are compared separately
+        let mut other = make_quote(Exchange::Bitfinex, 200.0, 200.1, 5);
+        other.symbol = "ETH-USD".to_string();
+        assert!(detector.on_quote(other).is_empty());
+
+        let events = detector.on_quote(make_quote(Exchange::Binance, 100.2, 100.25, 10));
+        let [closed] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(closed.kind, ArbitrageEventKind::Closed);
+        assert_eq!(closed.duration, Some(7));
+        assert!((closed.max_spread_bps - 19.98).abs() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_quote_sink_sends_best_quotes() {
+        use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+        let (sender, mut receiver) = mpsc::channel(10);
+        let mut sink = QuoteSink::new(Exchange::Okx, "BTC-USD", 3.0, sender);
+
+        let book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![DepthEntry { price: "100".parse().unwrap(), quantity: "1".parse().unwrap() }],
+            asks: vec![DepthEntry { price: "101".parse().unwrap(), quantity: "2".parse().unwrap() }],
+        });
+        sink.on_book(&Arc::new(book)).unwrap();
+
+        let quote = receiver.recv().await.unwrap();
+        assert_eq!((quote.exchange, quote.symbol.as_str()), (Exchange::Okx, "BTC-USD"));
+        assert_eq!((quote.bid_price, quote.ask_price, quote.ask_quantity), (100.0, 101.0, 2.0));
+        assert_eq!(quote.threshold_bps, 3.0);
+    }
+}
@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use crate::mdc_core::models::KlineInterval;
+use crate::mdc_core::sequencing::SequencingRules;
+use crate::mdc_server::exchange_connector::{ExchangeConnector, StreamKind};
+
+/// URL of a combined stream for the given raw stream endpoint (e.g. "wss://stream.binance.com:9443/ws/")
+///
+/// Combined streams are served under "/stream?streams=a/b/c" next to the raw "/ws/" path
+fn combined_stream_url(wss_endpoint: &str, names: &[String]) -> String {
+    let base = wss_endpoint.trim_end_matches('/');
+    let base = base.strip_suffix("/ws").unwrap_or(base);
+    format!("{}/stream?streams={}", base, names.join("/"))
+}
+
+/// Levels of the partial book depth streams
+const PARTIAL_DEPTH_LEVELS: [u64; 3] = [5, 10, 20];
+
+/// Name of the partial book depth stream with the fewest levels covering the requested ones (at most 20)
+///
+/// Intervals below a second select the 100 ms stream, the others the default one (1 s on spot, 250 ms on futures)
+fn partial_depth_stream(levels: u64, interval: u64) -> String {
+    let levels = PARTIAL_DEPTH_LEVELS
+        .iter()
+        .copied()
+        .find(|valid| *valid >= levels)
+        .unwrap_or(20);
+
+    match interval < 1000 {
+        true => format!("depth{}@100ms", levels),
+        false => format!("depth{}", levels),
+    }
+}
+
+/// Connector for Binance spot market data
+pub struct BinanceConnector {
+    rest_endpoint: String,
+    wss_endpoint: String,
+}
+
+impl BinanceConnector {
+    /// Create a new BinanceConnector
+    ///
+    /// # Arguments
+    /// * `rest_endpoint` - The Binance REST API endpoint (e.g. "https://api.binance.com/api/v3/")
+    /// * `wss_endpoint` - The Binance WebSocket endpoint (e.g. "wss://stream.binance.com:9443/ws/")
+    pub fn new(rest_endpoint: String, wss_endpoint: String) -> Self {
+        Self {
+            rest_endpoint,
+            wss_endpoint,
+        }
+    }
+}
+
+impl ExchangeConnector for BinanceConnector {
+    fn name(&self) -> &str {
+        "binance"
+    }
+
+    fn stream_name(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        let stream = match kind {
+            StreamKind::AllMarketPrice => return Some("!bookTicker".to_string()),
+            StreamKind::Depth => "depth@100ms".to_string(),
+            StreamKind::PartialDepth(levels) => partial_depth_stream(levels, 100),
+            StreamKind::PartialBook { levels, interval } => partial_depth_stream(levels, interval),
+            StreamKind::Trade => "trade".to_string(),
+            StreamKind::Price => "bookTicker".to_string(),
+            StreamKind::AggTrade => "aggTrade".to_string(),
+            StreamKind::Kline(interval) => format!("kline_{}", interval),
+            StreamKind::Ticker => "ticker".to_string(),
+            StreamKind::MiniTicker => "miniTicker".to_string(),
+            // Mark price, funding and liquidations only exist for futures
+            StreamKind::MarkPrice | StreamKind::Liquidation => return None,
+        };
+
+        Some(format!("{}@{}", instrument.to_lowercase(), stream))
+    }
+
+    fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        self.stream_name(kind, instrument).map(|name| format!("{}{}", self.wss_endpoint, name))
+    }
+
+    fn combined_stream_url(&self, names: &[String]) -> Option<String> {
+        Some(combined_stream_url(&self.wss_endpoint, names))
+    }
+
+    fn snapshot_url(&self, instrument: &str, limit: u64) -> Option<String> {
+        Some(format!("{}depth?symbol={}&limit={}", self.rest_endpoint, instrument, limit))
+    }
+
+    fn ws_api_snapshot_params(&self, instrument: &str, limit: u64) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "symbol": instrument, "limit": limit }))
+    }
+
+    fn snapshot_weight(&self, limit: u64) -> u64 {
+        match limit {
+            0..=100 => 5,
+            101..=500 => 25,
+            501..=1000 => 50,
+            _ => 250,
+        }
+    }
+
+    fn exchange_info_url(&self, instrument: &str) -> String {
+        format!("{}exchangeInfo?symbol={}", self.rest_endpoint, instrument)
+    }
+
+    fn symbols_url(&self) -> Option<String> {
+        Some(format!("{}exchangeInfo", self.rest_endpoint))
+    }
+
+    fn server_time_url(&self) -> Option<String> {
+        Some(format!("{}time", self.rest_endpoint))
+    }
+
+    fn sequencing_rules(&self) -> SequencingRules {
+        SequencingRules::BinanceSpot
+    }
+}
+
+/// Header, which carries the API key of the SBE streams
+const API_KEY_HEADER: &str = "X-MBX-APIKEY";
+
+fn default_sbe_endpoint() -> String {
+    "wss://stream-sbe.binance.com:9443/ws/".to_string()
+}
+
+/// SBE-encoded market data streams of Binance spot, which replace the JSON depth and trade streams (`sbe` feature)
+///
+/// The streams carry the same events in a binary encoding, which is cheaper to decode. They require an API key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BinanceSbe {
+    /// Raw stream endpoint of the SBE streams
+    #[serde(default = "default_sbe_endpoint")]
+    pub endpoint: String,
+    /// API key (Ed25519), which the SBE streams require. Taken from `BINANCE_API_KEY` if not set
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+impl Default for BinanceSbe {
+    fn default() -> Self {
+        Self {
+            endpoint: default_sbe_endpoint(),
+            api_key: None,
+        }
+    }
+}
+
+impl BinanceSbe {
+    /// URL of the SBE stream of the given kind. Only the depth updates and trades are taken from the SBE streams
+    pub fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        let stream = match kind {
+            StreamKind::Depth => "depth",
+            StreamKind::Trade => "trade",
+            _ => return None,
+        };
+        Some(format!("{}{}@{}", self.endpoint, instrument.to_lowercase(), stream))
+    }
+
+    /// The configured API key, or the one of the environment
+    pub fn api_key(&self) -> Option<String> {
+        self.api_key.clone().or_else(|| std::env::var("BINANCE_API_KEY").ok())
+    }
+
+    /// Headers of the connection requests of the SBE streams
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.api_key().into_iter().map(|api_key| (API_KEY_HEADER.to_string(), api_key)).collect()
+    }
+}
+
+/// Snapshot limits, which are accepted by the Binance futures depth endpoint
+const FUTURES_SNAPSHOT_LIMITS: [u64; 7] = [5, 10, 20, 50, 100, 500, 1000];
+
+/// Futures only accept a fixed set of limits, so the closest one covering the requested depth is used
+fn futures_snapshot_limit(limit: u64) -> u64 {
+    FUTURES_SNAPSHOT_LIMITS
+        .iter()
+        .copied()
+        .find(|valid| *valid >= limit)
+        .unwrap_or(1000)
+}
+
+/// Connector for Binance USD-M futures market data
+///
+/// Expects fapi/fstream endpoints (e.g. "https://fapi.binance.com/fapi/v1/" and "wss://fstream.binance.com/ws/")
+pub struct BinanceFuturesConnector {
+    rest_endpoint: String,
+    wss_endpoint: String,
+}
+
+impl BinanceFuturesConnector {
+    /// Create a new BinanceFuturesConnector
+    ///
+    /// # Arguments
+    /// * `rest_endpoint` - The Binance futures REST API endpoint (e.g. "https://fapi.binance.com/fapi/v1/")
+    /// * `wss_endpoint` - The Binance futures WebSocket endpoint (e.g. "wss://fstream.binance.com/ws/")
+    pub fn new(rest_endpoint: String, wss_endpoint: String) -> Self {
+        Self {
+            rest_endpoint,
+            wss_endpoint,
+        }
+    }
+}
+
+impl ExchangeConnector for BinanceFuturesConnector {
+    fn name(&self) -> &str {
+        "binance_futures"
+    }
+
+    fn stream_name(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        let stream = match kind {
+            StreamKind::AllMarketPrice => return Some("!bookTicker".to_string()),
+            StreamKind::Depth => "depth@100ms".to_string(),
+            StreamKind::PartialDepth(levels) => partial_depth_stream(levels, 100),
+            StreamKind::PartialBook { levels, interval } => partial_depth_stream(levels, interval),
+            StreamKind::Trade => return None,
+            StreamKind::Price => "bookTicker".to_string(),
+            StreamKind::AggTrade => "aggTrade".to_string(),
+            // Second klines are only provided for spot
+            StreamKind::Kline(KlineInterval::Second1) => return None,
+            StreamKind::Kline(interval) => format!("kline_{}", interval),
+            StreamKind::Ticker => "ticker".to_string(),
+            StreamKind::MiniTicker => "miniTicker".to_string(),
+            StreamKind::MarkPrice => "markPrice@1s".to_string(),
+            StreamKind::Liquidation => "forceOrder".to_string(),
+        };
+
+        Some(format!("{}@{}", instrument.to_lowercase(), stream))
+    }
+
+    fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        self.stream_name(kind, instrument).map(|name| format!("{}{}", self.wss_endpoint, name))
+    }
+
+    fn combined_stream_url(&self, names: &[String]) -> Option<String> {
+        Some(combined_stream_url(&self.wss_endpoint, names))
+    }
+
+    fn snapshot_url(&self, instrument: &str, limit: u64) -> Option<String> {
+        Some(format!("{}depth?symbol={}&limit={}", self.rest_endpoint, instrument, futures_snapshot_limit(limit)))
+    }
+
+    fn ws_api_snapshot_params(&self, instrument: &str, limit: u64) -> Option<serde_json::Value> {
+        Some(serde_json::json!({ "symbol": instrument, "limit": limit }))
+    }
+
+    fn snapshot_weight(&self, limit: u64) -> u64 {
+        match limit {
+            0..=50 => 2,
+            51..=100 => 5,
+            101..=500 => 10,
+            _ => 20,
+        }
+    }
+
+    fn exchange_info_url(&self, _instrument: &str) -> String {
+        format!("{}exchangeInfo", self.rest_endpoint)
+    }
+
+    fn symbols_url(&self) -> Option<String> {
+        Some(format!("{}exchangeInfo", self.rest_endpoint))
+    }
+
+    fn server_time_url(&self) -> Option<String> {
+        Some(format!("{}time", self.rest_endpoint))
+    }
+
+    fn sequencing_rules(&self) -> SequencingRules {
+        SequencingRules::BinanceFutures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_connector() -> BinanceConnector {
+        BinanceConnector::new(
+            "https://api.binance.com/api/v3/".to_string(),
+            "wss://stream.binance.com:9443/ws/".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_stream_urls() {
+        let connector = make_connector();
+
+        assert_eq!(connector.stream_url(StreamKind::Depth, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@depth@100ms");
+        assert_eq!(connector.stream_url(StreamKind::PartialDepth(20), "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@depth20@100ms");
+        assert_eq!(connector.stream_url(StreamKind::PartialDepth(7), "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@depth10@100ms");
+        assert_eq!(connector.stream_url(StreamKind::PartialDepth(50), "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@depth20@100ms");
+        assert_eq!(connector.stream_url(StreamKind::PartialBook { levels: 5, interval: 1000 }, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@depth5");
+        assert_eq!(connector.stream_url(StreamKind::PartialBook { levels: 5, interval: 100 }, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@depth5@100ms");
+        assert_eq!(connector.stream_url(StreamKind::Trade, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@trade");
+        assert_eq!(connector.stream_url(StreamKind::Price, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@bookTicker");
+        assert_eq!(connector.stream_url(StreamKind::AllMarketPrice, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/!bookTicker");
+        assert_eq!(connector.stream_url(StreamKind::AggTrade, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@aggTrade");
+        assert_eq!(connector.stream_url(StreamKind::Kline(KlineInterval::Second1), "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@kline_1s");
+        assert_eq!(connector.stream_url(StreamKind::Ticker, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@ticker");
+        assert_eq!(connector.stream_url(StreamKind::MiniTicker, "BTCUSDT").unwrap(), "wss://stream.binance.com:9443/ws/btcusdt@miniTicker");
+        assert_eq!(connector.stream_url(StreamKind::MarkPrice, "BTCUSDT"), None);
+        assert_eq!(connector.stream_name(StreamKind::Price, "BTCUSDT").unwrap(), "btcusdt@bookTicker");
+        assert_eq!(
+            connector.combined_stream_url(&["btcusdt@trade".to_string(), "btcusdt@kline_1m".to_string()]).unwrap(),
+            "wss://stream.binance.com:9443/stream?streams=btcusdt@trade/btcusdt@kline_1m"
+        );
+        assert_eq!(connector.stream_url(StreamKind::Liquidation, "BTCUSDT"), None);
+
+        let sbe = BinanceSbe { api_key: Some("key".to_string()), ..BinanceSbe::default() };
+        assert_eq!(sbe.stream_url(StreamKind::Depth, "BTCUSDT").unwrap(), "wss://stream-sbe.binance.com:9443/ws/btcusdt@depth");
+        assert_eq!(sbe.stream_url(StreamKind::Trade, "BTCUSDT").unwrap(), "wss://stream-sbe.binance.com:9443/ws/btcusdt@trade");
+        assert_eq!(sbe.stream_url(StreamKind::Price, "BTCUSDT"), None);
+        assert_eq!(sbe.headers(), vec![("X-MBX-APIKEY".to_string(), "key".to_string())]);
+    }
+
+    #[test]
+    fn test_rest_urls() {
+        let connector = make_connector();
+
+        assert_eq!(connector.snapshot_url("BTCUSDT", 100).unwrap(), "https://api.binance.com/api/v3/depth?symbol=BTCUSDT&limit=100");
+        assert_eq!(connector.exchange_info_url("BTCUSDT"), "https://api.binance.com/api/v3/exchangeInfo?symbol=BTCUSDT");
+        assert_eq!(connector.symbols_url().unwrap(), "https://api.binance.com/api/v3/exchangeInfo");
+        assert_eq!(connector.server_time_url().unwrap(), "https://api.binance.com/api/v3/time");
+        assert_eq!(connector.parse_server_time(r#"{"serverTime":1499827319559}"#).unwrap(), 1499827319559);
+        assert_eq!(connector.snapshot_weight(100), 5);
+        assert_eq!(connector.snapshot_weight(1000), 50);
+        assert_eq!(connector.snapshot_weight(5000), 250);
+    }
+
+    fn make_futures_connector() -> BinanceFuturesConnector {
+        BinanceFuturesConnector::new(
+            "https://fapi.binance.com/fapi/v1/".to_string(),
+            "wss://fstream.binance.com/ws/".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_futures_urls() {
+        let connector = make_futures_connector();
+
+        assert_eq!(connector.stream_url(StreamKind::Depth, "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@depth@100ms");
+        assert_eq!(connector.stream_url(StreamKind::Trade, "BTCUSDT"), None);
+        assert_eq!(connector.stream_url(StreamKind::Price, "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@bookTicker");
+        assert_eq!(connector.stream_url(StreamKind::AggTrade, "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@aggTrade");
+        assert_eq!(connector.stream_url(StreamKind::Kline(KlineInterval::Hour4), "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@kline_4h");
+        assert_eq!(connector.stream_url(StreamKind::Kline(KlineInterval::Second1), "BTCUSDT"), None);
+        assert_eq!(connector.stream_url(StreamKind::Ticker, "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@ticker");
+        assert_eq!(connector.stream_url(StreamKind::MarkPrice, "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@markPrice@1s");
+        assert_eq!(connector.stream_url(StreamKind::Liquidation, "BTCUSDT").unwrap(), "wss://fstream.binance.com/ws/btcusdt@forceOrder");
+        assert_eq!(
+            connector.combined_stream_url(&["btcusdt@aggTrade".to_string(), "btcusdt@markPrice@1s".to_string()]).unwrap(),
+            "wss://fstream.binance.com/stream?streams=btcusdt@aggTrade/btcusdt@markPrice@1s"
+        );
+        assert_eq!(connector.exchange_info_url("BTCUSDT"), "https://fapi.binance.com/fapi/v1/exchangeInfo");
+        assert_eq!(connector.server_time_url().unwrap(), "https://fapi.binance.com/fapi/v1/time");
+        assert_eq!(connector.sequencing_rules(), SequencingRules::BinanceFutures);
+    }
+
+    #[test]
+    fn test_futures_snapshot_limit_is_clamped() {
+        let connector = make_futures_connector();
+
+        assert_eq!(connector.snapshot_url("BTCUSDT", 100).unwrap(), "https://fapi.binance.com/fapi/v1/depth?symbol=BTCUSDT&limit=100");
+        assert_eq!(connector.snapshot_url("BTCUSDT", 200).unwrap(), "https://fapi.binance.com/fapi/v1/depth?symbol=BTCUSDT&limit=500");
+        assert_eq!(connector.snapshot_url("BTCUSDT", 5000).unwrap(), "https://fapi.binance.com/fapi/v1/depth?symbol=BTCUSDT&limit=1000");
+        assert_eq!(connector.snapshot_weight(20), 2);
+        assert_eq!(connector.snapshot_weight(200), 10);
+        assert_eq!(connector.snapshot_weight(5000), 20);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,743 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::value::RawValue;
+use serde_json::{json, Value};
+use crate::mdc_core::checksum::{bitfinex_raw_checksum, BookChecksum, BITFINEX_CHECKSUM_DEPTH};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::{DepthEntry, DepthSnapshot, DepthUpdate, IntoMarketEvent, MarketEvent, StreamMessage, SymbolMetadata, TradeEvent};
+use crate::mdc_core::sequencing::SequencingRules;
+use crate::mdc_server::exchange_connector::{ExchangeConnector, StreamKind};
+
+/// Flag of the `conf` message, which adds a checksum message after each book update
+const OB_CHECKSUM: u64 = 131072;
+
+/// Flag of the `conf` message, which adds a sequence number to each message of the connection
+const SEQ_ALL: u64 = 65536;
+
+/// Info code, with which Bitfinex asks the clients to reconnect (e.g. before a restart of the WebSocket server)
+const INFO_RECONNECT: u64 = 20051;
+
+/// Book lengths, which Bitfinex accepts in a book subscription
+const BOOK_LENGTHS: [u64; 3] = [25, 100, 250];
+
+/// Precision of a Bitfinex order book subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BitfinexPrecision {
+    /// Price levels with 5 significant digits
+    #[default]
+    P0,
+    P1,
+    P2,
+    P3,
+    /// Price levels with 1 significant digit
+    P4,
+    /// Raw book: individual orders
+    R0,
+}
+
+impl fmt::Display for BitfinexPrecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = match self {
+            BitfinexPrecision::P0 => "P0",
+            BitfinexPrecision::P1 => "P1",
+            BitfinexPrecision::P2 => "P2",
+            BitfinexPrecision::P3 => "P3",
+            BitfinexPrecision::P4 => "P4",
+            BitfinexPrecision::R0 => "R0",
+        };
+        write!(f, "{}", precision)
+    }
+}
+
+/// Connector for Bitfinex (v2 API) market data of trading pairs (e.g. "tBTCUSD")
+///
+/// All channels are served by the public WebSocket endpoint (e.g. "wss://api-pub.bitfinex.com/ws/2")
+/// and subscribed after connecting. A book subscription starts with a snapshot. Book messages carry no update ids,
+/// so the depth connection enables sequence numbers and checksums, which makes update ids connection specific
+pub struct BitfinexConnector {
+    rest_endpoint: String,
+    wss_endpoint: String,
+    precision: BitfinexPrecision,
+    book_length: u64,
+}
+
+impl BitfinexConnector {
+    /// Create a new BitfinexConnector
+    ///
+    /// # Arguments
+    /// * `rest_endpoint` - The Bitfinex public REST API endpoint (e.g. "https://api-pub.bitfinex.com/v2/")
+    /// * `wss_endpoint` - The Bitfinex public WebSocket endpoint (e.g. "wss://api-pub.bitfinex.com/ws/2")
+    /// * `precision` - Precision of the book subscription, `R0` subscribes to the raw book
+    /// * `max_depth` - Number of levels (or orders) per side to subscribe to. Rounded up to a length Bitfinex accepts
+    pub fn new(rest_endpoint: String, wss_endpoint: String, precision: BitfinexPrecision, max_depth: u64) -> Self {
+        let book_length = BOOK_LENGTHS
+            .into_iter()
+            .find(|length| *length >= max_depth)
+            .unwrap_or(BOOK_LENGTHS[BOOK_LENGTHS.len() - 1]);
+
+        Self {
+            rest_endpoint,
+            wss_endpoint,
+            precision,
+            book_length,
+        }
+    }
+}
+
+impl ExchangeConnector for BitfinexConnector {
+    fn name(&self) -> &str {
+        "bitfinex"
+    }
+
+    fn stream_name(&self, kind: StreamKind, _instrument: &str) -> Option<String> {
+        match kind {
+            StreamKind::Depth => Some("book".to_string()),
+            StreamKind::Trade => Some("trades".to_string()),
+            _ => None,
+        }
+    }
+
+    fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        self.stream_name(kind, instrument).map(|_| self.wss_endpoint.clone())
+    }
+
+    fn subscription(&self, kind: StreamKind, instrument: &str) -> Vec<String> {
+        match kind {
+            StreamKind::Depth => {
+                let mut subscribe = json!({
+                    "event": "subscribe",
+                    "channel": "book",
+                    "symbol": instrument,
+                    "prec": self.precision.to_string(),
+                    "len": self.book_length.to_string(),
+                });
+                if self.precision != BitfinexPrecision::R0 {
+                    subscribe["freq"] = json!("F0");
+                }
+
+                vec![json!({"event": "conf", "flags": OB_CHECKSUM | SEQ_ALL}).to_string(), subscribe.to_string()]
+            }
+            StreamKind::Trade => vec![json!({"event": "subscribe", "channel": "trades", "symbol": instrument}).to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn combined_stream_url(&self, _names: &[String]) -> Option<String> {
+        None
+    }
+
+    fn snapshot_url(&self, _instrument: &str, _limit: u64) -> Option<String> {
+        // REST snapshots don't carry the sequence numbers of the depth connection
+        None
+    }
+
+    fn snapshot_weight(&self, _limit: u64) -> u64 {
+        0
+    }
+
+    fn exchange_info_url(&self, instrument: &str) -> String {
+        let kind = if instrument.contains("F0:") { "pair:futures" } else { "pair" };
+        format!("{}conf/pub:info:{}", self.rest_endpoint, kind)
+    }
+
+    fn server_time_url(&self) -> Option<String> {
+        None
+    }
+
+    fn parse_symbol_metadata(&self, symbol: &str, response: &str) -> Result<SymbolMetadata> {
+        let pair = symbol.strip_prefix('t').unwrap_or(symbol);
+        let [pairs]: [Vec<(String, Vec<Value>)>; 1] = serde_json::from_str(response).context("Failed to parse pair info")?;
+
+        let (_, info) = pairs
+            .into_iter()
+            .find(|(name, _)| name == pair)
+            .ok_or_else(|| anyhow!("Symbol '{}' is not listed in pair info", symbol))?;
+
+        // Pairs with longer currency codes are separated with a colon (e.g. "TESTBTC:TESTUSD")
+        let (base, quote) = pair.split_once(':').unwrap_or_else(|| pair.split_at(pair.len().min(3)));
+        let size = |index: usize| info.get(index).and_then(Value::as_str).and_then(|size| size.parse::<FixedPoint>().ok());
+
+        Ok(SymbolMetadata {
+            symbol: symbol.to_string(),
+            status: "listed".to_string(),
+            base_asset: base.to_string(),
+            quote_asset: quote.to_string(),
+            // Prices have 5 significant digits instead of a tick size
+            tick_size: None,
+            step_size: None,
+            min_qty: size(3),
+            max_qty: size(4),
+        })
+    }
+
+    // Pair info only lists the pairs, which are open for trading
+    fn is_trading(&self, _metadata: &SymbolMetadata) -> bool {
+        true
+    }
+
+    fn sequencing_rules(&self) -> SequencingRules {
+        SequencingRules::Bitfinex
+    }
+
+    fn supports_redundant_depth(&self) -> bool {
+        false
+    }
+}
+
+fn decode_error(message: impl fmt::Display) -> serde_json::Error {
+    de::Error::custom(message.to_string())
+}
+
+fn parse<T: DeserializeOwned>(value: &RawValue) -> Result<T, serde_json::Error> {
+    serde_json::from_str(value.get())
+}
+
+/// A price or amount of a book entry, parsed from its JSON text, since going through f64 would round it
+struct BookNumber(FixedPoint);
+
+impl<'de> Deserialize<'de> for BookNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = Box::<RawValue>::deserialize(deserializer)?;
+        plain_decimal(text.get()).parse().map(BookNumber).map_err(de::Error::custom)
+    }
+}
+
+/// The JSON number in plain decimal notation. Bitfinex prints numbers like JavaScript, i.e. values below 10^-6
+/// in the exponent notation (e.g. `5e-7`)
+fn plain_decimal(text: &str) -> Cow<'_, str> {
+    let Some((mantissa, exponent)) = text.split_once(['e', 'E']) else {
+        return Cow::Borrowed(text);
+    };
+    let Ok(exponent) = exponent.parse::<i32>() else {
+        return Cow::Borrowed(text);
+    };
+
+    let (sign, mantissa) = mantissa.strip_prefix('-').map_or(("", mantissa), |mantissa| ("-", mantissa));
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    // Position of the decimal point within the digits
+    let point = integer.len() as i32 + exponent;
+
+    Cow::Owned(match point {
+        point if point <= 0 => format!("{}0.{}{}", sign, "0".repeat(point.unsigned_abs() as usize), digits),
+        point if point as usize >= digits.len() => format!("{}{}{}", sign, digits, "0".repeat(point as usize - digits.len())),
+        point => format!("{}{}.{}", sign, &digits[..point as usize], &digits[point as usize..]),
+    })
+}
+
+/// Subscription of the connection, as acknowledged by the `subscribed` event
+#[derive(Debug, Default, Deserialize)]
+struct BitfinexSubscription {
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    prec: Option<String>,
+}
+
+/// A text frame of a Bitfinex WebSocket connection
+enum BitfinexFrame {
+    /// Event without market data (subscription acknowledgement, configuration, info)
+    Event,
+    /// Acknowledged subscription
+    Subscribed(BitfinexSubscription),
+    /// Channel message: `[channel id, payload, ...trailing values]`. The values are kept as JSON text, so the numbers
+    /// of the book entries are parsed without rounding
+    Channel(Vec<Box<RawValue>>),
+}
+
+impl BitfinexFrame {
+    /// Parse a frame. Errors and reconnection requests fail the parsing, so the connection is reopened
+    fn parse(message: &str) -> Result<Self, serde_json::Error> {
+        if message.trim_start().starts_with('[') {
+            let channel: Vec<Box<RawValue>> = serde_json::from_str(message)?;
+            if channel.len() < 2 {
+                return Err(decode_error(format!("Unexpected Bitfinex message: '{}'", message)));
+            }
+            return Ok(BitfinexFrame::Channel(channel));
+        }
+
+        let value: Value = serde_json::from_str(message)?;
+        let event = match value {
+            Value::Object(ref event) => event,
+            _ => return Err(decode_error(format!("Unexpected Bitfinex message: '{}'", message))),
+        };
+
+        let code = event.get("code").and_then(Value::as_u64);
+        match event.get("event").and_then(Value::as_str) {
+            Some("error") => Err(decode_error(format!(
+                "Bitfinex error '{}': '{}'",
+                code.unwrap_or_default(),
+                event.get("msg").and_then(Value::as_str).unwrap_or_default()
+            ))),
+            Some("info") if code == Some(INFO_RECONNECT) => Err(decode_error("Bitfinex requested to reconnect")),
+            Some("subscribed") => Ok(BitfinexFrame::Subscribed(BitfinexSubscription::deserialize(&value)?)),
+            event => {
+                tracing::debug!("Bitfinex event '{}': '{}'", event.unwrap_or_default(), message);
+                Ok(BitfinexFrame::Event)
+            }
+        }
+    }
+}
+
+/// Sequence number of a channel message, which follows the payload
+fn sequence(message: &[Box<RawValue>], position: usize) -> Result<u64, serde_json::Error> {
+    message
+        .get(position)
+        .and_then(|value| parse(value).ok())
+        .ok_or_else(|| decode_error("Bitfinex message has no sequence number. Sequence numbers must be enabled with the 'conf' event"))
+}
+
+/// An order of a raw book
+#[derive(Debug, Clone, Copy)]
+struct RawOrder {
+    price: FixedPoint,
+    /// Positive for bids, negative for asks
+    amount: FixedPoint,
+    /// Arrival order, which breaks ties between orders of the same price
+    rank: u64,
+}
+
+/// Decoding state of a book connection
+#[derive(Default)]
+pub struct BitfinexBookState {
+    subscription: BitfinexSubscription,
+    /// Orders of a raw book by order id
+    orders: HashMap<u64, RawOrder>,
+    /// Total amount of the orders of a raw book by side (bid or not) and price
+    levels: HashMap<(bool, FixedPoint), FixedPoint>,
+    next_rank: u64,
+}
+
+impl BitfinexBookState {
+    fn is_raw(&self) -> bool {
+        self.subscription.prec.as_deref() == Some("R0")
+    }
+
+    /// Total amount of the orders at the price level as a book entry of its side
+    fn raw_level(&self, is_bid: bool, price: FixedPoint) -> DepthEntry {
+        let quantity = self.levels.get(&(is_bid, price)).copied().unwrap_or(FixedPoint::ZERO);
+
+        DepthEntry { price, quantity: if is_bid { quantity } else { FixedPoint::ZERO - quantity } }
+    }
+
+    /// Add the amount of an order to the total of its level, removing the level once it has no orders left
+    fn add_to_level(&mut self, is_bid: bool, price: FixedPoint, amount: FixedPoint) {
+        let key = (is_bid, price);
+        let total = self.levels.get(&key).copied().unwrap_or(FixedPoint::ZERO) + amount;
+        match total.is_zero() {
+            true => self.levels.remove(&key),
+            false => self.levels.insert(key, total),
+        };
+    }
+
+    /// Insert, update or remove (price 0) an order, returning the levels it has changed as `(is bid, entry)`
+    fn apply_order(&mut self, id: u64, price: FixedPoint, amount: FixedPoint) -> Vec<(bool, DepthEntry)> {
+        let previous = match price.is_zero() {
+            true => self.orders.remove(&id),
+            false => {
+                let rank = match self.orders.get(&id) {
+                    Some(order) if order.price == price => order.rank,
+                    _ => {
+                        self.next_rank += 1;
+                        self.next_rank
+                    }
+                };
+                self.orders.insert(id, RawOrder { price, amount, rank })
+            }
+        };
+
+        let mut changed = Vec::new();
+        if let Some(previous) = previous {
+            self.add_to_level(previous.amount > FixedPoint::ZERO, previous.price, FixedPoint::ZERO - previous.amount);
+            changed.push((previous.amount > FixedPoint::ZERO, previous.price));
+        }
+        if !price.is_zero() {
+            self.add_to_level(amount > FixedPoint::ZERO, price, amount);
+            changed.push((amount > FixedPoint::ZERO, price));
+        }
+        changed.dedup();
+
+        changed.into_iter().map(|(is_bid, price)| (is_bid, self.raw_level(is_bid, price))).collect()
+    }
+
+    /// Checksum of the best orders of the raw book
+    fn raw_checksum(&self) -> i32 {
+        let mut bids: Vec<(&u64, &RawOrder)> = self.orders.iter().filter(|(_, order)| order.amount > FixedPoint::ZERO).collect();
+        let mut asks: Vec<(&u64, &RawOrder)> = self.orders.iter().filter(|(_, order)| order.amount < FixedPoint::ZERO).collect();
+        bids.sort_by_key(|(_, order)| (std::cmp::Reverse(order.price), order.rank));
+        asks.sort_by_key(|(_, order)| (order.price, order.rank));
+
+        let best = |orders: Vec<(&u64, &RawOrder)>| orders
+            .into_iter()
+            .take(BITFINEX_CHECKSUM_DEPTH)
+            .map(|(id, order)| (*id, order.amount))
+            .collect::<Vec<_>>();
+
+        bitfinex_raw_checksum(&best(bids), &best(asks))
+    }
+
+    /// Book entries of a snapshot. Entries are `[price, count, amount]`, or `[order id, price, amount]` in a raw book
+    fn snapshot(&mut self, entries: &[Box<RawValue>], sequence: u64) -> Result<DepthSnapshot, serde_json::Error> {
+        let mut snapshot = DepthSnapshot { last_update_id: sequence, bids: Vec::new(), asks: Vec::new() };
+
+        if self.is_raw() {
+            self.orders.clear();
+            self.levels.clear();
+            for entry in entries {
+                let (id, BookNumber(price), BookNumber(amount)): (u64, BookNumber, BookNumber) = parse(entry)?;
+                self.apply_order(id, price, amount);
+            }
+
+            let mut levels: Vec<(bool, FixedPoint)> = self.levels.keys().copied().collect();
+            levels.sort();
+            for (is_bid, price) in levels {
+                let level = self.raw_level(is_bid, price);
+                if is_bid { snapshot.bids.push(level) } else { snapshot.asks.push(level) }
+            }
+        } else {
+            for entry in entries {
+                let (BookNumber(price), _count, BookNumber(amount)): (BookNumber, u64, BookNumber) = parse(entry)?;
+                if amount > FixedPoint::ZERO {
+                    snapshot.bids.push(DepthEntry { price, quantity: amount });
+                } else {
+                    snapshot.asks.push(DepthEntry { price, quantity: FixedPoint::ZERO - amount });
+                }
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Book levels changed by an update entry as `(is bid, entry)`
+    fn update(&mut self, entry: &RawValue) -> Result<Vec<(bool, DepthEntry)>, serde_json::Error> {
+        if self.is_raw() {
+            let (id, BookNumber(price), BookNumber(amount)): (u64, BookNumber, BookNumber) = parse(entry)?;
+            return Ok(self.apply_order(id, price, amount));
+        }
+
+        // A level with no orders is removed. Its amount is 1 for bids and -1 for asks
+        let (BookNumber(price), count, BookNumber(amount)): (BookNumber, u64, BookNumber) = parse(entry)?;
+        let is_bid = amount > FixedPoint::ZERO;
+        let quantity = match (count, is_bid) {
+            (0, _) => FixedPoint::ZERO,
+            (_, true) => amount,
+            (_, false) => FixedPoint::ZERO - amount,
+        };
+
+        Ok(vec![(is_bid, DepthEntry { price, quantity })])
+    }
+
+    /// Depth update with the given sequence number, which continues the previous message of the connection
+    fn depth_update(&self, sequence: u64, levels: Vec<(bool, DepthEntry)>, checksum: Option<BookChecksum>) -> DepthUpdate {
+        let (bids, asks): (Vec<_>, Vec<_>) = levels.into_iter().partition(|(is_bid, _)| *is_bid);
+
+        DepthUpdate {
+            event_type: "update".to_string(),
+            // Book messages carry no exchange time
+            event_time: Utc::now().timestamp_millis() as u64,
+            symbol: self.subscription.symbol.clone(),
+            first_update_id: sequence,
+            last_update_id: sequence,
+            previous_last_update_id: sequence.checked_sub(1),
+            bids: bids.into_iter().map(|(_, entry)| entry).collect(),
+            asks: asks.into_iter().map(|(_, entry)| entry).collect(),
+            checksum,
+            received: None,
+        }
+    }
+}
+
+/// Text frames of the Bitfinex book channel
+///
+/// Each message of the connection carries a sequence number, which is the update id (`u`), the previous number
+/// is `pu`. A snapshot is decoded into a DepthSnapshot, an update, heartbeat and checksum message into a DepthUpdate.
+/// The checksum of a level book is verified by the BookProcessor, the checksum of a raw book is verified against
+/// the orders of the connection, a mismatch fails the decoding, so the connection starts over with a snapshot
+pub struct BitfinexBookMessage;
+
+impl StreamMessage for BitfinexBookMessage {
+    type State = BitfinexBookState;
+
+    fn decode(state: &mut BitfinexBookState, message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let message = match BitfinexFrame::parse(message)? {
+            BitfinexFrame::Event => return Ok(Vec::new()),
+            BitfinexFrame::Subscribed(subscription) => {
+                state.subscription = subscription;
+                return Ok(Vec::new());
+            }
+            BitfinexFrame::Channel(message) => message,
+        };
+
+        let payload = &message[1];
+        let event = match payload.get() {
+            // Heartbeats are only numbered, if sequence numbers are enabled
+            r#""hb""# => match sequence(&message, 2) {
+                Ok(sequence) => state.depth_update(sequence, Vec::new(), None).into_market_event(),
+                Err(_) => return Ok(Vec::new()),
+            },
+            r#""cs""# => {
+                let Some(checksum) = message.get(2) else {
+                    return Err(decode_error("Bitfinex checksum message has no checksum"));
+                };
+                let checksum: i32 = parse(checksum)?;
+                let sequence = sequence(&message, 3)?;
+
+                if !state.is_raw() {
+                    state.depth_update(sequence, Vec::new(), Some(BookChecksum::Bitfinex(checksum))).into_market_event()
+                } else if state.raw_checksum() == checksum {
+                    state.depth_update(sequence, Vec::new(), None).into_market_event()
+                } else {
+                    return Err(decode_error(format!("Raw book checksum mismatch at '{}': expected '{}', computed '{}'", sequence, checksum, state.raw_checksum())));
+                }
+            }
+            text if text.starts_with('[') => {
+                let entries: Vec<Box<RawValue>> = parse(payload)?;
+                if entries.first().is_none_or(|entry| entry.get().starts_with('[')) {
+                    MarketEvent::DepthSnapshot(state.snapshot(&entries, sequence(&message, 2)?)?)
+                } else {
+                    let levels = state.update(payload)?;
+                    state.depth_update(sequence(&message, 2)?, levels, None).into_market_event()
+                }
+            }
+            other => return Err(decode_error(format!("Unexpected Bitfinex book message: '{}'", other))),
+        };
+
+        Ok(vec![event])
+    }
+}
+
+/// Decoding state of a trades connection
+#[derive(Default)]
+pub struct BitfinexTradeState {
+    subscription: BitfinexSubscription,
+}
+
+/// Text frames of the Bitfinex trades channel
+///
+/// Executed trades (`te`) are decoded into TradeEvents. The snapshot of recent trades and the later
+/// confirmations of executed trades (`tu`) are skipped
+pub struct BitfinexTradeMessage;
+
+impl StreamMessage for BitfinexTradeMessage {
+    type State = BitfinexTradeState;
+
+    fn decode(state: &mut BitfinexTradeState, message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let message = match BitfinexFrame::parse(message)? {
+            BitfinexFrame::Event => return Ok(Vec::new()),
+            BitfinexFrame::Subscribed(subscription) => {
+                state.subscription = subscription;
+                return Ok(Vec::new());
+            }
+            BitfinexFrame::Channel(message) => message,
+        };
+
+        if message[1].get() != r#""te""# {
+            return Ok(Vec::new());
+        }
+
+        let Some(trade) = message.get(2) else {
+            return Err(decode_error("Bitfinex trade message has no trade"));
+        };
+        let (trade_id, time, amount, price): (u64, u64, f64, f64) = parse(trade)?;
+        Ok(vec![MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: time,
+            symbol: state.subscription.symbol.clone(),
+            trade_id,
+            price,
+            quantity: amount.abs(),
+            trade_time: time,
+            // The amount is negative, if the taker sells, so the buyer is the maker
+            is_market_maker: amount < 0.0,
+            ignore: false,
+            received: None,
+        })])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::checksum::{bitfinex_checksum, crc32};
+    use crate::mdc_core::order_book::OrderBook;
+
+    fn make_connector(precision: BitfinexPrecision) -> BitfinexConnector {
+        BitfinexConnector::new(
+            "https://api-pub.bitfinex.com/v2/".to_string(),
+            "wss://api-pub.bitfinex.com/ws/2".to_string(),
+            precision,
+            100,
+        )
+    }
+
+    fn decode_book(state: &mut BitfinexBookState, message: &str) -> MarketEvent {
+        let mut events = BitfinexBookMessage::decode(state, message).unwrap();
+        assert_eq!(events.len(), 1);
+        events.remove(0)
+    }
+
+    fn fp(value: &str) -> FixedPoint {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_streams_and_subscriptions() {
+        let connector = make_connector(BitfinexPrecision::P0);
+
+        assert_eq!(connector.stream_url(StreamKind::Depth, "tBTCUSD").unwrap(), "wss://api-pub.bitfinex.com/ws/2");
+        assert_eq!(connector.stream_url(StreamKind::Price, "tBTCUSD"), None);
+        assert_eq!(
+            connector.subscription(StreamKind::Depth, "tBTCUSD"),
+            [
+                r#"{"event":"conf","flags":196608}"#,
+                r#"{"channel":"book","event":"subscribe","freq":"F0","len":"100","prec":"P0","symbol":"tBTCUSD"}"#,
+            ]
+        );
+        assert_eq!(
+            connector.subscription(StreamKind::Trade, "tBTCUSD"),
+            [r#"{"channel":"trades","event":"subscribe","symbol":"tBTCUSD"}"#]
+        );
+        assert_eq!(
+            make_connector(BitfinexPrecision::R0).subscription(StreamKind::Depth, "tBTCUSD")[1],
+            r#"{"channel":"book","event":"subscribe","len":"100","prec":"R0","symbol":"tBTCUSD"}"#
+        );
+        assert_eq!(connector.snapshot_url("tBTCUSD", 100), None);
+        assert_eq!(connector.server_time_url(), None);
+        assert!(!connector.supports_redundant_depth());
+        assert_eq!(BitfinexConnector::new(String::new(), String::new(), BitfinexPrecision::P0, 1000).book_length, 250);
+    }
+
+    #[test]
+    fn test_symbol_metadata() {
+        let connector = make_connector(BitfinexPrecision::P0);
+        assert_eq!(connector.exchange_info_url("tBTCUSD"), "https://api-pub.bitfinex.com/v2/conf/pub:info:pair");
+        assert_eq!(connector.exchange_info_url("tBTCF0:USTF0"), "https://api-pub.bitfinex.com/v2/conf/pub:info:pair:futures");
+
+        let response = r#"[[["BTCUSD",[null,null,null,"0.00006","2000.0",null,null,null,0.2,0.1]],["TESTBTC:TESTUSD",[null,null,null,"0.0006","200.0",null,null,null,0.2,0.1]]]]"#;
+        let metadata = connector.parse_symbol_metadata("tBTCUSD", response).unwrap();
+        assert_eq!((metadata.base_asset.as_str(), metadata.quote_asset.as_str()), ("BTC", "USD"));
+        assert_eq!(metadata.min_qty, Some(fp("0.00006")));
+        assert_eq!(metadata.max_qty, Some(fp("2000")));
+
+        let metadata = connector.parse_symbol_metadata("tTESTBTC:TESTUSD", response).unwrap();
+        assert_eq!((metadata.base_asset.as_str(), metadata.quote_asset.as_str()), ("TESTBTC", "TESTUSD"));
+        assert!(connector.parse_symbol_metadata("tETHUSD", response).is_err());
+    }
+
+    #[test]
+    fn test_decode_level_book() {
+        let mut state = BitfinexBookState::default();
+        assert!(BitfinexBookMessage::decode(&mut state, r#"{"event":"conf","status":"OK","flags":196608}"#).unwrap().is_empty());
+        assert!(BitfinexBookMessage::decode(&mut state, r#"{"event":"subscribed","channel":"book","chanId":17,"symbol":"tBTCUSD","prec":"P0","freq":"F0","len":"25","pair":"BTCUSD"}"#).unwrap().is_empty());
+
+        let MarketEvent::DepthSnapshot(snapshot) = decode_book(&mut state, "[17,[[6000,2,1.5],[5999,1,0.5],[6001,1,-2]],1]") else {
+            panic!("Snapshot expected");
+        };
+        assert_eq!(snapshot.last_update_id, 1);
+        assert_eq!(snapshot.bids, vec![DepthEntry { price: fp("6000"), quantity: fp("1.5") }, DepthEntry { price: fp("5999"), quantity: fp("0.5") }]);
+        assert_eq!(snapshot.asks, vec![DepthEntry { price: fp("6001"), quantity: fp("2") }]);
+        let mut book = OrderBook::new(&snapshot);
+
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, "[17,[5999,0,1],2]") else {
+            panic!("Update expected");
+        };
+        assert_eq!((update.first_update_id, update.last_update_id, update.previous_last_update_id), (2, 2, Some(1)));
+        assert_eq!(update.symbol, "tBTCUSD");
+        assert_eq!(update.bids, vec![DepthEntry { price: fp("5999"), quantity: FixedPoint::ZERO }]);
+        book.apply_update(OrderBook::bid(fp("5999")), FixedPoint::ZERO);
+
+        // The checksum message is an empty update, which carries the checksum of the book
+        let checksum = bitfinex_checksum(&book);
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, &format!("[17,\"cs\",{},3]", checksum)) else {
+            panic!("Update expected");
+        };
+        assert!(update.bids.is_empty() && update.asks.is_empty());
+        assert_eq!(update.previous_last_update_id, Some(2));
+        assert!(update.checksum.unwrap().verify(&book));
+
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, r#"[17,"hb",4]"#) else {
+            panic!("Update expected");
+        };
+        assert_eq!(update.last_update_id, 4);
+
+        assert!(BitfinexBookMessage::decode(&mut state, "[17,[6002,1,-1]]").is_err());
+        assert!(BitfinexBookMessage::decode(&mut state, r#"{"event":"info","code":20051,"msg":"Stopping. Please try to reconnect"}"#).is_err());
+        assert!(BitfinexBookMessage::decode(&mut state, r#"{"event":"error","code":10300,"msg":"Subscription failed (generic)"}"#).is_err());
+    }
+
+    #[test]
+    fn test_decode_level_book_numbers_as_sent() {
+        let mut state = BitfinexBookState::default();
+        BitfinexBookMessage::decode(&mut state, r#"{"event":"subscribed","channel":"book","chanId":17,"symbol":"tPEPUSD","prec":"P0","len":"25","pair":"PEPUSD"}"#).unwrap();
+
+        // Prices below 10^-6 are sent in the exponent notation, amounts may have more digits than an f64 holds
+        let MarketEvent::DepthSnapshot(snapshot) = decode_book(&mut state, "[17,[[0.0000086,1,98765432.12345678],[8.5e-7,1,0.5],[0.0000087,1,-2]],1]") else {
+            panic!("Snapshot expected");
+        };
+        assert_eq!(snapshot.bids, vec![
+            DepthEntry { price: fp("0.0000086"), quantity: fp("98765432.12345678") },
+            DepthEntry { price: fp("0.00000085"), quantity: fp("0.5") },
+        ]);
+        let book = OrderBook::new(&snapshot);
+
+        let checksum = crc32(b"0.0000086:98765432.12345678:0.0000087:-2:8.5e-7:0.5") as i32;
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, &format!("[17,\"cs\",{},2]", checksum)) else {
+            panic!("Update expected");
+        };
+        assert!(update.checksum.unwrap().verify(&book));
+    }
+
+    #[test]
+    fn test_decode_raw_book() {
+        let mut state = BitfinexBookState::default();
+        BitfinexBookMessage::decode(&mut state, r#"{"event":"subscribed","channel":"book","chanId":18,"symbol":"tBTCUSD","prec":"R0","len":"25","pair":"BTCUSD"}"#).unwrap();
+
+        let MarketEvent::DepthSnapshot(snapshot) = decode_book(&mut state, "[18,[[11,6000,1.5],[12,6000,0.5],[21,6001,-2]],1]") else {
+            panic!("Snapshot expected");
+        };
+        assert_eq!(snapshot.bids, vec![DepthEntry { price: fp("6000"), quantity: fp("2") }]);
+        assert_eq!(snapshot.asks, vec![DepthEntry { price: fp("6001"), quantity: fp("2") }]);
+
+        // Removing an order reduces its level, moving an order changes both levels
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, "[18,[11,0,1],2]") else {
+            panic!("Update expected");
+        };
+        assert_eq!(update.bids, vec![DepthEntry { price: fp("6000"), quantity: fp("0.5") }]);
+
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, "[18,[21,6002,-1],3]") else {
+            panic!("Update expected");
+        };
+        assert_eq!(update.asks, vec![DepthEntry { price: fp("6001"), quantity: FixedPoint::ZERO }, DepthEntry { price: fp("6002"), quantity: fp("1") }]);
+
+        let checksum = bitfinex_raw_checksum(&[(12, fp("0.5"))], &[(21, fp("-1"))]);
+        let MarketEvent::DepthUpdate(update) = decode_book(&mut state, &format!("[18,\"cs\",{},4]", checksum)) else {
+            panic!("Update expected");
+        };
+        assert_eq!(update.checksum, None);
+        assert!(BitfinexBookMessage::decode(&mut state, &format!("[18,\"cs\",{},5]", checksum.wrapping_add(1))).is_err());
+    }
+
+    #[test]
+    fn test_decode_trades() {
+        let mut state = BitfinexTradeState::default();
+        BitfinexTradeMessage::decode(&mut state, r#"{"event":"subscribed","channel":"trades","chanId":19,"symbol":"tBTCUSD","pair":"BTCUSD"}"#).unwrap();
+
+        assert!(BitfinexTradeMessage::decode(&mut state, "[19,[[401597395,1574694478808,0.005,7245.3]]]").unwrap().is_empty());
+        assert!(BitfinexTradeMessage::decode(&mut state, "[19,\"tu\",[401597393,1574694475039,-0.0012,7245.3]]").unwrap().is_empty());
+
+        let events = BitfinexTradeMessage::decode(&mut state, "[19,\"te\",[401597393,1574694475039,-0.0012,7245.3]]").unwrap();
+        let [MarketEvent::TradeEvent(trade)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(trade.symbol, "tBTCUSD");
+        assert_eq!(trade.trade_id, 401597393);
+        assert_eq!(trade.quantity, 0.0012);
+        assert_eq!(trade.trade_time, 1574694475039);
+        assert!(trade.is_market_maker);
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use crate::mdc_core::models::DepthSnapshot;
+use crate::mdc_core::order_book::{OrderBook, Side};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// Path of the book checkpoint of an exchange and instrument
+///
+/// Checkpoints are placed in the `.checkpoints` subdirectory of the capture directory. Unlike the session artifacts,
+/// the path doesn't change between sessions, so the next session of the instrument finds the checkpoint
+pub fn checkpoint_path<P: AsRef<Path>>(capture_dir: P, exchange: &str, instrument: &str) -> PathBuf {
+    capture_dir.as_ref().join(".checkpoints").join(format!("{}_{}.json", exchange, instrument))
+}
+
+/// Content of a checkpoint file: the time it has been taken and the book in the format of a REST snapshot,
+/// where `lastUpdateId` is the last update applied to the book. Prices and quantities are kept as decimal strings,
+/// so the book is restored exactly
+#[derive(Serialize)]
+struct CheckpointRecord {
+    time: i64,
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+}
+
+/// The book of a previous session along with the last update applied to it
+#[derive(Debug, Clone, Deserialize)]
+pub struct BookCheckpoint {
+    /// Local time in milliseconds since epoch, when the checkpoint has been taken
+    pub time: i64,
+    #[serde(flatten)]
+    pub snapshot: DepthSnapshot,
+}
+
+impl BookCheckpoint {
+    /// Write the book into the file. The file is replaced atomically, so a crash never leaves a partial checkpoint
+    ///
+    /// # Arguments
+    /// * `path` - Path of the checkpoint file
+    /// * `time` - Local time of the checkpoint in milliseconds since epoch
+    /// * `last_update_id` - The last update applied to the book
+    /// * `book` - The book
+    pub fn save(path: &Path, time: i64, last_update_id: u64, book: &OrderBook) -> Result<()> {
+        let levels = |side| book.levels(side).map(|entry| [entry.price.to_string(), entry.quantity.to_string()]).collect();
+        let record = CheckpointRecord {
+            time,
+            last_update_id,
+            bids: levels(Side::Bid),
+            asks: levels(Side::Ask),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create checkpoint directory: {:?}", parent))?;
+        }
+
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_vec(&record)?)
+            .with_context(|| format!("Failed to write checkpoint: {:?}", temporary))?;
+        fs::rename(&temporary, path)
+            .with_context(|| format!("Failed to replace checkpoint: {:?}", path))
+    }
+
+    /// Read the checkpoint from the file
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read checkpoint: {:?}", path))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse checkpoint: {:?}", path))
+    }
+
+    /// Age of the checkpoint in milliseconds at the given time
+    pub fn age(&self, now: i64) -> u64 {
+        now.saturating_sub(self.time).max(0) as u64
+    }
+}
+
+/// Writes the books sent by the BookProcessor into the checkpoint file
+///
+/// Each message is the last update id applied to the book along with the book itself
+pub struct CheckpointWriter {
+    path: PathBuf,
+    input: mpsc::Receiver<(u64, Arc<OrderBook>)>,
+    written: Counter,
+}
+
+impl CheckpointWriter {
+    /// Create a new CheckpointWriter
+    ///
+    /// # Arguments
+    /// * `path` - Path of the checkpoint file
+    /// * `input` - Receiver for the books to checkpoint
+    /// * `metrics` - Registry of the `checkpoints_written` counter
+    pub fn new(path: PathBuf, input: mpsc::Receiver<(u64, Arc<OrderBook>)>, metrics: &Metrics) -> Self {
+        Self {
+            path,
+            input,
+            written: metrics.counter("checkpoints_written"),
+        }
+    }
+
+    /// Run the CheckpointWriter as an asynchronous task
+    ///
+    /// A failed write is logged, the next book is written as usual
+    pub async fn run(mut self) {
+        while let Some((last_update_id, book)) = self.input.recv().await {
+            match BookCheckpoint::save(&self.path, Utc::now().timestamp_millis(), last_update_id, &book) {
+                Ok(()) => self.written.increment(1),
+                Err(e) => tracing::error!("Failed to write book checkpoint at update '{}'. Details: '{:#}'", last_update_id, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::DepthEntry;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_checkpoint", std::process::id()));
+        let path = checkpoint_path(&dir, "binance", "BTCUSDT");
+        assert!(BookCheckpoint::load(&path).is_err());
+
+        let entry = |price: &str, quantity: &str| DepthEntry { price: price.parse().unwrap(), quantity: quantity.parse().unwrap() };
+        let book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry("100.01", "0.5"), entry("99.99", "1.25")],
+            asks: vec![entry("100.02", "3")],
+        });
+        BookCheckpoint::save(&path, 1000, 42, &book).unwrap();
+
+        let checkpoint = BookCheckpoint::load(&path).unwrap();
+        assert_eq!((checkpoint.time, checkpoint.snapshot.last_update_id), (1000, 42));
+        assert_eq!(checkpoint.snapshot.bids, vec![entry("100.01", "0.5"), entry("99.99", "1.25")]);
+        assert_eq!(checkpoint.snapshot.asks, vec![entry("100.02", "3")]);
+        assert_eq!(checkpoint.age(4000), 3000);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
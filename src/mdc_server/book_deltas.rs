@@ -0,0 +1,207 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use crate::mdc_server::disk_space_guard::CaptureGate;
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::order_book::{OrderBook, Side};
+
+/// Price levels of the book changed by a single depth update
+///
+/// A level is listed at most once: either with its new quantity or as removed
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BookDelta {
+    /// Last update id of the depth update
+    pub update_id: u64,
+    /// Exchange event time of the depth update
+    pub event_time: u64,
+    /// Added or modified bid levels as `[price, quantity]`
+    pub changed_bids: Vec<(FixedPoint, FixedPoint)>,
+    /// Added or modified ask levels as `[price, quantity]`
+    pub changed_asks: Vec<(FixedPoint, FixedPoint)>,
+    /// Removed levels as `[side, price]`
+    pub removed: Vec<(Side, FixedPoint)>,
+}
+
+impl BookDelta {
+    /// Record a change of a price level, replacing an earlier change of the same level
+    ///
+    /// # Arguments
+    /// * `side` - Side of the level
+    /// * `price` - Price of the level
+    /// * `quantity` - The new quantity, 0 removes the level
+    pub fn record(&mut self, side: Side, price: FixedPoint, quantity: FixedPoint) {
+        let changed = match side {
+            Side::Bid => &mut self.changed_bids,
+            Side::Ask => &mut self.changed_asks,
+        };
+        changed.retain(|(changed_price, _)| *changed_price != price);
+        self.removed.retain(|removed| *removed != (side, price));
+
+        if quantity.is_zero() {
+            self.removed.push((side, price));
+        } else {
+            changed.push((price, quantity));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed_bids.is_empty() && self.changed_asks.is_empty() && self.removed.is_empty()
+    }
+
+    /// Apply the delta to a book rebuilt from the preceding snapshot and deltas
+    pub fn apply(&self, book: &mut OrderBook) {
+        for (price, quantity) in &self.changed_bids {
+            book.apply_update(OrderBook::bid(*price), *quantity);
+        }
+        for (price, quantity) in &self.changed_asks {
+            book.apply_update(OrderBook::ask(*price), *quantity);
+        }
+        for (side, price) in &self.removed {
+            let price_key = match side {
+                Side::Bid => OrderBook::bid(*price),
+                Side::Ask => OrderBook::ask(*price),
+            };
+            book.apply_update(price_key, FixedPoint::ZERO);
+        }
+    }
+}
+
+/// Full copy of the book, which replaces the book rebuilt by the consumer
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BookSnapshot {
+    /// Last update id applied to the book
+    pub update_id: u64,
+    /// Bid levels as `[price, quantity]`, best first
+    pub bids: Vec<(FixedPoint, FixedPoint)>,
+    /// Ask levels as `[price, quantity]`, best first
+    pub asks: Vec<(FixedPoint, FixedPoint)>,
+}
+
+impl BookSnapshot {
+    pub fn new(update_id: u64, book: &OrderBook) -> Self {
+        Self {
+            update_id,
+            bids: book.bids.iter().map(|level| (level.price, level.quantity)).collect(),
+            asks: book.asks.iter().map(|level| (level.price, level.quantity)).collect(),
+        }
+    }
+}
+
+/// Incremental representation of the book: a full snapshot followed by the deltas of each update
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BookDeltaEvent {
+    Snapshot(BookSnapshot),
+    Delta(BookDelta),
+}
+
+/// BookDeltaWriter appends book snapshots and deltas into a JSON lines file
+pub struct BookDeltaWriter {
+    input: mpsc::Receiver<BookDeltaEvent>,
+    writer: BufWriter<File>,
+    gate: CaptureGate,
+    deltas: Counter,
+    snapshots: Counter,
+}
+
+impl BookDeltaWriter {
+    /// Open the file for appending, creating it if needed
+    ///
+    /// # Arguments
+    /// * `path` - Path of the book delta file
+    /// * `input` - Receiver for the book snapshots and deltas
+    /// * `gate` - Switch, which pauses writing. Events received while writing is paused are discarded
+    /// * `metrics` - Registry of the writer counters
+    pub fn open(path: &PathBuf, input: mpsc::Receiver<BookDeltaEvent>, gate: CaptureGate, metrics: &Metrics) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open book delta file: {:?}", path))?;
+
+        Ok(Self {
+            input,
+            writer: BufWriter::new(file),
+            gate,
+            deltas: metrics.counter("book_deltas_written"),
+            snapshots: metrics.counter("book_delta_snapshots_written"),
+        })
+    }
+
+    fn write(&mut self, event: &BookDeltaEvent) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")?;
+        match event {
+            BookDeltaEvent::Snapshot(_) => self.snapshots.increment(1),
+            BookDeltaEvent::Delta(_) => self.deltas.increment(1),
+        }
+        Ok(())
+    }
+
+    /// Run the BookDeltaWriter as an asynchronous task
+    ///
+    /// The file is flushed whenever the input channel is drained
+    pub async fn run(mut self) {
+        while let Some(event) = self.input.recv().await {
+            if self.gate.is_paused() {
+                continue;
+            }
+
+            if let Err(e) = self.write(&event) {
+                tracing::error!("Failed to write book delta. Details: '{}'", e);
+            }
+
+            if self.input.is_empty() {
+                if let Err(e) = self.writer.flush() {
+                    tracing::error!("Failed to flush book delta file. Details: '{}'", e);
+                }
+            }
+        }
+
+        if let Err(e) = self.writer.flush() {
+            tracing::error!("Failed to flush book delta file. Details: '{}'", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_keeps_the_last_change_of_a_level() {
+        let mut delta = BookDelta::default();
+        delta.record(Side::Bid, FixedPoint::from(100.0), FixedPoint::from(1.0));
+        delta.record(Side::Bid, FixedPoint::from(100.0), FixedPoint::from(2.0));
+        delta.record(Side::Ask, FixedPoint::from(101.0), FixedPoint::ZERO);
+        assert_eq!(delta.changed_bids, vec![(FixedPoint::from(100.0), FixedPoint::from(2.0))]);
+        assert_eq!(delta.removed, vec![(Side::Ask, FixedPoint::from(101.0))]);
+
+        delta.record(Side::Bid, FixedPoint::from(100.0), FixedPoint::ZERO);
+        delta.record(Side::Ask, FixedPoint::from(101.0), FixedPoint::from(3.0));
+        assert!(delta.changed_bids.is_empty());
+        assert_eq!(delta.changed_asks, vec![(FixedPoint::from(101.0), FixedPoint::from(3.0))]);
+        assert_eq!(delta.removed, vec![(Side::Bid, FixedPoint::from(100.0))]);
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut delta = BookDelta { update_id: 42, event_time: 1000, ..Default::default() };
+        delta.record(Side::Bid, FixedPoint::from(100.5), FixedPoint::from(2.0));
+        delta.record(Side::Ask, FixedPoint::from(101.0), FixedPoint::ZERO);
+        assert_eq!(
+            serde_json::to_string(&BookDeltaEvent::Delta(delta)).unwrap(),
+            r#"{"type":"delta","update_id":42,"event_time":1000,"changed_bids":[[100.5,2.0]],"changed_asks":[],"removed":[["ask",101.0]]}"#
+        );
+
+        let snapshot = BookSnapshot { update_id: 41, bids: vec![(FixedPoint::from(100.0), FixedPoint::from(1.0))], asks: vec![] };
+        assert_eq!(
+            serde_json::to_string(&BookDeltaEvent::Snapshot(snapshot)).unwrap(),
+            r#"{"type":"snapshot","update_id":41,"bids":[[100.0,1.0]],"asks":[]}"#
+        );
+    }
+}
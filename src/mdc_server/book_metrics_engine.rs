@@ -0,0 +1,75 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::mpsc;
+use crate::mdc_core::book_metrics::BookMetrics;
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+
+/// BookMetricsEngine computes the imbalance, the microprice and the weighted mid of every book update
+/// and sends them as `BookMetrics` events along with the auxiliary events, so every sink receives them
+pub struct BookMetricsEngine {
+    depth: usize,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    output: mpsc::Sender<MarketEvent>,
+}
+
+impl BookMetricsEngine {
+    /// Create a new BookMetricsEngine
+    ///
+    /// # Arguments
+    /// * `depth` - Number of top levels per side, which the imbalance and the weighted mid are computed over
+    /// * `book_channel` - Receiver for OrderBook messages
+    /// * `output` - Sender for MarketEvent messages containing BookMetrics
+    pub fn new(depth: usize, book_channel: mpsc::Receiver<Arc<OrderBook>>, output: mpsc::Sender<MarketEvent>) -> Self {
+        Self {
+            depth,
+            book_channel,
+            output,
+        }
+    }
+
+    /// Run the BookMetricsEngine as an asynchronous task
+    ///
+    /// This method will continuously compute the metrics until the book channel is closed.
+    /// Books with an empty side are skipped
+    pub async fn run(mut self) {
+        while let Some(book) = self.book_channel.recv().await {
+            let Some(metrics) = BookMetrics::compute(&book, self.depth, Utc::now().timestamp_millis()) else {
+                continue;
+            };
+
+            if let Err(e) = self.output.send(MarketEvent::BookMetrics(metrics)).await {
+                tracing::error!("Failed to send book metrics. Details: '{}'", e);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    #[tokio::test]
+    async fn test_metrics_are_sent_for_two_sided_books() {
+        let (book_tx, book_rx) = mpsc::channel(10);
+        let (output_tx, mut output_rx) = mpsc::channel(10);
+        let engine = BookMetricsEngine::new(5, book_rx, output_tx);
+
+        let entry = |price: &str| DepthEntry { price: price.parse().unwrap(), quantity: "1".parse().unwrap() };
+        let one_sided = DepthSnapshot { last_update_id: 1, bids: vec![entry("100")], asks: vec![] };
+        let two_sided = DepthSnapshot { last_update_id: 2, bids: vec![entry("100")], asks: vec![entry("102")] };
+        book_tx.send(Arc::new(OrderBook::new(&one_sided))).await.unwrap();
+        book_tx.send(Arc::new(OrderBook::new(&two_sided))).await.unwrap();
+        drop(book_tx);
+
+        engine.run().await;
+
+        let Some(MarketEvent::BookMetrics(metrics)) = output_rx.recv().await else {
+            panic!("Book metrics are expected");
+        };
+        assert_eq!((metrics.mid_price, metrics.microprice, metrics.imbalance), (101.0, 101.0, 0.0));
+        assert!(output_rx.recv().await.is_none());
+    }
+}
@@ -1,13 +1,100 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use futures::future::BoxFuture;
 use tokio::sync::mpsc;
-use crate::mdc_server::models::{MarketEvent, DepthSnapshot, DepthUpdate};
-use crate::mdc_server::order_book::OrderBook;
+use tokio::time::{Duration, Instant};
+use crate::mdc_core::checksum::BookChecksum;
+use crate::mdc_core::continuity::{Resync, ResyncSource};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::level_pool::LEVEL_POOL;
+use crate::mdc_core::models::{MarketEvent, DepthEntry, DepthSnapshot, DepthUpdate, BboChange};
+use crate::mdc_core::order_book::{OrderBook, Side};
+use crate::mdc_core::sequencing::{SequencingRules, SequencingStrategy};
+use crate::mdc_server::book_deltas::{BookDelta, BookDeltaEvent, BookSnapshot};
+use crate::mdc_server::allocation_profiling::BOOK_ALLOCATIONS;
+use crate::mdc_server::book_validator::BookValidator;
+use crate::mdc_server::level_changes::{ChangeKind, LevelChange};
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_server::task_supervisor::RestartableTask;
+
+/// Maximum number of depth updates held while waiting for the first snapshot
+const MAX_PENDING_UPDATES: usize = 1000;
+
+/// Number of best levels per side of the published snapshots, unless set with `with_published_depth`
+pub const DEFAULT_PUBLISHED_DEPTH: usize = 20;
 
 /// BookProcessor is an asynchronous wrapper around OrderBook
-/// It processes MarketEvent messages from an input channel and sends updated OrderBook instances to an output channel
+/// It processes MarketEvent messages from an input channel and sends shared OrderBook snapshots to an output channel
+/// The processor owns the book and updates it in place. After each update it sends an immutable snapshot of the
+/// `published_depth` best levels (`OrderBook::top`), which the consumers may hold as long as they like. Each snapshot
+/// copies these levels, so the published depth should be no deeper than the consumers read (see the
+/// `publish_1000_updates` group of the `order_book` bench). The whole book is copied only for checkpoints
+/// Whenever the top of the book changes, a MarketEvent::BboChange is sent to a separate output channel
+/// Depth updates, which arrive before the first snapshot (e.g. during startup races), are held until it arrives
 pub struct BookProcessor {
     order_book: Option<OrderBook>,
+    published_depth: usize,
+    pending_updates: VecDeque<DepthUpdate>,
+    last_bbo: Option<BboChange>,
     input: mpsc::Receiver<MarketEvent>,
-    output: mpsc::Sender<OrderBook>,
+    output: mpsc::Sender<Arc<OrderBook>>,
+    bbo_output: mpsc::Sender<MarketEvent>,
+    level_change_output: Option<mpsc::Sender<Vec<LevelChange>>>,
+    book_deltas: Option<BookDeltas>,
+    max_depth: Option<usize>,
+    price_band: Option<f64>,
+    tick_size: Option<(FixedPoint, Counter)>,
+    validation: Option<Validation>,
+    crossed_check: Option<CrossedBookCheck>,
+    checksum_check: Option<ChecksumCheck>,
+    held_update_check: Option<HeldUpdateCheck>,
+    checkpoints: Option<Checkpoints>,
+    markers: Option<mpsc::Sender<MarketEvent>>,
+}
+
+/// Periodic checkpoints of the book along with the last applied update id
+struct Checkpoints {
+    output: mpsc::Sender<(u64, Arc<OrderBook>)>,
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+/// Incremental book deltas along with periodic full snapshots of the book
+struct BookDeltas {
+    output: mpsc::Sender<BookDeltaEvent>,
+    snapshot_interval: Duration,
+    last_snapshot: Option<Instant>,
+    pending: BookDelta,
+}
+
+/// Detection of a crossed book, i.e. the best bid at or above the best ask
+struct CrossedBookCheck {
+    snapshot_requests: mpsc::Sender<()>,
+    crossed_books: Counter,
+    is_crossed: bool,
+}
+
+/// Verification of the book against the checksums sent along with depth updates
+struct ChecksumCheck {
+    snapshot_requests: mpsc::Sender<()>,
+    mismatches: Counter,
+    is_diverged: bool,
+}
+
+/// Continuity check of the depth updates held until the first snapshot
+struct HeldUpdateCheck {
+    rules: SequencingRules,
+    snapshot_requests: mpsc::Sender<()>,
+    gaps: Counter,
+}
+
+/// Validation of the book against reference snapshots
+struct Validation {
+    validator: BookValidator,
+    input: Option<mpsc::Receiver<MarketEvent>>,
+    resyncs: Counter,
 }
 
 impl BookProcessor {
@@ -15,30 +102,358 @@ impl BookProcessor {
     ///
     /// # Arguments
     /// * `input` - Receiver for MarketEvent messages
-    /// * `output` - Sender for OrderBook updates
-    pub fn new(input: mpsc::Receiver<MarketEvent>, output: mpsc::Sender<OrderBook>) -> Self {
+    /// * `output` - Sender for shared OrderBook snapshots
+    /// * `bbo_output` - Sender for MarketEvent::BboChange messages
+    pub fn new(
+        input: mpsc::Receiver<MarketEvent>,
+        output: mpsc::Sender<Arc<OrderBook>>,
+        bbo_output: mpsc::Sender<MarketEvent>,
+    ) -> Self {
         Self {
             order_book: None,
+            published_depth: DEFAULT_PUBLISHED_DEPTH,
+            pending_updates: VecDeque::new(),
+            last_bbo: None,
             input,
             output,
+            bbo_output,
+            level_change_output: None,
+            book_deltas: None,
+            max_depth: None,
+            price_band: None,
+            tick_size: None,
+            validation: None,
+            crossed_check: None,
+            checksum_check: None,
+            held_update_check: None,
+            checkpoints: None,
+            markers: None,
+        }
+    }
+
+    /// Additionally send a `Resync` event to the given channel, whenever the book is replaced with a validation snapshot
+    pub fn with_markers(mut self, output: mpsc::Sender<MarketEvent>) -> Self {
+        self.markers = Some(output);
+        self
+    }
+
+    /// Additionally send the price level changes caused by each depth update to the output channel
+    pub fn with_level_changes(mut self, output: mpsc::Sender<Vec<LevelChange>>) -> Self {
+        self.level_change_output = Some(output);
+        self
+    }
+
+    /// Additionally send the levels changed by each depth update to the output channel, instead of the whole book
+    ///
+    /// A full snapshot of the book is sent first, after each snapshot or resync, after updates which were not sent
+    /// (e.g. while the book was crossed) and at most once per interval in milliseconds (0 disables periodic snapshots),
+    /// so a consumer can rebuild the book from the latest snapshot and the following deltas
+    pub fn with_book_deltas(mut self, output: mpsc::Sender<BookDeltaEvent>, snapshot_interval: u64) -> Self {
+        self.book_deltas = Some(BookDeltas {
+            output,
+            snapshot_interval: Duration::from_millis(snapshot_interval),
+            last_snapshot: None,
+            pending: BookDelta::default(),
+        });
+        self
+    }
+
+    /// Retain only the `max_depth` best levels per side, bounding the memory and the copy cost of the book
+    ///
+    /// Levels beyond the depth are pruned, as better levels arrive. Updates of pruned prices are ignored
+    /// until the next snapshot, so the book may hold fewer levels in the meantime
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Retain only the levels within `percent` of the mid price, pruning the others after each update
+    ///
+    /// As with the maximum depth, updates of pruned prices are ignored until the next snapshot,
+    /// so the band may be only partly filled after the mid price moves
+    pub fn with_price_band(mut self, percent: f64) -> Self {
+        self.price_band = Some(percent);
+        self
+    }
+
+    /// Build the book from a snapshot or a reference book, limited to the maximum depth and the price band if they are set
+    fn limit_depth(&self, order_book: OrderBook) -> OrderBook {
+        let mut order_book = match self.max_depth {
+            Some(max_depth) => order_book.with_max_depth(max_depth),
+            None => order_book,
+        };
+        if let Some(percent) = self.price_band {
+            order_book.prune_outside_band(percent);
+        }
+        order_book
+    }
+
+    /// Additionally check that prices of received levels are whole numbers of the symbol tick size
+    ///
+    /// Levels off the tick grid are still applied, but counted in the `book_off_tick_levels` counter,
+    /// as they indicate wrong symbol metadata or corrupted input
+    pub fn with_tick_size(mut self, tick_size: FixedPoint, metrics: &Metrics) -> Self {
+        self.tick_size = Some((tick_size, metrics.counter("book_off_tick_levels")));
+        self
+    }
+
+    /// Send snapshots of the `depth` best levels per side to the output channel (`DEFAULT_PUBLISHED_DEPTH` by default)
+    pub fn with_published_depth(mut self, depth: usize) -> Self {
+        self.published_depth = depth;
+        self
+    }
+
+    /// Additionally compare the book with reference snapshots received from the input channel
+    ///
+    /// A drift is logged and counted by the validator. If the validator is configured to resync,
+    /// the book is replaced with the reference and sent to the output channels, the `book_resyncs` counter is incremented
+    pub fn with_validation(mut self, validator: BookValidator, input: mpsc::Receiver<MarketEvent>, metrics: &Metrics) -> Self {
+        self.validation = Some(Validation { validator, input: Some(input), resyncs: metrics.counter("book_resyncs") });
+        self
+    }
+
+    /// Additionally check that the book is not crossed after each update
+    ///
+    /// A crossed book is corrupted, so it is not sent to the output channels until a snapshot (or further updates)
+    /// uncross it. Each time the book becomes crossed, the `book_crossed` counter is incremented
+    /// and a fresh snapshot is requested through the given channel
+    pub fn with_crossed_book_check(mut self, snapshot_requests: mpsc::Sender<()>, metrics: &Metrics) -> Self {
+        self.crossed_check = Some(CrossedBookCheck {
+            snapshot_requests,
+            crossed_books: metrics.counter("book_crossed"),
+            is_crossed: false,
+        });
+        self
+    }
+
+    /// Additionally verify the book against the checksum of each depth update, which carries one (e.g. OKX)
+    ///
+    /// A mismatch means the book has diverged from the venue book, so it is not sent to the output channels
+    /// until the next snapshot. On a mismatch the `book_checksum_mismatches` counter is incremented
+    /// and a fresh snapshot is requested through the given channel
+    pub fn with_checksum_check(mut self, snapshot_requests: mpsc::Sender<()>, metrics: &Metrics) -> Self {
+        self.checksum_check = Some(ChecksumCheck {
+            snapshot_requests,
+            mismatches: metrics.counter("book_checksum_mismatches"),
+            is_diverged: false,
+        });
+        self
+    }
+
+    /// Additionally check that the depth updates held until the first snapshot continue it without a gap
+    ///
+    /// Held updates may miss some, e.g. if the oldest ones have been discarded at the limit of held updates. If the
+    /// first update after the snapshot doesn't bridge it or an update doesn't follow the one before it, according to
+    /// the rules of the venue, the held updates are dropped, the `book_held_update_gaps` counter is incremented, the
+    /// following updates are held again and a fresh snapshot is requested through the given channel
+    pub fn with_held_update_check(mut self, rules: SequencingRules, snapshot_requests: mpsc::Sender<()>, metrics: &Metrics) -> Self {
+        self.held_update_check = Some(HeldUpdateCheck {
+            rules,
+            snapshot_requests,
+            gaps: metrics.counter("book_held_update_gaps"),
+        });
+        self
+    }
+
+    /// Additionally send the book along with the last applied update id to the output channel at most once per interval
+    ///
+    /// Checkpoints are skipped while the receiver is busy, so a slow disk never holds back the book processor
+    pub fn with_checkpoints(mut self, output: mpsc::Sender<(u64, Arc<OrderBook>)>, interval: u64) -> Self {
+        self.checkpoints = Some(Checkpoints { output, interval: Duration::from_millis(interval), last: None });
+        self
+    }
+
+    /// Send a checkpoint of the book after the given update, if checkpoints are enabled and the interval has passed
+    fn checkpoint(&mut self, update_id: u64) {
+        let (Some(checkpoints), Some(order_book)) = (self.checkpoints.as_mut(), self.order_book.as_ref()) else {
+            return;
+        };
+
+        if checkpoints.last.is_some_and(|last| last.elapsed() < checkpoints.interval) {
+            return;
+        }
+
+        // The book is copied only if the writer has room for the checkpoint
+        let is_closed = match checkpoints.output.try_reserve() {
+            Ok(permit) => {
+                permit.send((update_id, Arc::new(order_book.clone())));
+                checkpoints.last = Some(Instant::now());
+                false
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::debug!("Checkpoint writer is busy. Skipping checkpoint at update '{}'", update_id);
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => true,
+        };
+
+        if is_closed {
+            tracing::warn!("Checkpoint channel is closed. Disabling checkpoints");
+            self.checkpoints = None;
+        }
+    }
+
+    /// Verify the book against the checksum of the given update, if the check is enabled
+    ///
+    /// # Returns
+    /// `true` if the book has diverged from the venue book since the last snapshot and must not be sent
+    fn check_checksum(&mut self, update_id: u64, checksum: Option<BookChecksum>) -> bool {
+        let (Some(check), Some(order_book)) = (self.checksum_check.as_mut(), self.order_book.as_ref()) else {
+            return false;
+        };
+
+        let Some(checksum) = checksum.filter(|_| !check.is_diverged) else {
+            return check.is_diverged;
+        };
+
+        if !checksum.verify(order_book) {
+            tracing::warn!(
+                "Order book checksum mismatch after update '{}': expected '{}', computed '{}'. Requesting a fresh snapshot",
+                update_id,
+                checksum,
+                checksum.compute(order_book)
+            );
+            check.mismatches.increment(1);
+            check.is_diverged = true;
+
+            if let Err(mpsc::error::TrySendError::Closed(_)) = check.snapshot_requests.try_send(()) {
+                tracing::error!("Snapshot request channel is closed. Order book stays diverged");
+            }
+        }
+
+        check.is_diverged
+    }
+
+    /// Check whether the book is crossed after the given update, if the check is enabled
+    ///
+    /// # Returns
+    /// `true` if the book is crossed and must not be sent
+    fn check_crossed(&mut self, update_id: u64) -> bool {
+        let (Some(check), Some(order_book)) = (self.crossed_check.as_mut(), self.order_book.as_ref()) else {
+            return false;
+        };
+
+        let is_crossed = order_book.is_crossed();
+        if is_crossed && !check.is_crossed {
+            tracing::error!(
+                "Order book is crossed after update '{}': best bid '{:?}', best ask '{:?}'. Requesting a fresh snapshot",
+                update_id,
+                order_book.best_bid(),
+                order_book.best_ask()
+            );
+            check.crossed_books.increment(1);
+
+            // A full channel means a snapshot has already been requested
+            if let Err(mpsc::error::TrySendError::Closed(_)) = check.snapshot_requests.try_send(()) {
+                tracing::warn!("Snapshot requests are not accepted. Waiting for the next snapshot");
+            }
+        } else if !is_crossed && check.is_crossed {
+            tracing::info!("Order book is no longer crossed after update '{}'", update_id);
+        }
+
+        check.is_crossed = is_crossed;
+        is_crossed
+    }
+
+    /// Count the levels, which are off the tick grid, if the tick size is known
+    fn check_tick_size<'a>(&self, update_id: u64, levels: impl Iterator<Item = &'a DepthEntry>) {
+        let Some((tick_size, off_tick_levels)) = &self.tick_size else {
+            return;
+        };
+
+        let off_tick = levels.filter(|entry| !entry.price.is_multiple_of(*tick_size)).count() as u64;
+        if off_tick > 0 {
+            off_tick_levels.increment(off_tick);
+            tracing::warn!("'{}' levels of update '{}' are off the tick size '{}'", off_tick, update_id, tick_size);
         }
     }
 
     /// Send the current OrderBook state to the output channel
     ///
-    /// # Panics
+    /// # Errors
     /// * If sending to the output channel fails
     /// * If order_book is None
-    async fn send_current_state(&self) {
+    async fn send_current_state(&self) -> Result<()> {
         let order_book = self
             .order_book
             .as_ref()
-            .expect("Failed to send order book state: order book is not initialized");
-            
+            .ok_or_else(|| anyhow!("Failed to send order book state: order book is not initialized"))?;
+
         self.output
-            .send(order_book.clone())
+            .send(Arc::new(order_book.top(self.published_depth)))
             .await
-            .expect("Failed to send order book to output channel");
+            .context("Failed to send order book to output channel")
+    }
+
+    /// Send the levels changed since the last sent delta, or a full snapshot of the book if one is due
+    ///
+    /// # Arguments
+    /// * `update_id` - The last update id applied to the book
+    ///
+    /// # Errors
+    /// * If sending to the book delta output channel fails
+    async fn send_book_delta(&mut self, update_id: u64) -> Result<()> {
+        let (Some(deltas), Some(order_book)) = (self.book_deltas.as_mut(), self.order_book.as_ref()) else {
+            return Ok(());
+        };
+
+        let pending = std::mem::take(&mut deltas.pending);
+        let snapshot_due = match deltas.last_snapshot {
+            None => true,
+            Some(last) => !deltas.snapshot_interval.is_zero() && last.elapsed() >= deltas.snapshot_interval,
+        };
+
+        let event = if snapshot_due {
+            deltas.last_snapshot = Some(Instant::now());
+            BookDeltaEvent::Snapshot(BookSnapshot::new(update_id, order_book))
+        } else if pending.is_empty() {
+            return Ok(());
+        } else {
+            BookDeltaEvent::Delta(pending)
+        };
+
+        deltas.output.send(event).await.context("Failed to send book delta to output channel")
+    }
+
+    /// Discard the pending book delta and send a full snapshot next, e.g. after the book was replaced or not sent
+    fn reset_book_delta(&mut self) {
+        if let Some(deltas) = self.book_deltas.as_mut() {
+            deltas.pending = BookDelta::default();
+            deltas.last_snapshot = None;
+        }
+    }
+
+    /// Send a BboChange if the best bid or best ask differs from the last one sent
+    ///
+    /// # Arguments
+    /// * `update_id` - The last update id applied to the book
+    ///
+    /// # Errors
+    /// * If sending to the bbo output channel fails
+    /// * If order_book is None
+    async fn send_bbo_change(&mut self, update_id: u64) -> Result<()> {
+        let order_book = self
+            .order_book
+            .as_ref()
+            .ok_or_else(|| anyhow!("Failed to send bbo change: order book is not initialized"))?;
+
+        let best_bid = order_book.best_bid();
+        let best_ask = order_book.best_ask();
+
+        if let Some(last_bbo) = &self.last_bbo {
+            if last_bbo.best_bid == best_bid && last_bbo.best_ask == best_ask {
+                return Ok(());
+            }
+        }
+
+        let bbo = BboChange { update_id, best_bid, best_ask };
+        tracing::trace!("Top of book changed: '{}'", bbo);
+        self.last_bbo = Some(bbo.clone());
+
+        self.bbo_output
+            .send(MarketEvent::BboChange(bbo))
+            .await
+            .context("Failed to send bbo change to output channel")
     }
 
     /// Process a DepthUpdate
@@ -48,24 +463,79 @@ impl BookProcessor {
     ///
     /// # Behavior
     /// * Apply the update to the current OrderBook
+    /// * Send the resulting price level changes, if level changes are enabled
+    /// * Record the changed levels into the pending book delta, if book deltas are enabled
+    /// * Give the level buffers of the update back to the level pool
     ///
-    /// # Panics
+    /// # Errors
     /// * If order_book is None
-    async fn process_update(&mut self, update: DepthUpdate) {
+    /// * If sending to the level change output channel fails
+    async fn process_update(&mut self, mut update: DepthUpdate) -> Result<()> {
         tracing::debug!("Processing depth update: '{:?}'", update);
+        self.check_tick_size(update.last_update_id, update.bids.iter().chain(&update.asks));
+        if let Some(validation) = self.validation.as_mut() {
+            validation.validator.on_update(&update);
+        }
         
         let order_book = self
             .order_book
             .as_mut()
-            .expect("Cannot process depth update: order_book is not initialized");
+            .ok_or_else(|| anyhow!("Cannot process depth update: order_book is not initialized"))?;
         
-        for bid in update.bids {
-            order_book.apply_update(OrderBook::bid(bid.price), bid.quantity);
+        let record_changes = self.level_change_output.is_some();
+        let price_band = self.price_band;
+        let mut changes = Vec::new();
+        let mut delta = self.book_deltas.as_mut().map(|deltas| &mut deltas.pending);
+        if let Some(delta) = delta.as_mut() {
+            delta.update_id = update.last_update_id;
+            delta.event_time = update.event_time;
         }
+        let levels = update.bids.drain(..).map(|bid| (Side::Bid, bid))
+            .chain(update.asks.drain(..).map(|ask| (Side::Ask, ask)));
+
+        BOOK_ALLOCATIONS.measure(|| {
+            for (side, entry) in levels {
+                let price_key = match side {
+                    Side::Bid => OrderBook::bid(entry.price),
+                    Side::Ask => OrderBook::ask(entry.price),
+                };
+                if order_book.is_pruned(price_key) {
+                    continue;
+                }
+                let previous = order_book.apply_update(price_key, entry.quantity);
+                let kind = ChangeKind::classify(previous, entry.quantity);
+
+                if let (Some(delta), Some(_)) = (delta.as_mut(), kind) {
+                    delta.record(side, entry.price, entry.quantity);
+                }
 
-        for ask in update.asks {
-            order_book.apply_update(OrderBook::ask(ask.price), ask.quantity);
+                if let Some(kind) = kind.filter(|_| record_changes) {
+                    changes.push(LevelChange {
+                        update_id: update.last_update_id,
+                        event_time: update.event_time,
+                        side,
+                        kind,
+                        price: entry.price,
+                        quantity: entry.quantity,
+                        previous_quantity: previous.unwrap_or(FixedPoint::ZERO),
+                    });
+                }
+            }
+
+            if let Some(percent) = price_band {
+                order_book.prune_outside_band(percent);
+            }
+        });
+        LEVEL_POOL.give(update.bids);
+        LEVEL_POOL.give(update.asks);
+
+        if let Some(output) = &self.level_change_output {
+            if !changes.is_empty() {
+                output.send(changes).await.context("Failed to send level changes to output channel")?;
+            }
         }
+
+        Ok(())
     }
     
     /// Process a DepthSnapshot
@@ -75,40 +545,247 @@ impl BookProcessor {
     ///
     /// # Behavior
     /// * Replace the current OrderBook with a new one created from the snapshot
+    /// * Give the level buffers of the snapshot back to the level pool
     async fn process_snapshot(&mut self, snapshot: DepthSnapshot) {
         tracing::debug!("Processing depth snapshot: '{:?}'", snapshot);
-        self.order_book = Some(OrderBook::new(&snapshot));
+        self.check_tick_size(snapshot.last_update_id, snapshot.bids.iter().chain(&snapshot.asks));
+        if let Some(validation) = self.validation.as_mut() {
+            validation.validator.on_snapshot(&snapshot);
+        }
+        self.order_book = Some(BOOK_ALLOCATIONS.measure(|| self.limit_depth(OrderBook::new(&snapshot))));
+        LEVEL_POOL.give(snapshot.bids);
+        LEVEL_POOL.give(snapshot.asks);
+        self.reset_book_delta();
+        if let Some(check) = self.checksum_check.as_mut() {
+            check.is_diverged = false;
+        }
+    }
+
+    /// Hold a depth update, which arrived before the first snapshot
+    ///
+    /// Once the limit of held updates is reached, the oldest one is discarded
+    fn hold_update(&mut self, update: DepthUpdate) {
+        if self.pending_updates.is_empty() {
+            tracing::warn!("Depth update '{}' arrived before the first snapshot. Holding updates until it arrives", update.last_update_id);
+        }
+
+        if self.pending_updates.len() == MAX_PENDING_UPDATES {
+            if let Some(discarded) = self.pending_updates.pop_front() {
+                tracing::warn!("Discarding depth update '{}' held for more than '{}' updates", discarded.last_update_id, MAX_PENDING_UPDATES);
+            }
+        }
+        self.pending_updates.push_back(update);
+    }
+
+    /// The first held update, which doesn't continue the snapshot or the held update before it, if the check is enabled
+    ///
+    /// # Arguments
+    /// * `updates` - The held updates, which are newer than the snapshot, in the order of their arrival
+    /// * `snapshot_update_id` - The last update id of the snapshot
+    ///
+    /// # Returns
+    /// The update id, which the update should have continued, and the update
+    fn find_held_gap<'a>(&self, updates: &'a [DepthUpdate], snapshot_update_id: u64) -> Option<(u64, &'a DepthUpdate)> {
+        let check = self.held_update_check.as_ref()?;
+        let mut last_update_id = snapshot_update_id;
+        updates.iter().enumerate().find_map(|(index, update)| {
+            let expected = last_update_id;
+            last_update_id = update.last_update_id;
+            (!check.rules.is_continuation(update, expected, index == 0)).then_some((expected, update))
+        })
+    }
+
+    /// Take the held updates, which are newer than the snapshot, and discard the rest
+    ///
+    /// With the held update check, a gap in the held updates drops them along with the snapshot, before the snapshot
+    /// is published, and the book waits for a fresh snapshot
+    ///
+    /// # Returns
+    /// The updates, which continue the snapshot, or None, if the snapshot is to be dropped
+    fn take_held_updates(&mut self, snapshot_update_id: u64) -> Option<Vec<DepthUpdate>> {
+        let pending = std::mem::take(&mut self.pending_updates);
+        let held = pending.len();
+        let newer: Vec<DepthUpdate> = pending.into_iter().filter(|update| update.last_update_id > snapshot_update_id).collect();
+
+        let Some((expected, update)) = self.find_held_gap(&newer, snapshot_update_id) else {
+            if held > 0 {
+                tracing::info!("Applying '{}' of '{}' depth updates held until snapshot '{}'. The rest are covered by the snapshot", newer.len(), held, snapshot_update_id);
+            }
+            return Some(newer);
+        };
+
+        tracing::warn!(
+            "Held depth update '{}-{}' doesn't continue '{}' after snapshot '{}'. Dropping the snapshot and '{}' held updates and requesting a fresh snapshot",
+            update.first_update_id,
+            update.last_update_id,
+            expected,
+            snapshot_update_id,
+            held
+        );
+        newer.into_iter().for_each(|update| LEVEL_POOL.recycle(update));
+        // The snapshot misses the updates in the gap, so the following ones are held until the fresh snapshot
+        if let Some(check) = self.held_update_check.as_ref() {
+            check.gaps.increment(1);
+            if let Err(mpsc::error::TrySendError::Closed(_)) = check.snapshot_requests.try_send(()) {
+                tracing::warn!("Snapshot requests are not accepted. Waiting for the next snapshot");
+            }
+        }
+        None
+    }
+
+    /// Apply a depth update to the book and send the result to the output channels
+    async fn on_update(&mut self, update: DepthUpdate) -> Result<()> {
+        let update_id = update.last_update_id;
+        let checksum = update.checksum;
+        self.process_update(update).await?;
+        if self.check_checksum(update_id, checksum) || self.check_crossed(update_id) {
+            self.reset_book_delta();
+            return Ok(());
+        }
+        self.send_current_state().await?;
+        self.send_book_delta(update_id).await?;
+        self.send_bbo_change(update_id).await?;
+        self.checkpoint(update_id);
+        self.validate().await
+    }
+
+    /// Compare the book with the pending reference snapshot, if any
+    ///
+    /// # Behavior
+    /// * Log the levels, which differ from the reference
+    /// * Replace the book with the reference and send it, if resync is enabled
+    async fn validate(&mut self) -> Result<()> {
+        let (Some(validation), Some(order_book)) = (self.validation.as_mut(), self.order_book.as_ref()) else {
+            return Ok(());
+        };
+
+        let Some(report) = validation.validator.check(order_book) else {
+            return Ok(());
+        };
+
+        if report.mismatches.is_empty() {
+            tracing::debug!("Order book matches the reference at update '{}'", report.update_id);
+            return Ok(());
+        }
+
+        tracing::warn!(
+            "Order book drifted from the reference at update '{}': '{}' levels differ, e.g. '{:?}'",
+            report.update_id,
+            report.mismatches.len(),
+            report.mismatches[0]
+        );
+
+        if validation.validator.resync() {
+            tracing::warn!("Resyncing order book from the reference at update '{}'", report.update_id);
+            validation.resyncs.increment(1);
+            self.order_book = Some(self.limit_depth(report.reference));
+            self.reset_book_delta();
+            self.send_current_state().await?;
+            self.send_book_delta(report.update_id).await?;
+            self.send_bbo_change(report.update_id).await?;
+
+            if let Some(markers) = &self.markers {
+                let resync = Resync {
+                    time: Utc::now().timestamp_millis(),
+                    source: ResyncSource::Validation,
+                    previous_update_id: report.update_id,
+                    update_id: report.update_id,
+                };
+                markers.send(MarketEvent::Resync(resync)).await.context("Failed to send resync marker")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receive the next reference snapshot. Never completes if validation is disabled or its channel is closed
+    async fn next_reference(validation: &mut Option<Validation>) -> Option<MarketEvent> {
+        match validation.as_mut().and_then(|validation| validation.input.as_mut()) {
+            Some(input) => input.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Process a reference snapshot received from the validation channel
+    async fn process_reference(&mut self, event: Option<MarketEvent>) -> Result<()> {
+        let Some(validation) = self.validation.as_mut() else {
+            return Ok(());
+        };
+
+        match event {
+            Some(MarketEvent::DepthSnapshot(snapshot)) => {
+                tracing::debug!("Received reference snapshot '{}'", snapshot.last_update_id);
+                validation.validator.set_reference(snapshot);
+                self.validate().await?;
+            }
+            Some(event) => tracing::error!("BookProcessor received unexpected reference event type: '{}'. Discarding", event),
+            None => validation.input = None,
+        }
+
+        Ok(())
     }
 
     /// Run the BookProcessor as an asynchronous task
     ///
     /// This method will continuously process messages from the input channel until it is closed
-    /// DepthUpdate and DepthSnapshot messages are processed, all other message types will cause a panic
-    pub async fn run(mut self) {
+    /// DepthUpdate and DepthSnapshot messages are processed, all other message types are discarded.
+    /// The processor keeps its state if it fails, so it can be run again
+    pub async fn run(&mut self) -> Result<()> {
         tracing::info!("Starting BookProcessor");
         
-        while let Some(event) = self.input.recv().await {
-            match event {
-                MarketEvent::DepthUpdate(update) => {
-                    self.process_update(update).await;
-                    self.send_current_state().await;
+        loop {
+            let event = tokio::select! {
+                event = self.input.recv() => event,
+                reference = Self::next_reference(&mut self.validation) => {
+                    self.process_reference(reference).await?;
+                    continue;
                 }
+            };
+
+            let Some(event) = event else {
+                break;
+            };
+
+            match event {
+                MarketEvent::DepthUpdate(update) if self.order_book.is_none() => self.hold_update(update),
+                MarketEvent::DepthUpdate(update) => self.on_update(update).await?,
                 MarketEvent::DepthSnapshot(snapshot) => {
+                    let update_id = snapshot.last_update_id;
+                    let Some(held_updates) = self.take_held_updates(update_id) else {
+                        continue;
+                    };
                     self.process_snapshot(snapshot).await;
-                    self.send_current_state().await;
+                    if !self.check_crossed(update_id) {
+                        self.send_current_state().await?;
+                        self.send_book_delta(update_id).await?;
+                        self.send_bbo_change(update_id).await?;
+                        self.checkpoint(update_id);
+                    }
+                    for update in held_updates {
+                        self.on_update(update).await?;
+                    }
                 }
                 _ => {
                     tracing::error!("BookProcessor received unexpected event type: '{}'. Discarding", event);
                 }
             }
         }
+
+        Ok(())
+    }
+}
+
+impl RestartableTask for BookProcessor {
+    fn process(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(self.run())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mdc_server::models::{DepthEntry};
+    use crate::mdc_core::checksum::okx_checksum;
+    use crate::mdc_core::models::{DepthEntry};
     use tokio::sync::mpsc;
 
     // Helper function to create a test snapshot
@@ -116,12 +793,12 @@ mod tests {
         DepthSnapshot {
             last_update_id: 123456,
             bids: vec![
-                DepthEntry { price: 100.0, quantity: 10.0 },
-                DepthEntry { price: 99.5, quantity: 15.0 },
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) },
+                DepthEntry { price: FixedPoint::from(99.5), quantity: FixedPoint::from(15.0) },
             ],
             asks: vec![
-                DepthEntry { price: 100.5, quantity: 5.0 },
-                DepthEntry { price: 101.0, quantity: 8.0 },
+                DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::from(5.0) },
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(8.0) },
             ],
         }
     }
@@ -129,29 +806,30 @@ mod tests {
     #[tokio::test]
     async fn test_book_processor_initialization() {
         let (_input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
-        let (output_tx, mut output_rx) = mpsc::channel::<OrderBook>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
         
         let snapshot = create_test_snapshot();
         
-        let mut processor = BookProcessor::new(input_rx, output_tx);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx);
         
         processor.process_snapshot(snapshot.clone()).await;
-        processor.send_current_state().await;
+        processor.send_current_state().await.unwrap();
         
         let received_book = output_rx.recv().await.unwrap();
         
         assert_eq!(received_book.bids.len(), 2);
         assert_eq!(received_book.asks.len(), 2);
-        assert_eq!(received_book.bids.get(&OrderBook::bid(100.0)).unwrap(), &10.0);
-        assert_eq!(received_book.bids.get(&OrderBook::bid(99.5)).unwrap(), &15.0);
-        assert_eq!(received_book.asks.get(&OrderBook::ask(100.5)).unwrap(), &5.0);
-        assert_eq!(received_book.asks.get(&OrderBook::ask(101.0)).unwrap(), &8.0);
+        assert_eq!(received_book.bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(10.0));
+        assert_eq!(received_book.bids.get(FixedPoint::from(99.5)).unwrap(), FixedPoint::from(15.0));
+        assert_eq!(received_book.asks.get(FixedPoint::from(100.5)).unwrap(), FixedPoint::from(5.0));
+        assert_eq!(received_book.asks.get(FixedPoint::from(101.0)).unwrap(), FixedPoint::from(8.0));
     }
 
     #[tokio::test]
     async fn test_book_processor_update() {
         let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
-        let (output_tx, mut output_rx) = mpsc::channel::<OrderBook>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
         
         let snapshot = create_test_snapshot();
         
@@ -161,18 +839,22 @@ mod tests {
             symbol: "BTCUSDT".to_string(),
             first_update_id: 123457,
             last_update_id: 123458,
+            previous_last_update_id: None,
             bids: vec![
-                DepthEntry { price: 100.0, quantity: 12.0 },
-                DepthEntry { price: 99.0, quantity: 5.0 },
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(12.0) },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(5.0) },
             ],
             asks: vec![
-                DepthEntry { price: 100.5, quantity: 0.0 },
-                DepthEntry { price: 101.5, quantity: 3.0 },
+                DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::ZERO },
+                DepthEntry { price: FixedPoint::from(101.5), quantity: FixedPoint::from(3.0) },
             ],
+            checksum: None,
+            received: None,
         };
         
-        let processor = BookProcessor::new(input_rx, output_tx);
-        tokio::spawn(processor.run());
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx);
+        tokio::spawn(async move { processor.run().await });
         
         input_tx.send(MarketEvent::DepthSnapshot(snapshot)).await.unwrap();
         input_tx.send(MarketEvent::DepthUpdate(update)).await.unwrap();
@@ -183,24 +865,94 @@ mod tests {
         
         assert_eq!(update_book.bids.len(), 3);
         assert_eq!(update_book.asks.len(), 2);
-        assert_eq!(update_book.bids.get(&OrderBook::bid(100.0)).unwrap(), &12.0);
-        assert_eq!(update_book.bids.get(&OrderBook::bid(99.0)).unwrap(), &5.0);
-        assert_eq!(update_book.asks.get(&OrderBook::ask(100.5)), None);
-        assert_eq!(update_book.asks.get(&OrderBook::ask(101.5)).unwrap(), &3.0);
+        assert_eq!(update_book.bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(12.0));
+        assert_eq!(update_book.bids.get(FixedPoint::from(99.0)).unwrap(), FixedPoint::from(5.0));
+        assert_eq!(update_book.asks.get(FixedPoint::from(100.5)), None);
+        assert_eq!(update_book.asks.get(FixedPoint::from(101.5)).unwrap(), FixedPoint::from(3.0));
+    }
+
+    #[tokio::test]
+    async fn test_published_depth() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_published_depth(1);
+        tokio::spawn(async move { processor.run().await });
+
+        let update = DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 123457,
+            last_update_id: 123458,
+            previous_last_update_id: None,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::ZERO }],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        };
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(update)).await.unwrap();
+        drop(input_tx);
+
+        // The held snapshot is not affected by the update, and a level deeper than the published depth
+        // becomes the best one, once the best level is removed
+        let snapshot_book = output_rx.recv().await.unwrap();
+        let update_book = output_rx.recv().await.unwrap();
+        assert_eq!((snapshot_book.bids.len(), snapshot_book.asks.len()), (1, 1));
+        assert_eq!(snapshot_book.best_bid().unwrap().price, FixedPoint::from(100.0));
+        assert_eq!(update_book.best_bid().unwrap().price, FixedPoint::from(99.5));
+        assert_eq!(update_book.best_ask().unwrap().price, FixedPoint::from(100.5));
+    }
+
+    #[tokio::test]
+    async fn test_max_depth_drops_level_falling_out_of_seeded_levels() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_max_depth(2);
+        tokio::spawn(async move { processor.run().await });
+
+        let update = |update_id: u64, price: f64, quantity: f64| MarketEvent::DepthUpdate(DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: update_id,
+            last_update_id: update_id,
+            previous_last_update_id: None,
+            bids: vec![DepthEntry { price: FixedPoint::from(price), quantity: FixedPoint::from(quantity) }],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        });
+
+        // A better bid pushes 99.5 out of the two seeded levels, so its later update is ignored instead of
+        // bringing back a level, whose other changes were missed
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        input_tx.send(update(123457, 100.2, 1.0)).await.unwrap();
+        input_tx.send(update(123458, 99.5, 20.0)).await.unwrap();
+        drop(input_tx);
+
+        output_rx.recv().await.unwrap();
+        let pruned_book = output_rx.recv().await.unwrap();
+        let updated_book = output_rx.recv().await.unwrap();
+        let bid_prices = |book: &OrderBook| book.bids.iter().map(|entry| entry.price).collect::<Vec<_>>();
+        assert_eq!(bid_prices(&pruned_book), vec![FixedPoint::from(100.2), FixedPoint::from(100.0)]);
+        assert_eq!(bid_prices(&updated_book), vec![FixedPoint::from(100.2), FixedPoint::from(100.0)]);
     }
 
     #[tokio::test]
     async fn test_book_processor_multiple_updates() {
         let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
-        let (output_tx, mut output_rx) = mpsc::channel::<OrderBook>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
         
         let snapshot = DepthSnapshot {
             last_update_id: 123456,
             bids: vec![
-                DepthEntry { price: 100.0, quantity: 10.0 },
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) },
             ],
             asks: vec![
-                DepthEntry { price: 101.0, quantity: 5.0 },
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(5.0) },
             ],
         };
         
@@ -210,10 +962,13 @@ mod tests {
             symbol: "BTCUSDT".to_string(),
             first_update_id: 123457,
             last_update_id: 123458,
+            previous_last_update_id: None,
             bids: vec![
-                DepthEntry { price: 100.0, quantity: 12.0 },
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(12.0) },
             ],
             asks: vec![],
+            checksum: None,
+            received: None,
         };
 
         let update2 = DepthUpdate {
@@ -222,14 +977,18 @@ mod tests {
             symbol: "BTCUSDT".to_string(),
             first_update_id: 123459,
             last_update_id: 123460,
+            previous_last_update_id: None,
             bids: vec![],
             asks: vec![
-                DepthEntry { price: 101.0, quantity: 8.0 },
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(8.0) },
             ],
+            checksum: None,
+            received: None,
         };
         
-        let processor = BookProcessor::new(input_rx, output_tx);
-        tokio::spawn(processor.run());
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx);
+        tokio::spawn(async move { processor.run().await });
         
         input_tx.send(MarketEvent::DepthSnapshot(snapshot)).await.unwrap();
         input_tx.send(MarketEvent::DepthUpdate(update1)).await.unwrap();
@@ -243,34 +1002,35 @@ mod tests {
         
         assert_eq!(book1.bids.len(), 1);
         assert_eq!(book1.asks.len(), 1);
-        assert_eq!(book1.bids.get(&OrderBook::bid(100.0)).unwrap(), &12.0);
-        assert_eq!(book1.asks.get(&OrderBook::ask(101.0)).unwrap(), &5.0);
+        assert_eq!(book1.bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(12.0));
+        assert_eq!(book1.asks.get(FixedPoint::from(101.0)).unwrap(), FixedPoint::from(5.0));
         
         assert_eq!(book2.bids.len(), 1);
         assert_eq!(book2.asks.len(), 1);
-        assert_eq!(book2.bids.get(&OrderBook::bid(100.0)).unwrap(), &12.0);
-        assert_eq!(book2.asks.get(&OrderBook::ask(101.0)).unwrap(), &8.0);
+        assert_eq!(book2.bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(12.0));
+        assert_eq!(book2.asks.get(FixedPoint::from(101.0)).unwrap(), FixedPoint::from(8.0));
     }
 
     #[tokio::test]
     async fn test_book_processor_accepts_snapshot_after_init() {
         let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
-        let (output_tx, mut output_rx) = mpsc::channel::<OrderBook>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
         
         let initial_snapshot = create_test_snapshot();
         
         let second_snapshot = DepthSnapshot {
             last_update_id: 123460,
             bids: vec![
-                DepthEntry { price: 99.0, quantity: 15.0 },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(15.0) },
             ],
             asks: vec![
-                DepthEntry { price: 102.0, quantity: 8.0 },
+                DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(8.0) },
             ],
         };
         
-        let processor = BookProcessor::new(input_rx, output_tx);
-        tokio::spawn(processor.run());
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx);
+        tokio::spawn(async move { processor.run().await });
         
         input_tx.send(MarketEvent::DepthSnapshot(initial_snapshot)).await.unwrap();
         input_tx.send(MarketEvent::DepthSnapshot(second_snapshot.clone())).await.unwrap();
@@ -281,32 +1041,441 @@ mod tests {
         
         assert_eq!(received_book.bids.len(), 1);
         assert_eq!(received_book.asks.len(), 1);
-        assert_eq!(received_book.bids.get(&OrderBook::bid(99.0)).unwrap(), &15.0);
-        assert_eq!(received_book.asks.get(&OrderBook::ask(102.0)).unwrap(), &8.0);
+        assert_eq!(received_book.bids.get(FixedPoint::from(99.0)).unwrap(), FixedPoint::from(15.0));
+        assert_eq!(received_book.asks.get(FixedPoint::from(102.0)).unwrap(), FixedPoint::from(8.0));
     }
     
     #[tokio::test]
-    #[should_panic(expected = "Cannot process depth update: order_book is not initialized")]
-    async fn test_book_processor_rejects_update_before_snapshot() {
+    async fn test_book_processor_holds_updates_until_snapshot() {
         let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
-        let (output_tx, _output_rx) = mpsc::channel::<OrderBook>(100);
-        
-        let update = DepthUpdate {
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+
+        let make_update = |first_update_id, last_update_id, quantity| DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id,
+            last_update_id,
+            previous_last_update_id: None,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(quantity) },
+            ],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        };
+
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx);
+        let handle = tokio::spawn(async move { processor.run().await });
+
+        // The first update is covered by the snapshot, the second one continues it
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123455, 123456, 11.0))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123457, 123458, 12.0))).await.unwrap();
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        drop(input_tx);
+
+        let snapshot_book = output_rx.recv().await.unwrap();
+        assert_eq!(snapshot_book.bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(10.0));
+
+        let update_book = output_rx.recv().await.unwrap();
+        assert_eq!(update_book.bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(12.0));
+
+        assert!(output_rx.recv().await.is_none());
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_drops_held_updates_with_gap() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (snapshot_request_tx, mut snapshot_request_rx) = mpsc::channel::<()>(1);
+
+        let make_update = |first_update_id, last_update_id, quantity| DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id,
+            last_update_id,
+            previous_last_update_id: None,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(quantity) },
+            ],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        };
+
+        let metrics = Metrics::new();
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx)
+            .with_held_update_check(SequencingRules::BinanceSpot, snapshot_request_tx, &metrics);
+        let handle = tokio::spawn(async move { processor.run().await });
+
+        // Update 123457 has been missed, so the held update doesn't bridge the snapshot
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123458, 123459, 11.0))).await.unwrap();
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        snapshot_request_rx.recv().await.unwrap();
+        assert!(output_rx.try_recv().is_err());
+
+        // The snapshot isn't published, and the following updates are held until the fresh snapshot, which they continue
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123460, 123460, 12.0))).await.unwrap();
+        let fresh_snapshot = DepthSnapshot { last_update_id: 123459, ..create_test_snapshot() };
+        input_tx.send(MarketEvent::DepthSnapshot(fresh_snapshot)).await.unwrap();
+        drop(input_tx);
+
+        assert_eq!(output_rx.recv().await.unwrap().bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(10.0));
+        assert_eq!(output_rx.recv().await.unwrap().bids.get(FixedPoint::from(100.0)).unwrap(), FixedPoint::from(12.0));
+        assert!(output_rx.recv().await.is_none());
+        assert!(handle.await.unwrap().is_ok());
+        assert_eq!(metrics.counter("book_held_update_gaps").get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_bbo_change_only_on_top_of_book_change() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, _output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, mut bbo_rx) = mpsc::channel::<MarketEvent>(100);
+
+        let deep_update = DepthUpdate {
             event_type: "depthUpdate".to_string(),
             event_time: 1672515782136,
             symbol: "BTCUSDT".to_string(),
             first_update_id: 123457,
             last_update_id: 123458,
+            previous_last_update_id: None,
             bids: vec![
-                DepthEntry { price: 100.0, quantity: 12.0 },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(5.0) },
             ],
             asks: vec![],
+            checksum: None,
+            received: None,
         };
-        
-        let processor = BookProcessor::new(input_rx, output_tx);
-        let handle = tokio::spawn(processor.run());
-        
+
+        let top_update = DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782137,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 123459,
+            last_update_id: 123460,
+            previous_last_update_id: None,
+            bids: vec![],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::from(7.0) },
+            ],
+            checksum: None,
+            received: None,
+        };
+
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx);
+        tokio::spawn(async move { processor.run().await });
+
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(deep_update)).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(top_update)).await.unwrap();
+        drop(input_tx);
+
+        let MarketEvent::BboChange(initial) = bbo_rx.recv().await.unwrap() else {
+            panic!("Expected BboChange");
+        };
+        assert_eq!(initial.update_id, 123456);
+        assert_eq!(initial.best_bid, Some(DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) }));
+        assert_eq!(initial.best_ask, Some(DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::from(5.0) }));
+
+        let MarketEvent::BboChange(changed) = bbo_rx.recv().await.unwrap() else {
+            panic!("Expected BboChange");
+        };
+        assert_eq!(changed.update_id, 123460);
+        assert_eq!(changed.best_bid, Some(DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(10.0) }));
+        assert_eq!(changed.best_ask, Some(DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::from(7.0) }));
+
+        assert!(bbo_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_level_changes() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, _output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let (changes_tx, mut changes_rx) = mpsc::channel::<Vec<LevelChange>>(100);
+
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_level_changes(changes_tx);
+        tokio::spawn(async move { processor.run().await });
+
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 123457,
+            last_update_id: 123458,
+            previous_last_update_id: None,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(12.0) },
+                DepthEntry { price: FixedPoint::from(98.0), quantity: FixedPoint::ZERO },
+            ],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::ZERO },
+                DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(1.0) },
+            ],
+            checksum: None,
+            received: None,
+        })).await.unwrap();
+
+        let changes = changes_rx.recv().await.unwrap();
+        let summary: Vec<_> = changes.iter().map(|change| (change.side, change.kind, change.price.to_f64(), change.previous_quantity.to_f64())).collect();
+        assert_eq!(summary, vec![
+            (Side::Bid, ChangeKind::Modification, 100.0, 10.0),
+            (Side::Ask, ChangeKind::Deletion, 100.5, 5.0),
+            (Side::Ask, ChangeKind::Addition, 102.0, 0.0),
+        ]);
+        assert!(changes.iter().all(|change| change.update_id == 123458 && change.event_time == 1000));
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_book_deltas() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let (deltas_tx, mut deltas_rx) = mpsc::channel::<BookDeltaEvent>(100);
+
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_book_deltas(deltas_tx, 0);
+        tokio::spawn(async move { processor.run().await });
+
+        let update = |last_update_id, bids, asks| MarketEvent::DepthUpdate(DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: last_update_id,
+            last_update_id,
+            previous_last_update_id: None,
+            bids,
+            asks,
+            checksum: None,
+            received: None,
+        });
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        input_tx.send(update(
+            123457,
+            vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(12.0) }],
+            vec![
+                DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::ZERO },
+                DepthEntry { price: FixedPoint::from(102.0), quantity: FixedPoint::from(1.0) },
+            ],
+        )).await.unwrap();
+        // Removal of a missing level changes nothing, so no delta is sent
+        input_tx.send(update(123458, vec![DepthEntry { price: FixedPoint::from(90.0), quantity: FixedPoint::ZERO }], vec![])).await.unwrap();
+        input_tx.send(update(123459, vec![DepthEntry { price: FixedPoint::from(99.5), quantity: FixedPoint::ZERO }], vec![])).await.unwrap();
+
+        let BookDeltaEvent::Snapshot(snapshot) = deltas_rx.recv().await.unwrap() else {
+            panic!("The first book delta event must be a snapshot");
+        };
+        assert_eq!(snapshot.update_id, 123456);
+        let mut rebuilt = OrderBook::new(&DepthSnapshot {
+            last_update_id: snapshot.update_id,
+            bids: snapshot.bids.iter().map(|(price, quantity)| DepthEntry { price: *price, quantity: *quantity }).collect(),
+            asks: snapshot.asks.iter().map(|(price, quantity)| DepthEntry { price: *price, quantity: *quantity }).collect(),
+        });
+
+        for expected_update_id in [123457, 123459] {
+            let Some(BookDeltaEvent::Delta(delta)) = deltas_rx.recv().await else {
+                panic!("Updates after the snapshot must be sent as deltas");
+            };
+            assert_eq!(delta.update_id, expected_update_id);
+            delta.apply(&mut rebuilt);
+        }
+
+        let mut book = output_rx.recv().await.unwrap();
+        for _ in 0..3 {
+            book = output_rx.recv().await.unwrap();
+        }
+        assert_eq!(rebuilt.top_n(10), book.top_n(10));
+        assert_eq!(rebuilt.bids.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_counts_off_tick_levels() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_tick_size("0.5".parse().unwrap(), &metrics);
+        tokio::spawn(async move { processor.run().await });
+
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 123457,
+            last_update_id: 123458,
+            previous_last_update_id: None,
+            bids: vec![DepthEntry { price: "99.25".parse().unwrap(), quantity: FixedPoint::from(1.0) }],
+            asks: vec![DepthEntry { price: "101.5".parse().unwrap(), quantity: FixedPoint::from(1.0) }],
+            checksum: None,
+            received: None,
+        })).await.unwrap();
+
+        output_rx.recv().await.unwrap();
+        let book = output_rx.recv().await.unwrap();
+
+        // The level off the tick grid is still applied
+        assert_eq!(book.bids.get("99.25".parse().unwrap()), Some(FixedPoint::from(1.0)));
+        assert_eq!(metrics.snapshot()["book_off_tick_levels"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_resyncs_drifted_book() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (reference_tx, reference_rx) = mpsc::channel::<MarketEvent>(10);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, mut bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+
+        let validator = BookValidator::new(true, &metrics);
+        let (marker_tx, mut marker_rx) = mpsc::channel::<MarketEvent>(10);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx)
+            .with_validation(validator, reference_rx, &metrics)
+            .with_markers(marker_tx);
+        tokio::spawn(async move { processor.run().await });
+
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        output_rx.recv().await.unwrap();
+        bbo_rx.recv().await.unwrap();
+
+        let mut reference = create_test_snapshot();
+        reference.bids[0].quantity = FixedPoint::from(7.0);
+        reference_tx.send(MarketEvent::DepthSnapshot(reference)).await.unwrap();
+
+        let book = output_rx.recv().await.unwrap();
+        assert_eq!(book.best_bid(), Some(DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(7.0) }));
+        assert!(matches!(bbo_rx.recv().await.unwrap(), MarketEvent::BboChange(_)));
+        assert_eq!(metrics.snapshot()["book_drift_detected"], 1);
+        assert_eq!(metrics.snapshot()["book_drift_levels"], 1);
+        assert_eq!(metrics.snapshot()["book_resyncs"], 1);
+
+        let Some(MarketEvent::Resync(resync)) = marker_rx.recv().await else {
+            panic!("Resync marker is expected");
+        };
+        assert_eq!(resync.source, ResyncSource::Validation);
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_withholds_crossed_book() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let (request_tx, mut request_rx) = mpsc::channel::<()>(1);
+        let metrics = Metrics::new();
+
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_crossed_book_check(request_tx, &metrics);
+        tokio::spawn(async move { processor.run().await });
+
+        let make_update = |last_update_id: u64, bid: f64| DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: last_update_id,
+            last_update_id,
+            previous_last_update_id: None,
+            bids: vec![DepthEntry { price: FixedPoint::from(bid), quantity: FixedPoint::from(1.0) }],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        };
+
+        input_tx.send(MarketEvent::DepthSnapshot(create_test_snapshot())).await.unwrap();
+        output_rx.recv().await.unwrap();
+
+        // Both updates leave the book crossed, but a single snapshot is requested
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123457, 100.5))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123458, 100.7))).await.unwrap();
+        request_rx.recv().await.unwrap();
+
+        input_tx.send(MarketEvent::DepthSnapshot(DepthSnapshot { last_update_id: 123460, ..create_test_snapshot() })).await.unwrap();
+        let book = output_rx.recv().await.unwrap();
+        assert!(!book.is_crossed());
+        assert_eq!(book.bids.len(), 2);
+        assert!(request_rx.try_recv().is_err());
+        assert_eq!(metrics.snapshot()["book_crossed"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_book_processor_withholds_book_on_checksum_mismatch() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let (request_tx, mut request_rx) = mpsc::channel::<()>(1);
+        let metrics = Metrics::new();
+
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_checksum_check(request_tx, &metrics);
+        tokio::spawn(async move { processor.run().await });
+
+        let make_update = |last_update_id: u64, quantity: f64, checksum: i32| DepthUpdate {
+            event_type: "update".to_string(),
+            event_time: 1000,
+            symbol: "BTC-USDT".to_string(),
+            first_update_id: last_update_id,
+            last_update_id,
+            previous_last_update_id: Some(last_update_id - 1),
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(quantity) }],
+            asks: vec![],
+            checksum: Some(BookChecksum::Okx(checksum)),
+            received: None,
+        };
+
+        let snapshot = create_test_snapshot();
+        let mut expected = OrderBook::new(&snapshot);
+        input_tx.send(MarketEvent::DepthSnapshot(snapshot.clone())).await.unwrap();
+        output_rx.recv().await.unwrap();
+
+        expected.apply_update(OrderBook::bid(FixedPoint::from(100.0)), FixedPoint::from(7.0));
+        let checksum = okx_checksum(&expected);
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123457, 7.0, checksum))).await.unwrap();
+        assert_eq!(output_rx.recv().await.unwrap().best_bid().unwrap().quantity, FixedPoint::from(7.0));
+
+        // The book stays withheld after a mismatch, even if a later checksum matches by chance
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123458, 6.0, checksum))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(123459, 7.0, checksum))).await.unwrap();
+        request_rx.recv().await.unwrap();
+
+        input_tx.send(MarketEvent::DepthSnapshot(DepthSnapshot { last_update_id: 123460, ..snapshot })).await.unwrap();
+        let book = output_rx.recv().await.unwrap();
+        assert_eq!(book.best_bid().unwrap().quantity, FixedPoint::from(10.0));
+        assert!(request_rx.try_recv().is_err());
+        assert_eq!(metrics.snapshot()["book_checksum_mismatches"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_are_sent_once_per_interval() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, _output_rx) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_tx, _bbo_rx) = mpsc::channel::<MarketEvent>(100);
+        let (checkpoint_tx, mut checkpoint_rx) = mpsc::channel(1);
+        let mut processor = BookProcessor::new(input_rx, output_tx, bbo_tx).with_checkpoints(checkpoint_tx, 60_000);
+
+        let snapshot = create_test_snapshot();
+        let update = DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 123457,
+            last_update_id: 123458,
+            previous_last_update_id: None,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(12.0) }],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        };
+        input_tx.send(MarketEvent::DepthSnapshot(snapshot)).await.unwrap();
         input_tx.send(MarketEvent::DepthUpdate(update)).await.unwrap();
-        handle.await.unwrap();
+        drop(input_tx);
+        processor.run().await.unwrap();
+
+        let (update_id, book) = checkpoint_rx.recv().await.unwrap();
+        assert_eq!(update_id, 123456);
+        assert_eq!(book.best_bid().unwrap().quantity, FixedPoint::from(10.0));
+        assert!(checkpoint_rx.try_recv().is_err());
     }
 }
@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::Result;
+use tokio::sync::mpsc;
+use crate::mdc_core::depth_sequencer::BufferLimits;
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_core::sequencing::SequencingRules;
+use crate::mdc_server::book_processor::BookProcessor;
+use crate::mdc_server::depth_event_dispatcher::DepthEventDispatcher;
+use crate::mdc_server::metrics::Metrics;
+use crate::mdc_server::tape_replayer::{FrameDecoders, TapeReplayer};
+
+/// The book maintained by the pipeline at a point in time of a tape
+#[derive(Debug, Clone)]
+pub struct ReconstructedBook {
+    /// Number of book updates up to the point in time
+    pub updates: u64,
+    pub book: Arc<OrderBook>,
+}
+
+/// Replay the frames of a tape received up to `end_time` through the DepthEventDispatcher and the BookProcessor
+/// and return the resulting book
+///
+/// # Arguments
+/// * `path` - Path of the tape
+/// * `end_time` - Receive time in nanoseconds since epoch
+/// * `depth` - Number of best levels per side of the returned book
+/// * `sequencing_rules` - Sequencing rules of the venue, which recorded the tape
+/// * `limits` - Buffer limits of the dispatcher
+/// * `decoders` - Decoders of the depth and the trade frames of the venue
+///
+/// # Returns
+/// `None` if no book is complete at `end_time`, e.g. because the first snapshot is received later
+pub async fn reconstruct_book(
+    path: PathBuf,
+    end_time: u64,
+    depth: usize,
+    sequencing_rules: SequencingRules,
+    limits: BufferLimits,
+    decoders: FrameDecoders,
+) -> Result<Option<ReconstructedBook>> {
+    let (depth_sender, depth_receiver) = mpsc::channel::<MarketEvent>(100);
+    let (dispatch_sender, dispatch_receiver) = mpsc::channel::<MarketEvent>(100);
+    let (book_sender, mut book_receiver) = mpsc::channel::<Arc<OrderBook>>(100);
+    let (bbo_sender, bbo_receiver) = mpsc::channel::<MarketEvent>(100);
+    // Trades, prices and the other streams don't affect the book
+    let (ignored_sender, ignored_receiver) = mpsc::channel::<MarketEvent>(100);
+
+    let mut dispatcher = DepthEventDispatcher::new(depth_receiver, dispatch_sender, sequencing_rules, limits, &Metrics::new());
+    let mut book_processor = BookProcessor::new(dispatch_receiver, book_sender, bbo_sender).with_published_depth(depth);
+    let replayer = TapeReplayer::new(
+        path,
+        0.0,
+        depth_sender,
+        ignored_sender.clone(),
+        ignored_sender.clone(),
+        ignored_sender.clone(),
+        ignored_sender,
+    )
+    .with_decoders(decoders.0, decoders.1)
+    .with_end_time(end_time);
+
+    let dispatcher = tokio::spawn(async move { dispatcher.run().await });
+    let book_processor = tokio::spawn(async move { book_processor.run().await });
+    tokio::spawn(drain(bbo_receiver));
+    tokio::spawn(drain(ignored_receiver));
+    tokio::spawn(replayer.run());
+
+    let mut reconstructed = None;
+    let mut updates = 0;
+    while let Some(book) = book_receiver.recv().await {
+        updates += 1;
+        reconstructed = Some(ReconstructedBook { updates, book });
+    }
+
+    dispatcher.await??;
+    book_processor.await??;
+    Ok(reconstructed)
+}
+
+async fn drain(mut receiver: mpsc::Receiver<MarketEvent>) {
+    while receiver.recv().await.is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{frame_decoder, DepthUpdate, TradeEvent};
+
+    #[tokio::test]
+    async fn test_reconstructs_book_at_time() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_book_at.tape", std::process::id()));
+        let lines = [
+            "1000\tdepth#0\t{\"e\":\"depthUpdate\",\"E\":1,\"s\":\"BTCUSDT\",\"U\":99,\"u\":100,\"b\":[[\"99.0\",\"1.0\"]],\"a\":[]}",
+            "2000\tsnapshot\t{\"lastUpdateId\":100,\"bids\":[[\"100.0\",\"1.0\"]],\"asks\":[[\"101.0\",\"1.0\"]]}",
+            "3000\tdepth#0\t{\"e\":\"depthUpdate\",\"E\":2,\"s\":\"BTCUSDT\",\"U\":101,\"u\":105,\"b\":[[\"100.0\",\"2.0\"]],\"a\":[]}",
+            "3500\ttrade\t{\"e\":\"trade\",\"E\":3,\"s\":\"BTCUSDT\",\"t\":7,\"p\":\"100.5\",\"q\":\"0.1\",\"T\":1,\"m\":true,\"M\":true}",
+            "4000\tdepth#0\t{\"e\":\"depthUpdate\",\"E\":4,\"s\":\"BTCUSDT\",\"U\":106,\"u\":110,\"b\":[[\"100.0\",\"0\"]],\"a\":[]}",
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let reconstruct = |end_time| reconstruct_book(
+            path.clone(),
+            end_time,
+            10,
+            SequencingRules::BinanceSpot,
+            BufferLimits { max_size: 1000, max_age: 0 },
+            (frame_decoder::<DepthUpdate>, frame_decoder::<TradeEvent>),
+        );
+
+        assert!(reconstruct(1500).await.unwrap().is_none());
+
+        let at_snapshot = reconstruct(2000).await.unwrap().unwrap();
+        assert_eq!(at_snapshot.book.best_bid().map(|bid| bid.quantity), Some(FixedPoint::from_f64(1.0)));
+
+        let reconstructed = reconstruct(3999).await.unwrap().unwrap();
+        assert_eq!(reconstructed.updates, 2);
+        assert_eq!(reconstructed.book.best_bid().map(|bid| bid.quantity), Some(FixedPoint::from_f64(2.0)));
+
+        let reconstructed = reconstruct(u64::MAX).await.unwrap().unwrap();
+        assert_eq!(reconstructed.updates, 3);
+        assert_eq!(reconstructed.book.best_bid(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
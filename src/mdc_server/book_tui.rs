@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use chrono::{TimeZone, Utc};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::crossterm::{cursor, execute};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use crate::mdc_core::models::{DepthEntry, MarketEvent, TradeEvent};
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::pipeline_sink::PipelineSink;
+
+/// Number of the latest trades kept for the trade tape
+const TAPE_LENGTH: usize = 100;
+
+/// Interval between two redraws of the terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A trade of the trade tape
+#[derive(Debug, Clone, PartialEq)]
+struct TapeTrade {
+    time: u64,
+    price: f64,
+    quantity: f64,
+    /// Whether the buyer is the maker, i.e. the seller is the aggressor
+    buyer_maker: bool,
+}
+
+/// The state shown by the TUI: the top of the book, the latest trades and the statistics
+pub struct BookView {
+    depth: usize,
+    bids: Vec<DepthEntry>,
+    asks: Vec<DepthEntry>,
+    trades: VecDeque<TapeTrade>,
+    books: u64,
+    trade_count: u64,
+    /// Local time of the latest book in milliseconds since epoch
+    last_book: Option<i64>,
+}
+
+impl BookView {
+    /// Create a new BookView showing `depth` levels per side
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            bids: Vec::new(),
+            asks: Vec::new(),
+            trades: VecDeque::with_capacity(TAPE_LENGTH),
+            books: 0,
+            trade_count: 0,
+            last_book: None,
+        }
+    }
+
+    /// Show a new state of the book, received at the local time `now`
+    pub fn on_book(&mut self, book: &OrderBook, now: i64) {
+        (self.bids, self.asks) = book.top_n(self.depth);
+        self.books += 1;
+        self.last_book = Some(now);
+    }
+
+    /// Add a trade to the trade tape
+    pub fn on_trade(&mut self, trade: &TradeEvent) {
+        if self.trades.len() == TAPE_LENGTH {
+            self.trades.pop_back();
+        }
+        self.trades.push_front(TapeTrade {
+            time: trade.trade_time,
+            price: trade.price,
+            quantity: trade.quantity,
+            buyer_maker: trade.is_market_maker,
+        });
+        self.trade_count += 1;
+    }
+
+    /// Statistics line: best prices, mid, spread, imbalance of the shown levels and the counts
+    fn statistics(&self, now: i64) -> Line<'static> {
+        let best_bid = self.bids.first().map(|level| level.price.to_f64());
+        let best_ask = self.asks.first().map(|level| level.price.to_f64());
+        let format = |value: Option<f64>| value.map_or("-".to_string(), |value| value.to_string());
+
+        let mut spans = vec![
+            Span::styled(format!("Bid {}", format(best_bid)), Style::default().fg(Color::Green)),
+            Span::raw("  "),
+            Span::styled(format!("Ask {}", format(best_ask)), Style::default().fg(Color::Red)),
+        ];
+
+        if let (Some(bid), Some(ask)) = (best_bid, best_ask) {
+            let mid = (bid + ask) / 2.0;
+            spans.push(Span::raw(format!("  Mid {}  Spread {:.8} ({:.2} bps)", mid, ask - bid, (ask - bid) / mid * 10000.0)));
+        }
+
+        let volume = |levels: &[DepthEntry]| levels.iter().map(|level| level.quantity.to_f64()).sum::<f64>();
+        let (bid_volume, ask_volume) = (volume(&self.bids), volume(&self.asks));
+        if bid_volume + ask_volume > 0.0 {
+            spans.push(Span::raw(format!("  Imbalance {:+.2}", (bid_volume - ask_volume) / (bid_volume + ask_volume))));
+        }
+
+        let age = self.last_book.map_or("-".to_string(), |last_book| format!("{} ms", now - last_book));
+        spans.push(Span::raw(format!("  Books {}  Trades {}  Book age {}", self.books, self.trade_count, age)));
+        Line::from(spans)
+    }
+
+    /// Depth ladder: the asks from the highest shown price down, then the bids from the best price down,
+    /// with the cumulative quantity from the best level
+    fn ladder(&self) -> Table<'static> {
+        let rows = |levels: &[DepthEntry], color: Color| -> Vec<Row<'static>> {
+            let mut total = 0.0;
+            levels
+                .iter()
+                .map(|level| {
+                    total += level.quantity.to_f64();
+                    Row::new(vec![level.price.to_string(), level.quantity.to_string(), format!("{:.8}", total)])
+                        .style(Style::default().fg(color))
+                })
+                .collect()
+        };
+
+        let mut ladder = rows(&self.asks, Color::Red);
+        ladder.reverse();
+        ladder.extend(rows(&self.bids, Color::Green));
+
+        Table::new(ladder, [Constraint::Fill(1), Constraint::Fill(1), Constraint::Fill(1)])
+            .header(Row::new(vec!["Price", "Quantity", "Total"]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::bordered().title("Book"))
+    }
+
+    /// Trade tape: the latest trades first, buys in green and sells in red
+    fn tape(&self) -> Table<'static> {
+        let rows = self.trades.iter().map(|trade| {
+            let (side, color) = match trade.buyer_maker {
+                true => ("SELL", Color::Red),
+                false => ("BUY", Color::Green),
+            };
+            let time = Utc
+                .timestamp_millis_opt(trade.time as i64)
+                .single()
+                .map_or_else(String::new, |time| time.format("%H:%M:%S%.3f").to_string());
+            Row::new(vec![time, side.to_string(), trade.price.to_string(), trade.quantity.to_string()])
+                .style(Style::default().fg(color))
+        });
+
+        Table::new(rows, [Constraint::Length(12), Constraint::Length(4), Constraint::Fill(1), Constraint::Fill(1)])
+            .header(Row::new(vec!["Time", "Side", "Price", "Quantity"]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::bordered().title("Trades"))
+    }
+
+    /// Render the view into the frame
+    ///
+    /// # Arguments
+    /// * `frame` - The frame to draw
+    /// * `title` - Title of the view, e.g. the exchange and the instrument
+    /// * `now` - Local time in milliseconds since epoch
+    pub fn render(&self, frame: &mut Frame, title: &str, now: i64) {
+        let [header, body, footer] = Layout::vertical([Constraint::Length(3), Constraint::Fill(1), Constraint::Length(1)])
+            .areas(frame.area());
+        let [ladder, tape] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(body);
+
+        frame.render_widget(Paragraph::new(self.statistics(now)).block(Block::bordered().title(title.to_string())), header);
+        frame.render_widget(self.ladder(), ladder);
+        frame.render_widget(self.tape(), tape);
+        frame.render_widget(Paragraph::new("q: quit").style(Style::default().add_modifier(Modifier::DIM)), footer);
+    }
+}
+
+/// Pipeline sink, which feeds the maintained book and the trades of the pipeline into a BookView
+pub struct BookViewSink {
+    view: Arc<Mutex<BookView>>,
+}
+
+impl BookViewSink {
+    pub fn new(view: Arc<Mutex<BookView>>) -> Self {
+        Self { view }
+    }
+}
+
+impl PipelineSink for BookViewSink {
+    fn on_book(&mut self, book: &Arc<OrderBook>) -> Result<()> {
+        self.view.lock().expect("Book view lock is poisoned").on_book(book, Utc::now().timestamp_millis());
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: &MarketEvent) -> Result<()> {
+        if let MarketEvent::TradeEvent(trade) = event {
+            self.view.lock().expect("Book view lock is poisoned").on_trade(trade);
+        }
+        Ok(())
+    }
+}
+
+/// Restores the terminal once the TUI stops, also if it fails
+struct TerminalGuard {
+    tty: File,
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(self.tty, LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// BookTui draws a BookView in the terminal until `q`, `Esc` or `Ctrl-C` is pressed or it is stopped
+///
+/// It draws on `/dev/tty` rather than the standard output, so the output of the pipeline can be redirected
+pub struct BookTui {
+    view: Arc<Mutex<BookView>>,
+    title: String,
+    stop: Arc<AtomicBool>,
+}
+
+impl BookTui {
+    /// Create a new BookTui
+    ///
+    /// # Arguments
+    /// * `view` - The view, which is fed by a BookViewSink
+    /// * `title` - Title of the view, e.g. the exchange and the instrument
+    pub fn new(view: Arc<Mutex<BookView>>, title: String) -> Self {
+        Self { view, title, stop: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// A flag, which stops the TUI within a redraw interval once it is set
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Run the TUI. It blocks the calling thread until the TUI is quit or stopped
+    pub fn run(self) -> Result<()> {
+        let mut tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .context("Failed to open the terminal")?;
+
+        terminal::enable_raw_mode().context("Failed to switch the terminal to raw mode")?;
+        execute!(tty, EnterAlternateScreen, cursor::Hide)?;
+        let _guard = TerminalGuard { tty: tty.try_clone()? };
+        let mut terminal = Terminal::new(CrosstermBackend::new(tty))?;
+
+        while !self.stop.load(Ordering::Relaxed) {
+            let now = Utc::now().timestamp_millis();
+            terminal.draw(|frame| {
+                self.view.lock().expect("Book view lock is poisoned").render(frame, &self.title, now);
+            })?;
+
+            if !event::poll(REDRAW_INTERVAL)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+                if key.kind == KeyEventKind::Press && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c) {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sets the stop flag of a BookTui once it is dropped, e.g. when the session stops on a signal
+pub struct StopOnDrop(pub Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::DepthSnapshot;
+    use ratatui::backend::TestBackend;
+
+    fn entry(price: &str, quantity: &str) -> DepthEntry {
+        DepthEntry { price: price.parse().unwrap(), quantity: quantity.parse().unwrap() }
+    }
+
+    fn render(view: &BookView, title: &str, now: i64) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(140, 12)).unwrap();
+        terminal.draw(|frame| view.render(frame, title, now)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_render_book_and_trades() {
+        let mut view = BookView::new(2);
+        view.on_book(&OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry("100", "1"), entry("99", "3"), entry("98", "5")],
+            asks: vec![entry("101", "2"), entry("102", "4")],
+        }), 1000);
+        view.on_trade(&TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 1,
+            price: 100.0,
+            quantity: 0.5,
+            trade_time: 1000,
+            is_market_maker: true,
+            ignore: false,
+            received: None,
+        });
+
+        let text = render(&view, "binance BTCUSDT", 1250);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines[0].contains("binance BTCUSDT"));
+        assert!(lines[1].contains("Bid 100  Ask 101  Mid 100.5  Spread 1.00000000 (99.50 bps)"));
+        assert!(lines[1].contains("Imbalance -0.20  Books 1  Trades 1  Book age 250 ms"));
+
+        // The asks from the highest price down, then the bids from the best price down
+        let prices: Vec<&str> = lines[5..9].iter().map(|line| line.trim_start_matches('│').split_whitespace().next().unwrap()).collect();
+        assert_eq!(prices, vec!["102", "101", "100", "99"]);
+        assert!(lines[5].contains("6.00000000"));
+        assert!(lines[5].contains("00:00:01.000 SELL 100"));
+        assert!(lines[11].contains("q: quit"));
+    }
+}
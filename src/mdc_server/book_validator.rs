@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::{DepthSnapshot, DepthUpdate};
+use crate::mdc_core::order_book::{BookSide, OrderBook, Side};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// Number of recent depth updates kept to bring a reference snapshot up to the maintained book.
+/// At the usual update rate it covers minutes, while REST snapshots arrive within seconds
+const HISTORY_SIZE: usize = 1000;
+
+/// A price level, which differs between the maintained book and the reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelMismatch {
+    pub side: Side,
+    pub price: FixedPoint,
+    /// Quantity in the maintained book, `None` if the level is missing
+    pub local: Option<FixedPoint>,
+    /// Quantity in the reference book, `None` if the level is missing
+    pub reference: Option<FixedPoint>,
+}
+
+/// Result of a comparison of the maintained book with a reference snapshot
+#[derive(Debug, Clone)]
+pub struct DriftReport {
+    /// Update id, at which the books were compared
+    pub update_id: u64,
+    pub mismatches: Vec<LevelMismatch>,
+    /// The reference snapshot brought up to `update_id`
+    pub reference: OrderBook,
+}
+
+/// A reference snapshot along with the price range it covers
+struct Reference {
+    snapshot: DepthSnapshot,
+    /// The lowest bid of the snapshot. Lower bids may exist beyond the snapshot depth
+    bid_floor: Option<FixedPoint>,
+    /// The highest ask of the snapshot. Higher asks may exist beyond the snapshot depth
+    ask_ceiling: Option<FixedPoint>,
+}
+
+/// BookValidator detects drift of the maintained book from the exchange state
+///
+/// A reference REST snapshot is usually older than the maintained book, so the validator keeps the recent
+/// depth updates and applies them to the reference snapshot, the same way the book itself is built.
+/// The result has to match the maintained book within the price range covered by the snapshot
+pub struct BookValidator {
+    resync: bool,
+    history: VecDeque<DepthUpdate>,
+    /// Update id, after which the history is complete. `None` until the book is initialized
+    history_base: Option<u64>,
+    last_update_id: u64,
+    pending: Option<Reference>,
+    validations: Counter,
+    drifts: Counter,
+    drifted_levels: Counter,
+    skipped: Counter,
+}
+
+impl BookValidator {
+    /// Create a new BookValidator
+    ///
+    /// # Arguments
+    /// * `resync` - Whether the maintained book has to be replaced with the reference, if drift is detected
+    /// * `metrics` - Registry of the validation counters
+    pub fn new(resync: bool, metrics: &Metrics) -> Self {
+        Self {
+            resync,
+            history: VecDeque::new(),
+            history_base: None,
+            last_update_id: 0,
+            pending: None,
+            validations: metrics.counter("book_validations"),
+            drifts: metrics.counter("book_drift_detected"),
+            drifted_levels: metrics.counter("book_drift_levels"),
+            skipped: metrics.counter("book_validations_skipped"),
+        }
+    }
+
+    /// Whether the maintained book has to be replaced with the reference, if drift is detected
+    pub fn resync(&self) -> bool {
+        self.resync
+    }
+
+    /// Account a snapshot, which replaced the maintained book
+    pub fn on_snapshot(&mut self, snapshot: &DepthSnapshot) {
+        self.history.clear();
+        self.history_base = Some(snapshot.last_update_id);
+        self.last_update_id = snapshot.last_update_id;
+    }
+
+    /// Account a depth update, which has been applied to the maintained book
+    pub fn on_update(&mut self, update: &DepthUpdate) {
+        self.last_update_id = update.last_update_id;
+        self.history.push_back(update.clone());
+
+        if self.history.len() > HISTORY_SIZE {
+            if let Some(dropped) = self.history.pop_front() {
+                self.history_base = Some(dropped.last_update_id);
+            }
+        }
+    }
+
+    /// Set the reference snapshot for the next check. It replaces a reference, which has not been checked yet
+    pub fn set_reference(&mut self, snapshot: DepthSnapshot) {
+        self.pending = Some(Reference {
+            bid_floor: snapshot.bids.iter().map(|entry| entry.price).min(),
+            ask_ceiling: snapshot.asks.iter().map(|entry| entry.price).max(),
+            snapshot,
+        });
+    }
+
+    /// Compare the maintained book with the pending reference
+    ///
+    /// # Arguments
+    /// * `local` - The maintained book, after the last accounted update
+    ///
+    /// # Returns
+    /// The report if the comparison took place
+    ///
+    /// # Behavior
+    /// * A reference, which is newer than the maintained book, is kept until the book catches up
+    /// * A reference, which is older than the kept history, is dropped
+    pub fn check(&mut self, local: &OrderBook) -> Option<DriftReport> {
+        let reference = self.pending.take()?;
+        let reference_id = reference.snapshot.last_update_id;
+
+        if reference_id > self.last_update_id {
+            self.pending = Some(reference);
+            return None;
+        }
+
+        if self.history_base.is_none_or(|base| base > reference_id) {
+            tracing::debug!("Reference snapshot '{}' is older than the update history. Skipping", reference_id);
+            self.skipped.increment(1);
+            return None;
+        }
+
+        let mut book = OrderBook::new(&reference.snapshot);
+        for update in self.history.iter().filter(|update| update.last_update_id > reference_id) {
+            for entry in &update.bids {
+                book.apply_update(OrderBook::bid(entry.price), entry.quantity);
+            }
+            for entry in &update.asks {
+                book.apply_update(OrderBook::ask(entry.price), entry.quantity);
+            }
+        }
+
+        let bid_floor = reference.bid_floor;
+        let ask_ceiling = reference.ask_ceiling;
+        // Levels pruned from a book of limited depth are not maintained, so they are not compared either
+        let mut mismatches = compare_side(Side::Bid, &local.bids, &book.bids, |price| {
+            bid_floor.is_none_or(|floor| price >= floor) && !local.bids.is_pruned(price)
+        });
+        mismatches.extend(compare_side(Side::Ask, &local.asks, &book.asks, |price| {
+            ask_ceiling.is_none_or(|ceiling| price <= ceiling) && !local.asks.is_pruned(price)
+        }));
+
+        self.validations.increment(1);
+        if !mismatches.is_empty() {
+            self.drifts.increment(1);
+            self.drifted_levels.increment(mismatches.len() as u64);
+        }
+
+        Some(DriftReport { update_id: self.last_update_id, mismatches, reference: book })
+    }
+}
+
+/// Find the levels of one side, which differ between the books within the covered price range
+fn compare_side(
+    side: Side,
+    local: &BookSide,
+    reference: &BookSide,
+    covered: impl Fn(FixedPoint) -> bool,
+) -> Vec<LevelMismatch> {
+    let mut mismatches = Vec::new();
+
+    for level in local.iter().filter(|level| covered(level.price)) {
+        let expected = reference.get(level.price);
+        if expected != Some(level.quantity) {
+            mismatches.push(LevelMismatch { side, price: level.price, local: Some(level.quantity), reference: expected });
+        }
+    }
+
+    for level in reference.iter().filter(|level| covered(level.price)) {
+        if !local.contains(level.price) {
+            mismatches.push(LevelMismatch { side, price: level.price, local: None, reference: Some(level.quantity) });
+        }
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::DepthEntry;
+
+    fn entry(price: f64, quantity: f64) -> DepthEntry {
+        DepthEntry { price: FixedPoint::from(price), quantity: FixedPoint::from(quantity) }
+    }
+
+    fn make_snapshot(last: u64, bids: Vec<DepthEntry>, asks: Vec<DepthEntry>) -> DepthSnapshot {
+        DepthSnapshot { last_update_id: last, bids, asks }
+    }
+
+    fn make_update(first: u64, last: u64, bids: Vec<DepthEntry>, asks: Vec<DepthEntry>) -> DepthUpdate {
+        DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            last_update_id: last,
+            previous_last_update_id: None,
+            bids,
+            asks,
+            checksum: None,
+            received: None,
+        }
+    }
+
+    /// Apply an update to the book and account it, as the BookProcessor does
+    fn apply(validator: &mut BookValidator, book: &mut OrderBook, update: DepthUpdate) {
+        for entry in &update.bids {
+            book.apply_update(OrderBook::bid(entry.price), entry.quantity);
+        }
+        for entry in &update.asks {
+            book.apply_update(OrderBook::ask(entry.price), entry.quantity);
+        }
+        validator.on_update(&update);
+    }
+
+    #[test]
+    fn test_consistent_book_with_older_reference() {
+        let metrics = Metrics::new();
+        let mut validator = BookValidator::new(false, &metrics);
+        let initial = make_snapshot(100, vec![entry(100.0, 1.0), entry(99.0, 2.0), entry(98.0, 3.0)], vec![entry(101.0, 1.0)]);
+        let mut book = OrderBook::new(&initial);
+        validator.on_snapshot(&initial);
+
+        apply(&mut validator, &mut book, make_update(101, 105, vec![entry(99.0, 5.0)], vec![]));
+        apply(&mut validator, &mut book, make_update(106, 110, vec![entry(100.0, 0.0)], vec![entry(102.0, 4.0)]));
+
+        // The reference is taken after the first update and only covers two bid levels
+        validator.set_reference(make_snapshot(105, vec![entry(100.0, 1.0), entry(99.0, 5.0)], vec![entry(101.0, 1.0)]));
+        let report = validator.check(&book).unwrap();
+        assert_eq!(report.update_id, 110);
+        assert!(report.mismatches.is_empty());
+        assert!(validator.check(&book).is_none());
+        assert_eq!(metrics.snapshot()["book_validations"], 1);
+        assert_eq!(metrics.snapshot()["book_drift_detected"], 0);
+    }
+
+    #[test]
+    fn test_drift_is_reported() {
+        let metrics = Metrics::new();
+        let mut validator = BookValidator::new(true, &metrics);
+        let initial = make_snapshot(100, vec![entry(100.0, 1.0), entry(99.0, 2.0)], vec![entry(101.0, 1.0)]);
+        let mut book = OrderBook::new(&initial);
+        validator.on_snapshot(&initial);
+
+        // The maintained book missed the removal of the bid at 99 and has a stale ask quantity
+        apply(&mut validator, &mut book, make_update(101, 105, vec![], vec![entry(101.0, 3.0)]));
+        book.apply_update(OrderBook::ask(FixedPoint::from(101.0)), FixedPoint::from(2.5));
+
+        validator.set_reference(make_snapshot(103, vec![entry(100.0, 1.0), entry(98.0, 1.0)], vec![entry(101.0, 1.0)]));
+        let report = validator.check(&book).unwrap();
+        assert!(validator.resync());
+        assert_eq!(report.mismatches, vec![
+            LevelMismatch { side: Side::Bid, price: FixedPoint::from(99.0), local: Some(FixedPoint::from(2.0)), reference: None },
+            LevelMismatch { side: Side::Bid, price: FixedPoint::from(98.0), local: None, reference: Some(FixedPoint::from(1.0)) },
+            LevelMismatch { side: Side::Ask, price: FixedPoint::from(101.0), local: Some(FixedPoint::from(2.5)), reference: Some(FixedPoint::from(3.0)) },
+        ]);
+        assert_eq!(report.reference.best_ask(), Some(entry(101.0, 3.0)));
+        assert_eq!(metrics.snapshot()["book_drift_detected"], 1);
+        assert_eq!(metrics.snapshot()["book_drift_levels"], 3);
+    }
+
+    #[test]
+    fn test_reference_timing() {
+        let metrics = Metrics::new();
+        let mut validator = BookValidator::new(false, &metrics);
+        let initial = make_snapshot(100, vec![entry(100.0, 1.0)], vec![entry(101.0, 1.0)]);
+        let mut book = OrderBook::new(&initial);
+
+        // Nothing can be compared before the book is initialized
+        validator.set_reference(make_snapshot(90, vec![], vec![]));
+        validator.last_update_id = 100;
+        assert!(validator.check(&book).is_none());
+        assert_eq!(metrics.snapshot()["book_validations_skipped"], 1);
+
+        validator.on_snapshot(&initial);
+
+        // A reference ahead of the book waits for it to catch up
+        validator.set_reference(make_snapshot(105, vec![entry(100.0, 2.0)], vec![entry(101.0, 1.0)]));
+        assert!(validator.check(&book).is_none());
+        apply(&mut validator, &mut book, make_update(101, 105, vec![entry(100.0, 2.0)], vec![]));
+        assert!(validator.check(&book).unwrap().mismatches.is_empty());
+
+        // A reference older than the history can't be brought up to the book
+        for id in 0..=HISTORY_SIZE as u64 {
+            apply(&mut validator, &mut book, make_update(106 + id, 106 + id, vec![], vec![]));
+        }
+        validator.set_reference(make_snapshot(105, vec![entry(100.0, 2.0)], vec![entry(101.0, 1.0)]));
+        assert!(validator.check(&book).is_none());
+        assert_eq!(metrics.snapshot()["book_validations_skipped"], 2);
+    }
+}
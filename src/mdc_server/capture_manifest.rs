@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use crate::mdc_server::config::Config;
+use crate::mdc_core::models::SymbolMetadata;
+
+/// Describes a single capture session, so the captured data can be interpreted offline
+/// without re-querying the exchange
+///
+/// The manifest is stored as a JSON file in the capture directory, named after the session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureManifest {
+    pub instrument: String,
+    pub started_at: i64,
+    #[serde(alias = "binance_rest_endpoint")]
+    pub rest_endpoint: String,
+    #[serde(alias = "binance_wss_endpoint")]
+    pub wss_endpoint: String,
+    pub max_depth: u64,
+    pub symbol_metadata: Option<SymbolMetadata>,
+    pub tape_file: Option<String>,
+    /// Intervals, during which capture files were not written (e.g. due to low disk space)
+    #[serde(default)]
+    pub paused_intervals: Vec<PausedInterval>,
+}
+
+/// An interval in milliseconds since epoch, during which capture was paused. `to` is not set while it lasts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PausedInterval {
+    pub from: i64,
+    pub to: Option<i64>,
+}
+
+impl CaptureManifest {
+    /// Create a manifest for a session, which starts now
+    ///
+    /// # Arguments
+    /// * `config` - The configuration the session is running with
+    /// * `symbol_metadata` - Metadata of the captured symbol, if it is available
+    pub fn new(config: &Config, symbol_metadata: Option<SymbolMetadata>) -> Self {
+        Self {
+            instrument: config.instrument.clone(),
+            started_at: Utc::now().timestamp_millis(),
+            rest_endpoint: config.rest_endpoint.clone(),
+            wss_endpoint: config.wss_endpoint.clone(),
+            max_depth: config.max_depth,
+            symbol_metadata,
+            tape_file: None,
+            paused_intervals: Vec::new(),
+        }
+    }
+
+    /// Name of the session, which is used as a file stem for all the session artifacts
+    /// (e.g. 'BTCUSDT_20240101_120000')
+    pub fn session_name(&self) -> String {
+        let started_at = Utc
+            .timestamp_millis_opt(self.started_at)
+            .unwrap()
+            .format("%Y%m%d_%H%M%S");
+
+        format!("{}_{}", self.instrument, started_at)
+    }
+
+    /// Write the manifest into the capture directory, creating the directory if needed
+    ///
+    /// # Returns
+    /// * `Result<PathBuf>` - Path of the written manifest file
+    pub fn write<P: AsRef<Path>>(&self, capture_dir: P) -> Result<PathBuf> {
+        let capture_dir = capture_dir.as_ref();
+        fs::create_dir_all(capture_dir)
+            .with_context(|| format!("Failed to create capture directory: {:?}", capture_dir))?;
+
+        let path = capture_dir.join(format!("{}.manifest.json", self.session_name()));
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write capture manifest: {:?}", path))?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let manifest = CaptureManifest {
+            instrument: "BTCUSDT".to_string(),
+            started_at: 1704110400000,
+            rest_endpoint: "https://api.example.com/".to_string(),
+            wss_endpoint: "wss://stream.example.com/".to_string(),
+            max_depth: 100,
+            symbol_metadata: Some(SymbolMetadata {
+                symbol: "BTCUSDT".to_string(),
+                status: "TRADING".to_string(),
+                base_asset: "BTC".to_string(),
+                quote_asset: "USDT".to_string(),
+                tick_size: Some(FixedPoint::from(0.01)),
+                step_size: Some(FixedPoint::from(0.00001)),
+                min_qty: None,
+                max_qty: None,
+            }),
+            tape_file: Some("capture/BTCUSDT_20240101_120000.tape".to_string()),
+            paused_intervals: vec![PausedInterval { from: 1704110500000, to: Some(1704110600000) }],
+        };
+
+        assert_eq!(manifest.session_name(), "BTCUSDT_20240101_120000");
+
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_manifest", std::process::id()));
+        let path = manifest.write(&dir).unwrap();
+        assert!(path.ends_with("BTCUSDT_20240101_120000.manifest.json"));
+
+        let loaded: CaptureManifest = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(loaded.instrument, "BTCUSDT");
+        assert_eq!(loaded.started_at, 1704110400000);
+        assert_eq!(loaded.symbol_metadata, manifest.symbol_metadata);
+        assert_eq!(loaded.tape_file, manifest.tape_file);
+        assert_eq!(loaded.paused_intervals, manifest.paused_intervals);
+    }
+}
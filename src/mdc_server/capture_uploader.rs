@@ -0,0 +1,329 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::time::{interval, MissedTickBehavior};
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_server::s3_client::{S3Client, Signer};
+use crate::mdc_server::transport::Transport;
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_prefix() -> String {
+    "{exchange}/{symbol}/{date}/".to_string()
+}
+
+fn default_upload_interval() -> u64 {
+    60000
+}
+
+fn default_min_age() -> u64 {
+    60000
+}
+
+fn default_part_size() -> usize {
+    16 << 20
+}
+
+/// Upload of the capture files of finished sessions to S3-compatible object storage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureUpload {
+    /// URL of the storage, e.g. `https://s3.eu-central-1.amazonaws.com`
+    pub endpoint: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    pub bucket: String,
+    /// Template of the key prefix. `{exchange}`, `{symbol}`, `{session}`, `{date}` (YYYY-MM-DD), `{year}`, `{month}`
+    /// and `{day}` are replaced with the values of the session. The file name is appended to the prefix
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Access key, `AWS_ACCESS_KEY_ID` if not set
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    /// Secret key, `AWS_SECRET_ACCESS_KEY` if not set
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    /// Interval between scans of the capture directory in milliseconds. Failed uploads are retried on the next scan
+    #[serde(default = "default_upload_interval")]
+    pub interval: u64,
+    /// Time in milliseconds a file must stay unmodified before it is uploaded
+    #[serde(default = "default_min_age")]
+    pub min_age: u64,
+    /// Time in milliseconds since the last modification, after which an uploaded file is deleted locally.
+    /// Uploaded files are kept if not set
+    #[serde(default)]
+    pub retention: Option<u64>,
+    /// Size of the parts of multipart uploads in bytes. Smaller files are uploaded with a single request
+    #[serde(default = "default_part_size")]
+    pub part_size: usize,
+}
+
+impl CaptureUpload {
+    /// Create the client of the storage, taking missing credentials from the environment
+    pub fn client(&self, transport: Transport) -> Result<S3Client> {
+        let credential = |value: &Option<String>, variable: &str| {
+            value.clone()
+                .or_else(|| std::env::var(variable).ok())
+                .ok_or_else(|| anyhow!("Object storage credentials are missing: '{}' is not set", variable))
+        };
+
+        let signer = Signer::new(
+            credential(&self.access_key_id, "AWS_ACCESS_KEY_ID")?,
+            credential(&self.secret_access_key, "AWS_SECRET_ACCESS_KEY")?,
+            self.region.clone(),
+        );
+        S3Client::new(&self.endpoint, &self.bucket, signer, self.part_size)?.with_transport(transport)
+    }
+}
+
+/// Storage, which the capture files are uploaded into
+pub trait ObjectStore: Send + Sync {
+    /// Upload the file as the object with the given key, replacing an existing one
+    fn upload<'a>(&'a self, key: &'a str, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Subdirectory of the capture directory, which holds a marker file for every uploaded file
+pub const UPLOAD_MARKER_DIR: &str = ".uploaded";
+
+/// Session of a capture file, parsed from its name (`<INSTRUMENT>_<YYYYMMDD>_<HHMMSS>.<kind>`)
+pub(crate) struct FileSession<'a> {
+    pub name: &'a str,
+    pub instrument: &'a str,
+    pub date: &'a str,
+}
+
+impl<'a> FileSession<'a> {
+    pub fn parse(file_name: &'a str) -> Option<Self> {
+        let name = file_name.split('.').next()?;
+        let mut parts = name.rsplitn(3, '_');
+        let (time, date, instrument) = (parts.next()?, parts.next()?, parts.next()?);
+        let is_digits = |value: &str, length: usize| value.len() == length && value.bytes().all(|byte| byte.is_ascii_digit());
+
+        (name.len() < file_name.len() && is_digits(date, 8) && is_digits(time, 6))
+            .then_some(Self { name, instrument, date })
+    }
+}
+
+/// CaptureUploader uploads the capture files (tapes, manifests, outputs, reports, ...) of the finished sessions
+/// of an instrument and deletes the uploaded files once the retention has passed
+///
+/// The files of the current session are never touched. Uploaded files are marked in the `.uploaded` subdirectory
+/// of the capture directory, so they are neither uploaded again nor kept beyond the retention after a restart
+pub struct CaptureUploader {
+    store: Box<dyn ObjectStore>,
+    settings: CaptureUpload,
+    capture_dir: PathBuf,
+    exchange: String,
+    instrument: String,
+    session: String,
+    uploaded: Counter,
+    failed: Counter,
+    uploaded_bytes: Counter,
+    deleted: Counter,
+}
+
+impl CaptureUploader {
+    /// Create a new CaptureUploader
+    ///
+    /// # Arguments
+    /// * `store` - Storage, which the files are uploaded into
+    /// * `settings` - Key prefix, intervals and retention of the upload
+    /// * `capture_dir` - Directory of the capture files
+    /// * `exchange` - Name of the exchange, used in the key prefix
+    /// * `instrument` - Instrument, whose files are uploaded
+    /// * `session` - Name of the current session, whose files are skipped
+    /// * `metrics` - Registry of the `uploads_completed`, `uploads_failed`, `upload_bytes` and `uploads_deleted` counters
+    pub fn new(
+        store: Box<dyn ObjectStore>,
+        settings: CaptureUpload,
+        capture_dir: PathBuf,
+        exchange: &str,
+        instrument: &str,
+        session: String,
+        metrics: &Metrics,
+    ) -> Self {
+        Self {
+            store,
+            settings,
+            capture_dir,
+            exchange: exchange.to_string(),
+            instrument: instrument.to_string(),
+            session,
+            uploaded: metrics.counter("uploads_completed"),
+            failed: metrics.counter("uploads_failed"),
+            uploaded_bytes: metrics.counter("upload_bytes"),
+            deleted: metrics.counter("uploads_deleted"),
+        }
+    }
+
+    /// Object key of a capture file
+    fn key(&self, file_name: &str, session: &FileSession) -> String {
+        let (year, month, day) = (&session.date[..4], &session.date[4..6], &session.date[6..]);
+        let prefix = self.settings.prefix
+            .replace("{exchange}", &self.exchange)
+            .replace("{symbol}", session.instrument)
+            .replace("{session}", session.name)
+            .replace("{date}", &format!("{}-{}-{}", year, month, day))
+            .replace("{year}", year)
+            .replace("{month}", month)
+            .replace("{day}", day);
+
+        format!("{}{}", prefix, file_name)
+    }
+
+    /// Upload the files, which are due, and apply the retention to the uploaded ones
+    pub async fn scan(&self) -> Result<()> {
+        let marker_dir = self.capture_dir.join(UPLOAD_MARKER_DIR);
+        fs::create_dir_all(&marker_dir).with_context(|| format!("Failed to create upload marker directory: {:?}", marker_dir))?;
+        let entries = fs::read_dir(&self.capture_dir).with_context(|| format!("Failed to read capture directory: {:?}", self.capture_dir))?;
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(session) = FileSession::parse(&file_name) else {
+                continue;
+            };
+            if session.instrument != self.instrument || session.name == self.session {
+                continue;
+            }
+
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let age = metadata.modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .unwrap_or_default();
+            let marker = marker_dir.join(&file_name);
+
+            if !marker.exists() {
+                if age < Duration::from_millis(self.settings.min_age) {
+                    continue;
+                }
+
+                let key = self.key(&file_name, &session);
+                match self.store.upload(&key, &entry.path()).await {
+                    Ok(()) => {
+                        fs::write(&marker, &key).with_context(|| format!("Failed to write upload marker: {:?}", marker))?;
+                        self.uploaded.increment(1);
+                        self.uploaded_bytes.increment(metadata.len());
+                        tracing::info!("Uploaded '{}' as '{}'", file_name, key);
+                    }
+                    Err(e) => {
+                        self.failed.increment(1);
+                        tracing::warn!("Failed to upload '{}'. Retrying on the next scan. Details: '{:#}'", file_name, e);
+                        continue;
+                    }
+                }
+            }
+
+            if self.settings.retention.is_some_and(|retention| age >= Duration::from_millis(retention)) {
+                fs::remove_file(entry.path()).with_context(|| format!("Failed to delete uploaded file: {:?}", entry.path()))?;
+                let _ = fs::remove_file(&marker);
+                self.deleted.increment(1);
+                tracing::info!("Deleted uploaded file '{}'", file_name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the CaptureUploader as an asynchronous task, scanning the capture directory every interval
+    pub async fn run(self) {
+        let mut ticker = interval(Duration::from_millis(self.settings.interval.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.scan().await {
+                tracing::error!("Failed to scan capture files for upload. Details: '{:#}'", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct FakeStore {
+        keys: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    impl ObjectStore for FakeStore {
+        fn upload<'a>(&'a self, key: &'a str, _path: &'a Path) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                if self.fail {
+                    return Err(anyhow!("Storage is unavailable"));
+                }
+                self.keys.lock().unwrap().push(key.to_string());
+                Ok(())
+            })
+        }
+    }
+
+    fn make_settings(retention: Option<u64>) -> CaptureUpload {
+        serde_yaml::from_str::<CaptureUpload>("{endpoint: 'http://localhost:9000', bucket: capture, min_age: 0}")
+            .map(|settings| CaptureUpload { retention, ..settings })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_uploads_finished_sessions() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_uploader", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".checkpoints")).unwrap();
+        for name in [
+            "BTCUSDT_20240101_120000.tape.zst",
+            "BTCUSDT_20240101_120000.manifest.json",
+            "BTCUSDT_20240102_080000.tape",
+            "ETHUSDT_20240101_120000.tape",
+            "symbols.json",
+        ] {
+            fs::write(dir.join(name), "data").unwrap();
+        }
+
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let metrics = Metrics::new();
+        let uploader = |fail, retention| CaptureUploader::new(
+            Box::new(FakeStore { keys: keys.clone(), fail }),
+            make_settings(retention),
+            dir.clone(),
+            "binance",
+            "BTCUSDT",
+            "BTCUSDT_20240102_080000".to_string(),
+            &metrics,
+        );
+
+        uploader(true, None).scan().await.unwrap();
+        assert_eq!(metrics.snapshot()["uploads_failed"], 2);
+
+        uploader(false, None).scan().await.unwrap();
+        uploader(false, None).scan().await.unwrap();
+        let mut uploaded = keys.lock().unwrap().clone();
+        uploaded.sort();
+        assert_eq!(uploaded, vec![
+            "binance/BTCUSDT/2024-01-01/BTCUSDT_20240101_120000.manifest.json",
+            "binance/BTCUSDT/2024-01-01/BTCUSDT_20240101_120000.tape.zst",
+        ]);
+        assert!(dir.join("BTCUSDT_20240101_120000.tape.zst").exists());
+
+        uploader(false, Some(0)).scan().await.unwrap();
+        assert!(!dir.join("BTCUSDT_20240101_120000.tape.zst").exists());
+        assert!(dir.join("BTCUSDT_20240102_080000.tape").exists());
+        assert!(dir.join("ETHUSDT_20240101_120000.tape").exists());
+        assert_eq!(metrics.snapshot()["uploads_deleted"], 2);
+        assert_eq!(keys.lock().unwrap().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
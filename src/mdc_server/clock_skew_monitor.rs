@@ -0,0 +1,177 @@
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use tokio::time::{interval, Duration};
+use crate::mdc_server::exchange_connector::ExchangeConnector;
+use crate::mdc_server::metrics::{Counter, Gauge, Metrics};
+use crate::mdc_server::request_weight::RequestWeightBudget;
+use crate::mdc_server::transport::Transport;
+
+/// Server time requests per check. The sample with the shortest round trip is the most accurate one
+const SAMPLES_PER_CHECK: usize = 3;
+
+/// Weight of a server time request
+const SERVER_TIME_WEIGHT: u64 = 1;
+
+/// Clock offset in milliseconds, above which receive latencies are considered distorted and a warning is logged
+const MAX_CLOCK_OFFSET: i64 = 100;
+
+/// A single server time measurement. All times are in milliseconds since epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockSample {
+    /// Local time, at which the request has been sent
+    pub sent_at: i64,
+    /// Local time, at which the response has been received
+    pub received_at: i64,
+    /// Server time from the response
+    pub server_time: i64,
+}
+
+impl ClockSample {
+    pub fn round_trip(&self) -> i64 {
+        self.received_at - self.sent_at
+    }
+
+    /// Server clock minus local clock, assuming the server has taken its time in the middle of the round trip
+    ///
+    /// The error of the estimate is at most half of the round trip. A positive offset means the local clock is behind,
+    /// so receive latencies measured against the exchange event time are too low by the offset
+    pub fn offset(&self) -> i64 {
+        self.server_time - (self.sent_at + self.received_at) / 2
+    }
+}
+
+/// ClockSkewMonitor periodically estimates the offset of the local clock from the exchange server clock
+///
+/// The offset and the round trip of the most accurate sample of every check are published as the `clock_offset`
+/// and `clock_round_trip` gauges, failed checks are counted by the `clock_check_failures` counter
+pub struct ClockSkewMonitor {
+    connector: Arc<dyn ExchangeConnector>,
+    check_interval: u64,
+    weight_budget: Option<RequestWeightBudget>,
+    transport: Transport,
+    offset: Gauge,
+    round_trip: Gauge,
+    failures: Counter,
+}
+
+impl ClockSkewMonitor {
+    /// Create a new ClockSkewMonitor
+    ///
+    /// # Arguments
+    /// * `connector` - The exchange connector, which provides the server time endpoint
+    /// * `check_interval` - Check interval in milliseconds
+    /// * `metrics` - Registry of the clock gauges and the failure counter
+    pub fn new(connector: Arc<dyn ExchangeConnector>, check_interval: u64, metrics: &Metrics) -> Self {
+        Self {
+            connector,
+            check_interval,
+            weight_budget: None,
+            transport: Transport::default(),
+            offset: metrics.gauge("clock_offset"),
+            round_trip: metrics.gauge("clock_round_trip"),
+            failures: metrics.counter("clock_check_failures"),
+        }
+    }
+
+    /// Keep the weight of the server time requests within the budget, which may be shared with other streams
+    pub fn with_weight_budget(mut self, weight_budget: RequestWeightBudget) -> Self {
+        self.weight_budget = Some(weight_budget);
+        self
+    }
+
+    /// Send the requests with the proxy and TLS settings of the transport
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Request the server time once
+    async fn sample(&self) -> Result<ClockSample> {
+        if let Some(budget) = &self.weight_budget {
+            budget.acquire(SERVER_TIME_WEIGHT, "Server time").await;
+        }
+
+        let url = self.connector
+            .server_time_url()
+            .with_context(|| format!("Exchange '{}' doesn't provide the server time", self.connector.name()))?;
+
+        let sent_at = Utc::now().timestamp_millis();
+        let response = self.transport.get(&url)
+            .await
+            .context("Failed to send server time request")?;
+        let received_at = Utc::now().timestamp_millis();
+
+        if let Some(budget) = &self.weight_budget {
+            budget.on_http_response(&response);
+        }
+
+        let response_text = response
+            .error_for_status()
+            .context("Failed to get server time response")?
+            .text()
+            .await
+            .context("Failed to get response text for server time")?;
+        let server_time = self.connector.parse_server_time(&response_text)?;
+
+        Ok(ClockSample { sent_at, received_at, server_time })
+    }
+
+    /// Take several samples and keep the most accurate one
+    async fn check(&self) -> Result<ClockSample> {
+        let mut best: Option<ClockSample> = None;
+        for _ in 0..SAMPLES_PER_CHECK {
+            let sample = self.sample().await?;
+            if best.is_none_or(|best| sample.round_trip() < best.round_trip()) {
+                best = Some(sample);
+            }
+        }
+
+        best.context("No server time samples taken")
+    }
+
+    /// Run the ClockSkewMonitor as an asynchronous task
+    pub async fn run(self) {
+        let mut ticker = interval(Duration::from_millis(self.check_interval));
+
+        loop {
+            ticker.tick().await;
+
+            match self.check().await {
+                Ok(sample) => {
+                    self.offset.set(sample.offset());
+                    self.round_trip.set(sample.round_trip());
+
+                    if sample.offset().abs() > MAX_CLOCK_OFFSET {
+                        tracing::warn!(
+                            "Clock offset from the '{}' server is '{}' ms (round trip '{}' ms). Receive latencies are distorted",
+                            self.connector.name(), sample.offset(), sample.round_trip()
+                        );
+                    } else {
+                        tracing::debug!("Clock offset from the '{}' server: '{}' ms, round trip '{}' ms", self.connector.name(), sample.offset(), sample.round_trip());
+                    }
+                }
+                Err(e) => {
+                    self.failures.increment(1);
+                    tracing::error!("Failed to check the clock against the '{}' server. Details: '{:#}'", self.connector.name(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_is_measured_from_the_middle_of_the_round_trip() {
+        // The local clock is 40 ms behind, the request takes 10 ms each way
+        let sample = ClockSample { sent_at: 1_000, received_at: 1_020, server_time: 1_050 };
+        assert_eq!(sample.round_trip(), 20);
+        assert_eq!(sample.offset(), 40);
+
+        let ahead = ClockSample { sent_at: 1_000, received_at: 1_006, server_time: 950 };
+        assert_eq!(ahead.offset(), -53);
+    }
+}
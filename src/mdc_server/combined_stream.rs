@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, sleep_until, Instant};
+use tungstenite::Message;
+use crate::mdc_core::models::{MarketEvent, MarketEventSource, ReceiveTime};
+use crate::mdc_server::live_status::StreamStatusReporter;
+use crate::mdc_server::metrics::Metrics;
+use crate::mdc_server::transport::Transport;
+use crate::mdc_server::stall_watchdog::StallWatchdog;
+use crate::mdc_server::tape::TapeRecorder;
+
+/// Message of a combined stream: the payload of one of the multiplexed streams, tagged with the stream name
+#[derive(Deserialize)]
+struct CombinedMessage<'a> {
+    stream: String,
+    #[serde(borrow)]
+    data: &'a RawValue,
+}
+
+/// Destination of the messages of a single stream within a combined connection
+pub struct StreamRoute {
+    parse: fn(&str) -> Result<MarketEvent, serde_json::Error>,
+    output: mpsc::Sender<MarketEvent>,
+    recorder: Option<TapeRecorder>,
+    status: Option<StreamStatusReporter>,
+}
+
+impl StreamRoute {
+    /// Create a route of a stream, whose payloads are parsed as `T`
+    ///
+    /// # Arguments
+    /// * `output` - Channel for the parsed events of the stream
+    /// * `recorder` - Optional recorder, which persists the unwrapped payload of every message of the stream
+    /// * `status` - Optional reporter of the stream state
+    pub fn new<T>(
+        output: mpsc::Sender<MarketEvent>,
+        recorder: Option<TapeRecorder>,
+        status: Option<StreamStatusReporter>,
+    ) -> Self
+    where T: MarketEventSource,
+    {
+        Self {
+            parse: |payload| T::from_json(payload).map(T::into_market_event),
+            output,
+            recorder,
+            status,
+        }
+    }
+}
+
+/// A WebSocket client of a Binance combined stream (`/stream?streams=a/b/c`)
+///
+/// Messages of all streams arrive over a single connection wrapped into `{"stream": <name>, "data": <payload>}`.
+/// The demultiplexer unwraps every message and routes its payload to the route of the stream, so the rest of the
+/// pipeline (including the tape, which records the unwrapped payloads) can't tell it from separate connections.
+/// Like `MarketEventStream`, the connection is re-established after the reconnect timeout if it fails or stalls.
+pub struct CombinedEventStream {
+    url: String,
+    routes: HashMap<String, StreamRoute>,
+    reconnect_timeout: u64,
+    watchdog: Option<StallWatchdog>,
+    transport: Transport,
+}
+
+impl CombinedEventStream {
+    /// Create a new CombinedEventStream
+    ///
+    /// # Arguments
+    /// * `url` - The URL of the combined stream
+    /// * `routes` - Routes of the multiplexed streams by stream name (e.g. "btcusdt@trade")
+    /// * `reconnect_timeout` - Timeout in milliseconds to wait before attempting to reconnect after a connection failure
+    pub fn new(url: String, routes: HashMap<String, StreamRoute>, reconnect_timeout: u64) -> Self {
+        Self {
+            url,
+            routes,
+            reconnect_timeout,
+            watchdog: None,
+            transport: Transport::default(),
+        }
+    }
+
+    /// Reconnect the stream if no frames of any of the multiplexed streams are received for the given time
+    ///
+    /// # Arguments
+    /// * `stall_timeout` - Time in milliseconds without frames, after which the connection is stalled. 0 disables the check
+    /// * `metrics` - Registry of the stall counter
+    pub fn with_stall_timeout(mut self, stall_timeout: u64, metrics: &Metrics) -> Self {
+        self.watchdog = (stall_timeout > 0).then(|| StallWatchdog::new(stall_timeout, metrics));
+        self
+    }
+
+    /// Connect with the proxy and TLS settings of the transport
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    fn statuses(&self) -> impl Iterator<Item = &StreamStatusReporter> {
+        self.routes.values().filter_map(|route| route.status.as_ref())
+    }
+
+    /// Run the CombinedEventStream as an asynchronous task
+    ///
+    /// This method does not return under normal circumstances and should typically be spawned as a separate task
+    pub async fn run(&mut self) {
+        loop {
+            let result = self.run_session().await;
+            self.statuses().for_each(|status| status.on_disconnected());
+
+            match result {
+                Ok(()) => {
+                    tracing::trace!("Session '{}' finished", self.url);
+                }
+                Err(e) => {
+                    tracing::error!("Session '{}' finished with error: '{}'. Reconnecting in '{}' ms", self.url, e, self.reconnect_timeout);
+                    sleep(Duration::from_millis(self.reconnect_timeout)).await;
+                }
+            }
+        }
+    }
+
+    /// Run a single WebSocket session until the connection is closed or fails
+    async fn run_session(&mut self) -> Result<()> {
+        let (ws_stream, _) = self.transport.connect_websocket(self.url.as_str()).await?;
+        let (mut ws_writer, mut ws_reader) = ws_stream.split();
+        self.statuses().for_each(|status| status.on_connected());
+
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.on_message();
+        }
+
+        loop {
+            let stall_deadline = self.watchdog.as_ref().map(StallWatchdog::deadline);
+
+            tokio::select! {
+                msg = ws_reader.next() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+
+                    tracing::trace!("Received message: '{:?}'", msg);
+
+                    if let Some(watchdog) = &mut self.watchdog {
+                        watchdog.on_message();
+                    }
+
+                    match msg? {
+                        Message::Text(text) => self.on_message(&text).await?,
+                        Message::Ping(payload) => ws_writer.send(Message::Pong(payload)).await?,
+                        Message::Close(frame) => tracing::trace!("Channel was closed: {:?}", frame),
+                        _ => {}
+                    }
+                }
+                _ = sleep_until(stall_deadline.unwrap_or_else(Instant::now)), if stall_deadline.is_some() => {
+                    if let Some(watchdog) = &self.watchdog {
+                        return Err(watchdog.on_stall(&self.url));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unwrap a message of the combined stream and forward its payload to the route of the stream
+    ///
+    /// Messages of unknown streams are skipped
+    async fn on_message(&self, message: &str) -> Result<()> {
+        let received = ReceiveTime::now();
+        let message: CombinedMessage = serde_json::from_str(message)?;
+        let Some(route) = self.routes.get(&message.stream) else {
+            tracing::warn!("Skipping message of unexpected stream '{}'", message.stream);
+            return Ok(());
+        };
+
+        let payload = message.data.get();
+        if let Some(recorder) = &route.recorder {
+            recorder.record(payload).await;
+        }
+
+        let mut event = (route.parse)(payload)?;
+        event.stamp(received);
+        if let Some(status) = &route.status {
+            status.on_event(&event);
+        }
+
+        route.output.send(event).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{PriceUpdate, TradeEvent};
+    use crate::mdc_server::tape::TapeRecord;
+
+    #[tokio::test]
+    async fn test_messages_are_routed_by_stream() {
+        let (trade_tx, mut trade_rx) = mpsc::channel(10);
+        let (price_tx, mut price_rx) = mpsc::channel(10);
+        let (tape_tx, mut tape_rx) = mpsc::channel::<TapeRecord>(10);
+
+        let routes = HashMap::from([
+            ("btcusdt@trade".to_string(), StreamRoute::new::<TradeEvent>(trade_tx, Some(TapeRecorder::new("trade#0".to_string(), tape_tx)), None)),
+            ("btcusdt@bookTicker".to_string(), StreamRoute::new::<PriceUpdate>(price_tx, None, None)),
+        ]);
+        let stream = CombinedEventStream::new("wss://localhost/stream".to_string(), routes, 0);
+
+        let trade = r#"{"e":"trade","E":1,"s":"BTCUSDT","t":7,"p":"100.5","q":"0.1","T":1,"m":true,"M":true}"#;
+        stream.on_message(&format!(r#"{{"stream":"btcusdt@trade","data":{}}}"#, trade)).await.unwrap();
+        stream.on_message(r#"{"stream":"btcusdt@bookTicker","data":{"u":9,"s":"BTCUSDT","b":"100.0","B":"1.0","a":"101.0","A":"1.0"}}"#).await.unwrap();
+        stream.on_message(r#"{"stream":"btcusdt@depth","data":{}}"#).await.unwrap();
+
+        assert!(matches!(trade_rx.try_recv(), Ok(MarketEvent::TradeEvent(t)) if t.trade_id == 7));
+        assert!(matches!(price_rx.try_recv(), Ok(MarketEvent::PriceUpdate(p)) if p.update_id == 9));
+        assert!(trade_rx.try_recv().is_err());
+        assert!(price_rx.try_recv().is_err());
+
+        // The unwrapped payload is recorded under the source of the route
+        let record = tape_rx.try_recv().unwrap();
+        assert_eq!(record.source, "trade#0");
+        assert_eq!(record.payload, trade);
+    }
+}
@@ -1,56 +1,607 @@
-use anyhow::{Context, Result};
-use serde::Deserialize;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 use std::fs;
 use std::path::Path;
+use crate::mdc_core::checksum::{BITFINEX_CHECKSUM_DEPTH, OKX_CHECKSUM_DEPTH};
+use crate::mdc_core::depth_buckets::PriceBuckets;
+use crate::mdc_core::models::KlineInterval;
+use crate::mdc_server::exchange_connector::Exchange;
+use crate::mdc_server::fanout::ChannelPolicies;
+use crate::mdc_server::level_changes::LevelChangeFilter;
+use crate::mdc_server::okx_connector::OkxBookChannel;
+use crate::mdc_server::partial_depth::SnapshotSource;
+use crate::mdc_server::all_market_prices::PriceSource;
+use crate::mdc_server::bitfinex_connector::BitfinexPrecision;
+use crate::mdc_server::binance_connector::BinanceSbe;
+use crate::mdc_server::proxy::ProxyConfig;
+use crate::mdc_server::tls::TlsConfig;
+use crate::mdc_server::keepalive::KeepaliveConfig;
+use crate::mdc_server::transport::Transport;
+use crate::mdc_server::symbol_mapping::SymbolMap;
+use crate::mdc_server::symbol_discovery::SymbolDiscovery;
+use crate::mdc_server::tape::TapeCompression;
+use crate::mdc_server::capture_uploader::CaptureUpload;
+use crate::mdc_server::retention::Retention;
+use crate::mdc_server::alerting::Alerting;
+use crate::mdc_server::event_scripts::EventScript;
+use crate::mdc_core::anomaly::AnomalySettings;
+use crate::mdc_server::exchange_simulator::SimulationSettings;
+use crate::mdc_server::task_supervisor::RestartPolicy;
 
 /// Configuration for the Market Data Capture (MDC) server.
 ///
 /// This struct holds all the configuration parameters needed to run the MDC server
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    pub binance_rest_endpoint: String,
-    pub binance_wss_endpoint: String,
+    #[serde(default)]
+    pub exchange: Exchange,
+    /// REST API endpoint of the exchange
+    #[serde(alias = "binance_rest_endpoint")]
+    pub rest_endpoint: String,
+    /// WebSocket endpoint of the exchange
+    #[serde(alias = "binance_wss_endpoint")]
+    pub wss_endpoint: String,
+    /// Further endpoints, which serve the same streams as `wss_endpoint` (e.g. `wss://data-stream.binance.vision/ws/`).
+    /// Every stream connects to the fastest of them and fails over to another one, when its endpoint degrades
+    #[serde(default)]
+    pub binance_wss_alternate_endpoints: Vec<String>,
+    /// Binance WebSocket API endpoint, which serves the snapshots with `snapshot_source: ws_api`
+    #[serde(default = "default_binance_ws_api_endpoint")]
+    pub binance_ws_api_endpoint: String,
+    /// Proxy of the REST requests and WebSocket connections, e.g. a corporate egress proxy. Connections are direct if not set
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Custom CA certificates and certificate pins of the REST requests and WebSocket connections
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Offer permessage-deflate compression of the WebSocket messages, which venues supporting it then compress
+    #[serde(default)]
+    pub websocket_compression: bool,
+    #[serde(default)]
+    pub okx_book_channel: OkxBookChannel,
+    #[serde(default)]
+    pub bitfinex_book_precision: BitfinexPrecision,
     pub instrument: String,
+    #[serde(default)]
+    pub symbol_map: SymbolMap,
+    #[serde(default)]
+    pub symbol_discovery: SymbolDiscovery,
     pub max_depth: u64,
     pub connections: u64,
+    #[serde(default = "default_trade_connections")]
+    pub trade_connections: u64,
+    #[serde(default = "default_price_connections")]
+    pub price_connections: u64,
+    #[serde(default)]
+    pub price_source: PriceSource,
+    /// Consume the SBE-encoded depth and trade streams of Binance spot instead of the JSON ones (`sbe` feature)
+    #[serde(default)]
+    pub binance_sbe: Option<BinanceSbe>,
+    #[serde(default)]
+    pub agg_trade_connections: u64,
     pub reconnect_timeout: u64,
     pub snapshot_update_interval: u64,
+    #[serde(default)]
+    pub snapshot_limit: Option<u64>,
+    #[serde(default)]
+    pub snapshot_source: SnapshotSource,
+    #[serde(default = "default_partial_depth_levels")]
+    pub partial_depth_levels: u64,
+    #[serde(default = "default_capture_dir")]
+    pub capture_dir: String,
+    #[serde(default)]
+    pub tape_compression: Option<TapeCompression>,
+    #[serde(default)]
+    pub upload: Option<CaptureUpload>,
+    #[serde(default)]
+    pub retention: Option<Retention>,
+    #[serde(default)]
+    pub alerting: Option<Alerting>,
+    /// Scripts, which filter, modify or enrich the events before they reach the consumers (`scripting` feature)
+    #[serde(default)]
+    pub scripts: Vec<EventScript>,
+    /// Synthetic market data served by `mdc sim`. The defaults are used if not set
+    #[serde(default)]
+    pub simulator: Option<SimulationSettings>,
+    #[serde(default = "default_symbol_metadata_cache")]
+    pub symbol_metadata_cache: String,
+    #[serde(default = "default_symbol_metadata_ttl")]
+    pub symbol_metadata_ttl: u64,
+    #[serde(default)]
+    pub rollup_intervals: Vec<u64>,
+    #[serde(default = "default_rollup_imbalance_depth")]
+    pub rollup_imbalance_depth: usize,
+    #[serde(default)]
+    pub book_metrics: bool,
+    #[serde(default = "default_book_metrics_depth")]
+    pub book_metrics_depth: usize,
+    #[serde(default)]
+    pub trade_flow_window: u64,
+    #[serde(default = "default_trade_flow_interval")]
+    pub trade_flow_interval: u64,
+    #[serde(default)]
+    pub volatility_interval: u64,
+    #[serde(default = "default_volatility_window")]
+    pub volatility_window: u64,
+    #[serde(default)]
+    pub anomaly_detection: Option<AnomalySettings>,
+    #[serde(default = "default_health_window")]
+    pub health_window: u64,
+    #[serde(default = "default_health_latency_threshold")]
+    pub health_latency_threshold: u64,
+    #[serde(default = "default_health_min_score")]
+    pub health_min_score: f64,
+    #[serde(default = "default_min_free_space_mb")]
+    pub min_free_space_mb: u64,
+    #[serde(default = "default_resume_free_space_mb")]
+    pub resume_free_space_mb: u64,
+    #[serde(default = "default_disk_check_interval")]
+    pub disk_check_interval: u64,
+    #[serde(default)]
+    pub warn_free_space_mb: u64,
+    #[serde(default)]
+    pub cleanup_free_space_mb: u64,
+    #[serde(default = "default_dispatcher_buffer_size")]
+    pub dispatcher_buffer_size: usize,
+    #[serde(default = "default_dispatcher_buffer_max_age")]
+    pub dispatcher_buffer_max_age: u64,
+    #[serde(default = "default_output_depth")]
+    pub output_depth: usize,
+    #[serde(default)]
+    pub book_max_depth: usize,
+    #[serde(default)]
+    pub book_price_band: f64,
+    #[serde(default)]
+    pub output_buckets: Option<PriceBuckets>,
+    #[serde(default = "default_log_book_depth")]
+    pub log_book_depth: usize,
+    #[serde(default = "default_log_book_interval")]
+    pub log_book_interval: u64,
+    #[serde(default)]
+    pub fast_output: Option<String>,
+    #[serde(default)]
+    pub durable_output: bool,
+    #[serde(default)]
+    pub trade_sampling: bool,
+    #[serde(default = "default_trade_sampling_depth")]
+    pub trade_sampling_depth: usize,
+    #[serde(default = "default_trade_sampling_baseline")]
+    pub trade_sampling_baseline: u64,
+    #[serde(default)]
+    pub level_changes: bool,
+    #[serde(default)]
+    pub level_change_filter: LevelChangeFilter,
+    #[serde(default)]
+    pub book_deltas: bool,
+    #[serde(default = "default_book_delta_snapshot_interval")]
+    pub book_delta_snapshot_interval: u64,
+    #[serde(default)]
+    pub grpc_listen: Option<String>,
+    #[serde(default)]
+    pub rest_listen: Option<String>,
+    #[serde(default)]
+    pub published_book_depth: Option<usize>,
+    #[serde(default)]
+    pub dashboard: bool,
+    #[serde(default = "default_ready_max_message_age")]
+    pub ready_max_message_age: u64,
+    #[serde(default)]
+    pub book_validation_interval: u64,
+    #[serde(default)]
+    pub book_validation_resync: bool,
+    #[serde(default)]
+    pub book_validation_source: SnapshotSource,
+    #[serde(default)]
+    pub checkpoint_interval: u64,
+    #[serde(default = "default_checkpoint_max_age")]
+    pub checkpoint_max_age: u64,
+    #[serde(default)]
+    pub postgres_url: Option<String>,
+    #[serde(default = "default_postgres_flush_interval")]
+    pub postgres_flush_interval: u64,
+    #[serde(default = "default_postgres_batch_size")]
+    pub postgres_batch_size: usize,
+    #[serde(default = "default_postgres_snapshot_interval")]
+    pub postgres_snapshot_interval: u64,
+    #[serde(default)]
+    pub event_feed_listen: Option<String>,
+    #[serde(default)]
+    pub kline_intervals: Vec<KlineInterval>,
+    #[serde(default)]
+    pub ticker: bool,
+    #[serde(default)]
+    pub mini_ticker: bool,
+    #[serde(default)]
+    pub mark_price: bool,
+    #[serde(default)]
+    pub liquidations: bool,
+    #[serde(default)]
+    pub partial_book: bool,
+    #[serde(default = "default_partial_book_interval")]
+    pub partial_book_interval: u64,
+    #[serde(default)]
+    pub combined_streams: bool,
+    #[serde(default)]
+    pub stall_timeout: u64,
+    /// Pings, which the stream connections send, for venues which require the client to ping
+    #[serde(default)]
+    pub keepalive: Option<KeepaliveConfig>,
+    #[serde(default = "default_session_lifetime")]
+    pub session_lifetime: u64,
+    #[serde(default = "default_rest_weight_budget")]
+    pub rest_weight_budget: u64,
+    #[serde(default = "default_clock_check_interval")]
+    pub clock_check_interval: u64,
+    #[serde(default)]
+    pub task_restart_policy: RestartPolicy,
+    #[serde(default)]
+    pub channel_policies: ChannelPolicies,
+    #[serde(default)]
+    pub arbitrage_threshold_bps: Option<f64>,
 }
 
-/// Parses a YAML string into a `Config` struct.
+impl Config {
+    /// The canonical identifier of the instrument, which downstream sinks see (see `SymbolMap`)
+    pub fn canonical_symbol(&self) -> &str {
+        self.symbol_map.canonical(self.exchange, &self.instrument).unwrap_or(&self.instrument)
+    }
+
+    /// Levels per side, which the maintained book keeps, if it is pruned
+    ///
+    /// The book is pruned to `book_max_depth` and, if it is seeded from the partial depth stream, to
+    /// `partial_depth_levels`, since the levels beyond the seeded ones are unknown and would go stale.
+    /// `book_max_depth` is raised to the levels covered by the checksums of the venue, which a shallower book never matches
+    pub fn kept_depth(&self) -> Option<u64> {
+        let checksum_depth = match self.exchange {
+            Exchange::Okx => OKX_CHECKSUM_DEPTH,
+            Exchange::Bitfinex => BITFINEX_CHECKSUM_DEPTH,
+            _ => 0,
+        };
+        let max_depth = (self.book_max_depth > 0).then_some(self.book_max_depth.max(checksum_depth) as u64);
+        let seeded_depth = (self.snapshot_source == SnapshotSource::PartialStream).then_some(self.partial_depth_levels);
+        [max_depth, seeded_depth].into_iter().flatten().min()
+    }
+
+    /// Best levels per side of the book snapshots published after each update, by default the most levels any
+    /// consumer of the snapshots reads
+    pub fn published_depth(&self) -> usize {
+        self.published_book_depth.unwrap_or_else(|| {
+            [
+                self.output_depth,
+                self.log_book_depth,
+                self.trade_sampling_depth,
+                self.rollup_imbalance_depth,
+                self.book_metrics_depth,
+            ].into_iter().max().unwrap_or_default()
+        })
+    }
+
+    /// The proxy, TLS and compression settings of the outgoing connections
+    pub fn transport(&self) -> Transport {
+        Transport::new(self.proxy.clone(), self.tls.clone(), self.websocket_compression)
+    }
+}
+
+fn default_partial_depth_levels() -> u64 {
+    20
+}
+
+fn default_binance_ws_api_endpoint() -> String {
+    "wss://ws-api.binance.com:443/ws-api/v3".to_string()
+}
+
+fn default_partial_book_interval() -> u64 {
+    1000
+}
+
+fn default_trade_connections() -> u64 {
+    1
+}
+
+fn default_price_connections() -> u64 {
+    1
+}
+
+fn default_capture_dir() -> String {
+    "capture".to_string()
+}
+
+fn default_symbol_metadata_cache() -> String {
+    "capture/symbols.json".to_string()
+}
+
+fn default_symbol_metadata_ttl() -> u64 {
+    24 * 60 * 60 * 1000
+}
+
+fn default_rollup_imbalance_depth() -> usize {
+    5
+}
+
+fn default_book_metrics_depth() -> usize {
+    5
+}
+
+fn default_trade_flow_interval() -> u64 {
+    1000
+}
+
+fn default_volatility_window() -> u64 {
+    300_000
+}
+
+fn default_ready_max_message_age() -> u64 {
+    30000
+}
+
+fn default_checkpoint_max_age() -> u64 {
+    60_000
+}
+
+fn default_session_lifetime() -> u64 {
+    // Binance closes WebSocket connections after 24 hours
+    82_800_000
+}
+
+fn default_rest_weight_budget() -> u64 {
+    1200
+}
+
+fn default_clock_check_interval() -> u64 {
+    60_000
+}
+
+fn default_health_window() -> u64 {
+    60_000
+}
+
+fn default_health_latency_threshold() -> u64 {
+    1000
+}
+
+fn default_health_min_score() -> f64 {
+    0.5
+}
+
+fn default_min_free_space_mb() -> u64 {
+    1024
+}
+
+fn default_resume_free_space_mb() -> u64 {
+    2048
+}
+
+fn default_disk_check_interval() -> u64 {
+    5000
+}
+
+fn default_dispatcher_buffer_size() -> usize {
+    10_000
+}
+
+fn default_dispatcher_buffer_max_age() -> u64 {
+    30_000
+}
+
+fn default_output_depth() -> usize {
+    20
+}
+
+fn default_log_book_depth() -> usize {
+    10
+}
+
+fn default_log_book_interval() -> u64 {
+    1000
+}
+
+fn default_trade_sampling_depth() -> usize {
+    10
+}
+
+fn default_trade_sampling_baseline() -> u64 {
+    60_000
+}
+
+fn default_book_delta_snapshot_interval() -> u64 {
+    60_000
+}
+
+fn default_postgres_flush_interval() -> u64 {
+    1000
+}
+
+fn default_postgres_batch_size() -> usize {
+    10_000
+}
+
+fn default_postgres_snapshot_interval() -> u64 {
+    60_000
+}
+
+/// Expand a pipeline definition, which lists several `instruments`, into one definition per instrument
+fn expand_instruments(mut pipeline: Mapping) -> Result<Vec<Mapping>> {
+    let Some(instruments) = pipeline.remove("instruments") else {
+        return Ok(vec![pipeline]);
+    };
+
+    let Value::Sequence(instruments) = instruments else {
+        return Err(anyhow!("'instruments' must be a list of instruments"));
+    };
+
+    Ok(instruments
+        .into_iter()
+        .map(|instrument| {
+            let mut expanded = pipeline.clone();
+            expanded.insert(Value::from("instrument"), instrument);
+            expanded
+        })
+        .collect())
+}
+
+/// Check that the pipelines can run in the same process, i.e. they don't share instruments or listen addresses
+pub fn check_pipelines(pipelines: &[Config]) -> Result<()> {
+    if pipelines.is_empty() {
+        return Err(anyhow!("No pipelines are configured"));
+    }
+
+    for (index, pipeline) in pipelines.iter().enumerate() {
+        for other in &pipelines[..index] {
+            if pipeline.exchange == other.exchange && pipeline.instrument == other.instrument {
+                return Err(anyhow!("Instrument '{}' is captured by several pipelines", pipeline.instrument));
+            }
+
+            let shared = [
+                (&pipeline.grpc_listen, &other.grpc_listen),
+                (&pipeline.rest_listen, &other.rest_listen),
+                (&pipeline.event_feed_listen, &other.event_feed_listen),
+                (&pipeline.fast_output, &other.fast_output),
+            ];
+            if let Some((Some(value), _)) = shared.iter().find(|(value, other)| value.is_some() && value == other) {
+                return Err(anyhow!("'{}' is used by several pipelines", value));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a YAML string into a list of pipeline configurations.
+///
+/// The document is either a single pipeline or a `pipelines` list, optionally with `defaults` shared by all pipelines.
+/// A pipeline may list several `instruments` instead of a single `instrument`, which defines one pipeline per instrument.
 ///
 /// # Arguments
 /// * `yaml_data` - A string containing YAML-formatted configuration data
 ///
 /// # Returns
-/// * `Result<Config>` - The parsed configuration if successful, or an error if parsing fails
+/// * `Result<Vec<Config>>` - The parsed pipelines if successful, or an error if parsing fails
 ///
 /// # Errors
-/// Returns an error if the YAML data is invalid or missing required fields
-pub fn load_config_from_yaml_str(yaml_data: &str) -> Result<Config> {
-    let config: Config = serde_yaml::from_str(yaml_data)
-        .context("Failed to deserialize configuration from YAML")?;
-    Ok(config)
+/// Returns an error if the YAML data is invalid, missing required fields
+/// or the pipelines can't run in the same process
+pub fn load_pipelines_from_yaml_str(yaml_data: &str) -> Result<Vec<Config>> {
+    let document: Value = serde_yaml::from_str(yaml_data)
+        .context("Failed to parse configuration YAML")?;
+
+    let Value::Mapping(mut document) = document else {
+        return Err(anyhow!("Configuration must be a YAML mapping"));
+    };
+
+    let definitions = match document.remove("pipelines") {
+        None => vec![document],
+        Some(Value::Sequence(pipelines)) => {
+            let defaults = match document.remove("defaults") {
+                None => Mapping::new(),
+                Some(Value::Mapping(defaults)) => defaults,
+                Some(_) => return Err(anyhow!("'defaults' must be a YAML mapping")),
+            };
+
+            let defaults = rename_legacy_keys(defaults);
+            pipelines
+                .into_iter()
+                .map(|pipeline| match pipeline {
+                    Value::Mapping(pipeline) => {
+                        let mut merged = defaults.clone();
+                        merged.extend(rename_legacy_keys(pipeline));
+                        Ok(merged)
+                    }
+                    _ => Err(anyhow!("Every pipeline must be a YAML mapping")),
+                })
+                .collect::<Result<_>>()?
+        }
+        Some(_) => return Err(anyhow!("'pipelines' must be a list of pipelines")),
+    };
+
+    let mut pipelines = Vec::new();
+    for (index, definition) in definitions.into_iter().enumerate() {
+        for definition in expand_instruments(definition)? {
+            let mut config: Config = serde_yaml::from_value(Value::Mapping(definition))
+                .with_context(|| format!("Failed to deserialize configuration of pipeline '{}' from YAML", index))?;
+            config.symbol_map.check()
+                .with_context(|| format!("Invalid symbol map of pipeline '{}'", index))?;
+            if let Some(proxy) = &config.proxy {
+                proxy.check().with_context(|| format!("Invalid proxy of pipeline '{}'", index))?;
+            }
+            if let Some(tls) = &config.tls {
+                tls.check().with_context(|| format!("Invalid TLS configuration of pipeline '{}'", index))?;
+            }
+            if let Some(keepalive) = &config.keepalive {
+                keepalive.check().with_context(|| format!("Invalid keepalive of pipeline '{}'", index))?;
+            }
+
+            // The instrument may be given by its canonical symbol
+            if let Some(symbol) = config.symbol_map.venue_symbol(&config.instrument, config.exchange) {
+                config.instrument = symbol.to_string();
+            }
+            pipelines.push(config);
+        }
+    }
+
+    check_pipelines(&pipelines)?;
+    Ok(pipelines)
+}
+
+/// Rename the parameters, which have been renamed since, so a pipeline may override a default given by the former name
+fn rename_legacy_keys(mapping: Mapping) -> Mapping {
+    mapping
+        .into_iter()
+        .map(|(key, value)| match key.as_str() {
+            Some("binance_rest_endpoint") => (Value::from("rest_endpoint"), value),
+            Some("binance_wss_endpoint") => (Value::from("wss_endpoint"), value),
+            _ => (key, value),
+        })
+        .collect()
 }
 
-/// Loads a configuration from a YAML file at the specified path.
+/// Loads the pipeline configurations from a YAML file at the specified path.
 ///
 /// # Arguments
 /// * `path` - Path to the YAML configuration file
 ///
 /// # Returns
-/// * `Result<Config>` - The loaded configuration if successful, or an error if loading fails
+/// * `Result<Vec<Config>>` - The loaded pipelines if successful, or an error if loading fails
 ///
 /// # Errors
 /// Returns an error if:
 /// - The file cannot be read
 /// - The file content is not valid YAML
 /// - The YAML data is missing required fields
-pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config> {
+/// - The pipelines can't run in the same process
+pub fn load_pipelines<P: AsRef<Path>>(path: P) -> Result<Vec<Config>> {
     let data = fs::read_to_string(&path)
         .with_context(|| format!("Failed to read configuration from: {:?}", path.as_ref()))?;
-    let config = load_config_from_yaml_str(&data)?;
-    Ok(config)
+    load_pipelines_from_yaml_str(&data)
+}
+
+/// Print the pipelines as YAML, which can be loaded back
+///
+/// A single pipeline is printed as a single configuration, several pipelines as a `pipelines` list
+/// with every parameter resolved, i.e. with the defaults and the instruments expanded
+pub fn pipelines_to_yaml(pipelines: &[Config]) -> Result<String> {
+    #[derive(Serialize)]
+    struct Pipelines<'a> {
+        pipelines: &'a [Config],
+    }
+
+    let yaml = match pipelines {
+        [config] => serde_yaml::to_string(config),
+        _ => serde_yaml::to_string(&Pipelines { pipelines }),
+    };
+
+    yaml.context("Failed to serialize configuration to YAML")
+}
+
+/// Select the pipeline, which captures the instrument (by its venue or canonical symbol), or the first one
+pub fn select_pipeline(pipelines: Vec<Config>, instrument: Option<&str>) -> Config {
+    let position = instrument
+        .and_then(|instrument| pipelines.iter().position(|pipeline| {
+            pipeline.instrument == instrument || pipeline.canonical_symbol() == instrument
+        }))
+        .unwrap_or(0);
+
+    pipelines.into_iter().nth(position).expect("At least one pipeline is configured")
 }
 
 #[cfg(test)]
@@ -60,8 +611,8 @@ mod tests {
     #[test]
     fn test_load_config_from_yaml_str() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let test_content = r#"
-binance_rest_endpoint: "https://api.example.com"
-binance_wss_endpoint: "wss://stream.example.com"
+rest_endpoint: "https://api.example.com"
+wss_endpoint: "wss://stream.example.com"
 instrument: "BTCUSDT"
 max_depth: 10
 connections: 3
@@ -69,15 +620,306 @@ reconnect_timeout: 5000
 snapshot_update_interval: 30000
 "#;
 
-        let config = load_config_from_yaml_str(test_content)?;
+        let mut pipelines = load_pipelines_from_yaml_str(test_content)?;
+        assert_eq!(pipelines.len(), 1);
+        let config = pipelines.remove(0);
 
-        assert_eq!(config.binance_rest_endpoint, "https://api.example.com");
-        assert_eq!(config.binance_wss_endpoint, "wss://stream.example.com");
+        assert_eq!(config.exchange, Exchange::Binance);
+        assert_eq!(config.rest_endpoint, "https://api.example.com");
+        assert_eq!(config.wss_endpoint, "wss://stream.example.com");
+        assert!(config.binance_wss_alternate_endpoints.is_empty());
+        assert!(!config.websocket_compression);
+        assert_eq!(config.binance_ws_api_endpoint, "wss://ws-api.binance.com:443/ws-api/v3");
+        assert_eq!(config.okx_book_channel, OkxBookChannel::Books);
+        assert_eq!(config.bitfinex_book_precision, BitfinexPrecision::P0);
         assert_eq!(config.instrument, "BTCUSDT");
+        assert_eq!(config.symbol_map, SymbolMap::default());
+        assert_eq!(config.canonical_symbol(), "BTCUSDT");
         assert_eq!(config.max_depth, 10);
         assert_eq!(config.connections, 3);
+        assert_eq!(config.trade_connections, 1);
+        assert_eq!(config.price_connections, 1);
+        assert_eq!(config.agg_trade_connections, 0);
         assert_eq!(config.reconnect_timeout, 5000);
         assert_eq!(config.snapshot_update_interval, 30000);
+        assert_eq!(config.snapshot_limit, None);
+        assert_eq!(config.snapshot_source, SnapshotSource::Rest);
+        assert_eq!(config.partial_depth_levels, 20);
+        assert_eq!(config.capture_dir, "capture");
+        assert_eq!(config.tape_compression, None);
+        assert_eq!(config.upload, None);
+        assert_eq!(config.retention, None);
+        assert_eq!(config.alerting, None);
+        assert!(config.scripts.is_empty());
+        assert_eq!(config.simulator, None);
+        assert_eq!(config.symbol_metadata_cache, "capture/symbols.json");
+        assert_eq!(config.symbol_metadata_ttl, 86400000);
+        assert!(config.rollup_intervals.is_empty());
+        assert_eq!(config.rollup_imbalance_depth, 5);
+        assert!(!config.book_metrics);
+        assert_eq!(config.book_metrics_depth, 5);
+        assert_eq!(config.trade_flow_window, 0);
+        assert_eq!(config.trade_flow_interval, 1000);
+        assert_eq!(config.volatility_interval, 0);
+        assert_eq!(config.volatility_window, 300000);
+        assert_eq!(config.anomaly_detection, None);
+        assert_eq!(config.health_window, 60000);
+        assert_eq!(config.health_latency_threshold, 1000);
+        assert_eq!(config.health_min_score, 0.5);
+        assert_eq!(config.min_free_space_mb, 1024);
+        assert_eq!(config.resume_free_space_mb, 2048);
+        assert_eq!(config.disk_check_interval, 5000);
+        assert_eq!(config.warn_free_space_mb, 0);
+        assert_eq!(config.cleanup_free_space_mb, 0);
+        assert_eq!(config.dispatcher_buffer_size, 10000);
+        assert_eq!(config.dispatcher_buffer_max_age, 30000);
+        assert_eq!(config.output_depth, 20);
+        assert_eq!(config.book_max_depth, 0);
+        assert_eq!(config.book_price_band, 0.0);
+        assert_eq!(config.output_buckets, None);
+        assert_eq!(config.log_book_depth, 10);
+        assert_eq!(config.log_book_interval, 1000);
+        assert_eq!(config.fast_output, None);
+        assert!(!config.durable_output);
+        assert!(!config.trade_sampling);
+        assert_eq!(config.trade_sampling_depth, 10);
+        assert_eq!(config.trade_sampling_baseline, 60000);
+        assert!(!config.level_changes);
+        assert_eq!(config.level_change_filter, LevelChangeFilter::default());
+        assert!(!config.book_deltas);
+        assert_eq!(config.book_delta_snapshot_interval, 60000);
+        assert_eq!(config.grpc_listen, None);
+        assert_eq!(config.rest_listen, None);
+        assert!(!config.dashboard);
+        assert_eq!(config.ready_max_message_age, 30000);
+        assert_eq!(config.published_book_depth, None);
+        assert_eq!(config.published_depth(), 20);
+        assert_eq!(config.book_validation_interval, 0);
+        assert!(!config.book_validation_resync);
+        assert_eq!(config.book_validation_source, SnapshotSource::Rest);
+        assert_eq!(config.checkpoint_interval, 0);
+        assert_eq!(config.checkpoint_max_age, 60000);
+        assert_eq!(config.postgres_url, None);
+        assert_eq!(config.postgres_flush_interval, 1000);
+        assert_eq!(config.postgres_batch_size, 10000);
+        assert_eq!(config.postgres_snapshot_interval, 60000);
+        assert_eq!(config.event_feed_listen, None);
+        assert!(config.kline_intervals.is_empty());
+        assert!(!config.ticker);
+        assert!(!config.mini_ticker);
+        assert!(!config.mark_price);
+        assert!(!config.liquidations);
+        assert!(!config.partial_book);
+        assert_eq!(config.partial_book_interval, 1000);
+        assert!(!config.combined_streams);
+        assert_eq!(config.stall_timeout, 0);
+        assert_eq!(config.keepalive, None);
+        assert_eq!(config.session_lifetime, 82_800_000);
+        assert_eq!(config.rest_weight_budget, 1200);
+        assert_eq!(config.clock_check_interval, 60_000);
+        assert_eq!(config.task_restart_policy, RestartPolicy::Backoff);
+        assert_eq!(config.channel_policies, ChannelPolicies::default());
+        assert_eq!(config.symbol_discovery, SymbolDiscovery::default());
+        assert_eq!(config.price_source, PriceSource::Symbol);
+        assert_eq!(config.binance_sbe, None);
+        assert_eq!(config.proxy, None);
+        assert_eq!(config.tls, None);
+        assert_eq!(config.arbitrage_threshold_bps, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_pipelines_from_yaml_str() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let test_content = r#"
+defaults:
+  rest_endpoint: "https://api.example.com"
+  wss_endpoint: "wss://stream.example.com"
+  max_depth: 10
+  connections: 3
+  reconnect_timeout: 5000
+  snapshot_update_interval: 30000
+pipelines:
+  - instruments: ["BTCUSDT", "ETHUSDT"]
+    trade_connections: 2
+  - exchange: binance_futures
+    rest_endpoint: "https://fapi.example.com"
+    instrument: "BTCUSDT"
+    price_connections: 0
+    rest_listen: "127.0.0.1:8080"
+"#;
+
+        let pipelines = load_pipelines_from_yaml_str(test_content)?;
+
+        assert_eq!(pipelines.len(), 3);
+        assert_eq!(pipelines[0].instrument, "BTCUSDT");
+        assert_eq!(pipelines[1].instrument, "ETHUSDT");
+        assert_eq!(pipelines[1].trade_connections, 2);
+        assert_eq!(pipelines[1].rest_endpoint, "https://api.example.com");
+        assert_eq!(pipelines[2].exchange, Exchange::BinanceFutures);
+        assert_eq!(pipelines[2].rest_endpoint, "https://fapi.example.com");
+        assert_eq!(pipelines[2].wss_endpoint, "wss://stream.example.com");
+        assert_eq!(pipelines[2].price_connections, 0);
+        assert_eq!(pipelines[2].trade_connections, 1);
+
+        let resolved = load_pipelines_from_yaml_str(&pipelines_to_yaml(&pipelines)?)?;
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(resolved[1].instrument, "ETHUSDT");
+        assert_eq!(resolved[2].exchange, Exchange::BinanceFutures);
+        assert_eq!(resolved[2].rest_listen, Some("127.0.0.1:8080".to_string()));
+
+        assert_eq!(select_pipeline(pipelines, Some("ETHUSDT")).instrument, "ETHUSDT");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_former_endpoint_names() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let test_content = r#"
+defaults:
+  binance_rest_endpoint: "https://api.example.com"
+  binance_wss_endpoint: "wss://stream.example.com"
+  max_depth: 10
+  connections: 3
+  reconnect_timeout: 5000
+  snapshot_update_interval: 30000
+pipelines:
+  - instrument: "BTCUSDT"
+  - instrument: "BTC-USDT"
+    exchange: okx
+    rest_endpoint: "https://www.okx.com/api/v5/"
+    wss_endpoint: "wss://ws.okx.com:8443/ws/v5/public"
+"#;
+
+        let pipelines = load_pipelines_from_yaml_str(test_content)?;
+
+        assert_eq!(pipelines[0].rest_endpoint, "https://api.example.com");
+        assert_eq!(pipelines[0].wss_endpoint, "wss://stream.example.com");
+        assert_eq!(pipelines[1].rest_endpoint, "https://www.okx.com/api/v5/");
+        assert_eq!(pipelines[1].wss_endpoint, "wss://ws.okx.com:8443/ws/v5/public");
+
+        let single = r#"
+binance_rest_endpoint: "https://api.example.com"
+binance_wss_endpoint: "wss://stream.example.com"
+instrument: "BTCUSDT"
+max_depth: 10
+connections: 3
+reconnect_timeout: 5000
+snapshot_update_interval: 30000
+"#;
+        let pipelines = load_pipelines_from_yaml_str(single)?;
+        assert_eq!(pipelines[0].rest_endpoint, "https://api.example.com");
+        assert_eq!(pipelines[0].wss_endpoint, "wss://stream.example.com");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_symbols_of_pipelines() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let test_content = r#"
+defaults:
+  rest_endpoint: "https://api.example.com"
+  wss_endpoint: "wss://stream.example.com"
+  max_depth: 10
+  connections: 3
+  reconnect_timeout: 5000
+  snapshot_update_interval: 30000
+  symbol_map:
+    BTC-USD:
+      binance: "BTCUSDT"
+      okx: "BTC-USDT"
+pipelines:
+  - instruments: ["BTCUSDT", "ETHUSDT"]
+  - exchange: okx
+    instrument: "BTC-USD"
+"#;
+
+        let pipelines = load_pipelines_from_yaml_str(test_content)?;
+
+        assert_eq!(pipelines[0].canonical_symbol(), "BTC-USD");
+        assert_eq!(pipelines[1].canonical_symbol(), "ETHUSDT");
+        assert_eq!(pipelines[2].instrument, "BTC-USDT");
+        assert_eq!(pipelines[2].canonical_symbol(), "BTC-USD");
+        assert_eq!(select_pipeline(pipelines, Some("BTC-USD")).exchange, Exchange::Binance);
+
+        let ambiguous = test_content.replace(r#"okx: "BTC-USDT""#, "okx: \"BTC-USDT\"\n    BTC-USDT:\n      binance: \"BTCUSDT\"");
+        assert!(load_pipelines_from_yaml_str(&ambiguous).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicting_pipelines_are_rejected() {
+        let pipelines = |second: &str| format!(r#"
+defaults:
+  rest_endpoint: "https://api.example.com"
+  wss_endpoint: "wss://stream.example.com"
+  max_depth: 10
+  connections: 3
+  reconnect_timeout: 5000
+  snapshot_update_interval: 30000
+pipelines:
+  - instrument: "BTCUSDT"
+    grpc_listen: "127.0.0.1:50051"
+  - {}
+"#, second);
+
+        assert!(load_pipelines_from_yaml_str(&pipelines(r#"instrument: "ETHUSDT""#)).is_ok());
+        assert!(load_pipelines_from_yaml_str(&pipelines(r#"instrument: "BTCUSDT""#)).is_err());
+        assert!(load_pipelines_from_yaml_str(&pipelines(r#"{instrument: "ETHUSDT", grpc_listen: "127.0.0.1:50051"}"#)).is_err());
+        assert!(load_pipelines_from_yaml_str(&pipelines("{max_depth: 10}")).is_err());
+        assert!(load_pipelines_from_yaml_str("pipelines: []").is_err());
+    }
+
+    #[test]
+    fn test_kept_depth_of_partially_seeded_book() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let test_content = r#"
+rest_endpoint: "https://api.example.com"
+wss_endpoint: "wss://stream.example.com"
+instrument: "BTCUSDT"
+max_depth: 1000
+connections: 3
+reconnect_timeout: 5000
+snapshot_update_interval: 30000
+"#;
+
+        let mut config = load_pipelines_from_yaml_str(test_content)?.remove(0);
+        assert_eq!(config.kept_depth(), None);
+
+        config.snapshot_source = SnapshotSource::PartialStream;
+        assert_eq!(config.kept_depth(), Some(20));
+
+        config.book_max_depth = 10;
+        assert_eq!(config.kept_depth(), Some(10));
+
+        config.snapshot_source = SnapshotSource::Rest;
+        assert_eq!(config.kept_depth(), Some(10));
+
+        // The checksums of OKX cover the best 25 levels
+        config.exchange = Exchange::Okx;
+        assert_eq!(config.kept_depth(), Some(25));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_published_depth_covers_consumers() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let test_content = r#"
+rest_endpoint: "https://api.example.com"
+wss_endpoint: "wss://stream.example.com"
+instrument: "BTCUSDT"
+max_depth: 1000
+connections: 3
+reconnect_timeout: 5000
+snapshot_update_interval: 30000
+log_book_depth: 50
+"#;
+
+        let mut config = load_pipelines_from_yaml_str(test_content)?.remove(0);
+        assert_eq!(config.published_depth(), 50);
+
+        config.published_book_depth = Some(500);
+        assert_eq!(config.published_depth(), 500);
 
         Ok(())
     }
@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use crate::mdc_core::sequencing::{SequencingRules, SequencingStrategy};
+use crate::mdc_core::models::MarketEvent;
+
+/// Number of gaps within a window, at which the gap penalty is maximal
+const MAX_GAPS_PER_WINDOW: f64 = 5.0;
+
+/// Number of reconnects within the reconnect history, at which the reconnect penalty is maximal
+const MAX_RECENT_RECONNECTS: f64 = 3.0;
+
+/// Number of windows, over which reconnects are remembered
+const RECONNECT_HISTORY_WINDOWS: i64 = 10;
+
+/// Number of consecutive unhealthy windows, after which a connection is considered chronically unhealthy
+const CHRONIC_UNHEALTHY_WINDOWS: u32 = 3;
+
+/// Thresholds of connection health evaluation
+#[derive(Debug, Clone, Copy)]
+pub struct HealthPolicy {
+    /// Evaluation window in milliseconds
+    pub window: u64,
+    /// 95th percentile latency in milliseconds, above which the connection is penalized
+    pub latency_threshold: u64,
+    /// Score, below which the connection is considered unhealthy. 0 disables cycling
+    pub min_score: f64,
+}
+
+/// Result of a single window evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub score: f64,
+    pub latency_p95: Option<i64>,
+    pub gaps: u64,
+    pub recent_reconnects: usize,
+}
+
+/// ConnectionHealth scores a single depth connection by its latency, the gaps in its own update sequence
+/// and its reconnect history
+///
+/// The score is in range [0;1], where 1 is perfectly healthy. A connection, which stays below the minimum score
+/// for several consecutive windows, is chronically unhealthy and should be cycled
+pub struct ConnectionHealth {
+    policy: HealthPolicy,
+    sequencing_rules: SequencingRules,
+    latencies: Vec<i64>,
+    gaps: u64,
+    last_update_id: Option<u64>,
+    reconnects: VecDeque<i64>,
+    unhealthy_windows: u32,
+}
+
+impl ConnectionHealth {
+    /// Create a new ConnectionHealth
+    ///
+    /// # Arguments
+    /// * `policy` - Thresholds of health evaluation
+    /// * `sequencing_rules` - Venue-specific rules, used to detect gaps in the connection's update sequence
+    pub fn new(policy: HealthPolicy, sequencing_rules: SequencingRules) -> Self {
+        Self {
+            policy,
+            sequencing_rules,
+            latencies: Vec::new(),
+            gaps: 0,
+            last_update_id: None,
+            reconnects: VecDeque::new(),
+            unhealthy_windows: 0,
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.policy.window
+    }
+
+    /// Account an event received over the connection
+    ///
+    /// # Arguments
+    /// * `event` - The received event. Only depth updates are accounted
+    /// * `now` - Local receive time in milliseconds since epoch
+    pub fn observe(&mut self, event: &MarketEvent, now: i64) {
+        let MarketEvent::DepthUpdate(update) = event else {
+            return;
+        };
+
+        self.latencies.push(now - update.event_time as i64);
+
+        if let Some(last_update_id) = self.last_update_id {
+            if !self.sequencing_rules.follows(update, last_update_id) {
+                self.gaps += 1;
+            }
+        }
+
+        self.last_update_id = Some(update.last_update_id);
+    }
+
+    /// Account the end of a session
+    ///
+    /// # Arguments
+    /// * `now` - Time in milliseconds since epoch
+    /// * `cycled` - Whether the session has been ended on purpose, because the connection was unhealthy.
+    ///   Such sessions don't count as reconnects
+    pub fn on_session_end(&mut self, now: i64, cycled: bool) {
+        // The next session starts a new sequence
+        self.last_update_id = None;
+
+        if cycled {
+            self.unhealthy_windows = 0;
+        } else {
+            self.reconnects.push_back(now);
+        }
+    }
+
+    /// Evaluate the health over the last window and start a new one
+    pub fn evaluate(&mut self, now: i64) -> HealthReport {
+        let history_start = now - RECONNECT_HISTORY_WINDOWS * self.policy.window as i64;
+        while self.reconnects.front().is_some_and(|time| *time < history_start) {
+            self.reconnects.pop_front();
+        }
+
+        self.latencies.sort_unstable();
+        let latency_p95 = (!self.latencies.is_empty())
+            .then(|| self.latencies[(self.latencies.len() * 95).div_ceil(100) - 1]);
+
+        let threshold = self.policy.latency_threshold.max(1) as f64;
+        let latency_penalty = latency_p95
+            .map(|latency| ((latency as f64 - threshold) / threshold).clamp(0.0, 1.0))
+            .unwrap_or(0.0);
+        let gap_penalty = (self.gaps as f64 / MAX_GAPS_PER_WINDOW).min(1.0);
+        let reconnect_penalty = (self.reconnects.len() as f64 / MAX_RECENT_RECONNECTS).min(1.0);
+
+        let report = HealthReport {
+            score: 1.0 - (0.4 * latency_penalty + 0.4 * gap_penalty + 0.2 * reconnect_penalty),
+            latency_p95,
+            gaps: self.gaps,
+            recent_reconnects: self.reconnects.len(),
+        };
+
+        if report.score < self.policy.min_score {
+            self.unhealthy_windows += 1;
+        } else {
+            self.unhealthy_windows = 0;
+        }
+
+        self.latencies.clear();
+        self.gaps = 0;
+
+        report
+    }
+
+    /// Whether the connection has been unhealthy for several consecutive windows
+    pub fn is_chronically_unhealthy(&self) -> bool {
+        self.unhealthy_windows >= CHRONIC_UNHEALTHY_WINDOWS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::DepthUpdate;
+
+    fn make_policy() -> HealthPolicy {
+        HealthPolicy { window: 1000, latency_threshold: 100, min_score: 0.7 }
+    }
+
+    fn make_update(first: u64, last: u64, event_time: u64) -> MarketEvent {
+        MarketEvent::DepthUpdate(DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            last_update_id: last,
+            previous_last_update_id: None,
+            bids: vec![],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        })
+    }
+
+    #[test]
+    fn test_healthy_connection() {
+        let mut health = ConnectionHealth::new(make_policy(), SequencingRules::BinanceSpot);
+
+        health.observe(&make_update(1, 5, 1000), 1010);
+        health.observe(&make_update(6, 8, 1100), 1120);
+
+        let report = health.evaluate(2000);
+        assert_eq!(report.score, 1.0);
+        assert_eq!(report.latency_p95, Some(20));
+        assert_eq!(report.gaps, 0);
+        assert!(!health.is_chronically_unhealthy());
+    }
+
+    #[test]
+    fn test_latency_gaps_and_reconnects_reduce_score() {
+        let mut health = ConnectionHealth::new(make_policy(), SequencingRules::BinanceSpot);
+
+        health.observe(&make_update(1, 5, 1000), 1300);
+        health.observe(&make_update(10, 12, 1100), 1400);
+        health.on_session_end(1500, false);
+        // A new session starts a new sequence, so the jump is not a gap
+        health.observe(&make_update(20, 22, 1600), 1900);
+
+        let report = health.evaluate(2000);
+        assert_eq!(report.latency_p95, Some(300));
+        assert_eq!(report.gaps, 1);
+        assert_eq!(report.recent_reconnects, 1);
+        assert!((report.score - (1.0 - 0.4 - 0.4 * 0.2 - 0.2 / 3.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_chronically_unhealthy_connection() {
+        let mut health = ConnectionHealth::new(make_policy(), SequencingRules::BinanceSpot);
+
+        for window in 1..=3 {
+            assert!(!health.is_chronically_unhealthy());
+            health.observe(&make_update(1, 5, 0), window * 1000);
+            health.evaluate(window * 1000);
+        }
+        assert!(health.is_chronically_unhealthy());
+
+        health.on_session_end(4000, true);
+        assert!(!health.is_chronically_unhealthy());
+
+        let report = health.evaluate(5000);
+        assert_eq!(report.recent_reconnects, 0);
+    }
+
+    #[test]
+    fn test_old_reconnects_are_forgotten() {
+        let mut health = ConnectionHealth::new(make_policy(), SequencingRules::BinanceSpot);
+
+        health.on_session_end(1000, false);
+        assert_eq!(health.evaluate(5000).recent_reconnects, 1);
+        assert_eq!(health.evaluate(20000).recent_reconnects, 0);
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::{Html, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::output_tiers::BookFrame;
+
+/// The dashboard page, which renders the messages of the dashboard WebSocket
+const DASHBOARD_PAGE: &str = include_str!("dashboard.html");
+
+/// Interval between two messages to the dashboards in milliseconds. Book updates in between are skipped
+const PUBLISH_INTERVAL: u64 = 200;
+
+/// Number of the latest trades, which a newly connected dashboard receives
+const RECENT_TRADES: usize = 50;
+
+/// Messages, which a lagging dashboard may fall behind before it skips messages
+const DASHBOARD_BUFFER: usize = 64;
+
+/// A trade shown by the dashboard
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DashboardTrade {
+    /// Trade time in milliseconds since epoch
+    pub time: u64,
+    pub price: f64,
+    pub quantity: f64,
+    /// Side of the aggressor: `buy` or `sell`
+    pub side: &'static str,
+}
+
+/// A message of the dashboard WebSocket
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DashboardMessage {
+    /// The top of the maintained book
+    Book {
+        symbol: String,
+        #[serde(flatten)]
+        frame: BookFrame,
+    },
+    /// Trades since the previous message, the latest last
+    Trades { trades: Vec<DashboardTrade> },
+}
+
+impl DashboardMessage {
+    fn to_text(&self) -> Arc<str> {
+        serde_json::to_string(self).expect("Dashboard message is serializable").into()
+    }
+}
+
+#[derive(Clone)]
+struct DashboardState {
+    latest_book: watch::Receiver<Option<Arc<str>>>,
+    recent_trades: Arc<Mutex<VecDeque<DashboardTrade>>>,
+    messages: broadcast::Sender<Arc<str>>,
+}
+
+/// DashboardPublisher sends the top of the book and the trades of the pipeline to the connected dashboards
+///
+/// The book is sent at most every `PUBLISH_INTERVAL` milliseconds, the trades are sent in batches at the same interval
+pub struct DashboardPublisher {
+    symbol: String,
+    depth: usize,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    latest_book: watch::Sender<Option<Arc<str>>>,
+    recent_trades: Arc<Mutex<VecDeque<DashboardTrade>>>,
+    messages: broadcast::Sender<Arc<str>>,
+}
+
+/// Create the dashboard: a page at `/dashboard`, which shows the live book and the latest trades, and the WebSocket
+/// at `/dashboard/ws`, which streams them
+///
+/// # Arguments
+/// * `symbol` - Symbol shown by the dashboard
+/// * `depth` - Number of top levels per side
+/// * `book_channel` - Receiver for OrderBook messages
+/// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+///
+/// # Returns
+/// The publisher, which has to be run as a separate task, and the router of the dashboard
+pub fn dashboard(
+    symbol: &str,
+    depth: usize,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+) -> (DashboardPublisher, Router) {
+    let (latest_book, latest_book_receiver) = watch::channel(None);
+    let (messages, _) = broadcast::channel(DASHBOARD_BUFFER);
+    let recent_trades = Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_TRADES)));
+
+    let state = DashboardState {
+        latest_book: latest_book_receiver,
+        recent_trades: recent_trades.clone(),
+        messages: messages.clone(),
+    };
+    let router = Router::new()
+        .route("/dashboard", get(|| async { Html(DASHBOARD_PAGE) }))
+        .route("/dashboard/ws", get(connect))
+        .with_state(state);
+
+    let publisher = DashboardPublisher {
+        symbol: symbol.to_string(),
+        depth,
+        book_channel,
+        trade_channel,
+        latest_book,
+        recent_trades,
+        messages,
+    };
+    (publisher, router)
+}
+
+impl DashboardPublisher {
+    fn publish(&self, message: Arc<str>) {
+        // Without connected dashboards the message is dropped
+        let _ = self.messages.send(message);
+    }
+
+    /// Run the DashboardPublisher as an asynchronous task
+    ///
+    /// This method will continuously publish the book and the trades until the book channel is closed
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(PUBLISH_INTERVAL));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut sequence = 0;
+        let mut book = None;
+        let mut trades = Vec::new();
+        let mut trades_open = true;
+
+        loop {
+            tokio::select! {
+                update = self.book_channel.recv() => {
+                    match update {
+                        Some(update) => {
+                            sequence += 1;
+                            book = Some(update);
+                        }
+                        None => break,
+                    }
+                }
+                event = self.trade_channel.recv(), if trades_open => {
+                    match event {
+                        Some(MarketEvent::TradeEvent(trade)) => trades.push(DashboardTrade {
+                            time: trade.trade_time,
+                            price: trade.price,
+                            quantity: trade.quantity,
+                            side: if trade.is_market_maker { "sell" } else { "buy" },
+                        }),
+                        Some(event) => tracing::warn!("Unexpected event in dashboard trade channel: '{}'", event),
+                        None => trades_open = false,
+                    }
+                }
+                _ = ticker.tick() => {
+                    if let Some(book) = book.take() {
+                        let frame = BookFrame::new(sequence, Utc::now().timestamp_millis(), &book, self.depth);
+                        let message = DashboardMessage::Book { symbol: self.symbol.clone(), frame }.to_text();
+                        self.latest_book.send_replace(Some(message.clone()));
+                        self.publish(message);
+                    }
+
+                    if !trades.is_empty() {
+                        let mut recent_trades = self.recent_trades.lock().expect("Recent trades lock is poisoned");
+                        for trade in &trades {
+                            if recent_trades.len() == RECENT_TRADES {
+                                recent_trades.pop_front();
+                            }
+                            recent_trades.push_back(trade.clone());
+                        }
+                        // Published under the lock, so a dashboard connecting meanwhile doesn't receive the trades twice
+                        self.publish(DashboardMessage::Trades { trades: std::mem::take(&mut trades) }.to_text());
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn connect(State(state): State<DashboardState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| serve(socket, state))
+}
+
+/// Send the latest book and trades to a new dashboard, then every message until it disconnects
+async fn serve(mut socket: WebSocket, state: DashboardState) {
+    // Subscribe first, so no message is missed between the initial state and the stream
+    let mut messages = state.messages.subscribe();
+
+    let book = state.latest_book.borrow().clone();
+    let trades: Vec<DashboardTrade> = state.recent_trades.lock().expect("Recent trades lock is poisoned").iter().cloned().collect();
+    let mut initial = Vec::new();
+    initial.extend(book);
+    if !trades.is_empty() {
+        initial.push(DashboardMessage::Trades { trades }.to_text());
+    }
+
+    for message in initial {
+        if socket.send(Message::Text(message.to_string())).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            message = messages.recv() => {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::debug!("Dashboard is lagging. Skipped '{}' messages", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                if socket.send(Message::Text(message.to_string())).await.is_err() {
+                    return;
+                }
+            }
+            received = socket.recv() => {
+                // The dashboard doesn't send anything but pings and the close frame
+                if !matches!(received, Some(Ok(_))) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot, TradeEvent};
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite;
+
+    async fn next_message<S>(socket: &mut S) -> serde_json::Value
+    where
+        S: futures::Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+    {
+        match socket.next().await {
+            Some(Ok(tungstenite::Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+            message => panic!("Unexpected message: {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_streams_book_and_trades() {
+        let (book_sender, book_receiver) = mpsc::channel(10);
+        let (trade_sender, trade_receiver) = mpsc::channel(10);
+        let (publisher, router) = dashboard("BTCUSDT", 1, book_receiver, trade_receiver);
+        tokio::spawn(publisher.run());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let page = reqwest::get(format!("http://{}/dashboard", address)).await.unwrap().text().await.unwrap();
+        assert!(page.contains("/dashboard/ws"));
+
+        let entry = |price: f64| DepthEntry { price: price.into(), quantity: 1.0.into() };
+        book_sender.send(Arc::new(OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![entry(100.0), entry(99.0)],
+            asks: vec![entry(101.0)],
+        }))).await.unwrap();
+        trade_sender.send(MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 1,
+            price: 100.0,
+            quantity: 0.5,
+            trade_time: 1000,
+            is_market_maker: true,
+            ignore: false,
+            received: None,
+        })).await.unwrap();
+
+        // A dashboard connecting later receives the latest book and trades first
+        tokio::time::sleep(Duration::from_millis(PUBLISH_INTERVAL * 2)).await;
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/dashboard/ws", address)).await.unwrap();
+
+        let book = next_message(&mut socket).await;
+        assert_eq!((book["type"].as_str(), book["symbol"].as_str()), (Some("book"), Some("BTCUSDT")));
+        assert_eq!(book["bids"], serde_json::json!([[100.0, 1.0]]));
+        let trades = next_message(&mut socket).await;
+        assert_eq!(trades["trades"], serde_json::json!([{ "time": 1000, "price": 100.0, "quantity": 0.5, "side": "sell" }]));
+    }
+}
@@ -1,183 +1,296 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures::future::BoxFuture;
 use tokio::sync::mpsc;
-use crate::mdc_server::models::{MarketEvent, DepthUpdate, DepthSnapshot};
-use std::collections::BTreeMap;
+use tokio::time::Instant;
+use crate::mdc_core::depth_sequencer::{BufferLimits, DepthSequencer};
+use crate::mdc_core::continuity::{Resync, ResyncSource, SequenceGap};
+use crate::mdc_core::models::{MarketEvent, DepthUpdate, DepthSnapshot};
+use crate::mdc_core::sequencing::{SequencingRules, SequencingStrategy};
+use crate::mdc_server::metrics::{Counter, Gauge, Metrics};
+use crate::mdc_server::task_supervisor::RestartableTask;
 use tracing;
 
 /// DepthEventDispatcher manages the order of depth updates from multiple WebSocket connections
 /// It ensures that updates are processed in the correct order and without duplicates
-pub struct DepthEventDispatcher {
+///
+/// The sequencing itself is done by the exchange-agnostic DepthSequencer, the dispatcher only feeds it
+/// from the input channel, forwards its output and accounts evictions, gaps and resyncs
+pub struct DepthEventDispatcher<S: SequencingStrategy = SequencingRules> {
     input: mpsc::Receiver<MarketEvent>,
     output: mpsc::Sender<MarketEvent>,
-    last_processed_update_id: Option<u64>,
-    buffer: BTreeMap<u64, DepthUpdate>,
+    sequencer: DepthSequencer<S>,
+    limits: BufferLimits,
+    started: Instant,
+    evicted_by_size: Counter,
+    evicted_by_age: Counter,
+    gaps: Counter,
+    resyncs: Counter,
+    buffer_max: Gauge,
+    /// Whether updates are buffered behind a gap in the sequence, so each gap is accounted once
+    in_gap: bool,
+    markers: Option<mpsc::Sender<MarketEvent>>,
+    resume: Option<Resume>,
 }
 
-impl DepthEventDispatcher {
+/// A checkpoint of a previous session, which the sequence is resumed from until the live updates confirm or reject it
+struct Resume {
+    update_id: u64,
+    /// The checkpoint, which is forwarded as a snapshot when the dispatcher starts
+    snapshot: Option<DepthSnapshot>,
+    snapshot_requests: mpsc::Sender<()>,
+}
+
+impl<S: SequencingStrategy> DepthEventDispatcher<S> {
     /// Create a new DepthEventDispatcher
     ///
     /// # Arguments
     /// * `input` - Receiver for MarketEvent messages from multiple connections
     /// * `output` - Sender for filtered MarketEvent messages to the BookProcessor
+    /// * `sequencing` - Venue-specific rules of depth update continuity, e.g. the SequencingRules of the exchange
+    /// * `limits` - Limits of the buffer of pending updates
+    /// * `metrics` - Registry of the eviction, gap and resync counters and of the maximum buffer size gauge
     pub fn new(
         input: mpsc::Receiver<MarketEvent>,
         output: mpsc::Sender<MarketEvent>,
+        sequencing: S,
+        limits: BufferLimits,
+        metrics: &Metrics,
     ) -> Self {
         DepthEventDispatcher {
             input,
             output,
-            last_processed_update_id: None,
-            buffer: BTreeMap::new(),
+            sequencer: DepthSequencer::new(sequencing, limits),
+            limits,
+            started: Instant::now(),
+            evicted_by_size: metrics.counter("dispatcher_evicted_by_size"),
+            evicted_by_age: metrics.counter("dispatcher_evicted_by_age"),
+            gaps: metrics.counter("depth_gaps"),
+            resyncs: metrics.counter("depth_resyncs"),
+            buffer_max: metrics.gauge("dispatcher_buffer_max"),
+            in_gap: false,
+            markers: None,
+            resume: None,
         }
     }
 
-    /// Process a DepthUpdate event by adding it to the buffer
+    /// Start the sequence from a checkpoint of a previous session instead of waiting for the first snapshot
     ///
-    /// # Arguments
-    /// * `update` - The DepthUpdate to process
-    ///
-    /// # Behavior
-    /// * Always add the update to the buffer, using last_update_id as the key
-    async fn process_update(&mut self, update: DepthUpdate) {
-        let current_id_str = match self.last_processed_update_id {
-            Some(id) => id.to_string(),
-            None => "uninitialized".to_string(),
+    /// The checkpoint is forwarded as a snapshot. It is confirmed once a live update continues it.
+    /// If the first newer update doesn't continue it, the updates in between have been missed,
+    /// so a fresh snapshot is requested through the given channel
+    pub fn with_checkpoint(mut self, checkpoint: DepthSnapshot, snapshot_requests: mpsc::Sender<()>) -> Self {
+        self.sequencer.apply_snapshot(&checkpoint);
+        self.resume = Some(Resume { update_id: checkpoint.last_update_id, snapshot: Some(checkpoint), snapshot_requests });
+        self
+    }
+
+    /// Additionally send a `SequenceGap` event for every detected gap and a `Resync` event for every snapshot,
+    /// which restarts the sequence, to the given channel
+    pub fn with_markers(mut self, output: mpsc::Sender<MarketEvent>) -> Self {
+        self.markers = Some(output);
+        self
+    }
+
+    /// Send a continuity marker, if markers are enabled
+    async fn send_marker(&self, event: MarketEvent) -> Result<()> {
+        let Some(markers) = &self.markers else {
+            return Ok(());
         };
-        
+
+        markers.send(event).await.context("Failed to send continuity marker")
+    }
+
+    /// Milliseconds since the dispatcher has been created, the clock of the sequencer
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Process a DepthUpdate event by adding it to the buffer of the sequencer
+    fn process_update(&mut self, update: DepthUpdate) {
         tracing::debug!(
-            "Received depth update with ids: '{}-{}'. Current expected id: '{}'", 
-            update.first_update_id, 
+            "Received depth update with ids: '{}-{}'. Last processed id: '{:?}'",
+            update.first_update_id,
             update.last_update_id,
-            current_id_str
+            self.sequencer.last_processed_update_id()
         );
-        
-        self.buffer.insert(update.last_update_id, update);
+
+        let now = self.now();
+        self.sequencer.push_update(update, now);
     }
 
-    /// Process a DepthSnapshot event by updating the current update ID
-    ///
-    /// # Arguments
-    /// * `snapshot` - The DepthSnapshot to process
+    /// Evict updates, which exceed the buffer limits, and account the evictions
+    fn evict_stale(&mut self) {
+        let now = self.now();
+        let evictions = self.sequencer.evict_stale(now);
+
+        if evictions.by_age > 0 {
+            self.evicted_by_age.increment(evictions.by_age);
+            tracing::warn!(
+                "Evicted '{}' depth updates older than '{}' ms from the buffer. Last processed update id: '{:?}'",
+                evictions.by_age, self.limits.max_age, self.sequencer.last_processed_update_id()
+            );
+        }
+
+        if evictions.by_size > 0 {
+            self.evicted_by_size.increment(evictions.by_size);
+            tracing::warn!(
+                "Evicted '{}' depth updates from the buffer, which exceeded '{}' entries. Last processed update id: '{:?}'",
+                evictions.by_size, self.limits.max_size, self.sequencer.last_processed_update_id()
+            );
+        }
+    }
+
+    /// Account a gap, if updates stay buffered after the sequence has been drained, and the maximum buffer size
     ///
-    /// # Behavior
-    /// * Update the current update ID to the snapshot's last update ID
-    async fn process_snapshot(&mut self, snapshot: &DepthSnapshot) {
-        tracing::debug!("Received snapshot: '{:?}'", snapshot);
-        
-        if self.last_processed_update_id.is_none() {
-            tracing::trace!("The snapshot if first. Forwarding it and initializing expected id to: '{:?}'", snapshot.last_update_id);
-            self.last_processed_update_id = Some(snapshot.last_update_id);
-            self.output
-                .send(MarketEvent::DepthSnapshot(snapshot.clone()))
-                .await
-                .expect("Failed to forward DepthSnapshot to output channel");
-            
-            return;
+    /// Each connection delivers updates in order, so a buffered update after the snapshot means that
+    /// the updates before it have been missed by all connections
+    async fn check_gap(&mut self) -> Result<()> {
+        let buffered = self.sequencer.buffered();
+        if buffered as i64 > self.buffer_max.get() {
+            self.buffer_max.set(buffered as i64);
         }
-        
-        let last_processed_update_id = self.last_processed_update_id.unwrap();
-        
-        if snapshot.last_update_id <= last_processed_update_id {
-            tracing::trace!("Received snapshot, which update id '{}' is older then last processed update id '{}'. Skipping", snapshot.last_update_id, last_processed_update_id);
-            return;
+
+        let (Some(last_processed_update_id), Some(next)) = (self.sequencer.last_processed_update_id(), self.sequencer.first_buffered()) else {
+            self.in_gap = false;
+            return Ok(());
+        };
+
+        if self.in_gap {
+            return Ok(());
+        }
+
+        self.in_gap = true;
+        self.gaps.increment(1);
+        tracing::warn!("Gap in depth updates after update '{}'. '{}' updates are buffered", last_processed_update_id, buffered);
+
+        let gap = SequenceGap {
+            time: Utc::now().timestamp_millis(),
+            last_update_id: last_processed_update_id,
+            next_update_id: next.first_update_id,
+            buffered,
+        };
+        self.send_marker(MarketEvent::SequenceGap(gap)).await
+    }
+
+    /// Process a DepthSnapshot event, forwarding it if it restarts the sequence
+    async fn process_snapshot(&mut self, snapshot: DepthSnapshot) -> Result<()> {
+        tracing::debug!("Received snapshot: '{:?}'", snapshot);
+
+        let last_processed_update_id = self.sequencer.last_processed_update_id();
+        if !self.sequencer.apply_snapshot(&snapshot) {
+            tracing::trace!("Received snapshot, which update id '{}' is older then last processed update id '{:?}'. Skipping", snapshot.last_update_id, last_processed_update_id);
+            return Ok(());
         }
 
-        tracing::trace!("Received snapshot, which update id '{}' is newer, then last processed update id '{}'. Forwarding and starting update process from new update id", snapshot.last_update_id, last_processed_update_id);
-        self.last_processed_update_id = Some(snapshot.last_update_id);
+        tracing::trace!("Forwarding snapshot with update id '{}' and starting update process from it. Last processed update id: '{:?}'", snapshot.last_update_id, last_processed_update_id);
+        self.in_gap = false;
+        if let Some(previous_update_id) = last_processed_update_id {
+            self.resyncs.increment(1);
+            let resync = Resync {
+                time: Utc::now().timestamp_millis(),
+                source: ResyncSource::Snapshot,
+                previous_update_id,
+                update_id: snapshot.last_update_id,
+            };
+            self.send_marker(MarketEvent::Resync(resync)).await?;
+        }
 
         self.output
-            .send(MarketEvent::DepthSnapshot(snapshot.clone()))
+            .send(MarketEvent::DepthSnapshot(snapshot))
             .await
-            .expect("Failed to forward DepthSnapshot to output channel");
+            .context("Failed to forward DepthSnapshot to output channel")
     }
 
-    /// Process the buffer to send updates to the output channel
-    ///
-    /// # Behavior
-    /// * Implement Binance's rules for maintaining a local order book:
-    ///   1. Discard any event where `u` (last_update_id) is <= lastUpdateId of the snapshot
-    ///   2. The first buffered event should have lastUpdateId within its [U;u] range
-    /// * Process events in sequence
-    /// * Send events to the output channel
-    async fn process_buffer(&mut self) {
-        let Some(last_processed_update_id) = self.last_processed_update_id else {
-            tracing::trace!("No current_update_id set, skipping buffer processing");
+    /// Confirm or reject the checkpoint the sequence has been resumed from, if any, after the given update
+    fn check_resume(&mut self, update_id: u64) {
+        let Some(resume) = &self.resume else {
             return;
         };
-        
-        tracing::trace!("Processing buffer. Current expected id: '{}'", last_processed_update_id);
-        
-        if self.buffer.is_empty() {
-            tracing::trace!("The buffer is empty, nothing to process");
-            return;
-        }
-        
-        let mut expected_first_update_id = last_processed_update_id + 1;
-        let mut processed_keys = Vec::new();
-        
-        for (last_update_id, depth_update) in self.buffer.iter() {
-            if *last_update_id <= last_processed_update_id {
-                processed_keys.push(*last_update_id);
-                continue;
-            }
-            
-            if !(depth_update.first_update_id <= expected_first_update_id && expected_first_update_id < depth_update.last_update_id) {
-                break;
-            }
-            
-            processed_keys.push(*last_update_id);
-            expected_first_update_id = depth_update.last_update_id + 1;
-            
 
-            self.last_processed_update_id = Some(depth_update.last_update_id);
+        if self.sequencer.last_processed_update_id() != Some(resume.update_id) {
+            tracing::info!("Live depth updates continue checkpoint '{}'. Resumed without a snapshot", resume.update_id);
+            self.resume = None;
+        } else if update_id > resume.update_id {
+            tracing::warn!("Depth update '{}' doesn't continue checkpoint '{}'. Requesting a fresh snapshot", update_id, resume.update_id);
+            if let Err(mpsc::error::TrySendError::Closed(_)) = resume.snapshot_requests.try_send(()) {
+                tracing::error!("Snapshot request channel is closed. Waiting for the next snapshot");
+            }
+            self.resume = None;
+        }
+    }
 
+    /// Forward the buffered updates, which continue the sequence, to the output channel
+    async fn process_buffer(&mut self) -> Result<()> {
+        for depth_update in self.sequencer.drain() {
             tracing::trace!(
-                "Forwarding depth updates: '{}'-'{}'. Updated last processed id to: '{}'", 
-                depth_update.first_update_id, 
-                depth_update.last_update_id, 
+                "Forwarding depth updates: '{}'-'{}'",
+                depth_update.first_update_id,
                 depth_update.last_update_id
             );
-            
+
             self.output
-                .send(MarketEvent::DepthUpdate(depth_update.clone()))
+                .send(MarketEvent::DepthUpdate(depth_update))
                 .await
-                .expect("Failed to send DepthUpdate to output channel");
+                .context("Failed to send DepthUpdate to output channel")?;
         }
 
-        // Remove only the processed updates from the buffer
-        for key in processed_keys {
-            self.buffer.remove(&key);
-        }
+        Ok(())
     }
 
     /// Run the DepthEventDispatcher
     ///
     /// This method will continuously process messages from the input channel
-    /// and send filtered messages to the output channel
-    pub async fn run(mut self) {
+    /// and send filtered messages to the output channel. The dispatcher keeps its buffer if it fails,
+    /// so it can be run again
+    pub async fn run(&mut self) -> Result<()> {
         tracing::info!("Starting DepthEventDispatcher");
+
+        if let Some(checkpoint) = self.resume.as_mut().and_then(|resume| resume.snapshot.take()) {
+            tracing::info!("Resuming depth updates from checkpoint '{}'", checkpoint.last_update_id);
+            self.output
+                .send(MarketEvent::DepthSnapshot(checkpoint))
+                .await
+                .context("Failed to forward checkpoint to output channel")?;
+        }
         
         while let Some(event) = self.input.recv().await {
             match event {
                 MarketEvent::DepthUpdate(update) => {
-                    self.process_update(update).await;
-                    self.process_buffer().await;
+                    let update_id = update.last_update_id;
+                    self.process_update(update);
+                    self.process_buffer().await?;
+                    self.check_resume(update_id);
+                    self.check_gap().await?;
+                    self.evict_stale();
                 }
                 MarketEvent::DepthSnapshot(snapshot) => {
-                    self.process_snapshot(&snapshot).await;
-                    self.process_buffer().await;
+                    // A snapshot supersedes the checkpoint
+                    self.resume = None;
+                    self.process_snapshot(snapshot).await?;
+                    self.process_buffer().await?;
+                    self.check_gap().await?;
                 }
                 _ => {
                     tracing::error!("Received unexpected event type: '{:?}'. Discarding", &event);               
                 }
             }
         }
+
+        Ok(())
+    }
+}
+
+impl<S: SequencingStrategy> RestartableTask for DepthEventDispatcher<S> {
+    fn process(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(self.run())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::mdc_server::models::{DepthSnapshot};
+    use crate::mdc_core::models::{DepthSnapshot};
     use tokio::sync::mpsc;
     use tokio::time::{sleep, Duration};
     use tokio::task::JoinHandle;
@@ -189,8 +302,11 @@ mod tests {
             symbol: "BTCUSDT".to_string(),
             first_update_id: first,
             last_update_id: last,
+            previous_last_update_id: None,
             bids: vec![],
             asks: vec![],
+            checksum: None,
+            received: None,
         }
     }
     
@@ -202,6 +318,10 @@ mod tests {
         }
     }
     
+    fn make_limits() -> BufferLimits {
+        BufferLimits { max_size: 10_000, max_age: 0 }
+    }
+
     async fn setup_test() -> (
         mpsc::Sender<MarketEvent>,
         mpsc::Receiver<MarketEvent>,
@@ -214,8 +334,8 @@ mod tests {
         let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
         let (output_tx, output_rx) = mpsc::channel::<MarketEvent>(100);
         
-        let dispatcher = DepthEventDispatcher::new(input_rx, output_tx);
-        let handle = tokio::spawn(dispatcher.run());
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceSpot, make_limits(), &Metrics::new());
+        let handle = tokio::spawn(async move { let _ = dispatcher.run().await; });
 
         (input_tx, output_rx, handle)
     }
@@ -435,4 +555,158 @@ mod tests {
         verify_snapshot(received_snapshot, 100);
         verify_update(received_update, 101, 105);
     }
-}
+
+    async fn verify_no_output(output_rx: &mut mpsc::Receiver<MarketEvent>) {
+        tokio::select! {
+            _ = sleep(Duration::from_millis(100)) => {}
+            event = output_rx.recv() => {
+                panic!("Received unexpected event: '{:?}'", event);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_depth_event_dispatcher_evicts_by_size() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+        let limits = BufferLimits { max_size: 2, max_age: 0 };
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceSpot, limits, &metrics);
+        tokio::spawn(async move { dispatcher.run().await });
+
+        input_tx.send(MarketEvent::DepthUpdate(make_update(101, 105))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(106, 110))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(111, 115))).await.unwrap();
+        input_tx.send(MarketEvent::DepthSnapshot(make_snapshot(100))).await.unwrap();
+
+        // The update continuing the snapshot has been evicted, so nothing but the snapshot is forwarded
+        verify_snapshot(output_rx.recv().await.unwrap(), 100);
+        verify_no_output(&mut output_rx).await;
+
+        assert_eq!(metrics.snapshot()["dispatcher_evicted_by_size"], 1);
+        assert_eq!(metrics.snapshot()["dispatcher_evicted_by_age"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_depth_event_dispatcher_evicts_by_age() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+        let limits = BufferLimits { max_size: 10_000, max_age: 50 };
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceSpot, limits, &metrics);
+        tokio::spawn(async move { dispatcher.run().await });
+
+        input_tx.send(MarketEvent::DepthUpdate(make_update(101, 105))).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        input_tx.send(MarketEvent::DepthUpdate(make_update(106, 110))).await.unwrap();
+        input_tx.send(MarketEvent::DepthSnapshot(make_snapshot(105))).await.unwrap();
+
+        verify_snapshot(output_rx.recv().await.unwrap(), 105);
+        verify_update(output_rx.recv().await.unwrap(), 106, 110);
+
+        assert_eq!(metrics.snapshot()["dispatcher_evicted_by_age"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_depth_event_dispatcher_accounts_gaps_and_resyncs() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+        let (marker_tx, mut marker_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceSpot, make_limits(), &metrics)
+            .with_markers(marker_tx);
+        tokio::spawn(async move { dispatcher.run().await });
+
+        input_tx.send(MarketEvent::DepthSnapshot(make_snapshot(100))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(101, 105))).await.unwrap();
+        // Updates 106-110 are missed, the gap is accounted once
+        input_tx.send(MarketEvent::DepthUpdate(make_update(111, 115))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(116, 120))).await.unwrap();
+        input_tx.send(MarketEvent::DepthSnapshot(make_snapshot(112))).await.unwrap();
+
+        verify_snapshot(output_rx.recv().await.unwrap(), 100);
+        verify_update(output_rx.recv().await.unwrap(), 101, 105);
+        verify_snapshot(output_rx.recv().await.unwrap(), 112);
+        verify_update(output_rx.recv().await.unwrap(), 111, 115);
+        verify_update(output_rx.recv().await.unwrap(), 116, 120);
+
+        assert_eq!(metrics.snapshot()["depth_gaps"], 1);
+        assert_eq!(metrics.snapshot()["depth_resyncs"], 1);
+        assert_eq!(metrics.gauge_snapshot()["dispatcher_buffer_max"], 2);
+
+        let Some(MarketEvent::SequenceGap(gap)) = marker_rx.recv().await else {
+            panic!("SequenceGap marker is expected");
+        };
+        assert_eq!((gap.last_update_id, gap.next_update_id, gap.buffered), (105, 111, 1));
+        let Some(MarketEvent::Resync(resync)) = marker_rx.recv().await else {
+            panic!("Resync marker is expected");
+        };
+        assert_eq!((resync.source, resync.previous_update_id, resync.update_id), (ResyncSource::Snapshot, 105, 112));
+        assert!(marker_rx.try_recv().is_err());
+    }
+
+    fn make_futures_update(first: u64, last: u64, previous: u64) -> DepthUpdate {
+        DepthUpdate {
+            previous_last_update_id: Some(previous),
+            ..make_update(first, last)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_depth_event_dispatcher_futures_sequence() {
+        let _ = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .try_init();
+
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceFutures, make_limits(), &Metrics::new());
+        tokio::spawn(async move { dispatcher.run().await });
+
+        input_tx.send(MarketEvent::DepthSnapshot(make_snapshot(100))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_futures_update(120, 130, 105))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_futures_update(98, 105, 97))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_futures_update(140, 150, 131))).await.unwrap();
+
+        verify_snapshot(output_rx.recv().await.unwrap(), 100);
+        verify_update(output_rx.recv().await.unwrap(), 98, 105);
+        verify_update(output_rx.recv().await.unwrap(), 120, 130);
+
+        tokio::select! {
+            _ = sleep(Duration::from_millis(100)) => {}
+            _ = output_rx.recv() => {
+                panic!("Received update, which does not continue the sequence");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let (request_tx, mut request_rx) = mpsc::channel::<()>(1);
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceSpot, make_limits(), &Metrics::new())
+            .with_checkpoint(make_snapshot(100), request_tx.clone());
+        tokio::spawn(async move { let _ = dispatcher.run().await; });
+
+        input_tx.send(MarketEvent::DepthUpdate(make_update(95, 100))).await.unwrap();
+        input_tx.send(MarketEvent::DepthUpdate(make_update(101, 105))).await.unwrap();
+        verify_snapshot(output_rx.recv().await.unwrap(), 100);
+        verify_update(output_rx.recv().await.unwrap(), 101, 105);
+
+        // Updates in between have been missed, so a snapshot is requested
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let mut dispatcher = DepthEventDispatcher::new(input_rx, output_tx, SequencingRules::BinanceSpot, make_limits(), &Metrics::new())
+            .with_checkpoint(make_snapshot(100), request_tx);
+        tokio::spawn(async move { let _ = dispatcher.run().await; });
+
+        input_tx.send(MarketEvent::DepthUpdate(make_update(120, 125))).await.unwrap();
+        verify_snapshot(output_rx.recv().await.unwrap(), 100);
+        request_rx.recv().await.unwrap();
+
+        input_tx.send(MarketEvent::DepthSnapshot(make_snapshot(122))).await.unwrap();
+        verify_snapshot(output_rx.recv().await.unwrap(), 122);
+        verify_update(output_rx.recv().await.unwrap(), 120, 125);
+    }
+}
\ No newline at end of file
@@ -1,77 +1,288 @@
 use tokio::sync::mpsc;
 use tokio::time::{sleep, Duration};
-use anyhow::{Result, Context};
-use crate::mdc_server::models::{DepthSnapshot, MarketEvent, FromJson};
-use reqwest;
+use anyhow::{anyhow, Result, Context};
+use chrono::Utc;
+use crate::mdc_core::models::{DepthSnapshot, MarketEvent, FromJson};
+use crate::mdc_server::tape::TapeRecorder;
+use crate::mdc_server::exchange_connector::ExchangeConnector;
+use crate::mdc_server::request_weight::RequestWeightBudget;
+use crate::mdc_server::ws_api::WsApiClient;
+use crate::mdc_server::transport::Transport;
+use std::sync::Arc;
 use tracing;
 
-/// This class periodically requests order book snapshots using Binance REST API
+/// Snapshot limits, at which Binance REST API request weight or response size noticeably changes
+const SNAPSHOT_LIMIT_TIERS: [u64; 8] = [5, 10, 20, 50, 100, 500, 1000, 5000];
+
+/// Returns the smallest snapshot limit tier, which covers the requested depth
+fn limit_tier(depth: u64) -> u64 {
+    SNAPSHOT_LIMIT_TIERS
+        .iter()
+        .copied()
+        .find(|tier| *tier >= depth)
+        .unwrap_or(5000)
+}
+
+/// Selects the `limit` of REST snapshot requests
+///
+/// # Behavior
+/// * If a limit override is configured, it is always used
+/// * Otherwise the configured maximum depth is requested, reduced to the smallest limit tier that covers:
+///   * the depth, which is actually kept downstream (if the book is pruned)
+///   * the depth observed in the last snapshot, if the book turned out to be thinner than requested
+pub struct SnapshotDepthSelector {
+    max_depth: u64,
+    kept_depth: Option<u64>,
+    limit_override: Option<u64>,
+    observed_depth: Option<u64>,
+}
+
+impl SnapshotDepthSelector {
+    /// Create a new SnapshotDepthSelector
+    ///
+    /// # Arguments
+    /// * `max_depth` - The maximum depth of the order book to request (up to 5000)
+    /// * `kept_depth` - The number of levels per side kept downstream, if the book is pruned
+    /// * `limit_override` - Fixed snapshot limit, which disables the automatic selection
+    pub fn new(max_depth: u64, kept_depth: Option<u64>, limit_override: Option<u64>) -> Self {
+        Self {
+            max_depth,
+            kept_depth,
+            limit_override,
+            observed_depth: None,
+        }
+    }
+
+    /// Returns the limit for the next snapshot request
+    pub fn limit(&self) -> u64 {
+        if let Some(limit) = self.limit_override {
+            return limit;
+        }
+
+        let mut limit = self.max_depth;
+
+        if let Some(kept_depth) = self.kept_depth {
+            limit = limit.min(limit_tier(kept_depth));
+        }
+
+        if let Some(observed_depth) = self.observed_depth {
+            limit = limit.min(limit_tier(observed_depth));
+        }
+
+        limit.max(1)
+    }
+
+    /// Take the depth of a received snapshot into account
+    ///
+    /// # Arguments
+    /// * `snapshot` - The received snapshot
+    /// * `requested` - The limit the snapshot was requested with
+    pub fn observe(&mut self, snapshot: &DepthSnapshot, requested: u64) {
+        let levels = snapshot.bids.len().max(snapshot.asks.len()) as u64;
+
+        // A full response means the book may be deeper, so the observation must not cap further requests
+        self.observed_depth = if levels < requested { Some(levels) } else { None };
+    }
+}
+
+/// This class periodically requests order book snapshots using the exchange REST API
 /// and sends them to the DepthEventDispatcher as a MarketEvent::DepthSnapshot message
+///
+/// If a `RequestWeightBudget` is provided, requests wait until they fit into the budget
+///
+/// If a `WsApiClient` is provided, the snapshots are requested over the WebSocket API instead. Their responses
+/// are recorded the same way as REST responses
 pub struct DepthSnapshotStream {
-    binance_rest_endpoint: String,
+    connector: Arc<dyn ExchangeConnector>,
     instrument: String,
-    max_depth: u64,
+    depth_selector: SnapshotDepthSelector,
     update_interval: u64,
     output: mpsc::Sender<MarketEvent>,
+    recorder: Option<TapeRecorder>,
+    requests: Option<mpsc::Receiver<()>>,
+    weight_budget: Option<RequestWeightBudget>,
+    ws_api: Option<WsApiClient>,
+    transport: Transport,
+    deferred_start: bool,
 }
 
 impl DepthSnapshotStream {
     /// Create a new DepthSnapshotStream
     ///
     /// # Arguments
-    /// * `binance_rest_endpoint` - The Binance REST API endpoint
+    /// * `connector` - The exchange connector, which provides the snapshot endpoint
     /// * `instrument` - The trading instrument (e.g., "BTCUSDT")
-    /// * `max_depth` - The maximum depth of the order book to request (up to 5000)
+    /// * `depth_selector` - Selector of the depth to request
     /// * `update_interval` - The interval between snapshot updates in milliseconds
     /// * `output` - Sender for MarketEvent messages to the DepthEventDispatcher
+    /// * `recorder` - Optional recorder, which persists every raw snapshot response
     pub fn new(
-        binance_rest_endpoint: String,
+        connector: Arc<dyn ExchangeConnector>,
         instrument: String,
-        max_depth: u64,
+        depth_selector: SnapshotDepthSelector,
         update_interval: u64,
         output: mpsc::Sender<MarketEvent>,
+        recorder: Option<TapeRecorder>,
     ) -> Self {
         Self {
-            binance_rest_endpoint,
+            connector,
             instrument,
-            max_depth,
+            depth_selector,
             update_interval,
             output,
+            recorder,
+            requests: None,
+            weight_budget: None,
+            ws_api: None,
+            transport: Transport::default(),
+            deferred_start: false,
+        }
+    }
+
+    /// Keep the weight of the snapshot requests within the budget, which may be shared with other streams
+    pub fn with_weight_budget(mut self, weight_budget: RequestWeightBudget) -> Self {
+        self.weight_budget = Some(weight_budget);
+        self
+    }
+
+    /// Request the snapshots over the WebSocket API of the exchange instead of its REST API
+    pub fn with_ws_api(mut self, ws_api: WsApiClient) -> Self {
+        self.ws_api = Some(ws_api);
+        self
+    }
+
+    /// Send the REST requests with the proxy and TLS settings of the transport
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Additionally request a snapshot right away whenever a message is received from the channel,
+    /// instead of waiting for the end of the update interval
+    pub fn with_requests(mut self, requests: mpsc::Receiver<()>) -> Self {
+        self.requests = Some(requests);
+        self
+    }
+
+    /// Wait for the end of the first update interval (or for a snapshot request) before the first snapshot,
+    /// e.g. when the book has been resumed from a checkpoint
+    pub fn with_deferred_start(mut self) -> Self {
+        self.deferred_start = true;
+        self
+    }
+
+    /// Wait for the end of the update interval or for a snapshot request, whichever comes first
+    async fn wait(&mut self) {
+        let delay = sleep(Duration::from_millis(self.update_interval));
+        tokio::pin!(delay);
+
+        if let Some(requests) = self.requests.as_mut() {
+            tokio::select! {
+                _ = &mut delay => return,
+                request = requests.recv() => match request {
+                    Some(()) => {
+                        tracing::info!("Snapshot requested before the end of the update interval");
+                        return;
+                    }
+                    None => self.requests = None,
+                }
+            }
         }
+
+        delay.await;
     }
 
-    /// Get market data snapshot from the Binance REST API
-    async fn get_snapshot(&self) -> Result<DepthSnapshot> {
-        let url = format!("{}depth?symbol={}&limit={}", 
-            self.binance_rest_endpoint, 
-            self.instrument, 
-            self.max_depth);
+    /// Get market data snapshot from the exchange REST or WebSocket API
+    async fn get_snapshot(&mut self) -> Result<DepthSnapshot> {
+        let limit = self.depth_selector.limit();
+        let response_text = match self.ws_api.is_some() {
+            true => self.request_ws_api(limit).await?,
+            false => self.request_rest(limit).await?,
+        };
+
+        tracing::trace!("Received depth snapshot from {}: '{:?}'", self.connector.name(), response_text);
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&response_text).await;
+        }
         
-        let response = reqwest::get(&url)
+        let snapshot = DepthSnapshot::from_json(&response_text)
+            .context("Failed to parse snapshot")?;
+
+        self.depth_selector.observe(&snapshot, limit);
+        
+        Ok(snapshot)
+    }
+
+    /// Request a snapshot with the given limit from the exchange REST API
+    ///
+    /// # Returns
+    /// The text of the response
+    async fn request_rest(&self, limit: u64) -> Result<String> {
+        let url = self.connector
+            .snapshot_url(&self.instrument, limit)
+            .ok_or_else(|| anyhow!("Exchange '{}' doesn't provide REST snapshots", self.connector.name()))?;
+        if let Some(budget) = &self.weight_budget {
+            budget.acquire(self.connector.snapshot_weight(limit), "Snapshot").await;
+        }
+
+        let response = self.transport.get(&url)
             .await
-            .context("Failed to send snapshot request")?
+            .context("Failed to send snapshot request")?;
+
+        if let Some(budget) = &self.weight_budget {
+            budget.on_http_response(&response);
+        }
+
+        let response = response
             .error_for_status()
             .context("Failed to get snapshot response")?;
         
-        let response_text = response
+        response
             .text()
             .await
-            .context("Failed to get response text for snapshot")?;
+            .context("Failed to get response text for snapshot")
+    }
 
-        tracing::trace!("Received depth snapshot from binance: '{:?}'", response_text);
-        
-        let snapshot = DepthSnapshot::from_json(&response_text)
-            .context("Failed to parse snapshot")?;
-        
-        Ok(snapshot)
+    /// Request a snapshot with the given limit over the exchange WebSocket API
+    ///
+    /// # Returns
+    /// The result of the response, which has the format of the REST response
+    async fn request_ws_api(&mut self, limit: u64) -> Result<String> {
+        let params = self.connector
+            .ws_api_snapshot_params(&self.instrument, limit)
+            .ok_or_else(|| anyhow!("Exchange '{}' doesn't provide WebSocket API snapshots", self.connector.name()))?;
+        if let Some(budget) = &self.weight_budget {
+            budget.acquire(self.connector.snapshot_weight(limit), "Snapshot").await;
+        }
+
+        let ws_api = self.ws_api.as_mut().ok_or_else(|| anyhow!("WebSocket API client is not set"))?;
+        let response = ws_api
+            .request("depth", params)
+            .await
+            .context("Failed to send snapshot request")?;
+
+        if let Some(budget) = &self.weight_budget {
+            budget.on_response(response.status, response.used_weight(), None, Utc::now().timestamp_millis());
+        }
+
+        let result = response
+            .into_result()
+            .context("Failed to get snapshot response")?;
+        Ok(result.get().to_string())
     }
 
     /// Run the DepthSnapshotStream as an asynchronous task
     ///
-    /// This method will continuously request snapshots from the Binance REST API
-    /// at the specified interval and send them to the DepthEventDispatcher
-    pub async fn run(self) {
+    /// This method will continuously request snapshots from the exchange REST or WebSocket API
+    /// at the specified interval and send them to the DepthEventDispatcher.
+    /// A snapshot request cuts the current interval short
+    pub async fn run(mut self) {
         tracing::info!("Starting DepthSnapshotStream with update interval: '{}' ms", self.update_interval);
+
+        if self.deferred_start {
+            self.wait().await;
+        }
         
         loop {
             match self.get_snapshot().await {
@@ -85,7 +296,75 @@ impl DepthSnapshotStream {
                 }
             }
             
-            sleep(Duration::from_millis(self.update_interval)).await;
+            self.wait().await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::DepthEntry;
+
+    fn make_snapshot(levels: usize) -> DepthSnapshot {
+        DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) }; levels],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(1.0) }; levels],
+        }
+    }
+
+    #[test]
+    fn test_limit_defaults_to_max_depth() {
+        let selector = SnapshotDepthSelector::new(200, None, None);
+        assert_eq!(selector.limit(), 200);
+    }
+
+    #[test]
+    fn test_limit_reduced_to_kept_depth_tier() {
+        let selector = SnapshotDepthSelector::new(5000, Some(200), None);
+        assert_eq!(selector.limit(), 500);
+
+        let selector = SnapshotDepthSelector::new(5000, Some(1000), None);
+        assert_eq!(selector.limit(), 1000);
+
+        let selector = SnapshotDepthSelector::new(100, Some(200), None);
+        assert_eq!(selector.limit(), 100);
+    }
+
+    #[test]
+    fn test_limit_reduced_to_configured_book_depth() {
+        let yaml = "rest_endpoint: \"https://api.example.com\"\nwss_endpoint: \"wss://stream.example.com\"\n\
+            instrument: \"BTCUSDT\"\nmax_depth: 5000\nconnections: 1\nreconnect_timeout: 5000\nsnapshot_update_interval: 30000\n";
+        let selector = |yaml: &str| {
+            let config = crate::mdc_server::config::load_pipelines_from_yaml_str(yaml).unwrap().remove(0);
+            SnapshotDepthSelector::new(config.max_depth, config.kept_depth(), config.snapshot_limit)
+        };
+
+        assert_eq!(selector(yaml).limit(), 5000);
+        assert_eq!(selector(&format!("{}book_max_depth: 100\n", yaml)).limit(), 100);
+        assert_eq!(selector(&format!("{}book_max_depth: 300\n", yaml)).limit(), 500);
+    }
+
+    #[test]
+    fn test_limit_follows_observed_depth() {
+        let mut selector = SnapshotDepthSelector::new(5000, None, None);
+
+        selector.observe(&make_snapshot(30), 5000);
+        assert_eq!(selector.limit(), 50);
+
+        selector.observe(&make_snapshot(30), 50);
+        assert_eq!(selector.limit(), 50);
+
+        selector.observe(&make_snapshot(50), 50);
+        assert_eq!(selector.limit(), 5000);
+    }
+
+    #[test]
+    fn test_limit_override() {
+        let mut selector = SnapshotDepthSelector::new(5000, Some(200), Some(1000));
+        selector.observe(&make_snapshot(30), 1000);
+        assert_eq!(selector.limit(), 1000);
+    }
+}
@@ -0,0 +1,386 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer};
+use serde_json::json;
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::{DepthEntry, DepthSnapshot, DepthUpdate, MarketEvent, StreamMessage, SymbolMetadata, TradeEvent};
+use crate::mdc_core::sequencing::SequencingRules;
+use crate::mdc_server::exchange_connector::{ExchangeConnector, StreamKind};
+
+/// Update interval of the public book and trades channels. Tick-by-tick (`raw`) channels require authorization
+const CHANNEL_INTERVAL: &str = "100ms";
+
+/// Connector for Deribit (v2 API) market data of futures, perpetuals and options (e.g. "BTC-PERPETUAL",
+/// "BTC-27DEC24-100000-C")
+///
+/// Deribit speaks JSON-RPC over WebSocket (e.g. "wss://www.deribit.com/ws/api/v2"): channels are subscribed
+/// with a `public/subscribe` request after connecting and delivered as `subscription` notifications.
+/// A book subscription starts with a snapshot, which carries the `change_id` the following changes continue,
+/// so REST snapshots are not used
+pub struct DeribitConnector {
+    rest_endpoint: String,
+    wss_endpoint: String,
+}
+
+impl DeribitConnector {
+    /// Create a new DeribitConnector
+    ///
+    /// # Arguments
+    /// * `rest_endpoint` - The Deribit REST API endpoint (e.g. "https://www.deribit.com/api/v2/")
+    /// * `wss_endpoint` - The Deribit WebSocket endpoint (e.g. "wss://www.deribit.com/ws/api/v2")
+    pub fn new(rest_endpoint: String, wss_endpoint: String) -> Self {
+        Self {
+            rest_endpoint,
+            wss_endpoint,
+        }
+    }
+}
+
+impl ExchangeConnector for DeribitConnector {
+    fn name(&self) -> &str {
+        "deribit"
+    }
+
+    fn stream_name(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        match kind {
+            StreamKind::Depth => Some(format!("book.{}.{}", instrument, CHANNEL_INTERVAL)),
+            StreamKind::Trade => Some(format!("trades.{}.{}", instrument, CHANNEL_INTERVAL)),
+            _ => None,
+        }
+    }
+
+    fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        self.stream_name(kind, instrument).map(|_| self.wss_endpoint.clone())
+    }
+
+    fn subscription(&self, kind: StreamKind, instrument: &str) -> Vec<String> {
+        self.stream_name(kind, instrument)
+            .map(|channel| json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "public/subscribe",
+                "params": {"channels": [channel]},
+            }).to_string())
+            .into_iter()
+            .collect()
+    }
+
+    fn combined_stream_url(&self, _names: &[String]) -> Option<String> {
+        None
+    }
+
+    fn snapshot_url(&self, _instrument: &str, _limit: u64) -> Option<String> {
+        None
+    }
+
+    fn snapshot_weight(&self, _limit: u64) -> u64 {
+        0
+    }
+
+    fn exchange_info_url(&self, instrument: &str) -> String {
+        format!("{}public/get_instrument?instrument_name={}", self.rest_endpoint, instrument)
+    }
+
+    fn server_time_url(&self) -> Option<String> {
+        Some(format!("{}public/get_time", self.rest_endpoint))
+    }
+
+    fn parse_server_time(&self, response: &str) -> Result<i64> {
+        parse_response(response).context("Failed to parse server time")
+    }
+
+    fn parse_symbol_metadata(&self, symbol: &str, response: &str) -> Result<SymbolMetadata> {
+        let instrument: DeribitInstrument = parse_response(response).context("Failed to parse instrument")?;
+        if instrument.instrument_name != symbol {
+            return Err(anyhow!("Received instrument '{}' instead of '{}'", instrument.instrument_name, symbol));
+        }
+
+        Ok(instrument.into())
+    }
+
+    fn is_trading(&self, metadata: &SymbolMetadata) -> bool {
+        metadata.status == "active"
+    }
+
+    fn sequencing_rules(&self) -> SequencingRules {
+        SequencingRules::Deribit
+    }
+}
+
+/// Error of a JSON-RPC request
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Response to a JSON-RPC request
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+/// Parse the result of a REST (JSON-RPC over HTTP) response
+fn parse_response<T: DeserializeOwned>(response: &str) -> Result<T> {
+    let response: RpcResponse<T> = serde_json::from_str(response)?;
+    if let Some(error) = response.error {
+        return Err(anyhow!("Deribit error '{}': '{}'", error.code, error.message));
+    }
+
+    response.result.ok_or_else(|| anyhow!("Deribit response has no result"))
+}
+
+/// Instrument description, as reported by the `public/get_instrument` endpoint
+#[derive(Debug, Deserialize)]
+struct DeribitInstrument {
+    instrument_name: String,
+    is_active: bool,
+    base_currency: String,
+    quote_currency: String,
+    tick_size: FixedPoint,
+    /// Amounts are whole multiples of the minimal amount
+    min_trade_amount: FixedPoint,
+}
+
+impl From<DeribitInstrument> for SymbolMetadata {
+    fn from(instrument: DeribitInstrument) -> Self {
+        SymbolMetadata {
+            symbol: instrument.instrument_name,
+            status: if instrument.is_active { "active" } else { "inactive" }.to_string(),
+            base_asset: instrument.base_currency,
+            quote_asset: instrument.quote_currency,
+            tick_size: Some(instrument.tick_size),
+            step_size: Some(instrument.min_trade_amount),
+            min_qty: Some(instrument.min_trade_amount),
+            max_qty: None,
+        }
+    }
+}
+
+/// A text frame of a Deribit WebSocket connection: a response to a request (e.g. the subscription)
+/// or a `subscription` notification with channel data
+#[derive(Debug, Deserialize)]
+struct RpcFrame<T> {
+    method: Option<String>,
+    params: Option<RpcNotification<T>>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcNotification<T> {
+    data: T,
+}
+
+impl<T: DeserializeOwned> RpcFrame<T> {
+    /// Parse a frame into the data of a channel notification, if any. An error response fails the parsing
+    fn parse(message: &str) -> Result<Option<T>, serde_json::Error> {
+        let frame: Self = serde_json::from_str(message)?;
+
+        if let Some(error) = frame.error {
+            return Err(de::Error::custom(format!("Deribit error '{}': '{}'", error.code, error.message)));
+        }
+
+        match (frame.method.as_deref(), frame.params) {
+            (Some("subscription"), Some(notification)) => Ok(Some(notification.data)),
+            _ => {
+                tracing::debug!("Deribit message without channel data: '{}'", message);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A book level change, sent as `[action, price, amount]`. The action is `new`, `change` or `delete`
+struct DeribitLevel(DepthEntry);
+
+impl<'de> Deserialize<'de> for DeribitLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (action, price, quantity): (String, FixedPoint, FixedPoint) = Deserialize::deserialize(deserializer)?;
+        let quantity = if action == "delete" { FixedPoint::ZERO } else { quantity };
+
+        Ok(DeribitLevel(DepthEntry { price, quantity }))
+    }
+}
+
+#[derive(Deserialize)]
+struct DeribitBook {
+    /// `snapshot` or `change`
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: u64,
+    instrument_name: String,
+    change_id: u64,
+    /// Not set in a snapshot
+    prev_change_id: Option<u64>,
+    bids: Vec<DeribitLevel>,
+    asks: Vec<DeribitLevel>,
+}
+
+/// Notifications of the Deribit book channel
+///
+/// A snapshot is decoded into a DepthSnapshot, a change into a DepthUpdate: `u` is the `change_id`,
+/// `pu` the `prev_change_id` and `U` follows `pu`
+pub struct DeribitBookMessage;
+
+impl StreamMessage for DeribitBookMessage {
+    type State = ();
+
+    fn decode(_state: &mut (), message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let Some(book) = RpcFrame::<DeribitBook>::parse(message)? else {
+            return Ok(Vec::new());
+        };
+        let levels = |levels: Vec<DeribitLevel>| levels.into_iter().map(|level| level.0).collect::<Vec<_>>();
+
+        if book.kind == "snapshot" {
+            return Ok(vec![MarketEvent::DepthSnapshot(DepthSnapshot {
+                last_update_id: book.change_id,
+                bids: levels(book.bids),
+                asks: levels(book.asks),
+            })]);
+        }
+
+        Ok(vec![MarketEvent::DepthUpdate(DepthUpdate {
+            event_type: book.kind,
+            event_time: book.timestamp,
+            symbol: book.instrument_name,
+            first_update_id: book.prev_change_id.map_or(book.change_id, |previous| previous + 1),
+            last_update_id: book.change_id,
+            previous_last_update_id: book.prev_change_id,
+            bids: levels(book.bids),
+            asks: levels(book.asks),
+            checksum: None,
+            received: None,
+        })])
+    }
+}
+
+#[derive(Deserialize)]
+struct DeribitTrade {
+    /// Sequence number of the trade within the instrument
+    trade_seq: u64,
+    timestamp: u64,
+    price: f64,
+    amount: f64,
+    /// Direction of the taker
+    direction: String,
+    instrument_name: String,
+}
+
+/// Notifications of the Deribit trades channel. A notification may carry several trades
+///
+/// The trade id is the `trade_seq`, which is numeric and unique within the instrument
+pub struct DeribitTradeMessage;
+
+impl StreamMessage for DeribitTradeMessage {
+    type State = ();
+
+    fn decode(_state: &mut (), message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let Some(trades) = RpcFrame::<Vec<DeribitTrade>>::parse(message)? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(trades.into_iter().map(|trade| {
+            MarketEvent::TradeEvent(TradeEvent {
+                event_type: "trade".to_string(),
+                event_time: trade.timestamp,
+                symbol: trade.instrument_name,
+                trade_id: trade.trade_seq,
+                price: trade.price,
+                quantity: trade.amount,
+                trade_time: trade.timestamp,
+                // The buyer is the maker if the taker sells
+                is_market_maker: trade.direction == "sell",
+                ignore: false,
+                received: None,
+            })
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_connector() -> DeribitConnector {
+        DeribitConnector::new("https://www.deribit.com/api/v2/".to_string(), "wss://www.deribit.com/ws/api/v2".to_string())
+    }
+
+    #[test]
+    fn test_streams_and_rest_endpoints() {
+        let connector = make_connector();
+
+        assert_eq!(connector.stream_url(StreamKind::Depth, "BTC-PERPETUAL").unwrap(), "wss://www.deribit.com/ws/api/v2");
+        assert_eq!(connector.stream_url(StreamKind::Price, "BTC-PERPETUAL"), None);
+        assert_eq!(
+            connector.subscription(StreamKind::Depth, "BTC-PERPETUAL"),
+            [r#"{"id":1,"jsonrpc":"2.0","method":"public/subscribe","params":{"channels":["book.BTC-PERPETUAL.100ms"]}}"#]
+        );
+        assert_eq!(
+            connector.subscription(StreamKind::Trade, "BTC-27DEC24-100000-C"),
+            [r#"{"id":1,"jsonrpc":"2.0","method":"public/subscribe","params":{"channels":["trades.BTC-27DEC24-100000-C.100ms"]}}"#]
+        );
+        assert_eq!(connector.snapshot_url("BTC-PERPETUAL", 100), None);
+        assert_eq!(connector.sequencing_rules(), SequencingRules::Deribit);
+
+        assert_eq!(connector.server_time_url().unwrap(), "https://www.deribit.com/api/v2/public/get_time");
+        assert_eq!(connector.parse_server_time(r#"{"jsonrpc":"2.0","result":1550147385946,"usIn":1550147385946000}"#).unwrap(), 1550147385946);
+        assert!(connector.parse_server_time(r#"{"jsonrpc":"2.0","error":{"code":10028,"message":"too_many_requests"}}"#).is_err());
+
+        assert_eq!(connector.exchange_info_url("BTC-PERPETUAL"), "https://www.deribit.com/api/v2/public/get_instrument?instrument_name=BTC-PERPETUAL");
+        let instrument = r#"{"jsonrpc":"2.0","result":{"instrument_name":"BTC-PERPETUAL","kind":"future","is_active":true,"base_currency":"BTC","quote_currency":"USD","tick_size":0.5,"min_trade_amount":10,"contract_size":10}}"#;
+        let metadata = connector.parse_symbol_metadata("BTC-PERPETUAL", instrument).unwrap();
+        assert_eq!(metadata.status, "active");
+        assert!(connector.is_trading(&metadata));
+        assert_eq!(metadata.tick_size, Some("0.5".parse().unwrap()));
+        assert_eq!(metadata.step_size, Some("10".parse().unwrap()));
+        assert!(connector.parse_symbol_metadata("ETH-PERPETUAL", instrument).is_err());
+    }
+
+    #[test]
+    fn test_decode_book_notifications() {
+        let ack = r#"{"jsonrpc":"2.0","id":1,"result":["book.BTC-PERPETUAL.100ms"],"usIn":1,"usOut":2,"usDiff":1}"#;
+        assert!(DeribitBookMessage::decode(&mut (), ack).unwrap().is_empty());
+
+        let snapshot = r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"snapshot","timestamp":1554373962454,"instrument_name":"BTC-PERPETUAL","change_id":297217,"bids":[["new",5042.34,30],["new",5041.94,20]],"asks":[["new",5042.64,40]]}}}"#;
+        let events = DeribitBookMessage::decode(&mut (), snapshot).unwrap();
+        let [MarketEvent::DepthSnapshot(snapshot)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(snapshot.last_update_id, 297217);
+        assert_eq!(snapshot.bids.len(), 2);
+        assert_eq!(snapshot.asks[0], DepthEntry { price: "5042.64".parse().unwrap(), quantity: "40".parse().unwrap() });
+
+        let change = r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"book.BTC-PERPETUAL.100ms","data":{"type":"change","timestamp":1554373911330,"prev_change_id":297217,"instrument_name":"BTC-PERPETUAL","change_id":297218,"bids":[["delete",5041.94,0],["change",5042.34,10]],"asks":[]}}}"#;
+        let events = DeribitBookMessage::decode(&mut (), change).unwrap();
+        let [MarketEvent::DepthUpdate(update)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(update.symbol, "BTC-PERPETUAL");
+        assert_eq!(update.event_time, 1554373911330);
+        assert_eq!((update.first_update_id, update.last_update_id, update.previous_last_update_id), (297218, 297218, Some(297217)));
+        assert!(update.bids[0].quantity.is_zero());
+        assert_eq!(update.bids[1].quantity, "10".parse().unwrap());
+
+        let error = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"Invalid params"}}"#;
+        assert!(DeribitBookMessage::decode(&mut (), error).unwrap_err().to_string().contains("-32602"));
+    }
+
+    #[test]
+    fn test_decode_trade_notifications() {
+        let trades = r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":"trades.BTC-PERPETUAL.100ms","data":[{"trade_seq":30289432,"trade_id":"48079254","timestamp":1590484156350,"tick_direction":0,"price":8950,"mark_price":8948.9,"instrument_name":"BTC-PERPETUAL","index_price":8955.88,"direction":"sell","amount":10},{"trade_seq":30289433,"trade_id":"48079255","timestamp":1590484156350,"tick_direction":1,"price":8950.5,"mark_price":8948.9,"instrument_name":"BTC-PERPETUAL","index_price":8955.88,"direction":"buy","amount":20}]}}"#;
+        let events = DeribitTradeMessage::decode(&mut (), trades).unwrap();
+
+        let [MarketEvent::TradeEvent(sell), MarketEvent::TradeEvent(buy)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(sell.trade_id, 30289432);
+        assert_eq!(sell.price, 8950.0);
+        assert_eq!(sell.quantity, 10.0);
+        assert!(sell.is_market_maker);
+        assert!(!buy.is_market_maker);
+    }
+}
@@ -0,0 +1,426 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio::time::{interval, Duration};
+use crate::mdc_server::capture_manifest::{CaptureManifest, PausedInterval};
+use crate::mdc_server::retention::finished_session_files;
+use crate::mdc_server::metrics::{Counter, Gauge, Metrics};
+
+/// Shared switch, which pauses writing of capture files
+///
+/// File sinks check it before every write and discard data while capture is paused.
+/// Capture is paused either by the DiskSpaceGuard or by the operator, independently of each other
+#[derive(Debug, Clone, Default)]
+pub struct CaptureGate {
+    paused: Arc<AtomicBool>,
+    held: Arc<AtomicBool>,
+}
+
+impl CaptureGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed) || self.is_held()
+    }
+
+    /// Whether capture is paused by the operator
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::Relaxed)
+    }
+
+    /// Pause or resume capture on request of the operator
+    pub fn set_held(&self, held: bool) {
+        self.held.store(held, Ordering::Relaxed);
+    }
+
+    fn is_out_of_space(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+}
+
+/// Returns the space in bytes available to unprivileged users on the filesystem containing the path
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` is a valid statvfs buffer
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if result != 0 {
+        return Err(anyhow!("Failed to get filesystem stats of {:?}: '{}'", path, std::io::Error::last_os_error()));
+    }
+
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Returns the space in bytes available to the user of the process on the volume containing the path
+#[cfg(windows)]
+pub fn available_space(path: &Path) -> Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut available = 0u64;
+
+    // SAFETY: `wide_path` is a valid NUL-terminated string and `available` outlives the call
+    let result = unsafe { GetDiskFreeSpaceExW(wide_path.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) };
+    if result == 0 {
+        return Err(anyhow!("Failed to get free space of {:?}: '{}'", path, std::io::Error::last_os_error()));
+    }
+
+    Ok(available)
+}
+
+/// Deletion of the oldest capture files, once free space drops below a threshold
+struct Cleanup {
+    free_space: u64,
+    uploaded_only: bool,
+    files: Counter,
+    bytes: Counter,
+}
+
+/// DiskSpaceGuard periodically checks free space in the capture directory and pauses file sinks
+/// once it drops below the minimum. Capture is resumed automatically once enough space is freed
+///
+/// Optionally it warns about low free space before capture is paused, and deletes the oldest files of the finished
+/// sessions of the instrument to keep enough free space. Every paused interval is recorded in the session manifest
+pub struct DiskSpaceGuard {
+    capture_dir: PathBuf,
+    min_free_space: u64,
+    resume_free_space: u64,
+    check_interval: u64,
+    gate: CaptureGate,
+    manifest: CaptureManifest,
+    warn_free_space: u64,
+    warned: bool,
+    warnings: Option<Counter>,
+    free_space_gauge: Option<Gauge>,
+    cleanup: Option<Cleanup>,
+}
+
+impl DiskSpaceGuard {
+    /// Create a new DiskSpaceGuard
+    ///
+    /// # Arguments
+    /// * `capture_dir` - The capture directory to watch
+    /// * `min_free_space` - Free space in bytes, below which capture is paused
+    /// * `resume_free_space` - Free space in bytes, above which paused capture is resumed. At least `min_free_space`
+    /// * `check_interval` - Check interval in milliseconds
+    /// * `gate` - The switch, shared with file sinks
+    /// * `manifest` - The session manifest, which is rewritten whenever capture is paused or resumed
+    pub fn new(
+        capture_dir: PathBuf,
+        min_free_space: u64,
+        resume_free_space: u64,
+        check_interval: u64,
+        gate: CaptureGate,
+        manifest: CaptureManifest,
+    ) -> Self {
+        Self {
+            capture_dir,
+            min_free_space,
+            resume_free_space: resume_free_space.max(min_free_space),
+            check_interval,
+            gate,
+            manifest,
+            warn_free_space: 0,
+            warned: false,
+            warnings: None,
+            free_space_gauge: None,
+            cleanup: None,
+        }
+    }
+
+    /// Log a warning once free space drops below `warn_free_space` bytes, counted by the `disk_space_warnings` counter.
+    /// The free space is published in the `disk_free_bytes` gauge
+    pub fn with_warning(mut self, warn_free_space: u64, metrics: &Metrics) -> Self {
+        self.warn_free_space = warn_free_space;
+        self.warnings = Some(metrics.counter("disk_space_warnings"));
+        self.free_space_gauge = Some(metrics.gauge("disk_free_bytes"));
+        self
+    }
+
+    /// Delete the oldest files of the finished sessions of the instrument, once free space drops below
+    /// `cleanup_free_space` bytes, until it is back above it. Deletions are counted by the `disk_cleanup_files`
+    /// and `disk_cleanup_bytes` counters
+    ///
+    /// # Arguments
+    /// * `uploaded_only` - Delete only the files, which the CaptureUploader has uploaded
+    pub fn with_cleanup(mut self, cleanup_free_space: u64, uploaded_only: bool, metrics: &Metrics) -> Self {
+        self.cleanup = Some(Cleanup {
+            free_space: cleanup_free_space,
+            uploaded_only,
+            files: metrics.counter("disk_cleanup_files"),
+            bytes: metrics.counter("disk_cleanup_bytes"),
+        });
+        self
+    }
+
+    /// Warn once free space drops below the warning threshold, and again after it has recovered
+    ///
+    /// # Returns
+    /// `true` if a warning has been logged
+    fn check_warning(&mut self, free_space: u64) -> bool {
+        if let Some(gauge) = &self.free_space_gauge {
+            gauge.set(free_space.min(i64::MAX as u64) as i64);
+        }
+
+        if self.warned {
+            self.warned = free_space < self.warn_free_space;
+            return false;
+        }
+
+        if free_space < self.warn_free_space {
+            tracing::warn!(
+                "Free space in {:?} dropped to '{}' bytes, below the warning threshold of '{}' bytes",
+                self.capture_dir, free_space, self.warn_free_space
+            );
+            self.warned = true;
+            if let Some(warnings) = &self.warnings {
+                warnings.increment(1);
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// Delete the oldest files of the finished sessions of the instrument until `needed` bytes are freed
+    /// or no deletable file is left
+    ///
+    /// # Returns
+    /// Number of bytes freed
+    fn clean_up(&self, needed: u64) -> Result<u64> {
+        let Some(cleanup) = &self.cleanup else {
+            return Ok(0);
+        };
+
+        let files = finished_session_files(
+            &self.capture_dir,
+            &self.manifest.instrument,
+            &self.manifest.session_name(),
+            cleanup.uploaded_only
+        )?;
+
+        let mut freed = 0;
+        for file in files {
+            if freed >= needed {
+                break;
+            }
+
+            if let Err(e) = file.delete() {
+                tracing::warn!("Failed to free space. Details: '{:#}'", e);
+                continue;
+            }
+
+            tracing::warn!("Deleted {:?} ('{}' bytes) to free space", file.path, file.size);
+            freed += file.size;
+            cleanup.files.increment(1);
+            cleanup.bytes.increment(file.size);
+        }
+
+        Ok(freed)
+    }
+
+    /// Pause or resume capture according to the free space
+    ///
+    /// # Returns
+    /// `true` if capture has been paused or resumed
+    fn check(&mut self, free_space: u64, now: i64) -> bool {
+        let paused = self.gate.is_out_of_space();
+
+        if !paused && free_space < self.min_free_space {
+            tracing::error!(
+                "Free space in {:?} dropped to '{}' bytes, below the minimum of '{}' bytes. Pausing capture",
+                self.capture_dir, free_space, self.min_free_space
+            );
+            self.gate.set_paused(true);
+            self.manifest.paused_intervals.push(PausedInterval { from: now, to: None });
+            return true;
+        }
+
+        if paused && free_space >= self.resume_free_space {
+            tracing::warn!("Free space in {:?} is back to '{}' bytes. Resuming capture", self.capture_dir, free_space);
+            self.gate.set_paused(false);
+            if let Some(interval) = self.manifest.paused_intervals.last_mut() {
+                interval.to = Some(now);
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// Run the DiskSpaceGuard as an asynchronous task
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(self.check_interval));
+
+        loop {
+            ticker.tick().await;
+
+            let mut free_space = match available_space(&self.capture_dir) {
+                Ok(free_space) => free_space,
+                Err(e) => {
+                    tracing::warn!("Failed to check free space. Details: '{}'", e);
+                    continue;
+                }
+            };
+
+            let cleanup_free_space = self.cleanup.as_ref().map_or(0, |cleanup| cleanup.free_space);
+            if free_space < cleanup_free_space {
+                match self.clean_up(cleanup_free_space - free_space) {
+                    Ok(0) => tracing::warn!("Free space in {:?} is low, but no file is left to delete", self.capture_dir),
+                    Ok(_) => free_space = available_space(&self.capture_dir).unwrap_or(free_space),
+                    Err(e) => tracing::warn!("Failed to delete old capture files. Details: '{}'", e),
+                }
+            }
+
+            self.check_warning(free_space);
+
+            if self.check(free_space, Utc::now().timestamp_millis()) {
+                if let Err(e) = self.manifest.write(&self.capture_dir) {
+                    tracing::warn!("Failed to record paused interval in capture manifest. Details: '{}'", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::SystemTime;
+    use crate::mdc_server::capture_uploader::UPLOAD_MARKER_DIR;
+
+    fn make_manifest() -> CaptureManifest {
+        CaptureManifest {
+            instrument: "BTCUSDT".to_string(),
+            started_at: 0,
+            rest_endpoint: "https://api.example.com/".to_string(),
+            wss_endpoint: "wss://stream.example.com/".to_string(),
+            max_depth: 100,
+            symbol_metadata: None,
+            tape_file: None,
+            paused_intervals: vec![],
+        }
+    }
+
+    #[test]
+    fn test_available_space() {
+        assert!(available_space(&std::env::temp_dir()).unwrap() > 0);
+        assert!(available_space(Path::new("/non/existent/path")).is_err());
+    }
+
+    #[test]
+    fn test_pause_and_resume_with_hysteresis() {
+        let gate = CaptureGate::new();
+        let mut guard = DiskSpaceGuard::new(PathBuf::from("capture"), 100, 200, 1000, gate.clone(), make_manifest());
+
+        assert!(!guard.check(150, 1000));
+        assert!(!gate.is_paused());
+
+        assert!(guard.check(50, 2000));
+        assert!(gate.is_paused());
+        assert_eq!(guard.manifest.paused_intervals, vec![PausedInterval { from: 2000, to: None }]);
+
+        // Between the thresholds capture stays paused
+        assert!(!guard.check(150, 3000));
+        assert!(gate.is_paused());
+
+        assert!(guard.check(250, 4000));
+        assert!(!gate.is_paused());
+        assert_eq!(guard.manifest.paused_intervals, vec![PausedInterval { from: 2000, to: Some(4000) }]);
+    }
+
+    #[test]
+    fn test_warning_is_logged_once_per_drop() {
+        let metrics = Metrics::new();
+        let mut guard = DiskSpaceGuard::new(PathBuf::from("capture"), 100, 200, 1000, CaptureGate::new(), make_manifest())
+            .with_warning(300, &metrics);
+
+        assert!(!guard.check_warning(350));
+        assert!(guard.check_warning(250));
+        assert!(!guard.check_warning(150));
+        assert!(!guard.check_warning(350));
+        assert!(guard.check_warning(250));
+        assert_eq!(metrics.snapshot()["disk_space_warnings"], 2);
+        assert_eq!(metrics.gauge_snapshot()["disk_free_bytes"], 250);
+    }
+
+    #[test]
+    fn test_cleanup_deletes_oldest_finished_files() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_disk_cleanup", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(UPLOAD_MARKER_DIR)).unwrap();
+
+        // The manifest starts at 0, so the current session is BTCUSDT_19700101_000000
+        let files = [
+            ("BTCUSDT_20240101_000000.tape", 1000),
+            ("BTCUSDT_20240102_000000.tape", 1000),
+            ("BTCUSDT_20240103_000000.tape", 1000),
+            ("ETHUSDT_20240101_000000.tape", 1000),
+            ("BTCUSDT_19700101_000000.tape", 1000),
+        ];
+        for (index, (name, size)) in files.iter().enumerate() {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            file.set_len(*size).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(index as u64)).unwrap();
+        }
+        fs::write(dir.join(UPLOAD_MARKER_DIR).join("BTCUSDT_20240102_000000.tape"), "").unwrap();
+
+        let metrics = Metrics::new();
+        let guard = DiskSpaceGuard::new(dir.clone(), 100, 200, 1000, CaptureGate::new(), make_manifest())
+            .with_cleanup(10000, false, &metrics);
+        assert_eq!(guard.clean_up(1500).unwrap(), 2000);
+        assert!(!dir.join("BTCUSDT_20240101_000000.tape").exists());
+        assert!(!dir.join("BTCUSDT_20240102_000000.tape").exists());
+        assert!(!dir.join(UPLOAD_MARKER_DIR).join("BTCUSDT_20240102_000000.tape").exists());
+        assert!(dir.join("BTCUSDT_20240103_000000.tape").exists());
+
+        // Only uploaded files are deleted, the files of other instruments and the current session are kept
+        let guard = DiskSpaceGuard::new(dir.clone(), 100, 200, 1000, CaptureGate::new(), make_manifest())
+            .with_cleanup(10000, true, &metrics);
+        assert_eq!(guard.clean_up(5000).unwrap(), 0);
+        let guard = DiskSpaceGuard::new(dir.clone(), 100, 200, 1000, CaptureGate::new(), make_manifest())
+            .with_cleanup(10000, false, &metrics);
+        assert_eq!(guard.clean_up(5000).unwrap(), 1000);
+        assert!(dir.join("ETHUSDT_20240101_000000.tape").exists());
+        assert!(dir.join("BTCUSDT_19700101_000000.tape").exists());
+        assert_eq!(metrics.snapshot()["disk_cleanup_files"], 3);
+        assert_eq!(metrics.snapshot()["disk_cleanup_bytes"], 3000);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_operator_pause_is_independent_of_free_space() {
+        let gate = CaptureGate::new();
+        let mut guard = DiskSpaceGuard::new(PathBuf::from("capture"), 100, 200, 1000, gate.clone(), make_manifest());
+
+        gate.set_held(true);
+        assert!(gate.is_paused());
+
+        // Enough free space doesn't resume capture paused by the operator
+        assert!(!guard.check(250, 1000));
+        assert!(gate.is_paused());
+
+        assert!(guard.check(50, 2000));
+        gate.set_held(false);
+        assert!(gate.is_paused());
+
+        assert!(guard.check(250, 3000));
+        assert!(!gate.is_paused());
+    }
+}
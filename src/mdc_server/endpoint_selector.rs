@@ -0,0 +1,237 @@
+/// Weight of a new latency sample in the moving average of an endpoint
+const LATENCY_SMOOTHING: f64 = 0.3;
+/// Time in milliseconds, for which an endpoint is avoided after a failed session. It doubles with every further
+/// consecutive failure
+const DEGRADED_PERIOD: i64 = 30_000;
+/// Upper bound of the time in milliseconds, for which an endpoint is avoided
+const MAX_DEGRADED_PERIOD: i64 = 900_000;
+
+/// Measurements of one endpoint
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    /// Moving average of the time from the start of connecting until the connection is open, in milliseconds
+    connect_latency: Option<f64>,
+    /// Moving average of the time from the start of connecting until the first message, in milliseconds
+    first_message_latency: Option<f64>,
+    /// Number of consecutive failed sessions
+    failures: u32,
+    /// Time in milliseconds since the epoch, until which the endpoint is avoided
+    degraded_until: i64,
+}
+
+/// EndpointSelector chooses the endpoint of every connection attempt of a stream, which several endpoints serve
+/// (e.g. `stream.binance.com:9443`, `stream.binance.com:443` and `data-stream.binance.vision`)
+///
+/// Endpoints are ranked by the latency until the first message of their sessions. Endpoints, which haven't been measured
+/// yet, are tried first, so every endpoint gets a measurement. An endpoint, whose session fails (errors, stalls or is
+/// cycled due to poor health), is degraded for a period, which grows with its consecutive failures, and the stream fails
+/// over to the fastest of the other endpoints. If all endpoints are degraded, the one which recovers first is used
+#[derive(Debug)]
+pub struct EndpointSelector {
+    endpoints: Vec<Endpoint>,
+    current: usize,
+}
+
+impl EndpointSelector {
+    /// Create a new EndpointSelector
+    ///
+    /// # Arguments
+    /// * `urls` - URLs of the stream at every endpoint, the preferred one first. Must not be empty
+    pub fn new(urls: Vec<String>) -> Self {
+        assert!(!urls.is_empty(), "A stream needs at least one endpoint");
+        let endpoints = urls
+            .into_iter()
+            .map(|url| Endpoint {
+                url,
+                connect_latency: None,
+                first_message_latency: None,
+                failures: 0,
+                degraded_until: 0,
+            })
+            .collect();
+        Self { endpoints, current: 0 }
+    }
+
+    /// URL of the endpoint, which has been selected last
+    pub fn current(&self) -> &str {
+        &self.endpoints[self.current].url
+    }
+
+    /// Choose the endpoint of the next connection attempt
+    ///
+    /// # Arguments
+    /// * `now` - Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// URL of the chosen endpoint
+    pub fn select(&mut self, now: i64) -> &str {
+        // Unmeasured endpoints rank before all measured ones. On a tie the preferred endpoint wins
+        let available = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, endpoint)| endpoint.degraded_until <= now)
+            .min_by(|(_, a), (_, b)| {
+                a.first_message_latency.unwrap_or(-1.0).total_cmp(&b.first_message_latency.unwrap_or(-1.0))
+            })
+            .map(|(index, _)| index);
+
+        let selected = available.unwrap_or_else(|| {
+            self.endpoints
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, endpoint)| endpoint.degraded_until)
+                .map_or(0, |(index, _)| index)
+        });
+
+        if selected != self.current {
+            tracing::info!("Switching stream from endpoint '{}' to '{}'", self.current(), self.endpoints[selected].url);
+            self.current = selected;
+        }
+        self.current()
+    }
+
+    /// Whether another endpoint than the current one can take over right away
+    ///
+    /// # Arguments
+    /// * `now` - Current time in milliseconds since the epoch
+    pub fn can_fail_over(&self, now: i64) -> bool {
+        self.endpoints
+            .iter()
+            .enumerate()
+            .any(|(index, endpoint)| index != self.current && endpoint.degraded_until <= now)
+    }
+
+    /// Account the time from the start of connecting until the connection to the current endpoint is open
+    ///
+    /// # Arguments
+    /// * `latency` - Latency in milliseconds
+    pub fn on_connected(&mut self, latency: u64) {
+        let endpoint = &mut self.endpoints[self.current];
+        endpoint.connect_latency = Some(smooth(endpoint.connect_latency, latency));
+    }
+
+    /// Account the time from the start of connecting until the first message from the current endpoint
+    ///
+    /// # Arguments
+    /// * `latency` - Latency in milliseconds
+    pub fn on_first_message(&mut self, latency: u64) {
+        let endpoint = &mut self.endpoints[self.current];
+        endpoint.first_message_latency = Some(smooth(endpoint.first_message_latency, latency));
+        tracing::debug!(
+            "Endpoint '{}': connect latency '{:.0}' ms, first message latency '{}' ms",
+            endpoint.url,
+            endpoint.connect_latency.unwrap_or_default(),
+            latency
+        );
+    }
+
+    /// Account the end of a session with the current endpoint
+    ///
+    /// # Arguments
+    /// * `now` - Current time in milliseconds since the epoch
+    /// * `failed` - Whether the session has failed, rather than been closed or rotated
+    pub fn on_session_end(&mut self, now: i64, failed: bool) {
+        let endpoint = &mut self.endpoints[self.current];
+        if !failed {
+            endpoint.failures = 0;
+            return;
+        }
+
+        endpoint.failures += 1;
+        let period = DEGRADED_PERIOD
+            .saturating_mul(1 << (endpoint.failures - 1).min(16))
+            .min(MAX_DEGRADED_PERIOD);
+        endpoint.degraded_until = now + period;
+    }
+}
+
+/// Add the sample to the moving average
+fn smooth(average: Option<f64>, sample: u64) -> f64 {
+    let sample = sample as f64;
+    average.map_or(sample, |average| average + LATENCY_SMOOTHING * (sample - average))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selector() -> EndpointSelector {
+        EndpointSelector::new(vec![
+            "wss://stream.binance.com:9443/ws/btcusdt@depth".to_string(),
+            "wss://stream.binance.com:443/ws/btcusdt@depth".to_string(),
+            "wss://data-stream.binance.vision/ws/btcusdt@depth".to_string(),
+        ])
+    }
+
+    /// Connect to the selected endpoint and receive the first message after the latency
+    fn measure(selector: &mut EndpointSelector, now: i64, latency: u64) -> String {
+        let url = selector.select(now).to_string();
+        selector.on_connected(latency / 2);
+        selector.on_first_message(latency);
+        selector.on_session_end(now, false);
+        url
+    }
+
+    #[test]
+    fn test_measures_every_endpoint_and_prefers_the_fastest() {
+        let mut selector = selector();
+        assert_eq!(measure(&mut selector, 0, 300), "wss://stream.binance.com:9443/ws/btcusdt@depth");
+        assert_eq!(measure(&mut selector, 0, 100), "wss://stream.binance.com:443/ws/btcusdt@depth");
+        assert_eq!(measure(&mut selector, 0, 200), "wss://data-stream.binance.vision/ws/btcusdt@depth");
+
+        assert_eq!(selector.select(0), "wss://stream.binance.com:443/ws/btcusdt@depth");
+
+        // The fastest endpoint slows down and falls behind the next one
+        measure(&mut selector, 0, 400);
+        measure(&mut selector, 0, 400);
+        assert_eq!(selector.select(0), "wss://data-stream.binance.vision/ws/btcusdt@depth");
+    }
+
+    #[test]
+    fn test_fails_over_from_a_degraded_endpoint() {
+        let mut selector = selector();
+        for latency in [100, 200, 300] {
+            measure(&mut selector, 0, latency);
+        }
+        assert_eq!(selector.select(0), "wss://stream.binance.com:9443/ws/btcusdt@depth");
+
+        selector.on_session_end(1_000, true);
+        assert!(selector.can_fail_over(1_000));
+        assert_eq!(selector.select(1_000), "wss://stream.binance.com:443/ws/btcusdt@depth");
+
+        // The degraded endpoint is preferred again after its period
+        selector.on_session_end(2_000, false);
+        assert_eq!(selector.select(1_000 + DEGRADED_PERIOD), "wss://stream.binance.com:9443/ws/btcusdt@depth");
+
+        // Consecutive failures extend the period
+        selector.on_session_end(40_000, true);
+        assert_eq!(selector.select(40_000), "wss://stream.binance.com:443/ws/btcusdt@depth");
+        assert_eq!(selector.select(40_000 + 2 * DEGRADED_PERIOD - 1), "wss://stream.binance.com:443/ws/btcusdt@depth");
+        assert_eq!(selector.select(40_000 + 2 * DEGRADED_PERIOD), "wss://stream.binance.com:9443/ws/btcusdt@depth");
+    }
+
+    #[test]
+    fn test_uses_the_first_recovering_endpoint_if_all_are_degraded() {
+        let mut selector = selector();
+        selector.select(0);
+        selector.on_session_end(0, true);
+        selector.select(0);
+        selector.on_session_end(0, true);
+        selector.on_session_end(DEGRADED_PERIOD, true);
+        selector.select(0);
+        selector.on_session_end(0, true);
+
+        assert!(!selector.can_fail_over(0));
+        assert_eq!(selector.select(1), "wss://stream.binance.com:9443/ws/btcusdt@depth");
+    }
+
+    #[test]
+    fn test_single_endpoint() {
+        let mut selector = EndpointSelector::new(vec!["wss://ws.okx.com:8443/ws/v5/public".to_string()]);
+        selector.on_session_end(0, true);
+        assert!(!selector.can_fail_over(0));
+        assert_eq!(selector.select(0), "wss://ws.okx.com:8443/ws/v5/public");
+    }
+}
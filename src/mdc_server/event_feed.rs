@@ -0,0 +1,252 @@
+use std::sync::Arc;
+use bytes::Bytes;
+use chrono::Utc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_server::wire_format::EventEncoder;
+use crate::mdc_server::log_context::spawn;
+
+/// Number of encoded events buffered per client. A client, which falls further behind, skips events
+const CLIENT_BUFFER: usize = 4096;
+
+/// EventFeed encodes the events of the pipeline into the protobuf wire format and publishes them to the
+/// clients of the event feed server
+///
+/// Every event is encoded once, regardless of the number of connected clients
+pub struct EventFeed {
+    encoder: EventEncoder,
+    depth_channel: mpsc::Receiver<MarketEvent>,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    price_channel: mpsc::Receiver<MarketEvent>,
+    auxiliary_channel: mpsc::Receiver<MarketEvent>,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    frames: broadcast::Sender<Bytes>,
+    published: Counter,
+    skipped: Counter,
+}
+
+impl EventFeed {
+    /// Create a new EventFeed
+    ///
+    /// # Arguments
+    /// * `encoder` - Encoder of the events
+    /// * `depth_channel` - Receiver for the sequenced DepthUpdate and DepthSnapshot events
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    /// * `price_channel` - Receiver for MarketEvent messages containing PriceUpdates
+    /// * `auxiliary_channel` - Receiver for MarketEvent messages containing KlineEvents, AggTradeEvents,
+    ///   TickerEvents, MiniTickerEvents, MarkPriceEvents, LiquidationEvents and PartialBookEvents
+    /// * `book_channel` - Receiver for OrderBook messages
+    /// * `metrics` - Registry of the published and skipped event counters
+    pub fn new(
+        encoder: EventEncoder,
+        depth_channel: mpsc::Receiver<MarketEvent>,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+        price_channel: mpsc::Receiver<MarketEvent>,
+        auxiliary_channel: mpsc::Receiver<MarketEvent>,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+        metrics: &Metrics,
+    ) -> Self {
+        Self {
+            encoder,
+            depth_channel,
+            trade_channel,
+            price_channel,
+            auxiliary_channel,
+            book_channel,
+            frames: broadcast::channel(CLIENT_BUFFER).0,
+            published: metrics.counter("event_feed_published"),
+            skipped: metrics.counter("event_feed_skipped"),
+        }
+    }
+
+    /// Create the server, which accepts the clients of the feed on the given listener
+    pub fn server(&self, listener: TcpListener) -> EventFeedServer {
+        EventFeedServer { listener, frames: self.frames.clone(), skipped: self.skipped.clone() }
+    }
+
+    fn publish(&self, frame: Bytes) {
+        self.published.increment(1);
+        // Sending fails only if there are no clients
+        let _ = self.frames.send(frame);
+    }
+
+    /// Run the EventFeed as an asynchronous task
+    ///
+    /// This method will continuously publish events from all channels until they are closed
+    pub async fn run(mut self) {
+        let mut sequence = 0;
+
+        loop {
+            tokio::select! {
+                Some(event) = self.depth_channel.recv() => self.publish_event(&event),
+                Some(event) = self.trade_channel.recv() => self.publish_event(&event),
+                Some(event) = self.price_channel.recv() => self.publish_event(&event),
+                Some(event) = self.auxiliary_channel.recv() => self.publish_event(&event),
+                Some(book) = self.book_channel.recv() => {
+                    sequence += 1;
+                    let frame = self.encoder.encode_book(Utc::now().timestamp_millis(), sequence, &book);
+                    self.publish(frame);
+                }
+                else => break,
+            }
+        }
+    }
+
+    fn publish_event(&self, event: &MarketEvent) {
+        match self.encoder.encode_event(Utc::now().timestamp_millis(), event) {
+            Some(frame) => self.publish(frame),
+            None => tracing::warn!("Unexpected event in event feed: '{}'", event),
+        }
+    }
+}
+
+/// EventFeedServer accepts TCP clients of the event feed
+///
+/// Each client receives a stream of length-delimited `mdc.events.v1.Event` messages, starting with the first
+/// event published after it has connected. The server doesn't read anything from the clients
+pub struct EventFeedServer {
+    listener: TcpListener,
+    frames: broadcast::Sender<Bytes>,
+    skipped: Counter,
+}
+
+impl EventFeedServer {
+    /// Run the EventFeedServer as an asynchronous task
+    pub async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, address)) => {
+                    tracing::info!("Event feed client connected: '{}'", address);
+                    spawn(Self::serve(stream, self.frames.subscribe(), self.skipped.clone()));
+                }
+                Err(e) => tracing::warn!("Failed to accept event feed client. Details: '{}'", e),
+            }
+        }
+    }
+
+    /// Write the published events into the client socket until the client disconnects or the feed stops
+    async fn serve(mut stream: TcpStream, mut frames: broadcast::Receiver<Bytes>, skipped: Counter) {
+        loop {
+            match frames.recv().await {
+                Ok(frame) => {
+                    if let Err(e) = stream.write_all(&frame).await {
+                        tracing::info!("Event feed client disconnected. Details: '{}'", e);
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    tracing::debug!("Event feed client is too slow. Skipped '{}' events", count);
+                    skipped.increment(count);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use tokio::io::AsyncReadExt;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot, TradeEvent};
+    use crate::mdc_server::wire_format::proto::event::Payload;
+    use crate::mdc_server::wire_format::proto::{Event, PriceLevel};
+
+    async fn read_event(stream: &mut TcpStream) -> Event {
+        let mut delimiter = Vec::new();
+        loop {
+            let byte = stream.read_u8().await.unwrap();
+            delimiter.push(byte);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let mut message = vec![0; prost::decode_length_delimiter(delimiter.as_slice()).unwrap()];
+        stream.read_exact(&mut message).await.unwrap();
+        Event::decode(message.as_slice()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_clients_receive_encoded_events() {
+        let metrics = Metrics::new();
+        let (depth_sender, depth_receiver) = mpsc::channel(10);
+        let (trade_sender, trade_receiver) = mpsc::channel(10);
+        let (_price_sender, price_receiver) = mpsc::channel(10);
+        let (_auxiliary_sender, auxiliary_receiver) = mpsc::channel(10);
+        let (book_sender, book_receiver) = mpsc::channel(10);
+        let feed = EventFeed::new(
+            EventEncoder::new("binance", "BTCUSDT", 1),
+            depth_receiver,
+            trade_receiver,
+            price_receiver,
+            auxiliary_receiver,
+            book_receiver,
+            &metrics
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let frames = feed.frames.clone();
+        tokio::spawn(feed.server(listener).run());
+        tokio::spawn(feed.run());
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        while frames.receiver_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let snapshot = DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+            ],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(3.0) }],
+        };
+        depth_sender.send(MarketEvent::DepthSnapshot(snapshot.clone())).await.unwrap();
+        let event = read_event(&mut client).await;
+        assert_eq!(event.version, 1);
+        assert_eq!(event.exchange, "binance");
+        assert_eq!(event.symbol, "BTCUSDT");
+        let Some(Payload::DepthSnapshot(encoded)) = event.payload else {
+            panic!("Unexpected payload: '{:?}'", event.payload);
+        };
+        assert_eq!(encoded.last_update_id, 1);
+        assert_eq!(encoded.bids.len(), 2);
+
+        trade_sender.send(MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 7,
+            price: 100.5,
+            quantity: 0.1,
+            trade_time: 999,
+            is_market_maker: true,
+            ignore: true,
+            received: None,
+        })).await.unwrap();
+        let Some(Payload::Trade(trade)) = read_event(&mut client).await.payload else {
+            panic!("Trade is expected");
+        };
+        assert_eq!(trade.trade_id, 7);
+        assert!(trade.is_buyer_maker);
+
+        book_sender.send(Arc::new(OrderBook::new(&snapshot))).await.unwrap();
+        let Some(Payload::OrderBook(book)) = read_event(&mut client).await.payload else {
+            panic!("Order book is expected");
+        };
+        assert_eq!(book.sequence, 1);
+        assert_eq!(book.bids, vec![PriceLevel { price: 100.0, quantity: 1.0 }]);
+        assert_eq!(book.asks, vec![PriceLevel { price: 101.0, quantity: 3.0 }]);
+
+        assert_eq!(metrics.snapshot()["event_feed_published"], 3);
+    }
+}
@@ -0,0 +1,489 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use crate::mdc_core::models::{MarketEvent, ScriptFields};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+#[cfg(feature = "scripting")]
+pub use rhai_scripts::EventScripts;
+#[cfg(not(feature = "scripting"))]
+pub use disabled::EventScripts;
+
+/// Kind of an event, which scripts can process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptedEvent {
+    Trade,
+    AggTrade,
+    BookTicker,
+}
+
+impl ScriptedEvent {
+    /// Kind of the event, `None` if scripts don't process it
+    pub fn of(event: &MarketEvent) -> Option<Self> {
+        match event {
+            MarketEvent::TradeEvent(_) => Some(ScriptedEvent::Trade),
+            MarketEvent::AggTradeEvent(_) => Some(ScriptedEvent::AggTrade),
+            MarketEvent::PriceUpdate(_) => Some(ScriptedEvent::BookTicker),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptedEvent::Trade => "trade",
+            ScriptedEvent::AggTrade => "agg_trade",
+            ScriptedEvent::BookTicker => "book_ticker",
+        }
+    }
+}
+
+/// A script, which filters, modifies or enriches events before they reach the consumers of the pipeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventScript {
+    /// Name of the script, written into the fields it computes and into its log messages
+    pub name: String,
+    /// Kinds of the events, which the script processes. All kinds if empty
+    #[serde(default)]
+    pub events: Vec<ScriptedEvent>,
+    /// Source of the script
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Path of a file with the source of the script, if `source` is not set
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+impl EventScript {
+    /// Source of the script, read from `file` if it is not inline
+    pub fn load(&self) -> Result<String> {
+        match (&self.source, &self.file) {
+            (Some(source), None) => Ok(source.clone()),
+            (None, Some(file)) => std::fs::read_to_string(file)
+                .map_err(|e| anyhow!("Failed to read script '{}' from '{}'. Details: '{}'", self.name, file, e)),
+            _ => Err(anyhow!("Script '{}' must have either 'source' or 'file'", self.name)),
+        }
+    }
+
+    /// Whether the script processes events of the kind
+    pub fn processes(&self, kind: ScriptedEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// Result of running the scripts on an event
+#[derive(Debug, Default)]
+pub struct Processed {
+    /// The possibly modified event, `None` if a script has dropped it
+    pub event: Option<MarketEvent>,
+    /// Fields computed by the scripts
+    pub fields: Vec<ScriptFields>,
+    /// Number of scripts, which have failed. A failed script leaves the event unchanged
+    pub errors: u64,
+}
+
+#[cfg(feature = "scripting")]
+mod rhai_scripts {
+    use anyhow::{anyhow, Result};
+    use rhai::{Dynamic, Engine, Map, Scope, AST};
+    use crate::mdc_core::models::{MarketEvent, ScriptFields};
+    use super::{EventScript, Processed, ScriptedEvent};
+
+    /// Maximum number of operations of a single run of a script, which stops runaway loops
+    const MAX_OPERATIONS: u64 = 1_000_000;
+
+    /// The compiled event scripts of a pipeline, run by the Rhai engine
+    pub struct EventScripts {
+        engine: Engine,
+        scripts: Vec<(EventScript, AST)>,
+    }
+
+    fn number(value: &Dynamic) -> Option<f64> {
+        value.as_float().ok().or_else(|| value.as_int().ok().map(|value| value as f64))
+    }
+
+    /// The event as a Rhai object map
+    fn to_map(event: &MarketEvent, kind: ScriptedEvent) -> Map {
+        let mut map = Map::new();
+        map.insert("kind".into(), kind.name().into());
+        match event {
+            MarketEvent::TradeEvent(trade) => {
+                map.insert("symbol".into(), trade.symbol.clone().into());
+                map.insert("trade_id".into(), (trade.trade_id as i64).into());
+                map.insert("price".into(), trade.price.into());
+                map.insert("quantity".into(), trade.quantity.into());
+                map.insert("time".into(), (trade.trade_time as i64).into());
+                map.insert("buyer_maker".into(), trade.is_market_maker.into());
+            }
+            MarketEvent::AggTradeEvent(trade) => {
+                map.insert("symbol".into(), trade.symbol.clone().into());
+                map.insert("trade_id".into(), (trade.agg_trade_id as i64).into());
+                map.insert("first_trade_id".into(), (trade.first_trade_id as i64).into());
+                map.insert("last_trade_id".into(), (trade.last_trade_id as i64).into());
+                map.insert("price".into(), trade.price.into());
+                map.insert("quantity".into(), trade.quantity.into());
+                map.insert("time".into(), (trade.trade_time as i64).into());
+                map.insert("buyer_maker".into(), trade.is_market_maker.into());
+            }
+            MarketEvent::PriceUpdate(update) => {
+                map.insert("symbol".into(), update.symbol.clone().into());
+                map.insert("update_id".into(), (update.update_id as i64).into());
+                map.insert("bid_price".into(), update.best_bid_price.into());
+                map.insert("bid_quantity".into(), update.best_bid_quantity.into());
+                map.insert("ask_price".into(), update.best_ask_price.into());
+                map.insert("ask_quantity".into(), update.best_ask_quantity.into());
+            }
+            _ => {}
+        }
+        map
+    }
+
+    /// Write the fields of the map, which scripts may change, back into the event: the symbol, prices and quantities
+    fn apply(event: &mut MarketEvent, map: &Map) -> Result<()> {
+        let value = |name: &str| -> Result<Option<f64>> {
+            map.get(name)
+                .map(|value| number(value).ok_or_else(|| anyhow!("'{}' must be a number, not '{}'", name, value.type_name())))
+                .transpose()
+        };
+        let symbol = map.get("symbol")
+            .map(|value| value.clone().into_string().map_err(|kind| anyhow!("'symbol' must be a string, not '{}'", kind)))
+            .transpose()?;
+
+        match event {
+            MarketEvent::TradeEvent(trade) => {
+                trade.price = value("price")?.unwrap_or(trade.price);
+                trade.quantity = value("quantity")?.unwrap_or(trade.quantity);
+                trade.symbol = symbol.unwrap_or_else(|| trade.symbol.clone());
+            }
+            MarketEvent::AggTradeEvent(trade) => {
+                trade.price = value("price")?.unwrap_or(trade.price);
+                trade.quantity = value("quantity")?.unwrap_or(trade.quantity);
+                trade.symbol = symbol.unwrap_or_else(|| trade.symbol.clone());
+            }
+            MarketEvent::PriceUpdate(update) => {
+                update.best_bid_price = value("bid_price")?.unwrap_or(update.best_bid_price);
+                update.best_bid_quantity = value("bid_quantity")?.unwrap_or(update.best_bid_quantity);
+                update.best_ask_price = value("ask_price")?.unwrap_or(update.best_ask_price);
+                update.best_ask_quantity = value("ask_quantity")?.unwrap_or(update.best_ask_quantity);
+                update.symbol = symbol.unwrap_or_else(|| update.symbol.clone());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    impl EventScripts {
+        /// Compile the scripts
+        ///
+        /// # Returns
+        /// `None` if no scripts are configured
+        pub fn compile(scripts: &[EventScript]) -> Result<Option<Self>> {
+            if scripts.is_empty() {
+                return Ok(None);
+            }
+
+            let mut engine = Engine::new();
+            engine.set_max_operations(MAX_OPERATIONS);
+
+            let scripts = scripts
+                .iter()
+                .map(|script| {
+                    let ast = engine
+                        .compile(script.load()?)
+                        .map_err(|e| anyhow!("Failed to compile script '{}'. Details: '{}'", script.name, e))?;
+                    Ok((script.clone(), ast))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Some(Self { engine, scripts }))
+        }
+
+        /// Run the script on the event map
+        ///
+        /// # Returns
+        /// The map of the event, or `None` if the script drops the event
+        fn run(&self, ast: &AST, map: Map) -> Result<Option<Map>> {
+            let mut scope = Scope::new();
+            scope.push("event", map.clone());
+            let result: Dynamic = self.engine.eval_ast_with_scope(&mut scope, ast).map_err(|e| anyhow!("{}", e))?;
+
+            if result.is_unit() {
+                return Ok(None);
+            }
+            if let Ok(keep) = result.as_bool() {
+                return Ok(keep.then_some(map));
+            }
+            result
+                .try_cast::<Map>()
+                .map(Some)
+                .ok_or_else(|| anyhow!("Script must return the event, 'true' or 'false'"))
+        }
+
+        /// Run the scripts on the event in their order. Every script sees the event as changed by the scripts before it
+        pub fn process(&self, event: MarketEvent) -> Processed {
+            let Some(kind) = ScriptedEvent::of(&event) else {
+                return Processed { event: Some(event), ..Default::default() };
+            };
+            let mut processed = Processed { event: Some(event), ..Default::default() };
+
+            for (script, ast) in &self.scripts {
+                let Some(event) = processed.event.as_mut().filter(|_| script.processes(kind)) else {
+                    continue;
+                };
+
+                let map = to_map(event, kind);
+                let result = self.run(ast, map.clone()).and_then(|output| {
+                    let Some(output) = output else {
+                        return Ok(None);
+                    };
+                    let mut changed = event.clone();
+                    apply(&mut changed, &output)?;
+                    *event = changed;
+                    let fields = output
+                        .into_iter()
+                        .filter(|(name, _)| !map.contains_key(name.as_str()))
+                        .map(|(name, value)| Ok((name.to_string(), rhai::serde::from_dynamic(&value)?)))
+                        .collect::<Result<serde_json::Map<_, _>, Box<rhai::EvalAltResult>>>()
+                        .map_err(|e| anyhow!("Computed field can't be converted to JSON. Details: '{}'", e))?;
+                    Ok(Some(fields))
+                });
+
+                match result {
+                    Ok(None) => processed.event = None,
+                    Ok(Some(fields)) if !fields.is_empty() => processed.fields.push(ScriptFields {
+                        script: script.name.clone(),
+                        kind: kind.name().to_string(),
+                        event_time: event.event_time().unwrap_or_default(),
+                        fields,
+                    }),
+                    Ok(Some(_)) => {}
+                    Err(e) => {
+                        tracing::warn!("Script '{}' failed on event '{}'. Details: '{}'", script.name, event, e);
+                        processed.errors += 1;
+                    }
+                }
+            }
+            processed
+        }
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+mod disabled {
+    use anyhow::{anyhow, Result};
+    use crate::mdc_core::models::MarketEvent;
+    use super::{EventScript, Processed};
+
+    /// Event scripts are not available without the `scripting` feature
+    pub enum EventScripts {}
+
+    impl EventScripts {
+        /// Fail if any scripts are configured, since they can't be run
+        pub fn compile(scripts: &[EventScript]) -> Result<Option<Self>> {
+            match scripts.is_empty() {
+                true => Ok(None),
+                false => Err(anyhow!("Event scripts require MDC built with the 'scripting' feature")),
+            }
+        }
+
+        pub fn process(&self, _event: MarketEvent) -> Processed {
+            match *self {}
+        }
+    }
+}
+
+/// EventScriptStage runs the event scripts on the trades, the best bid/ask updates and the auxiliary events before
+/// they are fanned out to the consumers of the pipeline
+///
+/// Dropped events are counted by `script_dropped_events`, failed script runs by `script_errors`. The fields computed
+/// by the scripts are sent as `ScriptFields` events along with the auxiliary events
+pub struct EventScriptStage {
+    runner: ScriptRunner,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    trade_output: mpsc::Sender<MarketEvent>,
+    price_channel: mpsc::Receiver<MarketEvent>,
+    price_output: mpsc::Sender<MarketEvent>,
+    auxiliary_channel: mpsc::Receiver<MarketEvent>,
+}
+
+struct ScriptRunner {
+    scripts: EventScripts,
+    auxiliary_output: mpsc::Sender<MarketEvent>,
+    dropped: Counter,
+    errors: Counter,
+}
+
+impl ScriptRunner {
+    async fn process(&self, event: MarketEvent, output: &mpsc::Sender<MarketEvent>) -> Result<()> {
+        let processed = self.scripts.process(event);
+        self.errors.increment(processed.errors);
+
+        match processed.event {
+            Some(event) => output.send(event).await?,
+            None => self.dropped.increment(1),
+        }
+        for fields in processed.fields {
+            self.auxiliary_output.send(MarketEvent::ScriptFields(fields)).await?;
+        }
+        Ok(())
+    }
+}
+
+impl EventScriptStage {
+    /// Create a new EventScriptStage
+    ///
+    /// # Arguments
+    /// * `scripts` - The compiled scripts
+    /// * `trade` - Receiver and sender of MarketEvent messages containing TradeEvents
+    /// * `price` - Receiver and sender of MarketEvent messages containing PriceUpdates
+    /// * `auxiliary` - Receiver and sender of MarketEvent messages containing the auxiliary events
+    /// * `metrics` - Registry of the script counters
+    pub fn new(
+        scripts: EventScripts,
+        trade: (mpsc::Receiver<MarketEvent>, mpsc::Sender<MarketEvent>),
+        price: (mpsc::Receiver<MarketEvent>, mpsc::Sender<MarketEvent>),
+        auxiliary: (mpsc::Receiver<MarketEvent>, mpsc::Sender<MarketEvent>),
+        metrics: &Metrics,
+    ) -> Self {
+        Self {
+            runner: ScriptRunner {
+                scripts,
+                auxiliary_output: auxiliary.1,
+                dropped: metrics.counter("script_dropped_events"),
+                errors: metrics.counter("script_errors"),
+            },
+            trade_channel: trade.0,
+            trade_output: trade.1,
+            price_channel: price.0,
+            price_output: price.1,
+            auxiliary_channel: auxiliary.0,
+        }
+    }
+
+    /// Run the EventScriptStage as an asynchronous task
+    ///
+    /// This method will continuously process the events from all channels until they are closed
+    pub async fn run(mut self) {
+        let runner = &self.runner;
+        loop {
+            let result = tokio::select! {
+                Some(event) = self.trade_channel.recv() => runner.process(event, &self.trade_output).await,
+                Some(event) = self.price_channel.recv() => runner.process(event, &self.price_output).await,
+                Some(event) = self.auxiliary_channel.recv() => runner.process(event, &runner.auxiliary_output).await,
+                else => break,
+            };
+
+            if let Err(e) = result {
+                tracing::error!("Failed to send scripted event. Details: '{}'", e);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_from_yaml() {
+        let scripts: Vec<EventScript> = serde_yaml::from_str(r#"
+- name: large_trades
+  events: [trade, agg_trade]
+  source: "event.quantity >= 0.1"
+- name: notional
+  file: /etc/mdc/notional.rhai
+"#).unwrap();
+        assert_eq!(scripts[0].events, vec![ScriptedEvent::Trade, ScriptedEvent::AggTrade]);
+        assert_eq!(scripts[0].load().unwrap(), "event.quantity >= 0.1");
+        assert!(scripts[1].events.is_empty());
+        assert!(scripts[1].load().is_err());
+        assert!(EventScripts::compile(&[]).unwrap().is_none());
+        assert_eq!(EventScripts::compile(&scripts[..1]).is_ok(), cfg!(feature = "scripting"));
+    }
+
+    #[cfg(feature = "scripting")]
+    #[tokio::test]
+    async fn test_script_stage() {
+        use crate::mdc_core::models::{PriceUpdate, TradeEvent};
+
+        let scripts = serde_yaml::from_str::<Vec<EventScript>>(r#"
+- name: large_trades
+  events: [trade]
+  source: "event.quantity >= 0.1"
+- name: notional
+  events: [trade]
+  source: |
+    event.notional = event.price * event.quantity;
+    event.symbol = "BTC-USDT";
+    event
+- name: broken
+  events: [book_ticker]
+  source: "event.bid_price = \"x\"; event"
+"#).unwrap();
+        let scripts = EventScripts::compile(&scripts).unwrap().unwrap();
+
+        let (trade_tx, trade_rx) = mpsc::channel(10);
+        let (trade_out_tx, mut trade_out_rx) = mpsc::channel(10);
+        let (price_tx, price_rx) = mpsc::channel(10);
+        let (price_out_tx, mut price_out_rx) = mpsc::channel(10);
+        let (_auxiliary_tx, auxiliary_rx) = mpsc::channel(10);
+        let (auxiliary_out_tx, mut auxiliary_out_rx) = mpsc::channel(10);
+        let metrics = Metrics::new();
+        let stage = EventScriptStage::new(
+            scripts,
+            (trade_rx, trade_out_tx),
+            (price_rx, price_out_tx),
+            (auxiliary_rx, auxiliary_out_tx),
+            &metrics,
+        );
+        let task = tokio::spawn(stage.run());
+
+        let trade = |trade_id: u64, quantity: f64| MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id,
+            price: 100.0,
+            quantity,
+            trade_time: 1000,
+            is_market_maker: false,
+            ignore: false,
+            received: None,
+        });
+        trade_tx.send(trade(1, 0.01)).await.unwrap();
+        trade_tx.send(trade(2, 0.5)).await.unwrap();
+        price_tx.send(MarketEvent::PriceUpdate(PriceUpdate {
+            update_id: 1,
+            symbol: "BTCUSDT".to_string(),
+            best_bid_price: 99.0,
+            best_bid_quantity: 1.0,
+            best_ask_price: 101.0,
+            best_ask_quantity: 1.0,
+            received: None,
+        })).await.unwrap();
+
+        let Some(MarketEvent::TradeEvent(trade)) = trade_out_rx.recv().await else {
+            panic!("A trade is expected");
+        };
+        assert_eq!((trade.trade_id, trade.symbol.as_str()), (2, "BTC-USDT"));
+
+        let Some(MarketEvent::ScriptFields(fields)) = auxiliary_out_rx.recv().await else {
+            panic!("Script fields are expected");
+        };
+        assert_eq!((fields.script.as_str(), fields.kind.as_str(), fields.event_time), ("notional", "trade", 1000));
+        assert_eq!(fields.fields["notional"], serde_json::json!(50.0));
+
+        // A failed script leaves the event unchanged
+        let Some(MarketEvent::PriceUpdate(update)) = price_out_rx.recv().await else {
+            panic!("A best bid/ask update is expected");
+        };
+        assert_eq!(update.best_bid_price, 99.0);
+
+        let counters = metrics.snapshot();
+        assert_eq!((counters["script_dropped_events"], counters["script_errors"]), (1, 1));
+
+        drop((trade_tx, price_tx, _auxiliary_tx));
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::sync::Arc;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::mdc_server::binance_connector::{BinanceConnector, BinanceFuturesConnector};
+use crate::mdc_server::okx_connector::OkxConnector;
+use crate::mdc_server::bitfinex_connector::BitfinexConnector;
+use crate::mdc_server::deribit_connector::DeribitConnector;
+use crate::mdc_server::config::Config;
+use crate::mdc_core::models::{ExchangeInfo, FromJson, KlineInterval, SymbolMetadata};
+use crate::mdc_core::sequencing::SequencingRules;
+
+/// Supported exchanges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Exchange {
+    #[default]
+    Binance,
+    BinanceFutures,
+    Okx,
+    Bitfinex,
+    Deribit,
+}
+
+impl Exchange {
+    /// The symbol as the exchange names it
+    ///
+    /// Binance and OKX symbols are case-insensitive and uppercased. Bitfinex and Deribit symbols are case-sensitive
+    /// (e.g. "tBTCUSD") and kept verbatim
+    pub fn normalize_symbol(self, symbol: &str) -> String {
+        match self {
+            Exchange::Binance | Exchange::BinanceFutures | Exchange::Okx => symbol.to_uppercase(),
+            Exchange::Bitfinex | Exchange::Deribit => symbol.to_string(),
+        }
+    }
+}
+
+/// Kinds of real-time market data streams, which can be subscribed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Depth,
+    /// Partial book of the given number of top levels per side, sent as a whole on every update
+    PartialDepth(u64),
+    /// Partial book captured as it is, at the given update interval in milliseconds (100 or 1000)
+    PartialBook { levels: u64, interval: u64 },
+    Trade,
+    Price,
+    /// Best bid/ask updates of all symbols of the exchange over a single stream
+    AllMarketPrice,
+    AggTrade,
+    Kline(KlineInterval),
+    Ticker,
+    MiniTicker,
+    MarkPrice,
+    Liquidation,
+}
+
+impl fmt::Display for StreamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamKind::Depth => write!(f, "depth"),
+            StreamKind::PartialDepth(_) => write!(f, "partial_depth"),
+            StreamKind::PartialBook { .. } => write!(f, "partial_book"),
+            StreamKind::Trade => write!(f, "trade"),
+            StreamKind::Price => write!(f, "price"),
+            StreamKind::AllMarketPrice => write!(f, "all_market_price"),
+            StreamKind::AggTrade => write!(f, "agg_trade"),
+            StreamKind::Kline(interval) => write!(f, "kline_{}", interval),
+            StreamKind::Ticker => write!(f, "ticker"),
+            StreamKind::MiniTicker => write!(f, "mini_ticker"),
+            StreamKind::MarkPrice => write!(f, "mark_price"),
+            StreamKind::Liquidation => write!(f, "liquidation"),
+        }
+    }
+}
+
+/// Response of the Binance server time endpoint
+#[derive(Debug, Deserialize)]
+struct ServerTime {
+    #[serde(rename = "serverTime")]
+    server_time: i64,
+}
+
+/// Venue-specific part of the capture pipeline
+///
+/// The connector describes where market data of a venue comes from (WebSocket subscriptions and REST endpoints)
+/// and how depth updates have to be sequenced. The rest of the pipeline is venue agnostic
+pub trait ExchangeConnector: Send + Sync {
+    /// Name of the venue, used in logs and capture artifacts
+    fn name(&self) -> &str;
+
+    /// Name of the stream of the given kind for the instrument, or `None` if the venue doesn't provide it
+    ///
+    /// Messages of a combined stream are tagged with this name
+    fn stream_name(&self, kind: StreamKind, instrument: &str) -> Option<String>;
+
+    /// WebSocket URL of the stream of the given kind for the instrument, or `None` if the venue doesn't provide it
+    fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String>;
+
+    /// Text frames, which subscribe to the stream of the given kind after connecting to its URL (sent in order),
+    /// or none if the URL already selects the stream
+    fn subscription(&self, _kind: StreamKind, _instrument: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// WebSocket URL of a single connection, which multiplexes the streams with the given names,
+    /// or `None` if the venue doesn't provide combined streams
+    fn combined_stream_url(&self, names: &[String]) -> Option<String>;
+
+    /// REST URL of an order book snapshot request with the given depth limit, or `None` if the depth stream
+    /// starts with a snapshot itself. A snapshot is requested by reconnecting the depth stream in that case
+    fn snapshot_url(&self, instrument: &str, limit: u64) -> Option<String>;
+
+    /// Request weight of an order book snapshot request with the given depth limit
+    fn snapshot_weight(&self, limit: u64) -> u64;
+
+    /// Parameters of the WebSocket API `depth` request with the given depth limit, if the venue serves snapshots
+    /// over its WebSocket API (see `SnapshotSource::WsApi`)
+    fn ws_api_snapshot_params(&self, _instrument: &str, _limit: u64) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// REST URL of the symbol metadata request
+    fn exchange_info_url(&self, instrument: &str) -> String;
+
+    /// REST URL of the server time request, or `None` if the venue doesn't provide the server time
+    fn server_time_url(&self) -> Option<String>;
+
+    /// Server time in milliseconds since epoch from the response of the server time request
+    ///
+    /// The default implementation parses the Binance response (`{"serverTime": <milliseconds since epoch>}`)
+    fn parse_server_time(&self, response: &str) -> Result<i64> {
+        let server_time = ServerTime::from_json(response).context("Failed to parse server time")?;
+        Ok(server_time.server_time)
+    }
+
+    /// Metadata of the symbol from the response of the symbol metadata request
+    ///
+    /// The default implementation parses the Binance exchangeInfo response
+    fn parse_symbol_metadata(&self, symbol: &str, response: &str) -> Result<SymbolMetadata> {
+        let exchange_info = ExchangeInfo::from_json(response).context("Failed to parse exchangeInfo")?;
+
+        exchange_info
+            .symbols
+            .into_iter()
+            .find(|info| info.symbol == symbol)
+            .map(SymbolMetadata::from)
+            .ok_or_else(|| anyhow!("Symbol '{}' is not listed in exchangeInfo", symbol))
+    }
+
+    /// Whether the symbol is open for trading according to its metadata, so its market data is live
+    ///
+    /// The default implementation checks the Binance exchangeInfo status
+    fn is_trading(&self, metadata: &SymbolMetadata) -> bool {
+        metadata.status == "TRADING"
+    }
+
+    /// URL of the metadata of all symbols of the exchange, if it supports symbol discovery (see `SymbolDiscovery`)
+    fn symbols_url(&self) -> Option<String> {
+        None
+    }
+
+    /// Metadata of all symbols from the response of the symbols request
+    ///
+    /// The default implementation parses the Binance exchangeInfo response
+    fn parse_symbols(&self, response: &str) -> Result<Vec<SymbolMetadata>> {
+        let exchange_info = ExchangeInfo::from_json(response).context("Failed to parse exchangeInfo")?;
+        Ok(exchange_info.symbols.into_iter().map(SymbolMetadata::from).collect())
+    }
+
+    /// Rules, which the DepthEventDispatcher applies to depth updates of this venue
+    fn sequencing_rules(&self) -> SequencingRules;
+
+    /// Whether depth update ids are the same on all connections, so several depth connections can be merged.
+    /// Venues, which number the messages of each connection separately, are captured over a single depth connection
+    fn supports_redundant_depth(&self) -> bool {
+        true
+    }
+}
+
+/// Create the connector for the exchange selected in the configuration
+pub fn create_connector(config: &Config) -> Arc<dyn ExchangeConnector> {
+    match config.exchange {
+        Exchange::Binance => Arc::new(BinanceConnector::new(
+            config.rest_endpoint.clone(),
+            config.wss_endpoint.clone(),
+        )),
+        Exchange::BinanceFutures => Arc::new(BinanceFuturesConnector::new(
+            config.rest_endpoint.clone(),
+            config.wss_endpoint.clone(),
+        )),
+        Exchange::Okx => Arc::new(OkxConnector::new(
+            config.rest_endpoint.clone(),
+            config.wss_endpoint.clone(),
+            config.okx_book_channel,
+        )),
+        Exchange::Bitfinex => Arc::new(BitfinexConnector::new(
+            config.rest_endpoint.clone(),
+            config.wss_endpoint.clone(),
+            config.bitfinex_book_precision,
+            config.max_depth,
+        )),
+        Exchange::Deribit => Arc::new(DeribitConnector::new(
+            config.rest_endpoint.clone(),
+            config.wss_endpoint.clone(),
+        )),
+    }
+}
@@ -0,0 +1,800 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::time::{sleep, Instant};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+use crate::mdc_core::fixed_point::FixedPoint;
+
+/// Quote assets, which are recognized at the end of a symbol to report its base and quote asset
+const QUOTE_ASSETS: [&str; 6] = ["USDT", "USDC", "FDUSD", "BTC", "ETH", "EUR"];
+
+fn default_rest_listen() -> String {
+    "127.0.0.1:8090".to_string()
+}
+
+fn default_ws_listen() -> String {
+    "127.0.0.1:8091".to_string()
+}
+
+fn default_start_price() -> f64 {
+    30000.0
+}
+
+fn default_tick_size() -> f64 {
+    0.01
+}
+
+fn default_levels() -> usize {
+    100
+}
+
+fn default_depth_rate() -> f64 {
+    10.0
+}
+
+fn default_trade_rate() -> f64 {
+    5.0
+}
+
+fn default_price_rate() -> f64 {
+    20.0
+}
+
+fn default_price_jump_ticks() -> i64 {
+    100
+}
+
+fn default_burst_duration() -> u64 {
+    1000
+}
+
+fn default_burst_factor() -> f64 {
+    10.0
+}
+
+fn default_max_delay() -> u64 {
+    1000
+}
+
+/// Faults injected into the simulated streams to verify the resilience of the pipeline. Probabilities are per message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultInjection {
+    /// Probability of a connection being dropped without a close frame
+    #[serde(default)]
+    pub disconnect_probability: f64,
+    /// Probability of a message being delayed, which stalls its connection
+    #[serde(default)]
+    pub delay_probability: f64,
+    /// Maximum delay of a message in milliseconds
+    #[serde(default = "default_max_delay")]
+    pub max_delay: u64,
+    /// Probability of a message being sent twice
+    #[serde(default)]
+    pub duplicate_probability: f64,
+    /// Probability of a depth update being withheld from every connection, which skips its update id range
+    #[serde(default)]
+    pub skip_probability: f64,
+}
+
+/// Synthetic market data served by `mdc sim`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationSettings {
+    /// Address of the REST API (depth snapshots, exchangeInfo, server time)
+    #[serde(default = "default_rest_listen")]
+    pub rest_listen: String,
+    /// Address of the WebSocket streams (raw `/ws/<stream>` and combined `/stream?streams=`)
+    #[serde(default = "default_ws_listen")]
+    pub ws_listen: String,
+    #[serde(default = "default_start_price")]
+    pub start_price: f64,
+    #[serde(default = "default_tick_size")]
+    pub tick_size: f64,
+    /// Price levels per side of the book
+    #[serde(default = "default_levels")]
+    pub levels: usize,
+    /// Depth updates per second
+    #[serde(default = "default_depth_rate")]
+    pub depth_rate: f64,
+    /// Trades per second
+    #[serde(default = "default_trade_rate")]
+    pub trade_rate: f64,
+    /// bookTicker updates per second
+    #[serde(default = "default_price_rate")]
+    pub price_rate: f64,
+    /// Seed of the generated data. A random seed is used if not set
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Probability of a depth update moving the price by `price_jump_ticks` at once
+    #[serde(default)]
+    pub price_jump_probability: f64,
+    #[serde(default = "default_price_jump_ticks")]
+    pub price_jump_ticks: i64,
+    /// Interval between bursts in milliseconds, during which all rates are multiplied by `burst_factor`.
+    /// 0 disables bursts
+    #[serde(default)]
+    pub burst_interval: u64,
+    /// Duration of a burst in milliseconds
+    #[serde(default = "default_burst_duration")]
+    pub burst_duration: u64,
+    #[serde(default = "default_burst_factor")]
+    pub burst_factor: f64,
+    /// Faults injected into the streams. None by default
+    #[serde(default)]
+    pub faults: FaultInjection,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        serde_yaml::from_str("{}").expect("Simulation settings have defaults for every field")
+    }
+}
+
+/// xorshift64* generator. Synthetic data only has to look random, and a seed makes a run reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state of xorshift must not be zero
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    /// Uniform in `[0, n)`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n.max(1)
+    }
+}
+
+/// A synthetic order book of a single symbol, which produces Binance spot depth updates, trades and bookTicker updates
+///
+/// The price follows a random walk of one tick, with occasional jumps. The book always has `levels` levels per side
+/// on consecutive ticks, the best ask one tick above the best bid, so it is never crossed. The depth updates are
+/// consistent with the snapshots: a snapshot with the updates after it applied equals a later snapshot
+pub struct SyntheticMarket {
+    symbol: String,
+    tick_size: f64,
+    levels: i64,
+    price_jump_probability: f64,
+    price_jump_ticks: i64,
+    rng: Rng,
+    /// Best bid in ticks
+    best_bid: i64,
+    bids: BTreeMap<i64, FixedPoint>,
+    asks: BTreeMap<i64, FixedPoint>,
+    last_update_id: u64,
+    last_trade_id: u64,
+}
+
+impl SyntheticMarket {
+    pub fn new(symbol: &str, settings: &SimulationSettings, seed: u64) -> Self {
+        let levels = settings.levels.max(1) as i64;
+        let mut market = Self {
+            symbol: symbol.to_uppercase(),
+            tick_size: settings.tick_size,
+            levels,
+            price_jump_probability: settings.price_jump_probability,
+            price_jump_ticks: settings.price_jump_ticks,
+            rng: Rng::new(seed),
+            best_bid: ((settings.start_price / settings.tick_size).round() as i64).max(levels),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            last_update_id: 1000,
+            last_trade_id: 0,
+        };
+        market.rebalance(&mut BTreeMap::new(), &mut BTreeMap::new());
+        market
+    }
+
+    fn price(&self, ticks: i64) -> FixedPoint {
+        FixedPoint::from_f64(ticks as f64 * self.tick_size)
+    }
+
+    fn quantity(&mut self) -> FixedPoint {
+        FixedPoint::from_f64((1 + self.rng.below(5000)) as f64 / 1000.0)
+    }
+
+    fn format_levels<'a>(&self, levels: impl Iterator<Item = (&'a i64, &'a FixedPoint)>) -> Vec<[String; 2]> {
+        levels.map(|(ticks, quantity)| [self.price(*ticks).to_string(), quantity.to_string()]).collect()
+    }
+
+    /// Align the book with the best bid: levels, which cross it or exceed the depth, are removed, missing levels are added
+    fn rebalance(&mut self, bid_changes: &mut BTreeMap<i64, FixedPoint>, ask_changes: &mut BTreeMap<i64, FixedPoint>) {
+        let best_ask = self.best_bid + 1;
+        let (lowest_bid, highest_ask) = (self.best_bid - self.levels + 1, best_ask + self.levels - 1);
+
+        let stale_bids: Vec<_> = self.bids.keys().copied().filter(|ticks| *ticks > self.best_bid || *ticks < lowest_bid).collect();
+        for ticks in stale_bids {
+            self.bids.remove(&ticks);
+            bid_changes.insert(ticks, FixedPoint::ZERO);
+        }
+        let stale_asks: Vec<_> = self.asks.keys().copied().filter(|ticks| *ticks < best_ask || *ticks > highest_ask).collect();
+        for ticks in stale_asks {
+            self.asks.remove(&ticks);
+            ask_changes.insert(ticks, FixedPoint::ZERO);
+        }
+
+        for ticks in lowest_bid..=self.best_bid {
+            if !self.bids.contains_key(&ticks) {
+                let quantity = self.quantity();
+                self.bids.insert(ticks, quantity);
+                bid_changes.insert(ticks, quantity);
+            }
+        }
+        for ticks in best_ask..=highest_ask {
+            if !self.asks.contains_key(&ticks) {
+                let quantity = self.quantity();
+                self.asks.insert(ticks, quantity);
+                ask_changes.insert(ticks, quantity);
+            }
+        }
+    }
+
+    /// Depth snapshot in the format of the Binance REST API
+    pub fn snapshot(&self, limit: usize) -> Value {
+        json!({
+            "lastUpdateId": self.last_update_id,
+            "bids": self.format_levels(self.bids.iter().rev().take(limit)),
+            "asks": self.format_levels(self.asks.iter().take(limit)),
+        })
+    }
+
+    /// Move the market and return the depth update, which describes the move
+    pub fn next_depth_update(&mut self, event_time: i64) -> Value {
+        let (mut bid_changes, mut ask_changes) = (BTreeMap::new(), BTreeMap::new());
+
+        if self.rng.chance(self.price_jump_probability) {
+            self.best_bid += if self.rng.chance(0.5) { self.price_jump_ticks } else { -self.price_jump_ticks };
+        } else if self.rng.chance(0.3) {
+            self.best_bid += if self.rng.chance(0.5) { 1 } else { -1 };
+        }
+        // Keep every price positive
+        self.best_bid = self.best_bid.max(self.levels);
+        self.rebalance(&mut bid_changes, &mut ask_changes);
+
+        // Quantity changes near the top of the book
+        for _ in 0..1 + self.rng.below(5) {
+            let depth = self.rng.below(self.levels.min(20) as u64) as i64;
+            let quantity = self.quantity();
+            if self.rng.chance(0.5) {
+                self.bids.insert(self.best_bid - depth, quantity);
+                bid_changes.insert(self.best_bid - depth, quantity);
+            } else {
+                self.asks.insert(self.best_bid + 1 + depth, quantity);
+                ask_changes.insert(self.best_bid + 1 + depth, quantity);
+            }
+        }
+
+        let first_update_id = self.last_update_id + 1;
+        self.last_update_id += (bid_changes.len() + ask_changes.len()) as u64;
+
+        json!({
+            "e": "depthUpdate",
+            "E": event_time,
+            "s": self.symbol,
+            "U": first_update_id,
+            "u": self.last_update_id,
+            "b": self.format_levels(bid_changes.iter().rev()),
+            "a": self.format_levels(ask_changes.iter()),
+        })
+    }
+
+    /// A trade at the best bid or the best ask
+    pub fn next_trade(&mut self, event_time: i64) -> Value {
+        self.last_trade_id += 1;
+        let is_buyer_maker = self.rng.chance(0.5);
+        let ticks = if is_buyer_maker { self.best_bid } else { self.best_bid + 1 };
+        let quantity = FixedPoint::from_f64((1 + self.rng.below(1000)) as f64 / 1000.0);
+
+        json!({
+            "e": "trade",
+            "E": event_time,
+            "s": self.symbol,
+            "t": self.last_trade_id,
+            "p": self.price(ticks).to_string(),
+            "q": quantity.to_string(),
+            "T": event_time,
+            "m": is_buyer_maker,
+            "M": true,
+        })
+    }
+
+    /// The best bid and ask in the format of the bookTicker stream
+    pub fn book_ticker(&self) -> Value {
+        let best_ask = self.best_bid + 1;
+        json!({
+            "u": self.last_update_id,
+            "s": self.symbol,
+            "b": self.price(self.best_bid).to_string(),
+            "B": self.bids.get(&self.best_bid).copied().unwrap_or(FixedPoint::ZERO).to_string(),
+            "a": self.price(best_ask).to_string(),
+            "A": self.asks.get(&best_ask).copied().unwrap_or(FixedPoint::ZERO).to_string(),
+        })
+    }
+
+    /// exchangeInfo response with the symbol
+    pub fn exchange_info(&self) -> Value {
+        let quote_asset = QUOTE_ASSETS.iter().find(|quote| self.symbol.ends_with(*quote) && self.symbol.len() > quote.len());
+        let (base_asset, quote_asset) = match quote_asset {
+            Some(quote) => (&self.symbol[..self.symbol.len() - quote.len()], *quote),
+            None => (self.symbol.as_str(), ""),
+        };
+
+        json!({
+            "timezone": "UTC",
+            "serverTime": Utc::now().timestamp_millis(),
+            "symbols": [{
+                "symbol": self.symbol,
+                "status": "TRADING",
+                "baseAsset": base_asset,
+                "quoteAsset": quote_asset,
+                "filters": [
+                    { "filterType": "PRICE_FILTER", "tickSize": FixedPoint::from_f64(self.tick_size).to_string() },
+                    { "filterType": "LOT_SIZE", "stepSize": "0.001", "minQty": "0.001", "maxQty": "9000" },
+                ],
+            }],
+        })
+    }
+}
+
+/// Streams of the simulated exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SimulatedStream {
+    Depth,
+    Trade,
+    Price,
+}
+
+impl SimulatedStream {
+    /// Parse a Binance stream name, e.g. `btcusdt@depth@100ms`. Streams of other symbols aren't served
+    fn parse(name: &str, symbol: &str) -> Option<Self> {
+        let (stream_symbol, stream) = name.split_once('@')?;
+        if !stream_symbol.eq_ignore_ascii_case(symbol) {
+            return None;
+        }
+
+        match stream {
+            stream if stream.starts_with("depth") => Some(SimulatedStream::Depth),
+            "trade" => Some(SimulatedStream::Trade),
+            "bookTicker" => Some(SimulatedStream::Price),
+            _ => None,
+        }
+    }
+}
+
+/// A message of a simulated stream, delivered to every connection subscribed to it
+#[derive(Debug, Clone)]
+struct Published {
+    stream: SimulatedStream,
+    payload: Arc<String>,
+}
+
+/// Streams requested by the URI of a WebSocket connection: `/ws/<stream>` (raw) or `/stream?streams=<a>/<b>` (combined)
+///
+/// # Returns
+/// The names and the kinds of the served streams, and whether the connection is combined
+fn subscriptions(uri: &str, symbol: &str) -> (Vec<(String, SimulatedStream)>, bool) {
+    let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+    let streams = query.split('&').find_map(|parameter| parameter.strip_prefix("streams="));
+
+    let (names, combined): (Vec<&str>, bool) = match streams {
+        Some(streams) if path.trim_end_matches('/').ends_with("/stream") => (streams.split('/').collect(), true),
+        _ => (path.rsplit('/').next().into_iter().collect(), false),
+    };
+
+    let subscriptions = names
+        .into_iter()
+        .filter_map(|name| SimulatedStream::parse(name, symbol).map(|stream| (name.to_string(), stream)))
+        .collect();
+    (subscriptions, combined)
+}
+
+/// Rate multiplier of the periodic bursts
+#[derive(Debug, Clone, Copy)]
+struct Bursts {
+    interval: u64,
+    duration: u64,
+    factor: f64,
+}
+
+impl Bursts {
+    fn factor(&self, elapsed: Duration) -> f64 {
+        match self.interval > 0 && (elapsed.as_millis() as u64 % self.interval) < self.duration {
+            true => self.factor.max(f64::MIN_POSITIVE),
+            false => 1.0,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RestState {
+    symbol: String,
+    market: Arc<Mutex<SyntheticMarket>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolQuery {
+    symbol: Option<String>,
+    limit: Option<usize>,
+}
+
+type RestResult = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+impl RestState {
+    fn check_symbol(&self, symbol: Option<&str>) -> Result<(), (StatusCode, Json<Value>)> {
+        match symbol {
+            Some(symbol) if !symbol.eq_ignore_ascii_case(&self.symbol) => {
+                Err((StatusCode::BAD_REQUEST, Json(json!({ "code": -1121, "msg": "Invalid symbol." }))))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn depth(State(state): State<RestState>, Query(query): Query<SymbolQuery>) -> RestResult {
+    state.check_symbol(Some(query.symbol.as_deref().unwrap_or_default()))?;
+    let market = state.market.lock().expect("Synthetic market lock is poisoned");
+    Ok(Json(market.snapshot(query.limit.unwrap_or(100).min(5000))))
+}
+
+async fn exchange_info(State(state): State<RestState>, Query(query): Query<SymbolQuery>) -> RestResult {
+    state.check_symbol(query.symbol.as_deref())?;
+    Ok(Json(state.market.lock().expect("Synthetic market lock is poisoned").exchange_info()))
+}
+
+async fn server_time() -> Json<Value> {
+    Json(json!({ "serverTime": Utc::now().timestamp_millis() }))
+}
+
+/// ExchangeSimulator serves synthetic market data of a single symbol with the Binance spot API, so the pipeline
+/// can be developed and load-tested without connecting to the exchange
+///
+/// The REST API serves `/api/v3/depth`, `/api/v3/exchangeInfo` and `/api/v3/time`. The WebSocket server serves the
+/// `depth`, `trade` and `bookTicker` streams, both raw (`/ws/<stream>`) and combined (`/stream?streams=`).
+/// All connections receive the same messages, like the redundant connections to the exchange, unless faults are injected
+pub struct ExchangeSimulator {
+    symbol: String,
+    settings: SimulationSettings,
+    seed: u64,
+    market: Arc<Mutex<SyntheticMarket>>,
+    rest_listener: TcpListener,
+    ws_listener: TcpListener,
+}
+
+impl ExchangeSimulator {
+    /// Bind the REST and the WebSocket addresses of the simulator
+    pub async fn bind(symbol: &str, settings: SimulationSettings) -> Result<Self> {
+        let rest_listener = TcpListener::bind(&settings.rest_listen)
+            .await
+            .with_context(|| format!("Failed to listen on '{}'", settings.rest_listen))?;
+        let ws_listener = TcpListener::bind(&settings.ws_listen)
+            .await
+            .with_context(|| format!("Failed to listen on '{}'", settings.ws_listen))?;
+
+        let seed = settings.seed.unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64);
+        tracing::info!("Simulating '{}' with seed '{}'", symbol, seed);
+
+        Ok(Self {
+            symbol: symbol.to_uppercase(),
+            market: Arc::new(Mutex::new(SyntheticMarket::new(symbol, &settings, seed))),
+            settings,
+            seed,
+            rest_listener,
+            ws_listener,
+        })
+    }
+
+    pub fn rest_address(&self) -> Result<SocketAddr> {
+        Ok(self.rest_listener.local_addr()?)
+    }
+
+    pub fn ws_address(&self) -> Result<SocketAddr> {
+        Ok(self.ws_listener.local_addr()?)
+    }
+
+    /// Run the simulator until it fails to accept connections
+    pub async fn run(self) -> Result<()> {
+        let (sender, _) = broadcast::channel::<Published>(1024);
+        let bursts = Bursts {
+            interval: self.settings.burst_interval,
+            duration: self.settings.burst_duration,
+            factor: self.settings.burst_factor,
+        };
+        let started = Instant::now();
+
+        for (stream, rate, skip_probability) in [
+            (SimulatedStream::Depth, self.settings.depth_rate, self.settings.faults.skip_probability),
+            (SimulatedStream::Trade, self.settings.trade_rate, 0.0),
+            (SimulatedStream::Price, self.settings.price_rate, 0.0),
+        ] {
+            if rate > 0.0 {
+                let rng = Rng::new(self.seed ^ stream as u64);
+                tokio::spawn(generate(self.market.clone(), sender.clone(), stream, rate, bursts, started, skip_probability, rng));
+            }
+        }
+
+        let router = Router::new()
+            .route("/api/v3/depth", get(depth))
+            .route("/api/v3/exchangeInfo", get(exchange_info))
+            .route("/api/v3/time", get(server_time))
+            .with_state(RestState { symbol: self.symbol.clone(), market: self.market.clone() });
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(self.rest_listener, router).await {
+                tracing::error!("Simulated REST API stopped. Details: '{}'", e);
+            }
+        });
+
+        let mut connections: u64 = 0;
+        loop {
+            let (socket, address) = self.ws_listener.accept().await?;
+            connections += 1;
+            let (messages, symbol, faults) = (sender.subscribe(), self.symbol.clone(), self.settings.faults.clone());
+            // Each connection gets its own faults, like independent connections to the exchange
+            let rng = Rng::new(self.seed.wrapping_add(connections.wrapping_mul(0x9E37_79B9_7F4A_7C15)));
+
+            tokio::spawn(async move {
+                match serve_connection(socket, messages, &symbol, &faults, rng).await {
+                    Ok(()) => tracing::info!("Simulated stream connection from '{}' closed", address),
+                    Err(e) => tracing::warn!("Simulated stream connection from '{}' failed. Details: '{}'", address, e),
+                }
+            });
+        }
+    }
+}
+
+/// Produce the messages of a stream at its rate
+///
+/// # Arguments
+/// * `skip_probability` - Probability of a message being withheld from every connection
+/// * `rng` - Generator of the skipped messages
+#[allow(clippy::too_many_arguments)]
+async fn generate(
+    market: Arc<Mutex<SyntheticMarket>>,
+    sender: broadcast::Sender<Published>,
+    stream: SimulatedStream,
+    rate: f64,
+    bursts: Bursts,
+    started: Instant,
+    skip_probability: f64,
+    mut rng: Rng,
+) {
+    loop {
+        sleep(Duration::from_secs_f64(1.0 / (rate * bursts.factor(started.elapsed())))).await;
+
+        let now = Utc::now().timestamp_millis();
+        let payload = {
+            let mut market = market.lock().expect("Synthetic market lock is poisoned");
+            match stream {
+                SimulatedStream::Depth => market.next_depth_update(now),
+                SimulatedStream::Trade => market.next_trade(now),
+                SimulatedStream::Price => market.book_ticker(),
+            }
+        };
+
+        if rng.chance(skip_probability) {
+            tracing::info!("Injected skip of '{}'", payload);
+            continue;
+        }
+
+        // Sending fails while no client is connected, the market moves on regardless
+        let _ = sender.send(Published { stream, payload: Arc::new(payload.to_string()) });
+    }
+}
+
+/// Deliver the messages of the requested streams to a WebSocket client, injecting the connection faults
+// The error type of the handshake callback is defined by tungstenite
+#[allow(clippy::result_large_err)]
+async fn serve_connection(
+    socket: TcpStream,
+    mut messages: broadcast::Receiver<Published>,
+    symbol: &str,
+    faults: &FaultInjection,
+    mut rng: Rng,
+) -> Result<()> {
+    let mut uri = String::new();
+    let connection = tokio_tungstenite::accept_hdr_async(socket, |request: &Request, response: Response| {
+        uri = request.uri().to_string();
+        Ok(response)
+    })
+    .await?;
+
+    let (subscriptions, combined) = subscriptions(&uri, symbol);
+    match subscriptions.is_empty() {
+        true => tracing::warn!("Simulated stream connection to '{}' requests no simulated stream", uri),
+        false => tracing::info!("Simulated stream connection to '{}' opened", uri),
+    }
+
+    let (mut writer, mut reader) = connection.split();
+    loop {
+        tokio::select! {
+            message = messages.recv() => match message {
+                Ok(published) => {
+                    for (name, _) in subscriptions.iter().filter(|(_, stream)| *stream == published.stream) {
+                        let text = match combined {
+                            true => format!(r#"{{"stream":"{}","data":{}}}"#, name, published.payload),
+                            false => published.payload.to_string(),
+                        };
+
+                        if rng.chance(faults.disconnect_probability) {
+                            return Err(anyhow!("Injected disconnect"));
+                        }
+                        if rng.chance(faults.delay_probability) {
+                            let delay = rng.below(faults.max_delay + 1);
+                            tracing::debug!("Injected delay of '{}' ms into '{}'", delay, uri);
+                            sleep(Duration::from_millis(delay)).await;
+                        }
+                        if rng.chance(faults.duplicate_probability) {
+                            tracing::debug!("Injected duplicate into '{}'", uri);
+                            writer.send(Message::text(text.clone())).await?;
+                        }
+                        writer.send(Message::text(text)).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Simulated stream client of '{}' is too slow. Skipped '{}' messages", uri, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            incoming = reader.next() => match incoming {
+                Some(Ok(Message::Ping(payload))) => writer.send(Message::Pong(payload)).await?,
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{DepthSnapshot, DepthUpdate, FromJson};
+    use crate::mdc_core::order_book::OrderBook;
+
+    fn make_settings() -> SimulationSettings {
+        SimulationSettings {
+            rest_listen: "127.0.0.1:0".to_string(),
+            ws_listen: "127.0.0.1:0".to_string(),
+            levels: 20,
+            depth_rate: 1000.0,
+            price_jump_probability: 0.05,
+            price_jump_ticks: 30,
+            seed: Some(7),
+            ..SimulationSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_depth_updates_are_consistent_with_snapshots() {
+        let mut market = SyntheticMarket::new("btcusdt", &make_settings(), 7);
+        let snapshot = DepthSnapshot::from_json(&market.snapshot(1000).to_string()).unwrap();
+        let mut book = OrderBook::new(&snapshot);
+        let mut last_update_id = snapshot.last_update_id;
+
+        for time in 0..500 {
+            let update = DepthUpdate::from_json(&market.next_depth_update(time).to_string()).unwrap();
+            assert_eq!(update.first_update_id, last_update_id + 1);
+            last_update_id = update.last_update_id;
+
+            for bid in update.bids {
+                book.apply_update(OrderBook::bid(bid.price), bid.quantity);
+            }
+            for ask in update.asks {
+                book.apply_update(OrderBook::ask(ask.price), ask.quantity);
+            }
+            assert!(!book.is_crossed());
+        }
+
+        let expected = OrderBook::new(&DepthSnapshot::from_json(&market.snapshot(1000).to_string()).unwrap());
+        assert_eq!(book.bids, expected.bids);
+        assert_eq!(book.asks, expected.asks);
+        assert_eq!((book.bids.len(), book.asks.len()), (20, 20));
+        assert_eq!(market.book_ticker()["u"], last_update_id);
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let (streams, combined) = subscriptions("/stream?streams=btcusdt@depth@100ms/btcusdt@trade/ethusdt@trade", "BTCUSDT");
+        assert!(combined);
+        assert_eq!(streams, vec![
+            ("btcusdt@depth@100ms".to_string(), SimulatedStream::Depth),
+            ("btcusdt@trade".to_string(), SimulatedStream::Trade),
+        ]);
+
+        let (streams, combined) = subscriptions("/ws/btcusdt@bookTicker", "BTCUSDT");
+        assert!(!combined);
+        assert_eq!(streams, vec![("btcusdt@bookTicker".to_string(), SimulatedStream::Price)]);
+        assert!(subscriptions("/ws/btcusdt@kline_1m", "BTCUSDT").0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_serves_snapshots_and_streams() {
+        let simulator = ExchangeSimulator::bind("BTCUSDT", make_settings()).await.unwrap();
+        let (rest_address, ws_address) = (simulator.rest_address().unwrap(), simulator.ws_address().unwrap());
+        let task = tokio::spawn(simulator.run());
+
+        let snapshot = reqwest::get(format!("http://{}/api/v3/depth?symbol=BTCUSDT&limit=5", rest_address))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let snapshot = DepthSnapshot::from_json(&snapshot).unwrap();
+        assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (5, 5));
+
+        let invalid = reqwest::get(format!("http://{}/api/v3/depth?symbol=ETHUSDT", rest_address)).await.unwrap();
+        assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let url = format!("ws://{}/stream?streams=btcusdt@depth@100ms", ws_address);
+        let (mut connection, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let Some(Ok(Message::Text(text))) = connection.next().await else {
+            panic!("Expected a depth update");
+        };
+        let message: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(message["stream"], "btcusdt@depth@100ms");
+        assert!(DepthUpdate::from_json(&message["data"].to_string()).unwrap().last_update_id > snapshot.last_update_id);
+
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_injects_faults() {
+        let mut settings = make_settings();
+        settings.faults = FaultInjection { duplicate_probability: 1.0, skip_probability: 0.5, ..FaultInjection::default() };
+        let simulator = ExchangeSimulator::bind("BTCUSDT", settings).await.unwrap();
+        let url = format!("ws://{}/ws/btcusdt@depth@100ms", simulator.ws_address().unwrap());
+        let task = tokio::spawn(simulator.run());
+
+        let (mut connection, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut updates = Vec::new();
+        while updates.len() < 40 {
+            let Some(Ok(Message::Text(text))) = connection.next().await else {
+                panic!("Expected a depth update");
+            };
+            updates.push(DepthUpdate::from_json(&text).unwrap());
+        }
+
+        // Every update is sent twice, and some update id ranges are never sent
+        let pairs: Vec<_> = updates.chunks(2).map(|pair| (pair[0].first_update_id, pair[1].first_update_id, pair[0].last_update_id)).collect();
+        assert!(pairs.iter().all(|(first, copy, _)| first == copy));
+        assert!(pairs.windows(2).any(|pair| pair[1].0 > pair[0].2 + 1));
+        task.abort();
+
+        let mut settings = make_settings();
+        settings.faults.disconnect_probability = 1.0;
+        let simulator = ExchangeSimulator::bind("BTCUSDT", settings).await.unwrap();
+        let url = format!("ws://{}/ws/btcusdt@trade", simulator.ws_address().unwrap());
+        let task = tokio::spawn(simulator.run());
+
+        let (mut connection, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        assert!(!matches!(connection.next().await, Some(Ok(Message::Text(_)))));
+        task.abort();
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, Notify};
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_server::log_context::spawn;
+
+/// Messages, which a consumer with the `drop_oldest` policy may fall behind, before the oldest ones are dropped
+const DROP_OLDEST_CAPACITY: usize = 100;
+
+/// What happens to the messages of a consumer, which can't keep up with its stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelPolicy {
+    /// Wait for the consumer. A slow consumer holds back the stream and everything before it
+    #[default]
+    Block,
+    /// Keep the latest messages for the consumer and drop the oldest ones
+    DropOldest,
+    /// Keep only the latest message for the consumer (e.g. the latest book)
+    Conflate,
+}
+
+/// Policies of the consumers of the fanned out streams
+///
+/// Depth updates are always delivered, since the book can't be maintained without any of them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelPolicies {
+    #[serde(default)]
+    pub trade: ChannelPolicy,
+    #[serde(default)]
+    pub price: ChannelPolicy,
+    #[serde(default)]
+    pub bbo: ChannelPolicy,
+    #[serde(default)]
+    pub book: ChannelPolicy,
+    #[serde(default)]
+    pub auxiliary: ChannelPolicy,
+}
+
+#[derive(Debug)]
+struct QueueState<T> {
+    messages: VecDeque<T>,
+    /// The producer has finished, the queue is closed once it is drained
+    finished: bool,
+    /// The consumer has been dropped
+    closed: bool,
+}
+
+/// Queue between the Fanout and a consumer, which never makes the Fanout wait
+///
+/// A relay task moves the messages from the queue into the channel of the consumer
+#[derive(Debug)]
+struct ConsumerQueue<T> {
+    policy: ChannelPolicy,
+    state: Mutex<QueueState<T>>,
+    notify: Notify,
+}
+
+impl<T> ConsumerQueue<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState<T>> {
+        self.state.lock().expect("Consumer queue lock is poisoned")
+    }
+
+    fn finish(&self) {
+        self.lock().finished = true;
+        self.notify.notify_one();
+    }
+}
+
+impl<T> ConsumerQueue<T>
+where T: Send + 'static,
+{
+    /// Create the queue and spawn the relay task into the consumer channel
+    fn spawn(policy: ChannelPolicy, output: mpsc::Sender<T>) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            policy,
+            state: Mutex::new(QueueState { messages: VecDeque::new(), finished: false, closed: false }),
+            notify: Notify::new(),
+        });
+
+        let relay = queue.clone();
+        spawn(async move {
+            while let Some(message) = relay.pop().await {
+                if output.send(message).await.is_err() {
+                    relay.lock().closed = true;
+                    break;
+                }
+            }
+        });
+
+        queue
+    }
+
+    /// Queue a message according to the policy
+    ///
+    /// # Returns
+    /// The number of dropped messages, or `None` if the consumer has been dropped
+    fn push(&self, message: T) -> Option<u64> {
+        let mut state = self.lock();
+        if state.closed {
+            return None;
+        }
+
+        let dropped = match self.policy {
+            ChannelPolicy::Conflate => state.messages.drain(..).count(),
+            _ if state.messages.len() >= DROP_OLDEST_CAPACITY => state.messages.pop_front().map_or(0, |_| 1),
+            _ => 0,
+        };
+
+        state.messages.push_back(message);
+        drop(state);
+        self.notify.notify_one();
+        Some(dropped as u64)
+    }
+
+    /// Take the next message, waiting for it if needed
+    ///
+    /// # Returns
+    /// `None` once the producer has finished and the queue is drained
+    async fn pop(&self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.lock();
+                if let Some(message) = state.messages.pop_front() {
+                    return Some(message);
+                }
+                if state.finished {
+                    return None;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+}
+
+/// Output of the Fanout: either the consumer channel itself or a queue in front of it
+enum Output<T> {
+    Channel(mpsc::Sender<T>),
+    Queue(Arc<ConsumerQueue<T>>),
+}
+
+impl<T> Output<T>
+where T: Send + 'static,
+{
+    /// Deliver a message to the consumer
+    ///
+    /// # Returns
+    /// `false` if the consumer has been dropped
+    async fn send(&self, message: T, dropped: &Counter) -> bool {
+        match self {
+            Output::Channel(sender) => sender.send(message).await.is_ok(),
+            Output::Queue(queue) => queue.push(message).map(|count| dropped.increment(count)).is_some(),
+        }
+    }
+}
+
+/// The queued messages are still delivered, then the relay closes the consumer channel. This also happens if the
+/// Fanout is aborted
+impl<T> Drop for Output<T> {
+    fn drop(&mut self) {
+        if let Output::Queue(queue) = self {
+            queue.finish();
+        }
+    }
+}
+
+/// Fanout forwards every message from a single input channel to several output channels
+///
+/// It allows several consumers (e.g. the logger and the rollup engine) to receive the same stream of events
+/// Outputs, whose receivers have been dropped, are removed. The fanout stops once the input channel is closed
+/// or no outputs are left
+///
+/// With the `block` policy a slow consumer holds back the stream. With the other policies every consumer gets its own
+/// queue, so a slow consumer loses messages (counted by the `channel_dropped_<name>` counter) instead
+pub struct Fanout<T>
+where T: Clone + Send + 'static,
+{
+    name: String,
+    input: mpsc::Receiver<T>,
+    outputs: Vec<mpsc::Sender<T>>,
+    policy: ChannelPolicy,
+    dropped: Counter,
+}
+
+impl<T> Fanout<T>
+where T: Clone + Send + 'static,
+{
+    /// Create a new Fanout
+    ///
+    /// # Arguments
+    /// * `name` - Name of the forwarded stream, used in logs
+    /// * `input` - Receiver for the forwarded messages
+    /// * `outputs` - Senders, each of which receives every message
+    pub fn new(name: String, input: mpsc::Receiver<T>, outputs: Vec<mpsc::Sender<T>>) -> Self {
+        Self { name, input, outputs, policy: ChannelPolicy::Block, dropped: Counter::default() }
+    }
+
+    /// Apply the policy to consumers, which can't keep up
+    ///
+    /// # Arguments
+    /// * `policy` - Policy of every consumer of the stream
+    /// * `metrics` - Registry of the `channel_dropped_<name>` counter
+    pub fn with_policy(mut self, policy: ChannelPolicy, metrics: &Metrics) -> Self {
+        self.policy = policy;
+        self.dropped = metrics.counter(&format!("channel_dropped_{}", self.name));
+        self
+    }
+
+    /// Run the Fanout as an asynchronous task
+    pub async fn run(mut self) {
+        let policy = self.policy;
+        let mut outputs: Vec<Output<T>> = self.outputs
+            .drain(..)
+            .map(|output| match policy {
+                ChannelPolicy::Block => Output::Channel(output),
+                _ => Output::Queue(ConsumerQueue::spawn(policy, output)),
+            })
+            .collect();
+
+        while let Some(message) = self.input.recv().await {
+            let Some((last, others)) = outputs.split_last() else {
+                break;
+            };
+
+            let mut closed = Vec::new();
+
+            for (i, output) in others.iter().enumerate() {
+                if !output.send(message.clone(), &self.dropped).await {
+                    closed.push(i);
+                }
+            }
+
+            // The last output takes the message itself, which saves a clone in the common single output case
+            if !last.send(message, &self.dropped).await {
+                closed.push(outputs.len() - 1);
+            }
+
+            for i in closed.into_iter().rev() {
+                tracing::warn!("Consumer '{}' of '{}' stream is closed. Removing it", i, self.name);
+                outputs.remove(i);
+            }
+        }
+
+        tracing::debug!("Fanout of '{}' stream finished", self.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fanout_forwards_to_all_outputs() {
+        let (input_tx, input_rx) = mpsc::channel::<u64>(10);
+        let (first_tx, mut first_rx) = mpsc::channel::<u64>(10);
+        let (second_tx, mut second_rx) = mpsc::channel::<u64>(10);
+
+        tokio::spawn(Fanout::new("test".to_string(), input_rx, vec![first_tx, second_tx]).run());
+
+        input_tx.send(1).await.unwrap();
+        input_tx.send(2).await.unwrap();
+        drop(input_tx);
+
+        assert_eq!(first_rx.recv().await, Some(1));
+        assert_eq!(first_rx.recv().await, Some(2));
+        assert_eq!(first_rx.recv().await, None);
+        assert_eq!(second_rx.recv().await, Some(1));
+        assert_eq!(second_rx.recv().await, Some(2));
+        assert_eq!(second_rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_fanout_removes_closed_outputs() {
+        let (input_tx, input_rx) = mpsc::channel::<u64>(10);
+        let (first_tx, first_rx) = mpsc::channel::<u64>(10);
+        let (second_tx, mut second_rx) = mpsc::channel::<u64>(10);
+        drop(first_rx);
+
+        tokio::spawn(Fanout::new("test".to_string(), input_rx, vec![first_tx, second_tx]).run());
+
+        input_tx.send(1).await.unwrap();
+        input_tx.send(2).await.unwrap();
+        drop(input_tx);
+
+        assert_eq!(second_rx.recv().await, Some(1));
+        assert_eq!(second_rx.recv().await, Some(2));
+        assert_eq!(second_rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_doesnt_hold_back_the_others() {
+        let metrics = Metrics::new();
+        let (input_tx, input_rx) = mpsc::channel::<u64>(10);
+        let (slow_tx, mut slow_rx) = mpsc::channel::<u64>(1);
+        let (fast_tx, mut fast_rx) = mpsc::channel::<u64>(1);
+
+        let fanout = Fanout::new("book".to_string(), input_rx, vec![slow_tx, fast_tx])
+            .with_policy(ChannelPolicy::Conflate, &metrics);
+        tokio::spawn(fanout.run());
+
+        // The fast consumer receives every message, while the slow one doesn't read at all
+        for message in 1..=5 {
+            input_tx.send(message).await.unwrap();
+            assert_eq!(fast_rx.recv().await, Some(message));
+        }
+        drop(input_tx);
+        assert_eq!(fast_rx.recv().await, None);
+
+        // The slow consumer gets the messages buffered on the way to it and the latest one
+        let mut received = Vec::new();
+        while let Some(message) = slow_rx.recv().await {
+            received.push(message);
+        }
+        assert_eq!(received.last(), Some(&5));
+        assert!(received.len() <= 3);
+        assert_eq!(received.len() as u64 + metrics.snapshot()["channel_dropped_book"], 5);
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_the_latest_messages() {
+        let queue = ConsumerQueue {
+            policy: ChannelPolicy::DropOldest,
+            state: Mutex::new(QueueState { messages: VecDeque::new(), finished: false, closed: false }),
+            notify: Notify::new(),
+        };
+
+        let dropped: u64 = (0..DROP_OLDEST_CAPACITY as u64 + 5).map(|message| queue.push(message).unwrap()).sum();
+        assert_eq!(dropped, 5);
+        assert_eq!(queue.lock().messages.front(), Some(&5));
+
+        queue.lock().closed = true;
+        assert_eq!(queue.push(0), None);
+    }
+
+    #[test]
+    fn test_channel_policy_names() {
+        let policies: ChannelPolicies = serde_yaml::from_str("book: conflate\ntrade: drop_oldest").unwrap();
+        assert_eq!(policies.book, ChannelPolicy::Conflate);
+        assert_eq!(policies.trade, ChannelPolicy::DropOldest);
+        assert_eq!(policies.price, ChannelPolicy::Block);
+        assert!(serde_yaml::from_str::<ChannelPolicies>("depth: conflate").is_err());
+    }
+}
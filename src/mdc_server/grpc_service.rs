@@ -0,0 +1,273 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use crate::mdc_core::models::{DepthEntry, MarketEvent, TradeEvent};
+use crate::mdc_core::order_book::OrderBook;
+use proto::market_data_server::MarketData;
+use proto::{GetSnapshotRequest, OrderBookUpdate, PriceLevel, StreamOrderBookRequest, StreamTradesRequest, Trade};
+use crate::mdc_server::log_context::spawn;
+
+pub mod proto {
+    tonic::include_proto!("mdc");
+}
+
+/// Number of updates buffered per subscriber. A subscriber, which falls further behind, skips updates
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// The maintained book at a point in time
+#[derive(Debug, Clone)]
+struct BookState {
+    sequence: u64,
+    time: i64,
+    book: Arc<OrderBook>,
+}
+
+impl BookState {
+    fn to_update(&self, depth: usize) -> OrderBookUpdate {
+        let (bids, asks) = self.book.top_n(depth);
+        let levels = |side: Vec<DepthEntry>| {
+            side.into_iter()
+                .map(|entry| PriceLevel { price: entry.price.to_f64(), quantity: entry.quantity.to_f64() })
+                .collect()
+        };
+
+        OrderBookUpdate {
+            sequence: self.sequence,
+            time: self.time,
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+}
+
+impl From<&TradeEvent> for Trade {
+    fn from(trade: &TradeEvent) -> Self {
+        Self {
+            trade_id: trade.trade_id,
+            event_time: trade.event_time,
+            trade_time: trade.trade_time,
+            price: trade.price,
+            quantity: trade.quantity,
+            is_buyer_maker: trade.is_market_maker,
+        }
+    }
+}
+
+/// GrpcPublisher distributes the book and trade streams of the pipeline to the subscribers of the gRPC service
+pub struct GrpcPublisher {
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    books: broadcast::Sender<BookState>,
+    latest_book: watch::Sender<Option<BookState>>,
+    trades: broadcast::Sender<TradeEvent>,
+}
+
+impl GrpcPublisher {
+    /// Create a new GrpcPublisher
+    ///
+    /// # Arguments
+    /// * `book_channel` - Receiver for OrderBook messages
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    pub fn new(book_channel: mpsc::Receiver<Arc<OrderBook>>, trade_channel: mpsc::Receiver<MarketEvent>) -> Self {
+        Self {
+            book_channel,
+            trade_channel,
+            books: broadcast::channel(SUBSCRIBER_BUFFER).0,
+            latest_book: watch::channel(None).0,
+            trades: broadcast::channel(SUBSCRIBER_BUFFER).0,
+        }
+    }
+
+    /// Create the gRPC service, which serves the published streams
+    ///
+    /// # Arguments
+    /// * `default_depth` - Number of top levels per side for requests, which don't specify it
+    pub fn service(&self, default_depth: usize) -> MarketDataService {
+        MarketDataService {
+            books: self.books.clone(),
+            latest_book: self.latest_book.subscribe(),
+            trades: self.trades.clone(),
+            default_depth,
+        }
+    }
+
+    /// Run the GrpcPublisher as an asynchronous task
+    ///
+    /// This method will continuously publish events from both channels until they are closed
+    pub async fn run(mut self) {
+        let mut sequence = 0;
+
+        loop {
+            tokio::select! {
+                Some(book) = self.book_channel.recv() => {
+                    sequence += 1;
+                    let state = BookState { sequence, time: Utc::now().timestamp_millis(), book };
+                    self.latest_book.send_replace(Some(state.clone()));
+                    // Sending fails only if there are no subscribers
+                    let _ = self.books.send(state);
+                }
+                Some(event) = self.trade_channel.recv() => {
+                    match event {
+                        MarketEvent::TradeEvent(trade) => {
+                            let _ = self.trades.send(trade);
+                        }
+                        _ => tracing::warn!("Unexpected event in gRPC trade channel: '{}'", event),
+                    }
+                }
+                else => break,
+            }
+        }
+    }
+}
+
+/// Implementation of the `mdc.MarketData` gRPC service
+pub struct MarketDataService {
+    books: broadcast::Sender<BookState>,
+    latest_book: watch::Receiver<Option<BookState>>,
+    trades: broadcast::Sender<TradeEvent>,
+    default_depth: usize,
+}
+
+impl MarketDataService {
+    fn depth(&self, requested: u32) -> usize {
+        match requested {
+            0 => self.default_depth,
+            depth => depth as usize,
+        }
+    }
+
+    /// Forward a broadcast channel into a response stream, converting every item
+    ///
+    /// The forwarding task stops once the client disconnects or the channel is closed
+    fn forward<T, U, F>(mut source: broadcast::Receiver<T>, convert: F) -> ReceiverStream<Result<U, Status>>
+    where
+        T: Clone + Send + 'static,
+        U: Send + 'static,
+        F: Fn(&T) -> U + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(16);
+
+        spawn(async move {
+            loop {
+                match source.recv().await {
+                    Ok(item) => {
+                        if sender.send(Ok(convert(&item))).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::debug!("gRPC subscriber is too slow. Skipped '{}' updates", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        ReceiverStream::new(receiver)
+    }
+}
+
+#[tonic::async_trait]
+impl MarketData for MarketDataService {
+    type StreamOrderBookStream = ReceiverStream<Result<OrderBookUpdate, Status>>;
+    type StreamTradesStream = ReceiverStream<Result<Trade, Status>>;
+
+    async fn stream_order_book(
+        &self,
+        request: Request<StreamOrderBookRequest>,
+    ) -> Result<Response<Self::StreamOrderBookStream>, Status> {
+        let depth = self.depth(request.into_inner().depth);
+        let stream = Self::forward(self.books.subscribe(), move |state: &BookState| state.to_update(depth));
+        Ok(Response::new(stream))
+    }
+
+    async fn stream_trades(
+        &self,
+        _request: Request<StreamTradesRequest>,
+    ) -> Result<Response<Self::StreamTradesStream>, Status> {
+        let stream = Self::forward(self.trades.subscribe(), |trade: &TradeEvent| Trade::from(trade));
+        Ok(Response::new(stream))
+    }
+
+    async fn get_snapshot(&self, request: Request<GetSnapshotRequest>) -> Result<Response<OrderBookUpdate>, Status> {
+        let depth = self.depth(request.into_inner().depth);
+        match self.latest_book.borrow().as_ref() {
+            Some(state) => Ok(Response::new(state.to_update(depth))),
+            None => Err(Status::unavailable("Order book is not available yet")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+    use crate::mdc_core::models::DepthSnapshot;
+    use proto::market_data_client::MarketDataClient;
+    use proto::market_data_server::MarketDataServer;
+
+    #[tokio::test]
+    async fn test_streams_and_snapshot() {
+        let (book_sender, book_receiver) = mpsc::channel(10);
+        let (trade_sender, trade_receiver) = mpsc::channel(10);
+        let publisher = GrpcPublisher::new(book_receiver, trade_receiver);
+        let service = publisher.service(2);
+        tokio::spawn(publisher.run());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(MarketDataServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+        );
+
+        let mut client = MarketDataClient::connect(format!("http://{}", address)).await.unwrap();
+        assert_eq!(
+            client.get_snapshot(GetSnapshotRequest { depth: 0 }).await.unwrap_err().code(),
+            tonic::Code::Unavailable
+        );
+
+        let mut books = client.stream_order_book(StreamOrderBookRequest { depth: 1 }).await.unwrap().into_inner();
+        let mut trades = client.stream_trades(StreamTradesRequest {}).await.unwrap().into_inner();
+
+        book_sender.send(Arc::new(OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+            ],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(3.0) }],
+        }))).await.unwrap();
+        trade_sender.send(MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 7,
+            price: 100.5,
+            quantity: 0.1,
+            trade_time: 999,
+            is_market_maker: true,
+            ignore: true,
+            received: None,
+        })).await.unwrap();
+
+        let update = books.message().await.unwrap().unwrap();
+        assert_eq!(update.sequence, 1);
+        assert_eq!(update.bids, vec![PriceLevel { price: 100.0, quantity: 1.0 }]);
+        assert_eq!(update.asks, vec![PriceLevel { price: 101.0, quantity: 3.0 }]);
+
+        let trade = trades.message().await.unwrap().unwrap();
+        assert_eq!(trade.trade_id, 7);
+        assert!(trade.is_buyer_maker);
+
+        let snapshot = client.get_snapshot(GetSnapshotRequest { depth: 0 }).await.unwrap().into_inner();
+        assert_eq!(snapshot.sequence, 1);
+        assert_eq!(snapshot.bids.len(), 2);
+    }
+}
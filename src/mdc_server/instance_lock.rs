@@ -0,0 +1,156 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::common::daemon;
+
+/// Content of a lock file, identifying the instance which holds the lock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockOwner {
+    pid: u32,
+    exchange: String,
+    instrument: String,
+    started_at: i64,
+}
+
+/// A per exchange+instrument lock, which prevents several mdc instances on the same host
+/// from capturing the same data into the same capture directory
+///
+/// Locks are files in the `.locks` subdirectory of the capture directory, so the directory also serves
+/// as a registry of running instances. The lock is released when the value is dropped. Locks left by
+/// instances which are no longer running are taken over automatically
+pub struct InstanceLock {
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Acquire the lock for the exchange and instrument
+    ///
+    /// # Arguments
+    /// * `capture_dir` - The capture directory the instance writes to
+    /// * `exchange` - Name of the exchange
+    /// * `instrument` - The captured instrument
+    /// * `force` - Take the lock over even if it is held by another running instance
+    ///
+    /// # Errors
+    /// Returns an error if the lock is held by another running instance and `force` is not set
+    pub fn acquire<P: AsRef<Path>>(capture_dir: P, exchange: &str, instrument: &str, force: bool) -> Result<Self> {
+        let locks_dir = capture_dir.as_ref().join(".locks");
+        fs::create_dir_all(&locks_dir)
+            .with_context(|| format!("Failed to create lock directory: {:?}", locks_dir))?;
+
+        let path = locks_dir.join(format!("{}_{}.lock", exchange, instrument));
+        let owner = LockOwner {
+            pid: std::process::id(),
+            exchange: exchange.to_string(),
+            instrument: instrument.to_string(),
+            started_at: Utc::now().timestamp_millis(),
+        };
+        let content = serde_json::to_string(&owner)?;
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())
+                    .with_context(|| format!("Failed to write lock file: {:?}", path))?;
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                match Self::read_owner(&path) {
+                    Some(existing) if daemon::is_running(existing.pid as i32) && !force => {
+                        return Err(anyhow!(
+                            "'{}' on '{}' is already being captured into {:?} by process '{}'. Use --force to override",
+                            instrument, exchange, capture_dir.as_ref(), existing.pid
+                        ));
+                    }
+                    Some(existing) if daemon::is_running(existing.pid as i32) => {
+                        tracing::warn!("Forcefully taking over lock {:?} from running process '{}'", path, existing.pid);
+                    }
+                    _ => {
+                        tracing::warn!("Taking over stale lock {:?}", path);
+                    }
+                }
+
+                fs::write(&path, content)
+                    .with_context(|| format!("Failed to write lock file: {:?}", path))?;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to create lock file: {:?}", path));
+            }
+        }
+
+        tracing::info!("Acquired instance lock: {:?}", path);
+        Ok(Self { path })
+    }
+
+    fn read_owner(path: &Path) -> Option<LockOwner> {
+        let data = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // The lock could have been forcefully taken over by another instance, which must keep it
+        let is_owned = Self::read_owner(&self.path)
+            .map(|owner| owner.pid == std::process::id())
+            .unwrap_or(false);
+
+        if is_owned {
+            if let Err(e) = fs::remove_file(&self.path) {
+                tracing::warn!("Failed to release instance lock {:?}. Details: '{}'", self.path, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_capture_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_second_instance_is_rejected() {
+        let dir = make_capture_dir("lock_rejected");
+
+        let lock = InstanceLock::acquire(&dir, "binance", "BTCUSDT", false).unwrap();
+        assert!(InstanceLock::acquire(&dir, "binance", "BTCUSDT", false).is_err());
+        assert!(InstanceLock::acquire(&dir, "binance", "ETHUSDT", false).is_ok());
+
+        drop(lock);
+        assert!(InstanceLock::acquire(&dir, "binance", "BTCUSDT", false).is_ok());
+    }
+
+    #[test]
+    fn test_force_takes_over_lock() {
+        let dir = make_capture_dir("lock_forced");
+
+        let _lock = InstanceLock::acquire(&dir, "binance", "BTCUSDT", false).unwrap();
+        assert!(InstanceLock::acquire(&dir, "binance", "BTCUSDT", true).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let dir = make_capture_dir("lock_stale");
+        let locks_dir = dir.join(".locks");
+        fs::create_dir_all(&locks_dir).unwrap();
+
+        let stale_owner = LockOwner {
+            pid: u32::MAX,
+            exchange: "binance".to_string(),
+            instrument: "BTCUSDT".to_string(),
+            started_at: 0,
+        };
+        fs::write(locks_dir.join("binance_BTCUSDT.lock"), serde_json::to_string(&stale_owner).unwrap()).unwrap();
+
+        let lock = InstanceLock::acquire(&dir, "binance", "BTCUSDT", false).unwrap();
+        drop(lock);
+        assert!(!locks_dir.join("binance_BTCUSDT.lock").exists());
+    }
+}
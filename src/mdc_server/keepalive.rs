@@ -0,0 +1,196 @@
+use std::time::Duration;
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tungstenite::{Bytes, Message};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+fn default_pong_timeout() -> u64 {
+    10_000
+}
+
+/// Pings, which the client sends over every stream connection, for venues which close idle connections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeepaliveConfig {
+    /// Time in milliseconds between the pings
+    pub interval: u64,
+    /// Text frame sent as the ping (e.g. `ping` for OKX). A WebSocket ping frame is sent if not set
+    #[serde(default)]
+    pub payload: Option<String>,
+    /// Text frame, which answers a text ping (e.g. `pong` for OKX). It is consumed instead of decoded. Without it,
+    /// the answers of text pings aren't awaited
+    #[serde(default)]
+    pub pong: Option<String>,
+    /// Time in milliseconds to wait for the answer of a ping, before the connection is reconnected. 0 disables the check
+    #[serde(default = "default_pong_timeout")]
+    pub pong_timeout: u64,
+}
+
+impl KeepaliveConfig {
+    /// Check the settings for values, which would ping continuously
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.interval == 0 {
+            return Err(anyhow!("Keepalive interval must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// Keepalive sends the pings of a connection and detects connections, whose answers are missing
+///
+/// A ping is sent every interval. A WebSocket ping is answered by a pong frame, a text ping by the configured text
+/// frame. If the answer doesn't arrive within the pong timeout, the connection should be reconnected
+pub struct Keepalive {
+    config: KeepaliveConfig,
+    next_ping: Instant,
+    pong_deadline: Option<Instant>,
+    timeouts: Counter,
+}
+
+impl Keepalive {
+    /// Create a new Keepalive
+    ///
+    /// # Arguments
+    /// * `config` - Interval, payload and answer of the pings
+    /// * `metrics` - Registry of the `keepalive_timeouts` counter
+    pub fn new(config: KeepaliveConfig, metrics: &Metrics) -> Self {
+        Self {
+            next_ping: Instant::now() + Duration::from_millis(config.interval),
+            config,
+            pong_deadline: None,
+            timeouts: metrics.counter("keepalive_timeouts"),
+        }
+    }
+
+    /// Start over with a new connection: the first ping is sent an interval later
+    pub fn on_connected(&mut self) {
+        self.next_ping = Instant::now() + Duration::from_millis(self.config.interval);
+        self.pong_deadline = None;
+    }
+
+    /// Time, at which the next ping is due
+    pub fn next_ping(&self) -> Instant {
+        self.next_ping
+    }
+
+    /// Time, at which the connection is dead if the answer of the outstanding ping doesn't arrive until then
+    pub fn pong_deadline(&self) -> Option<Instant> {
+        self.pong_deadline
+    }
+
+    /// Take the next ping to send, and await its answer
+    pub fn ping(&mut self) -> Message {
+        let now = Instant::now();
+        self.next_ping = now + Duration::from_millis(self.config.interval);
+
+        let is_answered = self.config.payload.is_none() || self.config.pong.is_some();
+        if is_answered && self.config.pong_timeout > 0 && self.pong_deadline.is_none() {
+            self.pong_deadline = Some(now + Duration::from_millis(self.config.pong_timeout));
+        }
+
+        match &self.config.payload {
+            Some(payload) => Message::text(payload.as_str()),
+            None => Message::Ping(Bytes::new()),
+        }
+    }
+
+    /// Account a message received over the connection
+    ///
+    /// # Returns
+    /// `true` if the message answers a ping and must not be decoded
+    pub fn on_message(&mut self, message: &Message) -> bool {
+        let is_pong = match (message, &self.config.payload, &self.config.pong) {
+            (Message::Pong(_), None, _) => true,
+            (Message::Text(text), Some(_), Some(pong)) => text.as_str() == pong,
+            _ => false,
+        };
+
+        if is_pong {
+            self.pong_deadline = None;
+        }
+        is_pong
+    }
+
+    /// Account a missing answer
+    ///
+    /// # Returns
+    /// The error, which finishes the session of the connection
+    pub fn on_timeout(&self, url: &str) -> Error {
+        self.timeouts.increment(1);
+        tracing::warn!("Session '{}' didn't answer a ping within '{}' ms. Reconnecting", url, self.config.pong_timeout);
+        anyhow!("No answer to a ping within '{}' ms", self.config.pong_timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(payload: Option<&str>, pong: Option<&str>) -> KeepaliveConfig {
+        KeepaliveConfig {
+            interval: 25_000,
+            payload: payload.map(str::to_string),
+            pong: pong.map(str::to_string),
+            pong_timeout: 5_000,
+        }
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: KeepaliveConfig = serde_yaml::from_str("interval: 25000\npayload: ping\npong: pong").unwrap();
+        assert_eq!(config, KeepaliveConfig { pong_timeout: 10_000, ..self::config(Some("ping"), Some("pong")) });
+        assert!(config.check().is_ok());
+        assert!(KeepaliveConfig { interval: 0, ..config }.check().is_err());
+    }
+
+    #[test]
+    fn test_websocket_ping() {
+        let metrics = Metrics::new();
+        let mut keepalive = Keepalive::new(config(None, None), &metrics);
+        assert!(keepalive.next_ping() > Instant::now() + Duration::from_millis(24_000));
+        assert_eq!(keepalive.pong_deadline(), None);
+
+        assert_eq!(keepalive.ping(), Message::Ping(Bytes::new()));
+        assert!(keepalive.pong_deadline().is_some());
+
+        // Only the pong frame answers the ping
+        assert!(!keepalive.on_message(&Message::text("pong")));
+        assert!(keepalive.pong_deadline().is_some());
+        assert!(keepalive.on_message(&Message::Pong(Bytes::new())));
+        assert_eq!(keepalive.pong_deadline(), None);
+    }
+
+    #[test]
+    fn test_text_ping() {
+        let metrics = Metrics::new();
+        let mut keepalive = Keepalive::new(config(Some("ping"), Some("pong")), &metrics);
+
+        assert_eq!(keepalive.ping(), Message::text("ping"));
+        let deadline = keepalive.pong_deadline().unwrap();
+
+        // A further ping doesn't extend the deadline of the outstanding one
+        keepalive.ping();
+        assert_eq!(keepalive.pong_deadline(), Some(deadline));
+
+        assert!(!keepalive.on_message(&Message::text(r#"{"arg":{"channel":"trades"},"data":[]}"#)));
+        assert!(keepalive.on_message(&Message::text("pong")));
+        assert_eq!(keepalive.pong_deadline(), None);
+
+        keepalive.ping();
+        keepalive.on_connected();
+        assert_eq!(keepalive.pong_deadline(), None);
+
+        assert_eq!(keepalive.on_timeout("wss://ws.okx.com:8443/ws/v5/public").to_string(), "No answer to a ping within '5000' ms");
+        assert_eq!(metrics.counter("keepalive_timeouts").get(), 1);
+    }
+
+    #[test]
+    fn test_unanswered_text_ping() {
+        let metrics = Metrics::new();
+        let mut keepalive = Keepalive::new(config(Some(r#"{"event":"ping"}"#), None), &metrics);
+        assert_eq!(keepalive.ping(), Message::text(r#"{"event":"ping"}"#));
+        assert_eq!(keepalive.pong_deadline(), None);
+        assert!(!keepalive.on_message(&Message::Pong(Bytes::new())));
+    }
+}
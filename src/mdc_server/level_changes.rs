@@ -0,0 +1,208 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use crate::mdc_server::disk_space_guard::CaptureGate;
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::order_book::Side;
+
+/// Type of a change of a single price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// A new price level appeared
+    Addition,
+    /// The quantity of an existing price level changed
+    Modification,
+    /// A price level was removed
+    Deletion,
+}
+
+impl ChangeKind {
+    /// Classify an update of a price level
+    ///
+    /// # Arguments
+    /// * `previous` - Quantity of the level before the update, if the level existed
+    /// * `quantity` - The new quantity, 0 removes the level
+    ///
+    /// # Returns
+    /// `None` if the update didn't change the book (e.g. removal of a level, which doesn't exist)
+    pub fn classify(previous: Option<FixedPoint>, quantity: FixedPoint) -> Option<Self> {
+        match (previous, quantity.is_zero()) {
+            (None, true) => None,
+            (None, false) => Some(ChangeKind::Addition),
+            (Some(_), true) => Some(ChangeKind::Deletion),
+            (Some(previous), false) if previous == quantity => None,
+            (Some(_), false) => Some(ChangeKind::Modification),
+        }
+    }
+}
+
+/// A change of a single price level of the maintained book, caused by a depth update
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LevelChange {
+    /// Last update id of the depth update
+    pub update_id: u64,
+    /// Exchange event time of the depth update
+    pub event_time: u64,
+    pub side: Side,
+    pub kind: ChangeKind,
+    pub price: FixedPoint,
+    pub quantity: FixedPoint,
+    /// Quantity before the change, 0 for additions
+    pub previous_quantity: FixedPoint,
+}
+
+fn all_sides() -> Vec<Side> {
+    vec![Side::Bid, Side::Ask]
+}
+
+fn all_kinds() -> Vec<ChangeKind> {
+    vec![ChangeKind::Addition, ChangeKind::Modification, ChangeKind::Deletion]
+}
+
+/// Declarative filter of the recorded level changes. By default every change is recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelChangeFilter {
+    #[serde(default = "all_sides")]
+    pub sides: Vec<Side>,
+    #[serde(default = "all_kinds")]
+    pub kinds: Vec<ChangeKind>,
+}
+
+impl Default for LevelChangeFilter {
+    fn default() -> Self {
+        Self { sides: all_sides(), kinds: all_kinds() }
+    }
+}
+
+impl LevelChangeFilter {
+    pub fn matches(&self, change: &LevelChange) -> bool {
+        self.sides.contains(&change.side) && self.kinds.contains(&change.kind)
+    }
+}
+
+/// LevelChangeWriter appends the level changes, which pass the filter, into a JSON lines file
+///
+/// Changes are filtered before serialization, so filtered out changes cost no encoding or disk space
+pub struct LevelChangeWriter {
+    input: mpsc::Receiver<Vec<LevelChange>>,
+    filter: LevelChangeFilter,
+    writer: BufWriter<File>,
+    gate: CaptureGate,
+    written: Counter,
+    filtered: Counter,
+}
+
+impl LevelChangeWriter {
+    /// Open the file for appending, creating it if needed
+    ///
+    /// # Arguments
+    /// * `path` - Path of the level change file
+    /// * `input` - Receiver for the level changes of each depth update
+    /// * `filter` - Filter of the recorded changes
+    /// * `gate` - Switch, which pauses writing. Changes received while writing is paused are discarded
+    /// * `metrics` - Registry of the writer counters
+    pub fn open(
+        path: &PathBuf,
+        input: mpsc::Receiver<Vec<LevelChange>>,
+        filter: LevelChangeFilter,
+        gate: CaptureGate,
+        metrics: &Metrics,
+    ) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open level change file: {:?}", path))?;
+
+        Ok(Self {
+            input,
+            filter,
+            writer: BufWriter::new(file),
+            gate,
+            written: metrics.counter("level_changes_written"),
+            filtered: metrics.counter("level_changes_filtered"),
+        })
+    }
+
+    fn write(&mut self, changes: &[LevelChange]) -> Result<()> {
+        for change in changes {
+            if !self.filter.matches(change) {
+                self.filtered.increment(1);
+                continue;
+            }
+
+            serde_json::to_writer(&mut self.writer, change)?;
+            self.writer.write_all(b"\n")?;
+            self.written.increment(1);
+        }
+        Ok(())
+    }
+
+    /// Run the LevelChangeWriter as an asynchronous task
+    ///
+    /// The file is flushed whenever the input channel is drained
+    pub async fn run(mut self) {
+        while let Some(changes) = self.input.recv().await {
+            if self.gate.is_paused() {
+                continue;
+            }
+
+            if let Err(e) = self.write(&changes) {
+                tracing::error!("Failed to write level changes. Details: '{}'", e);
+            }
+
+            if self.input.is_empty() {
+                if let Err(e) = self.writer.flush() {
+                    tracing::error!("Failed to flush level change file. Details: '{}'", e);
+                }
+            }
+        }
+
+        if let Err(e) = self.writer.flush() {
+            tracing::error!("Failed to flush level change file. Details: '{}'", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(ChangeKind::classify(None, FixedPoint::from(1.0)), Some(ChangeKind::Addition));
+        assert_eq!(ChangeKind::classify(Some(FixedPoint::from(1.0)), FixedPoint::from(2.0)), Some(ChangeKind::Modification));
+        assert_eq!(ChangeKind::classify(Some(FixedPoint::from(1.0)), FixedPoint::ZERO), Some(ChangeKind::Deletion));
+        assert_eq!(ChangeKind::classify(None, FixedPoint::ZERO), None);
+        assert_eq!(ChangeKind::classify(Some(FixedPoint::from(1.0)), FixedPoint::from(1.0)), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter: LevelChangeFilter = serde_yaml::from_str("kinds: [deletion]").unwrap();
+        assert_eq!(filter.sides, vec![Side::Bid, Side::Ask]);
+
+        let mut change = LevelChange {
+            update_id: 1,
+            event_time: 1000,
+            side: Side::Ask,
+            kind: ChangeKind::Deletion,
+            price: FixedPoint::from(100.0),
+            quantity: FixedPoint::ZERO,
+            previous_quantity: FixedPoint::from(1.0),
+        };
+        assert!(filter.matches(&change));
+
+        change.kind = ChangeKind::Addition;
+        assert!(!filter.matches(&change));
+        assert!(LevelChangeFilter::default().matches(&change));
+
+        let bids_only = LevelChangeFilter { sides: vec![Side::Bid], ..LevelChangeFilter::default() };
+        assert!(!bids_only.matches(&change));
+    }
+}
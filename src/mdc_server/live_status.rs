@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::metrics::{Histogram, HistogramSnapshot, Metrics};
+use crate::mdc_server::output_tiers::BookFrame;
+
+/// Current top of the maintained book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopOfBook {
+    pub update_id: u64,
+    pub updated_at: i64,
+    pub bid_price: Option<f64>,
+    pub bid_quantity: Option<f64>,
+    pub ask_price: Option<f64>,
+    pub ask_quantity: Option<f64>,
+}
+
+/// Current state of a single WebSocket stream
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamStatus {
+    pub connected: bool,
+    pub reconnects: u64,
+    pub last_message_at: Option<i64>,
+    /// Local receive time minus exchange event time of the last event, in milliseconds
+    pub lag: Option<i64>,
+}
+
+/// Point-in-time view of a running instance, as served over the admin socket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveStatus {
+    pub exchange: String,
+    pub instrument: String,
+    pub pid: u32,
+    pub started_at: i64,
+    /// Time the status was taken at. All ages are computed relative to it
+    pub generated_at: i64,
+    pub top_of_book: Option<TopOfBook>,
+    pub streams: BTreeMap<String, StreamStatus>,
+    #[serde(default)]
+    pub counters: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub gauges: BTreeMap<String, i64>,
+    #[serde(default)]
+    pub histograms: BTreeMap<String, HistogramSnapshot>,
+}
+
+impl fmt::Display for LiveStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_value = |value: Option<f64>| value.map_or("-".to_string(), |value| value.to_string());
+        let format_age = |time: Option<i64>| time.map_or("never".to_string(), |time| format!("{} ms ago", self.generated_at - time));
+
+        writeln!(
+            f,
+            "{} {} (pid {}, up {} s)",
+            self.exchange,
+            self.instrument,
+            self.pid,
+            (self.generated_at - self.started_at) / 1000
+        )?;
+
+        match &self.top_of_book {
+            Some(top) => {
+                let spread = top.ask_price.zip(top.bid_price).map(|(ask, bid)| ask - bid);
+                writeln!(f, "Top of book (update {}, {}):", top.update_id, format_age(Some(top.updated_at)))?;
+                writeln!(f, "  bid: {} x {}", format_value(top.bid_price), format_value(top.bid_quantity))?;
+                writeln!(f, "  ask: {} x {}", format_value(top.ask_price), format_value(top.ask_quantity))?;
+                writeln!(f, "  spread: {}", format_value(spread))?;
+            }
+            None => writeln!(f, "Top of book: not available yet")?,
+        }
+
+        writeln!(f, "Streams:")?;
+        for (name, stream) in &self.streams {
+            writeln!(
+                f,
+                "  {:<10} {:<12} last message {:<14} lag {:<10} reconnects {}",
+                name,
+                if stream.connected { "connected" } else { "disconnected" },
+                format_age(stream.last_message_at),
+                stream.lag.map_or("-".to_string(), |lag| format!("{} ms", lag)),
+                stream.reconnects
+            )?;
+        }
+
+        if !self.counters.is_empty() {
+            writeln!(f, "Counters:")?;
+            for (name, value) in &self.counters {
+                writeln!(f, "  {:<32} {}", name, value)?;
+            }
+        }
+
+        if !self.gauges.is_empty() {
+            writeln!(f, "Gauges:")?;
+            for (name, value) in &self.gauges {
+                writeln!(f, "  {:<32} {}", name, value)?;
+            }
+        }
+
+        if !self.histograms.is_empty() {
+            let format_ms = |value: Option<i64>| value.map_or("-".to_string(), |value| format!("{} ms", value));
+            writeln!(f, "Histograms:")?;
+            for (name, histogram) in &self.histograms {
+                writeln!(
+                    f,
+                    "  {:<32} count {:<10} min {:<10} p50 {:<10} p99 {:<10} max {}",
+                    name,
+                    histogram.count,
+                    format_ms(histogram.min),
+                    format_ms(histogram.p50),
+                    format_ms(histogram.p99),
+                    format_ms(histogram.max)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The latest maintained book, numbered like the published book frames
+struct LatestBook {
+    sequence: u64,
+    time: i64,
+    book: Arc<OrderBook>,
+}
+
+/// Shared, continuously updated LiveStatus of the running instance
+#[derive(Clone)]
+pub struct StatusBoard {
+    status: Arc<Mutex<LiveStatus>>,
+    book: Arc<Mutex<Option<LatestBook>>>,
+    metrics: Metrics,
+}
+
+impl StatusBoard {
+    /// Create a new StatusBoard
+    ///
+    /// # Arguments
+    /// * `exchange` - Name of the exchange
+    /// * `instrument` - The captured instrument
+    /// * `metrics` - Registry of the counters, which are served along with the status
+    pub fn new(exchange: &str, instrument: &str, metrics: Metrics) -> Self {
+        let now = Utc::now().timestamp_millis();
+        let status = LiveStatus {
+            exchange: exchange.to_string(),
+            instrument: instrument.to_string(),
+            pid: std::process::id(),
+            started_at: now,
+            generated_at: now,
+            top_of_book: None,
+            streams: BTreeMap::new(),
+            counters: BTreeMap::new(),
+            gauges: BTreeMap::new(),
+            histograms: BTreeMap::new(),
+        };
+
+        Self { status: Arc::new(Mutex::new(status)), book: Arc::new(Mutex::new(None)), metrics }
+    }
+
+    /// Register a stream and return the reporter, which keeps its status up to date
+    ///
+    /// The receive latencies of the stream go to the `receive_latency_<kind>` histogram, shared by the connections
+    /// of the same kind (the stream name without the `#<index>` suffix)
+    pub fn stream(&self, name: String) -> StreamStatusReporter {
+        self.update(|status| {
+            status.streams.insert(name.clone(), StreamStatus::default());
+        });
+
+        let kind = name.split('#').next().unwrap_or_default();
+        let latency = self.metrics.histogram(&format!("receive_latency_{}", kind));
+        StreamStatusReporter { name, board: self.clone(), latency }
+    }
+
+    /// Take a point-in-time copy of the status
+    pub fn snapshot(&self) -> LiveStatus {
+        let mut status = self.status.lock().expect("Status board lock is poisoned").clone();
+        status.generated_at = Utc::now().timestamp_millis();
+        status.counters = self.metrics.snapshot();
+        status.gauges = self.metrics.gauge_snapshot();
+        status.histograms = self.metrics.histogram_snapshot();
+        status
+    }
+
+    /// Top levels of the latest maintained book, if there has been one
+    pub fn book_frame(&self, depth: usize) -> Option<BookFrame> {
+        let latest = self.book.lock().expect("Status board lock is poisoned");
+        latest.as_ref().map(|latest| BookFrame::new(latest.sequence, latest.time, &latest.book, depth))
+    }
+
+    fn set_book(&self, book: Arc<OrderBook>, now: i64) {
+        let mut latest = self.book.lock().expect("Status board lock is poisoned");
+        let sequence = latest.as_ref().map_or(1, |latest| latest.sequence + 1);
+        *latest = Some(LatestBook { sequence, time: now, book });
+    }
+
+    fn update<F: FnOnce(&mut LiveStatus)>(&self, f: F) {
+        f(&mut self.status.lock().expect("Status board lock is poisoned"));
+    }
+}
+
+/// Reports the state of a single stream to the StatusBoard
+pub struct StreamStatusReporter {
+    name: String,
+    board: StatusBoard,
+    latency: Histogram,
+}
+
+impl StreamStatusReporter {
+    fn update<F: FnOnce(&mut StreamStatus)>(&self, f: F) {
+        self.board.update(|status| {
+            if let Some(stream) = status.streams.get_mut(&self.name) {
+                f(stream);
+            }
+        });
+    }
+
+    pub fn on_connected(&self) {
+        self.update(|stream| stream.connected = true);
+    }
+
+    pub fn on_disconnected(&self) {
+        self.update(|stream| {
+            stream.connected = false;
+            stream.reconnects += 1;
+        });
+    }
+
+    /// Account an event at its local receive time (or now, if the event is not stamped)
+    pub fn on_event(&self, event: &MarketEvent) {
+        let latency = event.receive_latency();
+        if let Some(latency) = latency {
+            self.latency.record(latency);
+        }
+
+        let now = event.received().map_or_else(|| Utc::now().timestamp_millis(), |received| received.wall_clock);
+        self.update(|stream| {
+            stream.last_message_at = Some(now);
+            if latency.is_some() {
+                stream.lag = latency;
+            }
+        });
+    }
+}
+
+/// LiveStatusTracker keeps the top of book and the latest book on the StatusBoard up to date
+pub struct LiveStatusTracker {
+    board: StatusBoard,
+    bbo_channel: mpsc::Receiver<MarketEvent>,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+}
+
+impl LiveStatusTracker {
+    /// Create a new LiveStatusTracker
+    ///
+    /// # Arguments
+    /// * `board` - The StatusBoard to update
+    /// * `bbo_channel` - Receiver for MarketEvent messages containing BboChanges
+    /// * `book_channel` - Receiver for OrderBook messages
+    pub fn new(board: StatusBoard, bbo_channel: mpsc::Receiver<MarketEvent>, book_channel: mpsc::Receiver<Arc<OrderBook>>) -> Self {
+        Self { board, bbo_channel, book_channel }
+    }
+
+    /// Run the LiveStatusTracker as an asynchronous task
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                Some(event) = self.bbo_channel.recv() => {
+                    let MarketEvent::BboChange(bbo) = event else {
+                        tracing::warn!("Unexpected event in status bbo channel: '{}'", event);
+                        continue;
+                    };
+
+                    let top_of_book = TopOfBook {
+                        update_id: bbo.update_id,
+                        updated_at: Utc::now().timestamp_millis(),
+                        bid_price: bbo.best_bid.as_ref().map(|entry| entry.price.to_f64()),
+                        bid_quantity: bbo.best_bid.as_ref().map(|entry| entry.quantity.to_f64()),
+                        ask_price: bbo.best_ask.as_ref().map(|entry| entry.price.to_f64()),
+                        ask_quantity: bbo.best_ask.as_ref().map(|entry| entry.quantity.to_f64()),
+                    };
+
+                    self.board.update(|status| status.top_of_book = Some(top_of_book));
+                }
+                Some(book) = self.book_channel.recv() => {
+                    self.board.set_book(book, Utc::now().timestamp_millis());
+                }
+                else => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{BboChange, DepthEntry, DepthSnapshot, ReceiveTime, TradeEvent};
+
+    #[test]
+    fn test_stream_status_reporting() {
+        let board = StatusBoard::new("binance", "BTCUSDT", Metrics::new());
+        let reporter = board.stream("trade".to_string());
+
+        reporter.on_connected();
+        reporter.on_event(&MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 1,
+            price: 100.0,
+            quantity: 1.0,
+            trade_time: 1000,
+            is_market_maker: false,
+            ignore: true,
+            received: Some(ReceiveTime::at(1050)),
+        }));
+        reporter.on_disconnected();
+
+        let status = board.snapshot();
+        assert_eq!(status.streams["trade"], StreamStatus {
+            connected: false,
+            reconnects: 1,
+            last_message_at: Some(1050),
+            lag: Some(50),
+        });
+        assert_eq!(status.histograms["receive_latency_trade"].p50, Some(50));
+    }
+
+    #[tokio::test]
+    async fn test_tracker_updates_top_of_book() {
+        let board = StatusBoard::new("binance", "BTCUSDT", Metrics::new());
+        let (sender, receiver) = mpsc::channel(10);
+
+        let (book_sender, book_receiver) = mpsc::channel(10);
+
+        sender.send(MarketEvent::BboChange(BboChange {
+            update_id: 42,
+            best_bid: Some(DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) }),
+            best_ask: None,
+        })).await.unwrap();
+        drop(sender);
+
+        let snapshot = DepthSnapshot {
+            last_update_id: 42,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) }],
+            asks: vec![],
+        };
+        book_sender.send(Arc::new(OrderBook::new(&snapshot))).await.unwrap();
+        book_sender.send(Arc::new(OrderBook::new(&snapshot))).await.unwrap();
+        drop(book_sender);
+
+        assert!(board.book_frame(10).is_none());
+        LiveStatusTracker::new(board.clone(), receiver, book_receiver).run().await;
+
+        let top = board.snapshot().top_of_book.unwrap();
+        assert_eq!(top.update_id, 42);
+        assert_eq!(top.bid_price, Some(100.0));
+        assert_eq!(top.ask_price, None);
+
+        let frame = board.book_frame(10).unwrap();
+        assert_eq!(frame.sequence, 2);
+        assert_eq!(frame.bids, vec![[100.0, 1.0]]);
+    }
+}
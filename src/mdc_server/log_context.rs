@@ -0,0 +1,100 @@
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tracing::{Instrument, Span};
+
+/// The span of a pipeline, which the logs of all its stages carry, e.g. `pipeline{exchange=binance symbol=BTCUSDT}`
+///
+/// # Arguments
+/// * `exchange` - Name of the exchange
+/// * `symbol` - Canonical symbol of the captured instrument
+pub fn pipeline_span(exchange: &str, symbol: &str) -> Span {
+    tracing::info_span!("pipeline", exchange, symbol)
+}
+
+/// The span of a stream connection within its pipeline, e.g. `stream{stream=depth#0}`
+pub fn stream_span(stream: &str) -> Span {
+    tracing::info_span!("stream", stream)
+}
+
+/// Spawn a task, which stays within the current span
+///
+/// Tasks spawned with `tokio::spawn` start without a span, so the logs of a pipeline stage would lose the symbol and
+/// exchange of their pipeline
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future.in_current_span())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::EnvFilter;
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Output {
+        type Writer = Output;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn logs(output: &Output) -> String {
+        String::from_utf8(output.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spawned_tasks_log_within_the_pipeline_span() {
+        let output = Output::default();
+        let subscriber = tracing_subscriber::fmt().with_writer(output.clone()).with_ansi(false).finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        async {
+            spawn(async { tracing::info!("Starting book processor") }).await.unwrap();
+            spawn(async { tracing::info!("Connected") }.instrument(stream_span("depth#0"))).await.unwrap();
+        }.instrument(pipeline_span("binance", "BTCUSDT")).await;
+
+        let logs = logs(&output);
+        assert!(logs.contains("pipeline{exchange=\"binance\" symbol=\"BTCUSDT\"}: mdc::mdc_server::log_context::tests: Starting book processor"), "{}", logs);
+        assert!(logs.contains("pipeline{exchange=\"binance\" symbol=\"BTCUSDT\"}:stream{stream=\"depth#0\"}: mdc::mdc_server::log_context::tests: Connected"), "{}", logs);
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_symbol() {
+        let output = Output::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(output.clone())
+            .with_env_filter(EnvFilter::new("info,[pipeline{symbol=ETHUSDT}]=debug"))
+            .finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        for symbol in ["BTCUSDT", "ETHUSDT"] {
+            async {
+                spawn(async { tracing::debug!("Book updated") }).await.unwrap();
+            }.instrument(pipeline_span("binance", symbol)).await;
+        }
+
+        let logs = logs(&output);
+        assert!(logs.contains("ETHUSDT"), "{}", logs);
+        assert!(!logs.contains("BTCUSDT"), "{}", logs);
+    }
+}
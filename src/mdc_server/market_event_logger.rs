@@ -1,14 +1,26 @@
+use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Duration, Instant};
 
-use crate::mdc_server::models::{MarketEvent};
-use crate::mdc_server::order_book::OrderBook;
+use crate::mdc_core::models::{MarketEvent};
+use crate::mdc_core::order_book::OrderBook;
 
 /// EventLogger is responsible for logging market events to stdout
-/// It receives events from three channels: MarketEvent (for trades), MarketEvent (for prices), and OrderBook
+/// It receives events from five channels: MarketEvent (for trades), MarketEvent (for prices), OrderBook,
+/// MarketEvent (for top of book changes) and MarketEvent (for klines and aggregated trades)
+///
+/// The book is printed in full on every update, unless its output is limited with `with_book_output`
 pub struct MarketEventLogger {
     trade_channel: mpsc::Receiver<MarketEvent>,
     price_channel: mpsc::Receiver<MarketEvent>,
-    book_channel: mpsc::Receiver<OrderBook>,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    bbo_channel: mpsc::Receiver<MarketEvent>,
+    auxiliary_channel: mpsc::Receiver<MarketEvent>,
+    book_depth: usize,
+    book_interval: Duration,
+    /// The latest book, which hasn't been printed yet because of the book interval
+    pending_book: Option<Arc<OrderBook>>,
+    last_book_print: Option<Instant>,
 }
 
 impl MarketEventLogger {
@@ -18,24 +30,68 @@ impl MarketEventLogger {
     /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
     /// * `price_channel` - Receiver for MarketEvent messages containing PriceUpdates
     /// * `book_channel` - Receiver for OrderBook messages
+    /// * `bbo_channel` - Receiver for MarketEvent messages containing BboChanges
+    /// * `auxiliary_channel` - Receiver for MarketEvent messages containing KlineEvents, AggTradeEvents,
+    ///   TickerEvents, MiniTickerEvents, MarkPriceEvents, LiquidationEvents and PartialBookEvents
     pub fn new(
         trade_channel: mpsc::Receiver<MarketEvent>,
         price_channel: mpsc::Receiver<MarketEvent>,
-        book_channel: mpsc::Receiver<OrderBook>,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+        bbo_channel: mpsc::Receiver<MarketEvent>,
+        auxiliary_channel: mpsc::Receiver<MarketEvent>,
     ) -> Self {
         Self {
             trade_channel,
             price_channel,
             book_channel,
+            bbo_channel,
+            auxiliary_channel,
+            book_depth: usize::MAX,
+            book_interval: Duration::ZERO,
+            pending_book: None,
+            last_book_print: None,
         }
     }
 
+    /// Limit the book output
+    ///
+    /// # Arguments
+    /// * `depth` - Best levels per side printed for each book (0 prints the whole book)
+    /// * `interval` - Minimum interval between printed books in milliseconds. Books arriving within the interval
+    ///   are conflated, only the latest of them is printed at its end (0 prints every book)
+    pub fn with_book_output(mut self, depth: usize, interval: u64) -> Self {
+        self.book_depth = if depth == 0 { usize::MAX } else { depth };
+        self.book_interval = Duration::from_millis(interval);
+        self
+    }
+
+    /// The time, at which the next book can be printed
+    fn next_book_print(&self) -> Option<Instant> {
+        self.last_book_print.map(|last| last + self.book_interval)
+    }
+
+    fn on_book(&mut self, book: Arc<OrderBook>) {
+        if self.next_book_print().is_some_and(|next| Instant::now() < next) {
+            self.pending_book = Some(book);
+        } else {
+            self.print_book(&book);
+        }
+    }
+
+    fn print_book(&mut self, book: &OrderBook) {
+        println!("{}", book.format_top(self.book_depth));
+        self.last_book_print = Some(Instant::now());
+    }
+
     /// Run the EventLogger as an asynchronous task
     ///
-    /// This method will continuously process messages from all three channels
+    /// This method will continuously process messages from all channels
     /// and log them to stdout until all channels are closed
     pub async fn run(mut self) {
         loop {
+            let book_deadline = self.next_book_print().unwrap_or_else(Instant::now);
+            let book_pending = self.pending_book.is_some();
+
             tokio::select! {
                 Some(event) = self.trade_channel.recv() => {
                     match event {
@@ -51,14 +107,73 @@ impl MarketEventLogger {
                 }
                 
                 Some(book) = self.book_channel.recv() => {
-                    println!("{}", book);
+                    self.on_book(book);
+                }
+                _ = sleep_until(book_deadline), if book_pending => {
+                    if let Some(book) = self.pending_book.take() {
+                        self.print_book(&book);
+                    }
+                }
+                Some(event) = self.bbo_channel.recv() => {
+                    match event {
+                        MarketEvent::BboChange(bbo) => { println!("BBO: {}", bbo); },
+                        _ => { tracing::warn!("Unexpected event in bbo channel: '{}'", event); }
+                    }
+                }
+                Some(event) = self.auxiliary_channel.recv() => {
+                    match event {
+                        MarketEvent::KlineEvent(kline) => { println!("KLINE: {}", kline); },
+                        MarketEvent::AggTradeEvent(trade) => { println!("AGG_TRADE: {}", trade); },
+                        MarketEvent::TickerEvent(ticker) => { println!("TICKER: {}", ticker); },
+                        MarketEvent::MiniTickerEvent(ticker) => { println!("MINI_TICKER: {}", ticker); },
+                        MarketEvent::MarkPriceEvent(price) => { println!("MARK_PRICE: {}", price); },
+                        MarketEvent::LiquidationEvent(liquidation) => { println!("LIQUIDATION: {}", liquidation); },
+                        MarketEvent::PartialBook(book) => { println!("PARTIAL_BOOK: {}", book); },
+                        MarketEvent::BookMetrics(metrics) => { println!("BOOK_METRICS: {}", metrics); },
+                        MarketEvent::TradeFlow(flow) => { println!("TRADE_FLOW: {}", flow); },
+                        MarketEvent::Volatility(metrics) => { println!("VOLATILITY: {}", metrics); },
+                        MarketEvent::Anomaly(anomaly) => { println!("ANOMALY: {}", anomaly); },
+                        MarketEvent::ScriptFields(fields) => { println!("SCRIPT_FIELDS: {}", fields); },
+                        MarketEvent::SequenceGap(gap) => { println!("SEQUENCE_GAP: {}", gap); },
+                        MarketEvent::Resync(resync) => { println!("RESYNC: {}", resync); },
+                        _ => { tracing::warn!("Unexpected event in auxiliary channel: '{}'", event); }
+                    }
                 }
                 
                 // If all channels are closed, break the loop
                 else => break,
             }
         }
-        
-        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{DepthSnapshot, FromJson};
+
+    #[test]
+    fn test_books_within_the_interval_are_conflated() {
+        let (_trade_tx, trade_rx) = mpsc::channel(1);
+        let (_price_tx, price_rx) = mpsc::channel(1);
+        let (_book_tx, book_rx) = mpsc::channel(1);
+        let (_bbo_tx, bbo_rx) = mpsc::channel(1);
+        let (_auxiliary_tx, auxiliary_rx) = mpsc::channel(1);
+        let mut logger = MarketEventLogger::new(trade_rx, price_rx, book_rx, bbo_rx, auxiliary_rx).with_book_output(1, 1000);
+
+        let snapshot = DepthSnapshot::from_json(r#"{"lastUpdateId":7,"bids":[["100.0","1.0"]],"asks":[]}"#).unwrap();
+        let book = Arc::new(OrderBook::new(&snapshot));
+
+        logger.on_book(book.clone());
+        assert!(logger.pending_book.is_none());
+
+        logger.on_book(book.clone());
+        logger.on_book(book.clone());
+        assert!(logger.pending_book.is_some());
+
+        logger.last_book_print = Some(Instant::now() - Duration::from_millis(1000));
+        logger.pending_book = None;
+        logger.on_book(book);
+        assert!(logger.pending_book.is_none());
     }
 }
@@ -1,14 +1,43 @@
 use std::error::Error;
-use tokio_tungstenite::{connect_async, tungstenite};
+use tokio_tungstenite::tungstenite;
 use futures::{StreamExt, SinkExt};
 use tokio::sync::mpsc;
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{interval_at, sleep, sleep_until, Instant};
 use anyhow::Result;
+use chrono::Utc;
 use tungstenite::{Bytes, Message};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::{HeaderName, HeaderValue};
 use tungstenite::protocol::CloseFrame;
-use std::marker::PhantomData;
-use crate::mdc_server::models::{MarketEvent, MarketEventSource};
+use crate::mdc_core::models::{MarketEvent, ReceiveTime, StreamMessage};
+#[cfg(feature = "sbe")]
+use crate::mdc_core::sbe;
+use crate::mdc_server::tape::TapeRecorder;
+use crate::mdc_server::connection_health::ConnectionHealth;
+use crate::mdc_server::live_status::StreamStatusReporter;
+use crate::mdc_server::allocation_profiling::PARSE_ALLOCATIONS;
+use crate::mdc_server::metrics::Metrics;
+use crate::mdc_server::stall_watchdog::StallWatchdog;
+use crate::mdc_server::keepalive::{Keepalive, KeepaliveConfig};
+use crate::mdc_server::session_rotation::SessionRotation;
+use crate::mdc_server::transport::Transport;
+use crate::mdc_server::endpoint_selector::EndpointSelector;
+
+/// Pause in the stream in milliseconds, during which an unhealthy connection is considered safe to cycle
+const QUIET_PERIOD: u64 = 200;
+
+/// Reason, for which a session has ended without an error
+enum SessionEnd {
+    /// The connection has been closed by the server
+    Closed,
+    /// The connection has been cycled, because it is chronically unhealthy
+    Cycled,
+    /// The session has been rotated before reaching the session lifetime of the exchange
+    Rotated,
+    /// The session has been restarted to receive a fresh snapshot
+    SnapshotRequested,
+}
 
 /// A WebSocket client that connects to a market data stream and forwards events to a processing queue.
 ///
@@ -17,19 +46,55 @@ use crate::mdc_server::models::{MarketEvent, MarketEventSource};
 /// event queue for further processing. It automatically handles reconnection in case of connection
 /// failures.
 ///
-/// The generic type parameter `T` must implement the `MarketEventSource` trait, which defines
-/// how to parse JSON messages from the WebSocket stream into domain-specific event types.
+/// The generic type parameter `T` must implement the `StreamMessage` trait, which defines
+/// how to decode text frames of the WebSocket stream into market events. Its decoding state starts over
+/// with each connection.
+///
+/// Every event is stamped with its local receive time (`ReceiveTime`), taken when its frame is received.
+///
+/// If a `TapeRecorder` is provided, every raw text frame is recorded before it is parsed.
+///
+/// With the `sbe` feature, binary frames are decoded as well. A binary frame is recorded as the frames of
+/// the JSON stream, which carry its events, so its tape replays like a tape of the JSON stream.
+///
+/// If a `ConnectionHealth` is provided, the connection is scored every health window. A chronically unhealthy
+/// connection is cycled (reconnected) during the next pause in the stream, or after one more window at the latest.
+///
+/// If a stall timeout is set, a connection, which receives no frames for the timeout, is reconnected. If a keepalive
+/// is set, the client pings the venue and reconnects, when the answer is missing.
+///
+/// If a `SessionRotation` is provided, sessions are reconnected proactively before the exchange closes them,
+/// in turns with the other connections of the rotation group.
+///
+/// If subscription messages are provided, they are sent after connecting, for venues which subscribe over the connection
+/// instead of the URL. If headers are provided, they are sent with the connection request (e.g. an API key). If a transport is provided, the connection
+/// is made with its proxy and TLS settings. If snapshot requests are provided, a requested snapshot is obtained by reconnecting,
+/// for venues which start the depth stream with a snapshot.
+///
+/// If alternate endpoints are provided, every connection attempt goes to the endpoint with the lowest latency until
+/// the first message, and a failed session fails over to another endpoint without waiting for the reconnect timeout.
 pub struct MarketEventStream<T>
-where T: MarketEventSource,
+where T: StreamMessage,
 {
     url: String,
     event_queue: mpsc::Sender<MarketEvent>,
     reconnect_timeout: u64,
-    _phantom: PhantomData<T>,
+    recorder: Option<TapeRecorder>,
+    health: Option<ConnectionHealth>,
+    status: Option<StreamStatusReporter>,
+    watchdog: Option<StallWatchdog>,
+    keepalive: Option<Keepalive>,
+    rotation: Option<SessionRotation>,
+    subscription: Vec<String>,
+    headers: Vec<(String, String)>,
+    transport: Transport,
+    endpoints: EndpointSelector,
+    snapshot_requests: Option<mpsc::Receiver<()>>,
+    state: T::State,
 }
 
 impl<T> MarketEventStream<T>
-where T: MarketEventSource,
+where T: StreamMessage,
 {
     /// Creates a new `MarketEventStream` instance.
     ///
@@ -37,15 +102,124 @@ where T: MarketEventSource,
     /// * `url` - The WebSocket endpoint URL to connect to
     /// * `event_queue` - Channel for sending parsed market events to the processing pipeline
     /// * `reconnect_timeout` - Timeout in milliseconds to wait before attempting to reconnect after a connection failure
+    /// * `recorder` - Optional recorder, which persists every raw frame received from the WebSocket
+    /// * `health` - Optional health tracker, which enables automatic cycling of unhealthy connections
+    /// * `status` - Optional reporter, which publishes the stream state (connection, last message, lag) to the status board
     ///
     /// # Returns
     /// A new `MarketEventStream` instance configured with the provided parameters
-    pub fn new(url: String, event_queue: mpsc::Sender<MarketEvent>, reconnect_timeout: u64) -> Self {
+    pub fn new(
+        url: String,
+        event_queue: mpsc::Sender<MarketEvent>,
+        reconnect_timeout: u64,
+        recorder: Option<TapeRecorder>,
+        health: Option<ConnectionHealth>,
+        status: Option<StreamStatusReporter>,
+    ) -> Self {
         Self {
+            endpoints: EndpointSelector::new(vec![url.clone()]),
             url,
             event_queue,
             reconnect_timeout,
-            _phantom: PhantomData,
+            recorder,
+            health,
+            status,
+            watchdog: None,
+            keepalive: None,
+            rotation: None,
+            subscription: Vec::new(),
+            headers: Vec::new(),
+            transport: Transport::default(),
+            snapshot_requests: None,
+            state: T::State::default(),
+        }
+    }
+
+    /// Reconnect the stream if no frames are received for the given time
+    ///
+    /// # Arguments
+    /// * `stall_timeout` - Time in milliseconds without frames, after which the connection is stalled. 0 disables the check
+    /// * `metrics` - Registry of the stall counter
+    pub fn with_stall_timeout(mut self, stall_timeout: u64, metrics: &Metrics) -> Self {
+        self.watchdog = (stall_timeout > 0).then(|| StallWatchdog::new(stall_timeout, metrics));
+        self
+    }
+
+    /// Ping the venue periodically and reconnect the stream if a ping isn't answered
+    ///
+    /// # Arguments
+    /// * `keepalive` - Interval, payload and answer of the pings
+    /// * `metrics` - Registry of the keepalive timeout counter
+    pub fn with_keepalive(mut self, keepalive: KeepaliveConfig, metrics: &Metrics) -> Self {
+        self.keepalive = Some(Keepalive::new(keepalive, metrics));
+        self
+    }
+
+    /// Rotate the sessions of the stream in turns with the other connections of the rotation group
+    ///
+    /// # Arguments
+    /// * `rotation` - Rotation of the connection within its group
+    pub fn with_rotation(mut self, rotation: SessionRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Send the subscription messages after connecting
+    ///
+    /// # Arguments
+    /// * `subscription` - The text frames, which subscribe to the stream (e.g. an OKX `subscribe` operation), in order
+    pub fn with_subscription(mut self, subscription: Vec<String>) -> Self {
+        self.subscription = subscription;
+        self
+    }
+
+    /// Send the headers with the connection request
+    ///
+    /// # Arguments
+    /// * `headers` - Names and values of the headers (e.g. the API key of an authenticated stream)
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Connect with the proxy and TLS settings of the transport
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Connect to the fastest of several endpoints, which serve the same stream, and fail over between them
+    ///
+    /// The alternate URLs replace the endpoint, which the URL of the stream starts with, by each alternate endpoint.
+    /// A stream, whose URL doesn't start with the endpoint, keeps its only URL
+    ///
+    /// # Arguments
+    /// * `endpoint` - The endpoint, which the URL of the stream starts with (e.g. `wss://stream.binance.com:9443/ws/`)
+    /// * `alternates` - The endpoints, which serve the same streams (e.g. `wss://data-stream.binance.vision/ws/`)
+    pub fn with_alternate_endpoints(mut self, endpoint: &str, alternates: &[String]) -> Self {
+        if let Some(path) = self.url.strip_prefix(endpoint).filter(|_| !alternates.is_empty()) {
+            let urls = std::iter::once(self.url.clone())
+                .chain(alternates.iter().map(|alternate| format!("{}{}", alternate, path)))
+                .collect();
+            self.endpoints = EndpointSelector::new(urls);
+        }
+        self
+    }
+
+    /// Reconnect whenever a message is received from the channel, so the stream starts over with a fresh snapshot
+    ///
+    /// # Arguments
+    /// * `snapshot_requests` - Requests of a fresh snapshot (e.g. by the book processor)
+    pub fn with_snapshot_requests(mut self, snapshot_requests: mpsc::Receiver<()>) -> Self {
+        self.snapshot_requests = Some(snapshot_requests);
+        self
+    }
+
+    /// Receive the next snapshot request. Never completes if there are no requests or their channel is closed
+    async fn next_snapshot_request(snapshot_requests: &mut Option<mpsc::Receiver<()>>) -> Option<()> {
+        match snapshot_requests.as_mut() {
+            Some(requests) => requests.recv().await,
+            None => std::future::pending().await,
         }
     }
     
@@ -59,10 +233,41 @@ where T: MarketEventSource,
     /// be spawned as a separate task.
     pub async fn run(&mut self) {
         loop {
-            match self.run_session().await {
-                Ok(_) => {
+            self.url = self.endpoints.select(Utc::now().timestamp_millis()).to_string();
+            let result = self.run_session().await;
+
+            if let Some(status) = &self.status {
+                status.on_disconnected();
+            }
+
+            // A rotated session has already left the rotation group
+            if let Some(rotation) = &mut self.rotation {
+                rotation.on_disconnected();
+            }
+
+            let now = Utc::now().timestamp_millis();
+            let cycled = matches!(result, Ok(SessionEnd::Cycled | SessionEnd::Rotated | SessionEnd::SnapshotRequested));
+            if let Some(health) = &mut self.health {
+                health.on_session_end(now, cycled);
+            }
+            self.endpoints.on_session_end(now, matches!(result, Ok(SessionEnd::Cycled) | Err(_)));
+
+            match result {
+                Ok(SessionEnd::Cycled) => {
+                    tracing::info!("Session '{}' cycled due to poor health. Reconnecting", self.url);
+                }
+                Ok(SessionEnd::Rotated) => {
+                    tracing::info!("Session '{}' reached its lifetime. Reconnecting", self.url);
+                }
+                Ok(SessionEnd::SnapshotRequested) => {
+                    tracing::info!("Snapshot requested from session '{}'. Reconnecting", self.url);
+                }
+                Ok(SessionEnd::Closed) => {
                     tracing::trace!("Session '{}' finished", self.url);
                 }
+                Err(e) if self.endpoints.can_fail_over(now) => {
+                    tracing::error!("Session '{}' finished with error: '{}'. Failing over to another endpoint", self.url, e);
+                }
                 Err(e) => {
                     tracing::error!("Session '{}' finished with error: '{}'. Reconnecting in '{}' ms", self.url, e, self.reconnect_timeout);
                     sleep(Duration::from_millis(self.reconnect_timeout)).await;
@@ -77,31 +282,154 @@ where T: MarketEventSource,
     /// the connection is closed or an error occurs, and then returns.
     ///
     /// # Returns
-    /// * `Ok(())` if the session completed normally
+    /// * `Ok(...)` with the reason, for which the session has ended
     /// * `Err(...)` if an error occurred during the session
-    async fn run_session(&mut self) -> Result<()> {
-        let (ws_stream, _) = connect_async(&self.url).await?;
+    async fn run_session(&mut self) -> Result<SessionEnd> {
+        let mut request = self.url.as_str().into_client_request()?;
+        for (name, value) in &self.headers {
+            request.headers_mut().insert(HeaderName::try_from(name.as_str())?, HeaderValue::try_from(value.as_str())?);
+        }
+
+        let started = Instant::now();
+        let (ws_stream, _) = self.transport.connect_websocket(request).await?;
+        self.endpoints.on_connected(started.elapsed().as_millis() as u64);
+        let mut awaiting_first_message = true;
         let (mut ws_writer, mut ws_reader) = ws_stream.split();
 
-        while let Some(msg) = ws_reader.next().await {
-            tracing::trace!("Received message: '{:?}'", msg);
-            
-            match msg {
-                Ok(Message::Text(text)) => { self.on_message(&text).await?; }
-                Ok(Message::Ping(payload)) => { self.on_ping(&mut ws_writer, &payload).await?; }
-                Ok(Message::Close(frame)) => { self.on_close(frame).await?; }
-                Err(e) => { return Err(e.into()); }
-                _ => {}
+        for message in &self.subscription {
+            ws_writer.send(Message::text(message.as_str())).await?;
+        }
+        self.state = T::State::default();
+
+        if let Some(status) = &self.status {
+            status.on_connected();
+        }
+
+        // Without health tracking the evaluation branch is disabled, so the window value doesn't matter
+        let window = Duration::from_millis(self.health.as_ref().map_or(1000, |health| health.window()));
+        let mut evaluation = interval_at(Instant::now() + window, window);
+        let mut cycle_deadline: Option<Instant> = None;
+
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.on_message();
+        }
+
+        if let Some(keepalive) = &mut self.keepalive {
+            keepalive.on_connected();
+        }
+
+        if let Some(rotation) = &mut self.rotation {
+            rotation.on_connected();
+        }
+
+        loop {
+            let stall_deadline = self.watchdog.as_ref().map(StallWatchdog::deadline);
+            let rotation_deadline = self.rotation.as_ref().map(SessionRotation::deadline);
+            let next_ping = self.keepalive.as_ref().map(Keepalive::next_ping);
+            let pong_deadline = self.keepalive.as_ref().and_then(Keepalive::pong_deadline);
+
+            tokio::select! {
+                msg = ws_reader.next() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+
+                    tracing::trace!("Received message: '{:?}'", msg);
+
+                    if let Some(watchdog) = &mut self.watchdog {
+                        watchdog.on_message();
+                    }
+
+                    if awaiting_first_message && msg.is_ok() {
+                        self.endpoints.on_first_message(started.elapsed().as_millis() as u64);
+                        awaiting_first_message = false;
+                    }
+
+                    // The answer of a text ping isn't market data
+                    if let (Some(keepalive), Ok(message)) = (&mut self.keepalive, &msg) {
+                        if keepalive.on_message(message) {
+                            continue;
+                        }
+                    }
+
+                    match msg {
+                        Ok(Message::Text(text)) => { self.on_message(&text).await?; }
+                        #[cfg(feature = "sbe")]
+                        Ok(Message::Binary(data)) => { self.on_binary(&data).await?; }
+                        Ok(Message::Ping(payload)) => { self.on_ping(&mut ws_writer, &payload).await?; }
+                        Ok(Message::Close(frame)) => { self.on_close(frame).await?; }
+                        Err(e) => { return Err(e.into()); }
+                        _ => {}
+                    }
+                }
+                tick = evaluation.tick(), if self.health.is_some() => {
+                    // The stream has not paused for a whole window, the connection is cycled anyway
+                    if cycle_deadline.is_some_and(|deadline| tick >= deadline) {
+                        return Ok(SessionEnd::Cycled);
+                    }
+
+                    if self.evaluate_health() {
+                        cycle_deadline = cycle_deadline.or(Some(tick + window));
+                    } else {
+                        cycle_deadline = None;
+                    }
+                }
+                _ = sleep(Duration::from_millis(QUIET_PERIOD)), if cycle_deadline.is_some() => {
+                    return Ok(SessionEnd::Cycled);
+                }
+                _ = sleep_until(rotation_deadline.unwrap_or_else(Instant::now)), if rotation_deadline.is_some() => {
+                    // The rotation is postponed, unless another connection of the group covers the stream
+                    if self.rotation.as_mut().is_some_and(SessionRotation::try_rotate) {
+                        return Ok(SessionEnd::Rotated);
+                    }
+                }
+                _ = sleep_until(stall_deadline.unwrap_or_else(Instant::now)), if stall_deadline.is_some() => {
+                    if let Some(watchdog) = &self.watchdog {
+                        return Err(watchdog.on_stall(&self.url));
+                    }
+                }
+                _ = sleep_until(next_ping.unwrap_or_else(Instant::now)), if next_ping.is_some() => {
+                    if let Some(keepalive) = &mut self.keepalive {
+                        ws_writer.send(keepalive.ping()).await?;
+                    }
+                }
+                _ = sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                    if let Some(keepalive) = &self.keepalive {
+                        return Err(keepalive.on_timeout(&self.url));
+                    }
+                }
+                request = Self::next_snapshot_request(&mut self.snapshot_requests) => match request {
+                    Some(()) => return Ok(SessionEnd::SnapshotRequested),
+                    None => self.snapshot_requests = None,
+                }
             }
         }
-        Ok(())
+        Ok(SessionEnd::Closed)
+    }
+
+    /// Scores the connection over the last health window.
+    ///
+    /// # Returns
+    /// `true` if the connection is chronically unhealthy and should be cycled
+    fn evaluate_health(&mut self) -> bool {
+        let Some(health) = &mut self.health else {
+            return false;
+        };
+
+        let report = health.evaluate(Utc::now().timestamp_millis());
+        tracing::debug!("Health of session '{}': '{:?}'", self.url, report);
+
+        let is_chronically_unhealthy = health.is_chronically_unhealthy();
+        if is_chronically_unhealthy {
+            tracing::warn!("Session '{}' is chronically unhealthy: '{:?}'. Cycling it at the next quiet period", self.url, report);
+        }
+        is_chronically_unhealthy
     }
     
     /// Processes a text message received from the WebSocket.
     ///
-    /// This method parses the JSON message into a domain-specific event type using
-    /// the `MarketEventSource` implementation of type `T`, then forwards the event
-    /// to the processing queue.
+    /// This method decodes the message into market events using the `StreamMessage`
+    /// implementation of type `T`, then forwards the events to the processing queue.
     ///
     /// # Arguments
     /// * `message` - The text message received from the WebSocket
@@ -110,9 +438,57 @@ where T: MarketEventSource,
     /// * `Ok(())` if the message was processed successfully
     /// * `Err(...)` if an error occurred during processing
     async fn on_message(&mut self, message: &str) -> Result<()> {
-        let event = T::from_json(&message)?;
-        tracing::trace!("Received market event: '{:?}'", event);
-        self.event_queue.send(event.into_market_event()).await?;
+        let received = ReceiveTime::now();
+        if let Some(recorder) = &self.recorder {
+            recorder.record(message).await;
+        }
+
+        let events = PARSE_ALLOCATIONS.measure(|| T::decode(&mut self.state, message))?;
+        self.forward(events, received).await
+    }
+
+    /// Processes a binary message received from the WebSocket.
+    ///
+    /// This method decodes the message into market events using the `StreamMessage`
+    /// implementation of type `T`, records the events as frames of the JSON stream
+    /// and forwards them to the processing queue.
+    ///
+    /// # Arguments
+    /// * `message` - The binary message received from the WebSocket
+    ///
+    /// # Returns
+    /// * `Ok(())` if the message was processed successfully
+    /// * `Err(...)` if an error occurred during processing
+    #[cfg(feature = "sbe")]
+    async fn on_binary(&mut self, message: &[u8]) -> Result<()> {
+        let received = ReceiveTime::now();
+        let events = PARSE_ALLOCATIONS.measure(|| T::decode_binary(&mut self.state, message))?;
+
+        if let Some(recorder) = &self.recorder {
+            for frame in events.iter().filter_map(sbe::json_frame) {
+                recorder.record(&frame).await;
+            }
+        }
+
+        self.forward(events, received).await
+    }
+
+    /// Stamps the decoded events with the receive time of their frame and forwards them to the processing queue
+    async fn forward(&mut self, events: Vec<MarketEvent>, received: ReceiveTime) -> Result<()> {
+        for mut event in events {
+            tracing::trace!("Received market event: '{:?}'", event);
+            event.stamp(received);
+
+            if let Some(health) = &mut self.health {
+                health.observe(&event, received.wall_clock);
+            }
+
+            if let Some(status) = &self.status {
+                status.on_event(&event);
+            }
+
+            self.event_queue.send(event).await?;
+        }
         Ok(())
     }
 
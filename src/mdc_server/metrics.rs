@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use crate::mdc_core::level_pool::LEVEL_POOL;
+use crate::mdc_server::allocation_profiling;
+
+/// Upper bounds of the histogram buckets in milliseconds. Values above the last bound go to an overflow bucket
+const HISTOGRAM_BOUNDS: [i64; 14] = [-100, -10, 0, 1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 5000];
+
+/// A monotonically increasing counter, registered in Metrics
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    value: Arc<AtomicU64>,
+}
+
+impl Counter {
+    pub fn increment(&self, by: u64) {
+        self.value.fetch_add(by, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// The latest value of a quantity (e.g. the clock offset), registered in Metrics
+#[derive(Debug, Clone, Default)]
+pub struct Gauge {
+    value: Arc<AtomicI64>,
+}
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+struct HistogramState {
+    buckets: [AtomicU64; HISTOGRAM_BOUNDS.len() + 1],
+    count: AtomicU64,
+    sum: AtomicI64,
+    min: AtomicI64,
+    max: AtomicI64,
+}
+
+impl Default for HistogramState {
+    fn default() -> Self {
+        Self {
+            buckets: Default::default(),
+            count: AtomicU64::new(0),
+            sum: AtomicI64::new(0),
+            min: AtomicI64::new(i64::MAX),
+            max: AtomicI64::new(i64::MIN),
+        }
+    }
+}
+
+/// Distribution of millisecond values (e.g. latencies), registered in Metrics
+///
+/// Values may be negative, e.g. a latency measured against a clock, which is behind the exchange clock
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    state: Arc<HistogramState>,
+}
+
+/// Summary of a Histogram. Percentiles are estimated by the upper bounds of the buckets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub mean: Option<f64>,
+    pub p50: Option<i64>,
+    pub p99: Option<i64>,
+}
+
+impl Histogram {
+    pub fn record(&self, value: i64) {
+        let bucket = HISTOGRAM_BOUNDS.partition_point(|bound| *bound < value);
+        self.state.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.state.count.fetch_add(1, Ordering::Relaxed);
+        self.state.sum.fetch_add(value, Ordering::Relaxed);
+        self.state.min.fetch_min(value, Ordering::Relaxed);
+        self.state.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let count = self.state.count.load(Ordering::Relaxed);
+        if count == 0 {
+            return HistogramSnapshot::default();
+        }
+
+        let (min, max) = (self.state.min.load(Ordering::Relaxed), self.state.max.load(Ordering::Relaxed));
+        let percentile = |fraction: f64| {
+            let rank = ((count as f64 * fraction).ceil() as u64).max(1);
+            let mut seen = 0;
+            for (bucket, value) in self.state.buckets.iter().enumerate() {
+                seen += value.load(Ordering::Relaxed);
+                if seen >= rank {
+                    return HISTOGRAM_BOUNDS.get(bucket).map_or(max, |bound| (*bound).clamp(min, max));
+                }
+            }
+            max
+        };
+
+        HistogramSnapshot {
+            count,
+            min: Some(min),
+            max: Some(max),
+            mean: Some(self.state.sum.load(Ordering::Relaxed) as f64 / count as f64),
+            p50: Some(percentile(0.5)),
+            p99: Some(percentile(0.99)),
+        }
+    }
+}
+
+/// Registry of named counters, gauges and histograms, shared between pipeline components
+///
+/// Metrics are cheap to update from hot paths. The registry is only locked when a metric is
+/// registered or when all values are read
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    counters: Arc<Mutex<BTreeMap<String, Counter>>>,
+    gauges: Arc<Mutex<BTreeMap<String, Gauge>>>,
+    histograms: Arc<Mutex<BTreeMap<String, Histogram>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the counter with the given name, registering it if needed
+    pub fn counter(&self, name: &str) -> Counter {
+        self.counters
+            .lock()
+            .expect("Metrics lock is poisoned")
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Read the current values of all counters, including the process-wide counters of the level pool
+    /// and the allocation counters if allocation profiling is enabled
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        let mut snapshot: BTreeMap<String, u64> = self.counters
+            .lock()
+            .expect("Metrics lock is poisoned")
+            .iter()
+            .map(|(name, counter)| (name.clone(), counter.get()))
+            .collect();
+
+        snapshot.extend(LEVEL_POOL.counters());
+        if cfg!(feature = "alloc-profiling") {
+            snapshot.extend(allocation_profiling::snapshot());
+        }
+        snapshot
+    }
+
+    /// Get the gauge with the given name, registering it if needed
+    pub fn gauge(&self, name: &str) -> Gauge {
+        self.gauges
+            .lock()
+            .expect("Metrics lock is poisoned")
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Read the current values of all gauges
+    pub fn gauge_snapshot(&self) -> BTreeMap<String, i64> {
+        self.gauges
+            .lock()
+            .expect("Metrics lock is poisoned")
+            .iter()
+            .map(|(name, gauge)| (name.clone(), gauge.get()))
+            .collect()
+    }
+
+    /// Get the histogram with the given name, registering it if needed
+    pub fn histogram(&self, name: &str) -> Histogram {
+        self.histograms
+            .lock()
+            .expect("Metrics lock is poisoned")
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Read the current summaries of all histograms
+    pub fn histogram_snapshot(&self) -> BTreeMap<String, HistogramSnapshot> {
+        self.histograms
+            .lock()
+            .expect("Metrics lock is poisoned")
+            .iter()
+            .map(|(name, histogram)| (name.clone(), histogram.snapshot()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_shared_by_name() {
+        let metrics = Metrics::new();
+        let first = metrics.counter("evictions");
+        let second = metrics.clone().counter("evictions");
+
+        first.increment(2);
+        second.increment(3);
+        metrics.counter("other");
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot["evictions"], 5);
+        assert_eq!(snapshot["other"], 0);
+    }
+
+    #[test]
+    fn test_histogram_summary() {
+        let metrics = Metrics::new();
+        let latency = metrics.histogram("latency");
+        assert_eq!(latency.snapshot(), HistogramSnapshot::default());
+
+        for value in [-3, 4, 4, 7, 15, 15, 15, 40, 90, 12_000] {
+            metrics.histogram("latency").record(value);
+        }
+
+        let summary = &metrics.histogram_snapshot()["latency"];
+        assert_eq!(summary.count, 10);
+        assert_eq!((summary.min, summary.max), (Some(-3), Some(12_000)));
+        assert_eq!(summary.mean, Some(1218.7));
+        assert_eq!(summary.p50, Some(20));
+        assert_eq!(summary.p99, Some(12_000));
+    }
+}
@@ -2,9 +2,78 @@ pub mod config;
 pub mod server;
 
 pub mod market_event_stream;
-pub(crate) mod models;
-pub mod order_book;
 pub mod book_processor;
+pub mod book_validator;
 pub mod depth_event_dispatcher;
+pub mod trade_event_dispatcher;
+pub mod price_event_dispatcher;
 pub mod market_event_logger;
 pub mod depth_snapshot_stream;
+pub mod ws_api;
+pub mod proxy;
+pub mod tls;
+pub mod transport;
+pub mod ws_compression;
+pub mod partial_depth;
+pub mod all_market_prices;
+pub mod symbol_metadata;
+pub mod symbol_mapping;
+pub mod symbol_discovery;
+pub mod capture_manifest;
+pub mod tape;
+pub mod tape_replayer;
+pub mod exchange_connector;
+pub mod binance_connector;
+pub mod okx_connector;
+pub mod bitfinex_connector;
+pub mod deribit_connector;
+pub mod exchange_simulator;
+pub mod instance_lock;
+pub mod fanout;
+pub mod rollup_engine;
+pub mod book_metrics_engine;
+pub mod trade_flow_engine;
+pub mod volatility_engine;
+pub mod anomaly_engine;
+pub mod book_checkpoint;
+pub mod connection_health;
+pub mod live_status;
+pub mod session_report;
+pub mod admin_socket;
+pub mod disk_space_guard;
+pub mod metrics;
+pub mod allocation_profiling;
+pub mod output_tiers;
+pub mod trade_sampler;
+pub mod level_changes;
+pub mod book_deltas;
+pub mod grpc_service;
+pub mod tape_compactor;
+pub mod book_reconstruction;
+pub mod tape_inspector;
+pub mod capture_uploader;
+pub mod retention;
+pub mod s3_client;
+pub mod rest_api;
+pub mod dashboard;
+pub mod postgres_sink;
+pub mod wire_format;
+pub mod event_feed;
+pub mod combined_stream;
+pub mod supervisor;
+pub mod stall_watchdog;
+pub mod keepalive;
+pub mod session_rotation;
+pub mod endpoint_selector;
+pub mod request_weight;
+pub mod task_supervisor;
+pub mod log_context;
+pub mod pipeline_sink;
+pub mod pipeline_builder;
+pub mod clock_skew_monitor;
+pub mod arbitrage_monitor;
+#[cfg(unix)]
+pub mod systemd_notifier;
+pub mod alerting;
+pub mod event_scripts;
+pub mod book_tui;
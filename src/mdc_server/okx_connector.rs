@@ -0,0 +1,639 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use anyhow::{anyhow, Context, Result};
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use crate::mdc_core::checksum::{okx_text_checksum, BookChecksum, OKX_CHECKSUM_DEPTH};
+use crate::mdc_core::fixed_point::FixedPoint;
+use crate::mdc_core::models::{de_float_from_str, DepthEntry, DepthSnapshot, DepthUpdate, MarketEvent, StreamMessage, SymbolMetadata, TradeEvent};
+use crate::mdc_core::sequencing::SequencingRules;
+use crate::mdc_server::exchange_connector::{ExchangeConnector, StreamKind};
+
+/// OKX order book channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OkxBookChannel {
+    /// 400 levels, updates every 100 ms
+    #[default]
+    #[serde(rename = "books")]
+    Books,
+    /// 50 levels, tick-by-tick updates. OKX only serves it to higher fee tiers
+    #[serde(rename = "books50-l2-tbt")]
+    Books50L2Tbt,
+}
+
+impl fmt::Display for OkxBookChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OkxBookChannel::Books => write!(f, "books"),
+            OkxBookChannel::Books50L2Tbt => write!(f, "books50-l2-tbt"),
+        }
+    }
+}
+
+/// Instrument type of an OKX instrument id: `BTC-USDT` (spot), `BTC-USDT-SWAP`, `BTC-USD-250328` (futures)
+/// or `BTC-USD-250328-100000-C` (option)
+fn instrument_type(instrument: &str) -> &'static str {
+    match instrument.split('-').count() {
+        5 => "OPTION",
+        3 if instrument.ends_with("-SWAP") => "SWAP",
+        3 => "FUTURES",
+        _ => "SPOT",
+    }
+}
+
+/// Connector for OKX (v5 API) market data
+///
+/// All channels are served by the public WebSocket endpoint (e.g. "wss://ws.okx.com:8443/ws/v5/public")
+/// and subscribed after connecting. The book channels start with a snapshot, which carries the `seqId`
+/// the following updates continue, so REST snapshots are not used
+pub struct OkxConnector {
+    rest_endpoint: String,
+    wss_endpoint: String,
+    book_channel: OkxBookChannel,
+}
+
+impl OkxConnector {
+    /// Create a new OkxConnector
+    ///
+    /// # Arguments
+    /// * `rest_endpoint` - The OKX REST API endpoint (e.g. "https://www.okx.com/api/v5/")
+    /// * `wss_endpoint` - The OKX public WebSocket endpoint (e.g. "wss://ws.okx.com:8443/ws/v5/public")
+    /// * `book_channel` - The order book channel of the depth stream
+    pub fn new(rest_endpoint: String, wss_endpoint: String, book_channel: OkxBookChannel) -> Self {
+        Self {
+            rest_endpoint,
+            wss_endpoint,
+            book_channel,
+        }
+    }
+}
+
+impl ExchangeConnector for OkxConnector {
+    fn name(&self) -> &str {
+        "okx"
+    }
+
+    fn stream_name(&self, kind: StreamKind, _instrument: &str) -> Option<String> {
+        match kind {
+            StreamKind::Depth => Some(self.book_channel.to_string()),
+            StreamKind::Trade => Some("trades".to_string()),
+            _ => None,
+        }
+    }
+
+    fn stream_url(&self, kind: StreamKind, instrument: &str) -> Option<String> {
+        self.stream_name(kind, instrument).map(|_| self.wss_endpoint.clone())
+    }
+
+    fn subscription(&self, kind: StreamKind, instrument: &str) -> Vec<String> {
+        self.stream_name(kind, instrument)
+            .map(|channel| json!({"op": "subscribe", "args": [{"channel": channel, "instId": instrument}]}).to_string())
+            .into_iter()
+            .collect()
+    }
+
+    fn combined_stream_url(&self, _names: &[String]) -> Option<String> {
+        None
+    }
+
+    fn snapshot_url(&self, _instrument: &str, _limit: u64) -> Option<String> {
+        // REST snapshots don't carry a seqId, so they can't be sequenced with the updates
+        None
+    }
+
+    fn snapshot_weight(&self, _limit: u64) -> u64 {
+        0
+    }
+
+    fn exchange_info_url(&self, instrument: &str) -> String {
+        format!("{}public/instruments?instType={}&instId={}", self.rest_endpoint, instrument_type(instrument), instrument)
+    }
+
+    fn server_time_url(&self) -> Option<String> {
+        Some(format!("{}public/time", self.rest_endpoint))
+    }
+
+    fn parse_server_time(&self, response: &str) -> Result<i64> {
+        let [server_time] = parse_response::<OkxServerTime>(response).context("Failed to parse server time")?
+            .try_into()
+            .map_err(|data: Vec<_>| anyhow!("Expected a single server time, received '{}'", data.len()))?;
+
+        Ok(server_time.ts as i64)
+    }
+
+    fn parse_symbol_metadata(&self, symbol: &str, response: &str) -> Result<SymbolMetadata> {
+        parse_response::<OkxInstrument>(response)
+            .context("Failed to parse instruments")?
+            .into_iter()
+            .find(|instrument| instrument.inst_id == symbol)
+            .map(SymbolMetadata::from)
+            .ok_or_else(|| anyhow!("Symbol '{}' is not listed in instruments", symbol))
+    }
+
+    fn is_trading(&self, metadata: &SymbolMetadata) -> bool {
+        metadata.status == "live"
+    }
+
+    fn sequencing_rules(&self) -> SequencingRules {
+        SequencingRules::Okx
+    }
+}
+
+/// Envelope of the OKX REST responses
+#[derive(Debug, Deserialize)]
+struct OkxResponse<T> {
+    code: String,
+    #[serde(default)]
+    msg: String,
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+}
+
+/// Parse an OKX REST response. A non-zero code is an error
+fn parse_response<T: DeserializeOwned>(response: &str) -> Result<Vec<T>> {
+    let response: OkxResponse<T> = serde_json::from_str(response)?;
+    if response.code != "0" {
+        return Err(anyhow!("OKX error '{}': '{}'", response.code, response.msg));
+    }
+
+    Ok(response.data)
+}
+
+fn de_u64_from_str<'a, D>(deserializer: D) -> Result<u64, D::Error>
+where D: Deserializer<'a>,
+{
+    let str_val = String::deserialize(deserializer)?;
+    str_val.parse::<u64>().map_err(de::Error::custom)
+}
+
+/// Deserialize an optional decimal sent as a string, which is empty if the value is not set
+fn de_optional_fixed_point<'a, D>(deserializer: D) -> Result<Option<FixedPoint>, D::Error>
+where D: Deserializer<'a>,
+{
+    let str_val = String::deserialize(deserializer)?;
+    if str_val.is_empty() {
+        return Ok(None);
+    }
+
+    str_val.parse::<FixedPoint>().map(Some).map_err(de::Error::custom)
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxServerTime {
+    #[serde(deserialize_with = "de_u64_from_str")]
+    ts: u64,
+}
+
+/// Instrument description, as reported by the OKX instruments endpoint
+#[derive(Debug, Deserialize)]
+struct OkxInstrument {
+    #[serde(rename = "instId")]
+    inst_id: String,
+    state: String,
+    /// Empty for derivatives
+    #[serde(rename = "baseCcy", default)]
+    base_ccy: String,
+    #[serde(rename = "quoteCcy", default)]
+    quote_ccy: String,
+    #[serde(rename = "tickSz", deserialize_with = "de_optional_fixed_point", default)]
+    tick_size: Option<FixedPoint>,
+    #[serde(rename = "lotSz", deserialize_with = "de_optional_fixed_point", default)]
+    lot_size: Option<FixedPoint>,
+    #[serde(rename = "minSz", deserialize_with = "de_optional_fixed_point", default)]
+    min_size: Option<FixedPoint>,
+    #[serde(rename = "maxLmtSz", deserialize_with = "de_optional_fixed_point", default)]
+    max_limit_size: Option<FixedPoint>,
+}
+
+impl From<OkxInstrument> for SymbolMetadata {
+    fn from(instrument: OkxInstrument) -> Self {
+        SymbolMetadata {
+            symbol: instrument.inst_id,
+            status: instrument.state,
+            base_asset: instrument.base_ccy,
+            quote_asset: instrument.quote_ccy,
+            tick_size: instrument.tick_size,
+            step_size: instrument.lot_size,
+            min_qty: instrument.min_size,
+            max_qty: instrument.max_limit_size,
+        }
+    }
+}
+
+/// A text frame of an OKX WebSocket connection: either an event (subscription acknowledgement, error, notice)
+/// or data pushed by a channel
+#[derive(Debug, Deserialize)]
+struct OkxFrame<T> {
+    event: Option<String>,
+    code: Option<String>,
+    msg: Option<String>,
+    arg: Option<OkxArg>,
+    /// `snapshot` or `update` for the book channels
+    action: Option<String>,
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxArg {
+    #[serde(rename = "instId")]
+    inst_id: String,
+}
+
+impl<T: DeserializeOwned> OkxFrame<T> {
+    /// Parse a frame. Event frames are logged and carry no data, an error event fails the parsing
+    fn parse(message: &str) -> Result<Self, serde_json::Error> {
+        let frame: Self = serde_json::from_str(message)?;
+
+        match frame.event.as_deref() {
+            Some("error") => Err(de::Error::custom(format!(
+                "OKX error '{}': '{}'",
+                frame.code.as_deref().unwrap_or_default(),
+                frame.msg.as_deref().unwrap_or_default()
+            ))),
+            Some("notice") => {
+                tracing::warn!("OKX notice: '{}'", frame.msg.as_deref().unwrap_or_default());
+                Ok(frame)
+            }
+            Some(event) => {
+                tracing::debug!("OKX event '{}': '{}'", event, message);
+                Ok(frame)
+            }
+            None => Ok(frame),
+        }
+    }
+
+    fn instrument(&self) -> String {
+        self.arg.as_ref().map(|arg| arg.inst_id.clone()).unwrap_or_default()
+    }
+}
+
+/// A book level, sent as `[price, size, deprecated, number of orders]`. The strings are kept for the checksum
+#[derive(Clone)]
+struct OkxLevel {
+    entry: DepthEntry,
+    price: String,
+    size: String,
+}
+
+impl<'de> Deserialize<'de> for OkxLevel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let arr: Vec<String> = Vec::deserialize(deserializer)?;
+        if arr.len() < 2 {
+            return Err(de::Error::invalid_length(arr.len(), &"at least 2"));
+        }
+
+        let price = arr[0].parse::<FixedPoint>().map_err(de::Error::custom)?;
+        let quantity = arr[1].parse::<FixedPoint>().map_err(de::Error::custom)?;
+        let mut arr = arr.into_iter();
+
+        Ok(OkxLevel { entry: DepthEntry { price, quantity }, price: arr.next().unwrap_or_default(), size: arr.next().unwrap_or_default() })
+    }
+}
+
+#[derive(Deserialize)]
+struct OkxBookData {
+    asks: Vec<OkxLevel>,
+    bids: Vec<OkxLevel>,
+    #[serde(deserialize_with = "de_u64_from_str")]
+    ts: u64,
+    checksum: Option<i32>,
+    /// -1 for a snapshot
+    #[serde(rename = "prevSeqId")]
+    prev_seq_id: i64,
+    #[serde(rename = "seqId")]
+    seq_id: u64,
+}
+
+/// Decoding state of a book connection
+///
+/// OKX takes the checksum over the price and size strings it sent, which may carry trailing zeros (e.g. `0.10`),
+/// while the book formats its levels without them. The state keeps the levels of the connection as sent, so the
+/// checksum of an update can be translated into the formatting of the book. Deeper levels are kept as well, since
+/// they move into the best 25 once better ones are removed, and OKX doesn't send them again
+///
+/// OKX may reset the `seqId` to a lower value during maintenance. The state shifts the sequence ids from then on,
+/// so they keep growing
+#[derive(Default)]
+pub struct OkxBookState {
+    bids: BTreeMap<FixedPoint, OkxLevel>,
+    asks: BTreeMap<FixedPoint, OkxLevel>,
+    seq_offset: u64,
+}
+
+impl OkxBookState {
+    fn apply(&mut self, bids: &[OkxLevel], asks: &[OkxLevel]) {
+        for (side, levels) in [(&mut self.bids, bids), (&mut self.asks, asks)] {
+            for level in levels {
+                match level.entry.quantity.is_zero() {
+                    true => side.remove(&level.entry.price),
+                    false => side.insert(level.entry.price, level.clone()),
+                };
+            }
+        }
+    }
+
+    /// The checksum of the best levels formatted like `okx_checksum`, if the levels as sent match the OKX checksum.
+    /// Otherwise the OKX checksum is passed on as is, so it fails the check of the book
+    fn translate(&self, checksum: i32) -> i32 {
+        let bids: Vec<&OkxLevel> = self.bids.values().rev().take(OKX_CHECKSUM_DEPTH).collect();
+        let asks: Vec<&OkxLevel> = self.asks.values().take(OKX_CHECKSUM_DEPTH).collect();
+
+        let [sent_bids, sent_asks] = [&bids, &asks].map(|levels| levels
+            .iter()
+            .map(|level| (level.price.as_str(), level.size.as_str()))
+            .collect::<Vec<_>>());
+        if okx_text_checksum(&sent_bids, &sent_asks) != checksum {
+            return checksum;
+        }
+
+        let formatted = |levels: &[&OkxLevel]| levels
+            .iter()
+            .map(|level| (level.entry.price.to_string(), level.entry.quantity.to_string()))
+            .collect::<Vec<_>>();
+        okx_text_checksum(&formatted(&bids), &formatted(&asks))
+    }
+}
+
+/// Text frames of the OKX order book channels
+///
+/// A snapshot is decoded into a DepthSnapshot, an update into a DepthUpdate: `u` is the `seqId`, `pu` the `prevSeqId`
+/// and `U` follows `pu`. An update without changes keeps the `seqId`, an update after a reset of the `seqId` has a lower
+/// `seqId` than its `prevSeqId`. The checksum is translated by the `OkxBookState`
+pub struct OkxBookMessage;
+
+impl StreamMessage for OkxBookMessage {
+    type State = OkxBookState;
+
+    fn decode(state: &mut OkxBookState, message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let frame = OkxFrame::<OkxBookData>::parse(message)?;
+        let symbol = frame.instrument();
+        let is_snapshot = frame.action.as_deref() == Some("snapshot");
+        let levels = |levels: Vec<OkxLevel>| levels.into_iter().map(|level| level.entry).collect::<Vec<_>>();
+
+        Ok(frame.data.into_iter().map(|data| {
+            if is_snapshot {
+                state.bids.clear();
+                state.asks.clear();
+            }
+            state.apply(&data.bids, &data.asks);
+
+            if is_snapshot {
+                return MarketEvent::DepthSnapshot(DepthSnapshot {
+                    last_update_id: data.seq_id + state.seq_offset,
+                    bids: levels(data.bids),
+                    asks: levels(data.asks),
+                });
+            }
+
+            let previous = u64::try_from(data.prev_seq_id).ok().map(|previous| previous + state.seq_offset);
+            // After a reset the update continues the previous seqId with a lower one
+            if let Some(previous) = previous.filter(|previous| data.seq_id + state.seq_offset < *previous) {
+                tracing::info!("OKX reset the seqId of '{}' from '{}' to '{}'", symbol, data.prev_seq_id, data.seq_id);
+                state.seq_offset = previous + 1 - data.seq_id;
+            }
+            let seq_id = data.seq_id + state.seq_offset;
+            MarketEvent::DepthUpdate(DepthUpdate {
+                event_type: "update".to_string(),
+                event_time: data.ts,
+                symbol: symbol.clone(),
+                first_update_id: previous.map_or(seq_id, |previous| previous + 1),
+                last_update_id: seq_id,
+                previous_last_update_id: previous,
+                bids: levels(data.bids),
+                asks: levels(data.asks),
+                checksum: data.checksum.map(|checksum| BookChecksum::Okx(state.translate(checksum))),
+                received: None,
+            })
+        }).collect())
+    }
+}
+
+#[derive(Deserialize)]
+struct OkxTrade {
+    #[serde(rename = "instId")]
+    inst_id: String,
+    #[serde(rename = "tradeId", deserialize_with = "de_u64_from_str")]
+    trade_id: u64,
+    #[serde(rename = "px", deserialize_with = "de_float_from_str")]
+    price: f64,
+    #[serde(rename = "sz", deserialize_with = "de_float_from_str")]
+    size: f64,
+    /// Side of the taker
+    side: String,
+    #[serde(deserialize_with = "de_u64_from_str")]
+    ts: u64,
+}
+
+/// Text frames of the OKX trades channel. A frame may carry several trades
+pub struct OkxTradeMessage;
+
+impl StreamMessage for OkxTradeMessage {
+    type State = ();
+
+    fn decode(_state: &mut (), message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let frame = OkxFrame::<OkxTrade>::parse(message)?;
+
+        Ok(frame.data.into_iter().map(|trade| {
+            MarketEvent::TradeEvent(TradeEvent {
+                event_type: "trade".to_string(),
+                event_time: trade.ts,
+                symbol: trade.inst_id,
+                trade_id: trade.trade_id,
+                price: trade.price,
+                quantity: trade.size,
+                trade_time: trade.ts,
+                // The buyer is the maker if the taker sells
+                is_market_maker: trade.side == "sell",
+                ignore: false,
+                received: None,
+            })
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::checksum::crc32;
+    use crate::mdc_core::order_book::OrderBook;
+
+    fn make_connector() -> OkxConnector {
+        OkxConnector::new(
+            "https://www.okx.com/api/v5/".to_string(),
+            "wss://ws.okx.com:8443/ws/v5/public".to_string(),
+            OkxBookChannel::Books,
+        )
+    }
+
+    #[test]
+    fn test_streams_and_subscriptions() {
+        let connector = make_connector();
+
+        assert_eq!(connector.stream_url(StreamKind::Depth, "BTC-USDT").unwrap(), "wss://ws.okx.com:8443/ws/v5/public");
+        assert_eq!(connector.stream_url(StreamKind::Trade, "BTC-USDT").unwrap(), "wss://ws.okx.com:8443/ws/v5/public");
+        assert_eq!(connector.stream_url(StreamKind::Price, "BTC-USDT"), None);
+        assert_eq!(
+            connector.subscription(StreamKind::Depth, "BTC-USDT"),
+            [r#"{"args":[{"channel":"books","instId":"BTC-USDT"}],"op":"subscribe"}"#]
+        );
+        assert_eq!(
+            connector.subscription(StreamKind::Trade, "BTC-USDT"),
+            [r#"{"args":[{"channel":"trades","instId":"BTC-USDT"}],"op":"subscribe"}"#]
+        );
+        assert!(connector.subscription(StreamKind::Price, "BTC-USDT").is_empty());
+        assert_eq!(connector.combined_stream_url(&["trades".to_string()]), None);
+        assert_eq!(connector.snapshot_url("BTC-USDT", 400), None);
+        assert_eq!(connector.sequencing_rules(), SequencingRules::Okx);
+
+        let tbt = OkxConnector::new(String::new(), String::new(), OkxBookChannel::Books50L2Tbt);
+        assert_eq!(tbt.stream_name(StreamKind::Depth, "BTC-USDT").unwrap(), "books50-l2-tbt");
+    }
+
+    #[test]
+    fn test_rest_urls_and_responses() {
+        let connector = make_connector();
+
+        assert_eq!(connector.exchange_info_url("BTC-USDT"), "https://www.okx.com/api/v5/public/instruments?instType=SPOT&instId=BTC-USDT");
+        assert_eq!(connector.exchange_info_url("BTC-USDT-SWAP"), "https://www.okx.com/api/v5/public/instruments?instType=SWAP&instId=BTC-USDT-SWAP");
+        assert_eq!(connector.exchange_info_url("BTC-USD-250328"), "https://www.okx.com/api/v5/public/instruments?instType=FUTURES&instId=BTC-USD-250328");
+        assert_eq!(connector.server_time_url().unwrap(), "https://www.okx.com/api/v5/public/time");
+
+        assert_eq!(connector.parse_server_time(r#"{"code":"0","msg":"","data":[{"ts":"1597026383085"}]}"#).unwrap(), 1597026383085);
+        assert!(connector.parse_server_time(r#"{"code":"50011","msg":"Too Many Requests","data":[]}"#).is_err());
+
+        let instruments = r#"{"code":"0","msg":"","data":[{"instId":"BTC-USDT","instType":"SPOT","baseCcy":"BTC","quoteCcy":"USDT","tickSz":"0.1","lotSz":"0.00000001","minSz":"0.00001","maxLmtSz":"9999999999","state":"live"}]}"#;
+        let metadata = connector.parse_symbol_metadata("BTC-USDT", instruments).unwrap();
+        assert_eq!(metadata.status, "live");
+        assert!(connector.is_trading(&metadata));
+        assert_eq!(metadata.base_asset, "BTC");
+        assert_eq!(metadata.tick_size, Some("0.1".parse().unwrap()));
+        assert_eq!(metadata.min_qty, Some("0.00001".parse().unwrap()));
+        assert!(connector.parse_symbol_metadata("ETH-USDT", instruments).is_err());
+    }
+
+    #[test]
+    fn test_decode_book_frames() {
+        let snapshot = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["8476.98","415","0","13"]],"bids":[["8476.97","256","0","12"],["8475.55","101","0","1"]],"ts":"1597026383085","checksum":-855196043,"prevSeqId":-1,"seqId":123456}]}"#;
+        let mut state = OkxBookState::default();
+        let events = OkxBookMessage::decode(&mut state, snapshot).unwrap();
+        let [MarketEvent::DepthSnapshot(snapshot)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(snapshot.last_update_id, 123456);
+        assert_eq!(snapshot.bids.len(), 2);
+        assert_eq!(snapshot.asks[0], DepthEntry { price: "8476.98".parse().unwrap(), quantity: "415".parse().unwrap() });
+
+        let update = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[["8476.98","0","0","0"]],"bids":[],"ts":"1597026383185","checksum":1234,"prevSeqId":123456,"seqId":123470}]}"#;
+        let events = OkxBookMessage::decode(&mut state, update).unwrap();
+        let [MarketEvent::DepthUpdate(update)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(update.symbol, "BTC-USDT");
+        assert_eq!(update.event_time, 1597026383185);
+        assert_eq!((update.first_update_id, update.last_update_id, update.previous_last_update_id), (123457, 123470, Some(123456)));
+        assert!(update.asks[0].quantity.is_zero());
+        assert_eq!(update.checksum, Some(BookChecksum::Okx(1234)));
+
+        let ack = r#"{"event":"subscribe","arg":{"channel":"books","instId":"BTC-USDT"},"connId":"a4d3ae55"}"#;
+        assert!(OkxBookMessage::decode(&mut state, ack).unwrap().is_empty());
+
+        let error = r#"{"event":"error","code":"60012","msg":"Invalid request","connId":"a4d3ae55"}"#;
+        assert!(OkxBookMessage::decode(&mut state, error).unwrap_err().to_string().contains("60012"));
+    }
+
+    #[test]
+    fn test_decode_book_checksum_of_sent_strings() {
+        let mut state = OkxBookState::default();
+        let snapshot = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["8476.98","415","0","13"],["8477.00","0.10","0","1"]],"bids":[["8476.90","2.50","0","12"]],"ts":"1597026383085","checksum":0,"prevSeqId":-1,"seqId":1}]}"#;
+        let events = OkxBookMessage::decode(&mut state, snapshot).unwrap();
+        let [MarketEvent::DepthSnapshot(snapshot)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        let mut book = OrderBook::new(snapshot);
+
+        // OKX takes the checksum over its strings, trailing zeros included
+        let sent = crc32(b"8476.90:2.50:8476.98:415:8476.80:3.000:8477.00:0.10") as i32;
+        let update = format!(r#"{{"arg":{{"channel":"books","instId":"BTC-USDT"}},"action":"update","data":[{{"asks":[],"bids":[["8476.80","3.000","0","2"]],"ts":"1597026383185","checksum":{},"prevSeqId":1,"seqId":2}}]}}"#, sent);
+        let events = OkxBookMessage::decode(&mut state, &update).unwrap();
+        let [MarketEvent::DepthUpdate(update)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        book.apply_update(OrderBook::bid(update.bids[0].price), update.bids[0].quantity);
+
+        let checksum = update.checksum.unwrap();
+        assert_eq!(checksum, BookChecksum::Okx(crc32(b"8476.9:2.5:8476.98:415:8476.8:3:8477:0.1") as i32));
+        assert!(checksum.verify(&book));
+
+        // A checksum, which doesn't match the strings of the connection, fails the check of the book
+        let update = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"update","data":[{"asks":[],"bids":[],"ts":"1597026383285","checksum":1234,"prevSeqId":2,"seqId":2}]}"#;
+        let events = OkxBookMessage::decode(&mut state, update).unwrap();
+        let [MarketEvent::DepthUpdate(update)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert!(!update.checksum.unwrap().verify(&book));
+    }
+
+    #[test]
+    fn test_decode_book_checksum_of_mixed_precision() {
+        let mut state = OkxBookState::default();
+        let snapshot = r#"{"arg":{"channel":"books","instId":"BTC-USDT"},"action":"snapshot","data":[{"asks":[["0.12","0.1","0","1"],["0.13","0.10","0","1"]],"bids":[["0.11","5","0","2"],["0.1","0.100","0","1"]],"ts":"1597026383085","checksum":0,"prevSeqId":-1,"seqId":1}]}"#;
+        let events = OkxBookMessage::decode(&mut state, snapshot).unwrap();
+        let [MarketEvent::DepthSnapshot(snapshot)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        let mut book = OrderBook::new(snapshot);
+
+        // Each string counts as sent, however many decimals the others of the book have
+        let sent = crc32(b"0.11:5:0.12:0.1:0.105:2:0.13:0.10:0.1:0.100") as i32;
+        let update = format!(r#"{{"arg":{{"channel":"books","instId":"BTC-USDT"}},"action":"update","data":[{{"asks":[],"bids":[["0.105","2","0","1"]],"ts":"1597026383185","checksum":{},"prevSeqId":1,"seqId":2}}]}}"#, sent);
+        let events = OkxBookMessage::decode(&mut state, &update).unwrap();
+        let [MarketEvent::DepthUpdate(update)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        book.apply_update(OrderBook::bid(update.bids[0].price), update.bids[0].quantity);
+        assert!(update.checksum.unwrap().verify(&book));
+    }
+
+    #[test]
+    fn test_decode_book_after_seq_id_reset() {
+        let mut state = OkxBookState::default();
+        let frame = |action: &str, previous: i64, seq_id: u64| format!(
+            r#"{{"arg":{{"channel":"books","instId":"BTC-USDT"}},"action":"{}","data":[{{"asks":[],"bids":[],"ts":"1597026383085","prevSeqId":{},"seqId":{}}}]}}"#,
+            action, previous, seq_id
+        );
+        let decode = |state: &mut OkxBookState, frame: String| match OkxBookMessage::decode(state, &frame).unwrap().as_slice() {
+            [MarketEvent::DepthSnapshot(snapshot)] => (snapshot.last_update_id, None),
+            [MarketEvent::DepthUpdate(update)] => (update.last_update_id, update.previous_last_update_id),
+            events => panic!("Unexpected events: '{:?}'", events),
+        };
+
+        assert_eq!(decode(&mut state, frame("snapshot", -1, 100)), (100, None));
+        assert_eq!(decode(&mut state, frame("update", 100, 101)), (101, Some(100)));
+
+        // During maintenance OKX resets the seqId, and the update carries the last seqId as prevSeqId
+        assert_eq!(decode(&mut state, frame("update", 101, 5)), (102, Some(101)));
+        assert_eq!(decode(&mut state, frame("update", 5, 6)), (103, Some(102)));
+        assert_eq!(decode(&mut state, frame("update", 6, 6)), (103, Some(103)));
+        assert_eq!(decode(&mut state, frame("snapshot", -1, 8)), (105, None));
+    }
+
+    #[test]
+    fn test_decode_trade_frames() {
+        let trades = r#"{"arg":{"channel":"trades","instId":"BTC-USDT"},"data":[{"instId":"BTC-USDT","tradeId":"130639474","px":"42219.9","sz":"0.12060306","side":"buy","ts":"1630048897897","count":"3"},{"instId":"BTC-USDT","tradeId":"130639475","px":"42219.8","sz":"0.1","side":"sell","ts":"1630048897898","count":"1"}]}"#;
+        let events = OkxTradeMessage::decode(&mut (), trades).unwrap();
+
+        let [MarketEvent::TradeEvent(buy), MarketEvent::TradeEvent(sell)] = events.as_slice() else {
+            panic!("Unexpected events: '{:?}'", events);
+        };
+        assert_eq!(buy.trade_id, 130639474);
+        assert_eq!(buy.price, 42219.9);
+        assert_eq!(buy.trade_time, 1630048897897);
+        assert!(!buy.is_market_maker);
+        assert!(sell.is_market_maker);
+    }
+}
@@ -0,0 +1,501 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::Arc;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use memmap2::MmapMut;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
+use tokio::sync::mpsc::error::TrySendError;
+use crate::mdc_server::disk_space_guard::CaptureGate;
+use crate::mdc_server::metrics::{Counter, Metrics};
+use crate::mdc_core::depth_buckets::PriceBuckets;
+use crate::mdc_core::models::DepthEntry;
+use crate::mdc_core::order_book::{OrderBook, Side};
+
+/// Size of the shared memory header: version (u64) and payload length (u64)
+const SHARED_MEMORY_HEADER_SIZE: usize = 16;
+
+/// Frames, which the durable path may fall behind, before new frames are dropped. About 100 MB at 20 levels per side
+const DURABLE_OUTPUT_CAPACITY: usize = 100_000;
+
+/// Top levels of the book at a point in time, as published by the outputs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookFrame {
+    /// Number of the frame, increasing by one with every book update
+    pub sequence: u64,
+    /// Local time in milliseconds since epoch
+    pub time: i64,
+    /// `[price, quantity]` pairs, best first
+    pub bids: Vec<[f64; 2]>,
+    pub asks: Vec<[f64; 2]>,
+}
+
+impl BookFrame {
+    pub fn new(sequence: u64, time: i64, book: &OrderBook, depth: usize) -> Self {
+        let (bids, asks) = book.top_n(depth);
+        let levels = |side: Vec<DepthEntry>| side.into_iter().map(|entry| [entry.price.to_f64(), entry.quantity.to_f64()]).collect();
+
+        Self {
+            sequence,
+            time,
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    /// A frame of the book levels aggregated into price buckets, a fixed number per side
+    pub fn bucketed(sequence: u64, time: i64, book: &OrderBook, buckets: &PriceBuckets) -> Self {
+        Self {
+            sequence,
+            time,
+            bids: buckets.aggregate(book, Side::Bid),
+            asks: buckets.aggregate(book, Side::Ask),
+        }
+    }
+}
+
+/// Destination of book frames
+pub trait FrameSink: Send {
+    fn write(&mut self, frame: &BookFrame) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// Publishes the latest frame into a memory-mapped file, e.g. in `/dev/shm`
+///
+/// The file starts with a 16 byte header: a version (u64, little endian) and the payload length (u64),
+/// followed by the JSON encoded frame. The version is odd while the frame is being written, so readers
+/// retry if the version is odd or has changed while they were reading (seqlock)
+pub struct SharedMemoryFrameSink {
+    mmap: MmapMut,
+    version: u64,
+}
+
+impl SharedMemoryFrameSink {
+    /// Create the file (or truncate an existing one) and map it into memory
+    ///
+    /// # Arguments
+    /// * `path` - Path of the shared memory file
+    /// * `capacity` - Maximum size of an encoded frame in bytes
+    pub fn create(path: &Path, capacity: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_context(|| format!("Failed to open shared memory file: {:?}", path))?;
+        file.set_len((SHARED_MEMORY_HEADER_SIZE + capacity) as u64)?;
+
+        // SAFETY: the file is owned by this process for the lifetime of the session, concurrent readers
+        // only read it and synchronize through the version field
+        let mmap = unsafe { MmapMut::map_mut(&file) }
+            .with_context(|| format!("Failed to map shared memory file: {:?}", path))?;
+
+        Ok(Self { mmap, version: 0 })
+    }
+
+    fn version_field(&self) -> &AtomicU64 {
+        // SAFETY: the mapping is page aligned and at least SHARED_MEMORY_HEADER_SIZE bytes long
+        unsafe { &*(self.mmap.as_ptr() as *const AtomicU64) }
+    }
+}
+
+impl FrameSink for SharedMemoryFrameSink {
+    fn write(&mut self, frame: &BookFrame) -> Result<()> {
+        let payload = serde_json::to_vec(frame)?;
+        let capacity = self.mmap.len() - SHARED_MEMORY_HEADER_SIZE;
+        if payload.len() > capacity {
+            return Err(anyhow!("Frame of '{}' bytes exceeds shared memory capacity of '{}' bytes", payload.len(), capacity));
+        }
+
+        self.version += 1;
+        self.version_field().store(self.version, Ordering::Release);
+        fence(Ordering::Release);
+
+        self.mmap[8..SHARED_MEMORY_HEADER_SIZE].copy_from_slice(&(payload.len() as u64).to_le_bytes());
+        self.mmap[SHARED_MEMORY_HEADER_SIZE..SHARED_MEMORY_HEADER_SIZE + payload.len()].copy_from_slice(&payload);
+
+        self.version += 1;
+        fence(Ordering::Release);
+        self.version_field().store(self.version, Ordering::Release);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends every frame into a JSON lines file
+pub struct JsonLinesFrameSink {
+    writer: BufWriter<File>,
+}
+
+impl JsonLinesFrameSink {
+    /// Open the file for appending, creating it if needed
+    pub fn open(path: &PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open book output file: {:?}", path))?;
+
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+}
+
+impl FrameSink for JsonLinesFrameSink {
+    fn write(&mut self, frame: &BookFrame) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, frame)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// OutputTiers splits the book stream into two independent outputs:
+/// * the fast path, which always gets the latest frame and skips (conflates) frames it can't keep up with
+/// * the durable path, which gets every frame through a bounded queue, so it can lag behind without
+///   back-pressuring the pipeline or the fast path. Frames, which don't fit into the queue, are dropped and counted
+pub struct OutputTiers {
+    input: mpsc::Receiver<Arc<OrderBook>>,
+    depth: usize,
+    buckets: Option<PriceBuckets>,
+    fast_output: Option<watch::Sender<Option<BookFrame>>>,
+    durable_output: Option<mpsc::Sender<BookFrame>>,
+    durable_enqueued: Counter,
+    durable_dropped: Counter,
+}
+
+impl OutputTiers {
+    /// Create OutputTiers along with the writers of the configured outputs
+    ///
+    /// # Arguments
+    /// * `input` - Receiver for OrderBook updates
+    /// * `depth` - Number of top levels per side in each frame
+    /// * `fast_sink` - Optional sink of the fast path
+    /// * `durable_sink` - Optional sink of the durable path
+    /// * `gate` - Switch, which pauses the durable path. Frames are discarded while it is paused
+    /// * `metrics` - Registry of the output counters
+    ///
+    /// # Returns
+    /// OutputTiers and the writers, each of which has to be run as a separate task
+    pub fn new(
+        input: mpsc::Receiver<Arc<OrderBook>>,
+        depth: usize,
+        fast_sink: Option<Box<dyn FrameSink>>,
+        durable_sink: Option<Box<dyn FrameSink>>,
+        gate: CaptureGate,
+        metrics: &Metrics,
+    ) -> (Self, Option<FastWriter>, Option<DurableWriter>) {
+        let (fast_output, fast_writer) = match fast_sink {
+            Some(sink) => {
+                let (sender, receiver) = watch::channel(None);
+                let writer = FastWriter {
+                    input: receiver,
+                    sink,
+                    published: metrics.counter("fast_output_published"),
+                    conflated: metrics.counter("fast_output_conflated"),
+                };
+                (Some(sender), Some(writer))
+            }
+            None => (None, None),
+        };
+
+        let (durable_output, durable_writer) = match durable_sink {
+            Some(sink) => {
+                let (sender, receiver) = mpsc::channel(DURABLE_OUTPUT_CAPACITY);
+                let writer = DurableWriter {
+                    input: receiver,
+                    sink,
+                    gate,
+                    written: metrics.counter("durable_output_written"),
+                    discarded: metrics.counter("durable_output_discarded"),
+                };
+                (Some(sender), Some(writer))
+            }
+            None => (None, None),
+        };
+
+        let tiers = Self {
+            input,
+            depth,
+            buckets: None,
+            fast_output,
+            durable_output,
+            durable_enqueued: metrics.counter("durable_output_enqueued"),
+            durable_dropped: metrics.counter("durable_output_dropped"),
+        };
+
+        (tiers, fast_writer, durable_writer)
+    }
+
+    /// Aggregate the book levels into price buckets instead of taking the top levels
+    pub fn with_buckets(mut self, buckets: PriceBuckets) -> Self {
+        self.buckets = Some(buckets);
+        self
+    }
+
+    /// Run the OutputTiers as an asynchronous task
+    ///
+    /// Neither of the outputs ever blocks this task, so it always keeps up with the book processor
+    pub async fn run(mut self) {
+        let mut sequence = 0;
+
+        while let Some(book) = self.input.recv().await {
+            sequence += 1;
+            let time = Utc::now().timestamp_millis();
+            let frame = match &self.buckets {
+                Some(buckets) => BookFrame::bucketed(sequence, time, &book, buckets),
+                None => BookFrame::new(sequence, time, &book, self.depth),
+            };
+
+            if let Some(durable_output) = &self.durable_output {
+                match durable_output.try_send(frame.clone()) {
+                    Ok(()) => self.durable_enqueued.increment(1),
+                    Err(TrySendError::Full(_)) => self.durable_dropped.increment(1),
+                    Err(TrySendError::Closed(_)) => {
+                        tracing::warn!("Durable output is closed. Disabling it");
+                        self.durable_output = None;
+                    }
+                }
+            }
+
+            if let Some(fast_output) = &self.fast_output {
+                if fast_output.send(Some(frame)).is_err() {
+                    tracing::warn!("Fast output is closed. Disabling it");
+                    self.fast_output = None;
+                }
+            }
+        }
+    }
+}
+
+/// Writes the latest frame into the fast sink, skipping frames which were replaced before they could be written
+pub struct FastWriter {
+    input: watch::Receiver<Option<BookFrame>>,
+    sink: Box<dyn FrameSink>,
+    published: Counter,
+    conflated: Counter,
+}
+
+impl FastWriter {
+    /// Run the FastWriter as an asynchronous task
+    pub async fn run(mut self) {
+        let mut last_sequence = 0;
+
+        while self.input.changed().await.is_ok() {
+            let Some(frame) = self.input.borrow_and_update().clone() else {
+                continue;
+            };
+
+            self.conflated.increment(frame.sequence.saturating_sub(last_sequence + 1));
+            last_sequence = frame.sequence;
+
+            match self.sink.write(&frame) {
+                Ok(()) => self.published.increment(1),
+                Err(e) => tracing::error!("Failed to publish frame to fast output. Details: '{}'", e),
+            }
+        }
+    }
+}
+
+/// Writes every frame into the durable sink
+pub struct DurableWriter {
+    input: mpsc::Receiver<BookFrame>,
+    sink: Box<dyn FrameSink>,
+    gate: CaptureGate,
+    written: Counter,
+    discarded: Counter,
+}
+
+impl DurableWriter {
+    /// Run the DurableWriter as an asynchronous task
+    ///
+    /// The sink is flushed whenever the queue is drained
+    pub async fn run(mut self) {
+        while let Some(frame) = self.input.recv().await {
+            if self.gate.is_paused() {
+                self.discarded.increment(1);
+                continue;
+            }
+
+            match self.sink.write(&frame) {
+                Ok(()) => self.written.increment(1),
+                Err(e) => tracing::error!("Failed to write frame to durable output. Details: '{}'", e),
+            }
+
+            if self.input.is_empty() {
+                if let Err(e) = self.sink.flush() {
+                    tracing::error!("Failed to flush durable output. Details: '{}'", e);
+                }
+            }
+        }
+
+        if let Err(e) = self.sink.flush() {
+            tracing::error!("Failed to flush durable output. Details: '{}'", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::depth_buckets::BucketUnit;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use std::sync::Mutex;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    /// Sink, which collects frames in memory
+    struct CollectingSink {
+        frames: Arc<Mutex<Vec<BookFrame>>>,
+    }
+
+    impl FrameSink for CollectingSink {
+        fn write(&mut self, frame: &BookFrame) -> Result<()> {
+            self.frames.lock().unwrap().push(frame.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn make_book(bid: f64) -> OrderBook {
+        OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(bid), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(bid - 1.0), quantity: FixedPoint::from(2.0) },
+            ],
+            asks: vec![DepthEntry { price: FixedPoint::from(bid + 1.0), quantity: FixedPoint::from(3.0) }],
+        })
+    }
+
+    /// Read the latest frame from a shared memory file the way an external reader would
+    fn read_shared_memory(path: &Path) -> Option<BookFrame> {
+        let data = std::fs::read(path).unwrap();
+        let version = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        if version == 0 || version % 2 == 1 {
+            return None;
+        }
+
+        let len = u64::from_le_bytes(data[8..16].try_into().unwrap()) as usize;
+        serde_json::from_slice(&data[SHARED_MEMORY_HEADER_SIZE..SHARED_MEMORY_HEADER_SIZE + len]).ok()
+    }
+
+    #[test]
+    fn test_book_frame_takes_top_levels() {
+        let frame = BookFrame::new(7, 1000, &make_book(100.0), 1);
+        assert_eq!(frame.sequence, 7);
+        assert_eq!(frame.bids, vec![[100.0, 1.0]]);
+        assert_eq!(frame.asks, vec![[101.0, 3.0]]);
+    }
+
+    #[test]
+    fn test_bucketed_book_frame() {
+        let buckets = PriceBuckets { width: 5.0, unit: BucketUnit::Price, count: 2 };
+        let frame = BookFrame::bucketed(7, 1000, &make_book(100.0), &buckets);
+        assert_eq!(frame.bids, vec![[100.0, 1.0], [95.0, 2.0]]);
+        assert_eq!(frame.asks, vec![[105.0, 3.0], [110.0, 0.0]]);
+    }
+
+    #[test]
+    fn test_shared_memory_sink() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_{}", std::process::id(), "fast_output.shm"));
+        let mut sink = SharedMemoryFrameSink::create(&path, 4096).unwrap();
+        assert_eq!(read_shared_memory(&path), None);
+
+        let first = BookFrame::new(1, 1000, &make_book(100.0), 10);
+        let second = BookFrame::new(2, 1001, &make_book(99.0), 1);
+        sink.write(&first).unwrap();
+        sink.write(&second).unwrap();
+        assert_eq!(read_shared_memory(&path), Some(second));
+
+        let mut small_sink = SharedMemoryFrameSink::create(&path, 8).unwrap();
+        assert!(small_sink.write(&first).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_durable_path_is_complete_and_fast_path_is_latest() {
+        let (input_tx, input_rx) = mpsc::channel(100);
+        let fast_frames = Arc::new(Mutex::new(Vec::new()));
+        let durable_frames = Arc::new(Mutex::new(Vec::new()));
+        let metrics = Metrics::new();
+
+        let (tiers, fast_writer, durable_writer) = OutputTiers::new(
+            input_rx,
+            5,
+            Some(Box::new(CollectingSink { frames: fast_frames.clone() })),
+            Some(Box::new(CollectingSink { frames: durable_frames.clone() })),
+            CaptureGate::new(),
+            &metrics,
+        );
+
+        for i in 0..10 {
+            input_tx.send(Arc::new(make_book(100.0 + i as f64))).await.unwrap();
+        }
+        drop(input_tx);
+
+        // The writers start only after all frames are produced, so the fast path sees only the latest one
+        tiers.run().await;
+        fast_writer.unwrap().run().await;
+        durable_writer.unwrap().run().await;
+
+        let durable_frames = durable_frames.lock().unwrap();
+        assert_eq!(durable_frames.len(), 10);
+        assert_eq!(durable_frames.iter().map(|frame| frame.sequence).collect::<Vec<_>>(), (1..=10).collect::<Vec<_>>());
+
+        let fast_frames = fast_frames.lock().unwrap();
+        assert_eq!(fast_frames.len(), 1);
+        assert_eq!(fast_frames[0].sequence, 10);
+
+        let counters = metrics.snapshot();
+        assert_eq!(counters["durable_output_enqueued"], 10);
+        assert_eq!(counters["durable_output_written"], 10);
+        assert_eq!(counters["fast_output_published"], 1);
+        assert_eq!(counters["fast_output_conflated"], 9);
+    }
+
+    #[tokio::test]
+    async fn test_durable_path_drops_frames_beyond_capacity() {
+        let (input_tx, input_rx) = mpsc::channel(DURABLE_OUTPUT_CAPACITY + 5);
+        let durable_frames = Arc::new(Mutex::new(Vec::new()));
+        let metrics = Metrics::new();
+
+        let (tiers, _, durable_writer) = OutputTiers::new(
+            input_rx,
+            1,
+            None,
+            Some(Box::new(CollectingSink { frames: durable_frames.clone() })),
+            CaptureGate::new(),
+            &metrics,
+        );
+
+        let book = Arc::new(make_book(100.0));
+        for _ in 0..DURABLE_OUTPUT_CAPACITY + 5 {
+            input_tx.send(book.clone()).await.unwrap();
+        }
+        drop(input_tx);
+
+        // The durable writer starts only after all frames are produced, so the queue overflows
+        tiers.run().await;
+        durable_writer.unwrap().run().await;
+
+        let durable_frames = durable_frames.lock().unwrap();
+        assert_eq!(durable_frames.len(), DURABLE_OUTPUT_CAPACITY);
+        assert_eq!(durable_frames.last().unwrap().sequence, DURABLE_OUTPUT_CAPACITY as u64);
+
+        let counters = metrics.snapshot();
+        assert_eq!(counters["durable_output_enqueued"], DURABLE_OUTPUT_CAPACITY as u64);
+        assert_eq!(counters["durable_output_dropped"], 5);
+    }
+}
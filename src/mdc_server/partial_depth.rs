@@ -0,0 +1,215 @@
+use std::future::pending;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Duration, Instant};
+use crate::mdc_core::models::{DepthSnapshot, DepthUpdate, FromJson, MarketEvent, StreamMessage};
+use crate::mdc_server::tape::TapeRecorder;
+
+/// Source of the snapshots, which seed or validate the maintained book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotSource {
+    /// REST depth snapshots
+    #[default]
+    Rest,
+    /// The partial book depth stream, which covers only the top `partial_depth_levels` levels per side
+    PartialStream,
+    /// Depth snapshots requested over the WebSocket API (`depth` method), the same as the REST ones
+    WsApi,
+}
+
+/// A frame of a Binance partial book depth stream (`<symbol>@depth<levels>@100ms`), decoded as a snapshot of the top levels
+///
+/// Spot partial books have the format of REST snapshots. Futures ones have the format of depth updates, whose `u`
+/// is the id of the book, so the following depth update continues it (`pu`)
+pub struct PartialBookMessage;
+
+impl StreamMessage for PartialBookMessage {
+    type State = ();
+
+    fn decode(_state: &mut (), message: &str) -> Result<Vec<MarketEvent>, serde_json::Error> {
+        let snapshot = match DepthSnapshot::from_json(message) {
+            Ok(snapshot) => snapshot,
+            Err(_) => DepthSnapshot::from(DepthUpdate::from_json(message)?),
+        };
+
+        Ok(vec![MarketEvent::DepthSnapshot(snapshot)])
+    }
+}
+
+/// PartialSnapshotSampler forwards partial books as snapshots the way the DepthSnapshotStream forwards REST snapshots:
+/// the first received one, then one per update interval and one right after each snapshot request
+///
+/// The partial books in between are dropped. A forwarded partial book is recorded in the format of a REST snapshot,
+/// so the tape is replayed the same way
+pub struct PartialSnapshotSampler {
+    input: mpsc::Receiver<MarketEvent>,
+    output: mpsc::Sender<MarketEvent>,
+    update_interval: u64,
+    recorder: Option<TapeRecorder>,
+    requests: Option<mpsc::Receiver<()>>,
+    deferred_start: bool,
+}
+
+/// The next snapshot request, or never if there is no request channel
+async fn next_request(requests: &mut Option<mpsc::Receiver<()>>) -> Option<()> {
+    match requests {
+        Some(requests) => requests.recv().await,
+        None => pending().await,
+    }
+}
+
+impl PartialSnapshotSampler {
+    /// Create a new PartialSnapshotSampler
+    ///
+    /// # Arguments
+    /// * `input` - Receiver for the partial books of the partial depth stream
+    /// * `output` - Sender for MarketEvent messages to the DepthEventDispatcher (or to the book validation)
+    /// * `update_interval` - The interval between forwarded snapshots in milliseconds
+    /// * `recorder` - Optional recorder, which persists every forwarded snapshot
+    pub fn new(
+        input: mpsc::Receiver<MarketEvent>,
+        output: mpsc::Sender<MarketEvent>,
+        update_interval: u64,
+        recorder: Option<TapeRecorder>,
+    ) -> Self {
+        Self {
+            input,
+            output,
+            update_interval,
+            recorder,
+            requests: None,
+            deferred_start: false,
+        }
+    }
+
+    /// Additionally forward the next partial book whenever a message is received from the channel,
+    /// instead of waiting for the end of the update interval
+    pub fn with_requests(mut self, requests: mpsc::Receiver<()>) -> Self {
+        self.requests = Some(requests);
+        self
+    }
+
+    /// Wait for the end of the first update interval (or for a snapshot request) before the first snapshot,
+    /// e.g. when the book has been resumed from a checkpoint
+    pub fn with_deferred_start(mut self) -> Self {
+        self.deferred_start = true;
+        self
+    }
+
+    async fn forward(&self, snapshot: DepthSnapshot) {
+        tracing::debug!("Forwarding partial book '{}' as a snapshot", snapshot.last_update_id);
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&snapshot.to_json()).await;
+        }
+
+        if let Err(e) = self.output.send(MarketEvent::DepthSnapshot(snapshot)).await {
+            tracing::error!("Failed to send partial book snapshot. Details: '{}'", e);
+        }
+    }
+
+    /// Run the PartialSnapshotSampler as an asynchronous task
+    ///
+    /// This method will continuously forward partial books until the partial depth stream is closed
+    pub async fn run(mut self) {
+        tracing::info!("Starting PartialSnapshotSampler with update interval: '{}' ms", self.update_interval);
+
+        let interval = Duration::from_millis(self.update_interval);
+        let mut due = !self.deferred_start;
+        let mut next = Instant::now() + interval;
+
+        loop {
+            tokio::select! {
+                event = self.input.recv() => match event {
+                    Some(MarketEvent::DepthSnapshot(snapshot)) => {
+                        if due {
+                            self.forward(snapshot).await;
+                            due = false;
+                            next = Instant::now() + interval;
+                        }
+                    }
+                    Some(event) => tracing::warn!("Unexpected event in partial depth channel: '{}'", event),
+                    None => break,
+                },
+                _ = sleep_until(next), if !due => due = true,
+                request = next_request(&mut self.requests), if !due => match request {
+                    Some(()) => {
+                        tracing::info!("Snapshot requested before the end of the update interval");
+                        due = true;
+                    }
+                    None => self.requests = None,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPOT_PARTIAL_BOOK: &str = r#"{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[["0.0026","100"]]}"#;
+    const FUTURES_PARTIAL_BOOK: &str = r#"{"e":"depthUpdate","E":1571889248277,"T":1571889248276,"s":"BTCUSDT","U":390497796,"u":390497878,"pu":390497794,"b":[["7403.89","0.002"]],"a":[["7405.96","3.340"],["7406.63","4.525"]]}"#;
+
+    fn decode(message: &str) -> DepthSnapshot {
+        match PartialBookMessage::decode(&mut (), message).unwrap().pop() {
+            Some(MarketEvent::DepthSnapshot(snapshot)) => snapshot,
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_decode_partial_books() {
+        let spot = decode(SPOT_PARTIAL_BOOK);
+        assert_eq!((spot.last_update_id, spot.bids.len(), spot.asks.len()), (160, 1, 1));
+
+        let futures = decode(FUTURES_PARTIAL_BOOK);
+        assert_eq!((futures.last_update_id, futures.bids.len(), futures.asks.len()), (390497878, 1, 2));
+
+        // Recorded partial books are replayed as REST snapshots
+        let recorded = DepthSnapshot::from_json(&futures.to_json()).unwrap();
+        assert_eq!(recorded.last_update_id, 390497878);
+        assert_eq!(recorded.asks[1].price, futures.asks[1].price);
+    }
+
+    #[tokio::test]
+    async fn test_forward_one_partial_book_per_interval_and_request() {
+        let (input_sender, input_receiver) = mpsc::channel(10);
+        let (output_sender, mut output_receiver) = mpsc::channel(10);
+        let (request_sender, request_receiver) = mpsc::channel(1);
+        tokio::spawn(PartialSnapshotSampler::new(input_receiver, output_sender, 1000, None).with_requests(request_receiver).run());
+
+        let send = |id: u64| {
+            let input_sender = input_sender.clone();
+            async move {
+                let snapshot = DepthSnapshot { last_update_id: id, bids: vec![], asks: vec![] };
+                input_sender.send(MarketEvent::DepthSnapshot(snapshot)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        let forwarded = |output_receiver: &mut mpsc::Receiver<MarketEvent>| {
+            let mut ids = Vec::new();
+            while let Ok(MarketEvent::DepthSnapshot(snapshot)) = output_receiver.try_recv() {
+                ids.push(snapshot.last_update_id);
+            }
+            ids
+        };
+
+        for id in 1..=5 {
+            send(id).await;
+        }
+        assert_eq!(forwarded(&mut output_receiver), vec![1]);
+
+        request_sender.send(()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        send(6).await;
+        send(7).await;
+        assert_eq!(forwarded(&mut output_receiver), vec![6]);
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        send(8).await;
+        send(9).await;
+        assert_eq!(forwarded(&mut output_receiver), vec![8]);
+    }
+}
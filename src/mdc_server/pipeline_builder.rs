@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Result};
+use crate::mdc_server::config::Config;
+use crate::mdc_server::exchange_connector::Exchange;
+use crate::mdc_server::pipeline_sink::PipelineSink;
+use crate::mdc_server::server::MDCServer;
+
+/// Configuration of a pipeline without a configuration file: the spot BTCUSDT capture of the example mdc.yaml
+const DEFAULT_CONFIG: &str = r#"
+rest_endpoint: "https://api.binance.com/api/v3/"
+wss_endpoint: "wss://stream.binance.com:9443/ws/"
+instrument: "BTCUSDT"
+max_depth: 100
+connections: 3
+reconnect_timeout: 5000
+snapshot_update_interval: 5000
+"#;
+
+/// PipelineBuilder configures a capture pipeline, which is embedded into another program
+///
+/// Starts from the configuration of the example mdc.yaml (or a loaded one). The maintained book and the market
+/// events are delivered to the sinks
+///
+/// # Example
+/// ```no_run
+/// # async fn example(sink: impl mdc::PipelineSink + 'static) -> anyhow::Result<()> {
+/// mdc::PipelineBuilder::new()
+///     .symbol("ethusdt")
+///     .with_sink(sink)
+///     .build()?
+///     .run()
+///     .await
+/// # }
+/// ```
+pub struct PipelineBuilder {
+    config: Config,
+    sinks: Vec<Box<dyn PipelineSink>>,
+    record: bool,
+}
+
+impl PipelineBuilder {
+    /// Create a new PipelineBuilder with the default configuration
+    pub fn new() -> Self {
+        let config = serde_yaml::from_str(DEFAULT_CONFIG).expect("Default pipeline configuration is valid");
+        Self::from_config(config)
+    }
+
+    /// Create a new PipelineBuilder, which starts from the given configuration (e.g. a loaded pipeline)
+    pub fn from_config(config: Config) -> Self {
+        Self {
+            config,
+            sinks: Vec::new(),
+            record: false,
+        }
+    }
+
+    /// Capture the exchange through its public endpoints
+    pub fn exchange(mut self, exchange: Exchange) -> Self {
+        let (rest_endpoint, wss_endpoint) = match exchange {
+            Exchange::Binance => ("https://api.binance.com/api/v3/", "wss://stream.binance.com:9443/ws/"),
+            Exchange::BinanceFutures => ("https://fapi.binance.com/fapi/v1/", "wss://fstream.binance.com/ws/"),
+            Exchange::Okx => ("https://www.okx.com/api/v5/", "wss://ws.okx.com:8443/ws/v5/public"),
+            Exchange::Bitfinex => ("https://api-pub.bitfinex.com/v2/", "wss://api-pub.bitfinex.com/ws/2"),
+            Exchange::Deribit => ("https://www.deribit.com/api/v2/", "wss://www.deribit.com/ws/api/v2"),
+        };
+
+        self.config.exchange = exchange;
+        self.config.rest_endpoint = rest_endpoint.to_string();
+        self.config.wss_endpoint = wss_endpoint.to_string();
+        self
+    }
+
+    /// Capture the given instrument (e.g. "BTCUSDT" or "tBTCUSD")
+    ///
+    /// The symbol is normalized for the exchange on `build`, so it may be set before the exchange
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.config.instrument = symbol.to_string();
+        self
+    }
+
+    /// Write the capture artifacts (manifest, tape, rollups, ...) into the given directory
+    pub fn capture_dir(mut self, capture_dir: &str) -> Self {
+        self.config.capture_dir = capture_dir.to_string();
+        self
+    }
+
+    /// Adjust any other parameter of the configuration
+    pub fn configure(mut self, configure: impl FnOnce(&mut Config)) -> Self {
+        configure(&mut self.config);
+        self
+    }
+
+    /// Deliver the book and the market events to the sink
+    pub fn with_sink(mut self, sink: impl PipelineSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Persist every raw frame into a tape file in the capture directory
+    pub fn record(mut self, record: bool) -> Self {
+        self.record = record;
+        self
+    }
+
+    /// The configuration of the pipeline with the symbol normalized for the exchange
+    fn checked_config(&self) -> Result<Config> {
+        let mut config = self.config.clone();
+        config.instrument = config.exchange.normalize_symbol(&config.instrument);
+
+        if config.instrument.is_empty() {
+            return Err(anyhow!("Pipeline has no instrument"));
+        }
+        if config.connections == 0 {
+            return Err(anyhow!("Pipeline '{}' has no depth connections", config.instrument));
+        }
+
+        Ok(config)
+    }
+
+    /// Check the configuration and create the pipeline
+    pub fn build(self) -> Result<Pipeline> {
+        let config = self.checked_config()?;
+
+        Ok(Pipeline {
+            server: MDCServer::new(config).with_sinks(self.sinks),
+            record: self.record,
+        })
+    }
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A capture pipeline created by PipelineBuilder
+pub struct Pipeline {
+    server: MDCServer,
+    record: bool,
+}
+
+impl Pipeline {
+    /// Run the pipeline until it is stopped (e.g. by aborting the task) or fails
+    pub async fn run(self) -> Result<()> {
+        self.server.start(self.record, false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_adjusts_default_configuration() {
+        let builder = PipelineBuilder::new()
+            .exchange(Exchange::BinanceFutures)
+            .symbol("ethusdt")
+            .capture_dir("/tmp/mdc")
+            .configure(|config| config.connections = 1);
+
+        let config = builder.checked_config().unwrap();
+        assert_eq!(config.exchange, Exchange::BinanceFutures);
+        assert_eq!(config.rest_endpoint, "https://fapi.binance.com/fapi/v1/");
+        assert_eq!(config.instrument, "ETHUSDT");
+        assert_eq!(config.capture_dir, "/tmp/mdc");
+        assert_eq!(config.connections, 1);
+        assert_eq!(config.max_depth, 100);
+
+        assert!(builder.build().is_ok());
+        assert!(PipelineBuilder::new().symbol("").build().is_err());
+        assert!(PipelineBuilder::new().configure(|config| config.connections = 0).build().is_err());
+    }
+
+    #[test]
+    fn test_builder_keeps_case_sensitive_symbols() {
+        let config = PipelineBuilder::new().symbol("tBTCUSD").exchange(Exchange::Bitfinex).checked_config().unwrap();
+        assert_eq!(config.instrument, "tBTCUSD");
+
+        let config = PipelineBuilder::new().exchange(Exchange::Okx).symbol("btc-usdt").checked_config().unwrap();
+        assert_eq!(config.instrument, "BTC-USDT");
+    }
+}
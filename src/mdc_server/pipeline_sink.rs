@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use anyhow::Result;
+use tokio::sync::mpsc;
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+
+/// Consumer of the output of a pipeline embedded into another program (see `PipelineBuilder`)
+///
+/// The methods are called from a single pipeline task, so a slow sink holds back the pipeline. Errors are logged
+/// and don't stop the delivery
+pub trait PipelineSink: Send {
+    /// A new state of the maintained book
+    fn on_book(&mut self, book: &Arc<OrderBook>) -> Result<()>;
+
+    /// A trade, a best bid/ask update (bookTicker) or an auxiliary event (aggregated trade, kline, ticker, ...)
+    fn on_event(&mut self, event: &MarketEvent) -> Result<()>;
+}
+
+/// PipelineSinkForwarder delivers the book and the market events of the pipeline to the sinks
+pub struct PipelineSinkForwarder {
+    sinks: Vec<Box<dyn PipelineSink>>,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    price_channel: mpsc::Receiver<MarketEvent>,
+    auxiliary_channel: mpsc::Receiver<MarketEvent>,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    symbol: Option<String>,
+}
+
+impl PipelineSinkForwarder {
+    /// Create a new PipelineSinkForwarder
+    ///
+    /// # Arguments
+    /// * `sinks` - The sinks, which receive every book and event
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    /// * `price_channel` - Receiver for MarketEvent messages containing PriceUpdates
+    /// * `auxiliary_channel` - Receiver for MarketEvent messages containing KlineEvents, AggTradeEvents,
+    ///   TickerEvents, MiniTickerEvents, MarkPriceEvents, LiquidationEvents and PartialBookEvents
+    /// * `book_channel` - Receiver for OrderBook messages
+    pub fn new(
+        sinks: Vec<Box<dyn PipelineSink>>,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+        price_channel: mpsc::Receiver<MarketEvent>,
+        auxiliary_channel: mpsc::Receiver<MarketEvent>,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    ) -> Self {
+        Self {
+            sinks,
+            trade_channel,
+            price_channel,
+            auxiliary_channel,
+            book_channel,
+            symbol: None,
+        }
+    }
+
+    /// Replace the venue symbol of the forwarded events with the canonical symbol of the instrument (see `SymbolMap`)
+    pub fn with_symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    fn forward_event(&mut self, mut event: MarketEvent) {
+        if let (Some(symbol), Some(event_symbol)) = (&self.symbol, event.symbol_mut()) {
+            event_symbol.clone_from(symbol);
+        }
+
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.on_event(&event) {
+                tracing::error!("Pipeline sink failed to consume event '{}'. Details: '{:#}'", event, e);
+            }
+        }
+    }
+
+    fn forward_book(&mut self, book: &Arc<OrderBook>) {
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.on_book(book) {
+                tracing::error!("Pipeline sink failed to consume the book. Details: '{:#}'", e);
+            }
+        }
+    }
+
+    /// Run the PipelineSinkForwarder as an asynchronous task
+    ///
+    /// This method will continuously forward books and events from all channels until they are closed
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                Some(event) = self.trade_channel.recv() => self.forward_event(event),
+                Some(event) = self.price_channel.recv() => self.forward_event(event),
+                Some(event) = self.auxiliary_channel.recv() => self.forward_event(event),
+                Some(book) = self.book_channel.recv() => self.forward_book(&book),
+                else => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::mdc_core::models::{DepthSnapshot, FromJson, PriceUpdate};
+
+    /// Records the received books (by the number of bid levels) and price updates (by update id and symbol)
+    struct RecordingSink(Arc<Mutex<Vec<String>>>);
+
+    impl PipelineSink for RecordingSink {
+        fn on_book(&mut self, book: &Arc<OrderBook>) -> Result<()> {
+            self.0.lock().unwrap().push(format!("book {}", book.bids.len()));
+            Ok(())
+        }
+
+        fn on_event(&mut self, event: &MarketEvent) -> Result<()> {
+            if let MarketEvent::PriceUpdate(update) = event {
+                self.0.lock().unwrap().push(format!("price {} {}", update.update_id, update.symbol));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_books_and_events_reach_every_sink() {
+        let (first, second) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+        let (trade_tx, trade_rx) = mpsc::channel(10);
+        let (price_tx, price_rx) = mpsc::channel(10);
+        let (auxiliary_tx, auxiliary_rx) = mpsc::channel(10);
+        let (book_tx, book_rx) = mpsc::channel(10);
+
+        let forwarder = PipelineSinkForwarder::new(
+            vec![Box::new(RecordingSink(first.clone())), Box::new(RecordingSink(second.clone()))],
+            trade_rx,
+            price_rx,
+            auxiliary_rx,
+            book_rx,
+        ).with_symbol("BTC-USD");
+
+        let snapshot = DepthSnapshot::from_json(r#"{"lastUpdateId":7,"bids":[["100.0","1.0"]],"asks":[]}"#).unwrap();
+        book_tx.send(Arc::new(OrderBook::new(&snapshot))).await.unwrap();
+        drop(book_tx);
+
+        let price = r#"{"u":9,"s":"BTCUSDT","b":"100.0","B":"1.0","a":"101.0","A":"1.0"}"#;
+        price_tx.send(MarketEvent::PriceUpdate(serde_json::from_str::<PriceUpdate>(price).unwrap())).await.unwrap();
+        drop(price_tx);
+        drop(trade_tx);
+        drop(auxiliary_tx);
+
+        forwarder.run().await;
+
+        for received in [first, second] {
+            let mut received = received.lock().unwrap().clone();
+            received.sort();
+            assert_eq!(received, vec!["book 1".to_string(), "price 9 BTC-USD".to_string()]);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use tokio::sync::mpsc;
+use crate::mdc_core::deduplication::{MonotonicFilter, UpdateVerdict};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// PriceEventDispatcher merges bookTicker updates from multiple WebSocket connections
+/// It forwards updates in update id order, dropping copies and updates, which are older than an already forwarded one
+pub struct PriceEventDispatcher {
+    input: mpsc::Receiver<MarketEvent>,
+    output: mpsc::Sender<MarketEvent>,
+    filter: MonotonicFilter,
+    duplicates: Counter,
+    out_of_order: Counter,
+}
+
+impl PriceEventDispatcher {
+    /// Create a new PriceEventDispatcher
+    ///
+    /// # Arguments
+    /// * `input` - Receiver for MarketEvent messages from multiple connections
+    /// * `output` - Sender for filtered MarketEvent messages
+    /// * `metrics` - Registry of the dropped update counters
+    pub fn new(input: mpsc::Receiver<MarketEvent>, output: mpsc::Sender<MarketEvent>, metrics: &Metrics) -> Self {
+        Self {
+            input,
+            output,
+            filter: MonotonicFilter::default(),
+            duplicates: metrics.counter("price_duplicates"),
+            out_of_order: metrics.counter("price_out_of_order"),
+        }
+    }
+
+    /// Run the PriceEventDispatcher
+    ///
+    /// This method will continuously process messages from the input channel
+    /// and send the updates newer than all previous ones to the output channel
+    pub async fn run(mut self) {
+        tracing::info!("Starting PriceEventDispatcher");
+
+        while let Some(event) = self.input.recv().await {
+            let MarketEvent::PriceUpdate(update) = &event else {
+                tracing::error!("Received unexpected event type: '{:?}'. Discarding", &event);
+                continue;
+            };
+
+            match self.filter.accept(update.update_id) {
+                UpdateVerdict::New => {
+                    self.output
+                        .send(event)
+                        .await
+                        .expect("Failed to forward PriceUpdate to output channel");
+                }
+                UpdateVerdict::Duplicate => {
+                    tracing::trace!("Dropping duplicate price update '{}'", update.update_id);
+                    self.duplicates.increment(1);
+                }
+                UpdateVerdict::OutOfOrder => {
+                    tracing::debug!("Dropping outdated price update '{}'", update.update_id);
+                    self.out_of_order.increment(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::PriceUpdate;
+
+    fn make_update(update_id: u64) -> MarketEvent {
+        MarketEvent::PriceUpdate(PriceUpdate {
+            update_id,
+            symbol: "BTCUSDT".to_string(),
+            best_bid_price: 100.0,
+            best_bid_quantity: 1.0,
+            best_ask_price: 101.0,
+            best_ask_quantity: 1.0,
+            received: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_price_updates_are_forwarded_in_order_once() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+        tokio::spawn(PriceEventDispatcher::new(input_rx, output_tx, &metrics).run());
+
+        for update_id in [1, 1, 3, 2, 3, 4] {
+            input_tx.send(make_update(update_id)).await.unwrap();
+        }
+        drop(input_tx);
+
+        let mut forwarded = Vec::new();
+        while let Some(MarketEvent::PriceUpdate(update)) = output_rx.recv().await {
+            forwarded.push(update.update_id);
+        }
+
+        assert_eq!(forwarded, vec![1, 3, 4]);
+        assert_eq!(metrics.snapshot()["price_duplicates"], 2);
+        assert_eq!(metrics.snapshot()["price_out_of_order"], 1);
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::{Arc, Mutex};
+use chrono::Utc;
+use tokio::time::{sleep, Duration};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// Length of the request weight window of Binance in milliseconds
+const WEIGHT_WINDOW: i64 = 60_000;
+
+/// Wait in seconds after HTTP 429/418 responses, which don't tell how long to wait
+const DEFAULT_RETRY_AFTER: u64 = 60;
+
+#[derive(Debug, Default)]
+struct WeightState {
+    /// Start of the current weight window in milliseconds since epoch
+    window_start: i64,
+    /// Weight used in the current window
+    used: u64,
+    /// Time in milliseconds since epoch, until which no requests may be sent
+    retry_at: Option<i64>,
+}
+
+impl WeightState {
+    fn roll(&mut self, now: i64) {
+        let window_start = now - now.rem_euclid(WEIGHT_WINDOW);
+        if window_start != self.window_start {
+            self.window_start = window_start;
+            self.used = 0;
+        }
+    }
+}
+
+/// RequestWeightBudget keeps the REST request weight of the process below a budget per minute
+///
+/// Binance limits the request weight per IP and minute and bans IPs, which keep exceeding the limit. The budget
+/// accounts the weight of the sent requests and adopts the used weight reported by Binance in the
+/// `X-MBX-USED-WEIGHT-1M` header of each response, so requests of other processes on the same IP are accounted too.
+/// Requests, which would exceed the budget, are delayed until the next window. After HTTP 429 (rate limit) and
+/// 418 (IP ban) responses, requests are delayed as long as the `Retry-After` header tells
+#[derive(Debug, Clone)]
+pub struct RequestWeightBudget {
+    budget: u64,
+    state: Arc<Mutex<WeightState>>,
+    delayed: Counter,
+    rate_limited: Counter,
+}
+
+impl RequestWeightBudget {
+    /// Create a new RequestWeightBudget
+    ///
+    /// # Arguments
+    /// * `budget` - Request weight, which may be used per minute
+    /// * `metrics` - Registry of the `rest_requests_delayed` and `rest_rate_limited` counters
+    pub fn new(budget: u64, metrics: &Metrics) -> Self {
+        Self {
+            budget,
+            state: Arc::new(Mutex::new(WeightState::default())),
+            delayed: metrics.counter("rest_requests_delayed"),
+            rate_limited: metrics.counter("rest_rate_limited"),
+        }
+    }
+
+    /// Reserve the weight of a request
+    ///
+    /// # Arguments
+    /// * `weight` - Weight of the request
+    /// * `now` - Time in milliseconds since epoch
+    ///
+    /// # Returns
+    /// `None` if the request may be sent now, otherwise the time in milliseconds to wait before trying again
+    pub fn reserve(&self, weight: u64, now: i64) -> Option<u64> {
+        let mut state = self.state.lock().expect("Request weight lock is poisoned");
+        state.roll(now);
+
+        let delay = match state.retry_at {
+            Some(retry_at) if retry_at > now => Some((retry_at - now) as u64),
+            // A request heavier than the budget is allowed in an unused window, otherwise it is never sent
+            _ if state.used > 0 && state.used + weight > self.budget => Some((state.window_start + WEIGHT_WINDOW - now) as u64),
+            _ => None,
+        };
+
+        match delay {
+            Some(_) => self.delayed.increment(1),
+            None => state.used += weight,
+        }
+        delay
+    }
+
+    /// Wait until a request of the given weight fits into the budget and reserve its weight
+    ///
+    /// # Arguments
+    /// * `weight` - Weight of the request
+    /// * `request` - Name of the request in logs (e.g. "Snapshot")
+    pub async fn acquire(&self, weight: u64, request: &str) {
+        while let Some(delay) = self.reserve(weight, Utc::now().timestamp_millis()) {
+            tracing::warn!("{} request of weight '{}' exceeds the request weight budget. Delaying it by '{}' ms", request, weight, delay);
+            sleep(Duration::from_millis(delay)).await;
+        }
+    }
+
+    /// Account the response of a request
+    ///
+    /// # Arguments
+    /// * `status` - HTTP status of the response
+    /// * `used_weight` - Used weight of the current window, as reported by the exchange
+    /// * `retry_after` - Value of the `Retry-After` header in seconds, if any
+    /// * `now` - Time in milliseconds since epoch
+    pub fn on_response(&self, status: u16, used_weight: Option<u64>, retry_after: Option<u64>, now: i64) {
+        let mut state = self.state.lock().expect("Request weight lock is poisoned");
+        state.roll(now);
+
+        if let Some(used_weight) = used_weight {
+            state.used = used_weight;
+        }
+
+        if status == 429 || status == 418 {
+            let retry_after = retry_after.unwrap_or(DEFAULT_RETRY_AFTER);
+            state.retry_at = Some(now + retry_after as i64 * 1000);
+            self.rate_limited.increment(1);
+            tracing::error!("REST requests are rate limited (HTTP {}). Next request in '{}' s", status, retry_after);
+        }
+    }
+
+    /// Account an HTTP response by its status and the `X-MBX-USED-WEIGHT-1M` and `Retry-After` headers
+    pub fn on_http_response(&self, response: &reqwest::Response) {
+        self.on_response(
+            response.status().as_u16(),
+            header_value(response, "x-mbx-used-weight-1m"),
+            header_value(response, "retry-after"),
+            Utc::now().timestamp_millis()
+        );
+    }
+}
+
+/// Parse a numeric response header
+fn header_value(response: &reqwest::Response, name: &str) -> Option<u64> {
+    response.headers().get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_over_budget_wait_for_next_window() {
+        let metrics = Metrics::new();
+        let budget = RequestWeightBudget::new(100, &metrics);
+
+        assert_eq!(budget.reserve(50, 120_000), None);
+        assert_eq!(budget.reserve(50, 130_000), None);
+        assert_eq!(budget.reserve(5, 140_000), Some(40_000));
+        assert_eq!(budget.reserve(5, 180_000), None);
+
+        // Weight used by other processes is adopted from the response
+        budget.on_response(200, Some(98), None, 181_000);
+        assert_eq!(budget.reserve(5, 182_000), Some(58_000));
+
+        // A request heavier than the whole budget is still sent in an unused window
+        assert_eq!(budget.reserve(250, 240_000), None);
+
+        assert_eq!(metrics.snapshot()["rest_requests_delayed"], 2);
+    }
+
+    #[test]
+    fn test_rate_limit_responses_delay_requests() {
+        let metrics = Metrics::new();
+        let budget = RequestWeightBudget::new(1000, &metrics);
+
+        budget.on_response(429, Some(1200), Some(30), 10_000);
+        assert_eq!(budget.reserve(5, 20_000), Some(20_000));
+        assert_eq!(budget.reserve(5, 40_000), Some(20_000));
+
+        // The window has changed, but the ban without Retry-After lasts a minute
+        budget.on_response(418, None, None, 60_000);
+        assert_eq!(budget.reserve(5, 90_000), Some(30_000));
+        assert_eq!(budget.reserve(5, 120_000), None);
+
+        assert_eq!(metrics.snapshot()["rest_rate_limited"], 2);
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
+use crate::mdc_server::disk_space_guard::CaptureGate;
+use crate::mdc_server::live_status::StatusBoard;
+use crate::mdc_core::order_book::{ExecutionEstimate, Liquidity, OrderBook, Side};
+use crate::mdc_server::output_tiers::BookFrame;
+
+/// Distances from the mid price in basis points, which liquidity requests without `bps` report
+const DEFAULT_LIQUIDITY_BPS: [f64; 4] = [10.0, 25.0, 50.0, 100.0];
+
+/// Sinks, whose health the readiness probe checks, with the counters of their failures
+const SINK_ERROR_COUNTERS: [(&str, &str); 2] = [("postgres", "postgres_write_errors"), ("uploads", "uploads_failed")];
+
+/// The maintained book at a point in time
+#[derive(Debug, Clone)]
+struct BookState {
+    sequence: u64,
+    time: i64,
+    book: Arc<OrderBook>,
+}
+
+/// LatestBookTracker keeps the latest book, served by the REST API
+pub struct LatestBookTracker {
+    input: mpsc::Receiver<Arc<OrderBook>>,
+    latest: watch::Sender<Option<BookState>>,
+}
+
+impl LatestBookTracker {
+    /// Run the LatestBookTracker as an asynchronous task
+    pub async fn run(mut self) {
+        let mut sequence = 0;
+
+        while let Some(book) = self.input.recv().await {
+            sequence += 1;
+            self.latest.send_replace(Some(BookState { sequence, time: Utc::now().timestamp_millis(), book }));
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    instrument: String,
+    default_depth: usize,
+    latest: watch::Receiver<Option<BookState>>,
+    status: Option<StatusBoard>,
+    probes: ProbeSettings,
+}
+
+/// Settings of the liveness and readiness probes
+#[derive(Clone, Default)]
+pub struct ProbeSettings {
+    /// Switch of the capture files. Capture, which is paused, makes the instance not ready
+    pub gate: Option<CaptureGate>,
+    /// Time in milliseconds since the last message of a stream, after which the instance is not ready. 0 disables the check
+    pub max_message_age: u64,
+    /// Sink error counters seen by the previous readiness probe
+    sink_errors: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl ProbeSettings {
+    pub fn new(gate: Option<CaptureGate>, max_message_age: u64) -> Self {
+        Self { gate, max_message_age, sink_errors: Default::default() }
+    }
+}
+
+impl ApiState {
+    /// The latest book of the symbol
+    fn book(&self, symbol: &str) -> Result<BookState, ApiError> {
+        if !symbol.eq_ignore_ascii_case(&self.instrument) {
+            return Err(ApiError(StatusCode::NOT_FOUND, format!("Symbol '{}' is not captured by this instance", symbol)));
+        }
+
+        self.latest
+            .borrow()
+            .clone()
+            .ok_or_else(|| ApiError(StatusCode::SERVICE_UNAVAILABLE, "Order book is not available yet".to_string()))
+    }
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(BTreeMap::from([("error", self.1)]))).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BookQuery {
+    depth: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BookResponse {
+    symbol: String,
+    #[serde(flatten)]
+    frame: BookFrame,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TickerResponse {
+    symbol: String,
+    sequence: u64,
+    time: i64,
+    /// `[price, quantity]` of the best bid
+    bid: Option<[f64; 2]>,
+    /// `[price, quantity]` of the best ask
+    ask: Option<[f64; 2]>,
+    spread: Option<f64>,
+    mid_price: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LiquidityQuery {
+    /// Comma separated distances from the mid price in basis points
+    bps: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LiquidityResponse {
+    symbol: String,
+    sequence: u64,
+    time: i64,
+    mid_price: Option<f64>,
+    bids: Vec<Liquidity>,
+    asks: Vec<Liquidity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlippageQuery {
+    quantity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SlippageResponse {
+    symbol: String,
+    sequence: u64,
+    time: i64,
+    /// A market buy order, which takes the asks
+    buy: Option<ExecutionEstimate>,
+    /// A market sell order, which takes the bids
+    sell: Option<ExecutionEstimate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HealthResponse {
+    healthy: bool,
+    /// Time since the last book update in milliseconds
+    book_age: Option<i64>,
+    /// Connection state of each stream
+    streams: BTreeMap<String, bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamHealth {
+    connected: bool,
+    reconnects: u64,
+    /// Time since the last message of the stream in milliseconds
+    message_age: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProbeResponse {
+    /// Whether the probe passed
+    ok: bool,
+    /// Whether the book was built from a snapshot
+    book_initialized: bool,
+    /// Time since the last book update in milliseconds
+    book_age: Option<i64>,
+    streams: BTreeMap<String, StreamHealth>,
+    /// Health of each sink
+    sinks: BTreeMap<String, bool>,
+}
+
+/// Create the REST API, which serves the latest in-memory state of the book
+///
+/// # Arguments
+/// * `instrument` - The captured instrument. Other symbols are not found
+/// * `input` - Receiver for OrderBook messages
+/// * `default_depth` - Number of top levels per side for book requests, which don't specify it
+/// * `status` - Optional status board, which provides stream states for the health check
+/// * `probes` - Settings of the `/healthz` and `/readyz` probes
+///
+/// # Returns
+/// The tracker of the latest book, which has to be run as a separate task, and the API router
+pub fn rest_api(
+    instrument: &str,
+    input: mpsc::Receiver<Arc<OrderBook>>,
+    default_depth: usize,
+    status: Option<StatusBoard>,
+    probes: ProbeSettings,
+) -> (LatestBookTracker, Router) {
+    let (latest_sender, latest_receiver) = watch::channel(None);
+    let state = ApiState {
+        instrument: instrument.to_string(),
+        default_depth,
+        latest: latest_receiver,
+        status,
+        probes,
+    };
+
+    let router = Router::new()
+        .route("/book/:symbol", get(get_book))
+        .route("/ticker/:symbol", get(get_ticker))
+        .route("/liquidity/:symbol", get(get_liquidity))
+        .route("/slippage/:symbol", get(get_slippage))
+        .route("/health", get(get_health))
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+        .with_state(state);
+
+    (LatestBookTracker { input, latest: latest_sender }, router)
+}
+
+async fn get_book(
+    State(state): State<ApiState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<BookQuery>,
+) -> Result<Json<BookResponse>, ApiError> {
+    let book = state.book(&symbol)?;
+    let depth = query.depth.unwrap_or(state.default_depth);
+
+    Ok(Json(BookResponse {
+        symbol: state.instrument.clone(),
+        frame: BookFrame::new(book.sequence, book.time, &book.book, depth),
+    }))
+}
+
+async fn get_ticker(State(state): State<ApiState>, Path(symbol): Path<String>) -> Result<Json<TickerResponse>, ApiError> {
+    let book = state.book(&symbol)?;
+
+    Ok(Json(TickerResponse {
+        symbol: state.instrument.clone(),
+        sequence: book.sequence,
+        time: book.time,
+        bid: book.book.best_bid().map(|entry| [entry.price.to_f64(), entry.quantity.to_f64()]),
+        ask: book.book.best_ask().map(|entry| [entry.price.to_f64(), entry.quantity.to_f64()]),
+        spread: book.book.spread(),
+        mid_price: book.book.mid_price(),
+    }))
+}
+
+async fn get_liquidity(
+    State(state): State<ApiState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<LiquidityQuery>,
+) -> Result<Json<LiquidityResponse>, ApiError> {
+    let distances = match &query.bps {
+        Some(bps) => bps
+            .split(',')
+            .map(|bps| bps.trim().parse::<f64>().ok().filter(|bps| *bps >= 0.0))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ApiError(StatusCode::BAD_REQUEST, format!("Invalid distances '{}'", bps)))?,
+        None => DEFAULT_LIQUIDITY_BPS.to_vec(),
+    };
+
+    let book = state.book(&symbol)?;
+    let liquidity = |side| distances.iter().filter_map(|bps| book.book.liquidity_within(side, *bps)).collect();
+
+    Ok(Json(LiquidityResponse {
+        symbol: state.instrument.clone(),
+        sequence: book.sequence,
+        time: book.time,
+        mid_price: book.book.mid_price(),
+        bids: liquidity(Side::Bid),
+        asks: liquidity(Side::Ask),
+    }))
+}
+
+async fn get_slippage(
+    State(state): State<ApiState>,
+    Path(symbol): Path<String>,
+    Query(query): Query<SlippageQuery>,
+) -> Result<Json<SlippageResponse>, ApiError> {
+    if query.quantity <= 0.0 {
+        return Err(ApiError(StatusCode::BAD_REQUEST, format!("Invalid quantity '{}'", query.quantity)));
+    }
+
+    let book = state.book(&symbol)?;
+
+    Ok(Json(SlippageResponse {
+        symbol: state.instrument.clone(),
+        sequence: book.sequence,
+        time: book.time,
+        buy: book.book.estimate_execution(Side::Ask, query.quantity),
+        sell: book.book.estimate_execution(Side::Bid, query.quantity),
+    }))
+}
+
+/// Healthy if the book is available and all streams are connected
+async fn get_health(State(state): State<ApiState>) -> (StatusCode, Json<HealthResponse>) {
+    let book_age = state.latest.borrow().as_ref().map(|book| Utc::now().timestamp_millis() - book.time);
+    let streams: BTreeMap<String, bool> = state.status
+        .as_ref()
+        .map(|board| board.snapshot().streams.into_iter().map(|(name, stream)| (name, stream.connected)).collect())
+        .unwrap_or_default();
+
+    let healthy = book_age.is_some() && streams.values().all(|connected| *connected);
+    let code = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (code, Json(HealthResponse { healthy, book_age, streams }))
+}
+
+/// Report the state of the streams, the book and the sinks
+///
+/// # Arguments
+/// * `state` - State of the API
+/// * `check_sinks` - Whether to check the sink error counters, which advances the counters seen by the readiness probe
+fn probe(state: &ApiState, check_sinks: bool) -> ProbeResponse {
+    let now = Utc::now().timestamp_millis();
+    let book_age = state.latest.borrow().as_ref().map(|book| now - book.time);
+    let status = state.status.as_ref().map(StatusBoard::snapshot);
+
+    let streams = status
+        .as_ref()
+        .map(|status| {
+            status.streams.iter().map(|(name, stream)| {
+                let message_age = stream.last_message_at.map(|time| status.generated_at - time);
+                (name.clone(), StreamHealth { connected: stream.connected, reconnects: stream.reconnects, message_age })
+            }).collect()
+        })
+        .unwrap_or_default();
+
+    let mut sinks = BTreeMap::new();
+    if let Some(gate) = &state.probes.gate {
+        sinks.insert("capture".to_string(), !gate.is_paused());
+    }
+    if let (true, Some(status)) = (check_sinks, &status) {
+        let mut seen = state.probes.sink_errors.lock().expect("Probe lock is poisoned");
+        for (sink, counter) in SINK_ERROR_COUNTERS {
+            if let Some(errors) = status.counters.get(counter) {
+                // A sink is unhealthy if it failed since the previous probe
+                let previous = seen.insert(counter.to_string(), *errors).unwrap_or(*errors);
+                sinks.insert(sink.to_string(), *errors == previous);
+            }
+        }
+    }
+
+    ProbeResponse { ok: true, book_initialized: book_age.is_some(), book_age, streams, sinks }
+}
+
+/// Liveness probe. Passes while the instance serves requests, the state is only reported
+///
+/// Disconnected streams are reconnected by the instance itself, so they don't fail the probe
+async fn get_healthz(State(state): State<ApiState>) -> Json<ProbeResponse> {
+    Json(probe(&state, false))
+}
+
+/// Readiness probe. Passes if the book is initialized, all streams are connected and received a message recently,
+/// and all sinks are healthy
+async fn get_readyz(State(state): State<ApiState>) -> (StatusCode, Json<ProbeResponse>) {
+    let mut response = probe(&state, true);
+    let max_message_age = state.probes.max_message_age as i64;
+
+    response.ok = response.book_initialized
+        && response.sinks.values().all(|healthy| *healthy)
+        && response.streams.values().all(|stream| {
+            stream.connected && (max_message_age == 0 || stream.message_age.is_some_and(|age| age <= max_message_age))
+        });
+    let code = if response.ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (code, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_server::metrics::Metrics;
+    use crate::mdc_core::models::{BboChange, DepthEntry, DepthSnapshot, MarketEvent};
+
+    #[tokio::test]
+    async fn test_book_ticker_and_health() {
+        let metrics = Metrics::new();
+        let board = StatusBoard::new("binance", "BTCUSDT", metrics.clone());
+        let reporter = board.stream("depth#0".to_string());
+        let (sender, receiver) = mpsc::channel(10);
+        let gate = CaptureGate::new();
+        let (tracker, router) = rest_api("BTCUSDT", receiver, 1, Some(board), ProbeSettings::new(Some(gate.clone()), 60000));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let response = reqwest::get(format!("{}/health", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE.as_u16());
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE.as_u16());
+        let response = reqwest::get(format!("{}/healthz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK.as_u16());
+        let health: ProbeResponse = response.json().await.unwrap();
+        assert!(!health.book_initialized);
+        assert!(!health.streams["depth#0"].connected);
+        let response = reqwest::get(format!("{}/book/BTCUSDT", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE.as_u16());
+
+        sender.send(Arc::new(OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(2.0) },
+            ],
+            asks: vec![DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(3.0) }],
+        }))).await.unwrap();
+        drop(sender);
+        tracker.run().await;
+        reporter.on_connected();
+
+        let book: BookResponse = reqwest::get(format!("{}/book/btcusdt", base)).await.unwrap().json().await.unwrap();
+        assert_eq!(book.frame.sequence, 1);
+        assert_eq!(book.frame.bids, vec![[100.0, 1.0]]);
+
+        let book: BookResponse = reqwest::get(format!("{}/book/BTCUSDT?depth=5", base)).await.unwrap().json().await.unwrap();
+        assert_eq!(book.frame.bids.len(), 2);
+
+        let ticker: TickerResponse = reqwest::get(format!("{}/ticker/BTCUSDT", base)).await.unwrap().json().await.unwrap();
+        assert_eq!(ticker.bid, Some([100.0, 1.0]));
+        assert_eq!(ticker.spread, Some(1.0));
+        assert_eq!(ticker.mid_price, Some(100.5));
+
+        let liquidity: LiquidityResponse = reqwest::get(format!("{}/liquidity/BTCUSDT?bps=50,150", base)).await.unwrap().json().await.unwrap();
+        assert_eq!(liquidity.mid_price, Some(100.5));
+        assert_eq!(liquidity.bids.iter().map(|liquidity| liquidity.quantity).collect::<Vec<_>>(), vec![1.0, 3.0]);
+        assert_eq!(liquidity.asks[0].notional, 303.0);
+        let response = reqwest::get(format!("{}/liquidity/BTCUSDT?bps=10,x", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST.as_u16());
+
+        let slippage: SlippageResponse = reqwest::get(format!("{}/slippage/BTCUSDT?quantity=2", base)).await.unwrap().json().await.unwrap();
+        assert_eq!(slippage.buy.unwrap().average_price, 101.0);
+        assert_eq!(slippage.sell.unwrap().worst_price, 99.0);
+        let response = reqwest::get(format!("{}/slippage/BTCUSDT", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST.as_u16());
+
+        let response = reqwest::get(format!("{}/ticker/ETHUSDT", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND.as_u16());
+
+        let response = reqwest::get(format!("{}/health", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK.as_u16());
+        let health: HealthResponse = response.json().await.unwrap();
+        assert_eq!(health.streams, BTreeMap::from([("depth#0".to_string(), true)]));
+
+        // Connected without any message yet, so the stream is considered stale
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE.as_u16());
+        reporter.on_event(&MarketEvent::BboChange(BboChange { update_id: 1, best_bid: None, best_ask: None }));
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK.as_u16());
+        let ready: ProbeResponse = response.json().await.unwrap();
+        assert!(ready.book_initialized);
+        assert_eq!(ready.sinks, BTreeMap::from([("capture".to_string(), true)]));
+
+        // Sinks fail the probe while capture is paused or a sink failed since the previous probe
+        gate.set_held(true);
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE.as_u16());
+        gate.set_held(false);
+        let errors = metrics.counter("postgres_write_errors");
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK.as_u16());
+        errors.increment(1);
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE.as_u16());
+        let ready: ProbeResponse = response.json().await.unwrap();
+        assert!(!ready.sinks["postgres"]);
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK.as_u16());
+    }
+}
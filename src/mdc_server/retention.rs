@@ -0,0 +1,258 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::time::{interval, MissedTickBehavior};
+use crate::mdc_server::capture_uploader::{FileSession, UPLOAD_MARKER_DIR};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+fn default_retention_interval() -> u64 {
+    60000
+}
+
+/// Retention of the capture files of finished sessions and of rotated logs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Time in milliseconds since the last modification, after which a file is deleted
+    #[serde(default)]
+    pub max_age: Option<u64>,
+    /// Total size in MB of the retained files. The oldest files are deleted beyond it
+    #[serde(default)]
+    pub max_total_size_mb: Option<u64>,
+    /// Interval between the enforcements in milliseconds
+    #[serde(default = "default_retention_interval")]
+    pub interval: u64,
+    /// Log file, whose rotated copies (`<file>.1`, `<file>.2.gz`, ...) are retained along with the capture files
+    #[serde(default)]
+    pub rotated_logs: Option<String>,
+}
+
+/// A file, which may be deleted to enforce a limit
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct RetainedFile {
+    pub modified: SystemTime,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl RetainedFile {
+    fn from_entry(entry: &fs::DirEntry) -> Option<Self> {
+        let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+        Some(Self {
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            path: entry.path(),
+            size: metadata.len(),
+        })
+    }
+
+    /// Delete the file along with its upload marker
+    pub fn delete(&self) -> Result<()> {
+        fs::remove_file(&self.path).with_context(|| format!("Failed to delete {:?}", self.path))?;
+        if let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) {
+            let _ = fs::remove_file(dir.join(UPLOAD_MARKER_DIR).join(name));
+        }
+        Ok(())
+    }
+}
+
+/// Capture files of the finished sessions of the instrument, oldest first. The files of the current session are skipped
+///
+/// # Arguments
+/// * `uploaded_only` - Skip the files, which the CaptureUploader hasn't uploaded yet
+pub(crate) fn finished_session_files(capture_dir: &Path, instrument: &str, session: &str, uploaded_only: bool) -> Result<Vec<RetainedFile>> {
+    let marker_dir = capture_dir.join(UPLOAD_MARKER_DIR);
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(capture_dir).with_context(|| format!("Failed to read capture directory: {:?}", capture_dir))?.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let finished = FileSession::parse(&file_name)
+            .is_some_and(|file| file.instrument == instrument && file.name != session);
+        if !finished || (uploaded_only && !marker_dir.join(&file_name).exists()) {
+            continue;
+        }
+
+        files.extend(RetainedFile::from_entry(&entry));
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Rotated copies of the log file (`<file>.<suffix>`), oldest first. The log file itself is skipped
+fn rotated_log_files(log_file: &Path) -> Result<Vec<RetainedFile>> {
+    let dir = match log_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", log_file.file_name().unwrap_or_default().to_string_lossy());
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read log directory: {:?}", dir))?.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            files.extend(RetainedFile::from_entry(&entry));
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// RetentionJanitor periodically deletes the capture files of finished sessions of an instrument
+/// and rotated logs, which are older than the maximum age or exceed the maximum total size
+///
+/// The oldest files are deleted first. With upload enabled, files are deleted only once they are uploaded
+pub struct RetentionJanitor {
+    retention: Retention,
+    capture_dir: PathBuf,
+    instrument: String,
+    session: String,
+    uploaded_only: bool,
+    deleted_files: Counter,
+    deleted_bytes: Counter,
+}
+
+impl RetentionJanitor {
+    /// Create a new RetentionJanitor
+    ///
+    /// # Arguments
+    /// * `retention` - Limits and interval of the enforcement
+    /// * `capture_dir` - Directory of the capture files
+    /// * `instrument` - Instrument, whose files are retained
+    /// * `session` - Name of the current session, whose files are never deleted
+    /// * `uploaded_only` - Delete only the capture files, which the CaptureUploader has uploaded
+    /// * `metrics` - Registry of the `retention_deleted_files` and `retention_deleted_bytes` counters
+    pub fn new(
+        retention: Retention,
+        capture_dir: PathBuf,
+        instrument: &str,
+        session: String,
+        uploaded_only: bool,
+        metrics: &Metrics,
+    ) -> Self {
+        Self {
+            retention,
+            capture_dir,
+            instrument: instrument.to_string(),
+            session,
+            uploaded_only,
+            deleted_files: metrics.counter("retention_deleted_files"),
+            deleted_bytes: metrics.counter("retention_deleted_bytes"),
+        }
+    }
+
+    /// Delete the files beyond the maximum age and the maximum total size
+    ///
+    /// # Returns
+    /// Number of bytes deleted
+    pub fn enforce(&self, now: SystemTime) -> Result<u64> {
+        let mut files = finished_session_files(&self.capture_dir, &self.instrument, &self.session, self.uploaded_only)?;
+        if let Some(log_file) = &self.retention.rotated_logs {
+            files.extend(rotated_log_files(Path::new(log_file))?);
+            files.sort();
+        }
+
+        let max_age = self.retention.max_age.map(Duration::from_millis);
+        let max_total_size = self.retention.max_total_size_mb.map(|size| size * 1024 * 1024);
+        let mut total_size: u64 = files.iter().map(|file| file.size).sum();
+        let mut deleted = 0;
+
+        for file in files {
+            let expired = max_age.is_some_and(|max_age| now.duration_since(file.modified).unwrap_or_default() >= max_age);
+            let oversized = max_total_size.is_some_and(|max_total_size| total_size > max_total_size);
+            if !expired && !oversized {
+                break;
+            }
+
+            if let Err(e) = file.delete() {
+                tracing::warn!("Failed to enforce retention. Details: '{:#}'", e);
+                continue;
+            }
+
+            tracing::info!("Deleted {:?} ('{}' bytes) by retention", file.path, file.size);
+            total_size -= file.size;
+            deleted += file.size;
+            self.deleted_files.increment(1);
+            self.deleted_bytes.increment(file.size);
+        }
+
+        Ok(deleted)
+    }
+
+    /// Run the RetentionJanitor as an asynchronous task, enforcing the retention every interval
+    pub async fn run(self) {
+        let mut ticker = interval(Duration::from_millis(self.retention.interval.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.enforce(SystemTime::now()) {
+                tracing::error!("Failed to enforce retention. Details: '{:#}'", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_file(path: &Path, size: u64, modified_secs: u64) {
+        let file = fs::File::create(path).unwrap();
+        file.set_len(size).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified_secs)).unwrap();
+    }
+
+    #[test]
+    fn test_enforces_age_and_size() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_retention", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(UPLOAD_MARKER_DIR)).unwrap();
+
+        create_file(&dir.join("BTCUSDT_20240101_000000.tape"), 1 << 20, 100);
+        create_file(&dir.join("BTCUSDT_20240102_000000.tape"), 1 << 20, 200);
+        create_file(&dir.join("BTCUSDT_20240103_000000.tape"), 1 << 20, 300);
+        create_file(&dir.join("BTCUSDT_20240104_000000.tape"), 1 << 20, 400);
+        create_file(&dir.join("ETHUSDT_20240101_000000.tape"), 1 << 20, 100);
+        create_file(&dir.join("mdc.log"), 1 << 20, 100);
+        create_file(&dir.join("mdc.log.1.gz"), 1 << 20, 250);
+        fs::write(dir.join(UPLOAD_MARKER_DIR).join("BTCUSDT_20240101_000000.tape"), "").unwrap();
+
+        let metrics = Metrics::new();
+        let janitor = |retention: Retention| RetentionJanitor::new(
+            retention,
+            dir.clone(),
+            "BTCUSDT",
+            "BTCUSDT_20240104_000000".to_string(),
+            false,
+            &metrics,
+        );
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+
+        // Files modified at 100 are older than 850 s
+        let by_age = Retention { max_age: Some(850_000), max_total_size_mb: None, interval: 1000, rotated_logs: None };
+        assert_eq!(janitor(by_age).enforce(now).unwrap(), 1 << 20);
+        assert!(!dir.join("BTCUSDT_20240101_000000.tape").exists());
+        assert!(!dir.join(UPLOAD_MARKER_DIR).join("BTCUSDT_20240101_000000.tape").exists());
+        assert!(dir.join("ETHUSDT_20240101_000000.tape").exists());
+
+        // 2 finished sessions and the rotated log exceed 2 MB, so the oldest file goes
+        let log_file = dir.join("mdc.log").to_string_lossy().to_string();
+        let by_size = Retention { max_age: None, max_total_size_mb: Some(2), interval: 1000, rotated_logs: Some(log_file) };
+        assert_eq!(janitor(by_size.clone()).enforce(now).unwrap(), 1 << 20);
+        assert!(!dir.join("BTCUSDT_20240102_000000.tape").exists());
+        assert!(dir.join("mdc.log.1.gz").exists());
+        assert_eq!(janitor(by_size).enforce(now).unwrap(), 0);
+
+        // The current session and the active log are kept
+        let everything = Retention { max_age: Some(0), max_total_size_mb: None, interval: 1000, rotated_logs: Some(dir.join("mdc.log").to_string_lossy().to_string()) };
+        assert_eq!(janitor(everything).enforce(now).unwrap(), 2 << 20);
+        assert!(dir.join("BTCUSDT_20240104_000000.tape").exists());
+        assert!(dir.join("mdc.log").exists());
+        assert!(!dir.join("mdc.log.1.gz").exists());
+        assert_eq!(metrics.snapshot()["retention_deleted_files"], 4);
+        assert_eq!(metrics.snapshot()["retention_deleted_bytes"], 4 << 20);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,475 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use crate::mdc_core::models::{BboChange, MarketEvent, TradeEvent};
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::disk_space_guard::CaptureGate;
+
+/// Open/high/low/close of a price over a rollup interval
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Ohlc {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl Ohlc {
+    fn new(price: f64) -> Self {
+        Self { open: price, high: price, low: price, close: price }
+    }
+
+    fn update(&mut self, price: f64) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+    }
+}
+
+/// Aggregates of a single rollup interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rollup {
+    pub interval: u64,
+    pub start: i64,
+    pub end: i64,
+    pub best_bid: Option<Ohlc>,
+    pub best_ask: Option<Ohlc>,
+    pub trade_count: u64,
+    pub volume: f64,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub vwap: Option<f64>,
+    pub imbalance: Option<f64>,
+}
+
+/// Rollup of a single interval, which is still being accumulated
+#[derive(Debug, Clone)]
+struct RollupBucket {
+    start: i64,
+    has_events: bool,
+    best_bid: Option<Ohlc>,
+    best_ask: Option<Ohlc>,
+    trade_count: u64,
+    volume: f64,
+    buy_volume: f64,
+    sell_volume: f64,
+    notional: f64,
+    imbalance_sum: f64,
+    imbalance_samples: u64,
+}
+
+impl RollupBucket {
+    /// Create a bucket, which starts where the previous one has ended.
+    /// Best bid/ask open at the previous close, so quiet intervals still have prices
+    fn new(start: i64, previous: Option<&RollupBucket>) -> Self {
+        let carry = |ohlc: Option<Ohlc>| ohlc.map(|ohlc| Ohlc::new(ohlc.close));
+
+        Self {
+            start,
+            has_events: false,
+            best_bid: previous.and_then(|bucket| carry(bucket.best_bid)),
+            best_ask: previous.and_then(|bucket| carry(bucket.best_ask)),
+            trade_count: 0,
+            volume: 0.0,
+            buy_volume: 0.0,
+            sell_volume: 0.0,
+            notional: 0.0,
+            imbalance_sum: 0.0,
+            imbalance_samples: 0,
+        }
+    }
+
+    fn to_rollup(&self, interval: u64) -> Rollup {
+        Rollup {
+            interval,
+            start: self.start,
+            end: self.start + interval as i64,
+            best_bid: self.best_bid,
+            best_ask: self.best_ask,
+            trade_count: self.trade_count,
+            volume: self.volume,
+            buy_volume: self.buy_volume,
+            sell_volume: self.sell_volume,
+            vwap: (self.volume > 0.0).then(|| self.notional / self.volume),
+            imbalance: (self.imbalance_samples > 0).then(|| self.imbalance_sum / self.imbalance_samples as f64),
+        }
+    }
+}
+
+/// Maintains rollups for a set of intervals. Time is given explicitly (milliseconds since epoch),
+/// buckets are aligned to multiples of their interval
+pub struct RollupAggregator {
+    intervals: Vec<u64>,
+    imbalance_depth: usize,
+    buckets: Vec<Option<RollupBucket>>,
+}
+
+impl RollupAggregator {
+    /// Create a new RollupAggregator
+    ///
+    /// # Arguments
+    /// * `intervals` - Rollup intervals in milliseconds
+    /// * `imbalance_depth` - Number of top levels per side used to compute book imbalance
+    pub fn new(intervals: Vec<u64>, imbalance_depth: usize) -> Self {
+        let buckets = vec![None; intervals.len()];
+        Self { intervals, imbalance_depth, buckets }
+    }
+
+    /// Returns the buckets, which are current at the given time, creating them if needed
+    fn current_buckets(&mut self, now: i64) -> impl Iterator<Item = &mut RollupBucket> {
+        for (interval, bucket) in self.intervals.iter().zip(self.buckets.iter_mut()) {
+            if bucket.is_none() {
+                let start = now - now.rem_euclid(*interval as i64);
+                *bucket = Some(RollupBucket::new(start, None));
+            }
+        }
+
+        self.buckets.iter_mut().flatten()
+    }
+
+    /// Account a top of book change
+    pub fn on_bbo(&mut self, now: i64, bbo: &BboChange) {
+        for bucket in self.current_buckets(now) {
+            bucket.has_events = true;
+
+            if let Some(bid) = &bbo.best_bid {
+                match &mut bucket.best_bid {
+                    Some(ohlc) => ohlc.update(bid.price.to_f64()),
+                    None => bucket.best_bid = Some(Ohlc::new(bid.price.to_f64())),
+                }
+            }
+
+            if let Some(ask) = &bbo.best_ask {
+                match &mut bucket.best_ask {
+                    Some(ohlc) => ohlc.update(ask.price.to_f64()),
+                    None => bucket.best_ask = Some(Ohlc::new(ask.price.to_f64())),
+                }
+            }
+        }
+    }
+
+    /// Account a trade. Trades, where the buyer is the market maker, are sell-initiated
+    pub fn on_trade(&mut self, now: i64, trade: &TradeEvent) {
+        for bucket in self.current_buckets(now) {
+            bucket.has_events = true;
+            bucket.trade_count += 1;
+            bucket.volume += trade.quantity;
+            bucket.notional += trade.price * trade.quantity;
+
+            if trade.is_market_maker {
+                bucket.sell_volume += trade.quantity;
+            } else {
+                bucket.buy_volume += trade.quantity;
+            }
+        }
+    }
+
+    /// Account the book imbalance `(bid qty - ask qty) / (bid qty + ask qty)` over the top levels of the book
+    pub fn on_book(&mut self, now: i64, book: &OrderBook) {
+        let bid_quantity: f64 = book.bids.iter().take(self.imbalance_depth).map(|level| level.quantity.to_f64()).sum();
+        let ask_quantity: f64 = book.asks.iter().take(self.imbalance_depth).map(|level| level.quantity.to_f64()).sum();
+        let total = bid_quantity + ask_quantity;
+
+        if total <= 0.0 {
+            return;
+        }
+
+        let imbalance = (bid_quantity - ask_quantity) / total;
+
+        for bucket in self.current_buckets(now) {
+            bucket.has_events = true;
+            bucket.imbalance_sum += imbalance;
+            bucket.imbalance_samples += 1;
+        }
+    }
+
+    /// Close all buckets, which have ended by the given time
+    ///
+    /// # Returns
+    /// Rollups of the closed buckets. Intervals without any events are not reported,
+    /// prices are carried over to the next reported interval instead
+    pub fn flush(&mut self, now: i64) -> Vec<Rollup> {
+        let mut rollups = Vec::new();
+
+        for (interval, bucket) in self.intervals.iter().zip(self.buckets.iter_mut()) {
+            let Some(current) = bucket.as_ref() else {
+                continue;
+            };
+
+            if now < current.start + *interval as i64 {
+                continue;
+            }
+
+            if current.has_events {
+                rollups.push(current.to_rollup(*interval));
+            }
+
+            let start = now - now.rem_euclid(*interval as i64);
+            *bucket = Some(RollupBucket::new(start, Some(current)));
+        }
+
+        rollups
+    }
+}
+
+/// Destination of closed rollups
+pub trait RollupSink: Send {
+    fn write(&mut self, rollup: &Rollup) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// Writes rollups into a JSON lines file, one rollup per line
+pub struct JsonLinesRollupSink {
+    writer: BufWriter<File>,
+}
+
+impl JsonLinesRollupSink {
+    /// Open the file for appending, creating it if needed
+    pub fn open(path: &PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open rollup file: {:?}", path))?;
+
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+}
+
+impl RollupSink for JsonLinesRollupSink {
+    fn write(&mut self, rollup: &Rollup) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, rollup)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// RollupEngine maintains time-series aggregates (best bid/ask OHLC, traded volume, book imbalance averages)
+/// over configured intervals and writes them to a RollupSink, so consumers don't need to process raw tick data
+pub struct RollupEngine {
+    aggregator: RollupAggregator,
+    sink: Box<dyn RollupSink>,
+    gate: CaptureGate,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    bbo_channel: mpsc::Receiver<MarketEvent>,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+}
+
+impl RollupEngine {
+    /// Create a new RollupEngine
+    ///
+    /// # Arguments
+    /// * `aggregator` - Aggregator, configured with rollup intervals
+    /// * `sink` - Destination of closed rollups
+    /// * `gate` - Switch, which pauses writing. Rollups closed while writing is paused are discarded
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    /// * `bbo_channel` - Receiver for MarketEvent messages containing BboChanges
+    /// * `book_channel` - Receiver for OrderBook messages
+    pub fn new(
+        aggregator: RollupAggregator,
+        sink: Box<dyn RollupSink>,
+        gate: CaptureGate,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+        bbo_channel: mpsc::Receiver<MarketEvent>,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    ) -> Self {
+        Self {
+            aggregator,
+            sink,
+            gate,
+            trade_channel,
+            bbo_channel,
+            book_channel,
+        }
+    }
+
+    fn flush(&mut self, now: i64) {
+        let rollups = self.aggregator.flush(now);
+        if rollups.is_empty() {
+            return;
+        }
+
+        if self.gate.is_paused() {
+            tracing::debug!("Capture is paused. Discarding '{}' rollups", rollups.len());
+            return;
+        }
+
+        for rollup in &rollups {
+            tracing::debug!("Closed rollup: '{:?}'", rollup);
+            if let Err(e) = self.sink.write(rollup) {
+                tracing::error!("Failed to write rollup. Details: '{}'", e);
+            }
+        }
+
+        if let Err(e) = self.sink.flush() {
+            tracing::error!("Failed to flush rollup sink. Details: '{}'", e);
+        }
+    }
+
+    /// Run the RollupEngine as an asynchronous task
+    ///
+    /// This method will continuously aggregate events from all channels until all of them are closed
+    /// Ended intervals are checked every 100 ms
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(100));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                Some(event) = self.trade_channel.recv() => {
+                    match event {
+                        MarketEvent::TradeEvent(trade) => self.aggregator.on_trade(Utc::now().timestamp_millis(), &trade),
+                        _ => tracing::warn!("Unexpected event in rollup trade channel: '{}'", event),
+                    }
+                }
+                Some(event) = self.bbo_channel.recv() => {
+                    match event {
+                        MarketEvent::BboChange(bbo) => self.aggregator.on_bbo(Utc::now().timestamp_millis(), &bbo),
+                        _ => tracing::warn!("Unexpected event in rollup bbo channel: '{}'", event),
+                    }
+                }
+                Some(book) = self.book_channel.recv() => {
+                    self.aggregator.on_book(Utc::now().timestamp_millis(), &book);
+                }
+                _ = ticker.tick() => {
+                    self.flush(Utc::now().timestamp_millis());
+                }
+                else => break,
+            }
+
+            if self.trade_channel.is_closed() && self.bbo_channel.is_closed() && self.book_channel.is_closed()
+                && self.trade_channel.is_empty() && self.bbo_channel.is_empty() && self.book_channel.is_empty() {
+                break;
+            }
+        }
+
+        // Report the intervals, which were in progress when the pipeline stopped
+        self.flush(i64::MAX / 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    fn make_trade(price: f64, quantity: f64, is_market_maker: bool) -> TradeEvent {
+        TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 0,
+            symbol: "BTCUSDT".to_string(),
+            trade_id: 1,
+            price,
+            quantity,
+            trade_time: 0,
+            is_market_maker,
+            ignore: true,
+            received: None,
+        }
+    }
+
+    fn make_bbo(bid: f64, ask: f64) -> BboChange {
+        BboChange {
+            update_id: 1,
+            best_bid: Some(DepthEntry { price: FixedPoint::from(bid), quantity: FixedPoint::from(1.0) }),
+            best_ask: Some(DepthEntry { price: FixedPoint::from(ask), quantity: FixedPoint::from(1.0) }),
+        }
+    }
+
+    #[test]
+    fn test_bbo_ohlc_and_trade_volume() {
+        let mut aggregator = RollupAggregator::new(vec![1000], 1);
+
+        aggregator.on_bbo(10_100, &make_bbo(100.0, 101.0));
+        aggregator.on_bbo(10_200, &make_bbo(102.0, 103.0));
+        aggregator.on_bbo(10_300, &make_bbo(99.0, 100.0));
+        aggregator.on_trade(10_400, &make_trade(100.0, 2.0, false));
+        aggregator.on_trade(10_500, &make_trade(101.0, 1.0, true));
+
+        assert!(aggregator.flush(10_999).is_empty());
+
+        let rollups = aggregator.flush(11_000);
+        assert_eq!(rollups.len(), 1);
+
+        let rollup = &rollups[0];
+        assert_eq!(rollup.start, 10_000);
+        assert_eq!(rollup.end, 11_000);
+        assert_eq!(rollup.best_bid, Some(Ohlc { open: 100.0, high: 102.0, low: 99.0, close: 99.0 }));
+        assert_eq!(rollup.best_ask, Some(Ohlc { open: 101.0, high: 103.0, low: 100.0, close: 100.0 }));
+        assert_eq!(rollup.trade_count, 2);
+        assert_eq!(rollup.volume, 3.0);
+        assert_eq!(rollup.buy_volume, 2.0);
+        assert_eq!(rollup.sell_volume, 1.0);
+        assert_eq!(rollup.vwap, Some(301.0 / 3.0));
+    }
+
+    #[test]
+    fn test_book_imbalance_average() {
+        let mut aggregator = RollupAggregator::new(vec![1000], 2);
+        let book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(3.0) },
+                DepthEntry { price: FixedPoint::from(99.0), quantity: FixedPoint::from(3.0) },
+                DepthEntry { price: FixedPoint::from(98.0), quantity: FixedPoint::from(100.0) },
+            ],
+            asks: vec![
+                DepthEntry { price: FixedPoint::from(101.0), quantity: FixedPoint::from(2.0) },
+            ],
+        });
+        let empty_asks_book = OrderBook::new(&DepthSnapshot {
+            last_update_id: 2,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) }],
+            asks: vec![],
+        });
+
+        aggregator.on_book(500, &book);
+        aggregator.on_book(600, &empty_asks_book);
+
+        let rollups = aggregator.flush(1000);
+        assert_eq!(rollups[0].imbalance, Some((0.5 + 1.0) / 2.0));
+    }
+
+    #[test]
+    fn test_multiple_intervals_and_carry_over() {
+        let mut aggregator = RollupAggregator::new(vec![1000, 60_000], 1);
+
+        aggregator.on_bbo(60_500, &make_bbo(100.0, 101.0));
+
+        let rollups = aggregator.flush(61_000);
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].start, 60_000);
+
+        aggregator.on_bbo(61_500, &make_bbo(100.5, 101.5));
+
+        let rollups = aggregator.flush(62_000);
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].start, 61_000);
+        assert_eq!(rollups[0].best_bid, Some(Ohlc { open: 100.0, high: 100.5, low: 100.0, close: 100.5 }));
+
+        // Idle seconds are not reported, the next reported second opens at the last close
+        let rollups = aggregator.flush(120_000);
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].interval, 60_000);
+        assert_eq!(rollups[0].start, 60_000);
+        assert_eq!(rollups[0].best_bid, Some(Ohlc { open: 100.0, high: 100.5, low: 100.0, close: 100.5 }));
+
+        aggregator.on_trade(120_500, &make_trade(100.0, 1.0, false));
+        let rollups = aggregator.flush(121_000);
+        assert_eq!(rollups.len(), 1);
+        assert_eq!(rollups[0].start, 120_000);
+        assert_eq!(rollups[0].best_bid, Some(Ohlc::new(100.5)));
+    }
+}
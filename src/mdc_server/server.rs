@@ -1,129 +1,2021 @@
 use crate::mdc_server::config::Config;
 use crate::mdc_server::market_event_stream::MarketEventStream;
-use crate::mdc_server::models::{DepthUpdate, TradeEvent, PriceUpdate, MarketEvent};
+use crate::mdc_server::combined_stream::{CombinedEventStream, StreamRoute};
+use crate::mdc_core::models::{DepthSnapshot, DepthUpdate, TradeEvent, AggTradeEvent, PriceUpdate, KlineEvent, TickerEvent, MiniTickerEvent, MarkPriceEvent, LiquidationEvent, PartialBookEvent, MarketEvent, MarketEventSource, StreamMessage, frame_decoder};
+use crate::mdc_core::depth_sequencer::BufferLimits;
+use crate::mdc_core::fixed_point::FixedPoint;
 use crate::mdc_server::depth_event_dispatcher::DepthEventDispatcher;
+use crate::mdc_server::trade_event_dispatcher::TradeEventDispatcher;
+use crate::mdc_server::price_event_dispatcher::PriceEventDispatcher;
+use crate::mdc_server::postgres_sink::{PostgresRows, PostgresSettings, PostgresWriter};
 use crate::mdc_server::book_processor::BookProcessor;
+use crate::mdc_server::book_validator::BookValidator;
+use crate::mdc_server::book_checkpoint::{checkpoint_path, BookCheckpoint, CheckpointWriter};
 use crate::mdc_server::market_event_logger::MarketEventLogger;
-use crate::mdc_server::order_book::OrderBook;
-use crate::mdc_server::depth_snapshot_stream::DepthSnapshotStream;
-use tokio::sync::mpsc;
-use anyhow::{Result};
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::depth_snapshot_stream::{DepthSnapshotStream, SnapshotDepthSelector};
+use crate::mdc_server::ws_api::WsApiClient;
+use crate::mdc_server::partial_depth::{PartialBookMessage, PartialSnapshotSampler, SnapshotSource};
+use crate::mdc_server::symbol_metadata::{is_request_failure, SymbolMetadataCache};
+use crate::mdc_server::all_market_prices::{AllMarketPrices, PriceSource};
+use crate::mdc_server::book_metrics_engine::BookMetricsEngine;
+use crate::mdc_server::trade_flow_engine::TradeFlowEngine;
+use crate::mdc_core::trade_flow::TradeFlowWindow;
+use crate::mdc_server::volatility_engine::VolatilityEngine;
+use crate::mdc_server::anomaly_engine::AnomalyEngine;
+use crate::mdc_core::anomaly::{AnomalyDetector, AnomalyEvent};
+use crate::mdc_core::volatility::VolatilityWindow;
+use crate::mdc_server::capture_manifest::CaptureManifest;
+use crate::mdc_server::capture_uploader::CaptureUploader;
+use crate::mdc_server::book_reconstruction::reconstruct_book;
+use crate::mdc_server::tape_inspector::inspect_tape;
+use crate::mdc_server::exchange_simulator::ExchangeSimulator;
+use crate::mdc_server::retention::RetentionJanitor;
+use crate::mdc_server::tape::{TapeRecord, TapeRecorder, TapeWriter};
+use crate::mdc_server::tape_replayer::{FrameDecoders, TapeReplayer};
+use crate::mdc_server::tape_compactor::compact_tapes;
+use crate::mdc_server::exchange_connector::{create_connector, Exchange, ExchangeConnector, StreamKind};
+use crate::mdc_server::okx_connector::{OkxBookMessage, OkxTradeMessage};
+use crate::mdc_server::bitfinex_connector::{BitfinexBookMessage, BitfinexTradeMessage};
+use crate::mdc_server::deribit_connector::{DeribitBookMessage, DeribitTradeMessage};
+#[cfg(feature = "sbe")]
+use crate::mdc_core::sbe::SbeMessage;
+use crate::mdc_server::instance_lock::InstanceLock;
+use crate::mdc_server::fanout::{ChannelPolicy, Fanout};
+use crate::mdc_server::connection_health::{ConnectionHealth, HealthPolicy};
+use crate::mdc_server::live_status::{LiveStatusTracker, StatusBoard};
+use crate::mdc_server::session_report::SessionReporter;
+use crate::mdc_server::admin_socket::{admin_socket_path, query_status, send_command, AdminCommand};
+#[cfg(unix)]
+use crate::mdc_server::admin_socket::{AdminControls, AdminSocket};
+use crate::mdc_server::supervisor::SupervisorHandle;
+#[cfg(unix)]
+use crate::mdc_server::systemd_notifier::SystemdHandle;
+use crate::mdc_server::alerting::{AlertChannel, AlertEngine, AlertRule};
+use crate::mdc_server::event_scripts::{EventScriptStage, EventScripts};
+use crate::mdc_server::book_tui::{BookTui, BookView, BookViewSink, StopOnDrop};
+use crate::mdc_server::disk_space_guard::{CaptureGate, DiskSpaceGuard};
+use crate::mdc_server::metrics::Metrics;
+use crate::mdc_server::session_rotation::RotationGroup;
+use crate::mdc_server::request_weight::RequestWeightBudget;
+use crate::mdc_server::clock_skew_monitor::ClockSkewMonitor;
+use crate::mdc_server::task_supervisor::SupervisedTask;
+use crate::mdc_server::output_tiers::{BookFrame, FrameSink, JsonLinesFrameSink, OutputTiers, SharedMemoryFrameSink};
+use crate::mdc_server::rollup_engine::{JsonLinesRollupSink, RollupAggregator, RollupEngine};
+use crate::mdc_server::grpc_service::GrpcPublisher;
+use crate::mdc_server::grpc_service::proto::market_data_server::MarketDataServer;
+use crate::mdc_server::rest_api::{rest_api, ProbeSettings};
+use crate::mdc_server::dashboard::dashboard;
+use crate::mdc_server::log_context::{pipeline_span, spawn, stream_span};
+use crate::mdc_server::event_feed::EventFeed;
+use crate::mdc_server::wire_format::EventEncoder;
+use crate::mdc_server::level_changes::{LevelChange, LevelChangeWriter};
+use crate::mdc_server::book_deltas::{BookDeltaEvent, BookDeltaWriter};
+use crate::mdc_server::trade_sampler::{JsonLinesSampleSink, TradeSampler, TradeSamplingEngine};
+use crate::mdc_server::pipeline_sink::{PipelineSink, PipelineSinkForwarder};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, watch};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{Instrument, Span};
+use tonic::transport::Server;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 
 pub struct MDCServer {
-    config: Config
+    config: Config,
+    connector: Arc<dyn ExchangeConnector>,
+    metrics: Metrics,
+    supervisor: Option<SupervisorHandle>,
+    /// Reports the state of the pipeline to systemd, if the process runs as a systemd service
+    #[cfg(unix)]
+    systemd: Option<SystemdHandle>,
+    /// Sinks of an embedding program. They are handed over to the pipeline, when it is started
+    sinks: Mutex<Vec<Box<dyn PipelineSink>>>,
+    /// The all-market price stream shared by the pipelines of the process
+    all_market_prices: Option<AllMarketPrices>,
+}
+
+/// Input channels of the processing part of the pipeline (dispatcher, book processor and logger)
+struct PipelineInputs {
+    depth: mpsc::Sender<MarketEvent>,
+    trade: mpsc::Sender<MarketEvent>,
+    price: mpsc::Sender<MarketEvent>,
+    agg_trade: mpsc::Sender<MarketEvent>,
+    /// Events of the streams, which are forwarded without dispatching (klines, tickers, mark prices, liquidations)
+    auxiliary: mpsc::Sender<MarketEvent>,
+    /// Reference snapshots for book validation, if it is enabled
+    validation: Option<mpsc::Sender<MarketEvent>>,
+    /// Requests of a fresh snapshot from the book processor. Taken by the stream, which serves them
+    snapshot_requests: Option<mpsc::Receiver<()>>,
+    /// Sender of snapshot requests, e.g. for the admin socket
+    #[cfg_attr(not(unix), allow(dead_code))]
+    resync: mpsc::Sender<()>,
+    /// Requests to flush the batching sinks, e.g. from the admin socket
+    #[cfg_attr(not(unix), allow(dead_code))]
+    flush_requests: watch::Sender<()>,
+    /// Anomalies for the alert engine, if anomalies are alerted on
+    anomalies: Option<mpsc::Receiver<AnomalyEvent>>,
+}
+
+/// Book checkpointing of a live pipeline
+struct Checkpointing {
+    /// Path of the checkpoint file, which is written periodically
+    path: PathBuf,
+    /// The checkpoint of a previous session, which the sequence is resumed from
+    resume: Option<DepthSnapshot>,
+}
+
+/// Aborts the tasks when dropped
+struct AbortOnDrop(Vec<AbortHandle>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.iter().for_each(AbortHandle::abort);
+    }
+}
+
+/// Writes the SessionReport when dropped, i.e. when the capture stops for any reason
+struct ReportOnDrop(SessionReporter);
+
+impl Drop for ReportOnDrop {
+    fn drop(&mut self) {
+        match self.0.write() {
+            Ok(report) => tracing::info!("Session report written to {:?}. {}", self.0.path(), report),
+            Err(e) => tracing::error!("Failed to write session report. Details: '{:#}'", e),
+        }
+    }
+}
+
+/// Spawn a Fanout, which forwards the input channel to the given number of consumers
+///
+/// A single blocking consumer receives the input channel itself. Consumers with another policy are buffered
+/// by the Fanout, so their own channels only hold a single message
+fn spawn_fanout<T>(
+    name: &str,
+    input: mpsc::Receiver<T>,
+    consumers: usize,
+    policy: ChannelPolicy,
+    metrics: &Metrics,
+    tasks: &mut Vec<JoinHandle<()>>,
+) -> Vec<mpsc::Receiver<T>>
+where T: Clone + Send + 'static,
+{
+    if consumers <= 1 && policy == ChannelPolicy::Block {
+        return vec![input];
+    }
+
+    let capacity = if policy == ChannelPolicy::Block { 100 } else { 1 };
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..consumers).map(|_| mpsc::channel::<T>(capacity)).unzip();
+    let fanout = Fanout::new(name.to_string(), input, senders).with_policy(policy, metrics);
+
+    tasks.push(spawn(async move {
+        fanout.run().await;
+    }));
+
+    receivers
 }
 
 impl MDCServer {
-    pub(crate) fn new(config: Config) -> Self {
-        MDCServer{config}
+    pub fn new(config: Config) -> Self {
+        let connector = create_connector(&config);
+        MDCServer {
+            config,
+            connector,
+            metrics: Metrics::new(),
+            supervisor: None,
+            #[cfg(unix)]
+            systemd: None,
+            sinks: Mutex::new(Vec::new()),
+            all_market_prices: None,
+        }
+    }
+
+    /// Deliver the book and the market events of the pipeline to the sinks
+    pub fn with_sinks(mut self, sinks: Vec<Box<dyn PipelineSink>>) -> Self {
+        self.sinks.get_mut().expect("Pipeline sinks lock is poisoned").extend(sinks);
+        self
+    }
+
+    /// Allow the admin socket to add and remove pipelines of the process
+    pub fn with_supervisor(mut self, supervisor: SupervisorHandle) -> Self {
+        self.supervisor = Some(supervisor);
+        self
+    }
+
+    /// Report the readiness and the heartbeat of the pipeline to systemd
+    #[cfg(unix)]
+    pub fn with_systemd(mut self, systemd: SystemdHandle) -> Self {
+        self.systemd = Some(systemd);
+        self
+    }
+
+    /// Take the prices from the all-market price stream of the process (with `price_source: all_market`)
+    /// instead of connecting to it separately
+    pub fn with_all_market_prices(mut self, all_market_prices: AllMarketPrices) -> Self {
+        self.all_market_prices = Some(all_market_prices);
+        self
     }
 
-    pub(crate) async fn start(&self) -> Result<()> {
+    /// Create the capture manifest for this session, attaching cached symbol metadata to it
+    ///
+    /// # Errors
+    /// If the exchange doesn't list the instrument or it isn't trading. If the exchange can't be asked,
+    /// the capture starts without the metadata
+    async fn create_manifest(&self) -> Result<CaptureManifest> {
+        let metadata_cache = SymbolMetadataCache::new(
+            self.connector.clone(),
+            PathBuf::from(&self.config.symbol_metadata_cache),
+            self.config.symbol_metadata_ttl
+        ).with_transport(self.config.transport());
+
+        let symbol_metadata = match metadata_cache.get(&self.config.instrument).await {
+            Ok(metadata) if !self.connector.is_trading(&metadata) => {
+                return Err(anyhow!("Instrument '{}' isn't trading on '{}'. Status: '{}'", self.config.instrument, self.connector.name(), metadata.status));
+            }
+            Ok(metadata) => {
+                tracing::info!("Loaded symbol metadata: '{}'", metadata);
+                Some(metadata)
+            }
+            Err(e) if is_request_failure(&e) => {
+                tracing::warn!("Symbol metadata for '{}' is not available. Details: '{:#}'", self.config.instrument, e);
+                None
+            }
+            Err(e) => {
+                return Err(e.context(format!("Instrument '{}' isn't listed on '{}'", self.config.instrument, self.connector.name())));
+            }
+        };
+
+        Ok(CaptureManifest::new(&self.config, symbol_metadata))
+    }
+
+    /// Spawn the processing part of the pipeline and return its input channels
+    ///
+    /// # Arguments
+    /// * `tasks` - Handles of the spawned tasks
+    /// * `artifact_stem` - Path prefix of the artifacts written by the pipeline (e.g. rollups)
+    /// * `status` - Optional status board, which receives the current top of book
+    /// * `gate` - Switch, which pauses the file sinks of the pipeline
+    /// * `tick_size` - Tick size of the instrument from exchangeInfo, if known
+    /// * `checkpointing` - Book checkpointing, if it is enabled
+    /// * `scripts` - The compiled event scripts, if any are configured
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_processing(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        artifact_stem: &Path,
+        status: Option<&StatusBoard>,
+        gate: &CaptureGate,
+        tick_size: Option<FixedPoint>,
+        checkpointing: Option<Checkpointing>,
+        scripts: Option<EventScripts>,
+    ) -> PipelineInputs {
         let (depth_update_sender, depth_update_receiver) = mpsc::channel::<MarketEvent>(100);
         let (trade_update_sender, trade_update_receiver) = mpsc::channel::<MarketEvent>(100);
         let (price_update_sender, price_update_receiver) = mpsc::channel::<MarketEvent>(100);
+        let (agg_trade_update_sender, agg_trade_update_receiver) = mpsc::channel::<MarketEvent>(100);
+        let (auxiliary_sender, auxiliary_receiver) = mpsc::channel::<MarketEvent>(100);
         let (dispatch_sender, dispatch_receiver) = mpsc::channel::<MarketEvent>(100);
-        let (book_update_sender, book_update_receiver) = mpsc::channel::<OrderBook>(100);
-        
-        let mut tasks = Vec::new();
-        
-        for i in 0..self.config.connections {
-            let depth_url = format!("{}{}@depth@100ms", 
-                self.config.binance_wss_endpoint, 
-                self.config.instrument.to_lowercase());
-            
-            let mut depth_stream = MarketEventStream::<DepthUpdate>::new(
-                depth_url,
-                depth_update_sender.clone(), 
-                self.config.reconnect_timeout
-            );
+        let (trade_dispatch_sender, trade_dispatch_receiver) = mpsc::channel::<MarketEvent>(100);
+        let (price_dispatch_sender, price_dispatch_receiver) = mpsc::channel::<MarketEvent>(100);
+        let (book_update_sender, book_update_receiver) = mpsc::channel::<Arc<OrderBook>>(100);
+        let (bbo_update_sender, bbo_update_receiver) = mpsc::channel::<MarketEvent>(100);
 
-            tasks.push(tokio::spawn(async move {
-                tracing::info!("Starting depth update stream: '{}'", i);
-                depth_stream.run().await;
-            }));
+        let (snapshot_request_sender, snapshot_request_receiver) = mpsc::channel::<()>(1);
+        let mut dispatcher = DepthEventDispatcher::new(
+            depth_update_receiver,
+            dispatch_sender,
+            self.connector.sequencing_rules(),
+            BufferLimits {
+                max_size: self.config.dispatcher_buffer_size,
+                max_age: self.config.dispatcher_buffer_max_age,
+            },
+            &self.metrics
+        );
+        let (checkpoint_path, resume) = checkpointing
+            .map(|checkpointing| (Some(checkpointing.path), checkpointing.resume))
+            .unwrap_or_default();
+
+        if let Some(checkpoint) = resume {
+            dispatcher = dispatcher.with_checkpoint(checkpoint, snapshot_request_sender.clone());
         }
-        
-        let trade_url = format!("{}{}@trade", 
-            self.config.binance_wss_endpoint, 
-            self.config.instrument.to_lowercase());
-        
-        let mut trade_stream = MarketEventStream::<TradeEvent>::new(
-            trade_url,
-            trade_update_sender.clone(),
-            self.config.reconnect_timeout
+        // Continuity markers are delivered to the sinks along with the auxiliary events
+        let dispatcher = dispatcher.with_markers(auxiliary_sender.clone());
+        let dispatcher = SupervisedTask::new("depth event dispatcher", dispatcher, self.config.task_restart_policy, &self.metrics)
+            .with_resync(snapshot_request_sender.clone());
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting depth event dispatcher");
+            dispatcher.run().await;
+        }));
+
+        let event_feed_enabled = self.config.event_feed_listen.is_some();
+        let mut depth_receivers = spawn_fanout(
+            "depth",
+            dispatch_receiver,
+            1 + event_feed_enabled as usize,
+            ChannelPolicy::Block,
+            &self.metrics,
+            tasks
         );
 
-        tasks.push(tokio::spawn(async move {
-            tracing::info!("Starting trade update stream");
-            trade_stream.run().await;
+        let mut book_processor = BookProcessor::new(
+            depth_receivers.remove(0),
+            book_update_sender,
+            bbo_update_sender
+        ).with_published_depth(self.config.published_depth())
+        .with_crossed_book_check(snapshot_request_sender.clone(), &self.metrics)
+        .with_checksum_check(snapshot_request_sender.clone(), &self.metrics)
+        .with_held_update_check(self.connector.sequencing_rules(), snapshot_request_sender.clone(), &self.metrics)
+        .with_markers(auxiliary_sender.clone());
+
+        if let Some(tick_size) = tick_size {
+            book_processor = book_processor.with_tick_size(tick_size, &self.metrics);
+        }
+
+        // A book seeded from the partial depth stream is pruned to the seeded levels as well
+        if let Some(kept_depth) = self.config.kept_depth() {
+            let kept_depth = kept_depth as usize;
+            if self.config.book_max_depth > 0 && kept_depth > self.config.book_max_depth {
+                tracing::warn!(
+                    "'book_max_depth' of '{}' is below the levels covered by the checksums. Keeping '{}' levels",
+                    self.config.book_max_depth,
+                    kept_depth
+                );
+            }
+            if kept_depth < self.config.output_depth {
+                tracing::warn!(
+                    "Book depth is limited to '{}' levels, the outputs will carry fewer than the configured '{}'",
+                    kept_depth,
+                    self.config.output_depth
+                );
+            }
+            book_processor = book_processor.with_max_depth(kept_depth);
+        }
+
+        if self.config.book_price_band > 0.0 {
+            book_processor = book_processor.with_price_band(self.config.book_price_band);
+        }
+
+        if let Some(checkpoint_path) = checkpoint_path {
+            let (checkpoint_sender, checkpoint_receiver) = mpsc::channel(1);
+            let checkpoint_writer = CheckpointWriter::new(checkpoint_path.clone(), checkpoint_receiver, &self.metrics);
+            book_processor = book_processor.with_checkpoints(checkpoint_sender, self.config.checkpoint_interval);
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting checkpoint writer: '{:?}'", checkpoint_path);
+                checkpoint_writer.run().await;
+            }));
+        }
+
+        let mut validation_sender = None;
+        if self.config.book_validation_interval > 0 && !self.provides_rest_snapshots() {
+            tracing::warn!("Exchange '{}' doesn't provide REST snapshots. Book validation is disabled", self.connector.name());
+        } else if self.config.book_validation_interval > 0 {
+            let (sender, receiver) = mpsc::channel::<MarketEvent>(10);
+            let validator = BookValidator::new(self.config.book_validation_resync, &self.metrics);
+            book_processor = book_processor.with_validation(validator, receiver, &self.metrics);
+            validation_sender = Some(sender);
+        }
+
+        if self.config.level_changes {
+            let level_change_path = PathBuf::from(format!("{}.levels.jsonl", artifact_stem.to_string_lossy()));
+            let (level_change_sender, level_change_receiver) = mpsc::channel::<Vec<LevelChange>>(100);
+
+            match LevelChangeWriter::open(
+                &level_change_path,
+                level_change_receiver,
+                self.config.level_change_filter.clone(),
+                gate.clone(),
+                &self.metrics
+            ) {
+                Ok(level_change_writer) => {
+                    book_processor = book_processor.with_level_changes(level_change_sender);
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting level change writer: '{:?}'", level_change_path);
+                        level_change_writer.run().await;
+                    }));
+                }
+                Err(e) => tracing::error!("Level changes are disabled. Details: '{:#}'", e),
+            }
+        }
+
+        if self.config.book_deltas {
+            let book_delta_path = PathBuf::from(format!("{}.deltas.jsonl", artifact_stem.to_string_lossy()));
+            let (book_delta_sender, book_delta_receiver) = mpsc::channel::<BookDeltaEvent>(100);
+
+            match BookDeltaWriter::open(&book_delta_path, book_delta_receiver, gate.clone(), &self.metrics) {
+                Ok(book_delta_writer) => {
+                    book_processor = book_processor.with_book_deltas(book_delta_sender, self.config.book_delta_snapshot_interval);
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting book delta writer: '{:?}'", book_delta_path);
+                        book_delta_writer.run().await;
+                    }));
+                }
+                Err(e) => tracing::error!("Book deltas are disabled. Details: '{:#}'", e),
+            }
+        }
+
+        let book_processor = SupervisedTask::new("book processor", book_processor, self.config.task_restart_policy, &self.metrics)
+            .with_resync(snapshot_request_sender.clone());
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting book processor");
+            book_processor.run().await;
+        }));
+
+        let sinks = std::mem::take(&mut *self.sinks.lock().expect("Pipeline sinks lock is poisoned"));
+        let sinks_enabled = !sinks.is_empty();
+        let rollups_enabled = !self.config.rollup_intervals.is_empty();
+        let book_metrics_enabled = self.config.book_metrics;
+        let trade_flow_enabled = self.config.trade_flow_window > 0;
+        let volatility_enabled = self.config.volatility_interval > 0;
+        let anomalies_enabled = self.config.anomaly_detection.is_some();
+        let sampling_enabled = self.config.trade_sampling;
+        let grpc_enabled = self.config.grpc_listen.is_some();
+        let dashboard_enabled = self.config.rest_listen.is_some() && self.config.dashboard;
+        let postgres_enabled = self.config.postgres_url.is_some();
+        let trade_dispatcher = TradeEventDispatcher::new(trade_update_receiver, trade_dispatch_sender, "trade", &self.metrics);
+        tasks.push(spawn(async move {
+            tracing::info!("Starting trade event dispatcher");
+            trade_dispatcher.run().await;
         }));
-        
-        let price_url = format!(
-            "{}{}@bookTicker", 
-            self.config.binance_wss_endpoint, 
-            self.config.instrument.to_lowercase()
+
+        // The scripts run before the fanouts, so every consumer receives the filtered and modified events
+        let (trade_dispatch_receiver, price_dispatch_receiver, auxiliary_receiver) = match scripts {
+            Some(scripts) => {
+                let (trade_sender, trade_receiver) = mpsc::channel::<MarketEvent>(100);
+                let (price_sender, price_receiver) = mpsc::channel::<MarketEvent>(100);
+                let (scripted_auxiliary_sender, scripted_auxiliary_receiver) = mpsc::channel::<MarketEvent>(100);
+                let script_stage = EventScriptStage::new(
+                    scripts,
+                    (trade_dispatch_receiver, trade_sender),
+                    (price_dispatch_receiver, price_sender),
+                    (auxiliary_receiver, scripted_auxiliary_sender),
+                    &self.metrics
+                );
+
+                tasks.push(spawn(async move {
+                    tracing::info!("Starting event script stage");
+                    script_stage.run().await;
+                }));
+
+                (trade_receiver, price_receiver, scripted_auxiliary_receiver)
+            }
+            None => (trade_dispatch_receiver, price_dispatch_receiver, auxiliary_receiver),
+        };
+
+        let mut trade_receivers = spawn_fanout(
+            "trade",
+            trade_dispatch_receiver,
+            1 + rollups_enabled as usize + sampling_enabled as usize + grpc_enabled as usize + postgres_enabled as usize
+                + event_feed_enabled as usize + sinks_enabled as usize + trade_flow_enabled as usize + anomalies_enabled as usize
+                + dashboard_enabled as usize,
+            self.config.channel_policies.trade,
+            &self.metrics,
+            tasks
         );
-        
-        let mut price_stream = MarketEventStream::<PriceUpdate>::new(
-            price_url,
-            price_update_sender.clone(),
-            self.config.reconnect_timeout
+        let mut bbo_receivers = spawn_fanout(
+            "bbo",
+            bbo_update_receiver,
+            1 + rollups_enabled as usize + status.is_some() as usize + postgres_enabled as usize,
+            self.config.channel_policies.bbo,
+            &self.metrics,
+            tasks
         );
+        let output_tiers_enabled = self.config.fast_output.is_some() || self.config.durable_output;
+        let mut book_receivers = spawn_fanout(
+            "book",
+            book_update_receiver,
+            1 + rollups_enabled as usize + output_tiers_enabled as usize + sampling_enabled as usize + grpc_enabled as usize
+                + self.config.rest_listen.is_some() as usize + postgres_enabled as usize + event_feed_enabled as usize
+                + status.is_some() as usize + sinks_enabled as usize + book_metrics_enabled as usize
+                + volatility_enabled as usize + anomalies_enabled as usize + dashboard_enabled as usize,
+            self.config.channel_policies.book,
+            &self.metrics,
+            tasks
+        );
+        let (flush_request_sender, flush_request_receiver) = watch::channel(());
 
-        tasks.push(tokio::spawn(async move {
-            tracing::info!("Starting price update stream");
-            price_stream.run().await;
-        }));
-        
-        let snapshot_stream = DepthSnapshotStream::new(
-            self.config.binance_rest_endpoint.clone(),
-            self.config.instrument.clone(),
-            self.config.max_depth,
-            self.config.snapshot_update_interval,
-            depth_update_sender.clone()
+        if let Some(postgres_url) = &self.config.postgres_url {
+            let postgres_writer = PostgresWriter::new(
+                PostgresSettings {
+                    url: postgres_url.clone(),
+                    flush_interval: self.config.postgres_flush_interval,
+                    batch_size: self.config.postgres_batch_size,
+                    snapshot_interval: self.config.postgres_snapshot_interval,
+                    tls: self.config.tls.clone(),
+                },
+                PostgresRows::new(self.connector.name(), self.config.canonical_symbol(), self.config.output_depth),
+                trade_receivers.pop().expect("Fanout has a PostgreSQL consumer"),
+                bbo_receivers.pop().expect("Fanout has a PostgreSQL consumer"),
+                book_receivers.pop().expect("Fanout has a PostgreSQL consumer"),
+                &self.metrics
+            ).with_flush_requests(flush_request_receiver);
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting PostgreSQL writer");
+                postgres_writer.run().await;
+            }));
+        }
+
+        let mut price_receivers = spawn_fanout(
+            "price",
+            price_dispatch_receiver,
+            1 + event_feed_enabled as usize + sinks_enabled as usize,
+            self.config.channel_policies.price,
+            &self.metrics,
+            tasks
+        );
+        let mut auxiliary_receivers = spawn_fanout(
+            "auxiliary",
+            auxiliary_receiver,
+            1 + event_feed_enabled as usize + sinks_enabled as usize,
+            self.config.channel_policies.auxiliary,
+            &self.metrics,
+            tasks
         );
 
-        tasks.push(tokio::spawn(async move {
-            tracing::info!("Starting depth snapshot stream");
-            snapshot_stream.run().await;
-        }));
-        
-        let dispatcher = DepthEventDispatcher::new(
-            depth_update_receiver,
-            dispatch_sender
+        if sinks_enabled {
+            let sink_forwarder = PipelineSinkForwarder::new(
+                sinks,
+                trade_receivers.pop().expect("Fanout has a pipeline sink consumer"),
+                price_receivers.pop().expect("Fanout has a pipeline sink consumer"),
+                auxiliary_receivers.pop().expect("Fanout has a pipeline sink consumer"),
+                book_receivers.pop().expect("Fanout has a pipeline sink consumer")
+            ).with_symbol(self.config.canonical_symbol());
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting pipeline sink forwarder");
+                sink_forwarder.run().await;
+            }));
+        }
+
+        if let Some(event_feed_listen) = &self.config.event_feed_listen {
+            let event_feed = EventFeed::new(
+                EventEncoder::new(self.connector.name(), self.config.canonical_symbol(), self.config.output_depth),
+                depth_receivers.pop().expect("Fanout has an event feed consumer"),
+                trade_receivers.pop().expect("Fanout has an event feed consumer"),
+                price_receivers.pop().expect("Fanout has an event feed consumer"),
+                auxiliary_receivers.pop().expect("Fanout has an event feed consumer"),
+                book_receivers.pop().expect("Fanout has an event feed consumer"),
+                &self.metrics
+            );
+            self.spawn_event_feed(tasks, event_feed_listen, event_feed);
+        }
+
+        if let Some(rest_listen) = &self.config.rest_listen {
+            let book_channel = book_receivers.pop().expect("Fanout has a REST API consumer");
+            let dashboard_channels = dashboard_enabled.then(|| (
+                book_receivers.pop().expect("Fanout has a dashboard consumer"),
+                trade_receivers.pop().expect("Fanout has a dashboard consumer")
+            ));
+            self.spawn_rest_api(tasks, rest_listen, book_channel, dashboard_channels, status, gate);
+        }
+
+        if let Some(grpc_listen) = &self.config.grpc_listen {
+            self.spawn_grpc_service(
+                tasks,
+                grpc_listen,
+                book_receivers.pop().expect("Fanout has a gRPC consumer"),
+                trade_receivers.pop().expect("Fanout has a gRPC consumer")
+            );
+        }
+
+        if sampling_enabled {
+            let sample_path = PathBuf::from(format!("{}.samples.jsonl", artifact_stem.to_string_lossy()));
+            let sample_trades = trade_receivers.pop().expect("Fanout has a sampling consumer");
+            let sample_books = book_receivers.pop().expect("Fanout has a sampling consumer");
+
+            match JsonLinesSampleSink::open(&sample_path) {
+                Ok(sink) => {
+                    let sampling_engine = TradeSamplingEngine::new(
+                        TradeSampler::new(self.config.trade_sampling_depth, self.config.trade_sampling_baseline),
+                        Box::new(sink),
+                        gate.clone(),
+                        sample_trades,
+                        sample_books
+                    );
+
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting trade sampling engine: '{:?}'", sample_path);
+                        sampling_engine.run().await;
+                    }));
+                }
+                Err(e) => tracing::error!("Trade sampling is disabled. Details: '{:#}'", e),
+            }
+        }
+
+        if output_tiers_enabled {
+            self.spawn_output_tiers(
+                tasks,
+                book_receivers.pop().expect("Fanout has an output tiers consumer"),
+                artifact_stem,
+                gate
+            );
+        }
+
+        if rollups_enabled {
+            let rollup_path = PathBuf::from(format!("{}.rollups.jsonl", artifact_stem.to_string_lossy()));
+            let rollup_trades = trade_receivers.pop().expect("Fanout has a rollup consumer");
+            let rollup_bbo = bbo_receivers.pop().expect("Fanout has a rollup consumer");
+            let rollup_books = book_receivers.pop().expect("Fanout has a rollup consumer");
+
+            match JsonLinesRollupSink::open(&rollup_path) {
+                Ok(sink) => {
+                    let rollup_engine = RollupEngine::new(
+                        RollupAggregator::new(self.config.rollup_intervals.clone(), self.config.rollup_imbalance_depth),
+                        Box::new(sink),
+                        gate.clone(),
+                        rollup_trades,
+                        rollup_bbo,
+                        rollup_books
+                    );
+
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting rollup engine: '{:?}'", rollup_path);
+                        rollup_engine.run().await;
+                    }));
+                }
+                Err(e) => tracing::error!("Rollups are disabled. Details: '{:#}'", e),
+            }
+        }
+
+        if let Some(board) = status {
+            let status_tracker = LiveStatusTracker::new(
+                board.clone(),
+                bbo_receivers.pop().expect("Fanout has a status consumer"),
+                book_receivers.pop().expect("Fanout has a status consumer")
+            );
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting live status tracker");
+                status_tracker.run().await;
+            }));
+        }
+
+        if book_metrics_enabled {
+            let book_metrics_engine = BookMetricsEngine::new(
+                self.config.book_metrics_depth,
+                book_receivers.pop().expect("Fanout has a book metrics consumer"),
+                auxiliary_sender.clone()
+            );
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting book metrics engine");
+                book_metrics_engine.run().await;
+            }));
+        }
+
+        if trade_flow_enabled {
+            let trade_flow_engine = TradeFlowEngine::new(
+                TradeFlowWindow::new(self.config.trade_flow_window),
+                self.config.trade_flow_interval,
+                trade_receivers.pop().expect("Fanout has a trade flow consumer"),
+                auxiliary_sender.clone()
+            );
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting trade flow engine");
+                trade_flow_engine.run().await;
+            }));
+        }
+
+        if volatility_enabled {
+            let volatility_engine = VolatilityEngine::new(
+                VolatilityWindow::new(self.config.volatility_window),
+                self.config.volatility_interval,
+                book_receivers.pop().expect("Fanout has a volatility consumer"),
+                auxiliary_sender.clone()
+            );
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting volatility engine");
+                volatility_engine.run().await;
+            }));
+        }
+
+        let mut anomaly_alerts = None;
+        if let Some(settings) = &self.config.anomaly_detection {
+            let mut anomaly_engine = AnomalyEngine::new(
+                AnomalyDetector::new(settings.clone()),
+                book_receivers.pop().expect("Fanout has an anomaly consumer"),
+                trade_receivers.pop().expect("Fanout has an anomaly consumer"),
+                auxiliary_sender.clone(),
+                &self.metrics
+            );
+
+            if self.config.alerting.as_ref().is_some_and(|alerting| alerting.rules.contains(&AlertRule::Anomaly)) {
+                let (alert_sender, alert_receiver) = mpsc::channel(100);
+                anomaly_engine = anomaly_engine.with_alerts(alert_sender);
+                anomaly_alerts = Some(alert_receiver);
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting anomaly engine");
+                anomaly_engine.run().await;
+            }));
+        }
+
+        // Aggregated trades are deduplicated like trades and forwarded along with the other auxiliary events
+        let agg_trade_dispatcher = TradeEventDispatcher::new(
+            agg_trade_update_receiver,
+            auxiliary_sender.clone(),
+            "agg_trade",
+            &self.metrics
         );
+        tasks.push(spawn(async move {
+            tracing::info!("Starting aggregated trade event dispatcher");
+            agg_trade_dispatcher.run().await;
+        }));
 
-        tasks.push(tokio::spawn(async move {
-            tracing::info!("Starting depth event dispatcher");
-            dispatcher.run().await;
+        let price_dispatcher = PriceEventDispatcher::new(price_update_receiver, price_dispatch_sender, &self.metrics);
+        tasks.push(spawn(async move {
+            tracing::info!("Starting price event dispatcher");
+            price_dispatcher.run().await;
         }));
-        
-        let book_processor = BookProcessor::new(
-            dispatch_receiver,
-            book_update_sender
+
+        let market_event_logger = MarketEventLogger::new(
+            trade_receivers.remove(0),
+            price_receivers.remove(0),
+            book_receivers.remove(0),
+            bbo_receivers.remove(0),
+            auxiliary_receivers.remove(0)
+        ).with_book_output(self.config.log_book_depth, self.config.log_book_interval);
+        tasks.push(spawn(async move {
+            tracing::info!("Starting market event logger");
+            market_event_logger.run().await;
+        }));
+
+        PipelineInputs {
+            depth: depth_update_sender,
+            trade: trade_update_sender,
+            price: price_update_sender,
+            agg_trade: agg_trade_update_sender,
+            auxiliary: auxiliary_sender,
+            validation: validation_sender,
+            snapshot_requests: Some(snapshot_request_receiver),
+            resync: snapshot_request_sender,
+            flush_requests: flush_request_sender,
+            anomalies: anomaly_alerts,
+        }
+    }
+
+    /// Spawn the gRPC service, which streams the book and trades to other services
+    ///
+    /// The service is disabled with an error if the listen address is invalid
+    fn spawn_grpc_service(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        listen: &str,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+    ) {
+        let address: SocketAddr = match listen.parse() {
+            Ok(address) => address,
+            Err(e) => {
+                tracing::error!("gRPC service is disabled. Invalid listen address '{}': '{}'", listen, e);
+                return;
+            }
+        };
+
+        let publisher = GrpcPublisher::new(book_channel, trade_channel);
+        let service = MarketDataServer::new(publisher.service(self.config.output_depth));
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting gRPC publisher");
+            publisher.run().await;
+        }));
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting gRPC service on '{}'", address);
+            if let Err(e) = Server::builder().add_service(service).serve(address).await {
+                tracing::error!("gRPC service stopped. Details: '{}'", e);
+            }
+        }));
+    }
+
+    /// Spawn the REST API, which serves the latest state of the book, and the dashboard if its channels are given
+    ///
+    /// The API is disabled with an error if the listen address can't be bound
+    fn spawn_rest_api(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        listen: &str,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+        dashboard_channels: Option<(mpsc::Receiver<Arc<OrderBook>>, mpsc::Receiver<MarketEvent>)>,
+        status: Option<&StatusBoard>,
+        gate: &CaptureGate,
+    ) {
+        let listener = match std::net::TcpListener::bind(listen).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener)
+        }) {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("REST API is disabled. Failed to listen on '{}': '{}'", listen, e);
+                return;
+            }
+        };
+
+        let probes = ProbeSettings::new(Some(gate.clone()), self.config.ready_max_message_age);
+        let (tracker, mut router) = rest_api(self.config.canonical_symbol(), book_channel, self.config.output_depth, status.cloned(), probes);
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting latest book tracker");
+            tracker.run().await;
+        }));
+
+        if let Some((dashboard_books, dashboard_trades)) = dashboard_channels {
+            let (publisher, dashboard_router) = dashboard(
+                self.config.canonical_symbol(),
+                self.config.output_depth,
+                dashboard_books,
+                dashboard_trades
+            );
+            router = router.merge(dashboard_router);
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting dashboard publisher");
+                publisher.run().await;
+            }));
+        }
+
+        let address = listen.to_string();
+        tasks.push(spawn(async move {
+            tracing::info!("Starting REST API on '{}'", address);
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("REST API stopped. Details: '{}'", e);
+            }
+        }));
+    }
+
+    /// Spawn the event feed, which streams protobuf-encoded events to TCP clients
+    ///
+    /// The feed is disabled with an error if the listen address can't be bound
+    fn spawn_event_feed(&self, tasks: &mut Vec<JoinHandle<()>>, listen: &str, event_feed: EventFeed) {
+        let listener = match std::net::TcpListener::bind(listen).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener)
+        }) {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Event feed is disabled. Failed to listen on '{}': '{}'", listen, e);
+                return;
+            }
+        };
+
+        let server = event_feed.server(listener);
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting event feed");
+            event_feed.run().await;
+        }));
+
+        let address = listen.to_string();
+        tasks.push(spawn(async move {
+            tracing::info!("Starting event feed server on '{}'", address);
+            server.run().await;
+        }));
+    }
+
+    /// Spawn the fast and the durable book outputs, which are configured
+    ///
+    /// An output, which fails to open, is disabled with an error
+    fn spawn_output_tiers(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        input: mpsc::Receiver<Arc<OrderBook>>,
+        artifact_stem: &Path,
+        gate: &CaptureGate,
+    ) {
+        let depth = self.config.output_depth;
+        let levels = self.config.output_buckets.map_or(depth, |buckets| buckets.count);
+
+        let fast_sink = self.config.fast_output.as_ref().and_then(|path| {
+            // Every level takes at most two f64 numbers in JSON
+            let capacity = 256 + levels * 2 * 64;
+            match SharedMemoryFrameSink::create(Path::new(path), capacity) {
+                Ok(sink) => Some(Box::new(sink) as Box<dyn FrameSink>),
+                Err(e) => {
+                    tracing::error!("Fast output is disabled. Details: '{:#}'", e);
+                    None
+                }
+            }
+        });
+
+        let durable_sink = self.config.durable_output.then(|| {
+            let path = PathBuf::from(format!("{}.book.jsonl", artifact_stem.to_string_lossy()));
+            match JsonLinesFrameSink::open(&path) {
+                Ok(sink) => Some(Box::new(sink) as Box<dyn FrameSink>),
+                Err(e) => {
+                    tracing::error!("Durable output is disabled. Details: '{:#}'", e);
+                    None
+                }
+            }
+        }).flatten();
+
+        let (output_tiers, fast_writer, durable_writer) = OutputTiers::new(
+            input,
+            depth,
+            fast_sink,
+            durable_sink,
+            gate.clone(),
+            &self.metrics
         );
+        let output_tiers = match self.config.output_buckets {
+            Some(buckets) => output_tiers.with_buckets(buckets),
+            None => output_tiers,
+        };
 
-        tasks.push(tokio::spawn(async move {
-            tracing::info!("Starting book processor");
-            book_processor.run().await;
+        tasks.push(spawn(async move {
+            tracing::info!("Starting output tiers");
+            output_tiers.run().await;
         }));
-        
-        let market_event_logger = MarketEventLogger::new(
-            trade_update_receiver,
-            price_update_receiver,
-            book_update_receiver
+
+        if let Some(fast_writer) = fast_writer {
+            tasks.push(spawn(async move {
+                tracing::info!("Starting fast output writer");
+                fast_writer.run().await;
+            }));
+        }
+
+        if let Some(durable_writer) = durable_writer {
+            tasks.push(spawn(async move {
+                tracing::info!("Starting durable output writer");
+                durable_writer.run().await;
+            }));
+        }
+    }
+
+    /// The span of the pipeline, within which all its stages log
+    fn span(&self) -> Span {
+        pipeline_span(self.connector.name(), self.config.canonical_symbol())
+    }
+
+    /// Start live capture
+    ///
+    /// # Arguments
+    /// * `record` - If set, every raw frame is additionally persisted into a tape file in the capture directory
+    /// * `force` - Start even if another running instance captures the same instrument into the same capture directory
+    pub async fn start(&self, record: bool, force: bool) -> Result<()> {
+        self.capture(record, force).instrument(self.span()).await
+    }
+
+    async fn capture(&self, record: bool, force: bool) -> Result<()> {
+        let _instance_lock = InstanceLock::acquire(
+            &self.config.capture_dir,
+            self.connector.name(),
+            &self.config.instrument,
+            force
+        )?;
+
+        let scripts = EventScripts::compile(&self.config.scripts)?;
+        self.check_sbe()?;
+        let mut manifest = self.create_manifest().await?;
+        let mut tasks = Vec::new();
+        let gate = CaptureGate::new();
+
+        let tape_sender = if record {
+            let extension = match self.config.tape_compression {
+                Some(_) => "tape.zst",
+                None => "tape",
+            };
+            let tape_path = Path::new(&self.config.capture_dir).join(format!("{}.{}", manifest.session_name(), extension));
+            manifest.tape_file = Some(tape_path.to_string_lossy().to_string());
+
+            let (tape_sender, tape_receiver) = mpsc::channel::<TapeRecord>(1000);
+            let mut tape_writer = TapeWriter::new(tape_path, tape_receiver, gate.clone());
+            if let Some(compression) = self.config.tape_compression {
+                tape_writer = tape_writer.with_compression(compression);
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting tape writer");
+                tape_writer.run().await;
+            }));
+
+            Some(tape_sender)
+        } else {
+            None
+        };
+
+        let manifest_path = manifest.write(&self.config.capture_dir)?;
+        tracing::info!("Capture manifest written to: '{:?}'", manifest_path);
+
+        if self.config.min_free_space_mb > 0 || self.config.warn_free_space_mb > 0 || self.config.cleanup_free_space_mb > 0 {
+            let mut disk_space_guard = DiskSpaceGuard::new(
+                PathBuf::from(&self.config.capture_dir),
+                self.config.min_free_space_mb * 1024 * 1024,
+                self.config.resume_free_space_mb * 1024 * 1024,
+                self.config.disk_check_interval,
+                gate.clone(),
+                manifest.clone()
+            ).with_warning(self.config.warn_free_space_mb * 1024 * 1024, &self.metrics);
+
+            // With upload enabled, files are only deleted once they are safe in the object storage
+            if self.config.cleanup_free_space_mb > 0 {
+                disk_space_guard = disk_space_guard.with_cleanup(
+                    self.config.cleanup_free_space_mb * 1024 * 1024,
+                    self.config.upload.is_some(),
+                    &self.metrics,
+                );
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting disk space guard");
+                disk_space_guard.run().await;
+            }));
+        }
+
+        if let Some(upload) = &self.config.upload {
+            match upload.client(self.config.transport()) {
+                Ok(client) => {
+                    let uploader = CaptureUploader::new(
+                        Box::new(client),
+                        upload.clone(),
+                        PathBuf::from(&self.config.capture_dir),
+                        self.connector.name(),
+                        &self.config.instrument,
+                        manifest.session_name(),
+                        &self.metrics,
+                    );
+
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting capture uploader");
+                        uploader.run().await;
+                    }));
+                }
+                Err(e) => tracing::error!("Capture upload is disabled. Details: '{:#}'", e),
+            }
+        }
+
+        if let Some(retention) = &self.config.retention {
+            let janitor = RetentionJanitor::new(
+                retention.clone(),
+                PathBuf::from(&self.config.capture_dir),
+                &self.config.instrument,
+                manifest.session_name(),
+                self.config.upload.is_some(),
+                &self.metrics,
+            );
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting retention janitor");
+                janitor.run().await;
+            }));
+        }
+
+        let recorder = |source: String| {
+            tape_sender
+                .as_ref()
+                .map(|sender| TapeRecorder::new(source, sender.clone()))
+        };
+
+        let artifact_stem = Path::new(&self.config.capture_dir).join(manifest.session_name());
+        let status_board = StatusBoard::new(self.connector.name(), &self.config.instrument, self.metrics.clone());
+        let tick_size = manifest.symbol_metadata.as_ref().and_then(|metadata| metadata.tick_size);
+        let checkpointing = self.checkpointing();
+        let resumed = checkpointing.as_ref().is_some_and(|checkpointing| checkpointing.resume.is_some());
+        let mut inputs = self.spawn_processing(&mut tasks, &artifact_stem, Some(&status_board), &gate, tick_size, checkpointing, scripts);
+        let reporter = SessionReporter::new(
+            status_board.clone(),
+            manifest.session_name(),
+            PathBuf::from(format!("{}.report.json", artifact_stem.to_string_lossy())),
         );
 
-        tasks.push(tokio::spawn(async move {
-            tracing::info!("Starting market event logger");
-            market_event_logger.run().await;
+        // Windows builds have no admin socket
+        #[cfg(unix)]
+        {
+            let admin_socket = AdminSocket::bind(
+                admin_socket_path(&self.config.capture_dir, self.connector.name(), &self.config.instrument),
+                AdminControls {
+                    config: self.config.clone(),
+                    board: status_board.clone(),
+                    gate: gate.clone(),
+                    snapshot_requests: inputs.resync.clone(),
+                    flush_requests: inputs.flush_requests.clone(),
+                    reporter: reporter.clone(),
+                    supervisor: self.supervisor.clone(),
+                }
+            )?;
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting admin socket");
+                admin_socket.run().await;
+            }));
+        }
+
+        if let Some(alerting) = &self.config.alerting {
+            let mut channels: Vec<Box<dyn AlertChannel>> = Vec::new();
+            for destination in &alerting.destinations {
+                match destination.channel(self.config.transport()) {
+                    Ok(channel) => channels.push(Box::new(channel)),
+                    Err(e) => tracing::error!("Alert destination is disabled. Details: '{:#}'", e),
+                }
+            }
+
+            let mut alert_engine = AlertEngine::new(alerting, status_board.clone(), channels, &self.metrics);
+            if let Some(anomalies) = inputs.anomalies.take() {
+                alert_engine = alert_engine.with_anomalies(anomalies);
+            }
+            tasks.push(spawn(async move {
+                tracing::info!("Starting alert engine");
+                alert_engine.run().await;
+            }));
+        }
+
+        // Without REST snapshots the depth stream starts with a snapshot, so the first connection is reopened on request
+        let rest_snapshots = self.provides_rest_snapshots();
+        let snapshot_requests = inputs.snapshot_requests.take();
+        let (stream_snapshot_requests, mut rest_snapshot_requests) = match rest_snapshots {
+            true => (None, snapshot_requests),
+            false => (snapshot_requests, None),
+        };
+
+        match self.config.exchange {
+            Exchange::Okx => self.spawn_depth_streams::<OkxBookMessage>(&mut tasks, &inputs, &recorder, &status_board, stream_snapshot_requests)?,
+            Exchange::Bitfinex => self.spawn_depth_streams::<BitfinexBookMessage>(&mut tasks, &inputs, &recorder, &status_board, stream_snapshot_requests)?,
+            Exchange::Deribit => self.spawn_depth_streams::<DeribitBookMessage>(&mut tasks, &inputs, &recorder, &status_board, stream_snapshot_requests)?,
+            #[cfg(feature = "sbe")]
+            Exchange::Binance if self.config.binance_sbe.is_some() => self.spawn_depth_streams::<SbeMessage>(&mut tasks, &inputs, &recorder, &status_board, stream_snapshot_requests)?,
+            _ => self.spawn_depth_streams::<DepthUpdate>(&mut tasks, &inputs, &recorder, &status_board, stream_snapshot_requests)?,
+        }
+
+        if self.config.combined_streams && self.connector.combined_stream_url(&[]).is_some() {
+            self.spawn_combined_streams(&mut tasks, &inputs, &recorder, &status_board);
+        } else {
+            if self.config.combined_streams {
+                tracing::warn!("Exchange '{}' doesn't provide combined streams. Using separate connections", self.connector.name());
+            }
+            self.spawn_separate_streams(&mut tasks, &inputs, &recorder, &status_board);
+        }
+
+        if self.config.price_source == PriceSource::AllMarket {
+            self.subscribe_all_market_prices(&mut tasks, &inputs, &recorder);
+        }
+
+        // Validation snapshots and clock checks share the budget with the regular snapshots
+        let weight_budget = (self.config.rest_weight_budget > 0)
+            .then(|| RequestWeightBudget::new(self.config.rest_weight_budget, &self.metrics));
+
+        if self.config.clock_check_interval > 0 && self.connector.server_time_url().is_none() {
+            tracing::info!("Exchange '{}' doesn't provide the server time. Skipping clock skew monitor", self.connector.name());
+        } else if self.config.clock_check_interval > 0 {
+            let mut clock_monitor = ClockSkewMonitor::new(self.connector.clone(), self.config.clock_check_interval, &self.metrics)
+                .with_transport(self.config.transport());
+            if let Some(weight_budget) = &weight_budget {
+                clock_monitor = clock_monitor.with_weight_budget(weight_budget.clone());
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting clock skew monitor");
+                clock_monitor.run().await;
+            }));
+        }
+
+        let partial_depth_url = self.partial_depth_url();
+        // Partial books replace the REST snapshots they are configured for
+        let partial_seeding = partial_depth_url.is_some() && self.config.snapshot_source == SnapshotSource::PartialStream;
+        let partial_validation = partial_depth_url.is_some() && self.config.book_validation_source == SnapshotSource::PartialStream;
+
+        if let (Some(url), Some(snapshot_requests)) = (&partial_depth_url, rest_snapshot_requests.take_if(|_| partial_seeding)) {
+            let mut sampler = self.spawn_partial_depth_stream(
+                &mut tasks,
+                url,
+                0,
+                inputs.depth.clone(),
+                self.config.snapshot_update_interval,
+                recorder("snapshot".to_string()),
+                &status_board
+            ).with_requests(snapshot_requests);
+
+            // The book is resumed from the checkpoint, a snapshot is requested if the live updates don't continue it
+            if resumed {
+                sampler = sampler.with_deferred_start();
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting partial book snapshot sampler");
+                sampler.run().await;
+            }));
+        }
+
+        if let (Some(url), Some(validation)) = (&partial_depth_url, inputs.validation.take_if(|_| partial_validation)) {
+            let sampler = self.spawn_partial_depth_stream(
+                &mut tasks,
+                url,
+                1,
+                validation,
+                self.config.book_validation_interval,
+                None,
+                &status_board
+            );
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting book validation partial book sampler");
+                sampler.run().await;
+            }));
+        }
+
+        if let Some(snapshot_requests) = rest_snapshot_requests {
+            let mut snapshot_stream = DepthSnapshotStream::new(
+                self.connector.clone(),
+                self.config.instrument.clone(),
+                SnapshotDepthSelector::new(self.config.max_depth, self.config.kept_depth(), self.config.snapshot_limit),
+                self.config.snapshot_update_interval,
+                inputs.depth.clone(),
+                recorder("snapshot".to_string())
+            ).with_requests(snapshot_requests).with_transport(self.config.transport());
+            snapshot_stream = self.with_snapshot_source(snapshot_stream, self.config.snapshot_source);
+
+            if let Some(weight_budget) = &weight_budget {
+                snapshot_stream = snapshot_stream.with_weight_budget(weight_budget.clone());
+            }
+
+            // The book is resumed from the checkpoint, a snapshot is requested if the live updates don't continue it
+            if resumed {
+                snapshot_stream = snapshot_stream.with_deferred_start();
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting depth snapshot stream");
+                snapshot_stream.run().await;
+            }));
+        }
+
+        if let Some(validation) = inputs.validation {
+            let mut validation_stream = DepthSnapshotStream::new(
+                self.connector.clone(),
+                self.config.instrument.clone(),
+                SnapshotDepthSelector::new(self.config.max_depth, self.config.kept_depth(), self.config.snapshot_limit),
+                self.config.book_validation_interval,
+                validation,
+                None
+            ).with_transport(self.config.transport());
+            validation_stream = self.with_snapshot_source(validation_stream, self.config.book_validation_source);
+
+            if let Some(weight_budget) = &weight_budget {
+                validation_stream = validation_stream.with_weight_budget(weight_budget.clone());
+            }
+
+            tasks.push(spawn(async move {
+                tracing::info!("Starting book validation snapshot stream");
+                validation_stream.run().await;
+            }));
+        }
+
+        // The pipeline is watched by systemd until it stops
+        #[cfg(unix)]
+        let _systemd_registration = self.systemd.as_ref().map(|systemd| systemd.register(status_board.clone()));
+        // The report is written after the tasks have been stopped
+        let _report_guard = ReportOnDrop(reporter);
+        // The tasks are stopped along with the capture, e.g. when the instrument is removed over the admin socket
+        let _abort_guard = AbortOnDrop(tasks.iter().map(JoinHandle::abort_handle).collect());
+        for handle in tasks {
+            handle.await?;
+        }
+
+        Ok(())
+    }
+
+    /// Book checkpointing of the pipeline along with the checkpoint of the previous session, if it is recent enough
+    ///
+    /// Checkpoints are only taken if the exchange provides REST snapshots, since otherwise the depth stream itself
+    /// starts with a snapshot
+    fn checkpointing(&self) -> Option<Checkpointing> {
+        if self.config.checkpoint_interval == 0 {
+            return None;
+        }
+
+        if !self.provides_rest_snapshots() {
+            tracing::warn!("Exchange '{}' doesn't provide REST snapshots. Book checkpoints are disabled", self.connector.name());
+            return None;
+        }
+
+        let path = checkpoint_path(&self.config.capture_dir, self.connector.name(), &self.config.instrument);
+        let resume = match BookCheckpoint::load(&path) {
+            Ok(checkpoint) if checkpoint.age(Utc::now().timestamp_millis()) <= self.config.checkpoint_max_age => {
+                tracing::info!("Resuming the book from checkpoint '{}' taken at '{}'", checkpoint.snapshot.last_update_id, checkpoint.time);
+                Some(checkpoint.snapshot)
+            }
+            Ok(checkpoint) => {
+                tracing::info!("Checkpoint '{}' is older than '{}' ms. Waiting for a snapshot", checkpoint.snapshot.last_update_id, self.config.checkpoint_max_age);
+                None
+            }
+            Err(_) if !path.exists() => None,
+            Err(e) => {
+                tracing::warn!("Book checkpoint can't be resumed from. Details: '{:#}'", e);
+                None
+            }
+        };
+
+        Some(Checkpointing { path, resume })
+    }
+
+    /// URL of the partial book depth stream, if the book is seeded or validated from it
+    ///
+    /// REST snapshots are used with a warning if the exchange doesn't provide the stream
+    fn partial_depth_url(&self) -> Option<String> {
+        let validation = self.config.book_validation_interval > 0 && self.config.book_validation_source == SnapshotSource::PartialStream;
+        if self.config.snapshot_source != SnapshotSource::PartialStream && !validation {
+            return None;
+        }
+
+        let kind = StreamKind::PartialDepth(self.config.partial_depth_levels);
+        let url = self.connector.stream_url(kind, &self.config.instrument);
+        if url.is_none() {
+            tracing::warn!("Exchange '{}' doesn't provide partial book depth streams. Using REST snapshots", self.connector.name());
+        }
+        url
+    }
+
+    /// Request the snapshots of the stream over the WebSocket API, if it is their source
+    ///
+    /// REST snapshots are used with a warning if the exchange doesn't provide WebSocket API snapshots
+    fn with_snapshot_source(&self, stream: DepthSnapshotStream, source: SnapshotSource) -> DepthSnapshotStream {
+        if source != SnapshotSource::WsApi {
+            return stream;
+        }
+
+        if self.connector.ws_api_snapshot_params(&self.config.instrument, self.config.max_depth).is_none() {
+            tracing::warn!("Exchange '{}' doesn't provide WebSocket API snapshots. Using REST snapshots", self.connector.name());
+            return stream;
+        }
+        stream.with_ws_api(WsApiClient::new(self.config.binance_ws_api_endpoint.clone()).with_transport(self.config.transport()))
+    }
+
+    /// The captured partial book depth stream. Unlike the one seeding or validating the book, it has its own interval
+    fn partial_book_kind(&self) -> StreamKind {
+        StreamKind::PartialBook { levels: self.config.partial_depth_levels, interval: self.config.partial_book_interval }
+    }
+
+    /// Spawn connection `index` of the partial book depth stream
+    ///
+    /// # Returns
+    /// The sampler, which forwards its partial books as snapshots to the output and has to be run as a separate task
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_partial_depth_stream(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        url: &str,
+        index: u64,
+        output: mpsc::Sender<MarketEvent>,
+        update_interval: u64,
+        recorder: Option<TapeRecorder>,
+        status_board: &StatusBoard,
+    ) -> PartialSnapshotSampler {
+        let source = format!("{}#{}", StreamKind::PartialDepth(self.config.partial_depth_levels), index);
+        let (partial_sender, partial_receiver) = mpsc::channel(10);
+        let stream = MarketEventStream::<PartialBookMessage>::new(
+            url.to_string(),
+            partial_sender,
+            self.config.reconnect_timeout,
+            None,
+            None,
+            Some(status_board.stream(source.clone()))
+        ).with_stall_timeout(self.config.stall_timeout, &self.metrics);
+        let mut stream = self.subscribe(stream, StreamKind::PartialDepth(self.config.partial_depth_levels));
+
+        let span = stream_span(&source);
+        tasks.push(spawn(async move {
+            tracing::info!("Starting partial book depth stream: '{}'", index);
+            stream.run().await;
+        }.instrument(span)));
+
+        PartialSnapshotSampler::new(partial_receiver, output, update_interval, recorder)
+    }
+
+    /// Whether the exchange provides REST depth snapshots. Otherwise the depth stream itself starts with a snapshot
+    fn provides_rest_snapshots(&self) -> bool {
+        self.connector.snapshot_url(&self.config.instrument, self.config.max_depth).is_some()
+    }
+
+    /// Connect the stream with the transport, the alternate endpoints and the keepalive of the pipeline
+    fn configure_connection<T>(&self, stream: MarketEventStream<T>) -> MarketEventStream<T>
+    where T: StreamMessage,
+    {
+        let stream = stream
+            .with_transport(self.config.transport())
+            .with_alternate_endpoints(&self.config.wss_endpoint, &self.config.binance_wss_alternate_endpoints);
+
+        match &self.config.keepalive {
+            Some(keepalive) => stream.with_keepalive(keepalive.clone(), &self.metrics),
+            None => stream,
+        }
+    }
+
+    /// Subscribe the stream to the channel of the given kind, if the exchange requires a subscription, and configure
+    /// its connection
+    fn subscribe<T>(&self, stream: MarketEventStream<T>, kind: StreamKind) -> MarketEventStream<T>
+    where T: StreamMessage,
+    {
+        let subscription = self.connector.subscription(kind, &self.config.instrument);
+        let stream = self.configure_connection(match subscription.is_empty() {
+            true => stream,
+            false => stream.with_subscription(subscription),
+        });
+
+        match &self.config.binance_sbe {
+            Some(sbe) if sbe.stream_url(kind, &self.config.instrument).is_some() => stream.with_headers(sbe.headers()),
+            _ => stream,
+        }
+    }
+
+    /// URL of the stream of the given kind: the SBE stream if the pipeline consumes it, otherwise the JSON one
+    fn stream_url(&self, kind: StreamKind) -> Option<String> {
+        self.config.binance_sbe
+            .as_ref()
+            .and_then(|sbe| sbe.stream_url(kind, &self.config.instrument))
+            .or_else(|| self.connector.stream_url(kind, &self.config.instrument))
+    }
+
+    /// Check, that the pipeline can consume the SBE streams, if it is configured to
+    fn check_sbe(&self) -> Result<()> {
+        let Some(sbe) = &self.config.binance_sbe else {
+            return Ok(());
+        };
+
+        if self.config.exchange != Exchange::Binance {
+            return Err(anyhow!("SBE streams are only provided by Binance spot, not by '{}'", self.connector.name()));
+        }
+        if !cfg!(feature = "sbe") {
+            return Err(anyhow!("SBE streams require MDC built with the 'sbe' feature"));
+        }
+        if sbe.api_key().is_none() {
+            return Err(anyhow!("API key of the SBE streams is missing: 'BINANCE_API_KEY' is not set"));
+        }
+        Ok(())
+    }
+
+    /// Spawn the depth update streams, whose frames are decoded as `T`
+    ///
+    /// If `snapshot_requests` is set, the first connection is reopened on each request to receive a fresh snapshot
+    fn spawn_depth_streams<T>(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        inputs: &PipelineInputs,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+        status_board: &StatusBoard,
+        mut snapshot_requests: Option<mpsc::Receiver<()>>,
+    ) -> Result<()>
+    where T: StreamMessage,
+    {
+        let depth_url = self
+            .stream_url(StreamKind::Depth)
+            .ok_or_else(|| anyhow!("Exchange '{}' doesn't provide depth updates", self.connector.name()))?;
+
+        let health_policy = HealthPolicy {
+            window: self.config.health_window,
+            latency_threshold: self.config.health_latency_threshold,
+            min_score: self.config.health_min_score,
+        };
+
+        let connections = match self.connector.supports_redundant_depth() {
+            true => self.config.connections,
+            false if self.config.connections > 1 => {
+                tracing::warn!("Exchange '{}' numbers depth updates per connection. Using a single depth connection", self.connector.name());
+                1
+            }
+            false => self.config.connections,
+        };
+
+        let rotation = (self.config.session_lifetime > 0)
+            .then(|| RotationGroup::new(connections, self.config.session_lifetime));
+
+        for i in 0..connections {
+            let depth_stream = MarketEventStream::<T>::new(
+                depth_url.clone(),
+                inputs.depth.clone(),
+                self.config.reconnect_timeout,
+                recorder(format!("{}#{}", StreamKind::Depth, i)),
+                Some(ConnectionHealth::new(health_policy, self.connector.sequencing_rules())),
+                Some(status_board.stream(format!("{}#{}", StreamKind::Depth, i)))
+            ).with_stall_timeout(self.config.stall_timeout, &self.metrics);
+            let mut depth_stream = self.subscribe(depth_stream, StreamKind::Depth);
+
+            if let Some(rotation) = &rotation {
+                depth_stream = depth_stream.with_rotation(rotation.member(i));
+            }
+
+            if let Some(snapshot_requests) = snapshot_requests.take() {
+                depth_stream = depth_stream.with_snapshot_requests(snapshot_requests);
+            }
+
+            let span = stream_span(&format!("{}#{}", StreamKind::Depth, i));
+            tasks.push(spawn(async move {
+                tracing::info!("Starting depth update stream: '{}'", i);
+                depth_stream.run().await;
+            }.instrument(span)));
+        }
+
+        Ok(())
+    }
+
+    /// Spawn the trade streams, whose frames are decoded as `T`
+    fn spawn_trade_streams<T>(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        trade_url: String,
+        inputs: &PipelineInputs,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+        status_board: &StatusBoard,
+    ) where T: StreamMessage,
+    {
+        for i in 0..self.config.trade_connections {
+            let trade_stream = MarketEventStream::<T>::new(
+                trade_url.clone(),
+                inputs.trade.clone(),
+                self.config.reconnect_timeout,
+                recorder(format!("{}#{}", StreamKind::Trade, i)),
+                None,
+                Some(status_board.stream(format!("{}#{}", StreamKind::Trade, i)))
+            ).with_stall_timeout(self.config.stall_timeout, &self.metrics);
+            let mut trade_stream = self.subscribe(trade_stream, StreamKind::Trade);
+
+            let span = stream_span(&format!("{}#{}", StreamKind::Trade, i));
+            tasks.push(spawn(async move {
+                tracing::info!("Starting trade update stream: '{}'", i);
+                trade_stream.run().await;
+            }.instrument(span)));
+        }
+    }
+
+    /// Connections of the JSON trade stream of the instrument. None, if the trades come from the SBE stream
+    fn json_trade_connections(&self) -> u64 {
+        match self.config.binance_sbe {
+            Some(_) => 0,
+            None => self.config.trade_connections,
+        }
+    }
+
+    /// Connections of the price stream of the instrument. None, if the prices come from the all-market price stream
+    fn price_connections(&self) -> u64 {
+        match self.config.price_source {
+            PriceSource::Symbol => self.config.price_connections,
+            PriceSource::AllMarket => 0,
+        }
+    }
+
+    /// Route the prices of the instrument from the all-market price stream to the pipeline
+    ///
+    /// Without a stream shared by the process (e.g. `mdc tui`), the pipeline connects to it on its own
+    fn subscribe_all_market_prices(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        inputs: &PipelineInputs,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+    ) {
+        let all_market_prices = match (&self.all_market_prices, self.connector.stream_url(StreamKind::AllMarketPrice, &self.config.instrument)) {
+            (Some(all_market_prices), _) => all_market_prices.clone(),
+            (None, Some(url)) => AllMarketPrices::start(tasks, url, self.config.reconnect_timeout, self.config.transport()),
+            (None, None) => {
+                tracing::info!("Exchange '{}' doesn't provide all-market price stream. Skipping", self.connector.name());
+                return;
+            }
+        };
+
+        all_market_prices.subscribe(&self.config.instrument, inputs.price.clone(), recorder(format!("{}#0", StreamKind::Price)));
+    }
+
+    /// Spawn the trade streams with the decoder of the venue, or of the SBE stream
+    fn spawn_venue_trade_streams(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        inputs: &PipelineInputs,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+        status_board: &StatusBoard,
+    ) {
+        match self.stream_url(StreamKind::Trade) {
+            Some(trade_url) => match self.config.exchange {
+                Exchange::Okx => self.spawn_trade_streams::<OkxTradeMessage>(tasks, trade_url, inputs, recorder, status_board),
+                Exchange::Bitfinex => self.spawn_trade_streams::<BitfinexTradeMessage>(tasks, trade_url, inputs, recorder, status_board),
+                Exchange::Deribit => self.spawn_trade_streams::<DeribitTradeMessage>(tasks, trade_url, inputs, recorder, status_board),
+                #[cfg(feature = "sbe")]
+                Exchange::Binance if self.config.binance_sbe.is_some() => self.spawn_trade_streams::<SbeMessage>(tasks, trade_url, inputs, recorder, status_board),
+                _ => self.spawn_trade_streams::<TradeEvent>(tasks, trade_url, inputs, recorder, status_board),
+            },
+            None => tracing::info!("Exchange '{}' doesn't provide trade stream. Skipping", self.connector.name()),
+        }
+    }
+
+    /// Spawn the trade, price, aggregated trade and auxiliary streams, one connection per stream
+    fn spawn_separate_streams(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        inputs: &PipelineInputs,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+        status_board: &StatusBoard,
+    ) {
+        self.spawn_venue_trade_streams(tasks, inputs, recorder, status_board);
+
+        match self.connector.stream_url(StreamKind::Price, &self.config.instrument) {
+            Some(price_url) => {
+                for i in 0..self.price_connections() {
+                    let price_stream = MarketEventStream::<PriceUpdate>::new(
+                        price_url.clone(),
+                        inputs.price.clone(),
+                        self.config.reconnect_timeout,
+                        recorder(format!("{}#{}", StreamKind::Price, i)),
+                        None,
+                        Some(status_board.stream(format!("{}#{}", StreamKind::Price, i)))
+                    ).with_stall_timeout(self.config.stall_timeout, &self.metrics);
+                    let mut price_stream = self.configure_connection(price_stream);
+
+                    let span = stream_span(&format!("{}#{}", StreamKind::Price, i));
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting price update stream: '{}'", i);
+                        price_stream.run().await;
+                    }.instrument(span)));
+                }
+            }
+            None => tracing::info!("Exchange '{}' doesn't provide price stream. Skipping", self.connector.name()),
+        }
+
+        match self.connector.stream_url(StreamKind::AggTrade, &self.config.instrument) {
+            Some(agg_trade_url) => {
+                for i in 0..self.config.agg_trade_connections {
+                    let agg_trade_stream = MarketEventStream::<AggTradeEvent>::new(
+                        agg_trade_url.clone(),
+                        inputs.agg_trade.clone(),
+                        self.config.reconnect_timeout,
+                        recorder(format!("{}#{}", StreamKind::AggTrade, i)),
+                        None,
+                        Some(status_board.stream(format!("{}#{}", StreamKind::AggTrade, i)))
+                    ).with_stall_timeout(self.config.stall_timeout, &self.metrics);
+                    let mut agg_trade_stream = self.configure_connection(agg_trade_stream);
+
+                    let span = stream_span(&format!("{}#{}", StreamKind::AggTrade, i));
+                    tasks.push(spawn(async move {
+                        tracing::info!("Starting aggregated trade stream: '{}'", i);
+                        agg_trade_stream.run().await;
+                    }.instrument(span)));
+                }
+            }
+            None => tracing::info!("Exchange '{}' doesn't provide aggregated trade stream. Skipping", self.connector.name()),
+        }
+
+        for interval in &self.config.kline_intervals {
+            let kind = StreamKind::Kline(*interval);
+            self.spawn_auxiliary_stream::<KlineEvent>(tasks, kind, &inputs.auxiliary, recorder(format!("{}#0", kind)), status_board);
+        }
+
+        if self.config.ticker {
+            let kind = StreamKind::Ticker;
+            self.spawn_auxiliary_stream::<TickerEvent>(tasks, kind, &inputs.auxiliary, recorder(format!("{}#0", kind)), status_board);
+        }
+
+        if self.config.mini_ticker {
+            let kind = StreamKind::MiniTicker;
+            self.spawn_auxiliary_stream::<MiniTickerEvent>(tasks, kind, &inputs.auxiliary, recorder(format!("{}#0", kind)), status_board);
+        }
+
+        if self.config.mark_price {
+            let kind = StreamKind::MarkPrice;
+            self.spawn_auxiliary_stream::<MarkPriceEvent>(tasks, kind, &inputs.auxiliary, recorder(format!("{}#0", kind)), status_board);
+        }
+
+        if self.config.liquidations {
+            let kind = StreamKind::Liquidation;
+            self.spawn_auxiliary_stream::<LiquidationEvent>(tasks, kind, &inputs.auxiliary, recorder(format!("{}#0", kind)), status_board);
+        }
+
+        if self.config.partial_book {
+            let kind = self.partial_book_kind();
+            self.spawn_auxiliary_stream::<PartialBookEvent>(tasks, kind, &inputs.auxiliary, recorder(format!("{}#0", kind)), status_board);
+        }
+    }
+
+    /// Spawn the trade, price, aggregated trade and auxiliary streams, multiplexed over combined connections
+    ///
+    /// Combined connection `i` carries connection `i` of each of the trade, price and aggregated trade streams,
+    /// the auxiliary streams are carried by the first one. Tape sources and stream statuses are the same as with
+    /// separate connections. SBE trade streams are binary, so they keep their own connections
+    fn spawn_combined_streams(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        inputs: &PipelineInputs,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+        status_board: &StatusBoard,
+    ) {
+        if self.config.binance_sbe.is_some() {
+            self.spawn_venue_trade_streams(tasks, inputs, recorder, status_board);
+        }
+
+        let connections = [self.json_trade_connections(), self.price_connections(), self.config.agg_trade_connections]
+            .into_iter()
+            .max()
+            .unwrap_or_default()
+            .max(1);
+
+        for i in 0..connections {
+            let mut routes = Vec::new();
+
+            if i < self.json_trade_connections() {
+                routes.extend(self.stream_route::<TradeEvent>(StreamKind::Trade, i, &inputs.trade, recorder, status_board));
+            }
+
+            if i < self.price_connections() {
+                routes.extend(self.stream_route::<PriceUpdate>(StreamKind::Price, i, &inputs.price, recorder, status_board));
+            }
+
+            if i < self.config.agg_trade_connections {
+                routes.extend(self.stream_route::<AggTradeEvent>(StreamKind::AggTrade, i, &inputs.agg_trade, recorder, status_board));
+            }
+
+            if i == 0 {
+                for interval in &self.config.kline_intervals {
+                    routes.extend(self.stream_route::<KlineEvent>(StreamKind::Kline(*interval), 0, &inputs.auxiliary, recorder, status_board));
+                }
+
+                if self.config.ticker {
+                    routes.extend(self.stream_route::<TickerEvent>(StreamKind::Ticker, 0, &inputs.auxiliary, recorder, status_board));
+                }
+
+                if self.config.mini_ticker {
+                    routes.extend(self.stream_route::<MiniTickerEvent>(StreamKind::MiniTicker, 0, &inputs.auxiliary, recorder, status_board));
+                }
+
+                if self.config.mark_price {
+                    routes.extend(self.stream_route::<MarkPriceEvent>(StreamKind::MarkPrice, 0, &inputs.auxiliary, recorder, status_board));
+                }
+
+                if self.config.liquidations {
+                    routes.extend(self.stream_route::<LiquidationEvent>(StreamKind::Liquidation, 0, &inputs.auxiliary, recorder, status_board));
+                }
+
+                if self.config.partial_book {
+                    routes.extend(self.stream_route::<PartialBookEvent>(self.partial_book_kind(), 0, &inputs.auxiliary, recorder, status_board));
+                }
+            }
+
+            if routes.is_empty() {
+                continue;
+            }
+
+            let names: Vec<String> = routes.iter().map(|(name, _)| name.clone()).collect();
+            let Some(url) = self.connector.combined_stream_url(&names) else {
+                continue;
+            };
+
+            let mut combined_stream = CombinedEventStream::new(
+                url,
+                routes.into_iter().collect(),
+                self.config.reconnect_timeout
+            ).with_stall_timeout(self.config.stall_timeout, &self.metrics).with_transport(self.config.transport());
+
+            let span = stream_span(&format!("combined#{}", i));
+            tasks.push(spawn(async move {
+                tracing::info!("Starting combined stream: '{}' ('{}')", i, names.join("', '"));
+                combined_stream.run().await;
+            }.instrument(span)));
+        }
+    }
+
+    /// Route of connection `index` of the stream of the given kind within a combined connection
+    ///
+    /// # Returns
+    /// The stream name with its route, or `None` if the exchange doesn't provide the stream
+    fn stream_route<T>(
+        &self,
+        kind: StreamKind,
+        index: u64,
+        output: &mpsc::Sender<MarketEvent>,
+        recorder: &dyn Fn(String) -> Option<TapeRecorder>,
+        status_board: &StatusBoard,
+    ) -> Option<(String, StreamRoute)>
+    where T: MarketEventSource,
+    {
+        let Some(name) = self.connector.stream_name(kind, &self.config.instrument) else {
+            tracing::info!("Exchange '{}' doesn't provide '{}' stream. Skipping", self.connector.name(), kind);
+            return None;
+        };
+
+        let source = format!("{}#{}", kind, index);
+        Some((name, StreamRoute::new::<T>(output.clone(), recorder(source.clone()), Some(status_board.stream(source)))))
+    }
+
+    /// Spawn a single connection of a stream, whose events are forwarded without dispatching (e.g. klines)
+    ///
+    /// The stream is skipped if the exchange doesn't provide it
+    fn spawn_auxiliary_stream<T>(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        kind: StreamKind,
+        output: &mpsc::Sender<MarketEvent>,
+        recorder: Option<TapeRecorder>,
+        status: &StatusBoard,
+    ) where T: MarketEventSource,
+    {
+        let Some(url) = self.connector.stream_url(kind, &self.config.instrument) else {
+            tracing::info!("Exchange '{}' doesn't provide '{}' stream. Skipping", self.connector.name(), kind);
+            return;
+        };
+
+        let stream = MarketEventStream::<T>::new(
+            url,
+            output.clone(),
+            self.config.reconnect_timeout,
+            recorder,
+            None,
+            Some(status.stream(format!("{}#0", kind)))
+        ).with_stall_timeout(self.config.stall_timeout, &self.metrics);
+        let mut stream = self.subscribe(stream, kind);
+
+        let span = stream_span(&format!("{}#0", kind));
+        tasks.push(spawn(async move {
+            tracing::info!("Starting '{}' stream", kind);
+            stream.run().await;
+        }.instrument(span)));
+    }
+
+    /// Replay a recorded tape through the processing pipeline
+    ///
+    /// # Arguments
+    /// * `path` - Path of the tape file
+    /// * `speed` - Replay speed multiplier (1.0 - original speed, 0 - as fast as possible)
+    pub async fn replay(&self, path: PathBuf, speed: f64) -> Result<()> {
+        self.replay_tape(path, speed).instrument(self.span()).await
+    }
+
+    async fn replay_tape(&self, path: PathBuf, speed: f64) -> Result<()> {
+        let scripts = EventScripts::compile(&self.config.scripts)?;
+        let mut tasks = Vec::new();
+        let inputs = self.spawn_processing(&mut tasks, &path.with_extension("replay"), None, &CaptureGate::new(), None, None, scripts);
+
+        let mut replayer = TapeReplayer::new(
+            path,
+            speed,
+            inputs.depth,
+            inputs.trade,
+            inputs.price,
+            inputs.agg_trade,
+            inputs.auxiliary
+        );
+
+        let (depth_decoder, trade_decoder) = self.frame_decoders();
+        replayer = replayer.with_decoders(depth_decoder, trade_decoder);
+
+        tasks.push(spawn(async move {
+            tracing::info!("Starting tape replayer");
+            replayer.run().await;
         }));
-        
+
         for handle in tasks {
             handle.await?;
         }
 
         Ok(())
     }
+
+    /// Decoders of the recorded depth and trade frames of the venue
+    fn frame_decoders(&self) -> FrameDecoders {
+        match self.config.exchange {
+            Exchange::Okx => (frame_decoder::<OkxBookMessage>, frame_decoder::<OkxTradeMessage>),
+            Exchange::Bitfinex => (frame_decoder::<BitfinexBookMessage>, frame_decoder::<BitfinexTradeMessage>),
+            Exchange::Deribit => (frame_decoder::<DeribitBookMessage>, frame_decoder::<DeribitTradeMessage>),
+            _ => (frame_decoder::<DepthUpdate>, frame_decoder::<TradeEvent>),
+        }
+    }
+
+    /// Scan a tape and print the summary of its content and integrity
+    ///
+    /// # Arguments
+    /// * `path` - Tape file to inspect
+    /// * `silence` - Time in milliseconds without records, which is reported as a silence
+    /// * `json` - Print the summary as JSON
+    ///
+    /// # Returns
+    /// An error if the tape has integrity issues, after the summary has been printed
+    pub async fn inspect(&self, path: PathBuf, silence: u64, json: bool) -> Result<()> {
+        let limits = BufferLimits {
+            max_size: self.config.dispatcher_buffer_size,
+            max_age: self.config.dispatcher_buffer_max_age,
+        };
+        let summary = inspect_tape(&path, self.connector.sequencing_rules(), limits, self.frame_decoders(), silence).await?;
+
+        match json {
+            true => println!("{}", serde_json::to_string_pretty(&summary)?),
+            false => println!("{}", summary),
+        }
+
+        match summary.is_intact() {
+            true => Ok(()),
+            false => Err(anyhow!("Tape {:?} has integrity issues", path)),
+        }
+    }
+
+    /// Reconstruct the book at a point in time of a tape and print it or export it as JSON
+    ///
+    /// # Arguments
+    /// * `path` - Tape file to replay
+    /// * `time` - Receive time, up to which the tape is replayed
+    /// * `depth` - Levels per side. The configured output depth if not set
+    /// * `output` - File, which the book is written into as a JSON book frame. The book is printed if not set
+    pub async fn book_at(&self, path: PathBuf, time: DateTime<Utc>, depth: Option<usize>, output: Option<PathBuf>) -> Result<()> {
+        let end_time = time.timestamp_nanos_opt().ok_or_else(|| anyhow!("Time '{}' is out of range", time))?;
+        let limits = BufferLimits {
+            max_size: self.config.dispatcher_buffer_size,
+            max_age: self.config.dispatcher_buffer_max_age,
+        };
+
+        let depth = depth.unwrap_or(self.config.output_depth);
+        let reconstructed = reconstruct_book(path.clone(), end_time.max(0) as u64, depth, self.connector.sequencing_rules(), limits, self.frame_decoders())
+            .await?
+            .ok_or_else(|| anyhow!("No book is complete at '{}' in {:?}. The first snapshot is received later", time.to_rfc3339(), path))?;
+
+        match output {
+            Some(output) => {
+                let frame = BookFrame::new(reconstructed.updates, time.timestamp_millis(), &reconstructed.book, depth);
+                fs::write(&output, serde_json::to_string_pretty(&frame)?)
+                    .with_context(|| format!("Failed to write book: {:?}", output))?;
+                println!("Book at '{}' written to: {:?}", time.to_rfc3339(), output);
+            }
+            None => {
+                println!("Book at '{}' after '{}' book updates", time.to_rfc3339(), reconstructed.updates);
+                print!("{}", reconstructed.book.format_top(depth));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serve synthetic Binance spot market data of the instrument until the simulator fails
+    ///
+    /// A pipeline captures it with `rest_endpoint` and `wss_endpoint` pointing at the simulator
+    pub async fn simulate(&self) -> Result<()> {
+        let settings = self.config.simulator.clone().unwrap_or_default();
+        let simulator = ExchangeSimulator::bind(&self.config.instrument, settings).await?;
+
+        tracing::info!(
+            "Simulated exchange is listening. Set 'rest_endpoint: http://{}/api/v3/' and 'wss_endpoint: ws://{}/ws/' to capture it",
+            simulator.rest_address()?,
+            simulator.ws_address()?,
+        );
+        simulator.run().await
+    }
+
+    /// Capture the instrument and show its book, the latest trades and the spread in the terminal until the TUI is quit
+    ///
+    /// # Arguments
+    /// * `depth` - Levels per side of the depth ladder
+    /// * `record` - Record every raw frame into a tape file
+    /// * `force` - Start even if another running instance captures the same instrument
+    pub async fn tui(&self, depth: usize, record: bool, force: bool) -> Result<()> {
+        let view = Arc::new(Mutex::new(BookView::new(depth)));
+        self.sinks.lock().expect("Pipeline sinks lock is poisoned").push(Box::new(BookViewSink::new(view.clone())));
+
+        let tui = BookTui::new(view, format!("{} {}", self.connector.name(), self.config.instrument));
+        // The TUI restores the terminal within a redraw interval once the capture stops, e.g. on a signal
+        let _stop = StopOnDrop(tui.stop_flag());
+        let mut terminal = tokio::task::spawn_blocking(move || tui.run());
+
+        tokio::select! {
+            result = self.start(record, force) => result,
+            result = &mut terminal => result?,
+        }
+    }
+
+    /// Print the current state of the running instance, which captures the symbol
+    ///
+    /// # Arguments
+    /// * `symbol` - The instrument of the instance. The configured instrument is used if not set
+    pub async fn top(&self, symbol: Option<String>) -> Result<()> {
+        let instrument = symbol.unwrap_or_else(|| self.config.instrument.clone());
+        let path = admin_socket_path(&self.config.capture_dir, self.connector.name(), &instrument);
+
+        let status = query_status(&path).await?;
+        print!("{}", status);
+
+        Ok(())
+    }
+
+    /// Send a command to the admin socket of a running instance and print the response
+    ///
+    /// # Arguments
+    /// * `symbol` - Instrument of the instance. The configured instrument if not set
+    /// * `command` - The command line, e.g. "pause" or "book 10"
+    pub async fn admin(&self, symbol: Option<String>, command: &str) -> Result<()> {
+        let command: AdminCommand = command.parse()?;
+        let instrument = symbol.unwrap_or_else(|| self.config.instrument.clone());
+        let path = admin_socket_path(&self.config.capture_dir, self.connector.name(), &instrument);
+
+        println!("{}", send_command(&path, &command).await?);
+        Ok(())
+    }
+
+    /// Merge overlapping tapes into a single tape without duplicates and print the statistics
+    ///
+    /// # Arguments
+    /// * `inputs` - Tapes to merge, e.g. recorded by redundant hosts
+    /// * `output` - Path of the merged tape. Must not exist
+    pub async fn compact(&self, inputs: Vec<PathBuf>, output: PathBuf) -> Result<()> {
+        let stats = compact_tapes(&inputs, &output).await?;
+        println!("{}", stats);
+        println!("Compacted tape written to: {:?}", output);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::mdc_server::live_status::{LiveStatus, StatusBoard};
+
+/// Data quality statistics of a capture session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionReport {
+    pub exchange: String,
+    pub instrument: String,
+    /// Name of the session, the prefix of its capture files
+    pub session: String,
+    pub started_at: i64,
+    /// Time the report was taken at
+    pub generated_at: i64,
+    /// Uptime of the session in milliseconds
+    pub uptime: u64,
+    /// Depth update sequence gaps detected by the dispatcher (`depth_gaps`)
+    pub gaps: u64,
+    /// Snapshots, which restarted the depth update sequence, and books replaced by validation snapshots
+    /// (`depth_resyncs` and `book_resyncs`)
+    pub resyncs: u64,
+    /// Reconnects of all WebSocket streams
+    pub reconnects: u64,
+    /// Depth updates evicted from the dispatcher buffer and messages dropped by slow consumer policies
+    /// (`dispatcher_evicted_*` and `channel_dropped_*`)
+    pub dropped_events: u64,
+    /// Maximum number of depth updates, which waited in the dispatcher buffer (`dispatcher_buffer_max`)
+    pub max_dispatcher_buffer: u64,
+    /// All counters of the session
+    pub counters: BTreeMap<String, u64>,
+}
+
+impl SessionReport {
+    /// Summarize the status of a running session
+    ///
+    /// # Arguments
+    /// * `session` - Name of the session
+    /// * `status` - The current status of the session
+    pub fn new(session: &str, status: &LiveStatus) -> Self {
+        let counter = |name: &str| status.counters.get(name).copied().unwrap_or_default();
+        let dropped_events = status.counters
+            .iter()
+            .filter(|(name, _)| name.starts_with("dispatcher_evicted_") || name.starts_with("channel_dropped_"))
+            .map(|(_, value)| value)
+            .sum();
+
+        Self {
+            exchange: status.exchange.clone(),
+            instrument: status.instrument.clone(),
+            session: session.to_string(),
+            started_at: status.started_at,
+            generated_at: status.generated_at,
+            uptime: status.generated_at.saturating_sub(status.started_at).max(0) as u64,
+            gaps: counter("depth_gaps"),
+            resyncs: counter("depth_resyncs") + counter("book_resyncs"),
+            reconnects: status.streams.values().map(|stream| stream.reconnects).sum(),
+            dropped_events,
+            max_dispatcher_buffer: status.gauges.get("dispatcher_buffer_max").copied().unwrap_or_default().max(0) as u64,
+            counters: status.counters.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SessionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Session: '{}', Uptime: '{}' s, Gaps: '{}', Resyncs: '{}', Reconnects: '{}', Dropped events: '{}', Max dispatcher buffer: '{}'",
+            self.session,
+            self.uptime / 1000,
+            self.gaps,
+            self.resyncs,
+            self.reconnects,
+            self.dropped_events,
+            self.max_dispatcher_buffer,
+        )
+    }
+}
+
+/// Takes the SessionReport of a running session from its status board and writes it into the capture directory
+#[derive(Clone)]
+pub struct SessionReporter {
+    board: StatusBoard,
+    session: String,
+    path: PathBuf,
+}
+
+impl SessionReporter {
+    /// Create a new SessionReporter
+    ///
+    /// # Arguments
+    /// * `board` - Status board of the session
+    /// * `session` - Name of the session
+    /// * `path` - Path of the report file, which every written report replaces
+    pub fn new(board: StatusBoard, session: String, path: PathBuf) -> Self {
+        Self { board, session, path }
+    }
+
+    /// Take the report of the session
+    pub fn report(&self) -> SessionReport {
+        SessionReport::new(&self.session, &self.board.snapshot())
+    }
+
+    /// Take the report of the session and write it into the report file as JSON
+    pub fn write(&self) -> Result<SessionReport> {
+        let report = self.report();
+        write_report(&self.path, &report)?;
+        Ok(report)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn write_report(path: &Path, report: &SessionReport) -> Result<()> {
+    let data = serde_json::to_string_pretty(report)?;
+    fs::write(path, data).with_context(|| format!("Failed to write session report: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_server::metrics::Metrics;
+
+    #[test]
+    fn test_report_summarizes_status() {
+        let metrics = Metrics::new();
+        metrics.counter("depth_gaps").increment(2);
+        metrics.counter("depth_resyncs").increment(1);
+        metrics.counter("book_resyncs").increment(1);
+        metrics.counter("dispatcher_evicted_by_size").increment(3);
+        metrics.counter("channel_dropped_book").increment(4);
+        metrics.counter("stream_stalls").increment(5);
+        metrics.gauge("dispatcher_buffer_max").set(12);
+
+        let board = StatusBoard::new("binance", "BTCUSDT", metrics);
+        let stream = board.stream("depth#0".to_string());
+        stream.on_connected();
+        stream.on_disconnected();
+        stream.on_connected();
+
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_session.report.json", std::process::id()));
+        let reporter = SessionReporter::new(board, "BTCUSDT_20240101_000000".to_string(), path.clone());
+        let report = reporter.write().unwrap();
+        assert_eq!((report.gaps, report.resyncs, report.reconnects), (2, 2, 1));
+        assert_eq!((report.dropped_events, report.max_dispatcher_buffer), (7, 12));
+        assert_eq!(report.counters["stream_stalls"], 5);
+
+        let written: SessionReport = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, report);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Delay before a rotation, which has been postponed because no other connection of the group is connected
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Redundant connections of a single stream, whose sessions are rotated in turns
+#[derive(Debug, Clone)]
+pub struct RotationGroup {
+    size: u64,
+    lifetime: Duration,
+    connected: Arc<AtomicU64>,
+}
+
+impl RotationGroup {
+    /// Create a new RotationGroup
+    ///
+    /// # Arguments
+    /// * `size` - Number of connections in the group
+    /// * `lifetime` - Session lifetime in milliseconds, after which a connection is rotated
+    pub fn new(size: u64, lifetime: u64) -> Self {
+        Self {
+            size,
+            lifetime: Duration::from_millis(lifetime),
+            connected: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Rotation of the connection with the given index in the group
+    ///
+    /// The first sessions of the connections are shortened, so the connections are rotated evenly spread
+    /// over the lifetime instead of all at once
+    pub fn member(&self, index: u64) -> SessionRotation {
+        let offset = self.lifetime.mul_f64(index as f64 / self.size.max(1) as f64);
+        SessionRotation {
+            group: self.clone(),
+            first_session: true,
+            offset,
+            connected: false,
+            deadline: Instant::now(),
+        }
+    }
+}
+
+/// SessionRotation ends the sessions of a connection before the exchange does it (Binance closes WebSocket
+/// connections after 24 hours)
+///
+/// A connection is only rotated if another connection of its group is connected, so the group always has
+/// continuous coverage. A connection, which is the only one of its group, is rotated anyway
+pub struct SessionRotation {
+    group: RotationGroup,
+    first_session: bool,
+    offset: Duration,
+    connected: bool,
+    deadline: Instant,
+}
+
+impl SessionRotation {
+    /// Account a new session of the connection and schedule its rotation
+    pub fn on_connected(&mut self) {
+        let lifetime = if self.first_session { self.group.lifetime.saturating_sub(self.offset) } else { self.group.lifetime };
+        self.first_session = false;
+        self.deadline = Instant::now() + lifetime;
+
+        if !self.connected {
+            self.connected = true;
+            self.group.connected.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Account the end of a session, which has not been rotated
+    pub fn on_disconnected(&mut self) {
+        if self.connected {
+            self.connected = false;
+            self.group.connected.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Time, at which the session should be rotated
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Try to rotate the session
+    ///
+    /// # Returns
+    /// `true` if the session should be ended now. Otherwise the rotation is postponed
+    pub fn try_rotate(&mut self) -> bool {
+        let is_alone = self.group.size <= 1;
+        let rotated = self.group.connected
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |connected| (connected > 1 || is_alone).then(|| connected.saturating_sub(1)))
+            .is_ok();
+
+        if rotated {
+            self.connected = false;
+        } else {
+            self.deadline = Instant::now() + RETRY_DELAY;
+        }
+        rotated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_sessions_are_staggered() {
+        let group = RotationGroup::new(3, 90_000);
+        let mut first = group.member(0);
+        let mut last = group.member(2);
+
+        first.on_connected();
+        last.on_connected();
+        let now = Instant::now();
+        assert!(first.deadline() > now + Duration::from_secs(89));
+        assert!(last.deadline() > now + Duration::from_secs(29) && last.deadline() <= now + Duration::from_secs(30));
+
+        // The following sessions last the whole lifetime
+        last.on_connected();
+        assert!(last.deadline() > now + Duration::from_secs(89));
+    }
+
+    #[test]
+    fn test_rotation_keeps_one_connection() {
+        let group = RotationGroup::new(2, 60_000);
+        let mut first = group.member(0);
+        let mut second = group.member(1);
+
+        first.on_connected();
+        second.on_connected();
+
+        assert!(first.try_rotate());
+        // The first connection is reconnecting, the second one has to wait
+        assert!(!second.try_rotate());
+        assert!(second.deadline() > Instant::now());
+
+        first.on_connected();
+        assert!(second.try_rotate());
+
+        // A connection, which has been lost, doesn't cover the group
+        second.on_connected();
+        second.on_disconnected();
+        assert!(!first.try_rotate());
+    }
+
+    #[test]
+    fn test_single_connection_is_rotated() {
+        let mut rotation = RotationGroup::new(1, 60_000).member(0);
+        rotation.on_connected();
+        assert!(rotation.try_rotate());
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+use anyhow::{anyhow, Error};
+use tokio::time::Instant;
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// StallWatchdog detects WebSocket connections, which stay open but stop delivering messages
+///
+/// Every frame received over the connection (including pings) feeds the watchdog. If no frame arrives within
+/// the stall timeout, the connection is considered stalled and should be reconnected
+pub struct StallWatchdog {
+    timeout: Duration,
+    last_message: Instant,
+    stalls: Counter,
+}
+
+impl StallWatchdog {
+    /// Create a new StallWatchdog
+    ///
+    /// # Arguments
+    /// * `timeout` - Time in milliseconds without messages, after which the connection is stalled
+    /// * `metrics` - Registry of the `stream_stalls` counter
+    pub fn new(timeout: u64, metrics: &Metrics) -> Self {
+        Self {
+            timeout: Duration::from_millis(timeout),
+            last_message: Instant::now(),
+            stalls: metrics.counter("stream_stalls"),
+        }
+    }
+
+    /// Account a frame received over the connection (or a new connection)
+    pub fn on_message(&mut self) {
+        self.last_message = Instant::now();
+    }
+
+    /// Time, at which the connection is stalled if no frame arrives until then
+    pub fn deadline(&self) -> Instant {
+        self.last_message + self.timeout
+    }
+
+    /// Account a stall of the connection
+    ///
+    /// # Returns
+    /// The error, which finishes the session of the connection
+    pub fn on_stall(&self, url: &str) -> Error {
+        self.stalls.increment(1);
+        tracing::warn!("Session '{}' is stalled: no messages for '{}' ms. Reconnecting", url, self.timeout.as_millis());
+        anyhow!("No messages received for '{}' ms", self.timeout.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_is_extended_by_messages() {
+        let metrics = Metrics::new();
+        let mut watchdog = StallWatchdog::new(30_000, &metrics);
+        let first_deadline = watchdog.deadline();
+        assert!(first_deadline > Instant::now() + Duration::from_millis(29_000));
+
+        std::thread::sleep(Duration::from_millis(5));
+        watchdog.on_message();
+        assert!(watchdog.deadline() > first_deadline);
+
+        watchdog.on_stall("wss://localhost/ws/btcusdt@trade");
+        watchdog.on_stall("wss://localhost/ws/btcusdt@trade");
+        assert_eq!(metrics.snapshot()["stream_stalls"], 2);
+    }
+}
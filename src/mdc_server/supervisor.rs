@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::{anyhow, Context, Result};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{AbortHandle, Id, JoinSet};
+use crate::mdc_server::all_market_prices::{AllMarketPrices, PriceSource};
+use crate::mdc_server::arbitrage_monitor::{ArbitrageMonitor, QuoteSink, VenueQuote};
+use crate::mdc_server::config::{check_pipelines, Config};
+use crate::mdc_server::exchange_connector::{create_connector, Exchange, StreamKind};
+use crate::mdc_server::server::MDCServer;
+#[cfg(unix)]
+use crate::mdc_server::systemd_notifier::{SystemdHandle, SystemdNotifier};
+
+/// Request to change the set of running pipelines
+enum PipelineRequest {
+    Add { config: Box<Config>, reply: oneshot::Sender<Result<String>> },
+    Remove { exchange: Exchange, instrument: String, reply: oneshot::Sender<Result<String>> },
+}
+
+/// A cloneable handle, used by the admin sockets of the pipelines to add and remove pipelines
+#[derive(Clone)]
+pub struct SupervisorHandle {
+    requests: mpsc::Sender<PipelineRequest>,
+}
+
+impl SupervisorHandle {
+    async fn request(&self, request: PipelineRequest, reply: oneshot::Receiver<Result<String>>) -> Result<String> {
+        self.requests.send(request).await.map_err(|_| anyhow!("Pipeline supervisor is stopped"))?;
+        reply.await.map_err(|_| anyhow!("Pipeline supervisor is stopped"))?
+    }
+
+    /// Start capturing the instrument with the configuration of the template pipeline
+    ///
+    /// Listen addresses and the fast output of the template are not inherited, since they can't be shared
+    ///
+    /// # Returns
+    /// A message for the operator, or an error if the instrument is already captured
+    pub async fn add(&self, template: &Config, instrument: &str) -> Result<String> {
+        let mut config = template.clone();
+        config.instrument = config.exchange.normalize_symbol(instrument);
+        config.grpc_listen = None;
+        config.rest_listen = None;
+        config.event_feed_listen = None;
+        config.fast_output = None;
+
+        let (reply, receiver) = oneshot::channel();
+        self.request(PipelineRequest::Add { config: Box::new(config), reply }, receiver).await
+    }
+
+    /// Stop capturing the instrument of the exchange
+    ///
+    /// # Returns
+    /// A message for the operator, or an error if the instrument is not captured or it is the last one
+    pub async fn remove(&self, exchange: Exchange, instrument: &str) -> Result<String> {
+        let (reply, receiver) = oneshot::channel();
+        let instrument = exchange.normalize_symbol(instrument);
+        self.request(PipelineRequest::Remove { exchange, instrument, reply }, receiver).await
+    }
+}
+
+/// A running pipeline
+struct Pipeline {
+    config: Config,
+    abort: AbortHandle,
+    /// Whether the pipeline has been added at runtime
+    added: bool,
+}
+
+/// PipelineSupervisor runs the capture pipelines of the process and adds or removes them on request
+///
+/// Like with a static set of pipelines, a failure of a configured pipeline stops the process. A failure of a pipeline
+/// added at runtime is only logged
+pub struct PipelineSupervisor {
+    record: bool,
+    force: bool,
+    requests: mpsc::Receiver<PipelineRequest>,
+    handle: SupervisorHandle,
+    tasks: JoinSet<Result<()>>,
+    pipelines: HashMap<Id, Pipeline>,
+    /// Sender to the ArbitrageMonitor, if it runs
+    quotes: Option<mpsc::Sender<VenueQuote>>,
+    /// Handle of the SystemdNotifier, if the process runs as a systemd service
+    #[cfg(unix)]
+    systemd: Option<SystemdHandle>,
+    /// All-market price streams by their URL, started by the first pipeline with `price_source: all_market`
+    all_market_prices: HashMap<String, AllMarketPrices>,
+}
+
+impl PipelineSupervisor {
+    /// Create a new PipelineSupervisor
+    ///
+    /// # Arguments
+    /// * `record` - Record every raw frame into a tape file
+    /// * `force` - Start even if another running instance captures the same instrument
+    pub fn new(record: bool, force: bool) -> Self {
+        let (sender, requests) = mpsc::channel(10);
+        Self {
+            record,
+            force,
+            requests,
+            handle: SupervisorHandle { requests: sender },
+            tasks: JoinSet::new(),
+            pipelines: HashMap::new(),
+            quotes: None,
+            #[cfg(unix)]
+            systemd: None,
+            all_market_prices: HashMap::new(),
+        }
+    }
+
+    /// The all-market price stream of the pipeline's exchange, if the pipeline takes its prices from it
+    ///
+    /// The stream is started with the first pipeline, which needs it, and runs as long as the process
+    fn all_market_prices(&mut self, config: &Config) -> Option<AllMarketPrices> {
+        if config.price_source != PriceSource::AllMarket {
+            return None;
+        }
+
+        let url = create_connector(config).stream_url(StreamKind::AllMarketPrice, &config.instrument)?;
+        let all_market_prices = self.all_market_prices.entry(url.clone()).or_insert_with(|| {
+            // The tasks are detached, since the stream outlives the pipelines
+            let mut tasks = Vec::new();
+            AllMarketPrices::start(&mut tasks, url, config.reconnect_timeout, config.transport())
+        });
+        Some(all_market_prices.clone())
+    }
+
+    fn spawn(&mut self, config: Config, added: bool) {
+        let mut mdc_server = MDCServer::new(config.clone()).with_supervisor(self.handle.clone());
+        if let (Some(quotes), Some(threshold_bps)) = (&self.quotes, config.arbitrage_threshold_bps) {
+            let sink = QuoteSink::new(config.exchange, config.canonical_symbol(), threshold_bps, quotes.clone());
+            mdc_server = mdc_server.with_sinks(vec![Box::new(sink)]);
+        }
+        #[cfg(unix)]
+        if let Some(systemd) = &self.systemd {
+            mdc_server = mdc_server.with_systemd(systemd.clone());
+        }
+        if let Some(all_market_prices) = self.all_market_prices(&config) {
+            mdc_server = mdc_server.with_all_market_prices(all_market_prices);
+        }
+
+        let (record, force) = (self.record, self.force);
+        let abort = self.tasks.spawn(async move { mdc_server.start(record, force).await });
+        self.pipelines.insert(abort.id(), Pipeline { config, abort, added });
+    }
+
+    fn on_request(&mut self, request: PipelineRequest) {
+        match request {
+            PipelineRequest::Add { config, reply } => {
+                let _ = reply.send(self.add(*config));
+            }
+            PipelineRequest::Remove { exchange, instrument, reply } => {
+                let _ = reply.send(self.remove(exchange, &instrument));
+            }
+        }
+    }
+
+    fn add(&mut self, config: Config) -> Result<String> {
+        let mut configs: Vec<Config> = self.pipelines.values().map(|pipeline| pipeline.config.clone()).collect();
+        configs.push(config.clone());
+        check_pipelines(&configs)?;
+
+        tracing::info!("Adding pipeline of '{}'", config.instrument);
+        let message = format!("Started capturing '{}'", config.instrument);
+        self.spawn(config, true);
+        Ok(message)
+    }
+
+    fn remove(&mut self, exchange: Exchange, instrument: &str) -> Result<String> {
+        let id = self.pipelines
+            .iter()
+            .find(|(_, pipeline)| pipeline.config.exchange == exchange && pipeline.config.instrument == instrument)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| anyhow!("Instrument '{}' is not captured", instrument))?;
+
+        if self.pipelines.len() == 1 {
+            return Err(anyhow!("'{}' is the last captured instrument. Stop the instance instead", instrument));
+        }
+
+        tracing::info!("Removing pipeline of '{}'", instrument);
+        if let Some(pipeline) = self.pipelines.remove(&id) {
+            pipeline.abort.abort();
+        }
+        Ok(format!("Stopped capturing '{}'", instrument))
+    }
+
+    /// Start the ArbitrageMonitor, if any of the pipelines has an arbitrage threshold
+    ///
+    /// Opportunities are written into `arbitrage.jsonl` in the capture directory of the first of these pipelines.
+    /// The monitor stops once the supervisor and the pipelines are gone
+    fn start_arbitrage_monitor(&mut self, pipelines: &[Config]) -> Result<()> {
+        let Some(first) = pipelines.iter().find(|config| config.arbitrage_threshold_bps.is_some()) else {
+            return Ok(());
+        };
+
+        std::fs::create_dir_all(&first.capture_dir)
+            .with_context(|| format!("Failed to create capture directory: {:?}", first.capture_dir))?;
+        let (sender, receiver) = mpsc::channel(1000);
+        let monitor = ArbitrageMonitor::new(receiver)
+            .with_output(&PathBuf::from(&first.capture_dir).join("arbitrage.jsonl"))?;
+        self.quotes = Some(sender);
+
+        tokio::spawn(async move {
+            tracing::info!("Starting arbitrage monitor");
+            monitor.run().await;
+        });
+
+        Ok(())
+    }
+
+    /// Start the SystemdNotifier, if the process runs as a systemd service
+    ///
+    /// The process is ready once the configured pipelines are started
+    #[cfg(unix)]
+    fn start_systemd_notifier(&mut self, pipelines: &[Config]) {
+        let Some(notifier) = SystemdNotifier::from_env(pipelines.len()) else {
+            return;
+        };
+        self.systemd = Some(notifier.handle());
+
+        tokio::spawn(async move {
+            tracing::info!("Starting systemd notifier");
+            notifier.run().await;
+        });
+    }
+
+    /// Run the pipelines until all of them are finished or one of the configured pipelines fails
+    ///
+    /// # Arguments
+    /// * `pipelines` - The configured pipelines
+    pub async fn run(mut self, pipelines: Vec<Config>) -> Result<()> {
+        self.start_arbitrage_monitor(&pipelines)?;
+        #[cfg(unix)]
+        self.start_systemd_notifier(&pipelines);
+
+        for config in pipelines {
+            self.spawn(config, false);
+        }
+
+        loop {
+            tokio::select! {
+                Some(request) = self.requests.recv() => self.on_request(request),
+                finished = self.tasks.join_next_with_id() => {
+                    let Some(finished) = finished else {
+                        break;
+                    };
+
+                    match finished {
+                        Ok((id, result)) => {
+                            let pipeline = self.pipelines.remove(&id);
+                            match (result, pipeline) {
+                                (Err(e), Some(pipeline)) if pipeline.added => {
+                                    tracing::error!("Pipeline of '{}' failed. Details: '{:#}'", pipeline.config.instrument, e);
+                                }
+                                (Err(e), _) => return Err(e),
+                                (Ok(()), _) => {}
+                            }
+                        }
+                        // Removed pipelines are cancelled
+                        Err(e) if e.is_cancelled() => {}
+                        Err(e) => return Err(anyhow!("Pipeline panicked. Details: '{}'", e)),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
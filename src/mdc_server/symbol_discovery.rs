@@ -0,0 +1,255 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::common::pattern::matches_pattern;
+use crate::mdc_core::models::SymbolMetadata;
+use crate::mdc_server::config::{check_pipelines, Config};
+use crate::mdc_server::exchange_connector::{create_connector, ExchangeConnector};
+use crate::mdc_server::transport::Transport;
+
+/// Filters of the symbols, which a pipeline with an instrument pattern (e.g. `*USDT`) captures
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolDiscovery {
+    /// Only symbols quoted in the asset, e.g. `USDT`
+    #[serde(default)]
+    pub quote_asset: Option<String>,
+    /// Upper bound of the matched symbols. A pattern matching more fails the startup
+    #[serde(default = "default_max_symbols")]
+    pub max_symbols: usize,
+    /// Symbols or patterns, which are never captured
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+fn default_max_symbols() -> usize {
+    20
+}
+
+impl Default for SymbolDiscovery {
+    fn default() -> Self {
+        Self {
+            quote_asset: None,
+            max_symbols: default_max_symbols(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl SymbolDiscovery {
+    /// Whether the symbol passes the quote asset filter and isn't excluded
+    fn accepts(&self, metadata: &SymbolMetadata) -> bool {
+        let quoted = self.quote_asset.as_ref().is_none_or(|quote| quote.eq_ignore_ascii_case(&metadata.quote_asset));
+        quoted && !self.exclude.iter().any(|excluded| matches_pattern(excluded, &metadata.symbol))
+    }
+}
+
+/// Whether the instrument of a pipeline is a pattern, which is resolved into symbols at startup
+pub fn is_pattern(instrument: &str) -> bool {
+    instrument.contains(['*', '?'])
+}
+
+/// Select the trading symbols, which the pipeline's pattern and discovery filters match, in alphabetical order
+///
+/// # Arguments
+/// * `config` - The pipeline with the instrument pattern
+/// * `connector` - The connector of the pipeline's exchange
+/// * `symbols` - Metadata of all symbols of the exchange
+/// * `taken` - Symbols of the exchange, which other pipelines capture
+///
+/// # Errors
+/// If no symbol or more than `max_symbols` symbols match
+pub fn select_symbols(
+    config: &Config,
+    connector: &dyn ExchangeConnector,
+    symbols: &[SymbolMetadata],
+    taken: &[&str],
+) -> Result<Vec<String>> {
+    let discovery = &config.symbol_discovery;
+    let mut selected: Vec<String> = symbols
+        .iter()
+        .filter(|metadata| matches_pattern(&config.instrument, &metadata.symbol))
+        .filter(|metadata| connector.is_trading(metadata) && discovery.accepts(metadata))
+        .map(|metadata| metadata.symbol.clone())
+        .filter(|symbol| !taken.contains(&symbol.as_str()))
+        .collect();
+    selected.sort();
+    selected.dedup();
+
+    if selected.is_empty() {
+        return Err(anyhow!("Pattern '{}' doesn't match any trading symbol", config.instrument));
+    }
+    if selected.len() > discovery.max_symbols {
+        return Err(anyhow!(
+            "Pattern '{}' matches '{}' symbols, more than 'max_symbols' ('{}'). Narrow it down or raise the bound",
+            config.instrument,
+            selected.len(),
+            discovery.max_symbols
+        ));
+    }
+
+    Ok(selected)
+}
+
+/// Request the metadata of all symbols of the exchange
+async fn fetch_symbols(connector: &dyn ExchangeConnector, transport: &Transport) -> Result<Vec<SymbolMetadata>> {
+    let url = connector
+        .symbols_url()
+        .ok_or_else(|| anyhow!("Exchange '{}' doesn't support symbol discovery", connector.name()))?;
+
+    let response_text = transport.get(&url)
+        .await
+        .context("Failed to send symbols request")?
+        .error_for_status()
+        .context("Failed to get symbols response")?
+        .text()
+        .await
+        .context("Failed to get response text for symbols")?;
+
+    connector.parse_symbols(&response_text)
+}
+
+/// Replace every pipeline, whose instrument is a pattern, with one pipeline per matched symbol
+///
+/// The symbols are resolved from the exchange once at startup. Symbols, which other pipelines of the exchange
+/// capture, are skipped, so a symbol can be configured separately. Only the first pipeline of a pattern keeps
+/// the listen addresses and the fast output, since they can't be shared
+///
+/// # Errors
+/// If the symbols can't be requested, a pattern matches no symbols or too many, or the resulting pipelines
+/// can't run in the same process
+pub async fn discover_pipelines(pipelines: Vec<Config>) -> Result<Vec<Config>> {
+    if !pipelines.iter().any(|pipeline| is_pattern(&pipeline.instrument)) {
+        return Ok(pipelines);
+    }
+
+    let configured: Vec<(_, String)> = pipelines
+        .iter()
+        .filter(|pipeline| !is_pattern(&pipeline.instrument))
+        .map(|pipeline| (pipeline.exchange, pipeline.instrument.clone()))
+        .collect();
+
+    let mut discovered = Vec::new();
+    for pipeline in pipelines {
+        if !is_pattern(&pipeline.instrument) {
+            discovered.push(pipeline);
+            continue;
+        }
+
+        let connector = create_connector(&pipeline);
+        let symbols = fetch_symbols(connector.as_ref(), &pipeline.transport())
+            .await
+            .with_context(|| format!("Failed to resolve instrument pattern '{}'", pipeline.instrument))?;
+        let taken: Vec<&str> = configured
+            .iter()
+            .filter(|(exchange, _)| *exchange == pipeline.exchange)
+            .map(|(_, instrument)| instrument.as_str())
+            .collect();
+        let selected = select_symbols(&pipeline, connector.as_ref(), &symbols, &taken)?;
+        tracing::info!("Instrument pattern '{}' matches: '{}'", pipeline.instrument, selected.join(", "));
+
+        for (index, symbol) in selected.into_iter().enumerate() {
+            let mut config = pipeline.clone();
+            config.instrument = symbol;
+            if index > 0 {
+                config.grpc_listen = None;
+                config.rest_listen = None;
+                config.event_feed_listen = None;
+                config.fast_output = None;
+            }
+            discovered.push(config);
+        }
+    }
+
+    check_pipelines(&discovered)?;
+    Ok(discovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_server::binance_connector::BinanceConnector;
+    use crate::mdc_server::config::load_pipelines_from_yaml_str;
+
+    fn metadata(symbol: &str, quote_asset: &str, status: &str) -> SymbolMetadata {
+        SymbolMetadata {
+            symbol: symbol.to_string(),
+            status: status.to_string(),
+            base_asset: symbol.strip_suffix(quote_asset).unwrap_or(symbol).to_string(),
+            quote_asset: quote_asset.to_string(),
+            tick_size: None,
+            step_size: None,
+            min_qty: None,
+            max_qty: None,
+        }
+    }
+
+    fn pipeline(yaml: &str) -> Config {
+        let base = "rest_endpoint: \"http://127.0.0.1:1/\"\nwss_endpoint: \"ws://127.0.0.1:1/\"\nmax_depth: 10\nconnections: 1\nreconnect_timeout: 5000\nsnapshot_update_interval: 30000\n";
+        load_pipelines_from_yaml_str(&format!("{}{}", base, yaml)).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(!is_pattern("BTCUSDT"));
+        assert!(is_pattern("*USDT"));
+    }
+
+    #[test]
+    fn test_select_symbols() {
+        let connector = BinanceConnector::new("http://127.0.0.1:1/".to_string(), "ws://127.0.0.1:1/".to_string());
+        let symbols = [
+            metadata("ETHUSDT", "USDT", "TRADING"),
+            metadata("BTCUSDT", "USDT", "TRADING"),
+            metadata("LUNAUSDT", "USDT", "BREAK"),
+            metadata("BTCDOWNUSDT", "USDT", "TRADING"),
+            metadata("SOLUSDT", "USDT", "TRADING"),
+            metadata("ETHBTC", "BTC", "TRADING"),
+        ];
+
+        let config = pipeline("instrument: \"*USDT\"\nsymbol_discovery:\n  exclude: [\"*DOWNUSDT\"]\n");
+        assert_eq!(select_symbols(&config, &connector, &symbols, &["SOLUSDT"]).unwrap(), vec!["BTCUSDT", "ETHUSDT"]);
+
+        let config = pipeline("instrument: \"*\"\nsymbol_discovery:\n  quote_asset: BTC\n");
+        assert_eq!(select_symbols(&config, &connector, &symbols, &[]).unwrap(), vec!["ETHBTC"]);
+
+        let config = pipeline("instrument: \"*USDT\"\nsymbol_discovery:\n  max_symbols: 3\n");
+        assert!(select_symbols(&config, &connector, &symbols, &[]).is_err());
+
+        let config = pipeline("instrument: \"*EUR\"\n");
+        assert!(select_symbols(&config, &connector, &symbols, &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_discover_pipelines() {
+        let router = axum::Router::new().route("/exchangeInfo", axum::routing::get(|| async {
+            r#"{"symbols":[
+                {"symbol":"BTCUSDT","status":"TRADING","baseAsset":"BTC","quoteAsset":"USDT","filters":[]},
+                {"symbol":"ETHUSDT","status":"TRADING","baseAsset":"ETH","quoteAsset":"USDT","filters":[]},
+                {"symbol":"SOLUSDT","status":"TRADING","baseAsset":"SOL","quoteAsset":"USDT","filters":[]}
+            ]}"#
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let pipelines = load_pipelines_from_yaml_str(&format!(r#"
+defaults:
+  rest_endpoint: "http://{}/"
+  wss_endpoint: "ws://127.0.0.1:1/"
+  max_depth: 10
+  connections: 1
+  reconnect_timeout: 5000
+  snapshot_update_interval: 30000
+pipelines:
+  - instrument: "*USDT"
+    rest_listen: "127.0.0.1:8080"
+  - instrument: "ETHUSDT"
+"#, address)).unwrap();
+
+        let pipelines = discover_pipelines(pipelines).await.unwrap();
+        let instruments: Vec<&str> = pipelines.iter().map(|pipeline| pipeline.instrument.as_str()).collect();
+        assert_eq!(instruments, vec!["BTCUSDT", "SOLUSDT", "ETHUSDT"]);
+        assert_eq!(pipelines[0].rest_listen.as_deref(), Some("127.0.0.1:8080"));
+        assert_eq!(pipelines[1].rest_listen, None);
+    }
+}
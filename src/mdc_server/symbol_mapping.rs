@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use crate::mdc_server::exchange_connector::Exchange;
+
+/// Mapping of canonical instrument identifiers to the symbols of the venues, e.g. `BTC-USD` to `BTCUSDT` on Binance,
+/// `BTC-USDT` on OKX and `tBTCUSD` on Bitfinex
+///
+/// Configured in YAML as a mapping of canonical symbols to mappings of exchanges to venue symbols:
+/// ```yaml
+/// symbol_map:
+///   BTC-USD:
+///     binance: "BTCUSDT"
+///     okx: "BTC-USDT"
+/// ```
+/// Downstream sinks identify the instrument of a pipeline by its canonical symbol. An instrument, which isn't mapped,
+/// is its own canonical symbol
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SymbolMap(BTreeMap<String, BTreeMap<Exchange, String>>);
+
+impl SymbolMap {
+    /// The canonical symbol of the venue symbol, if it is mapped
+    pub fn canonical(&self, exchange: Exchange, instrument: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, symbols)| symbols.get(&exchange).is_some_and(|symbol| symbol == instrument))
+            .map(|(canonical, _)| canonical.as_str())
+    }
+
+    /// The symbol of the canonical instrument on the exchange, if it is mapped
+    pub fn venue_symbol(&self, canonical: &str, exchange: Exchange) -> Option<&str> {
+        self.0.get(canonical)?.get(&exchange).map(String::as_str)
+    }
+
+    /// Check that no venue symbol is mapped to several canonical symbols
+    pub fn check(&self) -> Result<()> {
+        let mut canonical_symbols = BTreeMap::new();
+
+        for (canonical, symbols) in &self.0 {
+            for (exchange, symbol) in symbols {
+                if let Some(other) = canonical_symbols.insert((exchange, symbol), canonical) {
+                    return Err(anyhow!(
+                        "Symbol '{}' of '{:?}' is mapped to both '{}' and '{}'", symbol, exchange, other, canonical
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_map(yaml: &str) -> SymbolMap {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_symbols_are_mapped_both_ways() {
+        let map = make_map(r#"
+BTC-USD:
+  binance: "BTCUSDT"
+  okx: "BTC-USDT"
+  bitfinex: "tBTCUSD"
+ETH-USD:
+  binance: "ETHUSDT"
+"#);
+        assert!(map.check().is_ok());
+
+        assert_eq!(map.canonical(Exchange::Okx, "BTC-USDT"), Some("BTC-USD"));
+        assert_eq!(map.canonical(Exchange::Binance, "ETHUSDT"), Some("ETH-USD"));
+        assert_eq!(map.canonical(Exchange::BinanceFutures, "BTCUSDT"), None);
+        assert_eq!(map.canonical(Exchange::Binance, "SOLUSDT"), None);
+
+        assert_eq!(map.venue_symbol("BTC-USD", Exchange::Bitfinex), Some("tBTCUSD"));
+        assert_eq!(map.venue_symbol("ETH-USD", Exchange::Okx), None);
+        assert_eq!(map.venue_symbol("BTCUSDT", Exchange::Binance), None);
+    }
+
+    #[test]
+    fn test_ambiguous_mapping_is_rejected() {
+        let map = make_map(r#"
+BTC-USD:
+  binance: "BTCUSDT"
+BTC-USDT:
+  binance: "BTCUSDT"
+"#);
+        assert!(map.check().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use crate::mdc_core::models::SymbolMetadata;
+use crate::mdc_server::exchange_connector::ExchangeConnector;
+use crate::mdc_server::transport::Transport;
+
+/// A single cached metadata record along with the time it was fetched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    fetched_at: i64,
+    metadata: SymbolMetadata,
+}
+
+/// This class provides symbol metadata (tick size, lot size, status) from the exchange (e.g. Binance exchangeInfo)
+/// It keeps the metadata in a JSON file on disk and only queries the exchange once the cached entry
+/// is older than the configured TTL
+pub struct SymbolMetadataCache {
+    connector: Arc<dyn ExchangeConnector>,
+    cache_path: PathBuf,
+    ttl: u64,
+    transport: Transport,
+}
+
+impl SymbolMetadataCache {
+    /// Create a new SymbolMetadataCache
+    ///
+    /// # Arguments
+    /// * `connector` - The exchange connector, which provides and parses the symbol metadata endpoint
+    /// * `cache_path` - Path to the JSON file, where metadata is persisted
+    /// * `ttl` - Time in milliseconds after which a cached entry is refreshed from the exchange
+    pub fn new(connector: Arc<dyn ExchangeConnector>, cache_path: PathBuf, ttl: u64) -> Self {
+        Self {
+            connector,
+            cache_path,
+            ttl,
+            transport: Transport::default(),
+        }
+    }
+
+    /// Send the requests with the proxy and TLS settings of the transport
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Get metadata for the symbol, refreshing it from the exchange if the cached entry is missing or expired
+    ///
+    /// # Behavior
+    /// * A fresh cached entry is returned without querying the exchange
+    /// * If refreshing fails, an expired cached entry is returned with a warning
+    pub async fn get(&self, symbol: &str) -> Result<SymbolMetadata> {
+        let mut entries = self.read_entries();
+        let cached = entries.get(symbol).cloned();
+
+        if let Some(entry) = &cached {
+            if self.is_fresh(entry, Utc::now().timestamp_millis()) {
+                tracing::debug!("Using cached metadata for symbol '{}'", symbol);
+                return Ok(entry.metadata.clone());
+            }
+        }
+
+        match self.fetch(symbol).await {
+            Ok(metadata) => {
+                entries.insert(symbol.to_string(), CacheEntry {
+                    fetched_at: Utc::now().timestamp_millis(),
+                    metadata: metadata.clone(),
+                });
+
+                if let Err(e) = self.write_entries(&entries) {
+                    tracing::warn!("Failed to persist symbol metadata cache. Details: '{}'", e);
+                }
+
+                Ok(metadata)
+            }
+            Err(e) => match cached {
+                Some(entry) => {
+                    tracing::warn!("Failed to refresh metadata for symbol '{}', using expired cache entry. Details: '{}'", symbol, e);
+                    Ok(entry.metadata)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Check whether a cached entry is still within its TTL
+    fn is_fresh(&self, entry: &CacheEntry, now: i64) -> bool {
+        now.saturating_sub(entry.fetched_at) < self.ttl as i64
+    }
+
+    /// Request symbol metadata from the exchange REST API
+    async fn fetch(&self, symbol: &str) -> Result<SymbolMetadata> {
+        let url = self.connector.exchange_info_url(symbol);
+
+        let response_text = self.transport.get(&url)
+            .await
+            .context("Failed to send exchangeInfo request")?
+            .error_for_status()
+            .context("Failed to get exchangeInfo response")?
+            .text()
+            .await
+            .context("Failed to get response text for exchangeInfo")?;
+
+        tracing::trace!("Received exchangeInfo from {}: '{:?}'", self.connector.name(), response_text);
+
+        self.connector.parse_symbol_metadata(symbol, &response_text)
+    }
+
+    /// Read all cached entries from disk. A missing or corrupted cache file is treated as empty
+    fn read_entries(&self) -> HashMap<String, CacheEntry> {
+        let Ok(data) = fs::read_to_string(&self.cache_path) else {
+            return HashMap::new();
+        };
+
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring corrupted symbol metadata cache '{:?}'. Details: '{}'", self.cache_path, e);
+            HashMap::new()
+        })
+    }
+
+    /// Persist all cached entries to disk
+    fn write_entries(&self, entries: &HashMap<String, CacheEntry>) -> Result<()> {
+        if let Some(parent) = self.cache_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {:?}", parent))?;
+        }
+
+        let data = serde_json::to_string_pretty(entries)?;
+        fs::write(&self.cache_path, data)
+            .with_context(|| format!("Failed to write symbol metadata cache: {:?}", self.cache_path))?;
+        Ok(())
+    }
+}
+
+/// Whether getting the metadata has failed because the exchange couldn't be asked (unreachable, rate limited,
+/// unavailable), rather than because it has rejected the symbol (not listed, `400 Bad Request` or `404 Not Found`)
+pub fn is_request_failure(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| !matches!(e.status(), Some(StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_server::binance_connector::BinanceConnector;
+
+    fn make_metadata(symbol: &str) -> SymbolMetadata {
+        SymbolMetadata {
+            symbol: symbol.to_string(),
+            status: "TRADING".to_string(),
+            base_asset: "BTC".to_string(),
+            quote_asset: "USDT".to_string(),
+            tick_size: Some(FixedPoint::from(0.01)),
+            step_size: Some(FixedPoint::from(0.00001)),
+            min_qty: Some(FixedPoint::from(0.00001)),
+            max_qty: Some(FixedPoint::from(9000.0)),
+        }
+    }
+
+    fn make_cache(name: &str, ttl: u64) -> SymbolMetadataCache {
+        let path = std::env::temp_dir()
+            .join(format!("mdc_test_{}_{}", std::process::id(), name))
+            .join("symbols.json");
+        let _ = fs::remove_file(&path);
+        // The endpoint is unreachable, so any attempt to refresh the cache fails
+        let connector = BinanceConnector::new("http://127.0.0.1:1/".to_string(), "ws://127.0.0.1:1/".to_string());
+        SymbolMetadataCache::new(Arc::new(connector), path, ttl)
+    }
+
+    #[tokio::test]
+    async fn test_fresh_entry_is_served_from_disk() {
+        let cache = make_cache("fresh", 60_000);
+        let mut entries = HashMap::new();
+        entries.insert("BTCUSDT".to_string(), CacheEntry {
+            fetched_at: Utc::now().timestamp_millis(),
+            metadata: make_metadata("BTCUSDT"),
+        });
+        cache.write_entries(&entries).unwrap();
+
+        let metadata = cache.get("BTCUSDT").await.unwrap();
+        assert_eq!(metadata, make_metadata("BTCUSDT"));
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_used_when_refresh_fails() {
+        let cache = make_cache("expired", 1_000);
+        let mut entries = HashMap::new();
+        entries.insert("BTCUSDT".to_string(), CacheEntry {
+            fetched_at: Utc::now().timestamp_millis() - 10_000,
+            metadata: make_metadata("BTCUSDT"),
+        });
+        cache.write_entries(&entries).unwrap();
+
+        let metadata = cache.get("BTCUSDT").await.unwrap();
+        assert_eq!(metadata, make_metadata("BTCUSDT"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_entry_fails_when_refresh_fails() {
+        let cache = make_cache("missing", 60_000);
+        let error = cache.get("ETHUSDT").await.unwrap_err();
+        assert!(is_request_failure(&error));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_symbols() {
+        let router = axum::Router::new().route("/exchangeInfo", axum::routing::get(
+            |axum::extract::Query(query): axum::extract::Query<HashMap<String, String>>| async move {
+                match query["symbol"].as_str() {
+                    "BTCUSDT" => (axum::http::StatusCode::OK, r#"{"symbols":[{"symbol":"BTCUSDT","status":"BREAK","baseAsset":"BTC","quoteAsset":"USDT","filters":[]}]}"#),
+                    "ETHUSDT" => (axum::http::StatusCode::OK, r#"{"symbols":[]}"#),
+                    _ => (axum::http::StatusCode::BAD_REQUEST, r#"{"code":-1121,"msg":"Invalid symbol."}"#),
+                }
+            }
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let connector = Arc::new(BinanceConnector::new(format!("http://{}/", address), "ws://127.0.0.1:1/".to_string()));
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_rejected", std::process::id())).join("symbols.json");
+        let _ = fs::remove_file(&path);
+        let cache = SymbolMetadataCache::new(connector.clone(), path, 60_000);
+
+        let metadata = cache.get("BTCUSDT").await.unwrap();
+        assert!(!connector.is_trading(&metadata));
+        assert!(!is_request_failure(&cache.get("ETHUSDT").await.unwrap_err()));
+        assert!(!is_request_failure(&cache.get("XXXUSDT").await.unwrap_err()));
+    }
+
+    #[test]
+    fn test_entry_freshness() {
+        let cache = make_cache("freshness", 1_000);
+        let entry = CacheEntry { fetched_at: 10_000, metadata: make_metadata("BTCUSDT") };
+
+        assert!(cache.is_fresh(&entry, 10_500));
+        assert!(!cache.is_fresh(&entry, 11_000));
+    }
+}
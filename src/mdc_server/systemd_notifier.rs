@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::Utc;
+use crate::mdc_server::live_status::{LiveStatus, StatusBoard};
+
+/// Longest interval between two checks of the pipelines
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Address of the notification socket of the service manager, `None` if the process doesn't run under systemd
+fn notify_socket() -> Option<SocketAddr> {
+    let path = std::env::var("NOTIFY_SOCKET").ok()?;
+    let address = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name)
+        }
+        _ => SocketAddr::from_pathname(&path),
+    };
+
+    address
+        .map_err(|e| tracing::error!("Invalid NOTIFY_SOCKET '{}'. Details: '{}'", path, e))
+        .ok()
+}
+
+/// Interval of the systemd watchdog of the service (`WatchdogSec`), `None` if it is disabled
+fn watchdog_interval() -> Option<Duration> {
+    // The watchdog may be meant for another process, e.g. a wrapper script
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Whether the pipeline is fully started: the book is built and all streams are connected
+fn is_started(status: &LiveStatus, has_book: bool) -> bool {
+    has_book && !status.streams.is_empty() && status.streams.values().all(|stream| stream.connected)
+}
+
+/// Time of the latest message of any stream of the pipeline, or its start if there hasn't been one
+fn heartbeat(status: &LiveStatus) -> i64 {
+    status.streams.values().filter_map(|stream| stream.last_message_at).max().unwrap_or(status.started_at)
+}
+
+/// A cloneable handle, used by the pipelines to report their state to the SystemdNotifier
+#[derive(Clone, Default)]
+pub struct SystemdHandle {
+    boards: Arc<Mutex<BTreeMap<u64, StatusBoard>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl SystemdHandle {
+    /// Watch the status board of a pipeline until the returned registration is dropped
+    pub fn register(&self, board: StatusBoard) -> SystemdRegistration {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.boards.lock().expect("Systemd handle lock is poisoned").insert(id, board);
+        SystemdRegistration { id, boards: self.boards.clone() }
+    }
+
+    fn boards(&self) -> Vec<StatusBoard> {
+        self.boards.lock().expect("Systemd handle lock is poisoned").values().cloned().collect()
+    }
+}
+
+/// Registration of a pipeline with the SystemdNotifier. The pipeline is no longer watched once it is dropped
+pub struct SystemdRegistration {
+    id: u64,
+    boards: Arc<Mutex<BTreeMap<u64, StatusBoard>>>,
+}
+
+impl Drop for SystemdRegistration {
+    fn drop(&mut self) {
+        self.boards.lock().expect("Systemd handle lock is poisoned").remove(&self.id);
+    }
+}
+
+/// SystemdNotifier reports the state of the process to systemd (`Type=notify` services)
+///
+/// `READY=1` is sent once the configured pipelines are registered, their books are built from the first snapshot
+/// and all of their streams are connected. With `WatchdogSec` set, the watchdog is fed with `WATCHDOG=1` as long as
+/// every pipeline received a message within the watchdog interval, so systemd restarts a stalled collector.
+/// Until the process is ready, the watchdog is fed unconditionally, the start-up is limited by `TimeoutStartSec`
+pub struct SystemdNotifier {
+    socket: UnixDatagram,
+    address: SocketAddr,
+    handle: SystemdHandle,
+    pipelines: usize,
+    watchdog: Option<Duration>,
+    ready: bool,
+    stalled: bool,
+}
+
+impl SystemdNotifier {
+    /// Create a SystemdNotifier, if the process runs as a systemd service with a notification socket
+    ///
+    /// # Arguments
+    /// * `pipelines` - Number of configured pipelines, which have to be started before the process is ready
+    pub fn from_env(pipelines: usize) -> Option<Self> {
+        let address = notify_socket()?;
+        match Self::new(address, pipelines, watchdog_interval()) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                tracing::error!("Systemd notifications are disabled. Details: '{}'", e);
+                None
+            }
+        }
+    }
+
+    /// Create a new SystemdNotifier
+    ///
+    /// # Arguments
+    /// * `address` - Address of the notification socket
+    /// * `pipelines` - Number of configured pipelines, which have to be started before the process is ready
+    /// * `watchdog` - Interval of the systemd watchdog, if it is enabled
+    pub fn new(address: SocketAddr, pipelines: usize, watchdog: Option<Duration>) -> io::Result<Self> {
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            address,
+            handle: SystemdHandle::default(),
+            pipelines,
+            watchdog,
+            ready: false,
+            stalled: false,
+        })
+    }
+
+    pub fn handle(&self) -> SystemdHandle {
+        self.handle.clone()
+    }
+
+    fn notify(&self, state: &str) {
+        if let Err(e) = self.socket.send_to_addr(state.as_bytes(), &self.address) {
+            tracing::warn!("Failed to notify systemd. Details: '{}'", e);
+        }
+    }
+
+    /// Check the pipelines, send `READY=1` once they are started and feed the watchdog while none of them stalls
+    fn check(&mut self) {
+        let now = Utc::now().timestamp_millis();
+        let pipelines: Vec<(LiveStatus, bool)> = self.handle
+            .boards()
+            .iter()
+            .map(|board| (board.snapshot(), board.book_frame(0).is_some()))
+            .collect();
+
+        if !self.ready && pipelines.len() >= self.pipelines && pipelines.iter().all(|(status, has_book)| is_started(status, *has_book)) {
+            tracing::info!("All pipelines are started. Notifying systemd");
+            self.notify(&format!("READY=1\nSTATUS=Capturing '{}' instruments", pipelines.len()));
+            self.ready = true;
+        }
+
+        let Some(watchdog) = self.watchdog else {
+            return;
+        };
+
+        let stalled: Vec<&str> = pipelines
+            .iter()
+            .filter(|(status, _)| self.ready && now - heartbeat(status) > watchdog.as_millis() as i64)
+            .map(|(status, _)| status.instrument.as_str())
+            .collect();
+
+        if stalled.is_empty() {
+            self.notify("WATCHDOG=1");
+        } else if !self.stalled {
+            tracing::error!("Pipelines of {:?} are stalled. Not feeding the systemd watchdog", stalled);
+        }
+        self.stalled = !stalled.is_empty();
+    }
+
+    /// Run the SystemdNotifier as an asynchronous task
+    pub async fn run(mut self) {
+        let period = self.watchdog.map_or(CHECK_INTERVAL, |watchdog| CHECK_INTERVAL.min(watchdog / 2));
+        let mut interval = tokio::time::interval(period);
+
+        loop {
+            interval.tick().await;
+            self.check();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{BboChange, DepthEntry, DepthSnapshot, MarketEvent};
+    use crate::mdc_core::order_book::OrderBook;
+    use crate::mdc_server::live_status::LiveStatusTracker;
+    use crate::mdc_server::metrics::Metrics;
+    use tokio::sync::mpsc;
+
+    fn received(socket: &UnixDatagram) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut buffer = [0; 256];
+        while let Ok(size) = socket.recv(&mut buffer) {
+            messages.push(String::from_utf8_lossy(&buffer[..size]).to_string());
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_ready_and_watchdog() {
+        let path = std::env::temp_dir().join(format!("mdc_systemd_test_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+
+        let address = SocketAddr::from_pathname(&path).unwrap();
+        let mut notifier = SystemdNotifier::new(address, 1, Some(Duration::from_secs(10))).unwrap();
+
+        // Not ready before the pipeline is registered, but the watchdog is fed
+        notifier.check();
+        assert_eq!(received(&socket), vec!["WATCHDOG=1"]);
+
+        let board = StatusBoard::new("binance", "BTCUSDT", Metrics::new());
+        let reporter = board.stream("depth#0".to_string());
+        let registration = notifier.handle().register(board.clone());
+        reporter.on_connected();
+        notifier.check();
+        assert_eq!(received(&socket), vec!["WATCHDOG=1"]);
+
+        let (book_sender, book_receiver) = mpsc::channel(10);
+        let (_, bbo_receiver) = mpsc::channel(10);
+        book_sender.send(Arc::new(OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![DepthEntry { price: FixedPoint::from(100.0), quantity: FixedPoint::from(1.0) }],
+            asks: vec![],
+        }))).await.unwrap();
+        drop(book_sender);
+        LiveStatusTracker::new(board.clone(), bbo_receiver, book_receiver).run().await;
+        reporter.on_event(&MarketEvent::BboChange(BboChange { update_id: 1, best_bid: None, best_ask: None }));
+
+        notifier.check();
+        assert_eq!(received(&socket), vec!["READY=1\nSTATUS=Capturing '1' instruments", "WATCHDOG=1"]);
+
+        // A pipeline without messages within the watchdog interval stops feeding the watchdog
+        notifier.watchdog = Some(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(5));
+        notifier.check();
+        assert!(received(&socket).is_empty());
+
+        drop(registration);
+        notifier.check();
+        assert_eq!(received(&socket), vec!["WATCHDOG=1"]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
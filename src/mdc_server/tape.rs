@@ -0,0 +1,415 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Context, Result};
+use async_compression::Level;
+use async_compression::tokio::bufread::ZstdDecoder;
+use async_compression::tokio::write::ZstdEncoder;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter, Lines};
+use tokio::sync::mpsc;
+use crate::mdc_server::disk_space_guard::CaptureGate;
+
+/// Magic number, which every zstd frame starts with
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+fn default_compression_level() -> i32 {
+    3
+}
+
+fn default_frame_size() -> usize {
+    1 << 20
+}
+
+/// On-the-fly zstd compression of tape files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapeCompression {
+    /// zstd compression level, 1 (fastest) to 22 (smallest)
+    #[serde(default = "default_compression_level")]
+    pub level: i32,
+    /// Uncompressed bytes of records per zstd frame. Records are only persisted with the frame they belong to,
+    /// so a crash loses the current frame
+    #[serde(default = "default_frame_size")]
+    pub frame_size: usize,
+}
+
+impl Default for TapeCompression {
+    fn default() -> Self {
+        Self { level: default_compression_level(), frame_size: default_frame_size() }
+    }
+}
+
+/// Returns the current wall-clock time in nanoseconds since the UNIX epoch
+pub fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before UNIX epoch")
+        .as_nanos() as u64
+}
+
+/// A single raw frame persisted in a tape file
+///
+/// Each record is stored as a single line: `<receive_time_ns>\t<source>\t<payload>`, where `source`
+/// identifies the stream the frame was received from (e.g. 'depth#0', 'trade', 'price', 'snapshot')
+#[derive(Debug, Clone, PartialEq)]
+pub struct TapeRecord {
+    pub receive_time: u64,
+    pub source: String,
+    pub payload: String,
+}
+
+impl TapeRecord {
+    /// Returns the stream kind of the record, i.e. the source without the connection index
+    pub fn kind(&self) -> &str {
+        self.source.split('#').next().unwrap_or_default()
+    }
+
+    /// Parse a record from a tape line
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut parts = line.splitn(3, '\t');
+        let (Some(receive_time), Some(source), Some(payload)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(anyhow!("Malformed tape record: '{}'", line));
+        };
+
+        Ok(Self {
+            receive_time: receive_time.parse().context("Malformed tape record receive time")?,
+            source: source.to_string(),
+            payload: payload.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for TapeRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Line breaks can only appear as insignificant whitespace in JSON payloads
+        write!(f, "{}\t{}\t{}", self.receive_time, self.source, self.payload.replace(['\n', '\r'], " "))
+    }
+}
+
+/// A cloneable handle, used by streams to submit received frames to the TapeWriter
+#[derive(Debug, Clone)]
+pub struct TapeRecorder {
+    source: String,
+    output: mpsc::Sender<TapeRecord>,
+}
+
+impl TapeRecorder {
+    /// Create a new TapeRecorder
+    ///
+    /// # Arguments
+    /// * `source` - Name of the stream, whose frames are recorded
+    /// * `output` - Sender for TapeRecord messages to the TapeWriter
+    pub fn new(source: String, output: mpsc::Sender<TapeRecord>) -> Self {
+        Self { source, output }
+    }
+
+    /// Stamp the frame with the receive time and submit it to the TapeWriter
+    pub async fn record(&self, payload: &str) {
+        let record = TapeRecord {
+            receive_time: now_nanos(),
+            source: self.source.clone(),
+            payload: payload.to_string(),
+        };
+
+        if let Err(e) = self.output.send(record).await {
+            tracing::error!("Failed to submit '{}' frame to the tape writer: {}", self.source, e);
+        }
+    }
+}
+
+/// The tape file as written by the TapeWriter, either plain or as a sequence of zstd frames
+enum TapeOutput {
+    Plain(BufWriter<File>),
+    Zstd {
+        /// The encoder of the current frame. It is only taken while the frame is finished
+        encoder: Option<ZstdEncoder<BufWriter<File>>>,
+        compression: TapeCompression,
+        /// Uncompressed bytes written into the current frame
+        frame_bytes: usize,
+    },
+}
+
+impl TapeOutput {
+    fn new(file: File, compression: Option<TapeCompression>) -> Self {
+        let writer = BufWriter::new(file);
+        match compression {
+            None => TapeOutput::Plain(writer),
+            Some(compression) => TapeOutput::Zstd {
+                encoder: Some(ZstdEncoder::with_quality(writer, Level::Precise(compression.level))),
+                compression,
+                frame_bytes: 0,
+            },
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            TapeOutput::Plain(writer) => writer.write_all(data).await,
+            TapeOutput::Zstd { encoder, compression, frame_bytes } => {
+                encoder.as_mut().expect("Tape encoder is present").write_all(data).await?;
+                *frame_bytes += data.len();
+                if *frame_bytes >= compression.frame_size {
+                    self.persist().await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The writer is idle. Plain tapes are flushed, compressed tapes keep filling the current frame
+    async fn on_idle(&mut self) -> io::Result<()> {
+        match self {
+            TapeOutput::Plain(writer) => writer.flush().await,
+            TapeOutput::Zstd { .. } => Ok(()),
+        }
+    }
+
+    /// Write everything received so far into the file. The current zstd frame is finished, the next record starts a new one
+    async fn persist(&mut self) -> io::Result<()> {
+        match self {
+            TapeOutput::Plain(writer) => writer.flush().await,
+            TapeOutput::Zstd { encoder, compression, frame_bytes } => {
+                if *frame_bytes == 0 {
+                    return Ok(());
+                }
+
+                let mut finished = encoder.take().expect("Tape encoder is present");
+                // Shutting down the encoder finishes the frame and flushes the file, which stays open
+                let result = finished.shutdown().await;
+                *encoder = Some(ZstdEncoder::with_quality(finished.into_inner(), Level::Precise(compression.level)));
+                *frame_bytes = 0;
+                result
+            }
+        }
+    }
+}
+
+/// TapeWriter appends every received TapeRecord to a tape file
+pub struct TapeWriter {
+    path: PathBuf,
+    input: mpsc::Receiver<TapeRecord>,
+    gate: CaptureGate,
+    compression: Option<TapeCompression>,
+}
+
+impl TapeWriter {
+    /// Create a new TapeWriter
+    ///
+    /// # Arguments
+    /// * `path` - Path of the tape file. Records are appended if the file already exists
+    /// * `input` - Receiver for TapeRecord messages
+    /// * `gate` - Switch, which pauses recording. Records received while recording is paused are discarded
+    pub fn new(path: PathBuf, input: mpsc::Receiver<TapeRecord>, gate: CaptureGate) -> Self {
+        Self { path, input, gate, compression: None }
+    }
+
+    /// Compress the tape with zstd. Records are written in independent frames, so a compressed tape can be appended to
+    pub fn with_compression(mut self, compression: TapeCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Run the TapeWriter as an asynchronous task
+    ///
+    /// This method will continuously append records to the tape file until the input channel is closed
+    /// A plain file is flushed whenever there are no more pending records, a compressed file whenever a frame is full.
+    /// Both are flushed when recording is paused
+    pub async fn run(mut self) {
+        tracing::info!("Starting TapeWriter. Recording to: '{:?}'", self.path);
+
+        if let Err(e) = self.write_records().await {
+            tracing::error!("TapeWriter stopped recording to '{:?}'. Details: '{}'", self.path, e);
+        }
+    }
+
+    async fn write_records(&mut self) -> Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open tape file: {:?}", self.path))?;
+        let mut writer = TapeOutput::new(file, self.compression);
+        let mut discarded: u64 = 0;
+
+        while let Some(record) = self.input.recv().await {
+            if self.gate.is_paused() {
+                if discarded == 0 {
+                    writer.persist().await?;
+                }
+                discarded += 1;
+                continue;
+            }
+
+            if discarded > 0 {
+                tracing::warn!("Recording to '{:?}' resumed. '{}' records were discarded while it was paused", self.path, discarded);
+                discarded = 0;
+            }
+
+            writer.write(format!("{}\n", record).as_bytes()).await?;
+
+            if self.input.is_empty() {
+                writer.on_idle().await?;
+            }
+        }
+
+        writer.persist().await?;
+        Ok(())
+    }
+}
+
+/// Sequential reader of tape files
+pub struct TapeReader {
+    lines: Lines<Box<dyn AsyncBufRead + Unpin + Send>>,
+    compressed: bool,
+    truncated: bool,
+}
+
+impl TapeReader {
+    /// Open a tape file for reading. zstd-compressed tapes are recognized by their content and decompressed
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(&path)
+            .await
+            .with_context(|| format!("Failed to open tape file: {:?}", path.as_ref()))?;
+
+        let mut reader = BufReader::new(file);
+        let compressed = reader.fill_buf().await?.starts_with(&ZSTD_MAGIC);
+        let reader: Box<dyn AsyncBufRead + Unpin + Send> = match compressed {
+            true => {
+                let mut decoder = ZstdDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::new(BufReader::new(decoder))
+            }
+            false => Box::new(reader),
+        };
+
+        Ok(Self { lines: reader.lines(), compressed, truncated: false })
+    }
+
+    /// Whether the tape is zstd-compressed
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Whether the tape has ended with an incomplete compressed frame, which has been skipped
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Read the next record. Returns `None` once the end of the tape is reached
+    ///
+    /// The last frame of a compressed tape is incomplete, if the recording process crashed.
+    /// Its records can't be recovered, so the tape ends with the last complete frame
+    pub async fn next_record(&mut self) -> Result<Option<TapeRecord>> {
+        loop {
+            let line = match self.lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return Ok(None),
+                Err(e) if self.compressed && e.kind() == io::ErrorKind::UnexpectedEof => {
+                    tracing::warn!("Compressed tape ends with an incomplete frame, which is skipped");
+                    self.truncated = true;
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if !line.trim().is_empty() {
+                return TapeRecord::parse(&line).map(Some);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tape_record_roundtrip() {
+        let record = TapeRecord {
+            receive_time: 1672515782136000001,
+            source: "depth#2".to_string(),
+            payload: "{\"e\":\"depthUpdate\",\n\"U\":1}".to_string(),
+        };
+
+        let line = record.to_string();
+        assert_eq!(line, "1672515782136000001\tdepth#2\t{\"e\":\"depthUpdate\", \"U\":1}");
+
+        let parsed = TapeRecord::parse(&line).unwrap();
+        assert_eq!(parsed.receive_time, 1672515782136000001);
+        assert_eq!(parsed.source, "depth#2");
+        assert_eq!(parsed.kind(), "depth");
+        assert_eq!(parsed.payload, "{\"e\":\"depthUpdate\", \"U\":1}");
+    }
+
+    #[test]
+    fn test_tape_record_malformed() {
+        assert!(TapeRecord::parse("not a record").is_err());
+        assert!(TapeRecord::parse("abc\ttrade\t{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tape_writer_and_reader() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_writer.tape", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (tx, rx) = mpsc::channel::<TapeRecord>(100);
+        let writer = tokio::spawn(TapeWriter::new(path.clone(), rx, CaptureGate::new()).run());
+
+        let trade_recorder = TapeRecorder::new("trade".to_string(), tx.clone());
+        let snapshot_recorder = TapeRecorder::new("snapshot".to_string(), tx);
+        trade_recorder.record("{\"t\":1}").await;
+        snapshot_recorder.record("{\"lastUpdateId\":2}").await;
+        drop(trade_recorder);
+        drop(snapshot_recorder);
+        writer.await.unwrap();
+
+        let mut reader = TapeReader::open(&path).await.unwrap();
+        let first = reader.next_record().await.unwrap().unwrap();
+        let second = reader.next_record().await.unwrap().unwrap();
+
+        assert_eq!(first.source, "trade");
+        assert_eq!(first.payload, "{\"t\":1}");
+        assert_eq!(second.source, "snapshot");
+        assert_eq!(second.payload, "{\"lastUpdateId\":2}");
+        assert!(first.receive_time <= second.receive_time);
+        assert!(reader.next_record().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_compressed_tape() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_writer.tape.zst", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (tx, rx) = mpsc::channel::<TapeRecord>(100);
+        let compression = TapeCompression { level: 3, frame_size: 64 };
+        let writer = tokio::spawn(TapeWriter::new(path.clone(), rx, CaptureGate::new()).with_compression(compression).run());
+
+        let recorder = TapeRecorder::new("depth#0".to_string(), tx);
+        for i in 0..10 {
+            recorder.record(&format!("{{\"u\":{}}}", i)).await;
+        }
+        drop(recorder);
+        writer.await.unwrap();
+
+        // A crash leaves an incomplete frame at the end of the tape
+        let mut data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(&ZSTD_MAGIC));
+        data.extend_from_slice(&ZSTD_MAGIC);
+        data.extend_from_slice(&[0x24, 0x00, 0x00]);
+        std::fs::write(&path, data).unwrap();
+
+        let mut reader = TapeReader::open(&path).await.unwrap();
+        assert!(reader.is_compressed());
+        for i in 0..10 {
+            let record = reader.next_record().await.unwrap().unwrap();
+            assert_eq!(record.payload, format!("{{\"u\":{}}}", i));
+        }
+        assert!(reader.next_record().await.unwrap().is_none());
+        assert!(reader.is_truncated());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use crate::mdc_core::models::{AggTradeEvent, DepthSnapshot, DepthUpdate, FromJson, KlineEvent, KlineInterval, LiquidationEvent, MarkPriceEvent, MiniTickerEvent, PriceUpdate, TickerEvent, TradeEvent};
+use crate::mdc_server::tape::{TapeReader, TapeRecord};
+
+/// Time in nanoseconds, for which a key is remembered after it was first seen.
+/// Copies of the same frame received by redundant hosts are expected well within it
+const DEDUP_WINDOW: u64 = 60_000_000_000;
+
+/// Identity of a recorded frame, shared by all copies of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RecordKey {
+    Depth { first_update_id: u64, last_update_id: u64 },
+    Snapshot { last_update_id: u64 },
+    Trade { trade_id: u64 },
+    Price { update_id: u64 },
+    AggTrade { agg_trade_id: u64 },
+    Kline { interval: KlineInterval, start_time: u64, event_time: u64 },
+    Ticker { event_time: u64 },
+    MiniTicker { event_time: u64 },
+    MarkPrice { event_time: u64 },
+    Liquidation { event_time: u64 },
+}
+
+impl RecordKey {
+    fn of(record: &TapeRecord) -> Result<Option<Self>> {
+        let key = match record.kind() {
+            "depth" => {
+                let update = DepthUpdate::from_json(&record.payload)?;
+                RecordKey::Depth { first_update_id: update.first_update_id, last_update_id: update.last_update_id }
+            }
+            "snapshot" => RecordKey::Snapshot { last_update_id: DepthSnapshot::from_json(&record.payload)?.last_update_id },
+            "trade" => RecordKey::Trade { trade_id: TradeEvent::from_json(&record.payload)?.trade_id },
+            "agg_trade" => RecordKey::AggTrade { agg_trade_id: AggTradeEvent::from_json(&record.payload)?.agg_trade_id },
+            "price" => RecordKey::Price { update_id: PriceUpdate::from_json(&record.payload)?.update_id },
+            "ticker" => RecordKey::Ticker { event_time: TickerEvent::from_json(&record.payload)?.event_time },
+            "mini_ticker" => RecordKey::MiniTicker { event_time: MiniTickerEvent::from_json(&record.payload)?.event_time },
+            "mark_price" => RecordKey::MarkPrice { event_time: MarkPriceEvent::from_json(&record.payload)?.event_time },
+            "liquidation" => RecordKey::Liquidation { event_time: LiquidationEvent::from_json(&record.payload)?.event_time },
+            kind if kind.starts_with("kline_") => {
+                let event = KlineEvent::from_json(&record.payload)?;
+                RecordKey::Kline { interval: event.kline.interval, start_time: event.kline.start_time, event_time: event.event_time }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(key))
+    }
+}
+
+/// Statistics of a compaction
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionStats {
+    /// Records read from each input tape
+    pub read: Vec<u64>,
+    /// Records taken into the output from each input tape
+    pub kept: Vec<u64>,
+    /// Records dropped, because an earlier received copy was kept
+    pub duplicates: u64,
+    /// Records kept without deduplication, because their identity couldn't be determined
+    pub unidentified: u64,
+}
+
+impl fmt::Display for CompactionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (read, kept)) in self.read.iter().zip(&self.kept).enumerate() {
+            writeln!(f, "Input #{}: read {}, kept {}", index, read, kept)?;
+        }
+        writeln!(f, "Duplicates dropped: {}", self.duplicates)?;
+        write!(f, "Kept without deduplication: {}", self.unidentified)
+    }
+}
+
+/// Merge overlapping tapes (e.g. recorded by redundant hosts) into a single tape without duplicates
+///
+/// # Arguments
+/// * `inputs` - Tapes to merge
+/// * `output` - Path of the merged tape. Must not exist
+///
+/// # Behavior
+/// * Records are merged in receive time order
+/// * Copies of a frame are identified by update id (depth updates, snapshots, prices), (aggregated) trade id or kline
+///   interval, start and event time, the copy with the earliest receive time is kept. This also drops copies received over redundant
+///   depth connections of a single host
+/// * Records, whose identity can't be determined, are kept as is
+pub async fn compact_tapes(inputs: &[PathBuf], output: &Path) -> Result<CompactionStats> {
+    let mut readers = Vec::with_capacity(inputs.len());
+    let mut heads = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let mut reader = TapeReader::open(input).await?;
+        heads.push(reader.next_record().await?);
+        readers.push(reader);
+    }
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output)
+        .await
+        .with_context(|| format!("Failed to create compacted tape: {:?}", output))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut stats = CompactionStats {
+        read: vec![0; inputs.len()],
+        kept: vec![0; inputs.len()],
+        ..CompactionStats::default()
+    };
+    let mut seen: HashMap<RecordKey, u64> = HashMap::new();
+    let mut last_pruned = 0;
+
+    loop {
+        let next = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(index, head)| head.as_ref().map(|record| (index, record.receive_time)))
+            .min_by_key(|(_, receive_time)| *receive_time);
+
+        let Some((index, receive_time)) = next else {
+            break;
+        };
+
+        let record = heads[index].take().expect("Head of the selected input is present");
+        heads[index] = readers[index].next_record().await?;
+        stats.read[index] += 1;
+
+        let keep = match RecordKey::of(&record) {
+            Ok(Some(key)) => seen.insert(key, receive_time).is_none(),
+            Ok(None) | Err(_) => {
+                stats.unidentified += 1;
+                true
+            }
+        };
+
+        if !keep {
+            stats.duplicates += 1;
+            continue;
+        }
+
+        writer.write_all(format!("{}\n", record).as_bytes()).await?;
+        stats.kept[index] += 1;
+
+        if receive_time > last_pruned + DEDUP_WINDOW {
+            seen.retain(|_, seen_at| *seen_at + DEDUP_WINDOW >= receive_time);
+            last_pruned = receive_time;
+        }
+    }
+
+    writer.flush().await?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn depth(first: u64, last: u64) -> String {
+        format!("{{\"e\":\"depthUpdate\",\"E\":1,\"s\":\"BTCUSDT\",\"U\":{},\"u\":{},\"b\":[],\"a\":[]}}", first, last)
+    }
+
+    fn trade(id: u64) -> String {
+        format!("{{\"e\":\"trade\",\"E\":1,\"s\":\"BTCUSDT\",\"t\":{},\"p\":\"100.5\",\"q\":\"0.1\",\"T\":1,\"m\":true,\"M\":true}}", id)
+    }
+
+    #[tokio::test]
+    async fn test_compact_prefers_earliest_copy() {
+        let dir = std::env::temp_dir().join(format!("mdc_test_{}_{}", std::process::id(), "compact"));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.tape");
+        let second = dir.join("second.tape");
+        let output = dir.join("merged.tape");
+        let _ = fs::remove_file(&output);
+
+        // The first host misses update 3..4, the second host receives update 1..2 later and misses trade 8
+        fs::write(&first, [
+            format!("100\tdepth#0\t{}", depth(1, 2)),
+            format!("110\tdepth#1\t{}", depth(1, 2)),
+            format!("300\ttrade\t{}", trade(7)),
+            format!("400\ttrade\t{}", trade(8)),
+            format!("500\tdepth#0\t{}", depth(5, 6)),
+            "600\tunknown\t{}".to_string(),
+        ].join("\n")).unwrap();
+        fs::write(&second, [
+            format!("150\tdepth#0\t{}", depth(1, 2)),
+            format!("250\tdepth#0\t{}", depth(3, 4)),
+            format!("290\ttrade\t{}", trade(7)),
+            format!("490\tdepth#0\t{}", depth(5, 6)),
+        ].join("\n")).unwrap();
+
+        let stats = compact_tapes(&[first.clone(), second.clone()], &output).await.unwrap();
+        assert_eq!(stats, CompactionStats { read: vec![6, 4], kept: vec![3, 3], duplicates: 4, unidentified: 1 });
+
+        let merged: Vec<_> = fs::read_to_string(&output).unwrap()
+            .lines()
+            .map(|line| TapeRecord::parse(line).unwrap().receive_time)
+            .collect();
+        assert_eq!(merged, vec![100, 250, 290, 400, 490, 600]);
+
+        // An existing output is never overwritten
+        assert!(compact_tapes(&[first], &output).await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
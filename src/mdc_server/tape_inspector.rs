@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::Serialize;
+use crate::mdc_core::depth_sequencer::{BufferLimits, DepthSequencer};
+use crate::mdc_core::models::{DepthSnapshot, FromJson, FrameDecoder, MarketEvent};
+use crate::mdc_core::sequencing::SequencingRules;
+use crate::mdc_server::tape::{TapeReader, TapeRecord};
+use crate::mdc_server::tape_replayer::FrameDecoders;
+
+/// Records of a stream kind
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct KindStats {
+    pub records: u64,
+    /// Bytes of the payloads
+    pub bytes: u64,
+    /// Records, whose payload can't be decoded
+    pub undecodable: u64,
+}
+
+/// A period without any record, e.g. while the capture was down or disconnected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Silence {
+    /// Receive time of the last record before the silence in nanoseconds since epoch
+    pub from: u64,
+    /// Receive time of the first record after the silence in nanoseconds since epoch
+    pub to: u64,
+}
+
+/// Summary of the content and the integrity of a tape
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TapeSummary {
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub size: u64,
+    pub compressed: bool,
+    pub records: u64,
+    /// Receive time of the first record in nanoseconds since epoch
+    pub first_receive_time: Option<u64>,
+    /// Receive time of the last record in nanoseconds since epoch
+    pub last_receive_time: Option<u64>,
+    /// Records by stream kind (`depth`, `trade`, `snapshot`, `kline_1m`, ...)
+    pub kinds: BTreeMap<String, KindStats>,
+    /// Records received before the record preceding them
+    pub out_of_order: u64,
+    /// Periods without records longer than the silence threshold
+    pub silences: Vec<Silence>,
+    /// Gaps in the depth update sequence, which the next snapshot had to recover
+    pub depth_gaps: u64,
+    /// Snapshots, which restarted the depth update sequence
+    pub resyncs: u64,
+    /// Lines, which aren't tape records
+    pub malformed: u64,
+    /// Whether the tape ends with an incomplete compressed frame
+    pub truncated: bool,
+    /// Read error, which has ended the scan before the end of the tape
+    pub error: Option<String>,
+}
+
+impl TapeSummary {
+    /// Time between the first and the last record in seconds
+    pub fn duration(&self) -> f64 {
+        match (self.first_receive_time, self.last_receive_time) {
+            (Some(first), Some(last)) => last.saturating_sub(first) as f64 / 1e9,
+            _ => 0.0,
+        }
+    }
+
+    /// Average records per second
+    pub fn rate(&self, records: u64) -> f64 {
+        match self.duration() {
+            duration if duration > 0.0 => records as f64 / duration,
+            _ => 0.0,
+        }
+    }
+
+    /// Whether the whole tape could be read and every record decoded
+    pub fn is_intact(&self) -> bool {
+        self.malformed == 0
+            && !self.truncated
+            && self.error.is_none()
+            && self.kinds.values().all(|stats| stats.undecodable == 0)
+    }
+}
+
+fn format_time(nanos: u64) -> String {
+    Utc.timestamp_nanos(nanos as i64).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+impl fmt::Display for TapeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = if self.compressed { "zstd" } else { "plain" };
+        writeln!(f, "Tape: {:?} ({}, {} bytes)", self.path, format, self.size)?;
+
+        if let (Some(first), Some(last)) = (self.first_receive_time, self.last_receive_time) {
+            writeln!(f, "Time range: {} - {} ({:.3} s)", format_time(first), format_time(last), self.duration())?;
+        }
+        writeln!(f, "Records: {} ({:.2}/s)", self.records, self.rate(self.records))?;
+        for (kind, stats) in &self.kinds {
+            write!(f, "  {}: {} records, {} bytes, {:.2}/s", kind, stats.records, stats.bytes, self.rate(stats.records))?;
+            match stats.undecodable {
+                0 => writeln!(f)?,
+                undecodable => writeln!(f, ", {} undecodable", undecodable)?,
+            }
+        }
+
+        writeln!(f, "Depth sequence gaps: {}, resyncs: {}", self.depth_gaps, self.resyncs)?;
+        writeln!(f, "Silences: {}", self.silences.len())?;
+        for silence in &self.silences {
+            writeln!(
+                f,
+                "  {} - {} ({:.3} s)",
+                format_time(silence.from),
+                format_time(silence.to),
+                (silence.to - silence.from) as f64 / 1e9
+            )?;
+        }
+        writeln!(f, "Out of order records: {}", self.out_of_order)?;
+
+        let mut issues = Vec::new();
+        if self.malformed > 0 {
+            issues.push(format!("{} malformed lines", self.malformed));
+        }
+        let undecodable: u64 = self.kinds.values().map(|stats| stats.undecodable).sum();
+        if undecodable > 0 {
+            issues.push(format!("{} undecodable records", undecodable));
+        }
+        if self.truncated {
+            issues.push("incomplete last compressed frame".to_string());
+        }
+        if let Some(error) = &self.error {
+            issues.push(format!("read error: {}", error));
+        }
+
+        match issues.is_empty() {
+            true => write!(f, "Integrity: OK"),
+            false => write!(f, "Integrity: {}", issues.join(", ")),
+        }
+    }
+}
+
+/// Follows the depth update sequence of a tape like the DepthEventDispatcher and counts its gaps and resyncs
+struct SequenceTracker {
+    sequencer: DepthSequencer,
+    in_gap: bool,
+    gaps: u64,
+    resyncs: u64,
+}
+
+impl SequenceTracker {
+    fn on_event(&mut self, event: MarketEvent, now: u64) {
+        match event {
+            MarketEvent::DepthUpdate(update) => self.sequencer.push_update(update, now),
+            MarketEvent::DepthSnapshot(snapshot) => {
+                let previous = self.sequencer.last_processed_update_id();
+                if self.sequencer.apply_snapshot(&snapshot) {
+                    self.in_gap = false;
+                    if previous.is_some() {
+                        self.resyncs += 1;
+                    }
+                }
+            }
+            _ => return,
+        }
+
+        self.sequencer.evict_stale(now);
+        self.sequencer.drain();
+
+        let in_gap = self.sequencer.last_processed_update_id().is_some() && self.sequencer.first_buffered().is_some();
+        if in_gap && !self.in_gap {
+            self.gaps += 1;
+        }
+        self.in_gap = in_gap;
+    }
+}
+
+/// Scan a tape and summarize its content and integrity
+///
+/// # Arguments
+/// * `path` - Path of the tape
+/// * `sequencing_rules` - Sequencing rules of the venue, which recorded the tape
+/// * `limits` - Buffer limits of the dispatcher
+/// * `decoders` - Decoders of the depth and the trade frames of the venue
+/// * `silence` - Time in milliseconds without records, which is reported as a silence
+///
+/// # Behavior
+/// * Malformed lines and undecodable records are counted and skipped
+/// * A read error ends the scan and is reported in the summary. Only a tape, which can't be opened, fails the scan
+pub async fn inspect_tape(
+    path: &Path,
+    sequencing_rules: SequencingRules,
+    limits: BufferLimits,
+    decoders: FrameDecoders,
+    silence: u64,
+) -> Result<TapeSummary> {
+    let mut reader = TapeReader::open(path).await?;
+    let mut summary = TapeSummary {
+        path: path.to_path_buf(),
+        size: tokio::fs::metadata(path).await?.len(),
+        compressed: reader.is_compressed(),
+        ..TapeSummary::default()
+    };
+    let mut tracker = SequenceTracker { sequencer: DepthSequencer::new(sequencing_rules, limits), in_gap: false, gaps: 0, resyncs: 0 };
+    let mut stream_decoders: HashMap<String, FrameDecoder> = HashMap::new();
+    let mut latest = None;
+
+    loop {
+        let record = match reader.next_record().await {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(e) => match e.downcast_ref::<io::Error>() {
+                // A line of a plain tape, which isn't UTF-8, is skipped by the reader
+                Some(error) if reader.is_compressed() || error.kind() != io::ErrorKind::InvalidData => {
+                    summary.error = Some(format!("{:#}", e));
+                    break;
+                }
+                _ => {
+                    summary.malformed += 1;
+                    continue;
+                }
+            },
+        };
+
+        summary.records += 1;
+        summary.first_receive_time.get_or_insert(record.receive_time);
+        summary.last_receive_time = Some(record.receive_time);
+
+        match latest {
+            Some(latest) if record.receive_time < latest => summary.out_of_order += 1,
+            Some(latest) if record.receive_time - latest > silence * 1_000_000 => {
+                summary.silences.push(Silence { from: latest, to: record.receive_time });
+            }
+            _ => {}
+        }
+        latest = latest.max(Some(record.receive_time));
+
+        let stats = summary.kinds.entry(record.kind().to_string()).or_default();
+        stats.records += 1;
+        stats.bytes += record.payload.len() as u64;
+
+        match decode(&record, decoders, &mut stream_decoders) {
+            Ok(events) => {
+                for event in events {
+                    tracker.on_event(event, record.receive_time / 1_000_000);
+                }
+            }
+            Err(_) => stats.undecodable += 1,
+        }
+    }
+
+    summary.truncated = reader.is_truncated();
+    summary.depth_gaps = tracker.gaps;
+    summary.resyncs = tracker.resyncs;
+    Ok(summary)
+}
+
+/// Decode the depth events of a record. Records of the other streams are only checked to be JSON
+fn decode(record: &TapeRecord, decoders: FrameDecoders, stream_decoders: &mut HashMap<String, FrameDecoder>) -> Result<Vec<MarketEvent>> {
+    let create_decoder = match record.kind() {
+        "depth" => decoders.0,
+        "trade" => decoders.1,
+        "snapshot" => return Ok(vec![MarketEvent::DepthSnapshot(DepthSnapshot::from_json(&record.payload)?)]),
+        _ => {
+            serde_json::from_str::<serde_json::Value>(&record.payload)?;
+            return Ok(vec![]);
+        }
+    };
+
+    let decoder = stream_decoders.entry(record.source.clone()).or_insert_with(create_decoder);
+    Ok(decoder(&record.payload)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::mdc_core::models::{frame_decoder, DepthUpdate, TradeEvent};
+
+    fn depth(first: u64, last: u64) -> String {
+        format!("{{\"e\":\"depthUpdate\",\"E\":1,\"s\":\"BTCUSDT\",\"U\":{},\"u\":{},\"b\":[],\"a\":[]}}", first, last)
+    }
+
+    #[tokio::test]
+    async fn test_inspect_tape() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_inspect.tape", std::process::id()));
+        let snapshot = |id: u64| format!("{{\"lastUpdateId\":{},\"bids\":[],\"asks\":[]}}", id);
+        let lines = [
+            format!("1000000000\tsnapshot\t{}", snapshot(100)),
+            format!("1100000000\tdepth#0\t{}", depth(101, 105)),
+            format!("1200000000\tdepth#1\t{}", depth(101, 105)),
+            // Updates 106..110 are missed, 111..115 waits for the next snapshot
+            format!("1300000000\tdepth#0\t{}", depth(111, 115)),
+            "1400000000\ttrade\t{\"e\":\"trade\",\"E\":1,\"s\":\"BTCUSDT\",\"t\":7,\"p\":\"100.5\",\"q\":\"0.1\",\"T\":1,\"m\":true,\"M\":true}".to_string(),
+            "1350000000\tprice\t{\"u\":9,\"s\":\"BTCUSDT\",\"b\":\"100.0\",\"B\":\"1.0\",\"a\":\"101.0\",\"A\":\"1.0\"}".to_string(),
+            "not a record".to_string(),
+            format!("9000000000\tsnapshot\t{}", snapshot(120)),
+            "9100000000\tdepth#0\t{\"e\":\"depthUpd".to_string(),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let summary = inspect_tape(
+            &path,
+            SequencingRules::BinanceSpot,
+            BufferLimits { max_size: 1000, max_age: 0 },
+            (frame_decoder::<DepthUpdate>, frame_decoder::<TradeEvent>),
+            5000,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(summary.records, 8);
+        assert_eq!(summary.first_receive_time, Some(1_000_000_000));
+        assert_eq!(summary.last_receive_time, Some(9_100_000_000));
+        assert_eq!(summary.kinds["depth"].records, 4);
+        assert_eq!(summary.kinds["depth"].undecodable, 1);
+        assert_eq!(summary.kinds["snapshot"].records, 2);
+        assert_eq!(summary.kinds["trade"].records, 1);
+        assert_eq!(summary.out_of_order, 1);
+        assert_eq!(summary.silences, vec![Silence { from: 1_400_000_000, to: 9_000_000_000 }]);
+        assert_eq!((summary.depth_gaps, summary.resyncs), (1, 1));
+        assert_eq!(summary.malformed, 1);
+        assert!(!summary.is_intact());
+        assert!((summary.rate(summary.records) - 8.0 / 8.1).abs() < 1e-9);
+
+        let report = summary.to_string();
+        assert!(report.contains("Time range: 1970-01-01T00:00:01.000Z - 1970-01-01T00:00:09.100Z (8.100 s)"), "{}", report);
+        assert!(report.ends_with("Integrity: 1 malformed lines, 1 undecodable records"), "{}", report);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Duration, Instant};
+use crate::mdc_core::models::{AggTradeEvent, DepthSnapshot, DepthUpdate, FromJson, IntoMarketEvent, KlineEvent, LiquidationEvent, MarketEvent, MarkPriceEvent, MiniTickerEvent, PartialBookEvent, PriceUpdate, ReceiveTime, TickerEvent, TradeEvent};
+use crate::mdc_core::models::{frame_decoder, FrameDecoder};
+use crate::mdc_server::tape::{TapeReader, TapeRecord};
+
+/// Constructors of the depth and the trade frame decoders of a venue
+pub type FrameDecoders = (fn() -> FrameDecoder, fn() -> FrameDecoder);
+
+/// TapeReplayer feeds frames from a recorded tape file back into the processing pipeline
+///
+/// Frames are released with the same relative timing they were recorded with, scaled by the replay speed
+/// Depth updates and snapshots are sent to the DepthEventDispatcher, trades, prices, aggregated trades and klines are sent to their channels
+pub struct TapeReplayer {
+    path: PathBuf,
+    speed: f64,
+    depth_output: mpsc::Sender<MarketEvent>,
+    trade_output: mpsc::Sender<MarketEvent>,
+    price_output: mpsc::Sender<MarketEvent>,
+    agg_trade_output: mpsc::Sender<MarketEvent>,
+    auxiliary_output: mpsc::Sender<MarketEvent>,
+    depth_decoder: fn() -> FrameDecoder,
+    trade_decoder: fn() -> FrameDecoder,
+    /// Decoders of the recorded connections, by tape source
+    decoders: HashMap<String, FrameDecoder>,
+    /// Receive time in nanoseconds since epoch, after which the replay stops
+    end_time: Option<u64>,
+}
+
+impl TapeReplayer {
+    /// Create a new TapeReplayer
+    ///
+    /// # Arguments
+    /// * `path` - Path of the tape file to replay
+    /// * `speed` - Replay speed multiplier (1.0 - original speed, 0 - as fast as possible)
+    /// * `depth_output` - Sender for depth updates and snapshots to the DepthEventDispatcher
+    /// * `trade_output` - Sender for trade events
+    /// * `price_output` - Sender for price updates
+    /// * `agg_trade_output` - Sender for aggregated trade events
+    /// * `auxiliary_output` - Sender for events, which are forwarded without dispatching (klines)
+    pub fn new(
+        path: PathBuf,
+        speed: f64,
+        depth_output: mpsc::Sender<MarketEvent>,
+        trade_output: mpsc::Sender<MarketEvent>,
+        price_output: mpsc::Sender<MarketEvent>,
+        agg_trade_output: mpsc::Sender<MarketEvent>,
+        auxiliary_output: mpsc::Sender<MarketEvent>,
+    ) -> Self {
+        Self {
+            path,
+            speed,
+            depth_output,
+            trade_output,
+            price_output,
+            agg_trade_output,
+            auxiliary_output,
+            depth_decoder: frame_decoder::<DepthUpdate>,
+            trade_decoder: frame_decoder::<TradeEvent>,
+            decoders: HashMap::new(),
+            end_time: None,
+        }
+    }
+
+    /// Stop the replay at the first frame received after `end_time` (nanoseconds since epoch)
+    pub fn with_end_time(mut self, end_time: u64) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    /// Decode the depth and trade frames with the given decoders instead of the Binance ones (e.g. for an OKX tape)
+    ///
+    /// A decoder is created for each recorded connection, so connections don't share their decoding state
+    pub fn with_decoders(mut self, depth_decoder: fn() -> FrameDecoder, trade_decoder: fn() -> FrameDecoder) -> Self {
+        self.depth_decoder = depth_decoder;
+        self.trade_decoder = trade_decoder;
+        self
+    }
+
+    /// Run the TapeReplayer as an asynchronous task
+    ///
+    /// This method replays the whole tape and returns, closing its output channels
+    pub async fn run(mut self) {
+        tracing::info!("Starting TapeReplayer for '{:?}' with speed: '{}'", self.path, self.speed);
+
+        match self.replay().await {
+            Ok(count) => tracing::info!("Replay of '{:?}' finished. Replayed '{}' frames", self.path, count),
+            Err(e) => tracing::error!("Replay of '{:?}' failed. Details: '{}'", self.path, e),
+        }
+    }
+
+    async fn replay(&mut self) -> Result<u64> {
+        let mut reader = TapeReader::open(&self.path).await?;
+        let started = Instant::now();
+        let mut first_receive_time = None;
+        let mut count = 0;
+
+        while let Some(record) = reader.next_record().await? {
+            if self.end_time.is_some_and(|end_time| record.receive_time > end_time) {
+                break;
+            }
+
+            let first = *first_receive_time.get_or_insert(record.receive_time);
+
+            if self.speed > 0.0 {
+                let offset = record.receive_time.saturating_sub(first) as f64 / self.speed;
+                sleep_until(started + Duration::from_nanos(offset as u64)).await;
+            }
+
+            match self.dispatch(&record).await {
+                Ok(()) => count += 1,
+                Err(e) => tracing::warn!("Skipping tape record from '{}'. Details: '{}'", record.source, e),
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Parse the recorded frame and send it to the channel matching its source
+    ///
+    /// The events are stamped with the recorded receive time, so latencies are the same as in the live capture
+    async fn dispatch(&mut self, record: &TapeRecord) -> Result<()> {
+        let (events, output) = match record.kind() {
+            "depth" => (self.decode_stream(self.depth_decoder, record)?, &self.depth_output),
+            "trade" => (self.decode_stream(self.trade_decoder, record)?, &self.trade_output),
+            kind => {
+                let (event, output) = self.decode_single(kind, &record.payload)?;
+                (vec![event], output)
+            }
+        };
+
+        for mut event in events {
+            event.stamp(ReceiveTime::at((record.receive_time / 1_000_000) as i64));
+            output.send(event).await?;
+        }
+        Ok(())
+    }
+
+    /// Decode a frame with the decoder of its recorded connection
+    fn decode_stream(&mut self, create_decoder: fn() -> FrameDecoder, record: &TapeRecord) -> Result<Vec<MarketEvent>> {
+        let decoder = self.decoders.entry(record.source.clone()).or_insert_with(create_decoder);
+        Ok(decoder(&record.payload)?)
+    }
+
+    /// Parse a frame of a stream, which carries a single event per frame, and select its channel
+    fn decode_single(&self, kind: &str, payload: &str) -> Result<(MarketEvent, &mpsc::Sender<MarketEvent>)> {
+        Ok(match kind {
+            "snapshot" => (DepthSnapshot::from_json(payload)?.into_market_event(), &self.depth_output),
+            "price" => (PriceUpdate::from_json(payload)?.into_market_event(), &self.price_output),
+            "agg_trade" => (AggTradeEvent::from_json(payload)?.into_market_event(), &self.agg_trade_output),
+            "ticker" => (TickerEvent::from_json(payload)?.into_market_event(), &self.auxiliary_output),
+            "mini_ticker" => (MiniTickerEvent::from_json(payload)?.into_market_event(), &self.auxiliary_output),
+            "mark_price" => (MarkPriceEvent::from_json(payload)?.into_market_event(), &self.auxiliary_output),
+            "liquidation" => (LiquidationEvent::from_json(payload)?.into_market_event(), &self.auxiliary_output),
+            "partial_book" => (PartialBookEvent::from_json(payload)?.into_market_event(), &self.auxiliary_output),
+            kind if kind.starts_with("kline_") => (KlineEvent::from_json(payload)?.into_market_event(), &self.auxiliary_output),
+            other => return Err(anyhow!("Unknown tape record source: '{}'", other)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::mdc_server::okx_connector::{OkxBookMessage, OkxTradeMessage};
+
+    #[tokio::test]
+    async fn test_replay_routes_frames_by_source() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_replay.tape", std::process::id()));
+        let lines = [
+            "1000\tsnapshot\t{\"lastUpdateId\":100,\"bids\":[[\"100.0\",\"1.0\"]],\"asks\":[[\"101.0\",\"1.0\"]]}",
+            "2000\tdepth#0\t{\"e\":\"depthUpdate\",\"E\":1,\"s\":\"BTCUSDT\",\"U\":101,\"u\":105,\"b\":[],\"a\":[]}",
+            "3000000000\ttrade\t{\"e\":\"trade\",\"E\":1,\"s\":\"BTCUSDT\",\"t\":7,\"p\":\"100.5\",\"q\":\"0.1\",\"T\":1,\"m\":true,\"M\":true}",
+            "4000\tprice\t{\"u\":9,\"s\":\"BTCUSDT\",\"b\":\"100.0\",\"B\":\"1.0\",\"a\":\"101.0\",\"A\":\"1.0\"}",
+            "4500\tkline_1m#0\t{\"e\":\"kline\",\"E\":2,\"s\":\"BTCUSDT\",\"k\":{\"t\":0,\"T\":59999,\"i\":\"1m\",\"f\":7,\"L\":7,\"o\":\"100.5\",\"c\":\"100.5\",\"h\":\"100.5\",\"l\":\"100.5\",\"v\":\"0.1\",\"n\":1,\"x\":false,\"q\":\"10.05\",\"V\":\"0\",\"Q\":\"0\"}}",
+            "4600\tagg_trade#0\t{\"e\":\"aggTrade\",\"E\":3,\"s\":\"BTCUSDT\",\"a\":5,\"p\":\"100.5\",\"q\":\"0.1\",\"f\":7,\"l\":7,\"T\":1,\"m\":true,\"M\":true}",
+            "4700\tmini_ticker#0\t{\"e\":\"24hrMiniTicker\",\"E\":4,\"s\":\"BTCUSDT\",\"c\":\"100.5\",\"o\":\"100.0\",\"h\":\"101.0\",\"l\":\"99.0\",\"v\":\"10\",\"q\":\"1005\"}",
+            "4800\tpartial_book#0\t{\"lastUpdateId\":160,\"bids\":[[\"100.0\",\"1.0\"]],\"asks\":[[\"101.0\",\"1.0\"]]}",
+            "5000\tunknown\t{}",
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let (depth_tx, mut depth_rx) = mpsc::channel::<MarketEvent>(100);
+        let (trade_tx, mut trade_rx) = mpsc::channel::<MarketEvent>(100);
+        let (price_tx, mut price_rx) = mpsc::channel::<MarketEvent>(100);
+        let (agg_trade_tx, mut agg_trade_rx) = mpsc::channel::<MarketEvent>(100);
+        let (auxiliary_tx, mut auxiliary_rx) = mpsc::channel::<MarketEvent>(100);
+
+        TapeReplayer::new(path, 0.0, depth_tx, trade_tx, price_tx, agg_trade_tx, auxiliary_tx).run().await;
+
+        assert!(matches!(depth_rx.recv().await, Some(MarketEvent::DepthSnapshot(s)) if s.last_update_id == 100));
+        assert!(matches!(depth_rx.recv().await, Some(MarketEvent::DepthUpdate(u)) if u.last_update_id == 105));
+        assert!(depth_rx.recv().await.is_none());
+        let trade = trade_rx.recv().await.unwrap();
+        assert!(matches!(&trade, MarketEvent::TradeEvent(t) if t.trade_id == 7));
+        assert_eq!(trade.received().map(|received| received.wall_clock), Some(3000));
+        assert!(trade_rx.recv().await.is_none());
+        assert!(matches!(price_rx.recv().await, Some(MarketEvent::PriceUpdate(p)) if p.update_id == 9));
+        assert!(price_rx.recv().await.is_none());
+        assert!(matches!(auxiliary_rx.recv().await, Some(MarketEvent::KlineEvent(k)) if k.kline.trade_count == 1));
+        assert!(matches!(auxiliary_rx.recv().await, Some(MarketEvent::MiniTickerEvent(t)) if t.event_time == 4));
+        assert!(matches!(auxiliary_rx.recv().await, Some(MarketEvent::PartialBook(b)) if b.last_update_id == 160));
+        assert!(auxiliary_rx.recv().await.is_none());
+        assert!(matches!(agg_trade_rx.recv().await, Some(MarketEvent::AggTradeEvent(t)) if t.agg_trade_id == 5));
+        assert!(agg_trade_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replay_with_decoders() {
+        let path = std::env::temp_dir().join(format!("mdc_test_{}_replay_okx.tape", std::process::id()));
+        let lines = [
+            "1000\tdepth#0\t{\"event\":\"subscribe\",\"arg\":{\"channel\":\"books\",\"instId\":\"BTC-USDT\"},\"connId\":\"a4d3ae55\"}",
+            "2000\tdepth#0\t{\"arg\":{\"channel\":\"books\",\"instId\":\"BTC-USDT\"},\"action\":\"snapshot\",\"data\":[{\"asks\":[[\"101.0\",\"1\",\"0\",\"1\"]],\"bids\":[[\"100.0\",\"1\",\"0\",\"1\"]],\"ts\":\"1\",\"checksum\":0,\"prevSeqId\":-1,\"seqId\":100}]}",
+            "3000\ttrade#0\t{\"arg\":{\"channel\":\"trades\",\"instId\":\"BTC-USDT\"},\"data\":[{\"instId\":\"BTC-USDT\",\"tradeId\":\"7\",\"px\":\"100.5\",\"sz\":\"0.1\",\"side\":\"buy\",\"ts\":\"2\"},{\"instId\":\"BTC-USDT\",\"tradeId\":\"8\",\"px\":\"100.5\",\"sz\":\"0.2\",\"side\":\"sell\",\"ts\":\"2\"}]}",
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let (depth_tx, mut depth_rx) = mpsc::channel::<MarketEvent>(100);
+        let (trade_tx, mut trade_rx) = mpsc::channel::<MarketEvent>(100);
+        let (price_tx, _price_rx) = mpsc::channel::<MarketEvent>(100);
+        let (agg_trade_tx, _agg_trade_rx) = mpsc::channel::<MarketEvent>(100);
+        let (auxiliary_tx, _auxiliary_rx) = mpsc::channel::<MarketEvent>(100);
+
+        TapeReplayer::new(path, 0.0, depth_tx, trade_tx, price_tx, agg_trade_tx, auxiliary_tx)
+            .with_decoders(frame_decoder::<OkxBookMessage>, frame_decoder::<OkxTradeMessage>)
+            .run()
+            .await;
+
+        assert!(matches!(depth_rx.recv().await, Some(MarketEvent::DepthSnapshot(s)) if s.last_update_id == 100));
+        assert!(depth_rx.recv().await.is_none());
+        assert!(matches!(trade_rx.recv().await, Some(MarketEvent::TradeEvent(t)) if t.trade_id == 7));
+        assert!(matches!(trade_rx.recv().await, Some(MarketEvent::TradeEvent(t)) if t.trade_id == 8));
+        assert!(trade_rx.recv().await.is_none());
+    }
+}
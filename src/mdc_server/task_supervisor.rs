@@ -0,0 +1,186 @@
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Instant};
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// Delay before the first restart of a task with the backoff policy
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum delay before a restart with the backoff policy. A task, which has been running for this long,
+/// is restarted after the initial delay again
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What happens to a pipeline task, which has failed (returned an error or panicked)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    /// Restart the task right away
+    Always,
+    /// Restart the task after a delay, which doubles with every consecutive failure
+    #[default]
+    Backoff,
+    /// Don't restart the task. The failure stops the pipeline
+    Never,
+}
+
+/// A pipeline component, which can be run again after a failure
+///
+/// The component keeps its channels and state between the runs, so a restart doesn't lose the pipeline wiring
+pub trait RestartableTask: Send {
+    /// Process the input until it is closed or an error occurs
+    fn process(&mut self) -> BoxFuture<'_, Result<()>>;
+}
+
+/// SupervisedTask runs a pipeline component and restarts it according to the restart policy if it fails
+///
+/// Restarts are logged and counted by the `task_restarts` counter. If a resync channel is provided, a fresh
+/// depth snapshot is requested on every restart, since events may have been lost with the failure
+pub struct SupervisedTask<T>
+where T: RestartableTask,
+{
+    name: String,
+    task: T,
+    policy: RestartPolicy,
+    restarts: Counter,
+    resync: Option<mpsc::Sender<()>>,
+}
+
+impl<T> SupervisedTask<T>
+where T: RestartableTask,
+{
+    /// Create a new SupervisedTask
+    ///
+    /// # Arguments
+    /// * `name` - Name of the task in logs
+    /// * `task` - The supervised component
+    /// * `policy` - Restart policy of the task
+    /// * `metrics` - Registry of the restart counter
+    pub fn new(name: &str, task: T, policy: RestartPolicy, metrics: &Metrics) -> Self {
+        Self {
+            name: name.to_string(),
+            task,
+            policy,
+            restarts: metrics.counter("task_restarts"),
+            resync: None,
+        }
+    }
+
+    /// Request a fresh depth snapshot through the channel whenever the task is restarted
+    pub fn with_resync(mut self, resync: mpsc::Sender<()>) -> Self {
+        self.resync = Some(resync);
+        self
+    }
+
+    /// Run the task until it finishes normally
+    ///
+    /// # Panics
+    /// If the task fails and the restart policy is `never`, so the failure is propagated to the pipeline
+    pub async fn run(mut self) {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            let started = Instant::now();
+            let failure = match AssertUnwindSafe(self.task.process()).catch_unwind().await {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(panic) => panic_message(panic.as_ref()),
+            };
+
+            if self.policy == RestartPolicy::Never {
+                panic!("Task '{}' failed. Details: '{}'", self.name, failure);
+            }
+
+            self.restarts.increment(1);
+            if started.elapsed() >= MAX_BACKOFF {
+                backoff = INITIAL_BACKOFF;
+            }
+
+            match self.policy {
+                RestartPolicy::Backoff => {
+                    tracing::error!("Task '{}' failed. Restarting in '{}' ms. Details: '{}'", self.name, backoff.as_millis(), failure);
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+                _ => tracing::error!("Task '{}' failed. Restarting. Details: '{}'", self.name, failure),
+            }
+
+            // A full channel means a snapshot has already been requested
+            if let Some(Err(mpsc::error::TrySendError::Closed(_))) = self.resync.as_ref().map(|resync| resync.try_send(())) {
+                tracing::warn!("Snapshot requests are not accepted. Task '{}' waits for the next snapshot", self.name);
+            }
+        }
+    }
+}
+
+/// Message of a caught panic
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic.downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    /// Fails the given number of times (by returning an error or panicking in turns), then finishes
+    struct FlakyTask {
+        failures: u32,
+        runs: u32,
+    }
+
+    impl RestartableTask for FlakyTask {
+        fn process(&mut self) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async move {
+                self.runs += 1;
+                if self.runs > self.failures {
+                    return Ok(());
+                }
+                if self.runs.is_multiple_of(2) {
+                    panic!("Flaky task panicked");
+                }
+                Err(anyhow!("Flaky task failed"))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_task_is_restarted_and_resynced() {
+        let metrics = Metrics::new();
+        let (resync_tx, mut resync_rx) = mpsc::channel(1);
+
+        SupervisedTask::new("flaky", FlakyTask { failures: 3, runs: 0 }, RestartPolicy::Always, &metrics)
+            .with_resync(resync_tx)
+            .run()
+            .await;
+
+        assert_eq!(metrics.snapshot()["task_restarts"], 3);
+        assert!(resync_rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_never_policy_propagates_the_failure() {
+        let metrics = Metrics::new();
+        let task = SupervisedTask::new("flaky", FlakyTask { failures: 1, runs: 0 }, RestartPolicy::Never, &metrics);
+
+        let result = tokio::spawn(task.run()).await;
+        assert!(result.is_err_and(|e| e.is_panic()));
+        assert_eq!(metrics.snapshot()["task_restarts"], 0);
+    }
+
+    #[test]
+    fn test_restart_policy_names() {
+        let policy: RestartPolicy = serde_yaml::from_str("backoff").unwrap();
+        assert_eq!(policy, RestartPolicy::Backoff);
+        assert_eq!(serde_yaml::from_str::<RestartPolicy>("never").unwrap(), RestartPolicy::Never);
+        assert!(serde_yaml::from_str::<RestartPolicy>("sometimes").is_err());
+    }
+}
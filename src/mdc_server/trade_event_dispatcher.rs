@@ -0,0 +1,150 @@
+use tokio::sync::mpsc;
+use crate::mdc_core::deduplication::{TradeDeduplicator, TradeVerdict};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_server::metrics::{Counter, Metrics};
+
+/// Number of trade ids behind the newest trade, which are remembered by the deduplicator.
+/// Redundant connections are never that far apart
+const TRADE_ID_WINDOW: u64 = 10_000;
+
+/// TradeEventDispatcher merges trades (or aggregated trades) from multiple WebSocket connections
+/// It forwards every trade once, dropping the copies received over the other connections
+pub struct TradeEventDispatcher {
+    input: mpsc::Receiver<MarketEvent>,
+    output: mpsc::Sender<MarketEvent>,
+    deduplicator: TradeDeduplicator,
+    duplicates: Counter,
+    stale: Counter,
+}
+
+impl TradeEventDispatcher {
+    /// Create a new TradeEventDispatcher
+    ///
+    /// # Arguments
+    /// * `input` - Receiver for MarketEvent messages from multiple connections
+    /// * `output` - Sender for deduplicated MarketEvent messages
+    /// * `name` - Name of the stream (e.g. "trade" or "agg_trade"), used as the prefix of the counters
+    /// * `metrics` - Registry of the dropped trade counters
+    pub fn new(input: mpsc::Receiver<MarketEvent>, output: mpsc::Sender<MarketEvent>, name: &str, metrics: &Metrics) -> Self {
+        Self {
+            input,
+            output,
+            deduplicator: TradeDeduplicator::new(TRADE_ID_WINDOW),
+            duplicates: metrics.counter(&format!("{}_duplicates", name)),
+            stale: metrics.counter(&format!("{}_stale", name)),
+        }
+    }
+
+    /// Run the TradeEventDispatcher
+    ///
+    /// This method will continuously process messages from the input channel
+    /// and send trades seen for the first time to the output channel
+    pub async fn run(mut self) {
+        tracing::info!("Starting TradeEventDispatcher");
+
+        while let Some(event) = self.input.recv().await {
+            // Aggregated trade ids are unique and increasing just like trade ids
+            let trade_id = match &event {
+                MarketEvent::TradeEvent(trade) => trade.trade_id,
+                MarketEvent::AggTradeEvent(trade) => trade.agg_trade_id,
+                _ => {
+                    tracing::error!("Received unexpected event type: '{:?}'. Discarding", &event);
+                    continue;
+                }
+            };
+
+            match self.deduplicator.accept(trade_id) {
+                TradeVerdict::New => {
+                    self.output
+                        .send(event)
+                        .await
+                        .expect("Failed to forward trade to output channel");
+                }
+                TradeVerdict::Duplicate => {
+                    tracing::trace!("Dropping duplicate trade '{}'", trade_id);
+                    self.duplicates.increment(1);
+                }
+                TradeVerdict::Stale => {
+                    tracing::warn!("Dropping trade '{}', which is too far behind the newest trade", trade_id);
+                    self.stale.increment(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{AggTradeEvent, TradeEvent};
+
+    fn make_trade(trade_id: u64) -> MarketEvent {
+        MarketEvent::TradeEvent(TradeEvent {
+            event_type: "trade".to_string(),
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            trade_id,
+            price: 100.0,
+            quantity: 1.0,
+            trade_time: 1000,
+            is_market_maker: false,
+            ignore: true,
+            received: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_trades_from_redundant_connections_are_forwarded_once() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+        tokio::spawn(TradeEventDispatcher::new(input_rx, output_tx, "trade", &metrics).run());
+
+        // The second connection lags behind and the first one misses trade 3
+        for trade_id in [1, 2, 1, 4, 2, 3, 4] {
+            input_tx.send(make_trade(trade_id)).await.unwrap();
+        }
+        drop(input_tx);
+
+        let mut forwarded = Vec::new();
+        while let Some(MarketEvent::TradeEvent(trade)) = output_rx.recv().await {
+            forwarded.push(trade.trade_id);
+        }
+
+        assert_eq!(forwarded, vec![1, 2, 4, 3]);
+        assert_eq!(metrics.snapshot()["trade_duplicates"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_aggregated_trades_are_keyed_on_aggregate_id() {
+        let (input_tx, input_rx) = mpsc::channel::<MarketEvent>(100);
+        let (output_tx, mut output_rx) = mpsc::channel::<MarketEvent>(100);
+        let metrics = Metrics::new();
+        tokio::spawn(TradeEventDispatcher::new(input_rx, output_tx, "agg_trade", &metrics).run());
+
+        for agg_trade_id in [10, 10, 11] {
+            input_tx.send(MarketEvent::AggTradeEvent(AggTradeEvent {
+                event_type: "aggTrade".to_string(),
+                event_time: 1000,
+                symbol: "BTCUSDT".to_string(),
+                agg_trade_id,
+                price: 100.0,
+                quantity: 1.0,
+                first_trade_id: agg_trade_id * 10,
+                last_trade_id: agg_trade_id * 10 + 5,
+                trade_time: 1000,
+                is_market_maker: false,
+                received: None,
+            })).await.unwrap();
+        }
+        drop(input_tx);
+
+        let mut forwarded = Vec::new();
+        while let Some(MarketEvent::AggTradeEvent(trade)) = output_rx.recv().await {
+            forwarded.push(trade.agg_trade_id);
+        }
+
+        assert_eq!(forwarded, vec![10, 11]);
+        assert_eq!(metrics.snapshot()["agg_trade_duplicates"], 1);
+    }
+}
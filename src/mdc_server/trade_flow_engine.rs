@@ -0,0 +1,92 @@
+use chrono::Utc;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::trade_flow::TradeFlowWindow;
+
+/// TradeFlowEngine computes the buy and sell volume, the trade count, the VWAP and the trade rate over a rolling
+/// window of trades, and sends them as `TradeFlow` events along with the auxiliary events, so every sink receives them
+pub struct TradeFlowEngine {
+    window: TradeFlowWindow,
+    interval: u64,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    output: mpsc::Sender<MarketEvent>,
+}
+
+impl TradeFlowEngine {
+    /// Create a new TradeFlowEngine
+    ///
+    /// # Arguments
+    /// * `window` - The rolling window of trades
+    /// * `interval` - Interval in milliseconds, at which the statistics are published
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    /// * `output` - Sender for MarketEvent messages containing TradeFlows
+    pub fn new(
+        window: TradeFlowWindow,
+        interval: u64,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+        output: mpsc::Sender<MarketEvent>,
+    ) -> Self {
+        Self {
+            window,
+            interval: interval.max(1),
+            trade_channel,
+            output,
+        }
+    }
+
+    /// Run the TradeFlowEngine as an asynchronous task
+    ///
+    /// This method will continuously account trades and publish the statistics every interval
+    /// until the trade channel is closed
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(self.interval));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                event = self.trade_channel.recv() => {
+                    match event {
+                        Some(MarketEvent::TradeEvent(trade)) => self.window.on_trade(Utc::now().timestamp_millis(), &trade),
+                        Some(event) => tracing::warn!("Unexpected event in trade flow channel: '{}'", event),
+                        None => break,
+                    }
+                }
+                _ = ticker.tick() => {
+                    let flow = self.window.flow(Utc::now().timestamp_millis());
+                    if let Err(e) = self.output.send(MarketEvent::TradeFlow(flow)).await {
+                        tracing::error!("Failed to send trade flow. Details: '{}'", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{FromJson, TradeEvent};
+
+    #[tokio::test]
+    async fn test_flow_is_published_every_interval() {
+        let (trade_tx, trade_rx) = mpsc::channel(10);
+        let (output_tx, mut output_rx) = mpsc::channel(10);
+        let engine = TradeFlowEngine::new(TradeFlowWindow::new(60_000), 100, trade_rx, output_tx);
+        let task = tokio::spawn(engine.run());
+
+        let trade = r#"{"e":"trade","E":1,"s":"BTCUSDT","t":7,"p":"100.0","q":"2.0","T":1,"m":true,"M":true}"#;
+        trade_tx.send(MarketEvent::TradeEvent(TradeEvent::from_json(trade).unwrap())).await.unwrap();
+
+        let Some(MarketEvent::TradeFlow(flow)) = output_rx.recv().await else {
+            panic!("Trade flow is expected");
+        };
+        assert_eq!((flow.sell_volume, flow.trade_count, flow.vwap), (2.0, 1, Some(100.0)));
+
+        drop(trade_tx);
+        task.await.unwrap();
+    }
+}
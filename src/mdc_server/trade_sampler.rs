@@ -0,0 +1,338 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use crate::mdc_server::disk_space_guard::CaptureGate;
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_server::output_tiers::BookFrame;
+
+/// Reason a book sample was taken
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleKind {
+    /// The book as it was when the trades arrived
+    Before,
+    /// The first book update received after the trades
+    After,
+    /// Periodic sample, independent of trades
+    Baseline,
+}
+
+/// Top-N book state sampled around trades
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BookSample {
+    pub kind: SampleKind,
+    /// Trades, which arrived between two book updates. Empty for baseline samples
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trade_ids: Vec<u64>,
+    #[serde(flatten)]
+    pub frame: BookFrame,
+}
+
+/// Trades waiting for the next book update
+struct PendingTrades {
+    trade_ids: Vec<u64>,
+    before: Option<BookFrame>,
+}
+
+/// TradeSampler decides when the book has to be sampled
+///
+/// Trades arriving between two book updates share the same book state, so they are grouped: the group
+/// produces a single `before` sample with the book at the arrival of its first trade and a single `after`
+/// sample with the next book update. A `baseline` sample is taken every baseline interval regardless of trades
+pub struct TradeSampler {
+    depth: usize,
+    baseline_interval: u64,
+    sequence: u64,
+    book: Option<Arc<OrderBook>>,
+    pending: Option<PendingTrades>,
+    last_baseline: Option<i64>,
+}
+
+impl TradeSampler {
+    /// Create a new TradeSampler
+    ///
+    /// # Arguments
+    /// * `depth` - Number of top levels per side in each sample
+    /// * `baseline_interval` - Interval between baseline samples in milliseconds. 0 disables baseline samples
+    pub fn new(depth: usize, baseline_interval: u64) -> Self {
+        Self {
+            depth,
+            baseline_interval,
+            sequence: 0,
+            book: None,
+            pending: None,
+            last_baseline: None,
+        }
+    }
+
+    fn frame(&self, now: i64) -> Option<BookFrame> {
+        self.book.as_ref().map(|book| BookFrame::new(self.sequence, now, book, self.depth))
+    }
+
+    /// Account a trade, which arrived at the given local time (milliseconds since epoch)
+    pub fn on_trade(&mut self, now: i64, trade_id: u64) {
+        if self.pending.is_none() {
+            self.pending = Some(PendingTrades { trade_ids: vec![], before: self.frame(now) });
+        }
+
+        if let Some(pending) = self.pending.as_mut() {
+            pending.trade_ids.push(trade_id);
+        }
+    }
+
+    /// Account a book update, which arrived at the given local time
+    ///
+    /// # Returns
+    /// The `before` and `after` samples of the pending trades, if any
+    pub fn on_book(&mut self, now: i64, book: Arc<OrderBook>) -> Vec<BookSample> {
+        self.sequence += 1;
+        self.book = Some(book);
+
+        let Some(pending) = self.pending.take() else {
+            return vec![];
+        };
+
+        let mut samples = Vec::with_capacity(2);
+        if let Some(before) = pending.before {
+            samples.push(BookSample { kind: SampleKind::Before, trade_ids: pending.trade_ids.clone(), frame: before });
+        }
+        if let Some(after) = self.frame(now) {
+            samples.push(BookSample { kind: SampleKind::After, trade_ids: pending.trade_ids, frame: after });
+        }
+        samples
+    }
+
+    /// Take a baseline sample if the baseline interval has passed since the last one
+    pub fn on_tick(&mut self, now: i64) -> Option<BookSample> {
+        if self.baseline_interval == 0 {
+            return None;
+        }
+
+        if let Some(last_baseline) = self.last_baseline {
+            if now < last_baseline + self.baseline_interval as i64 {
+                return None;
+            }
+        }
+
+        let frame = self.frame(now)?;
+        self.last_baseline = Some(now);
+        Some(BookSample { kind: SampleKind::Baseline, trade_ids: vec![], frame })
+    }
+
+    /// Report the `before` sample of the trades, for which no book update has arrived
+    pub fn finish(&mut self) -> Option<BookSample> {
+        let pending = self.pending.take()?;
+        pending.before.map(|before| BookSample { kind: SampleKind::Before, trade_ids: pending.trade_ids, frame: before })
+    }
+}
+
+/// Destination of book samples
+pub trait SampleSink: Send {
+    fn write(&mut self, sample: &BookSample) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// Writes book samples into a JSON lines file, one sample per line
+pub struct JsonLinesSampleSink {
+    writer: BufWriter<File>,
+}
+
+impl JsonLinesSampleSink {
+    /// Open the file for appending, creating it if needed
+    pub fn open(path: &PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open book sample file: {:?}", path))?;
+
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+}
+
+impl SampleSink for JsonLinesSampleSink {
+    fn write(&mut self, sample: &BookSample) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, sample)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// TradeSamplingEngine records the top-N book immediately before and after trades, plus a low-frequency
+/// baseline, instead of every book update
+pub struct TradeSamplingEngine {
+    sampler: TradeSampler,
+    sink: Box<dyn SampleSink>,
+    gate: CaptureGate,
+    trade_channel: mpsc::Receiver<MarketEvent>,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+}
+
+impl TradeSamplingEngine {
+    /// Create a new TradeSamplingEngine
+    ///
+    /// # Arguments
+    /// * `sampler` - Sampler, configured with the sample depth and the baseline interval
+    /// * `sink` - Destination of the samples
+    /// * `gate` - Switch, which pauses writing. Samples taken while writing is paused are discarded
+    /// * `trade_channel` - Receiver for MarketEvent messages containing TradeEvents
+    /// * `book_channel` - Receiver for OrderBook messages
+    pub fn new(
+        sampler: TradeSampler,
+        sink: Box<dyn SampleSink>,
+        gate: CaptureGate,
+        trade_channel: mpsc::Receiver<MarketEvent>,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    ) -> Self {
+        Self {
+            sampler,
+            sink,
+            gate,
+            trade_channel,
+            book_channel,
+        }
+    }
+
+    fn write(&mut self, samples: &[BookSample]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        if self.gate.is_paused() {
+            tracing::debug!("Capture is paused. Discarding '{}' book samples", samples.len());
+            return;
+        }
+
+        for sample in samples {
+            if let Err(e) = self.sink.write(sample) {
+                tracing::error!("Failed to write book sample. Details: '{}'", e);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Err(e) = self.sink.flush() {
+            tracing::error!("Failed to flush book sample sink. Details: '{}'", e);
+        }
+    }
+
+    /// Run the TradeSamplingEngine as an asynchronous task
+    ///
+    /// This method will continuously sample the book until both channels are closed
+    /// The baseline is checked and the sink is flushed every 100 ms
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(100));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                Some(event) = self.trade_channel.recv() => {
+                    match event {
+                        MarketEvent::TradeEvent(trade) => self.sampler.on_trade(Utc::now().timestamp_millis(), trade.trade_id),
+                        _ => tracing::warn!("Unexpected event in sampler trade channel: '{}'", event),
+                    }
+                }
+                Some(book) = self.book_channel.recv() => {
+                    let samples = self.sampler.on_book(Utc::now().timestamp_millis(), book);
+                    self.write(&samples);
+                }
+                _ = ticker.tick() => {
+                    let baseline = self.sampler.on_tick(Utc::now().timestamp_millis());
+                    self.write(baseline.as_slice());
+                    self.flush();
+                }
+                else => break,
+            }
+
+            if self.trade_channel.is_closed() && self.book_channel.is_closed()
+                && self.trade_channel.is_empty() && self.book_channel.is_empty() {
+                break;
+            }
+        }
+
+        let pending = self.sampler.finish();
+        self.write(pending.as_slice());
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    fn make_book(bid: f64) -> Arc<OrderBook> {
+        Arc::new(OrderBook::new(&DepthSnapshot {
+            last_update_id: 1,
+            bids: vec![
+                DepthEntry { price: FixedPoint::from(bid), quantity: FixedPoint::from(1.0) },
+                DepthEntry { price: FixedPoint::from(bid - 1.0), quantity: FixedPoint::from(2.0) },
+            ],
+            asks: vec![DepthEntry { price: FixedPoint::from(bid + 1.0), quantity: FixedPoint::from(3.0) }],
+        }))
+    }
+
+    #[test]
+    fn test_trades_are_sampled_before_and_after() {
+        let mut sampler = TradeSampler::new(1, 0);
+
+        // Without a book there is nothing to sample before the trade
+        sampler.on_trade(100, 1);
+        let samples = sampler.on_book(200, make_book(100.0));
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].kind, SampleKind::After);
+        assert_eq!(samples[0].trade_ids, vec![1]);
+
+        assert!(sampler.on_book(300, make_book(101.0)).is_empty());
+
+        sampler.on_trade(400, 2);
+        sampler.on_trade(410, 3);
+        let samples = sampler.on_book(500, make_book(99.0));
+        assert_eq!(samples, vec![
+            BookSample {
+                kind: SampleKind::Before,
+                trade_ids: vec![2, 3],
+                frame: BookFrame { sequence: 2, time: 400, bids: vec![[101.0, 1.0]], asks: vec![[102.0, 3.0]] },
+            },
+            BookSample {
+                kind: SampleKind::After,
+                trade_ids: vec![2, 3],
+                frame: BookFrame { sequence: 3, time: 500, bids: vec![[99.0, 1.0]], asks: vec![[100.0, 3.0]] },
+            },
+        ]);
+
+        sampler.on_trade(600, 4);
+        let pending = sampler.finish().unwrap();
+        assert_eq!(pending.kind, SampleKind::Before);
+        assert_eq!(pending.trade_ids, vec![4]);
+        assert!(sampler.finish().is_none());
+    }
+
+    #[test]
+    fn test_baseline_samples() {
+        let mut sampler = TradeSampler::new(5, 1000);
+
+        assert!(sampler.on_tick(0).is_none());
+
+        sampler.on_book(100, make_book(100.0));
+        let baseline = sampler.on_tick(200).unwrap();
+        assert_eq!(baseline.kind, SampleKind::Baseline);
+        assert_eq!(baseline.frame.bids.len(), 2);
+
+        assert!(sampler.on_tick(1100).is_none());
+        assert!(sampler.on_tick(1200).is_some());
+    }
+}
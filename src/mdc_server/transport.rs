@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use anyhow::{anyhow, Context, Result};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{client_async, WebSocketStream};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::client::Response;
+use url::Url;
+use crate::mdc_server::proxy::{host_name, ProxyConfig, ProxyRoute};
+use crate::mdc_server::tls::{TlsBackend, TlsConfig};
+use crate::mdc_server::ws_compression::{self, DeflateStream};
+
+/// WebSocket connection opened by a transport
+pub type WebSocketConnection = WebSocketStream<DeflateStream<ConnectionStream>>;
+
+/// Stream of a WebSocket connection, encrypted by the TLS backend of the transport for `wss` URLs
+#[derive(Debug)]
+pub enum ConnectionStream {
+    Plain(TcpStream),
+    NativeTls(Box<tokio_native_tls::TlsStream<TcpStream>>),
+    #[cfg(feature = "rustls-tls")]
+    Rustls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for ConnectionStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            ConnectionStream::NativeTls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            #[cfg(feature = "rustls-tls")]
+            ConnectionStream::Rustls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ConnectionStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            ConnectionStream::NativeTls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            #[cfg(feature = "rustls-tls")]
+            ConnectionStream::Rustls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            ConnectionStream::NativeTls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            #[cfg(feature = "rustls-tls")]
+            ConnectionStream::Rustls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ConnectionStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            ConnectionStream::NativeTls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+            #[cfg(feature = "rustls-tls")]
+            ConnectionStream::Rustls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+/// How the outgoing REST requests and WebSocket connections of a pipeline reach the exchange and other endpoints:
+/// through which proxy, with which TLS settings and whether WebSocket messages are compressed
+///
+/// The default connects directly with the TLS settings of the system and without compression
+#[derive(Debug, Clone, Default)]
+pub struct Transport {
+    pub proxy: Option<ProxyConfig>,
+    pub tls: Option<TlsConfig>,
+    /// Offer permessage-deflate compression of the WebSocket messages from the server
+    pub compression: bool,
+    /// Clients of `get`, per proxy route (the proxy URL, `None` if the requests aren't sent through a configured
+    /// proxy). Clones of the transport share them, so the requests reuse the pooled connections
+    clients: Arc<Mutex<HashMap<Option<String>, reqwest::Client>>>,
+}
+
+impl Transport {
+    /// Create a new Transport
+    ///
+    /// # Arguments
+    /// * `proxy` - Proxies of the connections, the proxy of the environment if not set
+    /// * `tls` - TLS settings, the ones of the system if not set
+    /// * `compression` - Offer permessage-deflate compression of the WebSocket messages
+    pub fn new(proxy: Option<ProxyConfig>, tls: Option<TlsConfig>, compression: bool) -> Self {
+        Self { proxy, tls, compression, clients: Arc::default() }
+    }
+
+    /// Builder of the HTTP client of the requests to the target, which sends them through its proxy
+    ///
+    /// Without a proxy configuration the client takes the proxy from the environment (e.g. `HTTPS_PROXY`)
+    ///
+    /// # Arguments
+    /// * `target` - URL of the endpoint, which the requests are sent to
+    ///
+    /// # Errors
+    /// If the target is routed through a SOCKS5 proxy, which REST requests can't use, or the CA certificates
+    /// can't be loaded
+    pub fn http_client(&self, target: &str) -> Result<reqwest::ClientBuilder> {
+        self.client_builder(self.http_route(target)?)
+    }
+
+    /// Proxy route of the REST requests to the target: `None` for a direct connection, or the proxy of the
+    /// environment if there is no proxy configuration
+    fn http_route(&self, target: &str) -> Result<Option<ProxyRoute>> {
+        let Some(proxy) = &self.proxy else {
+            return Ok(None);
+        };
+
+        let target = Url::parse(target).with_context(|| format!("Invalid URL: '{}'", target))?;
+        match proxy.route(&target)? {
+            Some(route) if route.is_socks() => Err(anyhow!(
+                "REST requests to '{}' can't be sent through SOCKS5 proxy '{}'. Route the host through an HTTP proxy",
+                target.host_str().unwrap_or_default(),
+                route.url
+            )),
+            route => Ok(route),
+        }
+    }
+
+    /// Builder of an HTTP client, which sends the requests through the route
+    fn client_builder(&self, route: Option<ProxyRoute>) -> Result<reqwest::ClientBuilder> {
+        let builder = match (&self.proxy, route) {
+            (None, _) => reqwest::Client::builder(),
+            (Some(_), None) => reqwest::Client::builder().no_proxy(),
+            (Some(_), Some(route)) => {
+                let mut http_proxy = reqwest::Proxy::all(route.url.as_str())?;
+                if let Some((username, password)) = &route.credentials {
+                    http_proxy = http_proxy.basic_auth(username, password);
+                }
+                reqwest::Client::builder().proxy(http_proxy)
+            }
+        };
+
+        match &self.tls {
+            Some(tls) => tls.configure(builder),
+            None => Ok(builder),
+        }
+    }
+
+    /// HTTP client of the requests to the target, which is built once per proxy route
+    fn client(&self, target: &str) -> Result<reqwest::Client> {
+        let route = self.http_route(target)?;
+        let key = route.as_ref().map(|route| route.url.to_string());
+        let mut clients = self.clients.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let client = self.client_builder(route)?.build()?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Check the server certificate of the response against the pin of its host
+    ///
+    /// With the native TLS backend, the request has been sent already, so a pin protects the received data, but not
+    /// the request itself. The rustls backend has checked the pin of an encrypted connection in the handshake
+    pub fn verify(&self, response: &reqwest::Response) -> Result<()> {
+        let Some(tls) = &self.tls else {
+            return Ok(());
+        };
+        if tls.backend == TlsBackend::Rustls && response.url().scheme() == "https" {
+            return Ok(());
+        }
+
+        let certificate = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate());
+        tls.verify_pin(response.url().host_str().unwrap_or_default(), certificate)
+    }
+
+    /// Send a GET request to the URL, like `reqwest::get`, with the client of its route
+    pub async fn get(&self, url: &str) -> Result<reqwest::Response> {
+        let response = self.client(url)?.get(url).send().await?;
+        self.verify(&response)?;
+        Ok(response)
+    }
+
+    /// Open a WebSocket connection, like `connect_async`
+    ///
+    /// A pinned server certificate is verified before the connection request is sent, by the rustls backend in the
+    /// handshake. Messages, which the server compresses, are inflated
+    ///
+    /// # Arguments
+    /// * `request` - The URL or the request of the connection
+    pub async fn connect_websocket<R>(&self, request: R) -> Result<(WebSocketConnection, Response)>
+    where R: IntoClientRequest + Unpin,
+    {
+        let mut request = request.into_client_request()?;
+        if self.compression {
+            ws_compression::offer(&mut request);
+        }
+
+        let target = Url::parse(&request.uri().to_string())?;
+        let host = host_name(&target).ok_or_else(|| anyhow!("WebSocket URL '{}' has no host", target))?;
+        let port = target.port_or_known_default().unwrap_or(443);
+        let route = match &self.proxy {
+            Some(proxy) => proxy.route(&target)?,
+            None => None,
+        };
+        let stream = match route {
+            Some(route) => route.connect(&host, port).await?,
+            None => TcpStream::connect((host.as_str(), port)).await?,
+        };
+
+        let stream = match (target.scheme(), &self.tls) {
+            #[cfg(feature = "rustls-tls")]
+            ("wss", Some(tls)) if tls.backend == TlsBackend::Rustls => {
+                let config = tls.rustls_config()?;
+                let server_name = rustls::ServerName::try_from(host.as_str())?;
+                let stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+                    .connect(server_name, stream)
+                    .await
+                    .with_context(|| format!("TLS handshake with '{}' failed", host))?;
+                ConnectionStream::Rustls(Box::new(stream))
+            }
+            ("wss", _) => {
+                let connector = match &self.tls {
+                    Some(tls) => tls.connector()?,
+                    None => native_tls::TlsConnector::new()?,
+                };
+                let stream = tokio_native_tls::TlsConnector::from(connector)
+                    .connect(&host, stream)
+                    .await
+                    .with_context(|| format!("TLS handshake with '{}' failed", host))?;
+                if let Some(tls) = &self.tls {
+                    let certificate = stream.get_ref().peer_certificate()?.map(|certificate| certificate.to_der()).transpose()?;
+                    tls.verify_pin(&host, certificate.as_deref())?;
+                }
+                ConnectionStream::NativeTls(Box::new(stream))
+            }
+            (_, tls) => {
+                if let Some(tls) = tls {
+                    tls.verify_pin(&host, None)?;
+                }
+                ConnectionStream::Plain(stream)
+            }
+        };
+
+        Ok(client_async(request, DeflateStream::new(stream, self.compression)).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clients_are_cached_per_route() {
+        let proxy: ProxyConfig = serde_yaml::from_str(r#"
+url: "http://proxy.corp:3128"
+endpoints:
+  - host: "127.0.0.1"
+"#).unwrap();
+        let transport = Transport::new(Some(proxy), None, false);
+        let clone = transport.clone();
+
+        transport.client("https://api.binance.com/api/v3/depth?symbol=BTCUSDT").unwrap();
+        clone.client("https://api.binance.com/api/v3/exchangeInfo").unwrap();
+        assert_eq!(transport.clients.lock().unwrap().len(), 1);
+
+        // The direct route gets a client of its own
+        transport.client("http://127.0.0.1:8080/api/v3/time").unwrap();
+        let clients = transport.clients.lock().unwrap();
+        assert_eq!(clients.len(), 2);
+        assert!(clients.contains_key(&None));
+        assert!(clients.contains_key(&Some("http://proxy.corp:3128/".to_string())));
+    }
+}
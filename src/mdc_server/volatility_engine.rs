@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use chrono::Utc;
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use crate::mdc_core::models::MarketEvent;
+use crate::mdc_core::order_book::OrderBook;
+use crate::mdc_core::volatility::VolatilityWindow;
+
+/// VolatilityEngine samples the mid price and the spread of the latest book every interval, and sends the rolling
+/// realized volatility and average spread as `Volatility` events along with the auxiliary events
+pub struct VolatilityEngine {
+    window: VolatilityWindow,
+    interval: u64,
+    book_channel: mpsc::Receiver<Arc<OrderBook>>,
+    output: mpsc::Sender<MarketEvent>,
+}
+
+impl VolatilityEngine {
+    /// Create a new VolatilityEngine
+    ///
+    /// # Arguments
+    /// * `window` - The rolling window of samples
+    /// * `interval` - Sampling interval in milliseconds
+    /// * `book_channel` - Receiver for OrderBook messages
+    /// * `output` - Sender for MarketEvent messages containing VolatilityMetrics
+    pub fn new(
+        window: VolatilityWindow,
+        interval: u64,
+        book_channel: mpsc::Receiver<Arc<OrderBook>>,
+        output: mpsc::Sender<MarketEvent>,
+    ) -> Self {
+        Self {
+            window,
+            interval: interval.max(1),
+            book_channel,
+            output,
+        }
+    }
+
+    /// Run the VolatilityEngine as an asynchronous task
+    ///
+    /// This method will continuously sample the latest book until the book channel is closed.
+    /// No sample is taken while the book has an empty side
+    pub async fn run(mut self) {
+        let mut ticker = interval(Duration::from_millis(self.interval));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut latest: Option<Arc<OrderBook>> = None;
+
+        loop {
+            tokio::select! {
+                book = self.book_channel.recv() => {
+                    match book {
+                        Some(book) => latest = Some(book),
+                        None => break,
+                    }
+                }
+                _ = ticker.tick() => {
+                    let Some((mid_price, spread)) = latest.as_ref().and_then(|book| book.mid_price().zip(book.spread())) else {
+                        continue;
+                    };
+
+                    let metrics = self.window.on_sample(Utc::now().timestamp_millis(), mid_price, spread);
+                    if let Err(e) = self.output.send(MarketEvent::Volatility(metrics)).await {
+                        tracing::error!("Failed to send volatility metrics. Details: '{}'", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::models::{DepthEntry, DepthSnapshot};
+
+    #[tokio::test]
+    async fn test_latest_book_is_sampled() {
+        let (book_tx, book_rx) = mpsc::channel(10);
+        let (output_tx, mut output_rx) = mpsc::channel(10);
+        let engine = VolatilityEngine::new(VolatilityWindow::new(60_000), 50, book_rx, output_tx);
+        let task = tokio::spawn(engine.run());
+
+        let entry = |price: &str| DepthEntry { price: price.parse().unwrap(), quantity: "1".parse().unwrap() };
+        let book = DepthSnapshot { last_update_id: 1, bids: vec![entry("100")], asks: vec![entry("102")] };
+        book_tx.send(Arc::new(OrderBook::new(&book))).await.unwrap();
+
+        let Some(MarketEvent::Volatility(metrics)) = output_rx.recv().await else {
+            panic!("Volatility metrics are expected");
+        };
+        assert_eq!((metrics.mid_price, metrics.spread, metrics.average_spread), (101.0, 2.0, 2.0));
+
+        drop(book_tx);
+        task.await.unwrap();
+    }
+}
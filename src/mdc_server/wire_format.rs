@@ -0,0 +1,398 @@
+use bytes::Bytes;
+use prost::Message;
+use crate::mdc_core::book_metrics;
+use crate::mdc_core::continuity;
+use crate::mdc_core::trade_flow;
+use crate::mdc_core::volatility;
+use crate::mdc_core::anomaly;
+use crate::mdc_core::models::{self, DepthEntry, MarketEvent};
+use crate::mdc_core::order_book;
+use proto::event::Payload;
+use proto::{AggTrade, BookMetrics, DepthSnapshot, DepthUpdate, Event, Kline, Liquidation, MarkPrice, MiniTicker, OrderBook, PartialBook, PriceLevel, PriceUpdate, Resync, SequenceGap, Ticker, TradeEvent, TradeFlow, Volatility, Anomaly, ScriptFields};
+use proto::resync;
+
+pub mod proto {
+    tonic::include_proto!("mdc.events.v1");
+}
+
+/// Version of the wire format, written into every event
+pub const WIRE_FORMAT_VERSION: u32 = 1;
+
+fn levels(entries: &[DepthEntry]) -> Vec<PriceLevel> {
+    entries
+        .iter()
+        .map(|entry| PriceLevel { price: entry.price.to_f64(), quantity: entry.quantity.to_f64() })
+        .collect()
+}
+
+impl From<&models::DepthUpdate> for DepthUpdate {
+    fn from(update: &models::DepthUpdate) -> Self {
+        Self {
+            event_time: update.event_time,
+            first_update_id: update.first_update_id,
+            last_update_id: update.last_update_id,
+            previous_last_update_id: update.previous_last_update_id,
+            bids: levels(&update.bids),
+            asks: levels(&update.asks),
+        }
+    }
+}
+
+impl From<&models::DepthSnapshot> for DepthSnapshot {
+    fn from(snapshot: &models::DepthSnapshot) -> Self {
+        Self {
+            last_update_id: snapshot.last_update_id,
+            bids: levels(&snapshot.bids),
+            asks: levels(&snapshot.asks),
+        }
+    }
+}
+
+impl From<&models::TradeEvent> for TradeEvent {
+    fn from(trade: &models::TradeEvent) -> Self {
+        Self {
+            trade_id: trade.trade_id,
+            event_time: trade.event_time,
+            trade_time: trade.trade_time,
+            price: trade.price,
+            quantity: trade.quantity,
+            is_buyer_maker: trade.is_market_maker,
+        }
+    }
+}
+
+impl From<&models::AggTradeEvent> for AggTrade {
+    fn from(trade: &models::AggTradeEvent) -> Self {
+        Self {
+            agg_trade_id: trade.agg_trade_id,
+            event_time: trade.event_time,
+            trade_time: trade.trade_time,
+            price: trade.price,
+            quantity: trade.quantity,
+            first_trade_id: trade.first_trade_id,
+            last_trade_id: trade.last_trade_id,
+            is_buyer_maker: trade.is_market_maker,
+        }
+    }
+}
+
+impl From<&models::PriceUpdate> for PriceUpdate {
+    fn from(price: &models::PriceUpdate) -> Self {
+        Self {
+            update_id: price.update_id,
+            best_bid_price: price.best_bid_price,
+            best_bid_quantity: price.best_bid_quantity,
+            best_ask_price: price.best_ask_price,
+            best_ask_quantity: price.best_ask_quantity,
+        }
+    }
+}
+
+impl From<&models::KlineEvent> for Kline {
+    fn from(event: &models::KlineEvent) -> Self {
+        let kline = &event.kline;
+        Self {
+            event_time: event.event_time,
+            interval: kline.interval.to_string(),
+            start_time: kline.start_time,
+            close_time: kline.close_time,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            quote_volume: kline.quote_volume,
+            trade_count: kline.trade_count,
+            taker_buy_volume: kline.taker_buy_volume,
+            taker_buy_quote_volume: kline.taker_buy_quote_volume,
+            is_closed: kline.is_closed,
+            first_trade_id: kline.first_trade_id,
+            last_trade_id: kline.last_trade_id,
+        }
+    }
+}
+
+impl From<&models::TickerEvent> for Ticker {
+    fn from(ticker: &models::TickerEvent) -> Self {
+        Self {
+            event_time: ticker.event_time,
+            price_change: ticker.price_change,
+            price_change_percent: ticker.price_change_percent,
+            weighted_average_price: ticker.weighted_average_price,
+            last_price: ticker.last_price,
+            last_quantity: ticker.last_quantity,
+            open: ticker.open,
+            high: ticker.high,
+            low: ticker.low,
+            volume: ticker.volume,
+            quote_volume: ticker.quote_volume,
+            open_time: ticker.open_time,
+            close_time: ticker.close_time,
+            first_trade_id: ticker.first_trade_id,
+            last_trade_id: ticker.last_trade_id,
+            trade_count: ticker.trade_count,
+        }
+    }
+}
+
+impl From<&models::MiniTickerEvent> for MiniTicker {
+    fn from(ticker: &models::MiniTickerEvent) -> Self {
+        Self {
+            event_time: ticker.event_time,
+            open: ticker.open,
+            high: ticker.high,
+            low: ticker.low,
+            close: ticker.close,
+            volume: ticker.volume,
+            quote_volume: ticker.quote_volume,
+        }
+    }
+}
+
+impl From<&models::MarkPriceEvent> for MarkPrice {
+    fn from(price: &models::MarkPriceEvent) -> Self {
+        Self {
+            event_time: price.event_time,
+            mark_price: price.mark_price,
+            index_price: price.index_price,
+            estimated_settle_price: price.estimated_settle_price,
+            funding_rate: price.funding_rate,
+            next_funding_time: price.next_funding_time,
+        }
+    }
+}
+
+impl From<&models::LiquidationEvent> for Liquidation {
+    fn from(liquidation: &models::LiquidationEvent) -> Self {
+        let order = &liquidation.order;
+        Self {
+            event_time: liquidation.event_time,
+            trade_time: order.trade_time,
+            side: order.side.clone(),
+            order_type: order.order_type.clone(),
+            time_in_force: order.time_in_force.clone(),
+            price: order.price,
+            average_price: order.average_price,
+            quantity: order.quantity,
+            last_filled_quantity: order.last_filled_quantity,
+            filled_quantity: order.filled_quantity,
+            status: order.status.clone(),
+        }
+    }
+}
+
+impl From<&models::PartialBookEvent> for PartialBook {
+    fn from(book: &models::PartialBookEvent) -> Self {
+        Self {
+            last_update_id: book.last_update_id,
+            event_time: book.event_time,
+            bids: levels(&book.bids),
+            asks: levels(&book.asks),
+        }
+    }
+}
+
+impl From<&book_metrics::BookMetrics> for BookMetrics {
+    fn from(metrics: &book_metrics::BookMetrics) -> Self {
+        Self {
+            depth: metrics.depth as u32,
+            bid_quantity: metrics.bid_quantity,
+            ask_quantity: metrics.ask_quantity,
+            imbalance: metrics.imbalance,
+            mid_price: metrics.mid_price,
+            microprice: metrics.microprice,
+            weighted_mid: metrics.weighted_mid,
+        }
+    }
+}
+
+impl From<&trade_flow::TradeFlow> for TradeFlow {
+    fn from(flow: &trade_flow::TradeFlow) -> Self {
+        Self {
+            window: flow.window,
+            buy_volume: flow.buy_volume,
+            sell_volume: flow.sell_volume,
+            trade_count: flow.trade_count,
+            vwap: flow.vwap,
+            trade_rate: flow.trade_rate,
+        }
+    }
+}
+
+impl From<&volatility::VolatilityMetrics> for Volatility {
+    fn from(metrics: &volatility::VolatilityMetrics) -> Self {
+        Self {
+            window: metrics.window,
+            samples: metrics.samples as u32,
+            mid_price: metrics.mid_price,
+            spread: metrics.spread,
+            realized_volatility: metrics.realized_volatility,
+            annualized_volatility: metrics.annualized_volatility,
+            average_spread: metrics.average_spread,
+            average_spread_bps: metrics.average_spread_bps,
+        }
+    }
+}
+
+impl From<&anomaly::AnomalyEvent> for Anomaly {
+    fn from(event: &anomaly::AnomalyEvent) -> Self {
+        Self {
+            kind: event.anomaly.kind().to_string(),
+            message: event.anomaly.to_string(),
+        }
+    }
+}
+
+impl From<&models::ScriptFields> for ScriptFields {
+    fn from(fields: &models::ScriptFields) -> Self {
+        Self {
+            script: fields.script.clone(),
+            kind: fields.kind.clone(),
+            event_time: fields.event_time,
+            fields: serde_json::Value::Object(fields.fields.clone()).to_string(),
+        }
+    }
+}
+
+impl From<&continuity::SequenceGap> for SequenceGap {
+    fn from(gap: &continuity::SequenceGap) -> Self {
+        Self {
+            last_update_id: gap.last_update_id,
+            next_update_id: gap.next_update_id,
+            buffered: gap.buffered as u32,
+        }
+    }
+}
+
+impl From<&continuity::Resync> for Resync {
+    fn from(resync: &continuity::Resync) -> Self {
+        let source = match resync.source {
+            continuity::ResyncSource::Snapshot => resync::Source::Snapshot,
+            continuity::ResyncSource::Validation => resync::Source::Validation,
+        };
+
+        Self {
+            source: source as i32,
+            previous_update_id: resync.previous_update_id,
+            update_id: resync.update_id,
+        }
+    }
+}
+
+/// EventEncoder encodes the events of a pipeline into the `mdc.events.v1` protobuf wire format
+///
+/// Every encoded event is a single `Event` message, prefixed with its length as a varint,
+/// so a stream of them can be written into a socket or a file as is
+#[derive(Debug, Clone)]
+pub struct EventEncoder {
+    exchange: String,
+    symbol: String,
+    depth: usize,
+}
+
+impl EventEncoder {
+    /// Create a new EventEncoder
+    ///
+    /// # Arguments
+    /// * `exchange` - Name of the exchange, written into every event
+    /// * `symbol` - Captured instrument, written into every event
+    /// * `depth` - Number of top levels per side in encoded order books
+    pub fn new(exchange: &str, symbol: &str, depth: usize) -> Self {
+        Self { exchange: exchange.to_string(), symbol: symbol.to_string(), depth }
+    }
+
+    fn encode(&self, time: i64, payload: Payload) -> Bytes {
+        let event = Event {
+            version: WIRE_FORMAT_VERSION,
+            exchange: self.exchange.clone(),
+            symbol: self.symbol.clone(),
+            time,
+            payload: Some(payload),
+        };
+
+        Bytes::from(event.encode_length_delimited_to_vec())
+    }
+
+    /// Encode a market event published at the given local time (milliseconds since epoch)
+    ///
+    /// # Returns
+    /// The length-delimited event, or `None` for events, which have no wire representation (BBO changes)
+    pub fn encode_event(&self, time: i64, event: &MarketEvent) -> Option<Bytes> {
+        let payload = match event {
+            MarketEvent::DepthUpdate(update) => Payload::DepthUpdate(update.into()),
+            MarketEvent::DepthSnapshot(snapshot) => Payload::DepthSnapshot(snapshot.into()),
+            MarketEvent::TradeEvent(trade) => Payload::Trade(trade.into()),
+            MarketEvent::PriceUpdate(price) => Payload::PriceUpdate(price.into()),
+            MarketEvent::KlineEvent(kline) => Payload::Kline(kline.into()),
+            MarketEvent::AggTradeEvent(trade) => Payload::AggTrade(trade.into()),
+            MarketEvent::TickerEvent(ticker) => Payload::Ticker(ticker.into()),
+            MarketEvent::MiniTickerEvent(ticker) => Payload::MiniTicker(ticker.into()),
+            MarketEvent::MarkPriceEvent(price) => Payload::MarkPrice(price.into()),
+            MarketEvent::LiquidationEvent(liquidation) => Payload::Liquidation(liquidation.into()),
+            MarketEvent::PartialBook(book) => Payload::PartialBook(book.into()),
+            MarketEvent::BookMetrics(metrics) => Payload::BookMetrics(metrics.into()),
+            MarketEvent::TradeFlow(flow) => Payload::TradeFlow(flow.into()),
+            MarketEvent::Volatility(metrics) => Payload::Volatility(metrics.into()),
+            MarketEvent::Anomaly(anomaly) => Payload::Anomaly(anomaly.into()),
+            MarketEvent::ScriptFields(fields) => Payload::ScriptFields(fields.into()),
+            MarketEvent::SequenceGap(gap) => Payload::SequenceGap(gap.into()),
+            MarketEvent::Resync(resync) => Payload::Resync(resync.into()),
+            MarketEvent::BboChange(_) => return None,
+        };
+
+        Some(self.encode(time, payload))
+    }
+
+    /// Encode the top of the maintained book published at the given local time
+    ///
+    /// # Arguments
+    /// * `time` - Local time in milliseconds since epoch
+    /// * `sequence` - Number of the book update
+    /// * `book` - The maintained book
+    pub fn encode_book(&self, time: i64, sequence: u64, book: &order_book::OrderBook) -> Bytes {
+        let (bids, asks) = book.top_n(self.depth);
+        self.encode(time, Payload::OrderBook(OrderBook { sequence, bids: levels(&bids), asks: levels(&asks) }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mdc_core::fixed_point::FixedPoint;
+    use crate::mdc_core::models::BboChange;
+
+    #[test]
+    fn test_encoding() {
+        let encoder = EventEncoder::new("binance-futures", "BTCUSDT", 5);
+        let update = models::DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 1672515782136,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 101,
+            last_update_id: 105,
+            previous_last_update_id: Some(100),
+            bids: vec![DepthEntry { price: FixedPoint::from(100.5), quantity: FixedPoint::ZERO }],
+            asks: vec![],
+            checksum: None,
+            received: None,
+        };
+
+        let frame = encoder.encode_event(1000, &MarketEvent::DepthUpdate(update)).unwrap();
+        let event = Event::decode_length_delimited(frame).unwrap();
+        assert_eq!(event.version, WIRE_FORMAT_VERSION);
+        assert_eq!(event.exchange, "binance-futures");
+        assert_eq!(event.symbol, "BTCUSDT");
+        assert_eq!(event.time, 1000);
+        assert_eq!(event.payload, Some(Payload::DepthUpdate(DepthUpdate {
+            event_time: 1672515782136,
+            first_update_id: 101,
+            last_update_id: 105,
+            previous_last_update_id: Some(100),
+            bids: vec![PriceLevel { price: 100.5, quantity: 0.0 }],
+            asks: vec![],
+        })));
+
+        let bbo = MarketEvent::BboChange(BboChange { update_id: 1, best_bid: None, best_ask: None });
+        assert!(encoder.encode_event(1000, &bbo).is_none());
+    }
+}
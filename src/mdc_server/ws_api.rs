@@ -0,0 +1,223 @@
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use crate::mdc_server::transport::{Transport, WebSocketConnection};
+
+/// Time in milliseconds to wait for the response of a request
+const RESPONSE_TIMEOUT: u64 = 10_000;
+
+/// Error of a rejected request
+#[derive(Debug, Deserialize)]
+pub struct WsApiError {
+    pub code: i64,
+    pub msg: String,
+}
+
+/// A rate limit with its usage, reported with every response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub rate_limit_type: String,
+    pub interval: String,
+    pub interval_num: u64,
+    pub limit: u64,
+    #[serde(default)]
+    pub count: Option<u64>,
+}
+
+/// Response of the Binance WebSocket API to a request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsApiResponse {
+    /// Id of the request. Not set for requests, which couldn't be parsed
+    #[serde(default)]
+    pub id: Option<u64>,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// Result of a successful request, as it is sent
+    #[serde(default)]
+    pub result: Option<Box<RawValue>>,
+    #[serde(default)]
+    pub error: Option<WsApiError>,
+    #[serde(default)]
+    pub rate_limits: Vec<RateLimit>,
+}
+
+impl WsApiResponse {
+    /// Request weight of the current minute, which the exchange has accounted to the IP
+    pub fn used_weight(&self) -> Option<u64> {
+        self.rate_limits
+            .iter()
+            .find(|limit| limit.rate_limit_type == "REQUEST_WEIGHT" && limit.interval == "MINUTE" && limit.interval_num == 1)
+            .and_then(|limit| limit.count)
+    }
+
+    /// The result of a successful request
+    ///
+    /// # Errors
+    /// If the request has been rejected
+    pub fn into_result(self) -> Result<Box<RawValue>> {
+        match (self.result, self.error) {
+            (Some(result), _) if self.status == 200 => Ok(result),
+            (_, Some(error)) => Err(anyhow!("Request rejected with status '{}': '{}' ('{}')", self.status, error.msg, error.code)),
+            _ => Err(anyhow!("Request failed with status '{}'", self.status)),
+        }
+    }
+}
+
+/// Client of the Binance WebSocket API (e.g. "wss://ws-api.binance.com:443/ws-api/v3"), which sends requests
+/// over a single persistent connection, saving the connection setup of a REST request
+///
+/// The connection is opened with the first request. If the server has closed it meanwhile (e.g. because it
+/// stayed idle for too long), the request is sent once more over a new connection
+pub struct WsApiClient {
+    url: String,
+    connection: Option<WebSocketConnection>,
+    next_id: u64,
+    transport: Transport,
+}
+
+impl WsApiClient {
+    /// Create a new WsApiClient
+    ///
+    /// # Arguments
+    /// * `url` - The WebSocket API endpoint
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            connection: None,
+            next_id: 0,
+            transport: Transport::default(),
+        }
+    }
+
+    /// Connect with the proxy and TLS settings of the transport
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Send the request and wait for its response
+    ///
+    /// # Arguments
+    /// * `method` - The method of the request (e.g. `depth`)
+    /// * `params` - The parameters of the request
+    ///
+    /// # Errors
+    /// If the request can't be sent or isn't answered in time. A rejected request is a response with an error
+    pub async fn request(&mut self, method: &str, params: serde_json::Value) -> Result<WsApiResponse> {
+        if self.connection.is_some() {
+            match self.try_request(method, &params).await {
+                Ok(response) => return Ok(response),
+                Err(e) => tracing::debug!("Request over the open WebSocket API connection failed: '{}'. Reconnecting", e),
+            }
+        }
+
+        self.try_request(method, &params).await
+    }
+
+    /// Send the request over the open connection, or a new one. The connection is dropped if the request fails
+    async fn try_request(&mut self, method: &str, params: &serde_json::Value) -> Result<WsApiResponse> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let request = serde_json::json!({ "id": id, "method": method, "params": params }).to_string();
+
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => {
+                let (connection, _) = self.transport.connect_websocket(self.url.as_str())
+                    .await
+                    .with_context(|| format!("Failed to connect to the WebSocket API '{}'", self.url))?;
+                connection
+            }
+        };
+
+        let response = timeout(Duration::from_millis(RESPONSE_TIMEOUT), Self::exchange(&mut connection, id, request))
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("No response within '{}' ms", RESPONSE_TIMEOUT)));
+
+        // The connection is kept for the next request, unless its state is unknown
+        self.connection = response.is_ok().then_some(connection);
+        response
+    }
+
+    /// Send the request and read the frames until its response
+    async fn exchange(connection: &mut WebSocketConnection, id: u64, request: String) -> Result<WsApiResponse> {
+        connection.send(Message::text(request)).await?;
+
+        while let Some(message) = connection.next().await {
+            match message? {
+                Message::Text(text) => {
+                    let response: WsApiResponse = serde_json::from_str(&text).context("Failed to parse WebSocket API response")?;
+                    if response.id.is_none_or(|response_id| response_id == id) {
+                        return Ok(response);
+                    }
+                }
+                Message::Ping(payload) => connection.send(Message::Pong(payload)).await?,
+                Message::Close(frame) => return Err(anyhow!("WebSocket API connection closed: '{:?}'", frame)),
+                _ => {}
+            }
+        }
+
+        Err(anyhow!("WebSocket API connection closed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serve `depth` requests, closing each connection after the given number of responses
+    async fn serve(listener: TcpListener, responses_per_connection: usize) {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut connection = tokio_tungstenite::accept_async(socket).await.unwrap();
+            for _ in 0..responses_per_connection {
+                let Some(Ok(Message::Text(text))) = connection.next().await else {
+                    break;
+                };
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = match request["params"]["symbol"].as_str() {
+                    Some("BTCUSDT") => serde_json::json!({
+                        "id": request["id"],
+                        "status": 200,
+                        "result": { "lastUpdateId": 160, "bids": [["100.0", "1.0"]], "asks": [["101.0", "2.0"]] },
+                        "rateLimits": [{ "rateLimitType": "REQUEST_WEIGHT", "interval": "MINUTE", "intervalNum": 1, "limit": 6000, "count": 7 }]
+                    }),
+                    _ => serde_json::json!({
+                        "id": request["id"],
+                        "status": 400,
+                        "error": { "code": -1121, "msg": "Invalid symbol." }
+                    }),
+                };
+                connection.send(Message::text(response.to_string())).await.unwrap();
+            }
+            connection.close(None).await.ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_one_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        // The server closes each connection after two responses, so the client has to reconnect
+        tokio::spawn(serve(listener, 2));
+
+        let mut client = WsApiClient::new(url);
+        for _ in 0..3 {
+            let response = client.request("depth", serde_json::json!({ "symbol": "BTCUSDT", "limit": 5 })).await.unwrap();
+            assert_eq!(response.used_weight(), Some(7));
+            let result = response.into_result().unwrap();
+            assert!(result.get().contains(r#""lastUpdateId":160"#));
+        }
+
+        let response = client.request("depth", serde_json::json!({ "symbol": "NOPE", "limit": 5 })).await.unwrap();
+        assert_eq!(response.status, 400);
+        assert!(response.into_result().unwrap_err().to_string().contains("Invalid symbol."));
+    }
+}
@@ -0,0 +1,387 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use flate2::{Decompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tungstenite::handshake::client::Request;
+use tungstenite::http::HeaderValue;
+
+/// Name of the extension in the `Sec-WebSocket-Extensions` headers
+const EXTENSION: &str = "permessage-deflate";
+/// Tail of every compressed message, which the sender strips (RFC 7692, section 7.2.1)
+const MESSAGE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+/// Upper bound of the frame size, the default maximum message size of tungstenite
+const MAX_FRAME_SIZE: usize = 64 << 20;
+/// Bytes read from the connection at once, and the output space reserved for every inflate step
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Offer permessage-deflate compression of the messages from the server in the connection request
+pub fn offer(request: &mut Request) {
+    request.headers_mut().insert("Sec-WebSocket-Extensions", HeaderValue::from_static(EXTENSION));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    /// The response to the connection request is being received
+    Handshake,
+    /// The frames are being received. Compressed frames are inflated, if the server has accepted the extension
+    Frames { negotiated: bool },
+}
+
+/// DeflateStream carries a WebSocket client connection and inflates the messages, which the server has compressed with
+/// the permessage-deflate extension (RFC 7692), before tungstenite reads them
+///
+/// tungstenite doesn't implement the extension, so the stream rewrites every compressed frame into an uncompressed one:
+/// its payload is inflated and the RSV1 bit cleared. The context of the compression is taken over from message to
+/// message, unless the server finishes the deflate stream. Messages to the server are sent uncompressed, which the
+/// extension permits, so writes pass through.
+///
+/// Without the offer, or if the server declines it, the stream passes the bytes through unchanged
+#[derive(Debug)]
+pub struct DeflateStream<S> {
+    inner: S,
+    phase: Phase,
+    /// Received bytes, which haven't been processed yet
+    input: Vec<u8>,
+    /// Processed bytes, which haven't been read yet, from `position` on
+    output: Vec<u8>,
+    position: usize,
+    decompress: Decompress,
+    /// Whether the frames of the current data message are compressed
+    compressed_message: bool,
+}
+
+impl<S> DeflateStream<S> {
+    /// Create a new DeflateStream
+    ///
+    /// # Arguments
+    /// * `inner` - The connection, before the connection request is sent over it
+    /// * `offered` - Whether the connection request offers the extension (see `offer`)
+    pub fn new(inner: S, offered: bool) -> Self {
+        Self {
+            inner,
+            phase: match offered {
+                true => Phase::Handshake,
+                false => Phase::Frames { negotiated: false },
+            },
+            input: Vec::new(),
+            output: Vec::new(),
+            position: 0,
+            decompress: Decompress::new(false),
+            compressed_message: false,
+        }
+    }
+
+    /// Move the processable part of the received bytes to the output
+    ///
+    /// # Returns
+    /// `true` if bytes have been processed, `false` if more bytes need to be received first
+    fn process(&mut self) -> io::Result<bool> {
+        match self.phase {
+            Phase::Handshake => {
+                let Some(end) = self.input.windows(4).position(|window| window == b"\r\n\r\n") else {
+                    return Ok(false);
+                };
+                let response: Vec<u8> = self.input.drain(..end + 4).collect();
+                let negotiated = is_negotiated(&response);
+                tracing::debug!("Server {} permessage-deflate compression", if negotiated { "accepted" } else { "declined" });
+
+                self.output.extend_from_slice(&response);
+                self.phase = Phase::Frames { negotiated };
+                Ok(true)
+            }
+            Phase::Frames { negotiated: false } => {
+                let processed = !self.input.is_empty();
+                self.output.append(&mut self.input);
+                Ok(processed)
+            }
+            Phase::Frames { negotiated: true } => self.process_frame(),
+        }
+    }
+
+    /// Move the next frame to the output, inflated if it is part of a compressed message
+    fn process_frame(&mut self) -> io::Result<bool> {
+        let Some((header_size, payload_size)) = frame_size(&self.input)? else {
+            return Ok(false);
+        };
+        let frame_end = header_size + payload_size;
+
+        let first = self.input[0];
+        let opcode = first & 0x0f;
+        let is_masked = self.input[1] & 0x80 != 0;
+
+        // Control frames can interleave the frames of a message and are never compressed
+        let is_control = opcode & 0x08 != 0;
+        if !is_control && opcode != 0 {
+            self.compressed_message = first & 0x40 != 0;
+        }
+
+        // Masked or reserved frames from the server are tungstenite's to reject
+        if is_control || is_masked || !self.compressed_message || (opcode == 0 && first & 0x40 != 0) {
+            self.output.extend(self.input.drain(..frame_end));
+            return Ok(true);
+        }
+
+        let mut payload = Vec::new();
+        inflate(&mut self.decompress, &self.input[header_size..frame_end], &mut payload)?;
+        if first & 0x80 != 0 {
+            inflate(&mut self.decompress, &MESSAGE_TAIL, &mut payload)?;
+        }
+        self.input.drain(..frame_end);
+
+        self.output.push(first & !0x40);
+        match payload.len() {
+            size if size < 126 => self.output.push(size as u8),
+            size if size <= u16::MAX as usize => {
+                self.output.push(126);
+                self.output.extend_from_slice(&(size as u16).to_be_bytes());
+            }
+            size => {
+                self.output.push(127);
+                self.output.extend_from_slice(&(size as u64).to_be_bytes());
+            }
+        }
+        self.output.extend_from_slice(&payload);
+        Ok(true)
+    }
+}
+
+/// Inflate the compressed data into the payload
+fn inflate(decompress: &mut Decompress, data: &[u8], payload: &mut Vec<u8>) -> io::Result<()> {
+    let mut consumed = 0;
+    loop {
+        payload.reserve(CHUNK_SIZE);
+        let (total_in, total_out) = (decompress.total_in(), decompress.total_out());
+        let status = decompress
+            .decompress_vec(&data[consumed..], payload, FlushDecompress::Sync)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let read = (decompress.total_in() - total_in) as usize;
+        let written = (decompress.total_out() - total_out) as usize;
+        consumed += read;
+
+        // The server has finished the deflate stream, the next message starts a new one
+        if status == Status::StreamEnd {
+            decompress.reset(false);
+        }
+
+        if payload.len() > MAX_FRAME_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Inflated frame exceeds '{}' bytes", MAX_FRAME_SIZE)));
+        }
+        // Everything is inflated, once the data is consumed and the payload has room to spare
+        if consumed == data.len() && payload.len() < payload.capacity() {
+            return Ok(());
+        }
+        if read == 0 && written == 0 && status != Status::StreamEnd {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Compressed frame can't be inflated"));
+        }
+    }
+}
+
+/// Sizes of the header and the payload of the frame, which the data starts with
+///
+/// # Returns
+/// None, if the frame hasn't been received completely yet
+fn frame_size(data: &[u8]) -> io::Result<Option<(usize, usize)>> {
+    let Some(&second) = data.get(1) else {
+        return Ok(None);
+    };
+
+    let (payload_size, mut header_size) = match second & 0x7f {
+        126 => match data.get(2..4) {
+            Some(size) => (u16::from_be_bytes([size[0], size[1]]) as usize, 4),
+            None => return Ok(None),
+        },
+        127 => match data.get(2..10).and_then(|size| size.try_into().ok()) {
+            Some(size) => (u64::from_be_bytes(size) as usize, 10),
+            None => return Ok(None),
+        },
+        size => (size as usize, 2),
+    };
+    if second & 0x80 != 0 {
+        header_size += 4;
+    }
+
+    if payload_size > MAX_FRAME_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Frame of '{}' bytes exceeds '{}' bytes", payload_size, MAX_FRAME_SIZE)));
+    }
+    Ok((data.len() >= header_size + payload_size).then_some((header_size, payload_size)))
+}
+
+/// Whether the response to the connection request accepts the permessage-deflate extension
+fn is_negotiated(response: &[u8]) -> bool {
+    String::from_utf8_lossy(response)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("Sec-WebSocket-Extensions"))
+        .flat_map(|(_, value)| value.split(','))
+        .any(|extension| extension.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(EXTENSION))
+}
+
+impl<S> AsyncRead for DeflateStream<S>
+where S: AsyncRead + Unpin,
+{
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.position < this.output.len() {
+                let size = buf.remaining().min(this.output.len() - this.position);
+                buf.put_slice(&this.output[this.position..this.position + size]);
+                this.position += size;
+                if this.position == this.output.len() {
+                    this.output.clear();
+                    this.position = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.phase == (Phase::Frames { negotiated: false }) && this.input.is_empty() {
+                return Pin::new(&mut this.inner).poll_read(cx, buf);
+            }
+
+            if this.process()? {
+                continue;
+            }
+
+            let mut chunk = [0u8; CHUNK_SIZE];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            this.input.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+impl<S> AsyncWrite for DeflateStream<S>
+where S: AsyncWrite + Unpin,
+{
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compress, Compression, FlushCompress};
+    use futures::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tungstenite::handshake::derive_accept_key;
+    use tungstenite::Message;
+    use crate::mdc_server::transport::Transport;
+
+    const FIRST: &str = r#"{"e":"depthUpdate","s":"BTCUSDT","b":[["100.0","1.0"],["99.0","2.0"]],"a":[["101.0","1.0"]]}"#;
+    const SECOND: &str = r#"{"e":"depthUpdate","s":"BTCUSDT","b":[["100.0","1.5"],["99.0","2.0"]],"a":[["101.0","0.5"]]}"#;
+
+    /// Compress the message with the context of the previous ones, as a server taking over the context does
+    fn deflate(compress: &mut Compress, message: &str) -> Vec<u8> {
+        let mut output = Vec::with_capacity(message.len() + 64);
+        compress.compress_vec(message.as_bytes(), &mut output, FlushCompress::Sync).unwrap();
+        assert!(output.ends_with(&MESSAGE_TAIL));
+        output.truncate(output.len() - MESSAGE_TAIL.len());
+        output
+    }
+
+    fn frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![first, 126];
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// Serve one connection, which accepts the extension if it is offered, and sends compressed and uncompressed messages
+    async fn serve(listener: TcpListener) -> String {
+        let (mut connection, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(connection.read_u8().await.unwrap());
+        }
+        let request = String::from_utf8(request).unwrap();
+        let key = request
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .find_map(|(name, value)| name.eq_ignore_ascii_case("Sec-WebSocket-Key").then(|| value.to_string()))
+            .unwrap();
+        let request = request.to_lowercase();
+        let offered = request.contains("sec-websocket-extensions: permessage-deflate");
+
+        let mut response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: {}\r\n",
+            derive_accept_key(key.as_bytes())
+        );
+        if offered {
+            response.push_str("Sec-WebSocket-Extensions: permessage-deflate\r\n");
+        }
+        response.push_str("\r\n");
+        connection.write_all(response.as_bytes()).await.unwrap();
+
+        // A message fragmented into two frames with a ping in between, and a message, whose compression refers to the first
+        let mut frames = Vec::new();
+        if offered {
+            let mut compress = Compress::new(Compression::default(), false);
+            let compressed = deflate(&mut compress, FIRST);
+            let (head, tail) = compressed.split_at(compressed.len() / 2);
+            frames.extend(frame(0x41, head));
+            frames.extend(frame(0x89, b"ping"));
+            frames.extend(frame(0x80, tail));
+            frames.extend(frame(0xc1, &deflate(&mut compress, SECOND)));
+        } else {
+            let (head, tail) = FIRST.as_bytes().split_at(FIRST.len() / 2);
+            frames.extend(frame(0x01, head));
+            frames.extend(frame(0x89, b"ping"));
+            frames.extend(frame(0x80, tail));
+            frames.extend(frame(0x81, SECOND.as_bytes()));
+        }
+        frames.extend(frame(0x81, b"uncompressed"));
+        connection.write_all(&frames).await.unwrap();
+        let _ = connection.read_u8().await;
+        request
+    }
+
+    async fn receive(compression: bool) -> (Vec<Message>, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws/btcusdt@depth", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve(listener));
+
+        let transport = Transport::new(None, None, compression);
+        let (mut connection, _) = transport.connect_websocket(url.as_str()).await.unwrap();
+        let mut messages = Vec::new();
+        while messages.len() < 4 {
+            messages.push(connection.next().await.unwrap().unwrap());
+        }
+        connection.close(None).await.unwrap();
+        (messages, server.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_inflates_compressed_messages() {
+        let (messages, request) = receive(true).await;
+        assert!(request.contains("sec-websocket-extensions: permessage-deflate"));
+        assert_eq!(messages, vec![Message::Ping("ping".into()), Message::text(FIRST), Message::text(SECOND), Message::text("uncompressed")]);
+    }
+
+    #[tokio::test]
+    async fn test_passes_uncompressed_connections_through() {
+        let (messages, request) = receive(false).await;
+        assert!(!request.contains("sec-websocket-extensions"));
+        assert_eq!(messages, vec![Message::Ping("ping".into()), Message::text(FIRST), Message::text(SECOND), Message::text("uncompressed")]);
+    }
+
+    #[test]
+    fn test_is_negotiated() {
+        assert!(is_negotiated(b"HTTP/1.1 101 Switching Protocols\r\nsec-websocket-extensions: permessage-deflate; client_max_window_bits=15\r\n\r\n"));
+        assert!(is_negotiated(b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: x-webkit, permessage-deflate\r\n\r\n"));
+        assert!(!is_negotiated(b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: x-webkit-deflate-frame\r\n\r\n"));
+        assert!(!is_negotiated(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n"));
+    }
+}